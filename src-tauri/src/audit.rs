@@ -0,0 +1,588 @@
+//! Audit log of destructive operations
+//!
+//! Every write to a device is appended as one JSON line to an audit file
+//! kept apart from the debug logs: which device (serial, model, size), which
+//! image (SHA-256 of the written bytes), which OS user started it, when, and
+//! how it ended. Shared lab machines and organisations with removable-media
+//! policies need this trail, so the app only ever appends to the file and
+//! never rotates or prunes it. It lives in the config directory, which
+//! clearing the cache leaves alone; `export_audit_log` copies it out, and
+//! `export_history` converts it to a JSON array or a CSV sheet for asset
+//! tracking.
+//!
+//! Operations can carry a free-text note ("card for greenhouse node 3"),
+//! set before, during or after they run. Notes change after the fact, so
+//! they live in a file of their own keyed by operation ID, and exports join
+//! them to the audit entries.
+//!
+//! Thread Safety:
+//! Appends and note changes are serialized by a global Mutex so lines never
+//! interleave.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::devices::BlockDevice;
+use crate::events::{self, AppEvent};
+use crate::utils::{get_config_dir, move_from_cache_dir};
+use crate::{log_info, log_warn};
+
+const MODULE: &str = "audit";
+
+/// Global mutex to serialize audit file appends and note changes
+static AUDIT_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// How a destructive operation ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditResult {
+    Success,
+    Failed,
+    Cancelled,
+}
+
+/// One destructive operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// RFC 3339 timestamp of the end of the operation
+    pub timestamp: String,
+    pub operation: String,
+    /// OS user the app runs as
+    pub user: String,
+    /// User who elevated the app through sudo or pkexec, if any
+    pub original_user: Option<String>,
+    pub device_path: String,
+    pub device_serial: Option<String>,
+    pub device_model: Option<String>,
+    pub device_size: Option<u64>,
+    pub image: String,
+    /// SHA-256 of the bytes written, None if writing did not finish
+    pub image_sha256: Option<String>,
+    /// Whether the device was read back and matched the image
+    pub verified: bool,
+    pub result: AuditResult,
+    pub error: Option<String>,
+    pub app_version: String,
+    /// ID the operation is tagged with in the logs; None in older entries
+    #[serde(default)]
+    pub operation_id: Option<String>,
+    /// Note on the operation, filled in by exports
+    #[serde(default)]
+    pub note: Option<String>,
+    /// RFC 3339 time the note was last changed
+    #[serde(default)]
+    pub note_updated_at: Option<String>,
+}
+
+impl AuditEntry {
+    /// Describe a flash of `image` to `device_path`
+    pub fn flash(
+        device_path: &str,
+        device: Option<&BlockDevice>,
+        image: &Path,
+        image_sha256: Option<String>,
+        verified: bool,
+        result: AuditResult,
+        error: Option<String>,
+    ) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            operation: "flash".to_string(),
+            user: current_user(),
+            original_user: original_user(),
+            device_path: device_path.to_string(),
+            device_serial: device.and_then(|d| d.serial.clone()),
+            device_model: device.map(|d| d.model.clone()),
+            device_size: device.map(|d| d.size),
+            image: image.display().to_string(),
+            image_sha256,
+            verified,
+            result,
+            error,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            operation_id: None,
+            note: None,
+            note_updated_at: None,
+        }
+    }
+
+    /// Tie the entry to the operation it records, so its note can be found
+    pub fn for_operation(self, operation_id: &str) -> Self {
+        Self {
+            operation_id: Some(operation_id.to_string()),
+            ..self
+        }
+    }
+
+    /// Describe an erase of `device_path`; the image is the new filesystem
+    pub fn erase(
+        device_path: &str,
+        device: Option<&BlockDevice>,
+        filesystem: &str,
+        result: AuditResult,
+        error: Option<String>,
+    ) -> Self {
+        Self {
+            operation: "erase".to_string(),
+            ..Self::flash(
+                device_path,
+                device,
+                Path::new(filesystem),
+                None,
+                false,
+                result,
+                error,
+            )
+        }
+    }
+}
+
+/// Path of a file in the config directory
+///
+/// Older versions kept the audit files in the cache directory; a file left
+/// there is moved over first.
+fn config_file(name: &str) -> PathBuf {
+    let path = get_config_dir(config::app::NAME).join(name);
+    match move_from_cache_dir(config::app::NAME, &path) {
+        Ok(false) => {}
+        Ok(true) => log_info!(MODULE, "Moved {} to {}", name, path.display()),
+        Err(e) => log_warn!(MODULE, "Failed to move {} from the cache: {}", name, e),
+    }
+    path
+}
+
+/// Get the audit file path
+pub fn audit_log_path() -> PathBuf {
+    config_file(config::audit::FILE_NAME)
+}
+
+/// Append an entry to the audit log
+pub fn record_audit_entry(entry: &AuditEntry) {
+    if let Err(e) = append(&audit_log_path(), entry) {
+        log_warn!(MODULE, "Failed to write audit entry: {}", e);
+    }
+}
+
+fn append(path: &Path, entry: &AuditEntry) -> Result<(), String> {
+    let line =
+        serde_json::to_string(entry).map_err(|e| format!("Failed to serialize entry: {}", e))?;
+
+    let _lock = AUDIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create audit directory: {}", e))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open audit log: {}", e))?;
+    // One write per line, so a crash can't leave half an entry mid-file
+    file.write_all(format!("{}\n", line).as_bytes())
+        .map_err(|e| format!("Failed to append to audit log: {}", e))
+}
+
+/// Copy the audit log to `destination`, returning the number of entries
+pub fn export_audit_log_to(destination: &Path) -> Result<usize, String> {
+    let _lock = AUDIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let content = match fs::read_to_string(audit_log_path()) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read audit log: {}", e)),
+    };
+    fs::write(destination, &content).map_err(|e| format!("Failed to export audit log: {}", e))?;
+
+    let entries = content.lines().filter(|l| !l.trim().is_empty()).count();
+    log_info!(
+        MODULE,
+        "Exported {} audit entries to {}",
+        entries,
+        destination.display()
+    );
+    Ok(entries)
+}
+
+/// Format of an exported history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        match format {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            _ => Err(format!("Unknown export format: {}", format)),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+        }
+    }
+}
+
+/// CSV columns, in `AuditEntry` field order
+const CSV_HEADER: [&str; 17] = [
+    "timestamp",
+    "operation",
+    "user",
+    "original_user",
+    "device_path",
+    "device_serial",
+    "device_model",
+    "device_size",
+    "image",
+    "image_sha256",
+    "verified",
+    "result",
+    "error",
+    "app_version",
+    "operation_id",
+    "note",
+    "note_updated_at",
+];
+
+/// Entries of the audit log, oldest first; unreadable lines are skipped
+fn read_entries(path: &Path) -> Result<Vec<AuditEntry>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read audit log: {}", e)),
+    };
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                log_warn!(MODULE, "Skipping unreadable audit entry: {}", e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Successful flashes in the audit log, oldest first
+pub fn successful_flashes() -> Result<Vec<AuditEntry>, String> {
+    let mut entries = read_entries(&audit_log_path())?;
+    entries.retain(|entry| entry.operation == "flash" && entry.result == AuditResult::Success);
+    Ok(entries)
+}
+
+/// Quote a CSV field if it needs it
+///
+/// Fields a spreadsheet would read as a formula (notes, device models and
+/// image names are free text) get a leading `'`, so opening the export
+/// cannot run anything.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn to_csv(entries: &[AuditEntry]) -> String {
+    let mut csv = CSV_HEADER.join(",") + "\r\n";
+    for entry in entries {
+        let result = match entry.result {
+            AuditResult::Success => "success",
+            AuditResult::Failed => "failed",
+            AuditResult::Cancelled => "cancelled",
+        };
+        let fields = [
+            entry.timestamp.clone(),
+            entry.operation.clone(),
+            entry.user.clone(),
+            entry.original_user.clone().unwrap_or_default(),
+            entry.device_path.clone(),
+            entry.device_serial.clone().unwrap_or_default(),
+            entry.device_model.clone().unwrap_or_default(),
+            entry.device_size.map(|s| s.to_string()).unwrap_or_default(),
+            entry.image.clone(),
+            entry.image_sha256.clone().unwrap_or_default(),
+            entry.verified.to_string(),
+            result.to_string(),
+            entry.error.clone().unwrap_or_default(),
+            entry.app_version.clone(),
+            entry.operation_id.clone().unwrap_or_default(),
+            entry.note.clone().unwrap_or_default(),
+            entry.note_updated_at.clone().unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv += &row.join(",");
+        csv += "\r\n";
+    }
+    csv
+}
+
+/// Write the flash history to `destination` as JSON or CSV
+///
+/// Returns the number of exported entries.
+pub fn export_history_to(destination: &Path, format: ExportFormat) -> Result<usize, String> {
+    let entries = {
+        let _lock = AUDIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entries = read_entries(&audit_log_path())?;
+        attach_notes(&mut entries, &load_notes(&notes_path()));
+        entries
+    };
+    let content = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&entries)
+            .map_err(|e| format!("Failed to serialize history: {}", e))?,
+        ExportFormat::Csv => to_csv(&entries),
+    };
+    fs::write(destination, content).map_err(|e| format!("Failed to export history: {}", e))?;
+
+    log_info!(
+        MODULE,
+        "Exported {} history entries to {}",
+        entries.len(),
+        destination.display()
+    );
+    Ok(entries.len())
+}
+
+/// Free-text note on an operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperationNote {
+    pub text: String,
+    /// RFC 3339 time the note was last changed
+    pub updated_at: String,
+}
+
+/// Get the notes file path
+fn notes_path() -> PathBuf {
+    config_file(config::audit::NOTES_FILE_NAME)
+}
+
+/// Notes by operation ID (empty if missing or unreadable)
+fn load_notes(path: &Path) -> BTreeMap<String, OperationNote> {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            log_warn!(MODULE, "Ignoring unreadable operation notes: {}", e);
+            BTreeMap::new()
+        }),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+/// Replace the note of an operation in the notes file at `path`
+fn write_note(path: &Path, id: &str, note: Option<&OperationNote>) -> Result<(), String> {
+    let mut notes = load_notes(path);
+    match note {
+        Some(note) => notes.insert(id.to_string(), note.clone()),
+        None => notes.remove(id),
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create audit directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&notes)
+        .map_err(|e| format!("Failed to serialize operation notes: {}", e))?;
+    fs::write(path, content).map_err(|e| format!("Failed to write operation notes: {}", e))
+}
+
+/// Fill in the notes of the entries' operations
+fn attach_notes(entries: &mut [AuditEntry], notes: &BTreeMap<String, OperationNote>) {
+    for entry in entries {
+        if let Some(note) = entry.operation_id.as_ref().and_then(|id| notes.get(id)) {
+            entry.note = Some(note.text.clone());
+            entry.note_updated_at = Some(note.updated_at.clone());
+        }
+    }
+}
+
+/// Set the note of operation `id`; empty text removes it
+///
+/// The operation may not have started yet or may be long finished, so the
+/// ID is not checked against the audit log. Returns the stored note.
+pub fn set_operation_note(id: &str, text: &str) -> Result<Option<OperationNote>, String> {
+    let text = text.trim();
+    if id.is_empty() {
+        return Err("Operation ID is empty".to_string());
+    }
+    if text.chars().count() > config::audit::MAX_NOTE_LEN {
+        return Err(format!(
+            "Notes are limited to {} characters",
+            config::audit::MAX_NOTE_LEN
+        ));
+    }
+    let note = (!text.is_empty()).then(|| OperationNote {
+        text: text.to_string(),
+        updated_at: chrono::Utc::now().to_rfc3339(),
+    });
+
+    {
+        let _lock = AUDIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        write_note(&notes_path(), id, note.as_ref())?;
+    }
+    log_info!(MODULE, "Note on {}: {:?}", id, text);
+    events::publish(AppEvent::OperationNoted {
+        operation_id: id.to_string(),
+        note: note.as_ref().map(|n| n.text.clone()),
+    });
+    Ok(note)
+}
+
+/// Name of the OS user the process runs as
+fn current_user() -> String {
+    #[cfg(unix)]
+    {
+        if let Some(name) = username_from_uid(unsafe { libc::getuid() }) {
+            return name;
+        }
+    }
+
+    let var = if cfg!(windows) { "USERNAME" } else { "USER" };
+    std::env::var(var)
+        .or_else(|_| std::env::var("LOGNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// User who started an elevated instance through sudo or pkexec
+fn original_user() -> Option<String> {
+    #[cfg(unix)]
+    {
+        if let Ok(user) = std::env::var("SUDO_USER") {
+            return Some(user);
+        }
+        if let Some(uid) = std::env::var("PKEXEC_UID")
+            .ok()
+            .and_then(|uid| uid.parse::<u32>().ok())
+        {
+            return Some(username_from_uid(uid).unwrap_or_else(|| format!("uid {}", uid)));
+        }
+    }
+    None
+}
+
+#[cfg(unix)]
+fn username_from_uid(uid: u32) -> Option<String> {
+    unsafe {
+        let pw = libc::getpwuid(uid);
+        if pw.is_null() || (*pw).pw_name.is_null() {
+            return None;
+        }
+        std::ffi::CStr::from_ptr((*pw).pw_name)
+            .to_str()
+            .ok()
+            .map(|s| s.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_writes_json_lines() {
+        let path = std::env::temp_dir().join(format!("audit-test-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let entry = AuditEntry::flash(
+            "/dev/sdx",
+            None,
+            Path::new("/tmp/image.img"),
+            Some("abc".to_string()),
+            true,
+            AuditResult::Success,
+            None,
+        );
+        append(&path, &entry).unwrap();
+        append(&path, &entry).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let parsed: AuditEntry = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(parsed.device_path, "/dev/sdx");
+        assert_eq!(parsed.result, AuditResult::Success);
+        assert!(lines[0].contains("\"result\":\"success\""));
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_history_csv() {
+        let path = std::env::temp_dir().join(format!("audit-csv-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut entry = AuditEntry::flash(
+            "/dev/sdx",
+            None,
+            Path::new("/tmp/Armbian, \"edge\".img"),
+            Some("abc".to_string()),
+            false,
+            AuditResult::Failed,
+            Some("Verification failed".to_string()),
+        );
+        entry.device_serial = Some("SN123".to_string());
+        append(&path, &entry).unwrap();
+        fs::write(&path, fs::read_to_string(&path).unwrap() + "not json\n").unwrap();
+
+        let entries = read_entries(&path).unwrap();
+        assert_eq!(entries.len(), 1);
+        let csv = to_csv(&entries);
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(lines[0].split(',').count(), CSV_HEADER.len());
+        assert!(lines[1].contains(",SN123,"));
+        assert!(lines[1].contains(",\"/tmp/Armbian, \"\"edge\"\".img\",abc,false,failed,"));
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_notes_joined_to_entries() {
+        let path = std::env::temp_dir().join(format!("audit-notes-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let note = |text: &str| OperationNote {
+            text: text.to_string(),
+            updated_at: "2025-01-01T12:00:00+00:00".to_string(),
+        };
+        write_note(&path, "flash-1", Some(&note("greenhouse node 3"))).unwrap();
+        write_note(&path, "flash-2", Some(&note("spare"))).unwrap();
+        write_note(&path, "flash-2", None).unwrap();
+
+        let entry = AuditEntry::erase("/dev/sdx", None, "exfat", AuditResult::Success, None);
+        let mut entries = vec![
+            entry.clone().for_operation("flash-1"),
+            entry.clone().for_operation("flash-2"),
+            entry,
+        ];
+        attach_notes(&mut entries, &load_notes(&path));
+        assert_eq!(entries[0].note.as_deref(), Some("greenhouse node 3"));
+        assert!(entries[0].note_updated_at.is_some());
+        assert_eq!(entries[1].note, None);
+        assert_eq!(entries[2].note, None);
+        assert!(
+            to_csv(&entries).contains(",flash-1,greenhouse node 3,2025-01-01T12:00:00+00:00\r\n")
+        );
+
+        // Formulas stay text
+        entries[0].note = Some("=HYPERLINK(\"http://x\",\"y\")".to_string());
+        entries[1].note = Some("@SUM(A1)".to_string());
+        let csv = to_csv(&entries);
+        assert!(csv.contains(",flash-1,\"'=HYPERLINK(\"\"http://x\"\",\"\"y\"\")\","));
+        assert!(csv.contains(",flash-2,'@SUM(A1),"));
+
+        let _ = fs::remove_file(&path);
+    }
+}
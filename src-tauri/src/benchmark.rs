@@ -0,0 +1,256 @@
+//! Decompression, hashing and I/O benchmarks
+//!
+//! Thread counts, chunk sizes and verification defaults are tuned by guess
+//! so far. `run_benchmark` (developer mode only) measures the candidates on
+//! the user's machine with the self-test image: single vs multi-threaded XZ
+//! decoding, zstd levels, the hashes used for downloads (SHA-256) and fast
+//! verification (CRC32C), and write/read throughput per chunk size. Results
+//! are logged and returned so they can be collected to pick better defaults.
+//! BLAKE3 is not among the hashes, as the app does not depend on it.
+
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Write};
+use std::num::NonZeroU64;
+use std::path::Path;
+use std::time::Instant;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::config;
+use crate::log_info;
+use crate::selftest::fixture_image;
+use crate::utils::{bytes_to_mb, get_cache_dir, get_cpu_cores, get_recommended_threads};
+
+const MODULE: &str = "benchmark";
+
+/// One measured candidate
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkResult {
+    /// What is being compared, e.g. "xz_decompress"
+    pub group: String,
+    /// The candidate, e.g. "4 threads"
+    pub name: String,
+    /// Uncompressed bytes processed
+    pub bytes: u64,
+    pub elapsed_ms: u64,
+    pub mb_per_sec: f64,
+    /// Size of the produced output, for compression ratios
+    pub output_bytes: Option<u64>,
+}
+
+/// All results of one run
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    pub started_at: String,
+    pub cpu_cores: usize,
+    pub sample_bytes: u64,
+    pub results: Vec<BenchmarkResult>,
+}
+
+/// Time `run`, which processes `bytes` bytes and returns the output size if any
+fn measure(
+    group: &str,
+    name: &str,
+    bytes: usize,
+    run: impl FnOnce() -> Result<Option<u64>, String>,
+) -> Result<BenchmarkResult, String> {
+    let started = Instant::now();
+    let output_bytes = run().map_err(|e| format!("{} {}: {}", group, name, e))?;
+    let elapsed = started.elapsed();
+
+    let secs = elapsed.as_secs_f64();
+    let result = BenchmarkResult {
+        group: group.to_string(),
+        name: name.to_string(),
+        bytes: bytes as u64,
+        elapsed_ms: elapsed.as_millis() as u64,
+        mb_per_sec: if secs > 0.0 {
+            bytes_to_mb(bytes as u64) / secs
+        } else {
+            0.0
+        },
+        output_bytes,
+    };
+    log_info!(
+        MODULE,
+        "{} / {}: {:.1} MB/s ({} ms)",
+        result.group,
+        result.name,
+        result.mb_per_sec,
+        result.elapsed_ms
+    );
+    Ok(result)
+}
+
+/// Thread counts worth comparing on this machine, without duplicates
+fn thread_counts() -> Vec<usize> {
+    let mut counts = vec![1, get_recommended_threads(), get_cpu_cores()];
+    counts.dedup();
+    counts
+}
+
+/// XZ decoding with one and several threads
+fn bench_xz(sample: &[u8]) -> Result<Vec<BenchmarkResult>, String> {
+    // Several blocks, as multi-threaded decoding works per block
+    let mut options = lzma_rust2::XzOptions::with_preset(1);
+    options.set_block_size(NonZeroU64::new(config::benchmark::XZ_BLOCK_SIZE));
+    let mut writer = lzma_rust2::XzWriter::new(Vec::new(), options)
+        .map_err(|e| format!("Failed to create XZ encoder: {}", e))?;
+    writer
+        .write_all(sample)
+        .map_err(|e| format!("Failed to compress sample: {}", e))?;
+    let compressed = writer
+        .finish()
+        .map_err(|e| format!("Failed to compress sample: {}", e))?;
+
+    thread_counts()
+        .into_iter()
+        .map(|threads| {
+            measure(
+                "xz_decompress",
+                &format!("{} thread(s)", threads),
+                sample.len(),
+                || {
+                    let mut output = Vec::with_capacity(sample.len());
+                    let decoded = if threads == 1 {
+                        lzma_rust2::XzReader::new(Cursor::new(&compressed), false)
+                            .read_to_end(&mut output)
+                    } else {
+                        lzma_rust2::XzReaderMt::new(Cursor::new(&compressed), false, threads as u32)
+                            .and_then(|mut reader| reader.read_to_end(&mut output))
+                    };
+                    decoded.map_err(|e| e.to_string())?;
+                    Ok(Some(compressed.len() as u64))
+                },
+            )
+        })
+        .collect()
+}
+
+/// zstd compression and decompression per level
+fn bench_zstd(sample: &[u8]) -> Result<Vec<BenchmarkResult>, String> {
+    let mut results = Vec::new();
+    for level in config::benchmark::ZSTD_LEVELS {
+        let mut compressed = Vec::new();
+        results.push(measure(
+            "zstd_compress",
+            &format!("level {}", level),
+            sample.len(),
+            || {
+                compressed = zstd::encode_all(sample, level).map_err(|e| e.to_string())?;
+                Ok(Some(compressed.len() as u64))
+            },
+        )?);
+        results.push(measure(
+            "zstd_decompress",
+            &format!("level {}", level),
+            sample.len(),
+            || {
+                let output = zstd::decode_all(compressed.as_slice()).map_err(|e| e.to_string())?;
+                Ok(Some(output.len() as u64))
+            },
+        )?);
+    }
+    Ok(results)
+}
+
+/// SHA-256 (download checksums) against CRC32C (fast verification)
+fn bench_hashes(sample: &[u8]) -> Result<Vec<BenchmarkResult>, String> {
+    Ok(vec![
+        measure("hash", "sha256", sample.len(), || {
+            std::hint::black_box(Sha256::digest(sample));
+            Ok(None)
+        })?,
+        measure("hash", "crc32c", sample.len(), || {
+            std::hint::black_box(crc32c::crc32c(sample));
+            Ok(None)
+        })?,
+    ])
+}
+
+/// Write (with sync) and read back a file in chunks of each candidate size
+///
+/// The read runs right after the write, so it largely measures the page
+/// cache; the write is what tells chunk sizes apart.
+fn bench_chunk_sizes(sample: &[u8], dir: &Path) -> Result<Vec<BenchmarkResult>, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join("chunk-benchmark.bin");
+
+    let mut results = Vec::new();
+    for chunk_size in config::benchmark::CHUNK_SIZES {
+        let label = format!("{} MB", chunk_size / (1024 * 1024));
+        let write = measure("chunk_write", &label, sample.len(), || {
+            let mut file = File::create(&path).map_err(|e| e.to_string())?;
+            for chunk in sample.chunks(chunk_size) {
+                file.write_all(chunk).map_err(|e| e.to_string())?;
+            }
+            file.sync_all().map_err(|e| e.to_string())?;
+            Ok(None)
+        });
+        let read = write.and_then(|write| {
+            results.push(write);
+            measure("chunk_read", &label, sample.len(), || {
+                let mut file = File::open(&path).map_err(|e| e.to_string())?;
+                let mut buffer = vec![0u8; chunk_size];
+                while file.read(&mut buffer).map_err(|e| e.to_string())? > 0 {}
+                Ok(None)
+            })
+        });
+        let _ = fs::remove_file(&path);
+        results.push(read?);
+    }
+    Ok(results)
+}
+
+/// Run every benchmark on a sample of `sample_size` bytes
+pub fn run_benchmarks(sample_size: usize) -> Result<BenchmarkReport, String> {
+    log_info!(
+        MODULE,
+        "Starting benchmarks with a {} MB sample",
+        sample_size / (1024 * 1024)
+    );
+    let sample = fixture_image(sample_size);
+    let scratch_dir = get_cache_dir(config::app::NAME).join("benchmark");
+
+    let mut results = bench_xz(&sample)?;
+    results.extend(bench_zstd(&sample)?);
+    results.extend(bench_hashes(&sample)?);
+    let chunk_results = bench_chunk_sizes(&sample, &scratch_dir);
+    let _ = fs::remove_dir(&scratch_dir);
+    results.extend(chunk_results?);
+
+    Ok(BenchmarkReport {
+        started_at: chrono::Utc::now().to_rfc3339(),
+        cpu_cores: get_cpu_cores(),
+        sample_bytes: sample.len() as u64,
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benchmarks_small_sample() {
+        let sample = fixture_image(300_000);
+
+        let xz = bench_xz(&sample).unwrap();
+        assert_eq!(xz.len(), thread_counts().len());
+        assert!(xz
+            .iter()
+            .all(|r| r.bytes == 300_000 && r.output_bytes.is_some()));
+
+        let zstd = bench_zstd(&sample).unwrap();
+        assert_eq!(zstd.len(), 2 * config::benchmark::ZSTD_LEVELS.len());
+        // Decompression gives back the sample size
+        assert_eq!(zstd[1].output_bytes, Some(300_000));
+
+        let dir = std::env::temp_dir().join(format!("benchmark-test-{}", std::process::id()));
+        let chunks = bench_chunk_sizes(&sample, &dir).unwrap();
+        assert_eq!(chunks.len(), 2 * config::benchmark::CHUNK_SIZES.len());
+        assert!(!dir.join("chunk-benchmark.bin").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
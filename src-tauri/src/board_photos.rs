@@ -0,0 +1,265 @@
+//! Board photo cache
+//!
+//! The board grid asks for the photo of every board it shows, so a lookup
+//! never waits on the network: it answers from disk with the cached photo
+//! or nothing, and refreshes the photo from cache.armbian.com in the
+//! background. A photo that arrived or changed is announced to every window
+//! with `board_image://ready`, so the grid shows a placeholder meanwhile
+//! and swaps the photo in when it lands.
+//!
+//! Each board is refreshed once per run. A board without a photo, or a
+//! refresh that fails offline, keeps its placeholder until the next start.
+//!
+//! Photos may come as PNG, WebP or AVIF and are stored with the matching
+//! extension. The cache is capped at `config::board_photos::MAX_CACHE_BYTES`:
+//! the photos shown least recently are evicted first, and come back on the
+//! next start.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use filetime::FileTime;
+use once_cell::sync::Lazy;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::cache::evict_dir_to_size;
+use crate::config;
+use crate::proxy;
+use crate::utils::get_cache_dir;
+use crate::{log_debug, log_info, log_warn};
+
+const MODULE: &str = "board_photos";
+
+/// Extensions of the photo formats, preferred first
+const FORMATS: [&str; 3] = ["avif", "webp", "png"];
+
+/// Boards whose photo was refreshed this run, or is being refreshed
+static REFRESHED: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Serializes writes, evictions and clearing of the photo folder
+static PHOTOS_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Payload of `board_image://ready`
+#[derive(Debug, Clone, Serialize)]
+pub struct BoardPhotoReady {
+    pub slug: String,
+    /// Cached photo file
+    pub path: String,
+}
+
+fn photos_dir() -> PathBuf {
+    get_cache_dir(config::app::NAME).join(config::board_photos::DIR_NAME)
+}
+
+fn photo_path(slug: &str, extension: &str) -> PathBuf {
+    photos_dir().join(format!("{}.{}", slug, extension))
+}
+
+/// Extension of a photo, from its content type or else its first bytes
+fn photo_extension(content_type: Option<&str>, bytes: &[u8]) -> Option<&'static str> {
+    let mime = content_type
+        .and_then(|value| value.split(';').next())
+        .map(|mime| mime.trim().to_ascii_lowercase());
+    match mime.as_deref() {
+        Some("image/png") => return Some("png"),
+        Some("image/webp") => return Some("webp"),
+        Some("image/avif") => return Some("avif"),
+        _ => {}
+    }
+
+    // Some servers label every file as a generic download
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        Some("webp")
+    } else if matches!(bytes.get(4..12), Some(b"ftypavif" | b"ftypavis")) {
+        Some("avif")
+    } else {
+        None
+    }
+}
+
+/// Whether `slug` can name a file, so it cannot point outside the cache
+fn is_valid_slug(slug: &str) -> bool {
+    !slug.is_empty()
+        && !slug.starts_with('.')
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Cached photo of a board, scheduling a refresh in the background
+///
+/// Returns at once; `None` until a first download succeeds.
+pub fn cached_photo(app: &AppHandle, slug: &str) -> Option<PathBuf> {
+    if !is_valid_slug(slug) {
+        return None;
+    }
+    let first = REFRESHED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(slug.to_string());
+    if first {
+        let (app, slug) = (app.clone(), slug.to_string());
+        tauri::async_runtime::spawn(async move { refresh(&app, &slug).await });
+    }
+
+    let path = FORMATS
+        .iter()
+        .map(|extension| photo_path(slug, extension))
+        .find(|path| path.is_file())?;
+    // Shown photos are the last to be evicted
+    let _ = filetime::set_file_mtime(&path, FileTime::now());
+    Some(path)
+}
+
+/// Download a board's photo and announce it if it is new
+async fn refresh(app: &AppHandle, slug: &str) {
+    let (bytes, extension) = match fetch(slug).await {
+        Ok(photo) => photo,
+        Err(e) => {
+            log_debug!(MODULE, "No photo for {}: {}", slug, e);
+            return;
+        }
+    };
+    let path = photo_path(slug, extension);
+    if std::fs::read(&path).is_ok_and(|cached| cached == bytes) {
+        return;
+    }
+
+    if let Err(e) = store(slug, extension, &bytes) {
+        log_warn!(MODULE, "Failed to cache photo of {}: {}", slug, e);
+        return;
+    }
+
+    let ready = BoardPhotoReady {
+        slug: slug.to_string(),
+        path: path.to_string_lossy().to_string(),
+    };
+    if let Err(e) = app.emit(config::events::BOARD_IMAGE_READY_EVENT, &ready) {
+        log_warn!(MODULE, "Failed to announce photo of {}: {}", slug, e);
+    }
+}
+
+/// Write a board's photo, drop its other formats and trim the cache
+fn store(slug: &str, extension: &str, bytes: &[u8]) -> std::io::Result<()> {
+    let _lock = PHOTOS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = photo_path(slug, extension);
+
+    // Written aside and renamed, so a lookup never finds half a photo
+    let temp = photo_path(slug, &format!("{}.part", extension));
+    let written = std::fs::create_dir_all(photos_dir())
+        .and_then(|_| std::fs::write(&temp, bytes))
+        .and_then(|_| std::fs::rename(&temp, &path));
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e);
+    }
+
+    for other in FORMATS.iter().filter(|&&other| other != extension) {
+        let _ = std::fs::remove_file(photo_path(slug, other));
+    }
+    if let Err(e) = evict_dir_to_size(&photos_dir(), config::board_photos::MAX_CACHE_BYTES) {
+        log_warn!(MODULE, "Failed to trim board photo cache: {}", e);
+    }
+    Ok(())
+}
+
+/// Delete every cached photo
+///
+/// Photos are downloaded again the next time their board is shown.
+pub fn clear() -> Result<(), String> {
+    let _lock = PHOTOS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = photos_dir();
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)
+            .map_err(|e| format!("Failed to clear board photos: {}", e))?;
+    }
+    REFRESHED.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    log_info!(MODULE, "Board photo cache cleared");
+    Ok(())
+}
+
+/// Download a board's photo in the best format the server offers
+async fn fetch(slug: &str) -> Result<(Vec<u8>, &'static str), String> {
+    let url = format!(
+        "{}{}/{}.png",
+        config::urls::BOARD_IMAGES_BASE,
+        config::urls::BOARD_IMAGE_SIZE,
+        slug
+    );
+    let client = proxy::client_builder()
+        .timeout(Duration::from_secs(config::http::SHORT_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let response = client
+        .get(&url)
+        .header(ACCEPT, config::board_photos::ACCEPT)
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Download failed: {}", e))?;
+    if bytes.is_empty() {
+        return Err("Empty response".to_string());
+    }
+    let extension = photo_extension(content_type.as_deref(), &bytes).ok_or_else(|| {
+        format!(
+            "Unsupported photo type: {}",
+            content_type.as_deref().unwrap_or("unknown")
+        )
+    })?;
+    Ok((bytes.to_vec(), extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_slug() {
+        assert!(is_valid_slug("orangepi5-plus"));
+        assert!(is_valid_slug("rock_5b"));
+        assert!(!is_valid_slug(""));
+        assert!(!is_valid_slug(".."));
+        assert!(!is_valid_slug("../etc/passwd"));
+        assert!(!is_valid_slug("a/b"));
+    }
+
+    #[test]
+    fn test_photo_extension() {
+        assert_eq!(photo_extension(Some("image/webp"), b""), Some("webp"));
+        assert_eq!(
+            photo_extension(Some("Image/AVIF; charset=binary"), b""),
+            Some("avif")
+        );
+
+        // Generic or missing types fall back to the file signature
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        assert_eq!(photo_extension(None, png), Some("png"));
+        let webp = b"RIFF\x24\0\0\0WEBPVP8 ";
+        assert_eq!(
+            photo_extension(Some("application/octet-stream"), webp),
+            Some("webp")
+        );
+        let avif = b"\0\0\0\x1cftypavif\0\0\0\0";
+        assert_eq!(photo_extension(None, avif), Some("avif"));
+
+        assert_eq!(photo_extension(Some("image/jpeg"), b"\xff\xd8\xff"), None);
+        assert_eq!(photo_extension(Some("text/html"), b"<html>"), None);
+    }
+}
@@ -3,12 +3,15 @@
 //! Handles persistent caching of downloaded Armbian images with
 //! configurable size limits and LRU (Least Recently Used) eviction.
 //!
+//! Customization writes to the device after flashing, never to the shared
+//! cached file.
+//!
 //! Thread Safety:
 //! All cache operations are protected by a global Mutex to prevent
 //! race conditions when multiple threads access the cache simultaneously.
 
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use std::time::SystemTime;
 
@@ -16,6 +19,7 @@ use filetime::FileTime;
 use once_cell::sync::Lazy;
 
 use crate::config;
+use crate::events::{self, AppEvent};
 use crate::utils::get_cache_dir;
 use crate::{log_debug, log_error, log_info, log_warn};
 
@@ -43,6 +47,42 @@ pub fn get_images_cache_dir() -> PathBuf {
     get_cache_dir(config::app::NAME).join("images")
 }
 
+/// Resolve a file the app may delete from a cache directory
+///
+/// Both paths are canonicalized, so `..` components and symlinked parent
+/// directories cannot point the deletion elsewhere. The file itself must be
+/// a regular file directly inside `dir`; symlinks are refused rather than
+/// followed. Returns the canonical path to delete, or `None` when nothing
+/// exists at `path`.
+pub fn deletable_cache_file(path: &Path, dir: &Path) -> Result<Option<PathBuf>, String> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    if metadata.file_type().is_symlink() {
+        return Err(format!("Refusing to delete symlink {}", path.display()));
+    }
+    if !metadata.is_file() {
+        return Err(format!("Not a file: {}", path.display()));
+    }
+
+    let dir = dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve cache directory: {}", e))?;
+    let canonical_path = path
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))?;
+    if canonical_path.parent() != Some(dir.as_path()) {
+        return Err(format!(
+            "{} is not in {}",
+            canonical_path.display(),
+            dir.display()
+        ));
+    }
+    Ok(Some(canonical_path))
+}
+
 /// Calculate total size of all cached images in bytes
 ///
 /// Scans the images cache directory and sums up file sizes.
@@ -53,21 +93,19 @@ pub fn calculate_cache_size() -> Result<u64, String> {
         .lock()
         .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
 
-    calculate_cache_size_internal()
+    calculate_cache_size_internal(&get_images_cache_dir())
 }
 
 /// Internal implementation of calculate_cache_size without locking
 ///
 /// Used by functions that already hold the cache lock.
-fn calculate_cache_size_internal() -> Result<u64, String> {
-    let cache_dir = get_images_cache_dir();
-
+fn calculate_cache_size_internal(cache_dir: &Path) -> Result<u64, String> {
     if !cache_dir.exists() {
         log_debug!(MODULE, "Cache directory doesn't exist, size is 0");
         return Ok(0);
     }
 
-    let entries = fs::read_dir(&cache_dir).map_err(|e| {
+    let entries = fs::read_dir(cache_dir).map_err(|e| {
         log_error!(MODULE, "Failed to read cache directory: {}", e);
         format!("Failed to read cache directory: {}", e)
     })?;
@@ -75,10 +113,10 @@ fn calculate_cache_size_internal() -> Result<u64, String> {
     let mut total_size: u64 = 0;
     let mut file_count = 0;
 
+    // Symlinks are not counted; eviction would not remove them
     for entry in entries.flatten() {
-        let path = entry.path();
-        if path.is_file() {
-            if let Ok(metadata) = fs::metadata(&path) {
+        if let Ok(metadata) = fs::symlink_metadata(entry.path()) {
+            if metadata.is_file() {
                 total_size += metadata.len();
                 file_count += 1;
             }
@@ -99,14 +137,12 @@ fn calculate_cache_size_internal() -> Result<u64, String> {
 ///
 /// Returns a vector of CacheEntry structs for LRU eviction.
 /// Note: This function does not acquire the cache lock - caller must ensure thread safety.
-fn get_cached_files_by_age_internal() -> Result<Vec<CacheEntry>, String> {
-    let cache_dir = get_images_cache_dir();
-
+fn get_cached_files_by_age_internal(cache_dir: &Path) -> Result<Vec<CacheEntry>, String> {
     if !cache_dir.exists() {
         return Ok(Vec::new());
     }
 
-    let entries = fs::read_dir(&cache_dir).map_err(|e| {
+    let entries = fs::read_dir(cache_dir).map_err(|e| {
         log_error!(MODULE, "Failed to read cache directory: {}", e);
         format!("Failed to read cache directory: {}", e)
     })?;
@@ -114,21 +150,26 @@ fn get_cached_files_by_age_internal() -> Result<Vec<CacheEntry>, String> {
     let mut files: Vec<CacheEntry> = Vec::new();
 
     for entry in entries.flatten() {
-        let path = entry.path();
-        if path.is_file() {
-            if let Ok(metadata) = fs::metadata(&path) {
-                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
-                files.push(CacheEntry {
-                    path,
-                    size: metadata.len(),
-                    modified,
-                });
+        let path = match deletable_cache_file(&entry.path(), cache_dir) {
+            Ok(Some(path)) => path,
+            Ok(None) => continue,
+            Err(e) => {
+                log_debug!(MODULE, "Skipping cache entry: {}", e);
+                continue;
             }
+        };
+        if let Ok(metadata) = fs::metadata(&path) {
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push(CacheEntry {
+                path,
+                size: metadata.len(),
+                modified,
+            });
         }
     }
 
     // Sort by modification time (oldest first for LRU eviction)
-    files.sort_by(|a, b| a.modified.cmp(&b.modified));
+    files.sort_by_key(|a| a.modified);
 
     Ok(files)
 }
@@ -142,7 +183,20 @@ pub fn evict_to_size(max_size: u64) -> Result<(), String> {
         .lock()
         .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
 
-    let current_size = calculate_cache_size_internal()?;
+    let removed_files = evict_dir_to_size(&get_images_cache_dir(), max_size)?;
+    if removed_files > 0 {
+        events::publish(AppEvent::CacheChanged { removed_files });
+    }
+
+    Ok(())
+}
+
+/// Evict the oldest files of `cache_dir` until it is under the limit
+///
+/// Returns the number of files removed. Does not acquire the cache lock;
+/// callers evicting the images cache must hold it.
+pub fn evict_dir_to_size(cache_dir: &Path, max_size: u64) -> Result<usize, String> {
+    let current_size = calculate_cache_size_internal(cache_dir)?;
 
     if current_size <= max_size {
         log_debug!(
@@ -151,7 +205,7 @@ pub fn evict_to_size(max_size: u64) -> Result<(), String> {
             current_size,
             max_size
         );
-        return Ok(());
+        return Ok(0);
     }
 
     log_info!(
@@ -161,8 +215,9 @@ pub fn evict_to_size(max_size: u64) -> Result<(), String> {
         max_size
     );
 
-    let files = get_cached_files_by_age_internal()?;
+    let files = get_cached_files_by_age_internal(cache_dir)?;
     let mut freed_space: u64 = 0;
+    let mut removed_files = 0;
     let target_free = current_size - max_size;
 
     for entry in files {
@@ -178,11 +233,12 @@ pub fn evict_to_size(max_size: u64) -> Result<(), String> {
         }
 
         freed_space += entry.size;
+        removed_files += 1;
     }
 
     log_info!(MODULE, "Evicted {} bytes from cache", freed_space);
 
-    Ok(())
+    Ok(removed_files)
 }
 
 /// Clear all cached images
@@ -212,17 +268,22 @@ pub fn clear_cache() -> Result<(), String> {
     let mut failed_count = 0;
 
     for entry in entries.flatten() {
-        let path = entry.path();
-        if path.is_file() {
-            match fs::remove_file(&path) {
-                Ok(()) => {
-                    removed_count += 1;
-                    log_debug!(MODULE, "Removed: {}", path.display());
-                }
-                Err(e) => {
-                    failed_count += 1;
-                    log_warn!(MODULE, "Failed to remove {}: {}", path.display(), e);
-                }
+        let path = match deletable_cache_file(&entry.path(), &cache_dir) {
+            Ok(Some(path)) => path,
+            Ok(None) => continue,
+            Err(e) => {
+                log_debug!(MODULE, "Skipping cache entry: {}", e);
+                continue;
+            }
+        };
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                removed_count += 1;
+                log_debug!(MODULE, "Removed: {}", path.display());
+            }
+            Err(e) => {
+                failed_count += 1;
+                log_warn!(MODULE, "Failed to remove {}: {}", path.display(), e);
             }
         }
     }
@@ -233,6 +294,9 @@ pub fn clear_cache() -> Result<(), String> {
         removed_count,
         failed_count
     );
+    events::publish(AppEvent::CacheChanged {
+        removed_files: removed_count,
+    });
 
     if failed_count > 0 {
         return Err(format!("Failed to remove {} cached files", failed_count));
@@ -273,6 +337,46 @@ pub fn get_cached_image(filename: &str) -> Option<PathBuf> {
     }
 }
 
+/// Save a copy of a cached image to `dest`, outside the cache
+///
+/// The standard library copy already clones the file where the volume
+/// supports it (APFS, btrfs and XFS), so saving an image next to the cache
+/// takes no time there.
+pub fn export_image(source: &Path, dest: &Path) -> Result<(), String> {
+    export_image_in(&get_images_cache_dir(), source, dest)
+}
+
+fn export_image_in(cache_dir: &Path, source: &Path, dest: &Path) -> Result<(), String> {
+    let source = deletable_cache_file(source, cache_dir)?
+        .ok_or_else(|| format!("Image not found: {}", source.display()))?;
+    if dest.canonicalize().ok().as_deref() == Some(source.as_path()) {
+        return Err("Cannot save an image over itself".to_string());
+    }
+
+    fs::copy(&source, dest).map_err(|e| {
+        let _ = fs::remove_file(dest);
+        format!(
+            "Failed to copy {} to {}: {}",
+            source.display(),
+            dest.display(),
+            e
+        )
+    })?;
+
+    // Saving counts as a use of the cached image
+    if let Err(e) = update_file_mtime(&source) {
+        log_warn!(MODULE, "Failed to update mtime for cached file: {}", e);
+    }
+
+    log_info!(
+        MODULE,
+        "Exported {} to {}",
+        source.display(),
+        dest.display()
+    );
+    Ok(())
+}
+
 /// Update file modification time to current time
 ///
 /// Used for LRU tracking - accessed files get their mtime updated.
@@ -301,10 +405,69 @@ mod tests {
     fn test_get_cached_files_by_age() {
         // Acquire lock and test internal function
         let _lock = CACHE_LOCK.lock().unwrap();
-        let result = get_cached_files_by_age_internal();
+        let result = get_cached_files_by_age_internal(&get_images_cache_dir());
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_deletable_cache_file() {
+        let root = std::env::temp_dir().join(format!("cache-delete-test-{}", std::process::id()));
+        let images = root.join("images");
+        fs::create_dir_all(images.join("nested")).unwrap();
+        fs::write(images.join("a.img"), b"image").unwrap();
+        fs::write(images.join("nested").join("b.img"), b"image").unwrap();
+        fs::write(root.join("outside.img"), b"keep").unwrap();
+
+        let allowed = deletable_cache_file(&images.join("a.img"), &images).unwrap();
+        assert_eq!(allowed, Some(images.canonicalize().unwrap().join("a.img")));
+        assert_eq!(
+            deletable_cache_file(&images.join("missing.img"), &images).unwrap(),
+            None
+        );
+
+        // `..` escapes, subdirectories and directories are refused
+        assert!(deletable_cache_file(&images.join("..").join("outside.img"), &images).is_err());
+        assert!(deletable_cache_file(&images.join("nested").join("b.img"), &images).is_err());
+        assert!(deletable_cache_file(&images.join("nested"), &images).is_err());
+
+        // A symlink is refused even when it points into the cache
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("outside.img"), images.join("link.img")).unwrap();
+            std::os::unix::fs::symlink(images.join("a.img"), images.join("self.img")).unwrap();
+            assert!(deletable_cache_file(&images.join("link.img"), &images).is_err());
+            assert!(deletable_cache_file(&images.join("self.img"), &images).is_err());
+
+            // Eviction leaves symlinks and their targets alone
+            assert_eq!(evict_dir_to_size(&images, 0).unwrap(), 1);
+            assert!(root.join("outside.img").exists());
+            assert!(fs::symlink_metadata(images.join("link.img")).is_ok());
+        }
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_export_image() {
+        let root = std::env::temp_dir().join(format!("cache-export-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let cache_dir = root.join("images");
+        fs::create_dir_all(&cache_dir).unwrap();
+        let source = cache_dir.join("board.img");
+        fs::write(&source, b"image").unwrap();
+
+        let dest = root.join("saved.img");
+        export_image_in(&cache_dir, &source, &dest).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), b"image");
+
+        // Only cached images, and never over themselves
+        assert!(export_image_in(&cache_dir, &dest, &root.join("other.img")).is_err());
+        assert!(export_image_in(&cache_dir, &source, &source).is_err());
+        assert_eq!(fs::read(&source).unwrap(), b"image");
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
     #[test]
     fn test_clear_cache_nonexistent() {
         // Should succeed even if directory doesn't exist
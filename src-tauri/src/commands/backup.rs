@@ -0,0 +1,120 @@
+//! Device backup commands
+//!
+//! Read a device into a compressed image file chosen by the user, e.g. to
+//! keep a working SD card before experimenting with it. Progress goes out
+//! as flash progress, and `cancel_operation` stops the backup.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State, Window};
+
+use crate::devices::get_block_devices;
+use crate::flash::{backup_device as do_backup, check_backup_path, lock_device, Watchdog};
+use crate::logging::begin_operation;
+use crate::utils::run_blocking_future;
+use crate::{log_error, log_info};
+
+use super::progress::ProgressEmitter;
+use super::state::AppState;
+use super::timeseries::{SampleSource, ThroughputSampler};
+
+/// Outcome of a finished backup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupSummary {
+    /// File the backup was written to
+    pub path: String,
+    /// Bytes read from the device
+    pub image_bytes: u64,
+    /// Size of the compressed file
+    pub compressed_bytes: u64,
+}
+
+/// Back up a device to a compressed image file chosen by the user
+///
+/// Returns None if the user cancelled the file dialog. On macOS the
+/// frontend requests authorization for the device first, as for flashing.
+#[tauri::command]
+pub async fn backup_device(
+    device_path: String,
+    state: State<'_, AppState>,
+    window: Window,
+    app: AppHandle,
+) -> Result<Option<BackupSummary>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let device = get_block_devices()?
+        .into_iter()
+        .find(|d| d.path == device_path)
+        .ok_or_else(|| format!("Device not found: {}", device_path))?;
+
+    let file_name = format!(
+        "{}-{}.img.xz",
+        device.name.replace(['/', '\\'], "_"),
+        chrono::Local::now().format("%Y%m%d")
+    );
+    let destination = window
+        .dialog()
+        .file()
+        .add_filter("Disk Image", &["xz", "zst"])
+        .set_file_name(file_name)
+        .set_title("Back Up Device")
+        .blocking_save_file();
+    let Some(destination) = destination else {
+        return Ok(None);
+    };
+    let output = destination
+        .as_path()
+        .map(PathBuf::from)
+        .ok_or_else(|| "Invalid path: not a valid file path".to_string())?;
+    check_backup_path(&output)?;
+
+    let operation = begin_operation("backup");
+    log_info!(
+        "backup",
+        "Backing up {} ({} bytes) to {}",
+        device_path,
+        device.size,
+        output.display()
+    );
+
+    let flash_state = state.operations(window.label()).flash_state.clone();
+    let _claim = state.claim_device(window.label(), operation.id(), &device_path)?;
+    let _system_lock = lock_device(&device_path)?;
+    let _sampler = ThroughputSampler::start(
+        operation.id(),
+        window.label(),
+        SampleSource::Flash(flash_state.clone()),
+    );
+    let _progress = ProgressEmitter::flash(&app, window.label(), flash_state.clone());
+    let watchdog = Watchdog::start(flash_state.clone(), &device_path);
+    let result = {
+        let (device_path, output, flash_state) =
+            (device_path.clone(), output.clone(), flash_state.clone());
+        run_blocking_future(async move {
+            do_backup(&device_path, device.size, &output, flash_state).await
+        })
+        .await
+    };
+    drop(watchdog);
+
+    match result {
+        Ok(compressed_bytes) => {
+            log_info!(
+                "backup",
+                "Backup of {} complete: {} bytes",
+                device_path,
+                compressed_bytes
+            );
+            Ok(Some(BackupSummary {
+                path: output.to_string_lossy().to_string(),
+                image_bytes: device.size,
+                compressed_bytes,
+            }))
+        }
+        Err(e) => {
+            log_error!("backup", "Backup of {} failed: {}", device_path, e);
+            Err(e)
+        }
+    }
+}
@@ -6,41 +6,127 @@ use std::collections::HashSet;
 use std::sync::Mutex;
 
 use once_cell::sync::Lazy;
-use tauri::State;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, State};
 
+use crate::audit::successful_flashes;
+use crate::config;
 use crate::devices::{get_block_devices as devices_get_block_devices, BlockDevice};
+use crate::events::{self, AppEvent};
+use crate::history::invalidate_verification;
 use crate::images::{
-    extract_images, fetch_all_images, filter_images_for_board, get_unique_boards, BoardInfo,
-    ImageInfo,
+    application_catalog, compare_versions, extract_images, extract_images_with_diagnostics,
+    fetch_all_images, filter_boards, filter_images_for_board, find_latest_image,
+    flashed_board_advisories, get_unique_boards, parse_image_filename, ApplicationInfo,
+    BoardAdvisory, BoardList, FlashedImage, ImageInfo, SupportTier,
 };
-use crate::{log_debug, log_error, log_info};
+use crate::utils::normalize_slug;
+use crate::{log_debug, log_error, log_info, log_warn};
 
-use super::state::AppState;
+use super::settings::{catalog_sources, device_aliases};
+use super::state::{AppState, DeviceLock};
 
 /// Track previously seen device paths to detect changes
 static PREV_DEVICE_PATHS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
 
-/// Get list of available boards
-#[tauri::command]
-pub async fn get_boards(state: State<'_, AppState>) -> Result<Vec<BoardInfo>, String> {
-    log_info!("board_queries", "Fetching boards list");
+/// Track previously seen device serials to invalidate stored verifications on unplug
+static PREV_DEVICE_SERIALS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Device list last pushed to the frontend by the watcher
+static LAST_PUSHED_DEVICES: Lazy<Mutex<Option<Vec<BlockDevice>>>> = Lazy::new(|| Mutex::new(None));
+
+/// Update check result for a cached or previously flashed image
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImageUpdateInfo {
+    /// Armbian version parsed from the filename
+    pub current_version: String,
+    /// Newest matching version in the catalog, if the board is still listed
+    pub latest_version: Option<String>,
+    /// Whether the catalog has a newer build than the checked image
+    pub update_available: bool,
+    /// Catalog entry of the newest matching image
+    pub latest_image: Option<ImageInfo>,
+}
 
-    // Fetch images if not cached
+/// Lock the images JSON, fetching it from the API on a cache miss
+async fn load_images_json<'a>(
+    state: &'a AppState,
+    app: &AppHandle,
+) -> Result<tokio::sync::MutexGuard<'a, Option<serde_json::Value>>, String> {
     let mut json_guard = state.images_json.lock().await;
     if json_guard.is_none() {
         log_info!("board_queries", "Cache miss - fetching from API");
-        let json = fetch_all_images().await.map_err(|e| {
+        let json = fetch_all_images(&catalog_sources(app)).await.map_err(|e| {
             log_error!("board_queries", "Failed to fetch boards: {}", e);
             e
         })?;
         *json_guard = Some(json);
     }
+    Ok(json_guard)
+}
 
+/// Get list of available boards
+///
+/// Boards are filtered by vendor ID, support levels and a case-insensitive
+/// search on name, slug and vendor name. Results are sorted for display and
+/// include per-vendor and per-support-level counts for the filter UI.
+#[tauri::command]
+pub async fn get_boards(
+    vendor: Option<String>,
+    support_levels: Option<Vec<SupportTier>>,
+    search: Option<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<BoardList, String> {
+    log_info!("board_queries", "Fetching boards list");
+    log_debug!(
+        "board_queries",
+        "Filters - vendor: {:?}, support_levels: {:?}, search: {:?}",
+        vendor,
+        support_levels,
+        search
+    );
+
+    let json_guard = load_images_json(&state, &app).await?;
     let json = json_guard.as_ref().unwrap();
-    let images = extract_images(json);
+    let (images, diagnostics) = extract_images_with_diagnostics(json);
     let boards = get_unique_boards(&images);
-    log_info!("board_queries", "Found {} boards", boards.len());
-    Ok(boards)
+    drop(json_guard);
+
+    if diagnostics.is_suspicious() {
+        log_warn!(
+            "board_queries",
+            "Image catalog may have changed schema (version: {:?}): {} records, {} parsed, {} skipped, {} errors. Sample errors: {:?}",
+            diagnostics.schema_version,
+            diagnostics.records,
+            diagnostics.parsed,
+            diagnostics.skipped,
+            diagnostics.errors,
+            diagnostics.sample_errors
+        );
+    } else if diagnostics.errors > 0 {
+        log_warn!(
+            "board_queries",
+            "{} catalog records failed to parse. Sample errors: {:?}",
+            diagnostics.errors,
+            diagnostics.sample_errors
+        );
+    }
+
+    let mut list = filter_boards(
+        boards,
+        vendor.as_deref(),
+        support_levels.as_deref(),
+        search.as_deref(),
+    );
+    list.catalog_diagnostics = diagnostics;
+    log_info!(
+        "board_queries",
+        "Found {} boards ({} total)",
+        list.boards.len(),
+        list.total_count
+    );
+    Ok(list)
 }
 
 /// Get images available for a specific board
@@ -102,13 +188,125 @@ pub async fn get_images_for_board(
     Ok(filtered)
 }
 
+/// Check whether a newer build exists for a cached or previously flashed image
+///
+/// Parses the Armbian filename and compares its version against the newest
+/// catalog image for the same board, distro release and kernel branch.
+/// Returns None if the filename does not follow the Armbian naming convention.
+#[tauri::command]
+pub async fn check_image_update(
+    filename: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Option<ImageUpdateInfo>, String> {
+    log_info!("board_queries", "Checking for image update: {}", filename);
+
+    let Some(parsed) = parse_image_filename(&filename) else {
+        log_info!(
+            "board_queries",
+            "Not an Armbian image filename, skipping update check: {}",
+            filename
+        );
+        return Ok(None);
+    };
+
+    let json_guard = load_images_json(&state, &app).await?;
+    let json = json_guard.as_ref().unwrap();
+    let images = extract_images(json);
+
+    let latest = find_latest_image(
+        &images,
+        &normalize_slug(&parsed.board),
+        parsed.distro_release.as_deref(),
+        parsed.kernel_branch.as_deref(),
+    );
+
+    let update_available = latest
+        .as_ref()
+        .is_some_and(|img| compare_versions(&img.armbian_version, &parsed.version).is_gt());
+
+    log_info!(
+        "board_queries",
+        "Image {} (version {}): latest {:?}, update available: {}",
+        filename,
+        parsed.version,
+        latest.as_ref().map(|img| &img.armbian_version),
+        update_available
+    );
+
+    Ok(Some(ImageUpdateInfo {
+        current_version: parsed.version,
+        latest_version: latest.as_ref().map(|img| img.armbian_version.clone()),
+        update_available,
+        latest_image: latest,
+    }))
+}
+
+/// Flag previously flashed images that went EOL or got a fix release since
+///
+/// Reads the successful flashes from the audit log and checks each image
+/// against the catalog. Without any flash, the catalog is not loaded.
+#[tauri::command]
+pub async fn get_flashed_board_advisories(
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Vec<BoardAdvisory>, String> {
+    let flashes: Vec<FlashedImage> = successful_flashes()?
+        .into_iter()
+        .map(|entry| FlashedImage {
+            image: entry.image,
+            flashed_at: entry.timestamp,
+            device_model: entry.device_model,
+        })
+        .collect();
+    if flashes.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let json_guard = load_images_json(&state, &app).await?;
+    let json = json_guard.as_ref().unwrap();
+    let images = extract_images(json);
+    let advisories = flashed_board_advisories(&flashes, &images, chrono::Local::now().date_naive());
+    log_info!(
+        "board_queries",
+        "{} advisories for {} previous flashes",
+        advisories.len(),
+        flashes.len()
+    );
+    Ok(advisories)
+}
+
+/// Get display metadata for known preinstalled applications
+///
+/// Maps `preinstalled_application` identifiers to display name, description,
+/// category and icon slug.
+#[tauri::command]
+pub fn get_application_catalog() -> Vec<ApplicationInfo> {
+    application_catalog().to_vec()
+}
+
 /// Get available block devices
 #[tauri::command]
-pub async fn get_block_devices() -> Result<Vec<BlockDevice>, String> {
-    let devices = devices_get_block_devices().map_err(|e| {
+pub async fn get_block_devices(app: AppHandle) -> Result<Vec<BlockDevice>, String> {
+    scan_block_devices(&app)
+}
+
+/// Devices locked by a running job, with the job holding each
+#[tauri::command]
+pub fn get_device_locks(state: State<'_, AppState>) -> Vec<DeviceLock> {
+    state.device_locks()
+}
+
+/// List block devices, publishing and logging what changed since the last scan
+fn scan_block_devices(app: &AppHandle) -> Result<Vec<BlockDevice>, String> {
+    let mut devices = devices_get_block_devices().map_err(|e| {
         log_error!("board_queries", "Failed to get block devices: {}", e);
         e
     })?;
+    let aliases = device_aliases(app);
+    for device in &mut devices {
+        device.apply_alias(&aliases);
+    }
 
     // Only log when device list changes
     let current_paths: HashSet<String> = devices.iter().map(|d| d.path.clone()).collect();
@@ -119,6 +317,11 @@ pub async fn get_block_devices() -> Result<Vec<BlockDevice>, String> {
         let added: Vec<_> = current_paths.difference(&prev_paths).collect();
         let removed: Vec<_> = prev_paths.difference(&current_paths).collect();
 
+        events::publish(AppEvent::DevicesChanged {
+            added: added.iter().map(|p| p.to_string()).collect(),
+            removed: removed.iter().map(|p| p.to_string()).collect(),
+        });
+
         if !added.is_empty() {
             log_info!("board_queries", "Device(s) added: {:?}", added);
         }
@@ -133,5 +336,34 @@ pub async fn get_block_devices() -> Result<Vec<BlockDevice>, String> {
         *prev_paths = current_paths;
     }
 
+    // A card that left our sight may have been written elsewhere
+    let current_serials: HashSet<String> =
+        devices.iter().filter_map(|d| d.serial.clone()).collect();
+    let mut prev_serials = PREV_DEVICE_SERIALS.lock().unwrap();
+    for serial in prev_serials.difference(&current_serials) {
+        invalidate_verification(serial, "device removed");
+    }
+    *prev_serials = current_serials;
+
     Ok(devices)
 }
+
+/// Push the device list to every window if it differs from the last push
+///
+/// Called by the device watcher after hotplug events; media inserted into a
+/// card reader keeps its path but changes size, so whole entries are compared.
+pub fn push_block_devices(app: &AppHandle) {
+    let Ok(devices) = scan_block_devices(app) else {
+        return;
+    };
+
+    let mut last_pushed = LAST_PUSHED_DEVICES.lock().unwrap();
+    if last_pushed.as_ref() == Some(&devices) {
+        return;
+    }
+    if let Err(e) = app.emit(config::events::DEVICES_CHANGED_EVENT, &devices) {
+        log_warn!("board_queries", "Failed to push device list: {}", e);
+        return;
+    }
+    *last_pushed = Some(devices);
+}
@@ -0,0 +1,250 @@
+//! Confirmation of dangerous write targets
+//!
+//! Fixed disks that are not the system disk (a second internal SSD, for
+//! example) can be written, but only after the user explicitly confirmed
+//! that exact disk. `confirm_dangerous_target` issues a token together with
+//! the device details the user acknowledges; `flash_image` refuses such a
+//! target without a matching, unexpired token. A token is bound to the
+//! device path, serial and size, so it can't be replayed against another
+//! disk, and is revoked once the disk has been written.
+//!
+//! Every target, removable or not, must also come from the current device
+//! enumeration: `flash_image` never opens a path the device list did not
+//! offer, and never a system disk.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::config;
+use crate::devices::{get_block_devices, BlockDevice};
+use crate::{log_info, log_warn};
+
+const MODULE: &str = "commands::confirmation";
+
+/// Identity of a confirmed device
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ConfirmedTarget {
+    path: String,
+    serial: Option<String>,
+    size: u64,
+}
+
+impl ConfirmedTarget {
+    fn of(device: &BlockDevice) -> Self {
+        Self {
+            path: device.path.clone(),
+            serial: device.serial.clone(),
+            size: device.size,
+        }
+    }
+}
+
+/// Issued tokens with their target and expiry
+static CONFIRMATIONS: Lazy<Mutex<HashMap<String, (ConfirmedTarget, Instant)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Token and the device details the user confirmed
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetConfirmation {
+    /// Pass to `flash_image` as `confirmation_token`
+    pub token: String,
+    pub device: BlockDevice,
+    /// Seconds the token stays valid
+    pub expires_in_secs: u64,
+}
+
+/// Whether writing to `device` needs a confirmation token
+pub fn needs_confirmation(device: &BlockDevice) -> bool {
+    !device.is_removable && !device.is_system
+}
+
+/// Unguessable token from the process-random hasher keys
+fn new_token() -> String {
+    let mut token = String::with_capacity(32);
+    for _ in 0..2 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos()),
+        );
+        token.push_str(&format!("{:016x}", hasher.finish()));
+    }
+    token
+}
+
+fn issue(device: &BlockDevice, now: Instant, lifetime: Duration) -> String {
+    let token = new_token();
+    let mut confirmations = CONFIRMATIONS.lock().unwrap_or_else(|e| e.into_inner());
+    confirmations.retain(|_, (_, expires)| *expires > now);
+    confirmations.insert(token.clone(), (ConfirmedTarget::of(device), now + lifetime));
+    token
+}
+
+fn check(token: Option<&str>, device: &BlockDevice, now: Instant) -> Result<(), String> {
+    let token = token.ok_or_else(|| {
+        format!(
+            "{} is a fixed disk; confirm it with confirm_dangerous_target first",
+            device.path
+        )
+    })?;
+
+    let confirmations = CONFIRMATIONS.lock().unwrap_or_else(|e| e.into_inner());
+    match confirmations.get(token) {
+        Some((target, expires)) if *expires > now && *target == ConfirmedTarget::of(device) => {
+            Ok(())
+        }
+        Some((_, expires)) if *expires <= now => {
+            Err(format!("Confirmation for {} has expired", device.path))
+        }
+        _ => Err(format!("Confirmation token does not match {}", device.path)),
+    }
+}
+
+/// Verify that `token` confirms `device`, if the device needs one
+pub fn check_dangerous_target(token: Option<&str>, device: &BlockDevice) -> Result<(), String> {
+    if !needs_confirmation(device) {
+        return Ok(());
+    }
+    check(token, device, Instant::now()).inspect_err(|e| {
+        log_warn!(MODULE, "Refusing fixed disk {}: {}", device.path, e);
+    })
+}
+
+/// Find the write target for `device_path` among the enumerated devices
+///
+/// Refuses paths the enumeration does not list (a stale selection, a typo,
+/// a buggy frontend) and system disks.
+pub fn find_flash_target(
+    devices: Vec<BlockDevice>,
+    device_path: &str,
+) -> Result<BlockDevice, String> {
+    let device = devices
+        .into_iter()
+        .find(|d| d.path == device_path)
+        .ok_or_else(|| format!("{} is not an available device", device_path))?;
+    if device.is_system {
+        return Err(format!(
+            "{} is a system disk and can't be written",
+            device_path
+        ));
+    }
+    Ok(device)
+}
+
+/// Revoke every confirmation for a device path after it was written
+pub fn revoke_confirmations(device_path: &str) {
+    CONFIRMATIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|_, (target, _)| target.path != device_path);
+}
+
+/// Confirm a fixed, non-system disk as write target
+///
+/// Returns the token `flash_image` requires together with the device details
+/// the user is acknowledging.
+#[tauri::command]
+pub fn confirm_dangerous_target(device_path: String) -> Result<TargetConfirmation, String> {
+    let device = get_block_devices()?
+        .into_iter()
+        .find(|d| d.path == device_path)
+        .ok_or_else(|| format!("Device not found: {}", device_path))?;
+
+    if device.is_system {
+        return Err(format!(
+            "{} is a system disk and can't be written",
+            device_path
+        ));
+    }
+    if !needs_confirmation(&device) {
+        return Err(format!(
+            "{} is removable and needs no confirmation",
+            device_path
+        ));
+    }
+
+    let lifetime = Duration::from_secs(config::flash::DANGEROUS_TARGET_CONFIRMATION_SECS);
+    let token = issue(&device, Instant::now(), lifetime);
+    log_info!(
+        MODULE,
+        "Fixed disk confirmed as target: {} ({}, {}, serial {:?})",
+        device.path,
+        device.model,
+        device.size_formatted,
+        device.serial
+    );
+
+    Ok(TargetConfirmation {
+        token,
+        device,
+        expires_in_secs: lifetime.as_secs(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed_disk(path: &str, serial: &str) -> BlockDevice {
+        BlockDevice {
+            path: path.to_string(),
+            name: path.trim_start_matches("/dev/").to_string(),
+            size: 500_000_000_000,
+            size_formatted: "500 GB".to_string(),
+            model: "Test SSD".to_string(),
+            is_removable: false,
+            is_system: false,
+            bus_type: Some("NVMe".to_string()),
+            serial: Some(serial.to_string()),
+            alias: None,
+            usb_bridge: None,
+        }
+    }
+
+    #[test]
+    fn test_token_is_bound_to_device() {
+        let now = Instant::now();
+        let disk = fixed_disk("/dev/test-nvme1", "A1");
+        let token = issue(&disk, now, Duration::from_secs(60));
+
+        assert!(check(None, &disk, now).is_err());
+        assert!(check(Some(&token), &disk, now).is_ok());
+        // Another disk swapped in at the same path
+        assert!(check(Some(&token), &fixed_disk("/dev/test-nvme1", "B2"), now).is_err());
+        assert!(check(Some("bogus"), &disk, now).is_err());
+        assert!(check(Some(&token), &disk, now + Duration::from_secs(61)).is_err());
+
+        revoke_confirmations("/dev/test-nvme1");
+        assert!(check(Some(&token), &disk, now).is_err());
+    }
+
+    #[test]
+    fn test_find_flash_target() {
+        let mut system = fixed_disk("/dev/test-sda", "S0");
+        system.is_system = true;
+        let devices = vec![system, fixed_disk("/dev/test-nvme1", "A1")];
+
+        let target = find_flash_target(devices.clone(), "/dev/test-nvme1").unwrap();
+        assert_eq!(target.serial.as_deref(), Some("A1"));
+        assert!(find_flash_target(devices.clone(), "/dev/test-sda").is_err());
+        // Only exact enumerated paths, not aliases or partitions of them
+        assert!(find_flash_target(devices.clone(), "/dev/test-nvme1p1").is_err());
+        assert!(find_flash_target(devices.clone(), "/dev/../dev/test-nvme1").is_err());
+        assert!(find_flash_target(devices, "/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_removable_needs_no_confirmation() {
+        let mut card = fixed_disk("/dev/test-sdz", "C3");
+        card.is_removable = true;
+        assert!(check_dangerous_target(None, &card).is_ok());
+        assert_ne!(new_token(), new_token());
+    }
+}
@@ -0,0 +1,34 @@
+//! Download credentials commands
+//!
+//! Manage the hosts that need a token or password to download from. The
+//! secrets go straight to the OS keychain and are never returned to the
+//! frontend.
+
+use crate::credentials::{load_sources, remove_source, set_source, AuthScheme, AuthSource};
+use crate::utils::run_blocking;
+
+/// List the hosts that have credentials, without the secrets
+#[tauri::command]
+pub fn get_download_credentials() -> Vec<AuthSource> {
+    load_sources()
+}
+
+/// Store credentials for a host ("dl.example.com" or "*.example.com")
+///
+/// `secret` is the bearer token, or the password for basic auth.
+#[tauri::command]
+pub async fn set_download_credentials(
+    host: String,
+    scheme: AuthScheme,
+    username: Option<String>,
+    secret: String,
+) -> Result<AuthSource, String> {
+    // Keychain access can block on a D-Bus or Keychain round trip
+    run_blocking(move || set_source(&host, scheme, username.as_deref(), &secret)).await
+}
+
+/// Forget the credentials of a host
+#[tauri::command]
+pub async fn remove_download_credentials(host: String) -> Result<(), String> {
+    run_blocking(move || remove_source(&host)).await
+}
@@ -3,15 +3,24 @@
 //! Handles selection and processing of user-provided custom images.
 
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
 use tauri::State;
 
 use crate::config;
-use crate::decompress::{decompress_local_file, needs_decompression};
-use crate::images::{extract_images, fetch_all_images, get_unique_boards, BoardInfo};
-use crate::utils::{get_cache_dir, normalize_slug};
-use crate::{log_error, log_info};
-
+use crate::decompress::{
+    decompress_dirs, decompress_local_file, needs_decompression, plan_decompression,
+    DecompressPlacement,
+};
+use crate::download::{hash_file_sha256, normalize_sha256, parse_sha256_file};
+use crate::flash::is_streamed;
+use crate::images::{
+    extract_images, fetch_all_images, get_unique_boards, parse_image_filename, BoardInfo,
+};
+use crate::utils::{is_document_portal_path, normalize_slug};
+use crate::{log_error, log_info, log_warn};
+
+use super::settings::{catalog_sources, pause_on_low_space};
 use super::state::AppState;
 
 /// Custom image info returned when user selects a local file
@@ -20,13 +29,107 @@ pub struct CustomImageInfo {
     pub path: String,
     pub name: String,
     pub size: u64,
+    /// Checksum file found next to the image, e.g. `foo.img.xz.sha`
+    pub sha_file: Option<String>,
+    /// Hash from `sha_file`, if it could be parsed
+    pub expected_sha256: Option<String>,
+    /// Set once the image was checked by `verify_custom_image`
+    pub sha_verification: Option<ShaVerification>,
+}
+
+/// Where the expected hash of a custom image came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShaSource {
+    File,
+    Manual,
+}
+
+/// Result of checking a custom image against its expected SHA256
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShaVerification {
+    pub source: ShaSource,
+    pub expected: String,
+    pub actual: String,
+    pub matched: bool,
+}
+
+/// Find and parse a checksum file next to `image_path`
+///
+/// Returns the checksum file and the hash for the image, if one parses.
+fn discover_sha_file(image_path: &Path) -> Option<(PathBuf, Option<String>)> {
+    let filename = image_path.file_name()?.to_str()?;
+
+    config::images::SHA_FILE_EXTENSIONS
+        .iter()
+        .map(|ext| image_path.with_file_name(format!("{}.{}", filename, ext)))
+        .find(|p| {
+            p.metadata()
+                .is_ok_and(|m| m.is_file() && m.len() <= config::images::MAX_SHA_FILE_SIZE)
+        })
+        .map(|sha_path| {
+            let hash = std::fs::read_to_string(&sha_path)
+                .ok()
+                .and_then(|content| parse_sha256_file(&content, Some(filename)));
+            if hash.is_none() {
+                log_warn!(
+                    "custom_image",
+                    "No SHA256 hash found in {}",
+                    sha_path.display()
+                );
+            }
+            (sha_path, hash)
+        })
+}
+
+/// Describe a custom image file, including any checksum file next to it
+fn custom_image_info(path: &Path) -> Result<CustomImageInfo, String> {
+    let metadata = std::fs::metadata(path).map_err(|e| {
+        log_error!(
+            "custom_image",
+            "Failed to read file info for {:?}: {}",
+            path,
+            e
+        );
+        format!("Failed to read file info: {}", e)
+    })?;
+
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let (sha_file, expected_sha256) = match discover_sha_file(path) {
+        Some((sha_path, hash)) => (Some(sha_path.to_string_lossy().to_string()), hash),
+        None => {
+            if is_document_portal_path(path) {
+                // The portal shares only the picked file, never its siblings
+                log_info!(
+                    "custom_image",
+                    "Image came through the Documents portal, checksum files next to it are not visible"
+                );
+            }
+            (None, None)
+        }
+    };
+
+    Ok(CustomImageInfo {
+        path: path.to_string_lossy().to_string(),
+        name,
+        size: metadata.len(),
+        sha_file,
+        expected_sha256,
+        sha_verification: None,
+    })
 }
 
 /// Check if a custom image needs decompression
+///
+/// XZ and zstd images are decompressed while flashing and don't.
 #[tauri::command]
 pub async fn check_needs_decompression(image_path: String) -> Result<bool, String> {
     let path = PathBuf::from(&image_path);
-    let needs = needs_decompression(&path);
+    let needs = needs_decompression(&path) && !is_streamed(&path);
     log_info!(
         "custom_image",
         "Check decompression for {}: {}",
@@ -36,27 +139,54 @@ pub async fn check_needs_decompression(image_path: String) -> Result<bool, Strin
     Ok(needs)
 }
 
+/// Where a custom image would be decompressed, with warnings and alternatives
+///
+/// The cache is avoided when it is RAM-backed (tmpfs, zram) or too small.
+#[tauri::command]
+pub async fn get_decompress_placement(image_path: String) -> Result<DecompressPlacement, String> {
+    let path = PathBuf::from(&image_path);
+    let placement = tokio::task::spawn_blocking(move || plan_decompression(&path))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))?;
+
+    if let Some(warning) = &placement.warning {
+        log_warn!("custom_image", "Decompression destination: {}", warning);
+    }
+    Ok(placement)
+}
+
 /// Decompress a custom image file
+///
+/// `target_dir` picks one of the directories offered by `get_decompress_placement`.
 /// Returns the path to the decompressed file
 #[tauri::command]
 pub async fn decompress_custom_image(
     image_path: String,
+    target_dir: Option<String>,
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
+    window: tauri::Window,
 ) -> Result<String, String> {
     log_info!("custom_image", "Starting decompression: {}", image_path);
     let path = PathBuf::from(&image_path);
-    let download_state = state.download_state.clone();
+    let download_state = state.operations(window.label()).download_state.clone();
 
     // Reset state for progress tracking
     download_state.reset();
+    download_state
+        .pause_on_low_space
+        .store(pause_on_low_space(&app), Ordering::SeqCst);
 
     // Run decompression in a blocking task
-    let result = tokio::task::spawn_blocking(move || decompress_local_file(&path, &download_state))
-        .await
-        .map_err(|e| {
-            log_error!("custom_image", "Decompression task failed: {}", e);
-            format!("Task failed: {}", e)
-        })?;
+    let result = tokio::task::spawn_blocking(move || {
+        let target_dir = target_dir.map(PathBuf::from);
+        decompress_local_file(&path, target_dir.as_deref(), &download_state)
+    })
+    .await
+    .map_err(|e| {
+        log_error!("custom_image", "Decompression task failed: {}", e);
+        format!("Task failed: {}", e)
+    })?;
 
     match &result {
         Ok(path) => {
@@ -98,34 +228,19 @@ pub async fn select_custom_image(window: tauri::Window) -> Result<Option<CustomI
                 log_error!("custom_image", "Invalid path: not a valid file path");
                 "Invalid path: not a valid file path".to_string()
             })?;
-            let metadata = std::fs::metadata(path_buf).map_err(|e| {
-                log_error!(
-                    "custom_image",
-                    "Failed to read file info for {:?}: {}",
-                    path_buf,
-                    e
-                );
-                format!("Failed to read file info: {}", e)
-            })?;
-
-            let name = path_buf
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown")
-                .to_string();
+            let info = custom_image_info(path_buf)?;
 
             log_info!(
                 "custom_image",
                 "Selected custom image: {} ({} bytes)",
-                name,
-                metadata.len()
+                info.name,
+                info.size
             );
+            if let Some(sha_file) = &info.sha_file {
+                log_info!("custom_image", "Found checksum file: {}", sha_file);
+            }
 
-            Ok(Some(CustomImageInfo {
-                path: path_buf.to_string_lossy().to_string(),
-                name,
-                size: metadata.len(),
-            }))
+            Ok(Some(info))
         }
         None => {
             log_info!("custom_image", "File picker cancelled by user");
@@ -134,6 +249,71 @@ pub async fn select_custom_image(window: tauri::Window) -> Result<Option<CustomI
     }
 }
 
+/// Check a custom image against its expected SHA256 before flashing
+///
+/// `expected_sha256` is a hash pasted by the user; without it the hash from
+/// the checksum file next to the image is used. A mismatch is reported in
+/// `sha_verification`, not as an error. Without any hash nothing is checked.
+#[tauri::command]
+pub async fn verify_custom_image(
+    image_path: String,
+    expected_sha256: Option<String>,
+    state: State<'_, AppState>,
+    window: tauri::Window,
+) -> Result<CustomImageInfo, String> {
+    let path = PathBuf::from(&image_path);
+    let mut info = custom_image_info(&path)?;
+
+    let (source, expected) = match expected_sha256.filter(|h| !h.trim().is_empty()) {
+        Some(manual) => (
+            ShaSource::Manual,
+            normalize_sha256(&manual)
+                .ok_or_else(|| format!("Invalid SHA256 hash: {}", manual.trim()))?,
+        ),
+        None => match info.expected_sha256.clone() {
+            Some(hash) => (ShaSource::File, hash),
+            None => return Ok(info),
+        },
+    };
+
+    log_info!(
+        "custom_image",
+        "Verifying {} against SHA256 from {:?}",
+        info.name,
+        source
+    );
+    let download_state = state.operations(window.label()).download_state.clone();
+    download_state.reset();
+    download_state
+        .is_verifying_sha
+        .store(true, Ordering::SeqCst);
+
+    let actual = hash_file_sha256(&path, &download_state).await;
+    download_state
+        .is_verifying_sha
+        .store(false, Ordering::SeqCst);
+    let actual = actual?;
+
+    let matched = actual == expected;
+    if matched {
+        log_info!("custom_image", "Custom image SHA256 verification PASSED");
+    } else {
+        log_error!(
+            "custom_image",
+            "Custom image SHA256 verification FAILED! Expected: {}, Got: {}",
+            expected,
+            actual
+        );
+    }
+    info.sha_verification = Some(ShaVerification {
+        source,
+        expected,
+        actual,
+        matched,
+    });
+    Ok(info)
+}
+
 /// Delete a decompressed custom image file
 #[tauri::command]
 pub async fn delete_decompressed_custom_image(image_path: String) -> Result<(), String> {
@@ -144,17 +324,18 @@ pub async fn delete_decompressed_custom_image(image_path: String) -> Result<(),
     );
     let path = PathBuf::from(&image_path);
 
-    // Safety check: only delete files in our custom-decompress directory
-    let custom_decompress_dir = get_cache_dir(config::app::NAME).join("custom-decompress");
-
-    if !path.starts_with(&custom_decompress_dir) {
+    // Safety check: only delete files in our decompression directories
+    let Some(custom_decompress_dir) = decompress_dirs()
+        .into_iter()
+        .find(|dir| path.parent() == Some(dir.as_path()))
+    else {
         log_error!(
             "custom_image",
             "Attempted to delete file outside custom-decompress cache: {}",
             image_path
         );
         return Err("Cannot delete files outside custom-decompress directory".to_string());
-    }
+    };
 
     if path.exists() {
         std::fs::remove_file(&path).map_err(|e| {
@@ -181,6 +362,7 @@ pub async fn delete_decompressed_custom_image(image_path: String) -> Result<(),
 pub async fn detect_board_from_filename(
     filename: String,
     state: State<'_, AppState>,
+    app: tauri::AppHandle,
 ) -> Result<Option<BoardInfo>, String> {
     log_info!(
         "custom_image",
@@ -188,48 +370,29 @@ pub async fn detect_board_from_filename(
         filename
     );
 
-    // 1. Extract filename from path (remove directory)
-    let path = PathBuf::from(&filename);
-    let filename_only = path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .ok_or("Invalid filename")?;
-
-    // 2. Remove extension(s) - handle .img.xz, .img.gz, .img.zst, .img.bz2, .img
-    let stem = filename_only
-        .strip_suffix(".xz")
-        .or_else(|| filename_only.strip_suffix(".gz"))
-        .or_else(|| filename_only.strip_suffix(".zst"))
-        .or_else(|| filename_only.strip_suffix(".bz2"))
-        .or_else(|| filename_only.strip_suffix(".img"))
-        .unwrap_or(filename_only);
-
-    // 3. Parse Armbian naming pattern: Armbian_VERSION_BOARD_DISTRO_VENDOR_KERNEL_FLAVOR
-    let parts: Vec<&str> = stem.split('_').collect();
-
-    // 4. Validate Armbian format (at least 4 parts, starts with "Armbian")
-    if parts.len() < 4 || !parts[0].eq_ignore_ascii_case("Armbian") {
+    // 1. Parse Armbian naming pattern: Armbian_VERSION_BOARD_DISTRO_VENDOR_KERNEL_FLAVOR
+    let Some(parsed) = parse_image_filename(&filename) else {
         log_info!(
             "custom_image",
             "Not an Armbian image or invalid format: {}",
-            filename_only
+            filename
         );
         return Ok(None);
-    }
+    };
 
-    // 5. Extract board name (index 2)
-    let board_name = parts[2];
+    // 2. Extract board name
+    let board_name = parsed.board.as_str();
     log_info!(
         "custom_image",
         "Extracted board name from filename: {}",
         board_name
     );
 
-    // 6. Normalize board name to slug format
+    // 3. Normalize board name to slug format
     let normalized_slug = normalize_slug(board_name);
     log_info!("custom_image", "Normalized board slug: {}", normalized_slug);
 
-    // 7. Ensure board data is loaded (auto-load if not cached)
+    // 4. Ensure board data is loaded (auto-load if not cached)
     // Use compare-and-swap pattern to prevent race conditions
     log_info!("custom_image", "Checking if board data is cached...");
     {
@@ -243,10 +406,12 @@ pub async fn detect_board_from_filename(
                 "custom_image",
                 "Board data not cached, fetching from API..."
             );
-            let json = fetch_all_images().await.map_err(|e| {
-                log_error!("custom_image", "Failed to fetch board data: {}", e);
-                format!("Failed to fetch board data: {}", e)
-            })?;
+            let json = fetch_all_images(&catalog_sources(&app))
+                .await
+                .map_err(|e| {
+                    log_error!("custom_image", "Failed to fetch board data: {}", e);
+                    format!("Failed to fetch board data: {}", e)
+                })?;
 
             // Cache the fetched data
             let mut json_guard = state.images_json.lock().await;
@@ -263,7 +428,7 @@ pub async fn detect_board_from_filename(
         }
     }
 
-    // 8. Get cached boards data (now guaranteed to be loaded)
+    // 5. Get cached boards data (now guaranteed to be loaded)
     // Extract boards in a scoped block to release lock early
     let matching_board = {
         log_info!("custom_image", "Accessing cached board data...");
@@ -281,7 +446,7 @@ pub async fn detect_board_from_filename(
         );
         // Lock released here
 
-        // 9. Find matching board by slug
+        // 6. Find matching board by slug
         boards
             .iter()
             .find(|board| board.slug == normalized_slug)
@@ -306,3 +471,33 @@ pub async fn detect_board_from_filename(
     log_info!("custom_image", "Board detection completed successfully");
     Ok(matching_board)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover_sha_file() {
+        let dir = std::env::temp_dir().join(format!("custom-sha-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let image = dir.join("foo.img.xz");
+        std::fs::write(&image, b"image").unwrap();
+        assert!(discover_sha_file(&image).is_none());
+
+        let hash = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        std::fs::write(
+            dir.join("foo.img.xz.sha"),
+            format!("{} *foo.img.xz\n", hash),
+        )
+        .unwrap();
+        let (sha_path, found) = discover_sha_file(&image).unwrap();
+        assert_eq!(sha_path, dir.join("foo.img.xz.sha"));
+        assert_eq!(found.as_deref(), Some(hash));
+
+        // A checksum file without a hash is still reported
+        std::fs::write(dir.join("foo.img.xz.sha"), "garbage").unwrap();
+        assert_eq!(discover_sha_file(&image).unwrap().1, None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,60 @@
+//! First-boot customization commands
+//!
+//! Run after a successful flash to preset the host name, default user,
+//! SSH and Wi-Fi network on the written device.
+
+use tauri::{State, Window};
+
+use crate::customization::{self, Customization, CustomizationReport, NetworkConfig};
+use crate::devices::get_block_devices;
+use crate::history::invalidate_verification;
+use crate::log_warn;
+use crate::utils::run_blocking;
+
+use super::confirmation::find_flash_target;
+use super::state::AppState;
+
+/// Apply first-boot customization to a device that was just written
+#[tauri::command]
+pub async fn apply_customization(
+    device_path: String,
+    customization: Customization,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<CustomizationReport, String> {
+    let _claim = state.claim_device(window.label(), "customize", &device_path)?;
+
+    // Same target rules as flashing: only an enumerated, non-system device
+    let device = get_block_devices()
+        .and_then(|devices| find_flash_target(devices, &device_path))
+        .inspect_err(|e| log_warn!("customization", "Refusing to customize: {}", e))?;
+
+    // The device no longer matches the image it was verified against
+    if let Some(serial) = device.serial.as_deref() {
+        invalidate_verification(serial, "customized");
+    }
+
+    // Mounting and unmounting run external tools
+    run_blocking(move || customization::apply_customization(&device_path, &customization)).await
+}
+
+/// Write Wi-Fi credentials for the first boot to a device that was just written
+#[tauri::command]
+pub async fn apply_network_config(
+    device_path: String,
+    config: NetworkConfig,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<CustomizationReport, String> {
+    let _claim = state.claim_device(window.label(), "customize", &device_path)?;
+
+    let device = get_block_devices()
+        .and_then(|devices| find_flash_target(devices, &device_path))
+        .inspect_err(|e| log_warn!("customization", "Refusing to customize: {}", e))?;
+
+    if let Some(serial) = device.serial.as_deref() {
+        invalidate_verification(serial, "customized");
+    }
+
+    run_blocking(move || customization::apply_network_config(&device_path, &config)).await
+}
@@ -0,0 +1,320 @@
+//! Fleet mode commands
+//!
+//! While fleet mode is active, a subscription to the event bus picks up the
+//! devices the hotplug watcher reports as added and starts a job for each one
+//! that matches the criteria. A job runs the same pipeline as a flash started
+//! from a window (write warning, target checks, authorization, flash and
+//! verification, audit entry), then writes the first-boot settings and ejects
+//! the device. At most `config::fleet::MAX_PARALLEL_JOBS` jobs write at once.
+//!
+//! Stopping fleet mode is a hard stop: running flashes are cancelled and
+//! queued jobs never start. Customization and eject, which take seconds and
+//! can't be interrupted safely, finish first.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Semaphore;
+
+use crate::config;
+use crate::customization;
+use crate::devices::get_block_devices;
+use crate::events::{self, AppEvent, SubscriptionId};
+use crate::flash::{eject_device, request_authorization, FlashState};
+use crate::fleet::{FleetJobStage, FleetSettings, FleetStatus};
+use crate::history::invalidate_verification;
+use crate::utils::{run_blocking, run_blocking_future};
+use crate::{log_error, log_info, log_warn};
+
+use super::onboarding::write_warning_accepted;
+use super::operations::run_flash;
+use super::settings::device_aliases;
+use super::state::AppState;
+
+const MODULE: &str = "fleet";
+
+/// A running fleet session
+struct Session {
+    settings: Arc<FleetSettings>,
+    subscription: SubscriptionId,
+    /// Write slots; closed when the session stops
+    slots: Arc<Semaphore>,
+    stopped: Arc<AtomicBool>,
+    next_id: u64,
+}
+
+#[derive(Default)]
+struct Fleet {
+    session: Option<Session>,
+    status: FleetStatus,
+    /// Flash state of jobs that are writing or verifying, by job ID
+    running: HashMap<String, Arc<FlashState>>,
+}
+
+static FLEET: Lazy<Mutex<Fleet>> = Lazy::new(|| Mutex::new(Fleet::default()));
+
+fn fleet() -> std::sync::MutexGuard<'static, Fleet> {
+    FLEET.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Let the frontend know the fleet status changed
+fn fleet_changed() {
+    events::publish(AppEvent::FleetChanged);
+}
+
+fn set_stage(id: &str, stage: FleetJobStage) {
+    fleet().status.set_stage(id, stage);
+    fleet_changed();
+}
+
+/// Start fleet mode: every matching device inserted from now on is written
+#[tauri::command]
+pub fn start_fleet_mode(settings: FleetSettings, app: AppHandle) -> Result<FleetStatus, String> {
+    if !write_warning_accepted(&app) {
+        return Err("The warning that writing erases the device has not been accepted".to_string());
+    }
+    if !Path::new(&settings.image_path).is_file() {
+        return Err(format!("Image not found: {}", settings.image_path));
+    }
+    settings.criteria.validate()?;
+    if let Some(customization) = &settings.customization {
+        customization.validate()?;
+    }
+    if let Some(network) = &settings.network {
+        network.validate()?;
+    }
+
+    let mut fleet = fleet();
+    if fleet.session.is_some() {
+        return Err("Fleet mode is already running".to_string());
+    }
+
+    let handle = app.clone();
+    let subscription = events::subscribe(move |event| {
+        if let AppEvent::DevicesChanged { added, .. } = event {
+            for device_path in added {
+                tauri::async_runtime::spawn(run_job(handle.clone(), device_path.clone()));
+            }
+        }
+    });
+    log_info!(
+        MODULE,
+        "Fleet mode started: {} (verify: {}, eject: {}, criteria: {:?})",
+        settings.image_path,
+        settings.verify,
+        settings.eject,
+        settings.criteria
+    );
+    fleet.status = FleetStatus::started(&settings.image_path);
+    fleet.session = Some(Session {
+        settings: Arc::new(settings),
+        subscription,
+        slots: Arc::new(Semaphore::new(config::fleet::MAX_PARALLEL_JOBS)),
+        stopped: Arc::new(AtomicBool::new(false)),
+        next_id: 0,
+    });
+    let status = fleet.status.clone();
+    drop(fleet);
+    fleet_changed();
+    Ok(status)
+}
+
+/// Stop fleet mode, cancelling running flashes and queued jobs
+#[tauri::command]
+pub fn stop_fleet_mode() -> FleetStatus {
+    let mut fleet = fleet();
+    if let Some(session) = fleet.session.take() {
+        events::unsubscribe(session.subscription);
+        session.stopped.store(true, Ordering::SeqCst);
+        session.slots.close();
+        for flash_state in fleet.running.values() {
+            flash_state.is_cancelled.store(true, Ordering::SeqCst);
+        }
+        log_info!(
+            MODULE,
+            "Fleet mode stopped: {} completed, {} failed, {} cancelled",
+            fleet.status.completed,
+            fleet.status.failed,
+            fleet.running.len()
+        );
+    }
+    fleet.status.active = false;
+    let status = fleet.status.clone();
+    drop(fleet);
+    fleet_changed();
+    status
+}
+
+/// Get the fleet status, with the progress of running flashes
+#[tauri::command]
+pub fn get_fleet_status() -> FleetStatus {
+    let fleet = fleet();
+    let mut status = fleet.status.clone();
+    for job in &mut status.jobs {
+        let Some(fs) = fleet.running.get(&job.id) else {
+            continue;
+        };
+        let total = fs.total_bytes.load(Ordering::SeqCst);
+        let done = if fs.is_verifying.load(Ordering::SeqCst) {
+            job.stage = FleetJobStage::Verifying;
+            fs.verified_bytes.load(Ordering::SeqCst)
+        } else {
+            fs.written_bytes.load(Ordering::SeqCst)
+        };
+        if total > 0 {
+            job.progress_percent = done as f64 / total as f64 * 100.0;
+        }
+    }
+    status
+}
+
+/// Handle one added device: check it against the criteria, then write it
+async fn run_job(app: AppHandle, device_path: String) {
+    let device = get_block_devices().and_then(|devices| {
+        devices
+            .into_iter()
+            .find(|device| device.path == device_path)
+            .ok_or_else(|| format!("{} is gone", device_path))
+    });
+    let mut device = match device {
+        Ok(device) => device,
+        Err(e) => {
+            log_warn!(MODULE, "Fleet mode skips {}: {}", device_path, e);
+            return;
+        }
+    };
+    device.apply_alias(&device_aliases(&app));
+
+    let (id, settings, slots, stopped) = {
+        let mut guard = fleet();
+        let Fleet {
+            session, status, ..
+        } = &mut *guard;
+        let Some(session) = session.as_mut() else {
+            return;
+        };
+        if let Some(reason) = session.settings.criteria.rejection(&device) {
+            log_info!(MODULE, "Fleet mode skips {}: {}", device_path, reason);
+            return;
+        }
+        if status.is_busy(&device_path) {
+            return;
+        }
+        session.next_id += 1;
+        let id = format!("fleet-{}", session.next_id);
+        status.add_job(&id, &device);
+        (
+            id,
+            session.settings.clone(),
+            session.slots.clone(),
+            session.stopped.clone(),
+        )
+    };
+    log_info!(
+        MODULE,
+        "Fleet job {}: {} ({}) inserted",
+        id,
+        device_path,
+        device.model
+    );
+    fleet_changed();
+
+    let result = match slots.acquire_owned().await {
+        Ok(_permit) => write_device(&app, &id, &device_path, &settings, &stopped).await,
+        Err(_) => Err("Fleet mode stopped".to_string()),
+    };
+    fleet().running.remove(&id);
+    app.state::<AppState>().release_window(&id);
+
+    let stage = match &result {
+        Ok(()) => {
+            log_info!(MODULE, "Fleet job {}: {} done", id, device_path);
+            FleetJobStage::Done
+        }
+        Err(e) if stopped.load(Ordering::SeqCst) => {
+            log_warn!(MODULE, "Fleet job {}: cancelled: {}", id, e);
+            FleetJobStage::Cancelled
+        }
+        Err(e) => {
+            log_error!(MODULE, "Fleet job {}: {} failed: {}", id, device_path, e);
+            FleetJobStage::Failed
+        }
+    };
+    fleet().status.finish(&id, stage, result.err());
+    fleet_changed();
+}
+
+/// Flash, customize and eject a device for job `id`
+async fn write_device(
+    app: &AppHandle,
+    id: &str,
+    device_path: &str,
+    settings: &FleetSettings,
+    stopped: &AtomicBool,
+) -> Result<(), String> {
+    if stopped.load(Ordering::SeqCst) {
+        return Err("Fleet mode stopped".to_string());
+    }
+    set_stage(id, FleetJobStage::Flashing);
+    if !request_authorization(device_path)? {
+        return Err("Authorization cancelled".to_string());
+    }
+
+    // The job ID stands in for a window label, giving each job its own state
+    let state = app.state::<AppState>();
+    fleet()
+        .running
+        .insert(id.to_string(), state.operations(id).flash_state.clone());
+    run_flash(
+        settings.image_path.clone(),
+        device_path.to_string(),
+        settings.verify,
+        None,
+        None,
+        None,
+        &state,
+        id,
+        app,
+    )
+    .await?;
+    fleet().running.remove(id);
+
+    if settings.customization.is_some() || settings.network.is_some() {
+        set_stage(id, FleetJobStage::Customizing);
+        let _claim = state.claim_device(id, "customize", device_path)?;
+        if let Some(serial) = get_block_devices()?
+            .iter()
+            .find(|device| device.path == device_path)
+            .and_then(|device| device.serial.as_deref())
+        {
+            invalidate_verification(serial, "customized");
+        }
+        let (path, customization, network) = (
+            device_path.to_string(),
+            settings.customization.clone(),
+            settings.network.clone(),
+        );
+        run_blocking(move || {
+            if let Some(customization) = customization {
+                customization::apply_customization(&path, &customization)?;
+            }
+            if let Some(network) = network {
+                customization::apply_network_config(&path, &network)?;
+            }
+            Ok(())
+        })
+        .await?;
+    }
+
+    if settings.eject {
+        set_stage(id, FleetJobStage::Ejecting);
+        let _claim = state.claim_device(id, "eject", device_path)?;
+        let path = device_path.to_string();
+        run_blocking_future(async move { eject_device(&path).await }).await?;
+    }
+    Ok(())
+}
@@ -2,14 +2,23 @@
 //!
 //! Tauri command handlers organized by responsibility.
 
+pub mod backup;
 pub mod board_queries;
+pub mod confirmation;
+pub mod credentials;
 pub mod custom_image;
+pub mod customization;
+pub mod fleet;
+pub mod onboarding;
 pub mod operations;
+pub mod partitions;
 pub mod progress;
+pub mod queue;
 pub mod scraping;
 pub mod settings;
 mod state;
 pub mod system;
+pub mod timeseries;
 pub mod update;
 
 // Re-export state for use in main.rs
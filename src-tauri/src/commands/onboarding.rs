@@ -0,0 +1,182 @@
+//! First-run onboarding commands
+//!
+//! Onboarding progress is kept in the settings store instead of the webview,
+//! so reinstalls and multiple windows see the same state, and `flash_image`
+//! can refuse to write until the user has accepted that writing erases the
+//! target device.
+//!
+//! Steps are completed in order: welcome, write warning, defaults.
+
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tauri_plugin_store::StoreExt;
+
+use crate::settings_store::commit_settings;
+use crate::{log_info, log_warn};
+
+const MODULE: &str = "commands::onboarding";
+const SETTINGS_STORE: &str = "settings.json";
+const ONBOARDING_KEY: &str = "onboarding";
+
+/// Serializes read-modify-write cycles from concurrent windows
+static ONBOARDING_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Onboarding steps, in the order they must be completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnboardingStep {
+    /// Welcome screen seen
+    Welcome,
+    /// Warning that writing erases the target accepted
+    WriteWarning,
+    /// Default settings (verification, cache, ...) chosen
+    Defaults,
+    /// All steps done
+    Complete,
+}
+
+impl OnboardingStep {
+    fn next(self) -> Self {
+        match self {
+            OnboardingStep::Welcome => OnboardingStep::WriteWarning,
+            OnboardingStep::WriteWarning => OnboardingStep::Defaults,
+            OnboardingStep::Defaults | OnboardingStep::Complete => OnboardingStep::Complete,
+        }
+    }
+}
+
+/// Persisted onboarding progress
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OnboardingState {
+    /// Next step to complete, `Complete` once onboarding is done
+    pub step: OnboardingStep,
+    /// RFC 3339 time the write warning was accepted
+    pub write_warning_accepted_at: Option<String>,
+    /// RFC 3339 time the last step was completed
+    pub completed_at: Option<String>,
+}
+
+impl Default for OnboardingState {
+    fn default() -> Self {
+        Self {
+            step: OnboardingStep::Welcome,
+            write_warning_accepted_at: None,
+            completed_at: None,
+        }
+    }
+}
+
+impl OnboardingState {
+    pub fn write_warning_accepted(&self) -> bool {
+        self.step > OnboardingStep::WriteWarning
+    }
+
+    /// Complete `step`; steps already done are accepted again, skipping ahead is not
+    fn advance(&mut self, step: OnboardingStep) -> Result<(), String> {
+        if step == OnboardingStep::Complete {
+            return Err("Complete is not an onboarding step".to_string());
+        }
+        if step < self.step {
+            return Ok(());
+        }
+        if step > self.step {
+            return Err(format!(
+                "Onboarding step {:?} must be completed before {:?}",
+                self.step, step
+            ));
+        }
+
+        let now = chrono::Utc::now().to_rfc3339();
+        if step == OnboardingStep::WriteWarning {
+            self.write_warning_accepted_at = Some(now.clone());
+        }
+        self.step = step.next();
+        if self.step == OnboardingStep::Complete {
+            self.completed_at = Some(now);
+        }
+        Ok(())
+    }
+}
+
+fn load_state(app: &tauri::AppHandle) -> OnboardingState {
+    match app.store(SETTINGS_STORE) {
+        Ok(store) => store
+            .get(ONBOARDING_KEY)
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default(),
+        Err(e) => {
+            log_info!(
+                MODULE,
+                "Error loading store, starting onboarding over: {}",
+                e
+            );
+            OnboardingState::default()
+        }
+    }
+}
+
+/// Whether the user accepted that writing erases the target device
+pub fn write_warning_accepted(app: &tauri::AppHandle) -> bool {
+    load_state(app).write_warning_accepted()
+}
+
+/// Get the onboarding progress
+#[tauri::command]
+pub fn get_onboarding_state(app: tauri::AppHandle) -> OnboardingState {
+    let _lock = ONBOARDING_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load_state(&app)
+}
+
+/// Complete an onboarding step and return the new progress
+#[tauri::command]
+pub fn advance_onboarding(
+    step: OnboardingStep,
+    app: tauri::AppHandle,
+) -> Result<OnboardingState, String> {
+    let _lock = ONBOARDING_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut state = load_state(&app);
+    let before = state.step;
+    state.advance(step).inspect_err(|e| {
+        log_warn!(MODULE, "{}", e);
+    })?;
+
+    if state.step != before {
+        log_info!(MODULE, "Onboarding step {:?} completed", step);
+        let value = serde_json::to_value(&state)
+            .map_err(|e| format!("Failed to serialize onboarding state: {}", e))?;
+        match app.store(SETTINGS_STORE) {
+            Ok(store) => store.set(ONBOARDING_KEY, value),
+            Err(e) => return Err(format!("Failed to access store: {}", e)),
+        }
+        commit_settings(&app)?;
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_complete_in_order() {
+        let mut state = OnboardingState::default();
+        assert!(!state.write_warning_accepted());
+        assert!(state.advance(OnboardingStep::WriteWarning).is_err());
+
+        state.advance(OnboardingStep::Welcome).unwrap();
+        state.advance(OnboardingStep::WriteWarning).unwrap();
+        assert!(state.write_warning_accepted());
+        assert!(state.write_warning_accepted_at.is_some());
+
+        // Repeating a finished step is harmless
+        state.advance(OnboardingStep::Welcome).unwrap();
+        assert_eq!(state.step, OnboardingStep::Defaults);
+
+        state.advance(OnboardingStep::Defaults).unwrap();
+        assert_eq!(state.step, OnboardingStep::Complete);
+        assert!(state.completed_at.is_some());
+        assert!(state.advance(OnboardingStep::Complete).is_err());
+    }
+}
@@ -3,16 +3,54 @@
 //! Handles download and flash operations.
 
 use std::path::PathBuf;
-use tauri::{AppHandle, State};
-use tauri_plugin_store::StoreExt;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, State, Window};
+
+use crate::audit::{record_audit_entry, set_operation_note, AuditEntry, AuditResult};
+use crate::cache::{deletable_cache_file, export_image, get_images_cache_dir};
 use crate::config;
-use crate::download::download_image as do_download;
-use crate::flash::{flash_image as do_flash, request_authorization};
-use crate::utils::get_cache_dir;
-use crate::{log_debug, log_error, log_info};
+use crate::devices::{get_block_devices, BlockDevice, UsbBridge};
+use crate::direct;
+use crate::download::{
+    check_download_url, download_image as do_download, extract_filename, is_download_cached,
+    probe_download, DownloadProbe, DownloadState,
+};
+use crate::events::{self, AppEvent};
+use crate::flash::{
+    check_capacity as do_check_capacity, eject_device as do_eject, erase_device as do_erase,
+    flash_image as do_flash, flash_range as do_flash_range, hexdump_lines, image_size, lock_device,
+    privilege_status, request_authorization, verify_device as do_verify, volume_serial,
+    write_boot_partition_report, CapacityReport, EraseFilesystem, EraseLayout, FlashState,
+    PrivilegeStatus, RangeWrite, SecondaryImage, TailCheck, VerifyMode, VerifyOptions, Watchdog,
+};
+use crate::history::{
+    invalidate_verification, load_history, record_flash_failure, record_flash_outcome,
+    record_speed, record_verified_device, DeviceReliability, FlashFailureRecord, MirrorStats,
+    OperationStage, VerifiedDevice,
+};
+use crate::images::ImageInfo;
+use crate::logging::begin_operation;
+use crate::mirrors::Mirror;
+use crate::settings::{self, EraseMode};
+use crate::utils::{
+    get_cache_dir, network_status, run_blocking, run_blocking_future, NetworkStatus, MB,
+};
+use crate::{log_debug, log_error, log_info, log_warn};
 
-use super::state::AppState;
+use super::confirmation::{check_dangerous_target, find_flash_target, revoke_confirmations};
+use super::onboarding::write_warning_accepted;
+use super::progress::ProgressEmitter;
+use super::settings::{
+    developer_throttles, download_retries, download_segments, get_mirror_region,
+    get_skip_zero_blocks, get_smart_write, insecure_urls_allowed, metered_allowed_networks,
+    pause_on_low_space,
+};
+use super::state::{AppState, JobControl};
+use super::timeseries::{SampleSource, ThroughputSampler};
 
 /// Request write authorization before starting the flash process
 /// This shows the authorization dialog (Touch ID on macOS) BEFORE downloading
@@ -50,13 +88,95 @@ pub async fn request_write_authorization(device_path: String) -> Result<bool, St
     result
 }
 
+/// Report the process privileges and the prompt that writing will trigger
+#[tauri::command]
+pub fn get_privilege_status() -> PrivilegeStatus {
+    let status = privilege_status();
+    log_debug!(
+        "operations",
+        "Privileges: elevated={}, helper={:?} (available: {}), write prompt: {:?}",
+        status.elevated,
+        status.helper,
+        status.helper_available,
+        status.write_prompt
+    );
+    status
+}
+
+/// Probe a download that is not cached yet with a HEAD request
+///
+/// `file_size` is the catalog size (0 if unknown). The probe's warnings
+/// (missing artifact, size mismatch) are shown before the download starts.
+#[tauri::command]
+pub async fn probe_image_download(
+    file_url: String,
+    file_size: u64,
+    app: AppHandle,
+) -> DownloadProbe {
+    // A refused URL is reported by the download itself
+    if is_download_cached(&file_url)
+        || check_download_url(&file_url, insecure_urls_allowed(&app)).is_err()
+    {
+        return DownloadProbe::default();
+    }
+    probe_download(&file_url, file_size).await
+}
+
+/// Whether a download needs the metered connection warning first
+#[derive(Debug, Clone, Serialize)]
+pub struct MeteredDownloadCheck {
+    /// Show the warning before downloading
+    pub warn: bool,
+    pub network: NetworkStatus,
+}
+
+/// Check the connection before a download that is not cached yet
+///
+/// Warns for images of at least `config::download::METERED_WARN_SIZE` on a
+/// metered connection the user has not allowed.
+#[tauri::command]
+pub async fn check_metered_download(
+    file_url: String,
+    file_size: u64,
+    app: AppHandle,
+) -> Result<MeteredDownloadCheck, String> {
+    if file_size < config::download::METERED_WARN_SIZE || is_download_cached(&file_url) {
+        return Ok(MeteredDownloadCheck {
+            warn: false,
+            network: NetworkStatus::default(),
+        });
+    }
+
+    let network = run_blocking(|| Ok(network_status())).await?;
+    let allowed = network
+        .network_id
+        .as_ref()
+        .is_some_and(|id| metered_allowed_networks(&app).contains(id));
+    if network.metered {
+        log_info!(
+            "operations",
+            "Metered connection: {} (allowed: {})",
+            network.network_name.as_deref().unwrap_or("unknown network"),
+            allowed
+        );
+    }
+
+    Ok(MeteredDownloadCheck {
+        warn: network.metered && !allowed,
+        network,
+    })
+}
+
 /// Start downloading an image
 #[tauri::command]
 pub async fn download_image(
     file_url: String,
     file_url_sha: Option<String>,
     state: State<'_, AppState>,
+    window: Window,
+    app: AppHandle,
 ) -> Result<String, String> {
+    let operation = begin_operation("download");
     log_info!("operations", "Starting download: {}", file_url);
     log_debug!(
         "operations",
@@ -71,7 +191,24 @@ pub async fn download_image(
     }
     let download_dir = get_cache_dir(config::app::NAME).join("images");
 
-    let download_state = state.download_state.clone();
+    let download_state = state.operations(window.label()).download_state.clone();
+    let mut job = state.start_job(
+        window.label(),
+        operation.id(),
+        "download",
+        &file_url,
+        JobControl::Download(download_state.clone()),
+    )?;
+    configure_download(&download_state, &app).await;
+    let _sampler = ThroughputSampler::start(
+        operation.id(),
+        window.label(),
+        SampleSource::Download(download_state.clone()),
+    );
+    let _progress = ProgressEmitter::download(&app, window.label(), download_state.clone());
+    events::publish(AppEvent::DownloadStarted {
+        url: file_url.clone(),
+    });
     let result = do_download(
         &file_url,
         file_url_sha.as_deref(),
@@ -79,6 +216,16 @@ pub async fn download_image(
         download_state,
     )
     .await;
+    job.finish(&result);
+
+    events::publish(AppEvent::DownloadFinished {
+        url: file_url.clone(),
+        path: result
+            .as_ref()
+            .ok()
+            .map(|p| p.to_string_lossy().to_string()),
+        error: result.as_ref().err().cloned(),
+    });
 
     match &result {
         Ok(path) => {
@@ -92,15 +239,157 @@ pub async fn download_image(
     }
 }
 
+/// Apply the settings that shape a download to `download_state`
+async fn configure_download(download_state: &DownloadState, app: &AppHandle) {
+    let (download_throttle, _) = developer_throttles(app);
+    if download_throttle > 0 {
+        log_warn!(
+            "operations",
+            "Developer mode: simulating a {} KB/s network",
+            download_throttle / 1024
+        );
+    }
+    download_state
+        .throttle_bytes_per_sec
+        .store(download_throttle, Ordering::SeqCst);
+    download_state
+        .allow_insecure
+        .store(insecure_urls_allowed(app), Ordering::SeqCst);
+    *download_state.mirror_region.lock().await = get_mirror_region(app.clone());
+    download_state
+        .segment_count
+        .store(download_segments(app), Ordering::SeqCst);
+    download_state
+        .retry_limit
+        .store(download_retries(app), Ordering::SeqCst);
+    download_state
+        .pause_on_low_space
+        .store(pause_on_low_space(app), Ordering::SeqCst);
+}
+
+/// Raw image to write at a byte offset after the main image
+#[derive(Debug, Clone, Deserialize)]
+pub struct SecondaryImageRequest {
+    pub path: String,
+    /// Byte offset on the device, at or after the end of the main image
+    pub offset: u64,
+}
+
 /// Start flashing an image to a device
+///
+/// With `secondary`, a raw partition image (e.g. a data filesystem) is
+/// written at its offset in the same operation and verified with the image.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn flash_image(
     image_path: String,
     device_path: String,
     verify: bool,
+    confirmation_token: Option<String>,
+    secondary: Option<SecondaryImageRequest>,
     state: State<'_, AppState>,
-    _app: AppHandle,
+    window: Window,
+    app: AppHandle,
 ) -> Result<(), String> {
+    run_flash(
+        image_path,
+        device_path,
+        verify,
+        confirmation_token,
+        secondary,
+        None,
+        &state,
+        window.label(),
+        &app,
+    )
+    .await
+}
+
+/// Download an image and flash it as it arrives, without caching it
+///
+/// Needs no free space for the image, at the cost of a download that cannot
+/// be resumed or reused. The image's checksum is required, and since the
+/// stream cannot be read twice, verification compares hashes only.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn flash_image_direct(
+    file_url: String,
+    file_url_sha: Option<String>,
+    device_path: String,
+    verify: bool,
+    confirmation_token: Option<String>,
+    state: State<'_, AppState>,
+    window: Window,
+    app: AppHandle,
+) -> Result<(), String> {
+    log_info!(
+        "operations",
+        "Starting direct write: {} -> {}",
+        file_url,
+        device_path
+    );
+    let owner = window.label();
+    let download_state = state.operations(owner).download_state.clone();
+    let flash_state = state.operations(owner).flash_state.clone();
+    configure_download(&download_state, &app).await;
+
+    let download = direct::open(&file_url, file_url_sha.as_deref(), download_state)
+        .await
+        .inspect_err(|e| log_error!("operations", "Direct write failed: {}", e))?;
+    flash_state.set_image_stream(Some(download.image));
+    let result = run_flash(
+        extract_filename(&file_url)?.to_string(),
+        device_path,
+        verify,
+        confirmation_token,
+        None,
+        Some(download.task),
+        &state,
+        owner,
+        &app,
+    )
+    .await;
+    // Closing the stream stops the download if the flash refused to start
+    flash_state.set_image_stream(None);
+    result
+}
+
+/// Move the note the owner set before starting onto the operation
+fn apply_pending_note(state: &AppState, owner: &str, operation_id: &str) {
+    if let Some(text) = state.operations(owner).take_pending_note() {
+        if let Err(e) = set_operation_note(operation_id, &text) {
+            log_warn!("operations", "Failed to note {}: {}", operation_id, e);
+        }
+    }
+}
+
+/// Flash an image on behalf of `owner`, a window label or a background job
+///
+/// `download` is the task feeding a streamed image; its outcome settles the
+/// flash's before anything is recorded, so a bad checksum fails the flash.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn run_flash(
+    image_path: String,
+    device_path: String,
+    verify: bool,
+    confirmation_token: Option<String>,
+    secondary: Option<SecondaryImageRequest>,
+    download: Option<JoinHandle<Result<(), String>>>,
+    state: &AppState,
+    owner: &str,
+    app: &AppHandle,
+) -> Result<(), String> {
+    if !write_warning_accepted(app) {
+        log_warn!(
+            "operations",
+            "Refusing to flash {}: write warning not accepted",
+            device_path
+        );
+        return Err("The warning that writing erases the device has not been accepted".to_string());
+    }
+
+    let operation = begin_operation("flash");
+    apply_pending_note(state, owner, operation.id());
     log_info!(
         "operations",
         "Starting flash: {} -> {} (verify: {})",
@@ -121,22 +410,1020 @@ pub async fn flash_image(
     log_debug!("operations", "Verification enabled: {}", verify);
 
     let path = PathBuf::from(&image_path);
-    let flash_state = state.flash_state.clone();
+    let flash_state = state.operations(owner).flash_state.clone();
+    let mut job = state.start_job(
+        owner,
+        operation.id(),
+        "flash",
+        &device_path,
+        JobControl::Flash(flash_state.clone()),
+    )?;
+    let _claim = state.claim_device(owner, operation.id(), &device_path)?;
+    let _system_lock = lock_device(&device_path)?;
+
+    // Only a device from the current enumeration is written, never an arbitrary
+    // path. Look it up before flashing, the partition table changes afterwards.
+    let device = get_block_devices()
+        .and_then(|devices| find_flash_target(devices, &device_path))
+        .inspect_err(|e| log_warn!("operations", "Refusing to flash: {}", e))?;
+    let serial = device.serial.clone();
+    if let Some(advice) = device.usb_bridge.as_ref().and_then(UsbBridge::advice) {
+        log_warn!("operations", "{}: {}", device_path, advice);
+    }
+    if let Some(warning) = device.usb_bridge.as_ref().and_then(UsbBridge::link_warning) {
+        log_warn!("operations", "{}: {}", device_path, warning);
+    }
+
+    check_dangerous_target(confirmation_token.as_deref(), &device)?;
+
+    if let Some(ref serial) = serial {
+        invalidate_verification(serial, "flash started");
+    }
+
+    let secondary = secondary
+        .map(|request| secondary_image_for(&path, &device, request))
+        .transpose()
+        .inspect_err(|e| log_warn!("operations", "Refusing to flash: {}", e))?;
+
+    // The zeroed space after the image would overlap the secondary image
+    let tail_check = match secondary {
+        Some(_) => None,
+        None => tail_check_for(&path, Some(&device), app),
+    };
+    let mut verify_options = VerifyOptions {
+        tail_check,
+        ..verify_options_for(&device_path, Some(&device), app)
+    };
+    // A streamed image cannot be read again, only compared by its hash
+    if flash_state.has_image_stream() {
+        verify_options.mode = VerifyMode::Hash;
+    }
+    flash_state.set_secondary_image(secondary);
+    events::publish(AppEvent::FlashStarted {
+        device_path: device_path.clone(),
+        image: image_path.clone(),
+    });
+    let (_, write_throttle) = developer_throttles(app);
+    if write_throttle > 0 {
+        log_warn!(
+            "operations",
+            "Developer mode: simulating a {} KB/s device",
+            write_throttle / 1024
+        );
+    }
+    flash_state
+        .write_throttle_bytes_per_sec
+        .store(write_throttle, Ordering::SeqCst);
+    flash_state
+        .smart_write
+        .store(get_smart_write(app.clone()), Ordering::SeqCst);
+    flash_state
+        .skip_zero_blocks
+        .store(get_skip_zero_blocks(app.clone()), Ordering::SeqCst);
+    let _sampler = ThroughputSampler::start(
+        operation.id(),
+        owner,
+        SampleSource::Flash(flash_state.clone()),
+    );
+    let _progress = ProgressEmitter::flash(app, owner, flash_state.clone());
+    let watchdog = Watchdog::start(flash_state.clone(), &device_path);
+    // The writers loop synchronously, keep them off the runtime's workers
+    let result = {
+        let (path, device_path, flash_state) =
+            (path.clone(), device_path.clone(), flash_state.clone());
+        run_blocking_future(async move {
+            do_flash(&path, &device_path, flash_state, verify, verify_options).await
+        })
+        .await
+    };
+    drop(watchdog);
+
+    // A failed download already fails the flash with its cause as a read
+    // error; a flash that read everything may still end on a bad checksum
+    let result = match download {
+        Some(download) => {
+            // Closing the stream stops the download if the flash ended early
+            flash_state.set_image_stream(None);
+            let downloaded = download
+                .await
+                .map_err(|e| format!("Download task failed: {}", e))
+                .and_then(|result| result);
+            result.and(downloaded)
+        }
+        None => result,
+    };
 
-    let result = do_flash(&path, &device_path, flash_state, verify).await;
+    // A bridge known for UAS trouble is the likely culprit, say how to avoid it
+    let result = result.map_err(
+        |e| match device.usb_bridge.as_ref().and_then(UsbBridge::advice) {
+            Some(advice) if !flash_state.is_cancelled.load(Ordering::SeqCst) => {
+                format!("{}; {}", e, advice)
+            }
+            _ => e,
+        },
+    );
 
     match &result {
         Ok(_) => {
             log_info!("operations", "Flash completed successfully");
+            revoke_confirmations(&device_path);
+            log_speed_summary(Some(&device), &flash_state);
+            record_flash_speeds(Some(&device), &flash_state);
+            if let Some(serial) = serial.as_deref() {
+                record_flash_outcome(serial, true);
+                if verify {
+                    remember_verification(serial, &path, device.size);
+                }
+            }
         }
         Err(e) => {
             log_error!("operations", "Flash failed: {}", e);
+            if flash_state.is_verifying.load(Ordering::SeqCst)
+                && !flash_state.is_cancelled.load(Ordering::SeqCst)
+            {
+                report_verify_failure(
+                    &path,
+                    &device_path,
+                    Some(&device),
+                    e,
+                    &flash_state,
+                    verify_options.tail_check,
+                    app,
+                );
+            }
+        }
+    }
+
+    let audit_result = match &result {
+        Ok(_) => AuditResult::Success,
+        Err(_) if flash_state.is_cancelled.load(Ordering::SeqCst) => AuditResult::Cancelled,
+        Err(_) => AuditResult::Failed,
+    };
+    let image_sha256 = flash_state
+        .image_sha256
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    // Requested is not done: the read-back counts only if it ran and passed
+    let verified = verify && result.is_ok() && flash_state.is_verifying.load(Ordering::SeqCst);
+    events::publish(AppEvent::FlashFinished {
+        device_path: device_path.clone(),
+        success: result.is_ok(),
+        cancelled: audit_result == AuditResult::Cancelled,
+        error: result.as_ref().err().cloned(),
+    });
+    record_audit_entry(
+        &AuditEntry::flash(
+            &device_path,
+            Some(&device),
+            &path,
+            image_sha256,
+            verified,
+            audit_result,
+            result.as_ref().err().cloned(),
+        )
+        .for_operation(operation.id()),
+    );
+
+    job.finish(&result);
+    result
+}
+
+/// Write part of a file at a byte offset of a device, e.g. a bootloader
+///
+/// Only that range of the device changes, so u-boot can be updated from an
+/// extracted blob without reflashing the card. `length` defaults to the whole
+/// file. Goes through the same write warning, target checks and
+/// authorization as `flash_image`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn flash_range(
+    input_path: String,
+    device_path: String,
+    device_offset: u64,
+    length: Option<u64>,
+    verify: bool,
+    confirmation_token: Option<String>,
+    state: State<'_, AppState>,
+    window: Window,
+    app: AppHandle,
+) -> Result<(), String> {
+    if !write_warning_accepted(&app) {
+        return Err("The warning that writing erases the device has not been accepted".to_string());
+    }
+
+    let operation = begin_operation("flash_range");
+    apply_pending_note(&state, window.label(), operation.id());
+    log_info!(
+        "operations",
+        "Starting range write: {} -> {} at byte {} (length: {:?}, verify: {})",
+        input_path,
+        device_path,
+        device_offset,
+        length,
+        verify
+    );
+
+    let path = PathBuf::from(&input_path);
+    let flash_state = state.operations(window.label()).flash_state.clone();
+    let _claim = state.claim_device(window.label(), operation.id(), &device_path)?;
+    let _system_lock = lock_device(&device_path)?;
+
+    let device = get_block_devices()
+        .and_then(|devices| find_flash_target(devices, &device_path))
+        .inspect_err(|e| log_warn!("operations", "Refusing to write range: {}", e))?;
+    check_dangerous_target(confirmation_token.as_deref(), &device)?;
+    let range = RangeWrite::from_file(&path, device_offset, length, device.size)?;
+
+    if let Some(ref serial) = device.serial {
+        invalidate_verification(serial, "range written");
+    }
+
+    let _sampler = ThroughputSampler::start(
+        operation.id(),
+        window.label(),
+        SampleSource::Flash(flash_state.clone()),
+    );
+    let _progress = ProgressEmitter::flash(&app, window.label(), flash_state.clone());
+    let watchdog = Watchdog::start(flash_state.clone(), &device_path);
+    let result = {
+        let (device_path, flash_state) = (device_path.clone(), flash_state.clone());
+        run_blocking_future(async move {
+            do_flash_range(&device_path, &range, flash_state, verify).await
+        })
+        .await
+    };
+    drop(watchdog);
+
+    let audit_result = match &result {
+        Ok(_) => {
+            log_info!("operations", "Range write completed successfully");
+            revoke_confirmations(&device_path);
+            AuditResult::Success
+        }
+        Err(e) => {
+            log_error!("operations", "Range write failed: {}", e);
+            if flash_state.is_cancelled.load(Ordering::SeqCst) {
+                AuditResult::Cancelled
+            } else {
+                AuditResult::Failed
+            }
+        }
+    };
+    let verified = verify && result.is_ok() && flash_state.is_verifying.load(Ordering::SeqCst);
+    record_audit_entry(
+        &AuditEntry::flash(
+            &device_path,
+            Some(&device),
+            &path,
+            None,
+            verified,
+            audit_result,
+            result.as_ref().err().cloned(),
+        )
+        .for_operation(operation.id()),
+    );
+
+    result
+}
+
+/// Erase a device, leaving one empty partition formatted `filesystem`
+///
+/// `filesystem` is "fat32" or "exfat"; without it, devices above 32 GiB get
+/// exFAT like SDXC cards. Goes through the same write warning, target checks
+/// and authorization as `flash_image`. Returns the filesystem name.
+#[tauri::command]
+pub async fn erase_device(
+    device_path: String,
+    filesystem: Option<String>,
+    confirmation_token: Option<String>,
+    state: State<'_, AppState>,
+    window: Window,
+    app: AppHandle,
+) -> Result<String, String> {
+    if !write_warning_accepted(&app) {
+        return Err("The warning that writing erases the device has not been accepted".to_string());
+    }
+
+    let operation = begin_operation("erase");
+    apply_pending_note(&state, window.label(), operation.id());
+    log_info!(
+        "operations",
+        "Starting erase: {} (filesystem: {:?})",
+        device_path,
+        filesystem
+    );
+
+    let flash_state = state.operations(window.label()).flash_state.clone();
+    let _claim = state.claim_device(window.label(), operation.id(), &device_path)?;
+    let _system_lock = lock_device(&device_path)?;
+
+    let device = get_block_devices()
+        .and_then(|devices| find_flash_target(devices, &device_path))
+        .inspect_err(|e| log_warn!("operations", "Refusing to erase: {}", e))?;
+    check_dangerous_target(confirmation_token.as_deref(), &device)?;
+    let filesystem = EraseFilesystem::choose(filesystem.as_deref(), device.size)?;
+    let layout = EraseLayout::new(device.size, filesystem, volume_serial())?;
+
+    if let Some(ref serial) = device.serial {
+        invalidate_verification(serial, "erased");
+    }
+
+    let _sampler = ThroughputSampler::start(
+        operation.id(),
+        window.label(),
+        SampleSource::Flash(flash_state.clone()),
+    );
+    let _progress = ProgressEmitter::flash(&app, window.label(), flash_state.clone());
+    let watchdog = Watchdog::start(flash_state.clone(), &device_path);
+    let result = {
+        let (device_path, flash_state) = (device_path.clone(), flash_state.clone());
+        run_blocking_future(async move { do_erase(&device_path, &layout, flash_state).await }).await
+    };
+    drop(watchdog);
+
+    let audit_result = match &result {
+        Ok(_) => {
+            log_info!("operations", "Erase completed successfully");
+            revoke_confirmations(&device_path);
+            AuditResult::Success
+        }
+        Err(e) => {
+            log_error!("operations", "Erase failed: {}", e);
+            if flash_state.is_cancelled.load(Ordering::SeqCst) {
+                AuditResult::Cancelled
+            } else {
+                AuditResult::Failed
+            }
         }
+    };
+    record_audit_entry(
+        &AuditEntry::erase(
+            &device_path,
+            Some(&device),
+            filesystem.name(),
+            audit_result,
+            result.as_ref().err().cloned(),
+        )
+        .for_operation(operation.id()),
+    );
+
+    result.map(|()| filesystem.name().to_string())
+}
+
+/// Check a device for fake capacity (counterfeit cards)
+///
+/// Writes markers across the device and reads them back; the sampled blocks
+/// are restored afterwards. Goes through the same write warning, target
+/// checks and authorization as `flash_image`, as a failed restore loses data.
+#[tauri::command]
+pub async fn check_device_capacity(
+    device_path: String,
+    confirmation_token: Option<String>,
+    state: State<'_, AppState>,
+    window: Window,
+    app: AppHandle,
+) -> Result<CapacityReport, String> {
+    if !write_warning_accepted(&app) {
+        return Err("The warning that writing erases the device has not been accepted".to_string());
     }
 
+    let operation = begin_operation("capacity_check");
+    log_info!("operations", "Starting capacity check: {}", device_path);
+
+    let flash_state = state.operations(window.label()).flash_state.clone();
+    let _claim = state.claim_device(window.label(), operation.id(), &device_path)?;
+    let _system_lock = lock_device(&device_path)?;
+
+    let device = get_block_devices()
+        .and_then(|devices| find_flash_target(devices, &device_path))
+        .inspect_err(|e| log_warn!("operations", "Refusing to check capacity: {}", e))?;
+    check_dangerous_target(confirmation_token.as_deref(), &device)?;
+
+    let _progress = ProgressEmitter::flash(&app, window.label(), flash_state.clone());
+    let watchdog = Watchdog::start(flash_state.clone(), &device_path);
+    let result = {
+        let (device_path, size, flash_state) =
+            (device_path.clone(), device.size, flash_state.clone());
+        run_blocking_future(async move { do_check_capacity(&device_path, size, flash_state).await })
+            .await
+    };
+    drop(watchdog);
+
+    match &result {
+        Ok(report) if report.genuine => {
+            log_info!("operations", "Capacity check passed for {}", device_path)
+        }
+        Ok(report) => log_warn!(
+            "operations",
+            "{} looks counterfeit: {} of {} markers lost",
+            device_path,
+            report.bad_samples,
+            report.samples
+        ),
+        Err(e) => log_error!("operations", "Capacity check failed: {}", e),
+    }
+    if result.is_ok() {
+        revoke_confirmations(&device_path);
+    }
     result
 }
 
+/// Outcome of a successful `verify_device` call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceVerification {
+    /// The result was taken from a stored verification instead of reading the device
+    pub cached: bool,
+    /// RFC 3339 timestamp of the verification
+    pub verified_at: String,
+}
+
+/// Verify a device against an image without writing to it
+///
+/// If the same card (by serial) was verified against the same image earlier in
+/// this session and nothing wrote to it since, the stored result is returned
+/// without reading the device. Fails with the mismatch details otherwise.
+#[tauri::command]
+pub async fn verify_device(
+    image_path: String,
+    device_path: String,
+    state: State<'_, AppState>,
+    window: Window,
+    app: AppHandle,
+) -> Result<DeviceVerification, String> {
+    let operation = begin_operation("verify");
+    log_info!(
+        "operations",
+        "Verifying device: {} against {}",
+        device_path,
+        image_path
+    );
+
+    let path = PathBuf::from(&image_path);
+    let flash_state = state.operations(window.label()).flash_state.clone();
+    let mut job = state.start_job(
+        window.label(),
+        operation.id(),
+        "verify",
+        &device_path,
+        JobControl::Flash(flash_state.clone()),
+    )?;
+    let _claim = state.claim_device(window.label(), operation.id(), &device_path)?;
+    let _system_lock = lock_device(&device_path)?;
+
+    let device = get_block_devices()
+        .ok()
+        .and_then(|devices| devices.into_iter().find(|d| d.path == device_path));
+    let serial = device.as_ref().and_then(|d| d.serial.clone());
+
+    if let (Some(serial), Some(device)) = (serial.as_deref(), device.as_ref()) {
+        let candidate = VerifiedDevice::new(&path, device.size)?;
+        if let Some(entry) = load_history().cached_verification(serial, &candidate) {
+            log_info!(
+                "operations",
+                "Device {} (serial {}) already verified at {}, skipping read-back",
+                device_path,
+                serial,
+                entry.verified_at
+            );
+            job.finish(&Ok::<(), String>(()));
+            events::publish(AppEvent::VerifyFinished {
+                device_path: device_path.clone(),
+                success: true,
+                cached: true,
+            });
+            return Ok(DeviceVerification {
+                cached: true,
+                verified_at: entry.verified_at.clone(),
+            });
+        }
+    }
+
+    let verify_options = verify_options_for(&device_path, device.as_ref(), &app);
+    let _sampler = ThroughputSampler::start(
+        operation.id(),
+        window.label(),
+        SampleSource::Flash(flash_state.clone()),
+    );
+    let _progress = ProgressEmitter::flash(&app, window.label(), flash_state.clone());
+    let watchdog = Watchdog::start(flash_state.clone(), &device_path);
+    let result = {
+        let (path, device_path, flash_state) =
+            (path.clone(), device_path.clone(), flash_state.clone());
+        run_blocking_future(async move {
+            do_verify(&path, &device_path, flash_state, verify_options).await
+        })
+        .await
+    };
+    drop(watchdog);
+    job.finish(&result);
+
+    events::publish(AppEvent::VerifyFinished {
+        device_path: device_path.clone(),
+        success: result.is_ok(),
+        cached: false,
+    });
+
+    match result {
+        Ok(()) => {
+            log_info!("operations", "Device verification passed");
+            log_speed_summary(device.as_ref(), &flash_state);
+            if let (Some(serial), Some(device)) = (serial.as_deref(), device.as_ref()) {
+                remember_verification(serial, &path, device.size);
+            }
+            Ok(DeviceVerification {
+                cached: false,
+                verified_at: chrono::Utc::now().to_rfc3339(),
+            })
+        }
+        Err(e) => {
+            log_error!("operations", "Device verification failed: {}", e);
+            if let Some(ref serial) = serial {
+                invalidate_verification(serial, "verification failed");
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Unmount and eject a device so the card can be pulled right away
+#[tauri::command]
+pub async fn eject_device(
+    device_path: String,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<(), String> {
+    let operation = begin_operation("eject");
+    log_info!("operations", "Ejecting device: {}", device_path);
+    let _claim = state.claim_device(window.label(), operation.id(), &device_path)?;
+
+    let result = {
+        let device_path = device_path.clone();
+        run_blocking_future(async move { do_eject(&device_path).await }).await
+    };
+    match &result {
+        Ok(()) => log_info!("operations", "Device {} ejected", device_path),
+        Err(e) => log_error!("operations", "Ejecting {} failed: {}", device_path, e),
+    }
+    result
+}
+
+/// Read-back strategy for the target device and the verify mode setting
+fn verify_options_for(
+    device_path: &str,
+    device: Option<&BlockDevice>,
+    app: &AppHandle,
+) -> VerifyOptions {
+    let options = VerifyOptions {
+        mode: settings::current(app).verify_mode,
+        ..device
+            .map(|d| VerifyOptions::for_device(device_path, &d.device_class()))
+            .unwrap_or_default()
+    };
+    log_debug!(
+        "operations",
+        "Verifying {} with {} stream(s), {:?} mode",
+        device_path,
+        options.streams,
+        options.mode
+    );
+    options
+}
+
+/// Region after the image to zero and check, in the "full" erase mode
+///
+/// Verification covers exactly the image, so on a larger device the
+/// remaining space is otherwise never looked at.
+fn tail_check_for(
+    image_path: &std::path::Path,
+    device: Option<&BlockDevice>,
+    app: &AppHandle,
+) -> Option<TailCheck> {
+    let device = device?;
+    let image_size = image_size(image_path)
+        .inspect_err(|e| log_info!("operations", "No tail check: {}", e))
+        .ok()?;
+    if device.size <= image_size {
+        return None;
+    }
+
+    let tail_check = if settings::current(app).erase_mode == EraseMode::Full {
+        TailCheck::for_device(image_size, device.size)
+    } else {
+        None
+    };
+    log_info!(
+        "operations",
+        "Device is {} MB larger than the image; {}",
+        (device.size - image_size) / MB,
+        match tail_check {
+            Some(tail) => format!(
+                "{} MB after the image will be zeroed and checked",
+                tail.len / MB
+            ),
+            None => "space after the image is not verified".to_string(),
+        }
+    );
+    tail_check
+}
+
+/// Check a requested secondary image against the main image and the device
+fn secondary_image_for(
+    image_path: &std::path::Path,
+    device: &BlockDevice,
+    request: SecondaryImageRequest,
+) -> Result<SecondaryImage, String> {
+    let primary_size = image_size(image_path)?;
+    let secondary = SecondaryImage::new(
+        std::path::Path::new(&request.path),
+        request.offset,
+        primary_size,
+        device.size,
+    )?;
+    log_info!(
+        "operations",
+        "Secondary image {} ({} MB) will be written at {} MB; space after the main image is not checked",
+        secondary.path.display(),
+        secondary.size / MB,
+        secondary.offset / MB
+    );
+    Ok(secondary)
+}
+
+/// Store a successful verification so `verify_device` can reuse it
+fn remember_verification(serial: &str, image_path: &std::path::Path, device_size: u64) {
+    match VerifiedDevice::new(image_path, device_size) {
+        Ok(entry) => record_verified_device(serial, entry),
+        Err(e) => log_warn!("operations", "Not remembering verification: {}", e),
+    }
+}
+
+/// Get flash success/failure statistics for a device serial
+///
+/// `likely_faulty` is set after repeated verification failures on the same
+/// serial, which points at the card or reader rather than the image.
+#[tauri::command]
+pub fn get_device_reliability(serial: String) -> DeviceReliability {
+    load_history().device_reliability(&serial)
+}
+
+/// Download statistics per mirror host, healthiest first
+///
+/// Hosts marked `flaky` failed repeatedly or more often than not.
+#[tauri::command]
+pub fn get_mirror_stats() -> Vec<MirrorStats> {
+    load_history().mirror_stats()
+}
+
+/// Probe the latency of every known download mirror
+///
+/// The results also order the mirrors tried by later downloads.
+#[tauri::command]
+pub async fn measure_mirrors() -> Vec<Mirror> {
+    crate::mirrors::measure_mirrors().await
+}
+
+/// Record a verification failure in the history and optionally on the card
+fn report_verify_failure(
+    image_path: &std::path::Path,
+    device_path: &str,
+    device: Option<&BlockDevice>,
+    error: &str,
+    flash_state: &FlashState,
+    tail_check: Option<TailCheck>,
+    app: &AppHandle,
+) {
+    let serial = device.and_then(|d| d.serial.clone());
+    if let Some(ref serial) = serial {
+        let reliability = record_flash_outcome(serial, false);
+        if reliability.likely_faulty {
+            log_warn!(
+                "operations",
+                "Device {} (serial {}) failed verification {} times in a row, the card or reader is likely at fault",
+                device_path,
+                serial,
+                reliability.consecutive_failures
+            );
+        }
+    }
+
+    let mismatch = flash_state
+        .verify_mismatch
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+
+    let record = FlashFailureRecord {
+        recorded_at: chrono::Utc::now().to_rfc3339(),
+        image: image_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        device_path: device_path.to_string(),
+        device_serial: serial,
+        device_model: device.map(|d| d.model.clone()),
+        device_class: device.map(|d| d.device_class()),
+        device_size: device.map(|d| d.size),
+        image_size: Some(flash_state.total_bytes.load(Ordering::SeqCst)),
+        tail_checked_bytes: tail_check.map(|tail| tail.len),
+        error: error.to_string(),
+        mismatch,
+        write_speed: flash_state.write_speed(),
+        read_speed: flash_state.read_speed(),
+        usb_bridge: device.and_then(|d| d.usb_bridge.clone()),
+    };
+
+    if settings::current(app).write_failure_report {
+        let previous_failures = load_history()
+            .flash_failures
+            .iter()
+            .filter(|f| match record.device_serial {
+                Some(_) => f.device_serial == record.device_serial,
+                None => f.device_model.is_some() && f.device_model == record.device_model,
+            })
+            .count();
+        let report = format_failure_report(&record, previous_failures);
+        if let Err(e) = write_boot_partition_report(device_path, &report) {
+            log_warn!(
+                "operations",
+                "Could not write failure report to {}: {}",
+                device_path,
+                e
+            );
+        }
+    }
+
+    record_flash_failure(record);
+}
+
+/// Format a human-readable flash failure report
+fn format_failure_report(record: &FlashFailureRecord, previous_failures: usize) -> String {
+    let mut report = format!(
+        "{} flash failure report\n\n\
+         Time: {}\n\
+         App version: {}\n\
+         Image: {}\n\
+         Device: {} ({}, {})\n\
+         Error: {}\n",
+        config::app::DISPLAY_NAME,
+        record.recorded_at,
+        env!("CARGO_PKG_VERSION"),
+        record.image,
+        record.device_path,
+        record.device_model.as_deref().unwrap_or("unknown model"),
+        record.device_class.as_deref().unwrap_or("unknown class"),
+        record.error,
+    );
+
+    if let Some(ref bridge) = record.usb_bridge {
+        report.push_str(&format!(
+            "USB bridge: {} ({}, {}, {})\n",
+            bridge.id(),
+            bridge.chipset.as_deref().unwrap_or("no known quirks"),
+            match bridge.uas {
+                Some(true) => "UAS",
+                Some(false) => "mass storage driver",
+                None => "driver unknown",
+            },
+            match bridge.speed_mbps {
+                Some(speed) => format!("{} Mbps", speed),
+                None => "speed unknown".to_string(),
+            }
+        ));
+        for advice in [bridge.advice(), bridge.link_warning()]
+            .into_iter()
+            .flatten()
+        {
+            report.push_str(&format!("Advice: {}\n", advice));
+        }
+    }
+
+    if let Some(ref m) = record.mismatch {
+        report.push_str(&format!(
+            "Mismatch offset: {} (0x{:x})\n\
+             Expected byte: {}\n\
+             Actual byte: {}\n\
+             Differing bytes in chunk: {} of {}\n",
+            m.offset,
+            m.offset,
+            m.expected
+                .map(|b| format!("0x{:02x}", b))
+                .unwrap_or_else(|| "none".to_string()),
+            m.actual
+                .map(|b| format!("0x{:02x}", b))
+                .unwrap_or_else(|| "none (short read)".to_string()),
+            m.differing_bytes,
+            m.chunk_size,
+        ));
+        if let Some(ref region) = m.region {
+            report.push_str(&format!("Region: {}\n", region));
+        }
+        for (label, hex) in [("Expected", &m.expected_hex), ("Actual", &m.actual_hex)] {
+            if !hex.is_empty() {
+                report.push_str(&format!("{} data:\n", label));
+                for line in hexdump_lines(m.context_offset, hex) {
+                    report.push_str(&format!("  {}\n", line));
+                }
+            }
+        }
+    }
+
+    if let (Some(device_size), Some(image_size)) = (record.device_size, record.image_size) {
+        if device_size > image_size {
+            let note = match record.tail_checked_bytes {
+                Some(bytes) => format!(
+                    "the first {} MB after the image were zeroed and checked",
+                    bytes / MB
+                ),
+                None => "space after the image was not verified".to_string(),
+            };
+            report.push_str(&format!(
+                "Device is {} MB larger than the image; {}\n",
+                (device_size - image_size) / MB,
+                note
+            ));
+        }
+    }
+
+    report.push_str(&format!(
+        "Write speed: {}\n\
+         Read-back speed: {}\n",
+        format_speed(record.write_speed),
+        format_speed(record.read_speed),
+    ));
+
+    report.push_str(&format!(
+        "Device serial: {}\n\
+         Previous failures with this device: {}\n",
+        record.device_serial.as_deref().unwrap_or("unknown"),
+        previous_failures
+    ));
+    report
+}
+
+/// Expected duration of each operation stage, in seconds
+///
+/// A stage is None when there is no history to estimate it from.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OperationEstimate {
+    pub download_secs: Option<f64>,
+    pub decompress_secs: Option<f64>,
+    pub write_secs: Option<f64>,
+    pub verify_secs: Option<f64>,
+    /// Image is already cached, so the download is skipped
+    pub cached: bool,
+    /// Decompressed image size (exact if cached, otherwise estimated)
+    pub image_bytes: u64,
+}
+
+/// Estimate how long downloading and flashing an image to a device will take
+///
+/// Uses the average download, decompression, write and verify speeds
+/// measured by previous operations, preferring write/verify samples from
+/// the same device class.
+#[tauri::command]
+pub async fn estimate_operation(
+    image: ImageInfo,
+    device: BlockDevice,
+) -> Result<OperationEstimate, String> {
+    let history = load_history();
+    let device_class = device.device_class();
+
+    let filename = image.file_url.rsplit('/').next().unwrap_or(&image.file_url);
+    // Cached as downloaded, or decompressed by an older version
+    let cache_dir = get_images_cache_dir();
+    let cached_path = [filename, filename.trim_end_matches(".xz")]
+        .into_iter()
+        .map(|name| cache_dir.join(name))
+        .find(|path| path.is_file());
+    let cached_size = cached_path
+        .as_deref()
+        .and_then(|path| image_size(path).ok());
+    let is_compressed = filename.ends_with(".xz");
+
+    let image_bytes = cached_size.unwrap_or_else(|| {
+        if is_compressed {
+            let ratio = history
+                .compression_ratio()
+                .unwrap_or(config::history::DEFAULT_COMPRESSION_RATIO);
+            (image.file_size as f64 * ratio) as u64
+        } else {
+            image.file_size
+        }
+    });
+
+    let duration = |bytes: u64, stage: OperationStage, class: Option<&str>| {
+        history
+            .average_speed(stage, class)
+            .filter(|&speed| speed > 0.0)
+            .map(|speed| bytes as f64 / speed)
+    };
+
+    let estimate = OperationEstimate {
+        download_secs: match cached_path {
+            Some(_) => Some(0.0),
+            None => duration(image.file_size, OperationStage::Download, None),
+        },
+        // Archives are decompressed while writing
+        decompress_secs: Some(0.0),
+        write_secs: duration(image_bytes, OperationStage::Write, Some(&device_class)),
+        verify_secs: duration(image_bytes, OperationStage::Verify, Some(&device_class)),
+        cached: cached_path.is_some(),
+        image_bytes,
+    };
+
+    log_debug!(
+        "operations",
+        "Estimate for {} on {} ({}): {:?}",
+        filename,
+        device.path,
+        device_class,
+        estimate
+    );
+
+    Ok(estimate)
+}
+
+/// Minimum read-back speed a healthy device of the given class should reach
+fn expected_min_read_speed(device_class: &str) -> u64 {
+    match device_class {
+        "sd" | "mmc" => config::flash::MIN_READ_SPEED_SD,
+        "usb" => config::flash::MIN_READ_SPEED_USB,
+        "sata" | "nvme" => config::flash::MIN_READ_SPEED_FAST,
+        _ => config::flash::MIN_READ_SPEED_DEFAULT,
+    }
+}
+
+/// Log write and read-back throughput and flag drastically slow reads
+fn log_speed_summary(device: Option<&BlockDevice>, flash_state: &FlashState) {
+    let write_speed = flash_state.write_speed();
+    let read_speed = flash_state.read_speed();
+    log_info!(
+        "operations",
+        "Throughput - write: {}, read-back: {}",
+        format_speed(write_speed),
+        format_speed(read_speed)
+    );
+
+    let (Some(device), Some(read_speed)) = (device, read_speed) else {
+        return;
+    };
+    let device_class = device.device_class();
+    let expected = expected_min_read_speed(&device_class) as f64;
+    if read_speed < expected * config::flash::SLOW_READ_FACTOR {
+        // A USB 2.0 link explains the speed as well as a failing card would
+        if let Some(warning) = device.usb_bridge.as_ref().and_then(UsbBridge::link_warning) {
+            log_warn!(
+                "operations",
+                "Read-back speed {} is low: {}",
+                format_speed(Some(read_speed)),
+                warning
+            );
+            return;
+        }
+        flash_state.is_read_slow.store(true, Ordering::SeqCst);
+        log_warn!(
+            "operations",
+            "Read-back speed {} is far below the expected {} for a {} device, the card may be failing or counterfeit",
+            format_speed(Some(read_speed)),
+            format_speed(Some(expected)),
+            device_class
+        );
+    }
+}
+
+fn format_speed(bytes_per_sec: Option<f64>) -> String {
+    match bytes_per_sec {
+        Some(speed) => format!("{:.1} MB/s", speed / MB as f64),
+        None => "n/a".to_string(),
+    }
+}
+
+/// Record write and verify throughput of a completed flash in the history
+fn record_flash_speeds(device: Option<&BlockDevice>, flash_state: &FlashState) {
+    let device_class = device.map(|d| d.device_class());
+    let total = flash_state.total_bytes.load(Ordering::SeqCst);
+
+    for (stage, elapsed_ms) in [
+        (
+            OperationStage::Write,
+            flash_state.write_elapsed_ms.load(Ordering::SeqCst),
+        ),
+        (
+            OperationStage::Verify,
+            flash_state.verify_elapsed_ms.load(Ordering::SeqCst),
+        ),
+    ] {
+        record_speed(
+            stage,
+            device_class.as_deref(),
+            total,
+            None,
+            Duration::from_millis(elapsed_ms),
+        );
+    }
+}
+
 /// Force delete a cached image regardless of cache settings
 ///
 /// Used when an image repeatedly fails to flash, suggesting the cached
@@ -147,44 +1434,69 @@ pub async fn force_delete_cached_image(image_path: String) -> Result<(), String>
 
     let path = PathBuf::from(&image_path);
 
-    // Safety check: only delete files in our cache directory
-    // Use canonicalize() to resolve symlinks and prevent path traversal attacks
-    let cache_dir = get_cache_dir(config::app::NAME)
-        .canonicalize()
-        .map_err(|e| format!("Failed to resolve cache directory: {}", e))?;
-
-    let canonical_path = path
-        .canonicalize()
-        .map_err(|e| format!("Failed to resolve image path: {}", e))?;
-
-    if !canonical_path.starts_with(&cache_dir) {
+    // Safety check: only regular files directly in the images cache, never symlinks
+    let canonical_path = deletable_cache_file(&path, &get_images_cache_dir()).map_err(|e| {
         log_error!(
             "operations",
-            "Attempted to force delete file outside cache: {} (resolved: {})",
+            "Refusing to force delete {}: {}",
             image_path,
-            canonical_path.display()
+            e
         );
-        return Err("Cannot delete files outside cache directory".to_string());
-    }
+        "Cannot delete files outside the image cache".to_string()
+    })?;
 
-    if path.exists() {
-        std::fs::remove_file(&path).map_err(|e| {
-            log_error!(
-                "operations",
-                "Failed to force delete image {}: {}",
-                image_path,
-                e
-            );
-            format!("Failed to delete image: {}", e)
-        })?;
-        log_info!("operations", "Force deleted cached image: {}", image_path);
-    } else {
-        log_debug!("operations", "Image already deleted: {}", image_path);
+    match canonical_path {
+        Some(canonical_path) => {
+            std::fs::remove_file(&canonical_path).map_err(|e| {
+                log_error!(
+                    "operations",
+                    "Failed to force delete image {}: {}",
+                    image_path,
+                    e
+                );
+                format!("Failed to delete image: {}", e)
+            })?;
+            log_info!("operations", "Force deleted cached image: {}", image_path);
+        }
+        None => log_debug!("operations", "Image already deleted: {}", image_path),
     }
 
     Ok(())
 }
 
+/// Save a copy of a cached image to a file chosen by the user
+///
+/// Returns the saved path, or None if the user cancelled.
+#[tauri::command]
+pub async fn export_cached_image(
+    image_path: String,
+    window: Window,
+) -> Result<Option<String>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let source = PathBuf::from(&image_path);
+    let file_name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| format!("Invalid image path: {}", image_path))?;
+    let destination = window
+        .dialog()
+        .file()
+        .set_file_name(file_name)
+        .set_title("Save Image")
+        .blocking_save_file();
+    let Some(destination) = destination else {
+        return Ok(None);
+    };
+    let destination = destination
+        .into_path()
+        .map_err(|_| "Invalid path: not a valid file path".to_string())?;
+
+    let saved = destination.to_string_lossy().into_owned();
+    run_blocking(move || export_image(&source, &destination)).await?;
+    Ok(Some(saved))
+}
+
 /// Delete a downloaded image file
 ///
 /// If image caching is enabled, the file is kept for future use.
@@ -193,65 +1505,35 @@ pub async fn force_delete_cached_image(image_path: String) -> Result<(), String>
 pub async fn delete_downloaded_image(image_path: String, app: AppHandle) -> Result<(), String> {
     log_info!("operations", "Delete request for image: {}", image_path);
 
-    // Check if cache is enabled
-    let cache_enabled = match app.store("settings.json") {
-        Ok(store) => store
-            .get("cache_enabled")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(true),
-        Err(_) => true, // Default to cache enabled
-    };
-
-    if cache_enabled {
+    if settings::current(&app).cache_enabled {
         log_info!("operations", "Cache enabled, keeping image: {}", image_path);
         return Ok(());
     }
 
     let path = PathBuf::from(&image_path);
 
-    // Safety check: only delete files in our cache directory
-    // Use canonicalize() to resolve symlinks and prevent path traversal attacks
-    let cache_dir = match get_cache_dir(config::app::NAME).canonicalize() {
-        Ok(dir) => dir,
-        Err(e) => {
+    // Safety check: only regular files directly in the images cache, never symlinks
+    let canonical_path = match deletable_cache_file(&path, &get_images_cache_dir()) {
+        Ok(Some(canonical_path)) => canonical_path,
+        Ok(None) => {
             log_debug!(
                 "operations",
-                "Cache directory doesn't exist yet, skipping delete: {}",
-                e
+                "Image doesn't exist, skipping delete: {}",
+                image_path
             );
             return Ok(());
         }
-    };
-
-    let canonical_path = match path.canonicalize() {
-        Ok(p) => p,
         Err(e) => {
-            log_debug!(
-                "operations",
-                "Image path doesn't exist or can't be resolved, skipping delete: {}",
-                e
-            );
-            return Ok(());
+            log_error!("operations", "Refusing to delete {}: {}", image_path, e);
+            return Err("Cannot delete files outside the image cache".to_string());
         }
     };
 
-    if !canonical_path.starts_with(&cache_dir) {
-        log_error!(
-            "operations",
-            "Attempted to delete file outside cache: {} (resolved: {})",
-            image_path,
-            canonical_path.display()
-        );
-        return Err("Cannot delete files outside cache directory".to_string());
-    }
-
-    if canonical_path.exists() {
-        std::fs::remove_file(&canonical_path).map_err(|e| {
-            log_error!("operations", "Failed to delete image {}: {}", image_path, e);
-            format!("Failed to delete image: {}", e)
-        })?;
-        log_info!("operations", "Deleted image: {}", image_path);
-    }
+    std::fs::remove_file(&canonical_path).map_err(|e| {
+        log_error!("operations", "Failed to delete image {}: {}", image_path, e);
+        format!("Failed to delete image: {}", e)
+    })?;
+    log_info!("operations", "Deleted image: {}", image_path);
 
     Ok(())
 }
@@ -259,11 +1541,14 @@ pub async fn delete_downloaded_image(image_path: String, app: AppHandle) -> Resu
 /// Continue a download that failed due to SHA unavailable
 /// Uses the already downloaded file without re-downloading
 #[tauri::command]
-pub async fn continue_download_without_sha(state: State<'_, AppState>) -> Result<String, String> {
+pub async fn continue_download_without_sha(
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<String, String> {
     log_info!("operations", "Continuing download without SHA verification");
 
     let download_dir = get_cache_dir(config::app::NAME).join("images");
-    let download_state = state.download_state.clone();
+    let download_state = state.operations(window.label()).download_state.clone();
 
     let result = crate::download::continue_without_sha(download_state, &download_dir).await;
 
@@ -282,8 +1567,13 @@ pub async fn continue_download_without_sha(state: State<'_, AppState>) -> Result
 /// Clean up a failed download (delete temp file)
 /// Called when user cancels after SHA unavailable error
 #[tauri::command]
-pub async fn cleanup_failed_download(state: State<'_, AppState>) -> Result<(), String> {
+pub async fn cleanup_failed_download(
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<(), String> {
     log_info!("operations", "Cleaning up failed download");
-    crate::download::cleanup_pending_download(state.download_state.clone()).await;
+    let download_dir = get_cache_dir(config::app::NAME).join("images");
+    let download_state = state.operations(window.label()).download_state.clone();
+    crate::download::cleanup_pending_download(download_state, &download_dir).await;
     Ok(())
 }
@@ -0,0 +1,123 @@
+//! Image partition commands
+//!
+//! Inspect an image, list its partitions and extract one of them to a raw
+//! file, e.g. the rootfs of a cached image for inspection. Progress goes out as
+//! flash progress, and `cancel_operation` stops an extraction. Partitions of
+//! a flashed device can be mounted and opened in the file manager.
+
+use std::path::PathBuf;
+
+use tauri::{AppHandle, State, Window};
+
+use crate::devices::get_block_devices;
+use crate::flash::{
+    extract_partition as do_extract, image_partitions, inspect_image as do_inspect,
+    mount_device_partition, ImageDetails, Partition,
+};
+use crate::logging::begin_operation;
+use crate::utils::run_blocking;
+use crate::{log_error, log_info};
+
+use super::progress::ProgressEmitter;
+use super::state::{AppState, JobControl};
+use super::system::open_with_system;
+
+/// Partition map, filesystems, version and sizes of an image
+#[tauri::command]
+pub async fn inspect_image(path: String) -> Result<ImageDetails, String> {
+    run_blocking(move || do_inspect(&PathBuf::from(path))).await
+}
+
+/// Partitions found in the image's partition table
+#[tauri::command]
+pub async fn list_image_partitions(image_path: String) -> Result<Vec<Partition>, String> {
+    run_blocking(move || image_partitions(&PathBuf::from(image_path))).await
+}
+
+/// Extract partition `index` (1-based, as listed) of an image to `dest`
+///
+/// Returns the size of the extracted partition.
+#[tauri::command]
+pub async fn extract_partition(
+    image_path: String,
+    index: usize,
+    dest: String,
+    state: State<'_, AppState>,
+    window: Window,
+    app: AppHandle,
+) -> Result<u64, String> {
+    let (image, output) = (PathBuf::from(&image_path), PathBuf::from(&dest));
+    if output == image {
+        return Err("The partition can't be extracted over its own image".to_string());
+    }
+
+    let operation = begin_operation("extract");
+    log_info!(
+        "partitions",
+        "Extracting partition {} of {} to {}",
+        index,
+        image_path,
+        dest
+    );
+
+    // Progress goes through the window's flash state, so a flash of the
+    // window keeps the extraction from starting and the other way round
+    let flash_state = state.operations(window.label()).flash_state.clone();
+    let mut job = state.start_job(
+        window.label(),
+        operation.id(),
+        "extract",
+        &image_path,
+        JobControl::Flash(flash_state.clone()),
+    )?;
+    flash_state.reset();
+    let _progress = ProgressEmitter::flash(&app, window.label(), flash_state.clone());
+    let result = run_blocking(move || do_extract(&image, index, &output, &flash_state)).await;
+
+    if let Err(e) = &result {
+        log_error!(
+            "partitions",
+            "Extraction of partition {} failed: {}",
+            index,
+            e
+        );
+    }
+    job.finish(&result);
+    result
+}
+
+/// Mount partition `index` (1-based) of a device, e.g. the boot partition
+/// after flashing, and open it in the file manager if `reveal` is set
+///
+/// Returns the mount point. The partition is unmounted again before the
+/// next operation on the device.
+#[tauri::command]
+pub async fn mount_partition(
+    device_path: String,
+    index: usize,
+    reveal: bool,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<String, String> {
+    if !get_block_devices()?.iter().any(|d| d.path == device_path) {
+        return Err(format!("Device not found: {}", device_path));
+    }
+    state.check_device_free(window.label(), &device_path)?;
+
+    let mount_point = {
+        let device_path = device_path.clone();
+        run_blocking(move || mount_device_partition(&device_path, index)).await?
+    };
+    let mount_point = mount_point.to_string_lossy().to_string();
+    log_info!(
+        "partitions",
+        "Partition {} of {} mounted at {}",
+        index,
+        device_path,
+        mount_point
+    );
+    if reveal {
+        open_with_system(&mount_point)?;
+    }
+    Ok(mount_point)
+}
@@ -1,11 +1,29 @@
 //! Progress tracking module
 //!
-//! Handles download and flash progress reporting.
+//! Handles download and flash progress reporting. While an operation runs,
+//! a `ProgressEmitter` pushes the same structs the `get_*_progress` commands
+//! return to the owning window as `download://progress` and
+//! `flash://progress` events, so the UI can subscribe instead of polling.
+
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use tauri::{AppHandle, Emitter, State, Window};
+
+use super::state::{AppState, Job};
+use super::timeseries::{latest_operation, SourceKind};
+use crate::config;
+use crate::download::DownloadState;
+use crate::flash::FlashState;
+use crate::segmented::SegmentProgress;
+use crate::utils::MB;
+use crate::{log_info, log_warn};
 
-use super::state::AppState;
+const MODULE: &str = "progress";
 
 /// Download progress information
 #[derive(Debug, Serialize, Deserialize)]
@@ -16,6 +34,18 @@ pub struct DownloadProgress {
     pub is_decompressing: bool,
     pub progress_percent: f64,
     pub error: Option<String>,
+    /// Mirror host serving the download, once known
+    pub mirror_host: Option<String>,
+    /// ID of the window's newest download, for `get_operation_timeseries`
+    pub operation_id: Option<String>,
+    /// Progress of each range of a segmented download, empty otherwise
+    pub segments: Vec<SegmentProgress>,
+    /// Free bytes left on the destination volume while they run low
+    pub low_space_bytes: Option<u64>,
+    /// Waiting until space is freed on the destination volume
+    pub paused_for_space: bool,
+    /// Times the download was retried after a network error
+    pub retries: u64,
 }
 
 /// Flash progress information
@@ -23,90 +53,243 @@ pub struct DownloadProgress {
 pub struct FlashProgress {
     pub total_bytes: u64,
     pub written_bytes: u64,
+    /// Part of `written_bytes` confirmed by the device at the last sync
+    pub synced_bytes: u64,
     pub verified_bytes: u64,
     pub is_verifying: bool,
     pub progress_percent: f64,
     pub error: Option<String>,
+    /// Writing is paused until AC power returns
+    pub paused_for_power: bool,
+    /// Write throughput in MB/s, once writing has started
+    pub write_speed_mbps: Option<f64>,
+    /// Read-back throughput in MB/s, once verification has started
+    pub read_speed_mbps: Option<f64>,
+    /// Speed of the running phase over the last few seconds, in MB/s
+    pub current_speed_mbps: Option<f64>,
+    /// Estimated seconds until the running phase completes
+    pub eta_secs: Option<u64>,
+    /// Read-back was far slower than expected for the device class
+    pub slow_read: bool,
+    /// No progress for longer than the stall timeout; the user may wait or cancel
+    pub stalled: bool,
+    /// Seconds without progress while stalled
+    pub stalled_secs: u64,
+    /// Bytes smart write found already on the device and did not write
+    pub skipped_bytes: u64,
+    /// All-zero bytes that were skipped instead of written
+    pub zero_skipped_bytes: u64,
+    /// SHA-256 of the image read back from the device, after hash verification
+    pub device_sha256: Option<String>,
+    /// Hash verification had no checksum of the written image and compared
+    /// the device with the image file instead
+    pub hash_fallback: bool,
+    /// ID of the window's newest flash or verify, for `get_operation_timeseries`
+    pub operation_id: Option<String>,
 }
 
-/// Get current download progress
-#[tauri::command]
-pub async fn get_download_progress(state: State<'_, AppState>) -> Result<DownloadProgress, String> {
-    let ds = &state.download_state;
-
-    let total = ds.total_bytes.load(std::sync::atomic::Ordering::SeqCst);
-    let downloaded = ds
-        .downloaded_bytes
-        .load(std::sync::atomic::Ordering::SeqCst);
-    let is_verifying_sha = ds
-        .is_verifying_sha
-        .load(std::sync::atomic::Ordering::SeqCst);
-    let is_decompressing = ds
-        .is_decompressing
-        .load(std::sync::atomic::Ordering::SeqCst);
-
-    let progress = if total > 0 {
-        (downloaded as f64 / total as f64) * 100.0
+fn percent(done: u64, total: u64) -> f64 {
+    if total > 0 {
+        (done as f64 / total as f64) * 100.0
     } else {
         0.0
-    };
+    }
+}
 
-    let error = ds.error.lock().await.clone();
+/// Snapshot of a window's download state
+fn download_progress(
+    ds: &DownloadState,
+    error: Option<String>,
+    mirror_host: Option<String>,
+    owner: &str,
+) -> DownloadProgress {
+    let total = ds.total_bytes.load(Ordering::SeqCst);
+    let downloaded = ds.downloaded_bytes.load(Ordering::SeqCst);
 
-    Ok(DownloadProgress {
+    DownloadProgress {
         total_bytes: total,
         downloaded_bytes: downloaded,
-        is_verifying_sha,
-        is_decompressing,
-        progress_percent: progress,
+        is_verifying_sha: ds.is_verifying_sha.load(Ordering::SeqCst),
+        is_decompressing: ds.is_decompressing.load(Ordering::SeqCst),
+        progress_percent: percent(downloaded, total),
         error,
-    })
+        mirror_host,
+        operation_id: latest_operation(owner, SourceKind::Download),
+        segments: ds
+            .segments
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone(),
+        low_space_bytes: ds
+            .is_low_on_space
+            .load(Ordering::SeqCst)
+            .then(|| ds.available_bytes.load(Ordering::SeqCst)),
+        paused_for_space: ds.is_paused_for_space.load(Ordering::SeqCst),
+        retries: ds.retries.load(Ordering::SeqCst),
+    }
 }
 
-/// Get current flash progress
-#[tauri::command]
-pub async fn get_flash_progress(state: State<'_, AppState>) -> Result<FlashProgress, String> {
-    let fs = &state.flash_state;
-
-    let total = fs.total_bytes.load(std::sync::atomic::Ordering::SeqCst);
-    let written = fs.written_bytes.load(std::sync::atomic::Ordering::SeqCst);
-    let verified = fs.verified_bytes.load(std::sync::atomic::Ordering::SeqCst);
-    let is_verifying = fs.is_verifying.load(std::sync::atomic::Ordering::SeqCst);
-
-    let progress = if is_verifying {
-        if total > 0 {
-            (verified as f64 / total as f64) * 100.0
-        } else {
-            0.0
-        }
-    } else if total > 0 {
-        (written as f64 / total as f64) * 100.0
-    } else {
-        0.0
-    };
-
-    let error = fs.error.lock().await.clone();
+/// Snapshot of a window's flash state
+fn flash_progress(fs: &FlashState, error: Option<String>, owner: &str) -> FlashProgress {
+    let total = fs.total_bytes.load(Ordering::SeqCst);
+    let written = fs.written_bytes.load(Ordering::SeqCst);
+    let verified = fs.verified_bytes.load(Ordering::SeqCst);
+    let is_verifying = fs.is_verifying.load(Ordering::SeqCst);
+    let throughput = fs.throughput();
 
-    Ok(FlashProgress {
+    FlashProgress {
         total_bytes: total,
         written_bytes: written,
+        synced_bytes: fs.synced_bytes.load(Ordering::SeqCst),
         verified_bytes: verified,
         is_verifying,
-        progress_percent: progress,
+        progress_percent: percent(if is_verifying { verified } else { written }, total),
         error,
-    })
+        paused_for_power: fs.is_paused_for_power.load(Ordering::SeqCst),
+        write_speed_mbps: fs.write_speed().map(|s| s / MB as f64),
+        read_speed_mbps: fs.read_speed().map(|s| s / MB as f64),
+        current_speed_mbps: throughput.map(|t| t.bytes_per_sec / MB as f64),
+        eta_secs: throughput.and_then(|t| t.eta_secs),
+        slow_read: fs.is_read_slow.load(Ordering::SeqCst),
+        stalled: fs.is_stalled.load(Ordering::SeqCst),
+        stalled_secs: fs.stalled_secs.load(Ordering::SeqCst),
+        skipped_bytes: fs.skipped_bytes.load(Ordering::SeqCst),
+        zero_skipped_bytes: fs.zero_ranges().total(),
+        device_sha256: fs
+            .device_sha256
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone(),
+        hash_fallback: fs.hash_fallback.load(Ordering::SeqCst),
+        operation_id: latest_operation(owner, SourceKind::Flash),
+    }
 }
 
-/// Cancel current operation
+/// Get download progress of the calling window
 #[tauri::command]
-pub async fn cancel_operation(state: State<'_, AppState>) -> Result<(), String> {
-    state
-        .download_state
-        .is_cancelled
-        .store(true, std::sync::atomic::Ordering::SeqCst);
-    state
-        .flash_state
-        .is_cancelled
-        .store(true, std::sync::atomic::Ordering::SeqCst);
+pub async fn get_download_progress(
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<DownloadProgress, String> {
+    let operations = state.operations(window.label());
+    let ds = &operations.download_state;
+
+    let error = ds.error.lock().await.clone();
+    let mirror_host = ds.mirror_host.lock().await.clone();
+
+    Ok(download_progress(ds, error, mirror_host, window.label()))
+}
+
+/// Get flash progress of the calling window
+#[tauri::command]
+pub async fn get_flash_progress(
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<FlashProgress, String> {
+    let operations = state.operations(window.label());
+    let fs = &operations.flash_state;
+
+    let error = fs.error.lock().await.clone();
+
+    Ok(flash_progress(fs, error, window.label()))
+}
+
+/// Pushes an operation's progress to its window until dropped
+///
+/// Events go out every `config::events::PROGRESS_PUSH_INTERVAL_MS`, plus a
+/// final one with the end state when the emitter is dropped.
+pub struct ProgressEmitter {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ProgressEmitter {
+    /// Push `download://progress` for the download state of window `owner`
+    pub fn download(app: &AppHandle, owner: &str, state: Arc<DownloadState>) -> Self {
+        let (app, owner) = (app.clone(), owner.to_string());
+        Self::start(move || {
+            // Runs on the emitter thread, outside the async runtime
+            let error = state.error.blocking_lock().clone();
+            let mirror_host = state.mirror_host.blocking_lock().clone();
+            let progress = download_progress(&state, error, mirror_host, &owner);
+            emit(
+                &app,
+                &owner,
+                config::events::DOWNLOAD_PROGRESS_EVENT,
+                &progress,
+            );
+        })
+    }
+
+    /// Push `flash://progress` for the flash state of window `owner`
+    pub fn flash(app: &AppHandle, owner: &str, state: Arc<FlashState>) -> Self {
+        let (app, owner) = (app.clone(), owner.to_string());
+        Self::start(move || {
+            let error = state.error.blocking_lock().clone();
+            let progress = flash_progress(&state, error, &owner);
+            emit(
+                &app,
+                &owner,
+                config::events::FLASH_PROGRESS_EVENT,
+                &progress,
+            );
+        })
+    }
+
+    fn start(push: impl Fn() + Send + 'static) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let interval = Duration::from_millis(config::events::PROGRESS_PUSH_INTERVAL_MS);
+
+        let handle = std::thread::Builder::new()
+            .name("progress-emitter".to_string())
+            .spawn(move || {
+                // The sender is dropped when pushing should stop
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    push();
+                }
+                push();
+            })
+            .map_err(|e| log_warn!(MODULE, "Failed to start progress emitter: {}", e))
+            .ok();
+
+        Self {
+            stop: Some(stop),
+            handle,
+        }
+    }
+}
+
+impl Drop for ProgressEmitter {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn emit<T: Serialize>(app: &AppHandle, owner: &str, event: &str, payload: &T) {
+    if let Err(e) = app.emit_to(owner, event, payload) {
+        log_warn!(MODULE, "Failed to emit {}: {}", event, e);
+    }
+}
+
+/// Cancel the operations of the calling window, leaving other windows alone
+#[tauri::command]
+pub async fn cancel_operation(state: State<'_, AppState>, window: Window) -> Result<(), String> {
+    state.operations(window.label()).cancel();
     Ok(())
 }
+
+/// Running and recently finished downloads, flashes and verifications
+#[tauri::command]
+pub fn get_jobs(state: State<'_, AppState>) -> Vec<Job> {
+    state.jobs()
+}
+
+/// Cancel one job by its operation ID, from any window
+#[tauri::command]
+pub fn cancel_job(job_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    log_info!(MODULE, "Cancelling job {}", job_id);
+    state.cancel_job(&job_id)
+}
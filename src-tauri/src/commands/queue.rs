@@ -0,0 +1,343 @@
+//! Download queue commands
+//!
+//! A background worker started with the app downloads queued images into
+//! the image cache. Every queue change wakes it to start whatever may run
+//! now. Pausing or removing a running item cancels its download; a paused
+//! download keeps its partial file and continues it when resumed. A changed
+//! bandwidth limit applies to downloads started afterwards. Nothing is queued
+//! or started while the image cache is turned off.
+//!
+//! Downloads interrupted by the last exit are resumed through the queue, so
+//! they finish in the background like any queued image.
+
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+use tauri::AppHandle;
+use tokio::sync::Notify;
+
+use crate::cache::get_images_cache_dir;
+use crate::download::{
+    check_download_url, cleanup_pending_download, download_image, DownloadState,
+};
+use crate::events::{self, AppEvent};
+use crate::interrupted::{
+    discard, discard_paused, find_interrupted, list_interrupted, InterruptedDownload,
+};
+use crate::queue::{
+    load_queue, update_queue, DownloadQueue, QueueItem, QueueItemStatus, QueuePriority,
+};
+use crate::settings;
+use crate::{log_debug, log_error, log_info, log_warn};
+
+use super::settings::{
+    developer_throttles, download_retries, download_segments, insecure_urls_allowed,
+    pause_on_low_space,
+};
+
+const MODULE: &str = "queue";
+
+/// Download state of running queue items, by item ID
+static RUNNING: Lazy<Mutex<HashMap<String, Arc<DownloadState>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Wakes the worker after a queue change
+static WAKE: Lazy<Notify> = Lazy::new(Notify::new);
+
+fn running() -> std::sync::MutexGuard<'static, HashMap<String, Arc<DownloadState>>> {
+    RUNNING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Let the worker and the frontend know the queue changed
+pub(crate) fn queue_changed() {
+    WAKE.notify_one();
+    events::publish(AppEvent::QueueChanged);
+}
+
+/// Cancel the download of a running item, if any
+///
+/// With `keep_partial`, the partial file stays for the item to continue.
+fn cancel_running(id: &str, keep_partial: bool) {
+    if let Some(state) = running().get(id) {
+        state.keep_partial.store(keep_partial, Ordering::SeqCst);
+        state.is_cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Start the queue worker; downloads interrupted by the last exit are queued again
+pub fn start_queue_worker(app: AppHandle) {
+    match update_queue(|queue| Ok(queue.recover())) {
+        Ok(0) => {}
+        Ok(recovered) => log_info!(MODULE, "Re-queued {} interrupted download(s)", recovered),
+        Err(e) => log_warn!(MODULE, "Failed to recover download queue: {}", e),
+    }
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            start_ready_items(&app);
+            WAKE.notified().await;
+        }
+    });
+}
+
+/// Mark the items that may run now as downloading and spawn their downloads
+fn start_ready_items(app: &AppHandle) {
+    // Queued images go to the image cache, so they wait for it to be turned on
+    if !settings::current(app).cache_enabled {
+        log_debug!(MODULE, "Image cache is off, not starting queued downloads");
+        return;
+    }
+    let (developer_limit, _) = developer_throttles(app);
+    let allow_insecure = insecure_urls_allowed(app);
+    let segments = download_segments(app);
+    let retries = download_retries(app);
+    let pause = pause_on_low_space(app);
+    let started = update_queue(|queue| {
+        let limit = queue.per_download_limit(developer_limit);
+        let mut started = Vec::new();
+        // A download still winding down after a pause holds on to its partial file
+        let winding_down: Vec<String> = running().keys().cloned().collect();
+        for id in queue.next_to_start() {
+            if winding_down.contains(&id) {
+                continue;
+            }
+            if let Some(item) = queue.get_mut(&id) {
+                item.status = QueueItemStatus::Downloading;
+                item.error = None;
+                started.push(item.clone());
+            }
+        }
+        Ok((started, limit))
+    });
+
+    match started {
+        Ok((items, limit)) => {
+            if !items.is_empty() {
+                events::publish(AppEvent::QueueChanged);
+            }
+            for item in items {
+                tauri::async_runtime::spawn(run_item(
+                    item,
+                    limit,
+                    allow_insecure,
+                    segments,
+                    retries,
+                    pause,
+                ));
+            }
+        }
+        Err(e) => log_error!(MODULE, "Failed to update download queue: {}", e),
+    }
+}
+
+async fn run_item(
+    item: QueueItem,
+    limit: u64,
+    allow_insecure: bool,
+    segments: u64,
+    retries: u64,
+    pause_on_low_space: bool,
+) {
+    log_info!(MODULE, "Downloading queued image: {}", item.label);
+    let state = Arc::new(DownloadState::new());
+    state.throttle_bytes_per_sec.store(limit, Ordering::SeqCst);
+    state.allow_insecure.store(allow_insecure, Ordering::SeqCst);
+    state.segment_count.store(segments, Ordering::SeqCst);
+    state.retry_limit.store(retries, Ordering::SeqCst);
+    state
+        .pause_on_low_space
+        .store(pause_on_low_space, Ordering::SeqCst);
+    running().insert(item.id.clone(), state.clone());
+
+    events::publish(AppEvent::DownloadStarted {
+        url: item.file_url.clone(),
+    });
+    let result = download_image(
+        &item.file_url,
+        item.file_url_sha.as_deref(),
+        &get_images_cache_dir(),
+        state.clone(),
+    )
+    .await;
+    running().remove(&item.id);
+    // A download left waiting for a decision on a missing SHA is not resumed here
+    cleanup_pending_download(state.clone(), &get_images_cache_dir()).await;
+
+    events::publish(AppEvent::DownloadFinished {
+        url: item.file_url.clone(),
+        path: result
+            .as_ref()
+            .ok()
+            .map(|p| p.to_string_lossy().to_string()),
+        error: result.as_ref().err().cloned(),
+    });
+
+    let update = update_queue(|queue| {
+        let Some(queued) = queue.get_mut(&item.id) else {
+            return Ok(());
+        };
+        queued.downloaded_bytes = state.downloaded_bytes.load(Ordering::SeqCst);
+        queued.total_bytes = state.total_bytes.load(Ordering::SeqCst);
+        // Paused while running: leave the item as the user set it
+        if queued.status != QueueItemStatus::Downloading {
+            return Ok(());
+        }
+        match &result {
+            Ok(path) => {
+                log_info!(MODULE, "Queued download completed: {}", item.label);
+                queued.status = QueueItemStatus::Completed;
+                queued.output_path = Some(path.to_string_lossy().to_string());
+            }
+            Err(e) => {
+                log_error!(MODULE, "Queued download failed: {}: {}", item.label, e);
+                queued.status = QueueItemStatus::Failed;
+                queued.error = Some(e.clone());
+            }
+        }
+        Ok(())
+    });
+    if let Err(e) = update {
+        log_error!(MODULE, "Failed to update download queue: {}", e);
+    }
+    queue_changed();
+}
+
+/// Get the download queue, with the progress of running downloads
+#[tauri::command]
+pub fn get_download_queue() -> DownloadQueue {
+    let mut queue = load_queue();
+    let running = running();
+    for item in &mut queue.items {
+        if let Some(state) = running.get(&item.id) {
+            item.downloaded_bytes = state.downloaded_bytes.load(Ordering::SeqCst);
+            item.total_bytes = state.total_bytes.load(Ordering::SeqCst);
+        }
+    }
+    queue
+}
+
+/// Add an image to the download queue
+#[tauri::command]
+pub fn enqueue_download(
+    file_url: String,
+    file_url_sha: Option<String>,
+    label: String,
+    priority: Option<QueuePriority>,
+    app: AppHandle,
+) -> Result<QueueItem, String> {
+    if !settings::current(&app).cache_enabled {
+        return Err("Queued downloads need the image cache, which is turned off".to_string());
+    }
+    check_download_url(&file_url, insecure_urls_allowed(&app))?;
+    let item = update_queue(|queue| {
+        queue.add(
+            &file_url,
+            file_url_sha.as_deref(),
+            &label,
+            priority.unwrap_or_default(),
+        )
+    })?;
+    log_info!(
+        MODULE,
+        "Queued download: {} ({})",
+        item.label,
+        item.file_url
+    );
+    queue_changed();
+    Ok(item)
+}
+
+/// Remove an item from the queue, cancelling its download if running
+///
+/// The partial file of a paused download goes with it.
+#[tauri::command]
+pub fn remove_queue_item(id: String) -> Result<(), String> {
+    let item = update_queue(|queue| queue.remove(&id))?;
+    cancel_running(&id, false);
+    if item.status == QueueItemStatus::Paused {
+        discard_paused(&item.file_url, &get_images_cache_dir());
+    }
+    queue_changed();
+    Ok(())
+}
+
+/// Move an item to a new position in the queue
+#[tauri::command]
+pub fn move_queue_item(id: String, index: usize) -> Result<(), String> {
+    update_queue(|queue| queue.move_item(&id, index))?;
+    queue_changed();
+    Ok(())
+}
+
+/// Pause or resume an item; pausing a running download cancels it
+///
+/// A paused download continues its partial file when resumed. Resuming a
+/// failed item retries it.
+#[tauri::command]
+pub fn set_queue_item_paused(id: String, paused: bool) -> Result<(), String> {
+    let previous = update_queue(|queue| queue.set_paused(&id, paused))?;
+    if paused && previous == QueueItemStatus::Downloading {
+        cancel_running(&id, true);
+    }
+    queue_changed();
+    Ok(())
+}
+
+/// Change the priority of an item
+#[tauri::command]
+pub fn set_queue_item_priority(id: String, priority: QueuePriority) -> Result<(), String> {
+    update_queue(|queue| queue.set_priority(&id, priority))?;
+    queue_changed();
+    Ok(())
+}
+
+/// Set how many queued downloads run at once and their combined speed limit
+#[tauri::command]
+pub fn set_download_queue_options(max_parallel: usize, bandwidth_kbps: u64) -> Result<(), String> {
+    update_queue(|queue| queue.set_options(max_parallel, bandwidth_kbps))?;
+    log_info!(
+        MODULE,
+        "Download queue: {} parallel, limit {} KB/s",
+        max_parallel,
+        bandwidth_kbps
+    );
+    queue_changed();
+    Ok(())
+}
+
+/// Remove completed items from the queue, returning how many were removed
+#[tauri::command]
+pub fn clear_completed_queue_items() -> Result<usize, String> {
+    let removed = update_queue(|queue| Ok(queue.clear_completed()))?;
+    if removed > 0 {
+        queue_changed();
+    }
+    Ok(removed)
+}
+
+/// Get the downloads interrupted by the last exit, for resuming or discarding
+#[tauri::command]
+pub fn get_interrupted_downloads() -> Vec<InterruptedDownload> {
+    list_interrupted()
+}
+
+/// Resume an interrupted download in the queue, where it continues its partial file
+#[tauri::command]
+pub fn resume_interrupted_download(id: String, app: AppHandle) -> Result<QueueItem, String> {
+    let download = find_interrupted(&id)?;
+    enqueue_download(
+        download.url,
+        download.sha_url,
+        download.filename,
+        Some(QueuePriority::High),
+        app,
+    )
+}
+
+/// Delete an interrupted download and its partial file
+#[tauri::command]
+pub fn discard_interrupted_download(id: String) -> Result<(), String> {
+    discard(&id)
+}
@@ -1,17 +1,25 @@
-//! Board image URL module
+//! Board image module
 //!
-//! Returns remote URLs for board images from cache.armbian.com
+//! Returns cached board photos; see `board_photos` for how they are kept
+//! fresh from cache.armbian.com.
 
-use crate::config;
+use tauri::AppHandle;
 
-/// Get board image URL - returns remote URL for the board image
+use crate::board_photos::{self, cached_photo};
+
+/// Get the cached photo of a board, refreshing it in the background
+///
+/// Never waits for a download: returns the cached file path or `None`, and
+/// a photo that arrives later is announced with `board_image://ready`.
+#[tauri::command]
+pub fn get_board_image_url(board_slug: String, app: AppHandle) -> Result<Option<String>, String> {
+    Ok(cached_photo(&app, &board_slug).map(|path| path.to_string_lossy().to_string()))
+}
+
+/// Delete every cached board photo
+///
+/// Boards show their placeholder until the photo is downloaded again.
 #[tauri::command]
-pub fn get_board_image_url(board_slug: String) -> Result<Option<String>, String> {
-    let url = format!(
-        "{}{}/{}.png",
-        config::urls::BOARD_IMAGES_BASE,
-        config::urls::BOARD_IMAGE_SIZE,
-        board_slug
-    );
-    Ok(Some(url))
+pub fn clear_board_image_cache() -> Result<(), String> {
+    board_photos::clear()
 }
@@ -1,142 +1,102 @@
 //! Settings persistence commands using Tauri Store plugin
 //!
-//! Manages user preferences like theme and language using the Tauri Store plugin.
+//! Manages user preferences like theme and language. The values live in the
+//! `settings::Settings` model, which checks them; every change is written
+//! through `settings_store::commit_settings`.
 
-use crate::log_info;
-use tauri_plugin_store::StoreExt;
+use std::collections::HashMap;
 
-const MODULE: &str = "commands::settings";
-const SETTINGS_STORE: &str = "settings.json";
-const MAX_LOG_SIZE: u64 = 5 * 1024 * 1024; // 5MB
-const MAX_LOG_LINES: usize = 10_000;
+use tauri::AppHandle;
 
-/// Default values for settings
-fn default_theme() -> String {
-    "auto".to_string()
-}
+use crate::cues::{play_cue, CompletionCues, CueOutcome};
+use crate::images::CatalogSource;
+use crate::proxy::ProxySettings;
+use crate::settings::{self, Settings};
+use crate::utils::{
+    host_compatibility, run_blocking, sandbox_status, HostCompatibility, SandboxStatus,
+};
+use crate::{log_info, log_warn};
 
-fn default_language() -> String {
-    "auto".to_string()
-}
-
-fn default_show_motd() -> bool {
-    true
-}
+use super::state::AppState;
 
-fn default_show_updater_modal() -> bool {
-    true
-}
+const MODULE: &str = "commands::settings";
+const MAX_LOG_SIZE: u64 = 5 * 1024 * 1024; // 5MB
+const MAX_LOG_LINES: usize = 10_000;
 
-fn default_developer_mode() -> bool {
-    false
+/// Replace the settings in `patch`, a JSON object keyed like `Settings`
+fn apply(app: &AppHandle, patch: serde_json::Value) -> Result<Settings, String> {
+    match patch {
+        serde_json::Value::Object(patch) => settings::update(app, &patch),
+        _ => Err("Settings must be an object".to_string()),
+    }
 }
 
-fn default_cache_enabled() -> bool {
-    true
+/// Get all settings
+#[tauri::command]
+pub fn get_settings(app: AppHandle) -> Settings {
+    settings::current(&app)
 }
 
-fn default_cache_max_size() -> u64 {
-    crate::cache::DEFAULT_MAX_SIZE
+/// Change several settings at once
+///
+/// `patch` holds the settings to change, keyed like `Settings`. Nothing is
+/// changed if one of them is invalid. Returns all settings.
+#[tauri::command]
+pub async fn update_settings(
+    patch: serde_json::Value,
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<Settings, String> {
+    let keys: Vec<String> = patch
+        .as_object()
+        .map(|patch| patch.keys().cloned().collect())
+        .unwrap_or_default();
+    log_info!(MODULE, "Updating settings: {}", keys.join(", "));
+    let catalog_changed = keys.iter().any(|key| key == "catalog_sources");
+    let settings = apply(&app, patch)?;
+    if catalog_changed {
+        *state.images_json.lock().await = None;
+    }
+    Ok(settings)
 }
 
 /// Get the current theme preference
 #[tauri::command]
-pub fn get_theme(app: tauri::AppHandle) -> String {
-    match app.store(SETTINGS_STORE) {
-        Ok(store) => match store.get("theme") {
-            Some(value) => value.as_str().unwrap_or("auto").to_string(),
-            None => {
-                log_info!(MODULE, "Theme not found in store, using default");
-                default_theme()
-            }
-        },
-        Err(e) => {
-            log_info!(MODULE, "Error loading store, using default theme: {}", e);
-            default_theme()
-        }
-    }
+pub fn get_theme(app: AppHandle) -> String {
+    settings::current(&app).theme.as_str().to_string()
 }
 
 /// Set the theme preference
 #[tauri::command]
-pub fn set_theme(theme: String, app: tauri::AppHandle) -> Result<(), String> {
+pub fn set_theme(theme: String, app: AppHandle) -> Result<(), String> {
     log_info!(MODULE, "Setting theme to: {}", theme);
-
-    match app.store(SETTINGS_STORE) {
-        Ok(store) => {
-            store.set("theme", theme);
-            Ok(())
-        }
-        Err(e) => Err(format!("Failed to access store: {}", e)),
-    }
+    apply(&app, serde_json::json!({ "theme": theme })).map(|_| ())
 }
 
 /// Get the current language preference
 #[tauri::command]
-pub fn get_language(app: tauri::AppHandle) -> String {
-    match app.store(SETTINGS_STORE) {
-        Ok(store) => match store.get("language") {
-            Some(value) => value.as_str().unwrap_or("auto").to_string(),
-            None => {
-                log_info!(MODULE, "Language not found in store, using default");
-                default_language()
-            }
-        },
-        Err(e) => {
-            log_info!(MODULE, "Error loading store, using default language: {}", e);
-            default_language()
-        }
-    }
+pub fn get_language(app: AppHandle) -> String {
+    settings::current(&app).language
 }
 
 /// Set the language preference
 #[tauri::command]
-pub fn set_language(language: String, app: tauri::AppHandle) -> Result<(), String> {
+pub fn set_language(language: String, app: AppHandle) -> Result<(), String> {
     log_info!(MODULE, "Setting language to: {}", language);
-
-    match app.store(SETTINGS_STORE) {
-        Ok(store) => {
-            store.set("language", language);
-            Ok(())
-        }
-        Err(e) => Err(format!("Failed to access store: {}", e)),
-    }
+    apply(&app, serde_json::json!({ "language": language })).map(|_| ())
 }
 
 /// Get the MOTD visibility preference
 #[tauri::command]
-pub fn get_show_motd(app: tauri::AppHandle) -> bool {
-    match app.store(SETTINGS_STORE) {
-        Ok(store) => match store.get("show_motd") {
-            Some(value) => value.as_bool().unwrap_or(true),
-            None => {
-                log_info!(MODULE, "show_motd not found in store, using default");
-                default_show_motd()
-            }
-        },
-        Err(e) => {
-            log_info!(
-                MODULE,
-                "Error loading store, using default show_motd: {}",
-                e
-            );
-            default_show_motd()
-        }
-    }
+pub fn get_show_motd(app: AppHandle) -> bool {
+    settings::current(&app).show_motd
 }
 
 /// Set the MOTD visibility preference
 #[tauri::command]
-pub fn set_show_motd(show: bool, app: tauri::AppHandle) -> Result<(), String> {
+pub fn set_show_motd(show: bool, app: AppHandle) -> Result<(), String> {
     log_info!(MODULE, "Setting show_motd to: {}", show);
-
-    match app.store(SETTINGS_STORE) {
-        Ok(store) => {
-            store.set("show_motd", show);
-            Ok(())
-        }
-        Err(e) => Err(format!("Failed to access store: {}", e)),
-    }
+    apply(&app, serde_json::json!({ "show_motd": show })).map(|_| ())
 }
 
 /// System information structure
@@ -144,11 +104,22 @@ pub fn set_show_motd(show: bool, app: tauri::AppHandle) -> Result<(), String> {
 pub struct SystemInfo {
     pub platform: String,
     pub arch: String,
+    /// Flatpak or snap sandbox, with the snap interfaces that are connected
+    pub sandbox: SandboxStatus,
+    /// Native vs emulated build, helper tools and limits for this host
+    pub compatibility: HostCompatibility,
 }
 
-/// Get the real system platform and architecture
+/// Get the real system platform and architecture, the sandbox the app runs in,
+/// and the host compatibility self-check
+///
+/// The self-check runs helper tools, so it stays off the async runtime.
 #[tauri::command]
-pub fn get_system_info() -> SystemInfo {
+pub async fn get_system_info() -> Result<SystemInfo, String> {
+    run_blocking(|| Ok(system_info())).await
+}
+
+fn system_info() -> SystemInfo {
     let platform = std::env::consts::OS.to_string();
     let arch = match std::env::consts::ARCH {
         "x86_64" => "x64",
@@ -159,7 +130,12 @@ pub fn get_system_info() -> SystemInfo {
     }
     .to_string();
 
-    SystemInfo { platform, arch }
+    SystemInfo {
+        platform,
+        arch,
+        sandbox: sandbox_status(),
+        compatibility: host_compatibility(),
+    }
 }
 
 /// Get the Tauri version
@@ -173,80 +149,115 @@ pub fn get_tauri_version() -> String {
 
 /// Get the updater modal visibility preference
 #[tauri::command]
-pub fn get_show_updater_modal(app: tauri::AppHandle) -> bool {
-    match app.store(SETTINGS_STORE) {
-        Ok(store) => match store.get("show_updater_modal") {
-            Some(value) => value.as_bool().unwrap_or(true),
-            None => {
-                log_info!(
-                    MODULE,
-                    "show_updater_modal not found in store, using default"
-                );
-                default_show_updater_modal()
-            }
-        },
-        Err(e) => {
-            log_info!(
-                MODULE,
-                "Error loading store, using default show_updater_modal: {}",
-                e
-            );
-            default_show_updater_modal()
-        }
-    }
+pub fn get_show_updater_modal(app: AppHandle) -> bool {
+    settings::current(&app).show_updater_modal
 }
 
 /// Set the updater modal visibility preference
 #[tauri::command]
-pub fn set_show_updater_modal(show: bool, app: tauri::AppHandle) -> Result<(), String> {
+pub fn set_show_updater_modal(show: bool, app: AppHandle) -> Result<(), String> {
     log_info!(MODULE, "Setting show_updater_modal to: {}", show);
-
-    match app.store(SETTINGS_STORE) {
-        Ok(store) => {
-            store.set("show_updater_modal", show);
-            Ok(())
-        }
-        Err(e) => Err(format!("Failed to access store: {}", e)),
-    }
+    apply(&app, serde_json::json!({ "show_updater_modal": show })).map(|_| ())
 }
 
 /// Get the developer mode preference
 #[tauri::command]
-pub fn get_developer_mode(app: tauri::AppHandle) -> bool {
-    match app.store(SETTINGS_STORE) {
-        Ok(store) => match store.get("developer_mode") {
-            Some(value) => value.as_bool().unwrap_or_else(default_developer_mode),
-            None => {
-                log_info!(MODULE, "developer_mode not found in store, using default");
-                default_developer_mode()
-            }
-        },
-        Err(e) => {
-            log_info!(
-                MODULE,
-                "Error loading store, using default developer_mode: {}",
-                e
-            );
-            default_developer_mode()
-        }
-    }
+pub fn get_developer_mode(app: AppHandle) -> bool {
+    settings::current(&app).developer_mode
 }
 
 /// Set the developer mode preference
+///
+/// The log level follows developer mode.
 #[tauri::command]
-pub fn set_developer_mode(enabled: bool, app: tauri::AppHandle) -> Result<(), String> {
+pub fn set_developer_mode(enabled: bool, app: AppHandle) -> Result<(), String> {
     log_info!(MODULE, "Setting developer_mode to: {}", enabled);
+    apply(&app, serde_json::json!({ "developer_mode": enabled })).map(|_| ())
+}
+
+/// Get whether failure reports are written to the card after a failed verification
+#[tauri::command]
+pub fn get_write_failure_report(app: AppHandle) -> bool {
+    settings::current(&app).write_failure_report
+}
 
-    // Update the log level based on developer mode
-    crate::logging::set_log_level(enabled);
+/// Set whether failure reports are written to the card after a failed verification
+#[tauri::command]
+pub fn set_write_failure_report(enabled: bool, app: AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting write_failure_report to: {}", enabled);
+    apply(&app, serde_json::json!({ "write_failure_report": enabled })).map(|_| ())
+}
 
-    match app.store(SETTINGS_STORE) {
-        Ok(store) => {
-            store.set("developer_mode", enabled);
-            Ok(())
-        }
-        Err(e) => Err(format!("Failed to access store: {}", e)),
-    }
+/// Get the erase mode ("quick" or "full"), see `settings::EraseMode`
+#[tauri::command]
+pub fn get_erase_mode(app: AppHandle) -> String {
+    settings::current(&app).erase_mode.as_str().to_string()
+}
+
+/// Set the erase mode ("quick" or "full")
+#[tauri::command]
+pub fn set_erase_mode(mode: String, app: AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting erase_mode to: {}", mode);
+    apply(&app, serde_json::json!({ "erase_mode": mode })).map(|_| ())
+}
+
+/// Get the verification mode ("full" byte comparison, "fast" CRC32C or
+/// "hash" SHA-256 of the whole image)
+#[tauri::command]
+pub fn get_verify_mode(app: AppHandle) -> String {
+    serde_json::to_value(settings::current(&app).verify_mode)
+        .ok()
+        .and_then(|mode| mode.as_str().map(|s| s.to_string()))
+        .unwrap_or_default()
+}
+
+/// Set the verification mode ("full", "fast" or "hash")
+#[tauri::command]
+pub fn set_verify_mode(mode: String, app: AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting verify_mode to: {}", mode);
+    apply(&app, serde_json::json!({ "verify_mode": mode })).map(|_| ())
+}
+
+/// Get whether flashing only writes chunks that differ from the device
+#[tauri::command]
+pub fn get_smart_write(app: AppHandle) -> bool {
+    settings::current(&app).smart_write
+}
+
+/// Set whether flashing only writes chunks that differ from the device
+#[tauri::command]
+pub fn set_smart_write(enabled: bool, app: AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting smart_write to: {}", enabled);
+    apply(&app, serde_json::json!({ "smart_write": enabled })).map(|_| ())
+}
+
+/// Get whether all-zero blocks are skipped instead of written
+#[tauri::command]
+pub fn get_skip_zero_blocks(app: AppHandle) -> bool {
+    settings::current(&app).skip_zero_blocks
+}
+
+/// Set whether all-zero blocks are skipped instead of written
+#[tauri::command]
+pub fn set_skip_zero_blocks(enabled: bool, app: AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting skip_zero_blocks to: {}", enabled);
+    apply(&app, serde_json::json!({ "skip_zero_blocks": enabled })).map(|_| ())
+}
+
+/// Get the download mirror region tried first ("auto" lets the redirector pick)
+#[tauri::command]
+pub fn get_mirror_region(app: AppHandle) -> String {
+    settings::current(&app).mirror_region
+}
+
+/// Set the download mirror region tried first
+///
+/// Mirrors in that region are tried before the redirector, and the others
+/// after it when a download fails.
+#[tauri::command]
+pub fn set_mirror_region(region: String, app: AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting mirror_region to: {}", region);
+    apply(&app, serde_json::json!({ "mirror_region": region })).map(|_| ())
 }
 
 /// Read only the last N lines from a file to avoid loading large files into memory
@@ -270,6 +281,304 @@ fn read_last_lines(path: &std::path::PathBuf, lines: usize) -> Result<String, St
     Ok(all_lines[start..].join("\n"))
 }
 
+/// Get the number of log files kept
+#[tauri::command]
+pub fn get_log_retention_count(app: AppHandle) -> u64 {
+    settings::current(&app).log_retention_count
+}
+
+/// Set the number of log files kept (1 to 1000)
+#[tauri::command]
+pub fn set_log_retention_count(count: u64, app: AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting log_retention_count to: {}", count);
+    apply(&app, serde_json::json!({ "log_retention_count": count })).map(|_| ())
+}
+
+/// Get the age in days after which log files are deleted (0 = never)
+#[tauri::command]
+pub fn get_log_retention_days(app: AppHandle) -> u64 {
+    settings::current(&app).log_retention_days
+}
+
+/// Set the age in days after which log files are deleted (0 = never)
+#[tauri::command]
+pub fn set_log_retention_days(days: u64, app: AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting log_retention_days to: {}", days);
+    apply(&app, serde_json::json!({ "log_retention_days": days })).map(|_| ())
+}
+
+/// Get the per-module log levels used in developer mode (e.g. "devices=debug")
+#[tauri::command]
+pub fn get_module_log_levels(app: AppHandle) -> String {
+    settings::current(&app).module_log_levels
+}
+
+/// Set the per-module log levels used in developer mode
+///
+/// Takes comma-separated `module=level` rules, e.g. "devices=debug, download=info".
+#[tauri::command]
+pub fn set_module_log_levels(levels: String, app: AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting module_log_levels to: {}", levels);
+    apply(&app, serde_json::json!({ "module_log_levels": levels })).map(|_| ())
+}
+
+/// Get the simulated network speed limit in KB/s used in developer mode (0 = off)
+#[tauri::command]
+pub fn get_download_throttle_kbps(app: AppHandle) -> u64 {
+    settings::current(&app).download_throttle_kbps
+}
+
+/// Set the simulated network speed limit in KB/s used in developer mode (0 = off)
+#[tauri::command]
+pub fn set_download_throttle_kbps(kbps: u64, app: AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting download_throttle_kbps to: {}", kbps);
+    apply(&app, serde_json::json!({ "download_throttle_kbps": kbps })).map(|_| ())
+}
+
+/// Get the simulated device write speed limit in KB/s used in developer mode (0 = off)
+#[tauri::command]
+pub fn get_write_throttle_kbps(app: AppHandle) -> u64 {
+    settings::current(&app).write_throttle_kbps
+}
+
+/// Set the simulated device write speed limit in KB/s used in developer mode (0 = off)
+#[tauri::command]
+pub fn set_write_throttle_kbps(kbps: u64, app: AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting write_throttle_kbps to: {}", kbps);
+    apply(&app, serde_json::json!({ "write_throttle_kbps": kbps })).map(|_| ())
+}
+
+/// Download and write speed limits in bytes per second, 0 when off
+///
+/// The throttles only apply in developer mode.
+pub fn developer_throttles(app: &AppHandle) -> (u64, u64) {
+    let settings = settings::current(app);
+    if !settings.developer_mode {
+        return (0, 0);
+    }
+    (
+        settings.download_throttle_kbps.saturating_mul(1024),
+        settings.write_throttle_kbps.saturating_mul(1024),
+    )
+}
+
+/// Networks the user allowed large downloads on although they are metered
+///
+/// Holds `NetworkStatus::network_id`s.
+pub fn metered_allowed_networks(app: &AppHandle) -> Vec<String> {
+    settings::current(app).metered_allowed_networks
+}
+
+/// Names the user gave devices, keyed by serial number
+pub fn device_aliases(app: &AppHandle) -> HashMap<String, String> {
+    settings::current(app).device_aliases
+}
+
+/// Get the device aliases, keyed by serial number
+#[tauri::command]
+pub fn get_device_aliases(app: AppHandle) -> HashMap<String, String> {
+    device_aliases(&app)
+}
+
+/// Name the device with `serial`; an empty alias removes the name
+///
+/// Aliases follow the serial, so a card keeps its name in any reader. Open
+/// device lists are refreshed with the new name, which enumerates the devices
+/// again, so the change runs off the async runtime.
+#[tauri::command]
+pub async fn set_device_alias(serial: String, alias: String, app: AppHandle) -> Result<(), String> {
+    run_blocking(move || {
+        let alias = alias.trim();
+        let mut aliases = device_aliases(&app);
+        if alias.is_empty() {
+            log_info!(MODULE, "Removing alias of device {}", serial);
+            aliases.remove(&serial);
+        } else {
+            log_info!(MODULE, "Naming device {} \"{}\"", serial, alias);
+            aliases.insert(serial, alias.to_string());
+        }
+        apply(&app, serde_json::json!({ "device_aliases": aliases })).map(|_| ())
+    })
+    .await
+}
+
+/// Cues announcing finished operations
+pub fn completion_cues(app: &AppHandle) -> CompletionCues {
+    settings::current(app).completion_cues
+}
+
+/// Get the cues announcing finished operations
+#[tauri::command]
+pub fn get_completion_cues(app: AppHandle) -> CompletionCues {
+    completion_cues(&app)
+}
+
+/// Set the cues announcing finished operations
+#[tauri::command]
+pub fn set_completion_cues(cues: CompletionCues, app: AppHandle) -> Result<(), String> {
+    log_info!(MODULE, "Setting completion cues to: {:?}", cues);
+    apply(&app, serde_json::json!({ "completion_cues": cues })).map(|_| ())
+}
+
+/// Play the completion sound for an outcome, to try it from the settings
+#[tauri::command]
+pub fn preview_completion_cue(outcome: CueOutcome, app: AppHandle) {
+    let cues = CompletionCues {
+        sound: true,
+        attention: false,
+        badge: false,
+    };
+    play_cue(&app, outcome, cues);
+}
+
+/// Catalogs merged with the official one, see `images::CatalogSource`
+///
+/// HTTP catalogs only take part while insecure downloads are allowed.
+pub fn catalog_sources(app: &AppHandle) -> Vec<CatalogSource> {
+    let settings = settings::current(app);
+    let allow_insecure = settings.allow_insecure_urls;
+    settings
+        .catalog_sources
+        .into_iter()
+        .filter(|source| {
+            let usable = allow_insecure || !source.is_insecure();
+            if !usable {
+                log_warn!(
+                    MODULE,
+                    "Skipping catalog \"{}\" over plain HTTP, insecure downloads are off",
+                    source.name
+                );
+            }
+            usable
+        })
+        .collect()
+}
+
+/// Get the catalogs merged with the official one
+///
+/// HTTP catalogs are listed even while insecure downloads are off.
+#[tauri::command]
+pub fn get_catalog_sources(app: AppHandle) -> Vec<CatalogSource> {
+    settings::current(&app).catalog_sources
+}
+
+/// Set the catalogs merged with the official one
+///
+/// The cached catalog is dropped, so the next board list loads the new set.
+/// Returns the normalized sources.
+#[tauri::command]
+pub async fn set_catalog_sources(
+    sources: Vec<CatalogSource>,
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<CatalogSource>, String> {
+    log_info!(
+        MODULE,
+        "Setting additional catalogs: {:?}",
+        sources.iter().map(|s| &s.url).collect::<Vec<_>>()
+    );
+    let settings = apply(&app, serde_json::json!({ "catalog_sources": sources }))?;
+    *state.images_json.lock().await = None;
+    Ok(settings.catalog_sources)
+}
+
+/// Whether image and checksum downloads may use plain HTTP
+///
+/// Off by default; meant for mirrors on the local network that do not serve
+/// HTTPS.
+pub fn insecure_urls_allowed(app: &AppHandle) -> bool {
+    settings::current(app).allow_insecure_urls
+}
+
+/// Parallel connections per download when the server accepts ranges
+///
+/// 1 downloads on a single connection.
+pub fn download_segments(app: &AppHandle) -> u64 {
+    settings::current(app).download_segments
+}
+
+/// Retries of a mirror after a network error before the next one is tried
+///
+/// 0 moves on to the next mirror at the first error.
+pub fn download_retries(app: &AppHandle) -> u64 {
+    settings::current(app).download_retries
+}
+
+/// Whether downloads wait for space to be freed when their volume runs low
+///
+/// Otherwise they only warn and carry on until a write fails.
+pub fn pause_on_low_space(app: &AppHandle) -> bool {
+    settings::current(app).pause_on_low_space
+}
+
+/// Get the proxy for all HTTP traffic
+#[tauri::command]
+pub fn get_proxy(app: AppHandle) -> ProxySettings {
+    settings::current(&app).proxy
+}
+
+/// Set the proxy for all HTTP traffic
+///
+/// `password` replaces the stored proxy password when given; it is
+/// forgotten when the user name is cleared. Returns the normalized settings.
+#[tauri::command]
+pub async fn set_proxy(
+    proxy: ProxySettings,
+    password: Option<String>,
+    app: AppHandle,
+) -> Result<ProxySettings, String> {
+    let proxy = proxy.normalized()?;
+    log_info!(MODULE, "Setting proxy mode to {:?}", proxy.mode);
+    let password = match password {
+        _ if proxy.username.is_empty() => Some(String::new()),
+        password => password,
+    };
+    if let Some(password) = password {
+        run_blocking(move || crate::proxy::set_password(&password)).await?;
+    }
+    let unchanged = settings::current(&app).proxy == proxy;
+    let settings = apply(&app, serde_json::json!({ "proxy": proxy }))?;
+    if unchanged {
+        // Only the password changed; changed settings reconfigure on their own
+        crate::proxy::configure(&settings.proxy);
+    }
+    Ok(settings.proxy)
+}
+
+/// Get the paste service logs are uploaded to
+#[tauri::command]
+pub fn get_paste_url(app: AppHandle) -> String {
+    settings::current(&app).paste_url
+}
+
+/// Set the paste service logs are uploaded to
+///
+/// An empty URL restores the default service. Returns the normalized URL.
+#[tauri::command]
+pub fn set_paste_url(url: String, app: AppHandle) -> Result<String, String> {
+    let url = apply(&app, serde_json::json!({ "paste_url": url }))?.paste_url;
+    if url == crate::config::paste::DEFAULT_URL {
+        log_info!(MODULE, "Setting paste_url to: {}", url);
+    } else {
+        log_warn!(
+            MODULE,
+            "Setting paste_url to custom endpoint {}, uploaded logs will be sent there",
+            url
+        );
+    }
+    Ok(url)
+}
+
+/// Delete old log files according to the retention settings
+pub fn apply_log_retention(app: AppHandle) {
+    let settings = settings::current(&app);
+    let retention = crate::logging::LogRetention::new(
+        settings.log_retention_count,
+        settings.log_retention_days,
+    );
+    crate::logging::apply_retention(retention);
+}
+
 /// Get the latest log file contents
 ///
 /// For large log files (>5MB), only the last 10,000 lines are returned
@@ -317,101 +626,33 @@ pub fn get_logs() -> Result<String, String> {
 ///
 /// Returns whether image caching is enabled (default: true).
 #[tauri::command]
-pub fn get_cache_enabled(app: tauri::AppHandle) -> bool {
-    match app.store(SETTINGS_STORE) {
-        Ok(store) => match store.get("cache_enabled") {
-            Some(value) => value.as_bool().unwrap_or_else(default_cache_enabled),
-            None => {
-                log_info!(MODULE, "cache_enabled not found in store, using default");
-                default_cache_enabled()
-            }
-        },
-        Err(e) => {
-            log_info!(
-                MODULE,
-                "Error loading store, using default cache_enabled: {}",
-                e
-            );
-            default_cache_enabled()
-        }
-    }
+pub fn get_cache_enabled(app: AppHandle) -> bool {
+    settings::current(&app).cache_enabled
 }
 
 /// Set the cache enabled preference
 #[tauri::command]
-pub fn set_cache_enabled(enabled: bool, app: tauri::AppHandle) -> Result<(), String> {
+pub fn set_cache_enabled(enabled: bool, app: AppHandle) -> Result<(), String> {
     log_info!(MODULE, "Setting cache_enabled to: {}", enabled);
-
-    match app.store(SETTINGS_STORE) {
-        Ok(store) => {
-            store.set("cache_enabled", enabled);
-            Ok(())
-        }
-        Err(e) => Err(format!("Failed to access store: {}", e)),
-    }
+    apply(&app, serde_json::json!({ "cache_enabled": enabled })).map(|_| ())
 }
 
 /// Get the maximum cache size in bytes
 ///
 /// Returns the configured maximum cache size (default: 20 GB).
 #[tauri::command]
-pub fn get_cache_max_size(app: tauri::AppHandle) -> u64 {
-    match app.store(SETTINGS_STORE) {
-        Ok(store) => match store.get("cache_max_size") {
-            Some(value) => value.as_u64().unwrap_or_else(default_cache_max_size),
-            None => {
-                log_info!(MODULE, "cache_max_size not found in store, using default");
-                default_cache_max_size()
-            }
-        },
-        Err(e) => {
-            log_info!(
-                MODULE,
-                "Error loading store, using default cache_max_size: {}",
-                e
-            );
-            default_cache_max_size()
-        }
-    }
+pub fn get_cache_max_size(app: AppHandle) -> u64 {
+    settings::current(&app).cache_max_size
 }
 
 /// Set the maximum cache size in bytes
 ///
-/// The size is validated to be between 1 GB and 500 GB.
+/// The size is validated to be between 1 GB and 100 GB; the cache is
+/// trimmed to the new size.
 #[tauri::command]
-pub fn set_cache_max_size(size: u64, app: tauri::AppHandle) -> Result<(), String> {
-    use crate::config::cache::{MAX_SIZE, MIN_SIZE};
-
-    // Validate cache size bounds
-    if size < MIN_SIZE {
-        return Err(format!(
-            "Cache size too small: {} bytes (minimum: {} bytes / 1 GB)",
-            size, MIN_SIZE
-        ));
-    }
-
-    if size > MAX_SIZE {
-        return Err(format!(
-            "Cache size too large: {} bytes (maximum: {} bytes / 100 GB)",
-            size, MAX_SIZE
-        ));
-    }
-
+pub fn set_cache_max_size(size: u64, app: AppHandle) -> Result<(), String> {
     log_info!(MODULE, "Setting cache_max_size to: {} bytes", size);
-
-    match app.store(SETTINGS_STORE) {
-        Ok(store) => {
-            store.set("cache_max_size", size);
-
-            // Trigger eviction if needed
-            if let Err(e) = crate::cache::evict_to_size(size) {
-                log_info!(MODULE, "Failed to evict cache after size change: {}", e);
-            }
-
-            Ok(())
-        }
-        Err(e) => Err(format!("Failed to access store: {}", e)),
-    }
+    apply(&app, serde_json::json!({ "cache_max_size": size })).map(|_| ())
 }
 
 /// Get the current cache size in bytes
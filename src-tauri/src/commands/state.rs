@@ -1,26 +1,482 @@
 //! Application state module
 //!
 //! Defines the shared application state used across commands.
+//!
+//! Download and flash state is owned per window (keyed by window label), so
+//! two windows can flash two cards at once: each window polls and cancels
+//! only its own operations. A device is locked by one job at a time, whichever
+//! window started it; locks are keyed by device identity, so two paths to the
+//! same device can't be used to get around them.
+//!
+//! Downloads, flashes, verifications and extractions are registered as jobs
+//! under their operation ID while they run, so their status can be listed
+//! and one job can be cancelled without touching the others of its window.
+//! A job runs on its window's download or flash state, which only one job
+//! may use at a time.
 
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+use crate::config;
+use crate::devices::device_identity;
 use crate::download::DownloadState;
-use crate::flash::FlashState;
+use crate::flash::{release_mounts, FlashState};
+use crate::settings::Settings;
+
+/// Download and flash state of one window
+pub struct WindowOperations {
+    pub download_state: Arc<DownloadState>,
+    pub flash_state: Arc<FlashState>,
+    /// Note for the next device operation, set before its ID exists
+    pub pending_note: std::sync::Mutex<Option<String>>,
+}
+
+impl WindowOperations {
+    fn new() -> Self {
+        Self {
+            download_state: Arc::new(DownloadState::new()),
+            flash_state: Arc::new(FlashState::new()),
+            pending_note: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Take the note set for the next device operation
+    pub fn take_pending_note(&self) -> Option<String> {
+        self.pending_note
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// Ask the running download and flash of this window to stop
+    pub fn cancel(&self) {
+        self.download_state
+            .is_cancelled
+            .store(true, Ordering::SeqCst);
+        self.flash_state.is_cancelled.store(true, Ordering::SeqCst);
+    }
+}
 
 /// Application state shared across all commands
 pub struct AppState {
     pub images_json: Mutex<Option<serde_json::Value>>,
-    pub download_state: Arc<DownloadState>,
-    pub flash_state: Arc<FlashState>,
+    /// Settings in effect, see `settings::reload`
+    pub settings: std::sync::RwLock<Settings>,
+    /// Operations by owner window label
+    operations: std::sync::Mutex<HashMap<String, Arc<WindowOperations>>>,
+    /// Locked devices by device identity
+    device_locks: std::sync::Mutex<HashMap<String, DeviceLock>>,
+    /// Running jobs and the most recently finished ones, oldest first
+    jobs: std::sync::Mutex<Vec<JobEntry>>,
+}
+
+/// A job holding a device
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceLock {
+    pub device_path: String,
+    /// Label of the window that started the job
+    pub owner: String,
+    /// Operation ID or kind of the job, e.g. "flash-20250101-120000-1"
+    pub job: String,
+    /// RFC 3339 time the lock was taken
+    pub since: String,
+}
+
+/// State a job's progress and cancellation go through
+#[derive(Clone)]
+pub enum JobControl {
+    Download(Arc<DownloadState>),
+    Flash(Arc<FlashState>),
+}
+
+impl JobControl {
+    fn cancel(&self) {
+        match self {
+            JobControl::Download(state) => state.is_cancelled.store(true, Ordering::SeqCst),
+            JobControl::Flash(state) => state.is_cancelled.store(true, Ordering::SeqCst),
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        match self {
+            JobControl::Download(state) => state.is_cancelled.load(Ordering::SeqCst),
+            JobControl::Flash(state) => state.is_cancelled.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Whether both go through the same state
+    fn shares_state(&self, other: &JobControl) -> bool {
+        match (self, other) {
+            (JobControl::Download(a), JobControl::Download(b)) => Arc::ptr_eq(a, b),
+            (JobControl::Flash(a), JobControl::Flash(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// Status of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// A download, flash or verification started by a window
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    /// Operation ID, e.g. "flash-20250101-120000-1"
+    pub id: String,
+    /// Kind of job: "download", "flash", "verify" or "extract"
+    pub kind: String,
+    /// Label of the window that started the job
+    pub owner: String,
+    /// Image URL or device path the job works on
+    pub target: String,
+    pub status: JobStatus,
+    /// RFC 3339 time the job started
+    pub started: String,
+    /// RFC 3339 time the job ended
+    pub finished: Option<String>,
+    /// Why the job failed, if it did
+    pub error: Option<String>,
+}
+
+struct JobEntry {
+    job: Job,
+    control: JobControl,
 }
 
 impl Default for AppState {
     fn default() -> Self {
         Self {
             images_json: Mutex::new(None),
-            download_state: Arc::new(DownloadState::new()),
-            flash_state: Arc::new(FlashState::new()),
+            settings: std::sync::RwLock::new(Settings::default()),
+            operations: std::sync::Mutex::new(HashMap::new()),
+            device_locks: std::sync::Mutex::new(HashMap::new()),
+            jobs: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl AppState {
+    /// Operations owned by a window, created on first use
+    pub fn operations(&self, owner: &str) -> Arc<WindowOperations> {
+        self.operations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(owner.to_string())
+            .or_insert_with(|| Arc::new(WindowOperations::new()))
+            .clone()
+    }
+
+    /// Cancel and forget the operations of a closed window
+    pub fn release_window(&self, owner: &str) {
+        let removed = self
+            .operations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(owner);
+        if let Some(operations) = removed {
+            operations.cancel();
         }
     }
+
+    /// Fail if a job holds the device
+    pub fn check_device_free(&self, owner: &str, device_path: &str) -> Result<(), String> {
+        let locks = self.device_locks.lock().unwrap_or_else(|e| e.into_inner());
+        match locks.get(&device_identity(device_path)) {
+            Some(lock) => Err(busy_error(owner, device_path, lock)),
+            None => Ok(()),
+        }
+    }
+
+    /// Lock a device for `job` of window `owner` until the claim is dropped
+    ///
+    /// Fails while any other job holds the device, including one of the same
+    /// window. Partitions of the device mounted on demand are unmounted first.
+    pub fn claim_device(
+        &self,
+        owner: &str,
+        job: &str,
+        device_path: &str,
+    ) -> Result<DeviceClaim<'_>, String> {
+        let identity = device_identity(device_path);
+        {
+            let mut locks = self.device_locks.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(lock) = locks.get(&identity) {
+                return Err(busy_error(owner, device_path, lock));
+            }
+            locks.insert(
+                identity.clone(),
+                DeviceLock {
+                    device_path: device_path.to_string(),
+                    owner: owner.to_string(),
+                    job: job.to_string(),
+                    since: chrono::Utc::now().to_rfc3339(),
+                },
+            );
+        }
+        // Unmounting runs external tools, so other windows must not wait on
+        // the lock table meanwhile. The entry above already reserves the device.
+        let claim = DeviceClaim {
+            state: self,
+            identity,
+        };
+        release_mounts(device_path);
+        Ok(claim)
+    }
+
+    /// Devices currently locked, oldest first
+    pub fn device_locks(&self) -> Vec<DeviceLock> {
+        let mut locks: Vec<DeviceLock> = self
+            .device_locks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect();
+        locks.sort_by(|a, b| a.since.cmp(&b.since));
+        locks
+    }
+
+    /// Register a running job until the returned guard is dropped
+    ///
+    /// `id` is the job's operation ID, `kind` its operation kind. Fails while
+    /// another job runs on the same state, which the new job would reset.
+    pub fn start_job(
+        &self,
+        owner: &str,
+        id: &str,
+        kind: &str,
+        target: &str,
+        control: JobControl,
+    ) -> Result<JobGuard<'_>, String> {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(running) = jobs.iter().find(|entry| {
+            entry.job.status == JobStatus::Running && entry.control.shares_state(&control)
+        }) {
+            return Err(format!(
+                "Another {} is running in this window ({})",
+                running.job.kind, running.job.id
+            ));
+        }
+        let job = Job {
+            id: id.to_string(),
+            kind: kind.to_string(),
+            owner: owner.to_string(),
+            target: target.to_string(),
+            status: JobStatus::Running,
+            started: chrono::Utc::now().to_rfc3339(),
+            finished: None,
+            error: None,
+        };
+        jobs.push(JobEntry { job, control });
+        Ok(JobGuard {
+            state: self,
+            id: id.to_string(),
+            outcome: None,
+        })
+    }
+
+    /// Running and recently finished jobs, oldest first
+    pub fn jobs(&self) -> Vec<Job> {
+        self.jobs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|entry| entry.job.clone())
+            .collect()
+    }
+
+    /// Ask one running job to stop
+    pub fn cancel_job(&self, id: &str) -> Result<(), String> {
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = jobs
+            .iter()
+            .find(|entry| entry.job.id == id)
+            .ok_or_else(|| format!("No job {}", id))?;
+        if entry.job.status != JobStatus::Running {
+            return Err(format!("Job {} already ended", id));
+        }
+        entry.control.cancel();
+        Ok(())
+    }
+
+    fn end_job(&self, id: &str, outcome: Option<Result<(), String>>) {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = jobs.iter_mut().find(|entry| entry.job.id == id) {
+            entry.job.status = match &outcome {
+                Some(Ok(())) => JobStatus::Succeeded,
+                _ if entry.control.is_cancelled() => JobStatus::Cancelled,
+                _ => JobStatus::Failed,
+            };
+            entry.job.error = outcome.and_then(Result::err);
+            entry.job.finished = Some(chrono::Utc::now().to_rfc3339());
+        }
+
+        // Only the newest finished jobs are kept
+        let finished = jobs
+            .iter()
+            .filter(|entry| entry.job.status != JobStatus::Running)
+            .count();
+        let mut excess = finished.saturating_sub(config::jobs::MAX_FINISHED);
+        jobs.retain(|entry| {
+            let drop = excess > 0 && entry.job.status != JobStatus::Running;
+            if drop {
+                excess -= 1;
+            }
+            !drop
+        });
+    }
+}
+
+fn busy_error(owner: &str, device_path: &str, lock: &DeviceLock) -> String {
+    format!(
+        "Device {} is busy with job {}{}",
+        device_path,
+        lock.job,
+        if lock.owner == owner {
+            ""
+        } else {
+            " from another window"
+        }
+    )
+}
+
+/// Exclusive access to a device, released on drop
+pub struct DeviceClaim<'a> {
+    state: &'a AppState,
+    identity: String,
+}
+
+/// A job's registration, which records how it ended when dropped
+pub struct JobGuard<'a> {
+    state: &'a AppState,
+    id: String,
+    outcome: Option<Result<(), String>>,
+}
+
+impl JobGuard<'_> {
+    /// Record the job's result; a job dropped without one failed early
+    pub fn finish<T>(&mut self, result: &Result<T, String>) {
+        self.outcome = Some(result.as_ref().map(|_| ()).map_err(Clone::clone));
+    }
+}
+
+impl Drop for JobGuard<'_> {
+    fn drop(&mut self) {
+        self.state.end_job(&self.id, self.outcome.take());
+    }
+}
+
+impl Drop for DeviceClaim<'_> {
+    fn drop(&mut self) {
+        self.state
+            .device_locks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.identity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operations_are_per_window() {
+        let state = AppState::default();
+        let first = state.operations("main");
+        let second = state.operations("window-1");
+        assert!(Arc::ptr_eq(&first, &state.operations("main")));
+
+        second.cancel();
+        assert!(!first.flash_state.is_cancelled.load(Ordering::SeqCst));
+        assert!(second.flash_state.is_cancelled.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_device_claim() {
+        let state = AppState::default();
+        let claim = state.claim_device("main", "flash-1", "/dev/sdx").unwrap();
+        let Err(err) = state.claim_device("window-1", "verify-2", "/dev/sdx") else {
+            panic!("a locked device was claimed again");
+        };
+        assert!(
+            err.contains("busy with job flash-1 from another window"),
+            "{}",
+            err
+        );
+        // The window holding the lock can't start a second job on it either
+        assert!(state.claim_device("main", "verify-3", "/dev/sdx").is_err());
+        assert!(state.check_device_free("main", "/dev/sdx").is_err());
+        assert!(state
+            .claim_device("window-1", "verify-2", "/dev/sdy")
+            .is_ok());
+        assert_eq!(state.device_locks().len(), 1);
+        drop(claim);
+        assert!(state.device_locks().is_empty());
+        assert!(state
+            .claim_device("window-1", "verify-2", "/dev/sdx")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_jobs() {
+        let state = AppState::default();
+        let operations = state.operations("main");
+        let control = JobControl::Flash(operations.flash_state.clone());
+        let mut flash = state
+            .start_job("main", "flash-1", "flash", "/dev/sdx", control.clone())
+            .unwrap();
+        let download = state
+            .start_job(
+                "main",
+                "download-2",
+                "download",
+                "https://example.com/a.img.xz",
+                JobControl::Download(operations.download_state.clone()),
+            )
+            .unwrap();
+        assert_eq!(state.jobs().len(), 2);
+
+        // The flash state is taken until the flash ends
+        assert!(state
+            .start_job("main", "extract-3", "extract", "a.img", control.clone())
+            .is_err());
+        let other = JobControl::Flash(state.operations("window-1").flash_state.clone());
+        drop(
+            state
+                .start_job("window-1", "extract-4", "extract", "a.img", other)
+                .unwrap(),
+        );
+
+        // Only the named job is cancelled, not the whole window
+        state.cancel_job("download-2").unwrap();
+        assert!(operations
+            .download_state
+            .is_cancelled
+            .load(Ordering::SeqCst));
+        assert!(!operations.flash_state.is_cancelled.load(Ordering::SeqCst));
+        drop(download);
+        assert_eq!(state.jobs()[1].status, JobStatus::Cancelled);
+        assert!(state.cancel_job("download-2").is_err());
+        assert!(state.cancel_job("verify-9").is_err());
+
+        flash.finish(&Err::<(), _>("Write failed".to_string()));
+        drop(flash);
+        let job = &state.jobs()[0];
+        assert_eq!(job.status, JobStatus::Failed);
+        assert_eq!(job.error.as_deref(), Some("Write failed"));
+        assert!(job.finished.is_some());
+        assert!(state
+            .start_job("main", "extract-5", "extract", "a.img", control)
+            .is_ok());
+    }
 }
@@ -2,7 +2,19 @@
 //!
 //! Platform-specific system operations like opening URLs and locale detection.
 
-use crate::{log_debug, log_info};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use super::state::AppState;
+use crate::audit::{export_audit_log_to, export_history_to, ExportFormat};
+use crate::benchmark::{run_benchmarks, BenchmarkReport};
+use crate::config;
+use crate::logging::get_log_dir;
+use crate::selftest::SelftestReport;
+use crate::utils::{get_cache_dir, run_blocking, LocaleInfo};
+use crate::{log_debug, log_info, log_warn};
 use sys_locale::get_locale;
 
 const MODULE: &str = "commands::system";
@@ -19,6 +31,103 @@ pub fn log_debug_from_frontend(module: String, message: String) {
     log_debug!(&format!("frontend::{}", module), "{}", message);
 }
 
+/// Export the audit log of device writes to a file chosen by the user
+///
+/// Returns the number of exported entries, or None if the user cancelled.
+#[tauri::command]
+pub async fn export_audit_log(window: tauri::Window) -> Result<Option<usize>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let destination = window
+        .dialog()
+        .file()
+        .add_filter("Audit Log", &["jsonl"])
+        .set_file_name(config::audit::FILE_NAME)
+        .set_title("Export Audit Log")
+        .blocking_save_file();
+
+    let Some(destination) = destination else {
+        return Ok(None);
+    };
+    let path = destination
+        .as_path()
+        .ok_or_else(|| "Invalid path: not a valid file path".to_string())?;
+
+    export_audit_log_to(path).map(Some)
+}
+
+/// Export the flash history with checksums and device serials as "json" or
+/// "csv", e.g. for asset-tracking spreadsheets
+///
+/// Without `path` the file is chosen in a save dialog. Returns the number of
+/// exported entries, or None if the user cancelled.
+#[tauri::command]
+pub async fn export_history(
+    path: Option<String>,
+    format: String,
+    window: tauri::Window,
+) -> Result<Option<usize>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let format = ExportFormat::parse(&format)?;
+    let destination = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let chosen = window
+                .dialog()
+                .file()
+                .add_filter("Flash History", &[format.extension()])
+                .set_file_name(format!(
+                    "{}.{}",
+                    config::audit::EXPORT_FILE_STEM,
+                    format.extension()
+                ))
+                .set_title("Export Flash History")
+                .blocking_save_file();
+            let Some(chosen) = chosen else {
+                return Ok(None);
+            };
+            chosen
+                .into_path()
+                .map_err(|_| "Invalid path: not a valid file path".to_string())?
+        }
+    };
+
+    export_history_to(&destination, format).map(Some)
+}
+
+/// Attach a note to an operation, before, during or after it runs
+///
+/// `id` is the operation ID from the progress of a running operation. Before
+/// it exists, the note is held for the next device operation the window
+/// starts, e.g. "card for greenhouse node 3". Empty text removes the note.
+#[tauri::command]
+pub fn set_operation_note(
+    id: Option<String>,
+    text: String,
+    state: State<'_, AppState>,
+    window: tauri::Window,
+) -> Result<(), String> {
+    match id {
+        Some(id) => crate::audit::set_operation_note(&id, &text).map(|_| ()),
+        None => {
+            let text = text.trim();
+            if text.chars().count() > config::audit::MAX_NOTE_LEN {
+                return Err(format!(
+                    "Notes are limited to {} characters",
+                    config::audit::MAX_NOTE_LEN
+                ));
+            }
+            *state
+                .operations(window.label())
+                .pending_note
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()) = (!text.is_empty()).then(|| text.to_string());
+            Ok(())
+        }
+    }
+}
+
 /// Get the system locale (e.g., "en-US", "it-IT", "de-DE")
 /// Returns the language code for i18n initialization
 #[tauri::command]
@@ -28,25 +137,178 @@ pub fn get_system_locale() -> String {
     locale
 }
 
+/// Get the system locale and the closest supported app language
+///
+/// `supported` replaces the built-in language list, e.g. with the languages
+/// the frontend actually ships.
+#[tauri::command]
+pub fn get_locale_info(supported: Option<Vec<String>>) -> LocaleInfo {
+    let locale = get_locale().unwrap_or_else(|| "en-US".to_string());
+    let info = match supported {
+        Some(ref languages) => {
+            let languages: Vec<&str> = languages.iter().map(String::as_str).collect();
+            LocaleInfo::negotiate(&locale, &languages)
+        }
+        None => LocaleInfo::negotiate(&locale, config::i18n::SUPPORTED_LANGUAGES),
+    };
+    log_info!(
+        MODULE,
+        "System locale {} negotiated to language {} (rtl: {})",
+        info.system_locale,
+        info.language,
+        info.rtl
+    );
+    info
+}
+
+/// Location that `reveal_path` can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevealKind {
+    Logs,
+    Cache,
+    LastDownload,
+}
+
+/// Open the logs, cache or last downloaded image location in the file manager
+///
+/// For the last download of the calling window, the folder containing the
+/// image is opened.
+#[tauri::command]
+pub async fn reveal_path(
+    kind: RevealKind,
+    state: State<'_, AppState>,
+    window: tauri::Window,
+) -> Result<(), String> {
+    let dir: PathBuf = match kind {
+        RevealKind::Logs => get_log_dir(),
+        RevealKind::Cache => get_cache_dir(config::app::NAME),
+        RevealKind::LastDownload => {
+            let image = state
+                .operations(window.label())
+                .download_state
+                .output_path
+                .lock()
+                .await
+                .clone()
+                .filter(|path| path.exists())
+                .ok_or_else(|| "No downloaded image in this session".to_string())?;
+            image
+                .parent()
+                .map(|parent| parent.to_path_buf())
+                .ok_or_else(|| format!("Invalid image path: {}", image.display()))?
+        }
+    };
+
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    log_info!(MODULE, "Revealing {:?} location: {}", kind, dir.display());
+    open_with_system(&dir.to_string_lossy())
+}
+
+/// Open another app window with its own download and flash
+///
+/// Returns the label of the new window.
+#[tauri::command]
+pub async fn open_new_window(app: tauri::AppHandle) -> Result<String, String> {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    static NEXT_WINDOW: AtomicU32 = AtomicU32::new(1);
+
+    let label = format!(
+        "{}{}",
+        config::app::EXTRA_WINDOW_LABEL_PREFIX,
+        NEXT_WINDOW.fetch_add(1, Ordering::SeqCst)
+    );
+    // Same geometry as the main window in tauri.conf.json
+    tauri::WebviewWindowBuilder::new(&app, &label, tauri::WebviewUrl::default())
+        .title(config::app::DISPLAY_NAME)
+        .inner_size(1100.0, 700.0)
+        .min_inner_size(1050.0, 575.0)
+        .resizable(true)
+        .build()
+        .map_err(|e| format!("Failed to open window: {}", e))?;
+
+    log_info!(MODULE, "Opened window {}", label);
+    Ok(label)
+}
+
+/// URL schemes `open_url` hands to the desktop
+const ALLOWED_URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// Why `open_url` refused or failed to open a URL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OpenUrlError {
+    /// Not a parseable absolute URL
+    Invalid { reason: String },
+    /// Parsed, but the scheme could start something other than a browser
+    UnsupportedScheme { scheme: String },
+    /// The desktop opener could not be started
+    LaunchFailed { message: String },
+}
+
+impl std::fmt::Display for OpenUrlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenUrlError::Invalid { reason } => write!(f, "Invalid URL: {}", reason),
+            OpenUrlError::UnsupportedScheme { scheme } => {
+                write!(f, "Unsupported URL scheme: {}", scheme)
+            }
+            OpenUrlError::LaunchFailed { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Check a URL and return its normalized, percent-encoded form
+///
+/// Only the normalized form is passed on, so spaces, quotes and control
+/// characters never reach the opener as separate arguments.
+fn validate_url(url: &str) -> Result<String, OpenUrlError> {
+    let parsed = reqwest::Url::parse(url.trim()).map_err(|e| OpenUrlError::Invalid {
+        reason: e.to_string(),
+    })?;
+
+    if !ALLOWED_URL_SCHEMES.contains(&parsed.scheme()) {
+        return Err(OpenUrlError::UnsupportedScheme {
+            scheme: parsed.scheme().to_string(),
+        });
+    }
+    if parsed.scheme() != "mailto" && parsed.host_str().map_or(true, str::is_empty) {
+        return Err(OpenUrlError::Invalid {
+            reason: "missing host".to_string(),
+        });
+    }
+
+    Ok(parsed.into())
+}
+
 /// Open a URL in the default browser
 /// On Linux when running as root, uses runuser to open as the original user
 #[tauri::command]
-pub fn open_url(url: String) -> Result<(), String> {
+pub fn open_url(url: String) -> Result<(), OpenUrlError> {
+    let url = validate_url(&url).inspect_err(|e| {
+        log_warn!(MODULE, "Refusing to open URL {:?}: {}", url, e);
+    })?;
     log_info!(MODULE, "Opening URL: {}", url);
+    open_with_system(&url).map_err(|message| OpenUrlError::LaunchFailed { message })
+}
 
+/// Open a URL or path with the desktop's default handler
+pub(super) fn open_with_system(url: &str) -> Result<(), String> {
     #[cfg(target_os = "linux")]
     {
-        open_url_linux(&url)
+        open_url_linux(url)
     }
 
     #[cfg(target_os = "macos")]
     {
-        open_url_macos(&url)
+        open_url_macos(url)
     }
 
     #[cfg(target_os = "windows")]
     {
-        open_url_windows(&url)
+        open_url_windows(url)
     }
 }
 
@@ -179,14 +441,83 @@ fn open_url_macos(url: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Open through ShellExecuteW, never through `cmd`, whose parser would treat
+/// `&`, `|` or `^` in the URL as command syntax
 #[cfg(target_os = "windows")]
 fn open_url_windows(url: &str) -> Result<(), String> {
-    use std::process::Command;
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::UI::Shell::ShellExecuteW;
+    use windows_sys::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+    let wide = |s: &str| -> Vec<u16> { OsStr::new(s).encode_wide().chain(Some(0)).collect() };
+    let operation = wide("open");
+    let file = wide(url);
+
+    // SAFETY: both strings are NUL-terminated and outlive the call
+    let result = unsafe {
+        ShellExecuteW(
+            std::ptr::null_mut(),
+            operation.as_ptr(),
+            file.as_ptr(),
+            std::ptr::null(),
+            std::ptr::null(),
+            SW_SHOWNORMAL,
+        )
+    } as isize;
+
+    // Values above 32 mean success
+    if result > 32 {
+        Ok(())
+    } else {
+        Err(format!("Failed to open URL: ShellExecute error {}", result))
+    }
+}
 
-    Command::new("cmd")
-        .args(["/c", "start", "", url])
-        .spawn()
-        .map_err(|e| format!("Failed to open URL: {}", e))?;
+/// Run the end-to-end self-test against a scratch file that must not exist yet
+///
+/// Not exposed in the UI; see `crate::selftest`.
+#[tauri::command]
+pub async fn run_selftest(scratch_path: String) -> SelftestReport {
+    crate::selftest::run(&PathBuf::from(scratch_path)).await
+}
 
-    Ok(())
+/// Benchmark decompression, hashing and chunk sizes on this machine
+///
+/// Only available in developer mode; takes tens of seconds.
+#[tauri::command]
+pub async fn run_benchmark(app: tauri::AppHandle) -> Result<BenchmarkReport, String> {
+    if !super::settings::get_developer_mode(app) {
+        return Err("Benchmarks are only available in developer mode".to_string());
+    }
+
+    run_blocking(|| run_benchmarks(config::benchmark::SAMPLE_SIZE)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_url() {
+        assert_eq!(
+            validate_url("https://armbian.com/a b").unwrap(),
+            "https://armbian.com/a%20b"
+        );
+        assert!(validate_url("mailto:info@armbian.com").is_ok());
+        assert_eq!(
+            validate_url("file:///etc/passwd"),
+            Err(OpenUrlError::UnsupportedScheme {
+                scheme: "file".to_string()
+            })
+        );
+        assert!(matches!(
+            validate_url("--help"),
+            Err(OpenUrlError::Invalid { .. })
+        ));
+        assert!(matches!(
+            validate_url("javascript:alert(1)"),
+            Err(OpenUrlError::UnsupportedScheme { .. })
+        ));
+    }
 }
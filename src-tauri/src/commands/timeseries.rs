@@ -0,0 +1,388 @@
+//! Throughput time series
+//!
+//! While a download, flash or verify runs, a sampler thread reads the byte
+//! counters of the owning window's state once per
+//! `config::timeseries::SAMPLE_INTERVAL_MS` and records the throughput since
+//! the previous sample. The UI fetches the series by operation ID (the ID the
+//! operation's log lines are tagged with) to draw a speed graph.
+//!
+//! Download samples pause while the SHA check and decompression run, as
+//! those stages have no byte counter.
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::download::DownloadState;
+use crate::flash::FlashState;
+use crate::log_warn;
+
+const MODULE: &str = "timeseries";
+
+/// Stage an operation was in when a sample was taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferPhase {
+    Download,
+    Write,
+    Verify,
+}
+
+/// One throughput sample
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThroughputSample {
+    /// Milliseconds since the operation started
+    pub elapsed_ms: u64,
+    pub phase: TransferPhase,
+    /// Bytes processed in this phase so far
+    pub processed_bytes: u64,
+    /// Throughput since the previous sample
+    pub bytes_per_sec: f64,
+}
+
+/// Recent samples of an operation, as returned to the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationTimeseries {
+    pub operation_id: String,
+    /// The operation ended, no more samples will be added
+    pub finished: bool,
+    pub sample_interval_ms: u64,
+    /// Oldest first
+    pub samples: Vec<ThroughputSample>,
+}
+
+/// State whose byte counters are sampled
+#[derive(Clone)]
+pub enum SampleSource {
+    Download(Arc<DownloadState>),
+    Flash(Arc<FlashState>),
+}
+
+impl SampleSource {
+    fn kind(&self) -> SourceKind {
+        match self {
+            SampleSource::Download(_) => SourceKind::Download,
+            SampleSource::Flash(_) => SourceKind::Flash,
+        }
+    }
+
+    /// Current phase and its byte count, None while no counter moves
+    fn read(&self) -> Option<(TransferPhase, u64)> {
+        use std::sync::atomic::Ordering;
+
+        match self {
+            SampleSource::Download(state) => {
+                if state.is_verifying_sha.load(Ordering::SeqCst)
+                    || state.is_decompressing.load(Ordering::SeqCst)
+                {
+                    return None;
+                }
+                Some((
+                    TransferPhase::Download,
+                    state.downloaded_bytes.load(Ordering::SeqCst),
+                ))
+            }
+            SampleSource::Flash(state) => Some(if state.is_verifying.load(Ordering::SeqCst) {
+                (
+                    TransferPhase::Verify,
+                    state.verified_bytes.load(Ordering::SeqCst),
+                )
+            } else {
+                (
+                    TransferPhase::Write,
+                    state.written_bytes.load(Ordering::SeqCst),
+                )
+            }),
+        }
+    }
+}
+
+/// Which state of a window a series was taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    Download,
+    Flash,
+}
+
+/// Samples of one operation
+#[derive(Debug)]
+struct Series {
+    operation_id: String,
+    /// Label of the window running the operation
+    owner: String,
+    kind: SourceKind,
+    started: Instant,
+    finished: bool,
+    samples: VecDeque<ThroughputSample>,
+}
+
+impl Series {
+    fn new(operation_id: &str, owner: &str, kind: SourceKind, started: Instant) -> Self {
+        Self {
+            operation_id: operation_id.to_string(),
+            owner: owner.to_string(),
+            kind,
+            started,
+            finished: false,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Add a sample, dropping those older than the retention window
+    fn record(&mut self, now: Instant, phase: TransferPhase, processed_bytes: u64) {
+        let elapsed_ms = now.duration_since(self.started).as_millis() as u64;
+
+        // A new phase counts its bytes from zero
+        let (since_ms, since_bytes) = match self.samples.back() {
+            Some(last) if elapsed_ms <= last.elapsed_ms => return,
+            Some(last) if last.phase == phase => (last.elapsed_ms, last.processed_bytes),
+            Some(last) => (last.elapsed_ms, 0),
+            None => (0, 0),
+        };
+        let interval_secs = (elapsed_ms - since_ms) as f64 / 1000.0;
+        let bytes_per_sec = if interval_secs > 0.0 {
+            processed_bytes.saturating_sub(since_bytes) as f64 / interval_secs
+        } else {
+            0.0
+        };
+
+        self.samples.push_back(ThroughputSample {
+            elapsed_ms,
+            phase,
+            processed_bytes,
+            bytes_per_sec,
+        });
+
+        let retention_ms = config::timeseries::RETENTION_SECS * 1000;
+        while self
+            .samples
+            .front()
+            .is_some_and(|s| s.elapsed_ms + retention_ms < elapsed_ms)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Samples within `span_secs` of the newest one
+    fn recent(&self, span_secs: u64) -> OperationTimeseries {
+        let newest = self.samples.back().map_or(0, |s| s.elapsed_ms);
+        let span_ms = span_secs * 1000;
+
+        OperationTimeseries {
+            operation_id: self.operation_id.clone(),
+            finished: self.finished,
+            sample_interval_ms: config::timeseries::SAMPLE_INTERVAL_MS,
+            samples: self
+                .samples
+                .iter()
+                .filter(|s| s.elapsed_ms + span_ms >= newest)
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Series of running and recently finished operations, oldest first
+#[derive(Debug, Default)]
+struct TimeseriesStore {
+    series: Vec<Series>,
+}
+
+impl TimeseriesStore {
+    fn start(&mut self, operation_id: &str, owner: &str, kind: SourceKind, now: Instant) {
+        self.series.retain(|s| s.operation_id != operation_id);
+        self.series
+            .push(Series::new(operation_id, owner, kind, now));
+    }
+
+    fn get_mut(&mut self, operation_id: &str) -> Option<&mut Series> {
+        self.series
+            .iter_mut()
+            .find(|s| s.operation_id == operation_id)
+    }
+
+    /// Mark a series finished, keeping only the newest finished ones
+    fn finish(&mut self, operation_id: &str) {
+        if let Some(series) = self.get_mut(operation_id) {
+            series.finished = true;
+        }
+
+        let finished = self.series.iter().filter(|s| s.finished).count();
+        let mut excess = finished.saturating_sub(config::timeseries::FINISHED_KEPT);
+        self.series.retain(|s| {
+            if excess > 0 && s.finished {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// ID of the newest operation of a window on the given state
+    fn latest(&self, owner: &str, kind: SourceKind) -> Option<String> {
+        self.series
+            .iter()
+            .rev()
+            .find(|s| s.owner == owner && s.kind == kind)
+            .map(|s| s.operation_id.clone())
+    }
+}
+
+static STORE: Lazy<Mutex<TimeseriesStore>> = Lazy::new(|| Mutex::new(TimeseriesStore::default()));
+
+fn store() -> std::sync::MutexGuard<'static, TimeseriesStore> {
+    STORE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// ID of the newest download or flash operation started by a window
+pub fn latest_operation(owner: &str, kind: SourceKind) -> Option<String> {
+    store().latest(owner, kind)
+}
+
+/// Samples an operation's throughput until dropped
+pub struct ThroughputSampler {
+    operation_id: String,
+    source: SampleSource,
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ThroughputSampler {
+    /// Start sampling `source` for the operation `operation_id` of window `owner`
+    pub fn start(operation_id: &str, owner: &str, source: SampleSource) -> Self {
+        store().start(operation_id, owner, source.kind(), Instant::now());
+
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread_id = operation_id.to_string();
+        let thread_source = source.clone();
+        let interval = Duration::from_millis(config::timeseries::SAMPLE_INTERVAL_MS);
+
+        let handle = std::thread::Builder::new()
+            .name("throughput-sampler".to_string())
+            .spawn(move || {
+                // The sender is dropped when sampling should stop
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    sample(&thread_id, &thread_source);
+                }
+            })
+            .map_err(|e| log_warn!(MODULE, "Failed to start throughput sampler: {}", e))
+            .ok();
+
+        Self {
+            operation_id: operation_id.to_string(),
+            source,
+            stop: Some(stop),
+            handle,
+        }
+    }
+}
+
+impl Drop for ThroughputSampler {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        // Close the series with the final byte count
+        sample(&self.operation_id, &self.source);
+        store().finish(&self.operation_id);
+    }
+}
+
+fn sample(operation_id: &str, source: &SampleSource) {
+    let Some((phase, processed_bytes)) = source.read() else {
+        return;
+    };
+    if let Some(series) = store().get_mut(operation_id) {
+        series.record(Instant::now(), phase, processed_bytes);
+    }
+}
+
+/// Get the throughput samples of an operation from the last `seconds` seconds
+///
+/// Series of running operations and of the last few finished ones are kept.
+#[tauri::command]
+pub fn get_operation_timeseries(
+    id: String,
+    seconds: Option<u64>,
+) -> Result<OperationTimeseries, String> {
+    let span_secs = seconds
+        .unwrap_or(config::timeseries::DEFAULT_SPAN_SECS)
+        .min(config::timeseries::RETENTION_SECS);
+
+    store()
+        .get_mut(&id)
+        .map(|series| series.recent(span_secs))
+        .ok_or_else(|| format!("No throughput data for operation {}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_record_rates() {
+        let start = Instant::now();
+        let mut series = Series::new("flash-1", "main", SourceKind::Flash, start);
+
+        series.record(at(start, 1000), TransferPhase::Write, 4_000);
+        series.record(at(start, 2000), TransferPhase::Write, 10_000);
+        // Verification counts from zero again
+        series.record(at(start, 2500), TransferPhase::Verify, 5_000);
+        // Out of order samples are ignored
+        series.record(at(start, 2500), TransferPhase::Verify, 6_000);
+
+        let rates: Vec<f64> = series.samples.iter().map(|s| s.bytes_per_sec).collect();
+        assert_eq!(rates, vec![4_000.0, 6_000.0, 10_000.0]);
+    }
+
+    #[test]
+    fn test_retention_and_span() {
+        let start = Instant::now();
+        let mut series = Series::new("download-1", "main", SourceKind::Download, start);
+        let retention_ms = config::timeseries::RETENTION_SECS * 1000;
+
+        series.record(at(start, 1000), TransferPhase::Download, 1);
+        series.record(at(start, 2000), TransferPhase::Download, 2);
+        series.record(at(start, 2000 + retention_ms), TransferPhase::Download, 3);
+        assert_eq!(series.samples.len(), 2);
+
+        let recent = series.recent(1);
+        assert_eq!(recent.samples.len(), 1);
+        assert_eq!(recent.samples[0].processed_bytes, 3);
+    }
+
+    #[test]
+    fn test_store_keeps_newest_finished() {
+        let now = Instant::now();
+        let mut store = TimeseriesStore::default();
+        let total = config::timeseries::FINISHED_KEPT + 2;
+
+        for i in 0..total {
+            let id = format!("flash-{}", i);
+            store.start(&id, "main", SourceKind::Flash, now);
+            store.finish(&id);
+        }
+        store.start("flash-running", "window-1", SourceKind::Flash, now);
+
+        assert_eq!(store.series.len(), config::timeseries::FINISHED_KEPT + 1);
+        assert!(store.get_mut("flash-0").is_none());
+        assert!(store.get_mut(&format!("flash-{}", total - 1)).is_some());
+        assert_eq!(
+            store.latest("main", SourceKind::Flash),
+            Some(format!("flash-{}", total - 1))
+        );
+        assert_eq!(store.latest("main", SourceKind::Download), None);
+    }
+}
@@ -26,7 +26,7 @@ pub async fn get_github_release(version: String) -> Result<GitHubRelease, String
         return Err("Version cannot be empty".to_string());
     }
 
-    let client = reqwest::Client::builder()
+    let client = crate::proxy::client_builder()
         .user_agent("Armbian-Imager")
         .build()
         .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
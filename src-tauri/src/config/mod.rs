@@ -15,6 +15,25 @@ pub mod app {
 
     /// User agent for HTTP requests
     pub const USER_AGENT: &str = "Armbian-Imager/1.0";
+
+    /// Label prefix of additional windows (must match capabilities/default.json)
+    pub const EXTRA_WINDOW_LABEL_PREFIX: &str = "window-";
+}
+
+/// Interface language settings
+pub mod i18n {
+    /// Languages with a translation in src/locales
+    pub const SUPPORTED_LANGUAGES: &[&str] = &[
+        "en", "de", "es", "fr", "hr", "it", "ja", "ko", "nl", "pl", "pt", "pt-BR", "ru", "sl",
+        "sv", "tr", "uk", "zh",
+    ];
+
+    /// Language used when nothing better matches the system locale
+    pub const DEFAULT_LANGUAGE: &str = "en";
+
+    /// Languages written right to left
+    pub const RTL_LANGUAGES: &[&str] =
+        &["ar", "ckb", "dv", "fa", "he", "ps", "sd", "ug", "ur", "yi"];
 }
 
 /// API endpoints and URLs
@@ -22,6 +41,9 @@ pub mod urls {
     /// Armbian all-images JSON endpoint
     pub const ALL_IMAGES: &str = "https://github.armbian.com/armbian-images.json";
 
+    /// Detached minisign signature of the all-images JSON
+    pub const ALL_IMAGES_SIGNATURE: &str = "https://github.armbian.com/armbian-images.json.minisig";
+
     /// Base URL for board images (cache.armbian.com/images/{size}/{board_slug}.png)
     pub const BOARD_IMAGES_BASE: &str = "https://cache.armbian.com/images/";
 
@@ -29,6 +51,18 @@ pub mod urls {
     pub const BOARD_IMAGE_SIZE: &str = "272";
 }
 
+/// Board photo cache settings
+pub mod board_photos {
+    /// Folder of the app cache holding board photos
+    pub const DIR_NAME: &str = "board-photos";
+
+    /// Size of the photo cache before the least recently shown are evicted (50 MB)
+    pub const MAX_CACHE_BYTES: u64 = 50 * 1024 * 1024;
+
+    /// Photo formats asked of the server, preferred first
+    pub const ACCEPT: &str = "image/avif,image/webp,image/png;q=0.8";
+}
+
 /// Download and decompression settings
 pub mod download {
     /// Download buffer size (1 MB)
@@ -39,6 +73,183 @@ pub mod download {
 
     /// Chunk size for streaming writes (4 MB)
     pub const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+    /// Assumed image-to-archive size ratio when the decompressed size is unknown
+    pub const DECOMPRESS_SIZE_RATIO: u64 = 4;
+
+    /// Free space to leave on the filesystem decompressed images are written to (512 MB)
+    pub const DECOMPRESS_FREE_MARGIN: u64 = 512 * 1024 * 1024;
+
+    /// Directory created in Downloads or home when the cache cannot hold an image
+    pub const DECOMPRESS_ALTERNATIVE_DIR: &str = "armbian-imager-decompress";
+
+    /// Downloads at least this large warn first on a metered connection (1 GB)
+    pub const METERED_WARN_SIZE: u64 = 1024 * 1024 * 1024;
+
+    /// Manifest of running downloads in the cache directory, for resuming them
+    pub const MANIFEST_FILE_NAME: &str = "downloads.json";
+
+    /// Default parallel connections per download (the "download_segments" setting)
+    pub const DEFAULT_SEGMENTS: u64 = 4;
+
+    /// Most parallel connections a download may use
+    pub const MAX_SEGMENTS: u64 = 16;
+
+    /// Downloads are not split into ranges smaller than this (16 MB)
+    pub const MIN_SEGMENT_SIZE: u64 = 16 * 1024 * 1024;
+
+    /// Appended to the partial file name while a segmented download fills it
+    pub const SEGMENTED_SUFFIX: &str = ".segmented";
+
+    /// How often the progress of each range goes to the download manifest
+    pub const SEGMENT_CHECKPOINT_INTERVAL_MS: u64 = 2000;
+
+    /// Free space on the download volume below which a warning is raised (2 GB)
+    pub const LOW_SPACE_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+    /// Minimum interval between free space checks while downloading (seconds)
+    pub const SPACE_CHECK_INTERVAL_SECS: u64 = 5;
+
+    /// Received chunks a direct write holds before the download waits for the device
+    pub const DIRECT_WRITE_QUEUE_CHUNKS: usize = 256;
+
+    /// Default retries of a mirror after a network error (the "download_retries" setting)
+    pub const DEFAULT_RETRIES: u64 = 5;
+
+    /// Most retries of a mirror the setting allows
+    pub const MAX_RETRIES: u64 = 20;
+
+    /// Wait before the first retry, doubled for each further one (milliseconds)
+    pub const RETRY_BASE_DELAY_MS: u64 = 1000;
+
+    /// Longest wait between two retries (milliseconds)
+    pub const RETRY_MAX_DELAY_MS: u64 = 30_000;
+}
+
+/// Operation history settings
+pub mod history {
+    /// History file name inside the cache directory
+    pub const FILE_NAME: &str = "history.json";
+
+    /// Maximum number of speed samples kept
+    pub const MAX_SAMPLES: usize = 200;
+
+    /// Maximum number of flash failure records kept
+    pub const MAX_FAILURE_RECORDS: usize = 100;
+
+    /// Consecutive verification failures after which a device is considered faulty
+    pub const FAULTY_DEVICE_CONSECUTIVE_FAILURES: u32 = 2;
+
+    /// Stored verifications older than this are not trusted (1 hour)
+    pub const VERIFY_CACHE_MAX_AGE_SECS: i64 = 60 * 60;
+
+    /// File name of the failure report written to the boot partition
+    pub const FAILURE_REPORT_FILE: &str = "flash-failure.txt";
+
+    /// Number of recent samples averaged for speed estimates
+    pub const SPEED_SAMPLE_WINDOW: usize = 10;
+
+    /// Samples smaller than this are ignored (64 MB)
+    pub const MIN_SAMPLE_BYTES: u64 = 64 * 1024 * 1024;
+
+    /// Recent latencies kept per mirror host
+    pub const MIRROR_LATENCY_WINDOW: usize = 20;
+
+    /// Consecutive download failures after which a mirror is considered flaky
+    pub const FLAKY_MIRROR_CONSECUTIVE_FAILURES: u32 = 2;
+
+    /// Downloads from a mirror before its failure share can make it flaky
+    pub const FLAKY_MIRROR_MIN_ATTEMPTS: u32 = 3;
+
+    /// Assumed decompressed/compressed size ratio before any decompression was measured
+    pub const DEFAULT_COMPRESSION_RATIO: f64 = 4.0;
+}
+
+/// Audit log settings
+pub mod audit {
+    /// Audit file name inside the config directory
+    pub const FILE_NAME: &str = "audit.jsonl";
+
+    /// Suggested name of an exported flash history, without extension
+    pub const EXPORT_FILE_STEM: &str = "armbian-imager-history";
+
+    /// Notes on operations, by operation ID, inside the config directory
+    pub const NOTES_FILE_NAME: &str = "operation-notes.json";
+
+    /// Longest note on an operation, in characters
+    pub const MAX_NOTE_LEN: usize = 500;
+}
+
+/// Event bus settings
+pub mod events {
+    /// Name of the Tauri event carrying bus events to the frontend
+    pub const FRONTEND_EVENT: &str = "app-event";
+
+    /// Minimum interval between progress events sent to the frontend, per operation
+    pub const PROGRESS_MIN_INTERVAL_MS: u64 = 200;
+
+    /// Tauri event carrying `DownloadProgress` to the window running the download
+    pub const DOWNLOAD_PROGRESS_EVENT: &str = "download://progress";
+
+    /// Tauri event carrying `FlashProgress` to the window running the flash or verify
+    pub const FLASH_PROGRESS_EVENT: &str = "flash://progress";
+
+    /// Interval between pushed progress events (4 Hz)
+    pub const PROGRESS_PUSH_INTERVAL_MS: u64 = 250;
+
+    /// Tauri event carrying the block device list to every window after a hotplug
+    pub const DEVICES_CHANGED_EVENT: &str = "devices://changed";
+
+    /// Tauri event announcing a board photo that was downloaded or changed
+    pub const BOARD_IMAGE_READY_EVENT: &str = "board_image://ready";
+}
+
+/// Throughput time series settings
+pub mod timeseries {
+    /// Interval between throughput samples of a running operation
+    pub const SAMPLE_INTERVAL_MS: u64 = 1000;
+
+    /// Samples older than this are dropped while an operation runs
+    pub const RETENTION_SECS: u64 = 600;
+
+    /// Span returned when the caller does not ask for one
+    pub const DEFAULT_SPAN_SECS: u64 = 60;
+
+    /// Finished operations whose series stay available for the UI
+    pub const FINISHED_KEPT: usize = 8;
+}
+
+/// Self-test settings
+pub mod selftest {
+    /// Hidden command line flag running the self-test, followed by a scratch file path
+    pub const CLI_FLAG: &str = "--selftest";
+
+    /// Size of the generated test image, more than two write chunks plus a partial one
+    pub const IMAGE_SIZE: usize = 2 * super::flash::CHUNK_SIZE + 1024 * 1024 + 4097;
+
+    /// Offset of the byte flipped to check that verification catches corruption
+    pub const CORRUPT_OFFSET: u64 = super::flash::CHUNK_SIZE as u64 + 12345;
+}
+
+/// Benchmark settings
+pub mod benchmark {
+    /// Size of the generated sample every benchmark processes (64 MB)
+    pub const SAMPLE_SIZE: usize = 64 * 1024 * 1024;
+
+    /// Uncompressed XZ block size, so multi-threaded decoding has blocks to share
+    pub const XZ_BLOCK_SIZE: u64 = 4 * 1024 * 1024;
+
+    /// zstd levels compared
+    pub const ZSTD_LEVELS: [i32; 3] = [1, 3, 9];
+
+    /// Write and read chunk sizes compared
+    pub const CHUNK_SIZES: [usize; 3] = [1024 * 1024, 4 * 1024 * 1024, 16 * 1024 * 1024];
+}
+
+/// Developer mode settings
+pub mod developer {
+    /// Longest sleep of a throttled transfer between cancellation checks
+    pub const THROTTLE_SLICE_MS: u64 = 100;
 }
 
 /// Flash operation settings
@@ -52,11 +263,141 @@ pub mod flash {
     /// Erase chunk size (1 MB)
     pub const ERASE_CHUNK_SIZE: usize = 1024 * 1024;
 
+    /// Largest device erased as FAT32 unless asked otherwise; SDXC cards
+    /// above it come formatted exFAT (32 GiB)
+    pub const ERASE_FAT32_MAX_SIZE: u64 = 32 * 1024 * 1024 * 1024;
+
+    /// Volume label of an erased device
+    pub const ERASE_VOLUME_LABEL: &str = "ARMBIAN";
+
+    /// Markers written by the fake capacity check, spread over the device
+    pub const CAPACITY_CHECK_SAMPLES: usize = 256;
+
+    /// Size of each capacity check marker (64 KB); all markers together
+    /// outgrow the write cache of card controllers
+    pub const CAPACITY_CHECK_BLOCK_SIZE: usize = 64 * 1024;
+
+    /// Range discarded or zeroed per ioctl call, so cancelling is noticed (1 GB)
+    pub const DISCARD_CHUNK_SIZE: u64 = 1024 * 1024 * 1024;
+
+    /// How long to wait for an OS-level device lock held briefly elsewhere
+    pub const DEVICE_LOCK_WAIT_MS: u64 = 2000;
+
     /// Progress log interval (percentage points)
     pub const LOG_INTERVAL_PERCENT: u64 = 6;
 
+    /// Bytes written between syncs to the device (32 MB)
+    ///
+    /// Each sync advances the synced byte count, so progress reflects data
+    /// the device accepted rather than data parked in write caches.
+    pub const SYNC_INTERVAL: u64 = 32 * 1024 * 1024;
+
+    /// Time window of the rolling write and read-back speed (seconds)
+    pub const SPEED_WINDOW_SECS: u64 = 5;
+
+    /// Minimum span of speed samples before a rate and ETA are reported (milliseconds)
+    pub const SPEED_MIN_SPAN_MS: u64 = 1000;
+
     /// Delay after unmount before writing (milliseconds)
     pub const UNMOUNT_DELAY_MS: u64 = 500;
+
+    /// Expected minimum read-back speed of SD cards (Class 10 guarantees 10 MB/s writes)
+    pub const MIN_READ_SPEED_SD: u64 = 10 * 1024 * 1024;
+
+    /// Expected minimum read-back speed of USB drives
+    pub const MIN_READ_SPEED_USB: u64 = 15 * 1024 * 1024;
+
+    /// Expected minimum read-back speed of SATA/NVMe drives
+    pub const MIN_READ_SPEED_FAST: u64 = 50 * 1024 * 1024;
+
+    /// Expected minimum read-back speed of other devices
+    pub const MIN_READ_SPEED_DEFAULT: u64 = 5 * 1024 * 1024;
+
+    /// Read-back below this fraction of the expected minimum triggers a warning
+    pub const SLOW_READ_FACTOR: f64 = 0.5;
+
+    /// Space after the image zeroed and checked in "full" erase mode (64 MB)
+    pub const TAIL_CHECK_SIZE: u64 = 64 * 1024 * 1024;
+
+    /// Required alignment of a secondary image's offset on the device (1 MB)
+    pub const SECONDARY_ALIGNMENT: u64 = 1024 * 1024;
+
+    /// Largest blob written by a byte-range flash, e.g. a bootloader (64 MB)
+    pub const RANGE_WRITE_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+    /// Number of concurrent readers for parallel verification
+    pub const PARALLEL_VERIFY_STREAMS: usize = 4;
+
+    /// Device classes fast enough to benefit from parallel verification
+    pub const PARALLEL_VERIFY_CLASSES: &[&str] = &["nvme", "usb"];
+
+    /// Battery level at or below which writing pauses until AC returns (percent)
+    pub const POWER_PAUSE_BATTERY_PERCENT: u8 = 10;
+
+    /// Minimum interval between power source checks (seconds)
+    pub const POWER_CHECK_INTERVAL_SECS: u64 = 5;
+
+    /// Seconds without progress after which an operation is reported as stalled
+    pub const STALL_TIMEOUT_SECS: u64 = 30;
+
+    /// Watchdog progress polling interval (milliseconds)
+    pub const WATCHDOG_POLL_MS: u64 = 250;
+
+    /// Lifetime of a confirmation for a fixed (non-removable) target; long
+    /// enough to cover the download that runs before writing (seconds)
+    pub const DANGEROUS_TARGET_CONFIRMATION_SECS: u64 = 60 * 60;
+}
+
+/// Device backups
+pub mod backup {
+    /// XZ preset for backups, the one Armbian's own images use
+    pub const XZ_PRESET: u32 = 6;
+
+    /// Uncompressed size of each XZ block; blocks are compressed in parallel
+    pub const XZ_BLOCK_SIZE: u64 = 32 * 1024 * 1024;
+
+    /// Zstandard level for backups
+    pub const ZSTD_LEVEL: i32 = 3;
+}
+
+/// First-boot customization
+pub mod customization {
+    /// Preset file placed in the boot directory
+    pub const FIRST_RUN_FILE: &str = "armbian_first_run.txt";
+
+    /// Marker file Armbian's first-login script sources presets from,
+    /// relative to the root filesystem
+    pub const LOGIN_PRESET_FILE: &str = "root/.not_logged_in_yet";
+
+    /// Host name file, relative to the root filesystem
+    pub const HOSTNAME_FILE: &str = "etc/hostname";
+
+    /// Hosts file, whose 127.0.1.1 line names the host, relative to the root filesystem
+    pub const HOSTS_FILE: &str = "etc/hosts";
+
+    /// Address Debian maps the host name to in `HOSTS_FILE`
+    pub const HOSTNAME_ADDRESS: &str = "127.0.1.1";
+
+    /// Hidden command line flag of the elevated writer, followed by a request file
+    pub const ELEVATED_FLAG: &str = "--write-first-boot";
+
+    /// How long the elevated writer may take, including the password prompt
+    pub const ELEVATED_TIMEOUT_SECS: u64 = 120;
+
+    /// Link that enables the SSH server at boot, relative to the root filesystem
+    pub const SSH_UNIT_LINK: &str = "etc/systemd/system/multi-user.target.wants/ssh.service";
+
+    /// Target of `SSH_UNIT_LINK`, as seen from the booted system
+    pub const SSH_UNIT_TARGET: &str = "/lib/systemd/system/ssh.service";
+
+    /// Maximum host name length (one DNS label)
+    pub const MAX_HOSTNAME_LEN: usize = 63;
+
+    /// Maximum user name length accepted by useradd
+    pub const MAX_USER_NAME_LEN: usize = 32;
+
+    /// Maximum Wi-Fi network name length (bytes)
+    pub const MAX_SSID_LEN: usize = 32;
 }
 
 /// Progress logging intervals
@@ -76,12 +417,21 @@ pub mod logging {
     /// Decompression progress log interval (MB)
     pub const DECOMPRESS_LOG_INTERVAL_MB: u64 = 100;
 
-    /// Linux sync interval for flush operations
-    pub const LINUX_SYNC_INTERVAL: u64 = 32 * 1024 * 1024;
+    /// Number of log files kept by default
+    pub const DEFAULT_RETENTION_COUNT: u64 = 10;
+
+    /// Log files older than this are deleted by default (0 keeps them regardless of age)
+    pub const DEFAULT_RETENTION_DAYS: u64 = 30;
+
+    /// Upper bound for the configurable number of kept log files
+    pub const MAX_RETENTION_COUNT: u64 = 1000;
 }
 
 /// Log paste service settings
 pub mod paste {
+    /// Armbian paste service, used unless another endpoint is configured
+    pub const DEFAULT_URL: &str = "https://paste.armbian.com";
+
     /// Maximum lines to show in truncated preview
     pub const TRUNCATE_LINES: usize = 500;
 
@@ -99,6 +449,102 @@ pub mod devices {
 
     /// Maximum device size for removable media (2 TB)
     pub const MAX_SIZE_BYTES: u64 = 2 * 1024 * 1024 * 1024 * 1024;
+
+    /// Quiet time after a hotplug event before devices are listed again
+    ///
+    /// Inserting a card produces a burst of events (disk, partitions,
+    /// media change) that is coalesced into one refresh.
+    pub const HOTPLUG_SETTLE_MS: u64 = 750;
+
+    /// Rescan interval where hotplug events are unavailable
+    pub const HOTPLUG_POLL_INTERVAL_SECS: u64 = 2;
+
+    /// Longest device alias, in characters
+    pub const MAX_ALIAS_LEN: usize = 64;
+
+    /// USB bridge chipsets known to misbehave with UAS: vendor ID, product ID, name
+    ///
+    /// Writes through these stall, reset or fail with I/O errors unless the
+    /// kernel falls back to the plain mass storage driver.
+    pub const UAS_QUIRK_BRIDGES: &[(&str, &str, &str)] = &[
+        ("152d", "0578", "JMicron JMS578"),
+        ("152d", "0583", "JMicron JMS583"),
+        ("152d", "1561", "JMicron JMS561"),
+        ("152d", "0567", "JMicron JMS567"),
+        ("174c", "55aa", "ASMedia ASM1051E/ASM1153E"),
+        ("174c", "1153", "ASMedia ASM1153"),
+        ("2537", "1066", "Norelsys NS1066"),
+        ("2537", "1068", "Norelsys NS1068"),
+        ("0bda", "9210", "Realtek RTL9210"),
+        ("1f75", "0621", "Innostor IS621"),
+    ];
+
+    /// Link speed of USB 2.0 high speed, in Mbit/s
+    pub const USB_HIGH_SPEED_MBPS: u32 = 480;
+
+    /// Lowest bcdUSB of a SuperSpeed capable device
+    ///
+    /// USB 2.0 devices with link power management report 2.10, as do most
+    /// USB 3 devices held to high speed, so 2.10 says nothing either way.
+    pub const SUPERSPEED_MIN_BCD_USB: u16 = 0x0300;
+}
+
+/// Per-operation working directory settings
+pub mod workdir {
+    /// Folder next to a destination that holds the working directories
+    pub const DIR_NAME: &str = ".work";
+}
+
+/// Flatpak and snap packaging settings
+pub mod sandbox {
+    /// Snap interfaces the app plugs, reported in the system info
+    pub const SNAP_PLUGS: &[&str] = &[
+        "udisks2",
+        "removable-media",
+        "hardware-observe",
+        "mount-observe",
+        "network",
+    ];
+
+    /// Snap interfaces without which a strictly confined snap cannot flash
+    pub const SNAP_REQUIRED_PLUGS: &[&str] = &["udisks2", "hardware-observe"];
+
+    /// Directories inside `$SNAP` searched for bundled helper tools, in order
+    pub const SNAP_BINARY_DIRS: &[&str] = &["usr/bin", "usr/sbin", "bin", "sbin"];
+}
+
+/// Host architecture settings
+pub mod host {
+    /// Linux directories searched for tools missing from PATH (sbin is often
+    /// not on a desktop user's PATH)
+    pub const LINUX_EXTRA_BINARY_DIRS: &[&str] = &["/usr/sbin", "/sbin", "/usr/local/sbin"];
+
+    /// macOS directories searched on Apple Silicon (Homebrew prefix first)
+    pub const MACOS_ARM64_EXTRA_BINARY_DIRS: &[&str] = &["/opt/homebrew/bin", "/usr/local/bin"];
+
+    /// macOS directories searched on Intel Macs
+    pub const MACOS_X64_EXTRA_BINARY_DIRS: &[&str] = &["/usr/local/bin"];
+
+    /// In-box Windows PowerShell, relative to %SystemRoot%
+    pub const WINDOWS_POWERSHELL_PATH: &str = "System32\\WindowsPowerShell\\v1.0\\powershell.exe";
+
+    /// Memory per decompression thread on ARM hosts (256 MB)
+    pub const THREAD_MEMORY_BUDGET: u64 = 256 * 1024 * 1024;
+}
+
+/// External tool settings
+pub mod process {
+    /// Timeout for quick queries (lsblk, findmnt, diskutil info, pmset)
+    pub const QUERY_TIMEOUT_SECS: u64 = 15;
+
+    /// Timeout for mounting and unmounting partitions
+    pub const MOUNT_TIMEOUT_SECS: u64 = 60;
+
+    /// Timeout for a system-wide sync, which can flush a lot of cached data
+    pub const SYNC_TIMEOUT_SECS: u64 = 300;
+
+    /// Timeout for discarding a whole device with blkdiscard
+    pub const DISCARD_TIMEOUT_SECS: u64 = 600;
 }
 
 /// HTTP client settings
@@ -111,6 +557,74 @@ pub mod http {
 
     /// Short timeout for quick requests like board info (10 seconds)
     pub const SHORT_TIMEOUT_SECS: u64 = 10;
+
+    /// Redirects followed per request, as reqwest does by default
+    pub const MAX_REDIRECTS: usize = 10;
+
+    /// Keychain entry of the proxy password; not a host name, so no download
+    /// credentials can take its place
+    pub const PROXY_KEYCHAIN_KEY: &str = "<proxy>";
+}
+
+/// Download mirror settings
+pub mod mirrors {
+    /// Armbian's download redirector, which sends each client to a mirror
+    pub const REDIRECTOR: &str = "https://dl.armbian.com/";
+
+    /// Mirrors serving the redirector's tree under a base URL, by region
+    pub const MIRRORS: &[(&str, &str)] = &[
+        ("europe", "https://mirrors.dotsrc.org/armbian-dl/"),
+        ("europe", "https://mirror.netcologne.de/armbian/dl/"),
+        ("north-america", "https://stpete-mirror.armbian.com/dl/"),
+        ("north-america", "https://armbian.tnahosting.net/dl/"),
+        (
+            "asia",
+            "https://mirrors.tuna.tsinghua.edu.cn/armbian-releases/",
+        ),
+        ("asia", "https://mirrors.bfsu.edu.cn/armbian-releases/"),
+    ];
+
+    /// Regions a user can prefer; "auto" leaves the choice to the redirector
+    pub const REGIONS: &[&str] = &["auto", "europe", "north-america", "asia"];
+
+    /// Timeout of a mirror latency probe (seconds)
+    pub const PROBE_TIMEOUT_SECS: u64 = 5;
+}
+
+/// Settings file configuration
+pub mod settings {
+    /// Store file holding the settings
+    pub const FILE_NAME: &str = "settings.json";
+
+    /// Suffixes of the new content being written, the last good copy and a
+    /// broken file set aside, appended to the settings file name
+    pub const TEMP_SUFFIX: &str = ".tmp";
+    pub const BACKUP_SUFFIX: &str = ".bak";
+    pub const CORRUPT_SUFFIX: &str = ".corrupt";
+
+    /// Layout version of the settings, see `settings::MIGRATIONS`
+    pub const VERSION: u32 = 2;
+
+    /// Store key holding the layout version
+    pub const VERSION_KEY: &str = "settings_version";
+}
+
+/// Image catalog trust settings
+pub mod catalog {
+    /// Minisign public key (base64) the catalog signature is checked against
+    ///
+    /// Armbian does not sign the catalog yet. While this is `None` the
+    /// signature is not fetched and the catalog is only protected by HTTPS.
+    pub const SIGNING_KEY: Option<&str> = None;
+
+    /// Origin tag of images from the official catalog
+    pub const OFFICIAL_ORIGIN: &str = "armbian";
+
+    /// Most additional catalogs that can be configured
+    pub const MAX_SOURCES: usize = 8;
+
+    /// Longest name of an additional catalog (characters)
+    pub const MAX_SOURCE_NAME_LEN: usize = 40;
 }
 
 /// Image filtering constants
@@ -123,6 +637,33 @@ pub mod images {
 
     /// Temporary download file suffix
     pub const DOWNLOAD_SUFFIX: &str = ".downloading";
+
+    /// Boards whose newest image is older than this are flagged as stale (days)
+    pub const STALE_AFTER_DAYS: i64 = 365;
+
+    /// Extensions of checksum files looked for next to a custom image
+    pub const SHA_FILE_EXTENSIONS: [&str; 2] = ["sha", "sha256"];
+
+    /// Larger files next to a custom image are not checksum files (64 KB)
+    pub const MAX_SHA_FILE_SIZE: u64 = 64 * 1024;
+
+    /// Distribution releases past their end of life, with the date (YYYY-MM-DD)
+    ///
+    /// Debian releases count until the end of LTS, Ubuntu releases until the
+    /// end of standard support. Releases with a future date are flagged once
+    /// that date has passed.
+    pub const EOL_RELEASES: [(&str, &str); 10] = [
+        ("stretch", "2022-06-30"),
+        ("buster", "2024-06-30"),
+        ("bullseye", "2026-08-31"),
+        ("bionic", "2023-05-31"),
+        ("focal", "2025-05-31"),
+        ("kinetic", "2023-07-20"),
+        ("lunar", "2024-01-25"),
+        ("mantic", "2024-07-11"),
+        ("oracular", "2025-07-10"),
+        ("plucky", "2026-01-15"),
+    ];
 }
 
 /// Cache management settings
@@ -138,4 +679,73 @@ pub mod cache {
 
     /// Maximum consecutive flash failures before auto-deleting cached image
     pub const MAX_FLASH_FAILURES: u32 = 3;
+
+    /// Write buffer of image files produced from a device or another image (4 MB)
+    pub const COPY_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+}
+
+/// Credentials for gated download areas
+pub mod credentials {
+    /// File listing the hosts that have credentials, in the config directory
+    pub const FILE_NAME: &str = "download-credentials.json";
+
+    /// Service name the secrets are stored under in the OS keychain
+    pub const KEYCHAIN_SERVICE: &str = "armbian-imager-download";
+}
+
+/// Job registry settings
+pub mod jobs {
+    /// Finished jobs kept for status queries
+    pub const MAX_FINISHED: usize = 50;
+}
+
+/// Download queue settings
+pub mod queue {
+    /// Queue file name in the cache directory
+    pub const FILE_NAME: &str = "download-queue.json";
+
+    /// Suffix of the new queue being written, after the file name and the
+    /// process ID so that two instances never write the same file
+    pub const TEMP_SUFFIX: &str = ".tmp";
+
+    /// Queued downloads running at once by default
+    pub const DEFAULT_MAX_PARALLEL: usize = 1;
+
+    /// Most queued downloads allowed to run at once
+    pub const MAX_PARALLEL: usize = 3;
+}
+
+/// Completion cue configuration
+pub mod cues {
+    /// Sound theme event and fallback file for a successful operation (Linux)
+    pub const LINUX_SUCCESS_SOUND: (&str, &str) = (
+        "complete",
+        "/usr/share/sounds/freedesktop/stereo/complete.oga",
+    );
+
+    /// Sound theme event and fallback file for a failed operation (Linux)
+    pub const LINUX_FAILURE_SOUND: (&str, &str) = (
+        "dialog-error",
+        "/usr/share/sounds/freedesktop/stereo/dialog-error.oga",
+    );
+
+    /// System sounds for a successful and a failed operation (macOS)
+    pub const MACOS_SUCCESS_SOUND: &str = "/System/Library/Sounds/Glass.aiff";
+    pub const MACOS_FAILURE_SOUND: &str = "/System/Library/Sounds/Basso.aiff";
+
+    /// Longest a sound player may run
+    pub const SOUND_TIMEOUT_SECS: u64 = 10;
+}
+
+/// Fleet mode configuration
+pub mod fleet {
+    /// Devices written at the same time; further insertions wait
+    pub const MAX_PARALLEL_JOBS: usize = 4;
+
+    /// Jobs kept in the status shown in the UI
+    pub const MAX_JOBS_KEPT: usize = 100;
+
+    /// Largest device written unless the criteria name another bound (64 GB);
+    /// keeps backup drives and the like from being wiped unasked
+    pub const DEFAULT_MAX_SIZE_BYTES: u64 = 64_000_000_000;
 }
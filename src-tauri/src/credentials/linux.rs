@@ -0,0 +1,132 @@
+//! Linux keychain: the freedesktop Secret Service (GNOME Keyring, KWallet)
+//!
+//! Items are looked up by their `service` and `host` attributes. Secrets
+//! are transferred with the "plain" algorithm, which is fine on the
+//! private session bus. A locked keyring is reported as an error rather
+//! than prompting for the password.
+
+use std::collections::HashMap;
+
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+use crate::config;
+
+const DESTINATION: &str = "org.freedesktop.secrets";
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const DEFAULT_COLLECTION: &str = "/org/freedesktop/secrets/aliases/default";
+
+const LOCKED: &str = "The keyring is locked; unlock it and try again";
+
+/// Secret as (session, parameters, value, content type)
+type Secret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+fn dbus_error(e: zbus::Error) -> String {
+    format!("Secret Service error: {}", e)
+}
+
+fn attributes(host: &str) -> HashMap<&str, &str> {
+    HashMap::from([
+        ("service", config::credentials::KEYCHAIN_SERVICE),
+        ("host", host),
+    ])
+}
+
+/// Session bus connection with an open Secret Service session
+struct SecretService {
+    connection: Connection,
+    session: OwnedObjectPath,
+}
+
+impl SecretService {
+    fn connect() -> Result<Self, String> {
+        let connection =
+            Connection::session().map_err(|e| format!("Secret Service unavailable: {}", e))?;
+        let service = Proxy::new(
+            &connection,
+            DESTINATION,
+            SERVICE_PATH,
+            "org.freedesktop.Secret.Service",
+        )
+        .map_err(dbus_error)?;
+        let (_, session): (OwnedValue, OwnedObjectPath) = service
+            .call("OpenSession", &("plain", Value::from("")))
+            .map_err(dbus_error)?;
+        Ok(Self {
+            connection,
+            session,
+        })
+    }
+
+    fn proxy<'a>(&'a self, path: &'a str, interface: &'a str) -> Result<Proxy<'a>, String> {
+        Proxy::new(&self.connection, DESTINATION, path, interface).map_err(dbus_error)
+    }
+
+    /// Unlocked items stored for `host`
+    fn find(&self, host: &str) -> Result<Vec<OwnedObjectPath>, String> {
+        let service = self.proxy(SERVICE_PATH, "org.freedesktop.Secret.Service")?;
+        let (unlocked, locked): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) = service
+            .call("SearchItems", &(attributes(host),))
+            .map_err(dbus_error)?;
+        if unlocked.is_empty() && !locked.is_empty() {
+            return Err(LOCKED.to_string());
+        }
+        Ok(unlocked)
+    }
+}
+
+pub fn store_secret(host: &str, secret: &str) -> Result<(), String> {
+    let service = SecretService::connect()?;
+    let collection = service.proxy(DEFAULT_COLLECTION, "org.freedesktop.Secret.Collection")?;
+
+    let mut properties: HashMap<&str, Value> = HashMap::new();
+    properties.insert(
+        "org.freedesktop.Secret.Item.Label",
+        Value::from(format!("{}: {}", config::app::NAME, host)),
+    );
+    properties.insert(
+        "org.freedesktop.Secret.Item.Attributes",
+        Value::from(attributes(host)),
+    );
+    let secret: Secret = (
+        service.session.clone(),
+        Vec::new(),
+        secret.as_bytes().to_vec(),
+        "text/plain".to_string(),
+    );
+
+    // Replaces an item with the same attributes
+    let (_, prompt): (OwnedObjectPath, OwnedObjectPath) = collection
+        .call("CreateItem", &(properties, secret, true))
+        .map_err(dbus_error)?;
+    if prompt.as_str() != "/" {
+        return Err(LOCKED.to_string());
+    }
+    Ok(())
+}
+
+pub fn load_secret(host: &str) -> Result<Option<String>, String> {
+    let service = SecretService::connect()?;
+    let Some(item) = service.find(host)?.into_iter().next() else {
+        return Ok(None);
+    };
+    let item = service.proxy(item.as_str(), "org.freedesktop.Secret.Item")?;
+    let (_, _, value, _): Secret = item
+        .call("GetSecret", &(service.session.clone(),))
+        .map_err(dbus_error)?;
+    String::from_utf8(value)
+        .map(Some)
+        .map_err(|_| "Stored secret is not text".to_string())
+}
+
+pub fn delete_secret(host: &str) -> Result<(), String> {
+    let service = SecretService::connect()?;
+    for item in service.find(host)? {
+        let item = service.proxy(item.as_str(), "org.freedesktop.Secret.Item")?;
+        let prompt: OwnedObjectPath = item.call("Delete", &()).map_err(dbus_error)?;
+        if prompt.as_str() != "/" {
+            return Err(LOCKED.to_string());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! macOS keychain: generic passwords in the login Keychain
+
+use security_framework::passwords::{
+    delete_generic_password, get_generic_password, set_generic_password,
+};
+
+use crate::config;
+
+/// errSecItemNotFound
+const ITEM_NOT_FOUND: i32 = -25300;
+
+pub fn store_secret(host: &str, secret: &str) -> Result<(), String> {
+    set_generic_password(
+        config::credentials::KEYCHAIN_SERVICE,
+        host,
+        secret.as_bytes(),
+    )
+    .map_err(|e| format!("Failed to store credentials in the Keychain: {}", e))
+}
+
+pub fn load_secret(host: &str) -> Result<Option<String>, String> {
+    match get_generic_password(config::credentials::KEYCHAIN_SERVICE, host) {
+        Ok(value) => String::from_utf8(value)
+            .map(Some)
+            .map_err(|_| "Stored secret is not text".to_string()),
+        Err(e) if e.code() == ITEM_NOT_FOUND => Ok(None),
+        Err(e) => Err(format!(
+            "Failed to read credentials from the Keychain: {}",
+            e
+        )),
+    }
+}
+
+pub fn delete_secret(host: &str) -> Result<(), String> {
+    match delete_generic_password(config::credentials::KEYCHAIN_SERVICE, host) {
+        Ok(()) => Ok(()),
+        Err(e) if e.code() == ITEM_NOT_FOUND => Ok(()),
+        Err(e) => Err(format!(
+            "Failed to delete credentials from the Keychain: {}",
+            e
+        )),
+    }
+}
@@ -0,0 +1,330 @@
+//! Credentials for gated download areas
+//!
+//! Pre-release and partner images can sit behind a token. A source is
+//! configured per host with either a bearer token or a user name and
+//! password for basic auth. The list of sources is kept in the config
+//! directory, so clearing the cache leaves it alone; the secrets only in
+//! the OS keychain (Keychain on macOS,
+//! Credential Manager on Windows, the Secret Service on Linux).
+//!
+//! Credentials are attached only to HTTPS requests whose host matches a
+//! source. reqwest drops the Authorization header when a redirect leaves
+//! the host, so a redirector handing off to a public mirror never passes
+//! it on.
+//!
+//! Thread Safety:
+//! Reads and writes of the sources file are serialized by a global Mutex.
+
+#[cfg(target_os = "linux")]
+mod linux;
+
+#[cfg(target_os = "macos")]
+mod macos;
+
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(target_os = "linux")]
+use self::linux as keychain;
+
+#[cfg(target_os = "macos")]
+use self::macos as keychain;
+
+#[cfg(target_os = "windows")]
+use self::windows as keychain;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use reqwest::RequestBuilder;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::utils::{get_config_dir, move_from_cache_dir, run_blocking};
+use crate::{log_debug, log_info, log_warn};
+
+const MODULE: &str = "credentials";
+
+/// Global mutex to serialize sources file access
+static SOURCES_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// How a source authenticates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthScheme {
+    Bearer,
+    Basic,
+}
+
+/// A download host that needs credentials; the secret is in the keychain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthSource {
+    /// Host name, or "*.example.com" for every subdomain of example.com
+    pub host: String,
+    pub scheme: AuthScheme,
+    /// User name for basic auth
+    pub username: Option<String>,
+}
+
+/// Lowercase a host pattern and check that it is a bare host name
+pub fn normalize_host(host: &str) -> Result<String, String> {
+    let host = host.trim().trim_end_matches('.').to_ascii_lowercase();
+    let name = host.strip_prefix("*.").unwrap_or(&host);
+    let valid = !name.is_empty()
+        && name.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    if !valid {
+        return Err(format!("Not a host name: {}", host));
+    }
+    Ok(host)
+}
+
+/// Whether a source host pattern covers `host`
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.ends_with('.') && sub.len() > 1),
+        None => pattern == host,
+    }
+}
+
+/// Source whose credentials a request to `url` gets, exact hosts before wildcards
+fn source_for<'a>(sources: &'a [AuthSource], url: &str) -> Option<&'a AuthSource> {
+    let url = reqwest::Url::parse(url).ok()?;
+    // Never send credentials in the clear
+    if url.scheme() != "https" {
+        return None;
+    }
+    let host = url.host_str()?.to_ascii_lowercase();
+    sources
+        .iter()
+        .find(|source| source.host == host)
+        .or_else(|| {
+            sources
+                .iter()
+                .find(|source| host_matches(&source.host, &host))
+        })
+}
+
+/// Get the sources file path
+fn sources_path() -> PathBuf {
+    get_config_dir(config::app::NAME).join(config::credentials::FILE_NAME)
+}
+
+/// Move a sources file left in the cache directory by older versions
+fn migrate_from_cache(path: &Path) {
+    match move_from_cache_dir(config::app::NAME, path) {
+        Ok(false) => {}
+        Ok(true) => log_info!(
+            MODULE,
+            "Moved download credentials list to {}",
+            path.display()
+        ),
+        Err(e) => log_warn!(
+            MODULE,
+            "Failed to move download credentials list from the cache: {}",
+            e
+        ),
+    }
+}
+
+fn load_internal() -> Vec<AuthSource> {
+    let path = sources_path();
+    migrate_from_cache(&path);
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            log_warn!(
+                MODULE,
+                "Ignoring unreadable download credentials list: {}",
+                e
+            );
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn save_internal(sources: &[AuthSource]) -> Result<(), String> {
+    let path = sources_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create credentials directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(sources)
+        .map_err(|e| format!("Failed to serialize download credentials: {}", e))?;
+    fs::write(&path, content).map_err(|e| format!("Failed to write download credentials: {}", e))
+}
+
+/// Configured sources, without their secrets
+pub fn load_sources() -> Vec<AuthSource> {
+    let _lock = SOURCES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load_internal()
+}
+
+/// Add or replace the credentials of a host
+///
+/// `secret` is the bearer token, or the password for basic auth.
+pub fn set_source(
+    host: &str,
+    scheme: AuthScheme,
+    username: Option<&str>,
+    secret: &str,
+) -> Result<AuthSource, String> {
+    let host = normalize_host(host)?;
+    let username = username.map(str::trim).filter(|u| !u.is_empty());
+    if scheme == AuthScheme::Basic && username.is_none() {
+        return Err("Basic authentication needs a user name".to_string());
+    }
+    if secret.is_empty() {
+        return Err("The token or password is empty".to_string());
+    }
+
+    let source = AuthSource {
+        host,
+        scheme,
+        username: match scheme {
+            AuthScheme::Basic => username.map(|u| u.to_string()),
+            AuthScheme::Bearer => None,
+        },
+    };
+
+    let _lock = SOURCES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    keychain::store_secret(&source.host, secret)?;
+    let mut sources = load_internal();
+    sources.retain(|s| s.host != source.host);
+    sources.push(source.clone());
+    save_internal(&sources)?;
+    log_info!(
+        MODULE,
+        "Stored {:?} credentials for {}",
+        source.scheme,
+        source.host
+    );
+    Ok(source)
+}
+
+/// Forget the credentials of a host
+pub fn remove_source(host: &str) -> Result<(), String> {
+    let host = normalize_host(host)?;
+    let _lock = SOURCES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut sources = load_internal();
+    sources.retain(|s| s.host != host);
+    save_internal(&sources)?;
+    keychain::delete_secret(&host)?;
+    log_info!(MODULE, "Removed credentials for {}", host);
+    Ok(())
+}
+
+/// Store the password of the manually configured proxy
+pub fn store_proxy_password(password: &str) -> Result<(), String> {
+    keychain::store_secret(config::http::PROXY_KEYCHAIN_KEY, password)
+}
+
+/// Password of the manually configured proxy, if one is stored
+pub fn load_proxy_password() -> Result<Option<String>, String> {
+    keychain::load_secret(config::http::PROXY_KEYCHAIN_KEY)
+}
+
+/// Forget the password of the manually configured proxy
+pub fn delete_proxy_password() -> Result<(), String> {
+    keychain::delete_secret(config::http::PROXY_KEYCHAIN_KEY)
+}
+
+/// Source and secret for a request to `url`, if one is configured
+fn credentials_for(url: &str) -> Option<(AuthSource, String)> {
+    let source = source_for(&load_sources(), url)?.clone();
+    match keychain::load_secret(&source.host) {
+        Ok(Some(secret)) => Some((source, secret)),
+        Ok(None) => {
+            log_warn!(MODULE, "No secret in the keychain for {}", source.host);
+            None
+        }
+        Err(e) => {
+            log_warn!(
+                MODULE,
+                "Failed to read credentials for {}: {}",
+                source.host,
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Attach the credentials configured for the host of `url`, if any
+pub async fn authorize(request: RequestBuilder, url: &str) -> RequestBuilder {
+    let lookup_url = url.to_string();
+    let credentials = run_blocking(move || Ok(credentials_for(&lookup_url)))
+        .await
+        .ok()
+        .flatten();
+
+    match credentials {
+        Some((source, secret)) => {
+            log_debug!(
+                MODULE,
+                "Authenticating to {} ({:?})",
+                source.host,
+                source.scheme
+            );
+            match source.scheme {
+                AuthScheme::Bearer => request.bearer_auth(secret),
+                AuthScheme::Basic => {
+                    request.basic_auth(source.username.unwrap_or_default(), Some(secret))
+                }
+            }
+        }
+        None => request,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(host: &str) -> AuthSource {
+        AuthSource {
+            host: host.to_string(),
+            scheme: AuthScheme::Bearer,
+            username: None,
+        }
+    }
+
+    #[test]
+    fn test_normalize_host() {
+        assert_eq!(
+            normalize_host(" Beta.Armbian.com. ").unwrap(),
+            "beta.armbian.com"
+        );
+        assert_eq!(normalize_host("*.armbian.com").unwrap(), "*.armbian.com");
+        assert!(normalize_host("https://armbian.com").is_err());
+        assert!(normalize_host("armbian.com/path").is_err());
+        assert!(normalize_host("*.").is_err());
+        assert!(normalize_host("").is_err());
+    }
+
+    #[test]
+    fn test_source_for() {
+        let sources = vec![source("*.armbian.com"), source("partner.armbian.com")];
+
+        let matched = |url| source_for(&sources, url).map(|s| s.host.as_str());
+        // Exact hosts win over wildcards
+        assert_eq!(
+            matched("https://partner.armbian.com/x.img.xz"),
+            Some("partner.armbian.com")
+        );
+        assert_eq!(
+            matched("https://dl.armbian.com/x.img.xz"),
+            Some("*.armbian.com")
+        );
+        // The wildcard covers subdomains only, and lookalike domains not at all
+        assert_eq!(matched("https://armbian.com/x.img.xz"), None);
+        assert_eq!(matched("https://evilarmbian.com/x.img.xz"), None);
+        // Not over plain HTTP
+        assert_eq!(matched("http://dl.armbian.com/x.img.xz"), None);
+    }
+}
@@ -0,0 +1,100 @@
+//! Windows keychain: generic credentials in the Credential Manager
+
+use std::ffi::OsStr;
+use std::os::windows::ffi::OsStrExt;
+use std::ptr;
+
+use windows_sys::Win32::Foundation::{GetLastError, ERROR_NOT_FOUND, FILETIME};
+use windows_sys::Win32::Security::Credentials::{
+    CredDeleteW, CredFree, CredReadW, CredWriteW, CREDENTIALW, CRED_PERSIST_LOCAL_MACHINE,
+    CRED_TYPE_GENERIC,
+};
+
+use crate::config;
+
+/// Credential Manager target name of a host, NUL terminated
+fn target(host: &str) -> Vec<u16> {
+    OsStr::new(&format!(
+        "{}:{}",
+        config::credentials::KEYCHAIN_SERVICE,
+        host
+    ))
+    .encode_wide()
+    .chain(Some(0))
+    .collect()
+}
+
+pub fn store_secret(host: &str, secret: &str) -> Result<(), String> {
+    let mut target = target(host);
+    let mut blob = secret.as_bytes().to_vec();
+    let credential = CREDENTIALW {
+        Flags: 0,
+        Type: CRED_TYPE_GENERIC,
+        TargetName: target.as_mut_ptr(),
+        Comment: ptr::null_mut(),
+        LastWritten: FILETIME {
+            dwLowDateTime: 0,
+            dwHighDateTime: 0,
+        },
+        CredentialBlobSize: blob.len() as u32,
+        CredentialBlob: blob.as_mut_ptr(),
+        Persist: CRED_PERSIST_LOCAL_MACHINE,
+        AttributeCount: 0,
+        Attributes: ptr::null_mut(),
+        TargetAlias: ptr::null_mut(),
+        UserName: ptr::null_mut(),
+    };
+
+    // SAFETY: every pointer in `credential` outlives the call
+    if unsafe { CredWriteW(&credential, 0) } == 0 {
+        return Err(format!(
+            "Failed to store credentials in the Credential Manager (error {})",
+            unsafe { GetLastError() }
+        ));
+    }
+    Ok(())
+}
+
+pub fn load_secret(host: &str) -> Result<Option<String>, String> {
+    let target = target(host);
+    let mut credential: *mut CREDENTIALW = ptr::null_mut();
+
+    // SAFETY: on success `credential` points to a buffer freed with CredFree below
+    if unsafe { CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &mut credential) } == 0 {
+        let error = unsafe { GetLastError() };
+        if error == ERROR_NOT_FOUND {
+            return Ok(None);
+        }
+        return Err(format!(
+            "Failed to read credentials from the Credential Manager (error {})",
+            error
+        ));
+    }
+
+    let value = unsafe {
+        let blob = std::slice::from_raw_parts(
+            (*credential).CredentialBlob,
+            (*credential).CredentialBlobSize as usize,
+        )
+        .to_vec();
+        CredFree(credential as *const _);
+        blob
+    };
+    String::from_utf8(value)
+        .map(Some)
+        .map_err(|_| "Stored secret is not text".to_string())
+}
+
+pub fn delete_secret(host: &str) -> Result<(), String> {
+    let target = target(host);
+    if unsafe { CredDeleteW(target.as_ptr(), CRED_TYPE_GENERIC, 0) } == 0 {
+        let error = unsafe { GetLastError() };
+        if error != ERROR_NOT_FOUND {
+            return Err(format!(
+                "Failed to delete credentials from the Credential Manager (error {})",
+                error
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,245 @@
+//! Completion cues
+//!
+//! Operators running fleet mode or long flashes don't watch the screen, so a
+//! finished flash or verification is announced: a system sound, a request
+//! for attention (flashing taskbar button, bouncing dock icon) and a badge on
+//! the app icon. Success and failure use different sounds and attention
+//! levels; cancelled operations stay silent. The badge is cleared once a
+//! window of the app gets the focus again.
+//!
+//! Cues subscribe to `FlashFinished` and `VerifyFinished` on the event bus, so
+//! every operation that publishes those is covered, whoever started it.
+
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, UserAttentionType, WebviewWindow};
+
+use crate::commands::settings::completion_cues;
+use crate::events::{self, AppEvent};
+use crate::{log_debug, log_warn};
+
+const MODULE: &str = "cues";
+
+/// Operations finished since a window last had the focus
+static UNSEEN: AtomicI64 = AtomicI64::new(0);
+
+/// Which cues announce a finished operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletionCues {
+    pub sound: bool,
+    /// Flash the taskbar button or bounce the dock icon
+    pub attention: bool,
+    /// Badge on the app icon until the app is focused
+    pub badge: bool,
+}
+
+impl Default for CompletionCues {
+    fn default() -> Self {
+        Self {
+            sound: false,
+            attention: true,
+            badge: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CueOutcome {
+    Success,
+    Failure,
+}
+
+/// Announce finished flashes and verifications from now on
+pub fn start_completion_cues(app: AppHandle) {
+    events::subscribe(move |event| {
+        if let Some(outcome) = outcome_of(event) {
+            play_cue(&app, outcome, completion_cues(&app));
+        }
+    });
+}
+
+/// Outcome announced for an event, None for events without a cue
+fn outcome_of(event: &AppEvent) -> Option<CueOutcome> {
+    match event {
+        AppEvent::FlashFinished {
+            cancelled: true, ..
+        } => None,
+        AppEvent::FlashFinished { success, .. } | AppEvent::VerifyFinished { success, .. } => {
+            Some(if *success {
+                CueOutcome::Success
+            } else {
+                CueOutcome::Failure
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Give the cues enabled in `cues` for an outcome
+pub fn play_cue(app: &AppHandle, outcome: CueOutcome, cues: CompletionCues) {
+    log_debug!(MODULE, "Completion cue: {:?} ({:?})", outcome, cues);
+    if cues.sound {
+        // Players take a moment; event handlers must return quickly
+        let spawned = std::thread::Builder::new()
+            .name("completion-sound".to_string())
+            .spawn(move || {
+                if let Err(e) = play_sound(outcome) {
+                    log_warn!(MODULE, "Failed to play completion sound: {}", e);
+                }
+            });
+        if let Err(e) = spawned {
+            log_warn!(MODULE, "Failed to start completion sound: {}", e);
+        }
+    }
+
+    let windows = app.webview_windows();
+    if cues.attention {
+        let level = match outcome {
+            CueOutcome::Success => UserAttentionType::Informational,
+            CueOutcome::Failure => UserAttentionType::Critical,
+        };
+        for window in windows.values() {
+            if let Err(e) = window.request_user_attention(Some(level)) {
+                log_debug!(MODULE, "Attention request failed: {}", e);
+            }
+        }
+    }
+    if cues.badge {
+        let unseen = UNSEEN.fetch_add(1, Ordering::SeqCst) + 1;
+        for window in windows.values() {
+            set_badge(window, outcome, unseen);
+        }
+    }
+}
+
+/// Remove the badge once the user is back in the app
+pub fn clear_badge(window: &WebviewWindow) {
+    if UNSEEN.swap(0, Ordering::SeqCst) == 0 {
+        return;
+    }
+    #[cfg(target_os = "macos")]
+    let result = window.set_badge_label(None);
+    #[cfg(target_os = "linux")]
+    let result = window.set_badge_count(None);
+    #[cfg(target_os = "windows")]
+    let result: tauri::Result<()> = {
+        let _ = window;
+        Ok(())
+    };
+    if let Err(e) = result {
+        log_debug!(MODULE, "Failed to clear badge: {}", e);
+    }
+}
+
+/// Badge the app icon: a mark per outcome on macOS, the count of unseen
+/// operations on Linux; Windows has no badge without an overlay icon
+fn set_badge(window: &WebviewWindow, outcome: CueOutcome, unseen: i64) {
+    #[cfg(target_os = "macos")]
+    let result = {
+        let _ = unseen;
+        window.set_badge_label(Some(
+            match outcome {
+                CueOutcome::Success => "✓",
+                CueOutcome::Failure => "!",
+            }
+            .to_string(),
+        ))
+    };
+    #[cfg(target_os = "linux")]
+    let result = {
+        let _ = outcome;
+        window.set_badge_count(Some(unseen))
+    };
+    #[cfg(target_os = "windows")]
+    let result: tauri::Result<()> = {
+        let _ = (window, outcome, unseen);
+        Ok(())
+    };
+    if let Err(e) = result {
+        log_debug!(MODULE, "Failed to set badge: {}", e);
+    }
+}
+
+/// Play the desktop's sound for the outcome through canberra, falling back to
+/// the freedesktop sound files
+#[cfg(target_os = "linux")]
+fn play_sound(outcome: CueOutcome) -> Result<(), String> {
+    use crate::config;
+    use crate::utils::run_command;
+    use std::time::Duration;
+
+    let (event_id, file) = match outcome {
+        CueOutcome::Success => config::cues::LINUX_SUCCESS_SOUND,
+        CueOutcome::Failure => config::cues::LINUX_FAILURE_SOUND,
+    };
+    let timeout = Duration::from_secs(config::cues::SOUND_TIMEOUT_SECS);
+    match run_command("canberra-gtk-play", &["-i", event_id], timeout) {
+        Ok(output) if output.success() => Ok(()),
+        _ => run_command("paplay", &[file], timeout)?.check().map(|_| ()),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn play_sound(outcome: CueOutcome) -> Result<(), String> {
+    use crate::config;
+    use crate::utils::run_command;
+    use std::time::Duration;
+
+    let file = match outcome {
+        CueOutcome::Success => config::cues::MACOS_SUCCESS_SOUND,
+        CueOutcome::Failure => config::cues::MACOS_FAILURE_SOUND,
+    };
+    let timeout = Duration::from_secs(config::cues::SOUND_TIMEOUT_SECS);
+    run_command("afplay", &[file], timeout)?.check().map(|_| ())
+}
+
+#[cfg(target_os = "windows")]
+fn play_sound(outcome: CueOutcome) -> Result<(), String> {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{MessageBeep, MB_ICONASTERISK, MB_ICONHAND};
+
+    let sound = match outcome {
+        CueOutcome::Success => MB_ICONASTERISK,
+        CueOutcome::Failure => MB_ICONHAND,
+    };
+    // SAFETY: MessageBeep only queues a system sound
+    if unsafe { MessageBeep(sound) } == 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flash_finished(success: bool, cancelled: bool) -> AppEvent {
+        AppEvent::FlashFinished {
+            device_path: "/dev/sdb".to_string(),
+            success,
+            cancelled,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_outcome_of() {
+        assert_eq!(
+            outcome_of(&flash_finished(true, false)),
+            Some(CueOutcome::Success)
+        );
+        assert_eq!(
+            outcome_of(&flash_finished(false, false)),
+            Some(CueOutcome::Failure)
+        );
+        assert_eq!(outcome_of(&flash_finished(false, true)), None);
+        let verified = AppEvent::VerifyFinished {
+            device_path: "/dev/sdb".to_string(),
+            success: false,
+            cached: false,
+        };
+        assert_eq!(outcome_of(&verified), Some(CueOutcome::Failure));
+        assert_eq!(outcome_of(&AppEvent::QueueChanged), None);
+    }
+}
@@ -0,0 +1,905 @@
+//! First-boot customization
+//!
+//! After a flash, the user can preset the host name, the default user and
+//! whether the SSH server runs, so a headless board is reachable on first
+//! boot without a serial console. The first partition of the device is
+//! mounted and Armbian's first-login presets are written to
+//! `armbian_first_run.txt` in its boot directory.
+//!
+//! When that partition is the root filesystem (the usual Armbian layout),
+//! the presets also go to `/root/.not_logged_in_yet`, which the first-login
+//! script sources, and the host name (with its `/etc/hosts` entry) and SSH
+//! unit link are set directly. Images with a separate boot partition only
+//! get the preset file; settings that need the root filesystem are reported
+//! as skipped.
+//!
+//! The files of a root filesystem belong to root. On Linux, an app that is
+//! not running as root mounts it through UDisks2 like any user, then runs
+//! itself through pkexec as the elevated writer to change the files. The
+//! request goes through a file only the user can read, so no password shows
+//! up in a process list. Since the writer runs as root, no path in the image
+//! is followed through a symbolic link: an image could otherwise point
+//! `etc/hostname` at a file of the host.
+//!
+//! Wi-Fi credentials are written the same way, as the `FR_net_*` variables
+//! Armbian's first-run service reads to join a network before anyone logs
+//! in. Both kinds of settings are merged into the files, so applying one
+//! keeps what the other wrote.
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::flash::{boot_dir, with_first_partition};
+use crate::{log_error, log_info, log_warn};
+
+const MODULE: &str = "customization";
+
+/// Settings applied to a written device, as chosen in the UI
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Customization {
+    pub hostname: Option<String>,
+    /// Default user created on first login
+    pub user_name: Option<String>,
+    /// Password of the default user, also used for root
+    pub password: Option<String>,
+    /// Start the SSH server at boot
+    pub ssh_enabled: bool,
+}
+
+/// Wi-Fi network a headless board joins on first boot
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    pub wifi_ssid: String,
+    /// WPA passphrase or 64-digit hex key, None for an open network
+    pub wifi_psk: Option<String>,
+    /// ISO 3166 country code for the regulatory domain
+    pub country_code: Option<String>,
+}
+
+/// A shell variable assignment in a preset file
+type Preset = (&'static str, String);
+
+/// What `apply_customization` changed on the device
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CustomizationReport {
+    /// Files written, relative to the partition root
+    pub written: Vec<String>,
+    /// Files removed, relative to the partition root
+    #[serde(default)]
+    pub removed: Vec<String>,
+    /// Settings that need the root filesystem, which is not on the first partition
+    pub skipped: Vec<String>,
+}
+
+/// Settings written to the first partition in one go
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum FirstBoot {
+    Customization(Customization),
+    Network(NetworkConfig),
+}
+
+impl FirstBoot {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            FirstBoot::Customization(customization) => customization.validate(),
+            FirstBoot::Network(network) => network.validate(),
+        }
+    }
+
+    fn write(&self, mount_point: &Path) -> Result<CustomizationReport, String> {
+        match self {
+            FirstBoot::Customization(customization) => {
+                write_customization(mount_point, customization)
+            }
+            FirstBoot::Network(network) => write_network_config(mount_point, network),
+        }
+    }
+}
+
+/// Request to the elevated writer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ElevatedRequest {
+    mount_point: PathBuf,
+    settings: FirstBoot,
+}
+
+impl Customization {
+    /// Check the values before anything is written to the device
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(hostname) = &self.hostname {
+            if !is_valid_hostname(hostname) {
+                return Err(format!("Invalid host name: {}", hostname));
+            }
+        }
+        if let Some(user_name) = &self.user_name {
+            if !is_valid_user_name(user_name) {
+                return Err(format!("Invalid user name: {}", user_name));
+            }
+            if self.password.is_none() {
+                return Err("A password is required for the default user".to_string());
+            }
+        }
+        if let Some(password) = &self.password {
+            if password.is_empty() || password.chars().any(char::is_control) {
+                return Err(
+                    "The password must not be empty or contain control characters".to_string(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Preset variables read by Armbian's first-login script
+    fn presets(&self) -> Vec<Preset> {
+        let mut presets = Vec::new();
+        if let Some(password) = &self.password {
+            presets.push(("PRESET_ROOT_PASSWORD", password.clone()));
+        }
+        if let Some(user_name) = &self.user_name {
+            presets.push(("PRESET_USER_NAME", user_name.clone()));
+            presets.push(("PRESET_DEFAULT_REALNAME", user_name.clone()));
+            if let Some(password) = &self.password {
+                presets.push(("PRESET_USER_PASSWORD", password.clone()));
+            }
+        }
+        presets
+    }
+}
+
+impl NetworkConfig {
+    /// Check the values before anything is written to the device
+    pub fn validate(&self) -> Result<(), String> {
+        let ssid_len = self.wifi_ssid.len();
+        if !(1..=config::customization::MAX_SSID_LEN).contains(&ssid_len)
+            || self.wifi_ssid.chars().any(char::is_control)
+        {
+            return Err(format!("Invalid Wi-Fi network name: {}", self.wifi_ssid));
+        }
+        if let Some(psk) = &self.wifi_psk {
+            if !is_valid_psk(psk) {
+                return Err(
+                    "The Wi-Fi password must be 8 to 63 characters or 64 hex digits".to_string(),
+                );
+            }
+        }
+        if let Some(country) = &self.country_code {
+            if country.len() != 2 || !country.chars().all(|c| c.is_ascii_uppercase()) {
+                return Err(format!("Invalid country code: {}", country));
+            }
+        }
+        Ok(())
+    }
+
+    /// Variables for the first-run service (`FR_net_*`) and the first-login
+    /// script (`PRESET_NET_*`)
+    fn presets(&self) -> (Vec<Preset>, Vec<Preset>) {
+        let key = self.wifi_psk.clone().unwrap_or_default();
+        let country = self.country_code.clone().unwrap_or_default();
+        let first_run = vec![
+            (
+                "FR_general_delete_this_file_after_completion",
+                "1".to_string(),
+            ),
+            ("FR_net_change_defaults", "1".to_string()),
+            ("FR_net_wifi_enabled", "1".to_string()),
+            ("FR_net_wifi_ssid", self.wifi_ssid.clone()),
+            ("FR_net_wifi_key", key.clone()),
+            ("FR_net_wifi_countrycode", country.clone()),
+        ];
+        let login = vec![
+            ("PRESET_NET_CHANGE_DEFAULTS", "1".to_string()),
+            ("PRESET_NET_WIFI_ENABLED", "1".to_string()),
+            ("PRESET_NET_WIFI_SSID", self.wifi_ssid.clone()),
+            ("PRESET_NET_WIFI_KEY", key),
+            ("PRESET_NET_WIFI_COUNTRYCODE", country),
+        ];
+        (first_run, login)
+    }
+}
+
+/// WPA2 passphrase (8 to 63 printable ASCII characters) or raw 64-digit hex key
+fn is_valid_psk(psk: &str) -> bool {
+    let passphrase = (8..=63).contains(&psk.len()) && psk.chars().all(|c| (' '..='~').contains(&c));
+    let hex_key = psk.len() == 64 && psk.chars().all(|c| c.is_ascii_hexdigit());
+    passphrase || hex_key
+}
+
+/// Host name made of one DNS label: letters, digits and inner hyphens
+fn is_valid_hostname(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= config::customization::MAX_HOSTNAME_LEN
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// User name as accepted by Debian's adduser, and not root
+fn is_valid_user_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let first_ok = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_');
+    first_ok
+        && name != "root"
+        && name.len() <= config::customization::MAX_USER_NAME_LEN
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Set `presets` in the content of a preset file, replacing earlier values
+///
+/// The files are sourced by a shell, so every value is single-quoted.
+fn merge_presets(existing: &str, presets: &[Preset]) -> String {
+    let mut lines: Vec<String> = existing
+        .lines()
+        .filter(|line| {
+            let key = line.split('=').next().unwrap_or_default().trim();
+            !presets.iter().any(|(k, _)| *k == key)
+        })
+        .map(str::to_string)
+        .collect();
+    if lines.iter().all(|line| line.trim().is_empty()) {
+        lines = vec!["# Written by Armbian Imager".to_string()];
+    }
+    lines.extend(
+        presets
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, shell_quote(value))),
+    );
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Path of a file relative to the mount point, refusing symbolic links on the way
+///
+/// Every component that exists must be a plain file or directory, and the
+/// deepest one must still be inside the mount point.
+fn image_path(mount_point: &Path, relative: &Path) -> Result<PathBuf, String> {
+    let mut path = mount_point.to_path_buf();
+    let mut existing = mount_point.to_path_buf();
+    for component in relative.components() {
+        match component {
+            Component::Normal(name) => path.push(name),
+            _ => return Err(format!("{} is outside the partition", relative.display())),
+        }
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(format!(
+                    "Refusing to follow the symbolic link {}",
+                    path.display()
+                ))
+            }
+            Ok(_) => existing = path.clone(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    let root = mount_point
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", mount_point.display(), e))?;
+    let resolved = existing
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", existing.display(), e))?;
+    if !resolved.starts_with(&root) {
+        return Err(format!("{} is outside the partition", path.display()));
+    }
+    Ok(path)
+}
+
+/// Read a file relative to the mount point, empty if missing
+fn read_file(mount_point: &Path, relative: &Path) -> Result<String, String> {
+    match fs::read_to_string(image_path(mount_point, relative)?) {
+        Ok(existing) => Ok(existing),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(format!("Failed to read {}: {}", relative.display(), e)),
+    }
+}
+
+/// Write `contents` to a file relative to the mount point and note it
+fn write_file(
+    mount_point: &Path,
+    relative: &Path,
+    contents: &str,
+    report: &mut CustomizationReport,
+) -> Result<(), String> {
+    use std::io::Write;
+
+    let path = image_path(mount_point, relative)?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // A link planted after the check is not followed either
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_NOFOLLOW);
+    }
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    report.written.push(relative.to_string_lossy().to_string());
+    Ok(())
+}
+
+/// Merge `presets` into a preset file relative to the mount point
+fn update_preset_file(
+    mount_point: &Path,
+    relative: &Path,
+    presets: &[Preset],
+    report: &mut CustomizationReport,
+) -> Result<(), String> {
+    let existing = read_file(mount_point, relative)?;
+    write_file(
+        mount_point,
+        relative,
+        &merge_presets(&existing, presets),
+        report,
+    )
+}
+
+/// Preset file in the boot directory, relative to the mount point
+fn first_run_file(mount_point: &Path) -> PathBuf {
+    let boot = boot_dir(mount_point);
+    boot.strip_prefix(mount_point)
+        .unwrap_or(Path::new(""))
+        .join(config::customization::FIRST_RUN_FILE)
+}
+
+/// The mounted partition is a root filesystem
+fn is_root_fs(mount_point: &Path) -> bool {
+    mount_point.join("etc").is_dir()
+}
+
+/// Point the `127.0.1.1` line of a hosts file at `hostname`, adding one if missing
+fn set_hosts_name(existing: &str, hostname: &str) -> String {
+    let entry = format!("{}\t{}", config::customization::HOSTNAME_ADDRESS, hostname);
+    let mut found = false;
+    let mut lines: Vec<String> = existing
+        .lines()
+        .map(|line| {
+            if line.split_whitespace().next() == Some(config::customization::HOSTNAME_ADDRESS) {
+                found = true;
+                entry.clone()
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    if !found {
+        lines.push(entry);
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// First-login preset file, if the partition has one
+fn login_preset_file(mount_point: &Path) -> Option<&'static Path> {
+    let relative = Path::new(config::customization::LOGIN_PRESET_FILE);
+    (is_root_fs(mount_point) && mount_point.join(relative).is_file()).then_some(relative)
+}
+
+/// Write the customization into a mounted first partition
+fn write_customization(
+    mount_point: &Path,
+    customization: &Customization,
+) -> Result<CustomizationReport, String> {
+    let mut report = CustomizationReport::default();
+
+    let presets = customization.presets();
+    update_preset_file(
+        mount_point,
+        &first_run_file(mount_point),
+        &presets,
+        &mut report,
+    )?;
+    if let Some(login_preset) = login_preset_file(mount_point) {
+        update_preset_file(mount_point, login_preset, &presets, &mut report)?;
+    }
+
+    let is_root_fs = is_root_fs(mount_point);
+
+    if let Some(hostname) = &customization.hostname {
+        if is_root_fs {
+            write_file(
+                mount_point,
+                Path::new(config::customization::HOSTNAME_FILE),
+                &format!("{}\n", hostname),
+                &mut report,
+            )?;
+            // The old name would no longer resolve, so sudo and others stall
+            let hosts = Path::new(config::customization::HOSTS_FILE);
+            let existing = read_file(mount_point, hosts)?;
+            write_file(
+                mount_point,
+                hosts,
+                &set_hosts_name(&existing, hostname),
+                &mut report,
+            )?;
+        } else {
+            report.skipped.push("hostname".to_string());
+        }
+    }
+
+    if is_root_fs {
+        let link = config::customization::SSH_UNIT_LINK.to_string();
+        let existed = set_ssh_enabled(mount_point, customization.ssh_enabled)?;
+        if customization.ssh_enabled {
+            report.written.push(link);
+        } else if existed {
+            report.removed.push(link);
+        }
+    } else {
+        report.skipped.push("ssh".to_string());
+    }
+
+    Ok(report)
+}
+
+/// Write the Wi-Fi settings into a mounted first partition
+fn write_network_config(
+    mount_point: &Path,
+    network: &NetworkConfig,
+) -> Result<CustomizationReport, String> {
+    let mut report = CustomizationReport::default();
+    let (first_run, login) = network.presets();
+
+    // The first-login presets go along, so the login script does not ask again
+    let mut boot_presets = first_run;
+    boot_presets.extend(login.iter().cloned());
+    update_preset_file(
+        mount_point,
+        &first_run_file(mount_point),
+        &boot_presets,
+        &mut report,
+    )?;
+    if let Some(login_preset) = login_preset_file(mount_point) {
+        update_preset_file(mount_point, login_preset, &login, &mut report)?;
+    }
+    Ok(report)
+}
+
+/// Create or remove the unit link `systemctl enable ssh` would manage
+///
+/// Returns whether there was a link before. The link itself is the one
+/// symbolic link expected on the way; the directories above it are checked.
+#[cfg(unix)]
+fn set_ssh_enabled(mount_point: &Path, enabled: bool) -> Result<bool, String> {
+    let relative = Path::new(config::customization::SSH_UNIT_LINK);
+    let unit_dir = relative.parent().unwrap_or(Path::new(""));
+    let link = image_path(mount_point, unit_dir)?.join(relative.file_name().unwrap_or_default());
+    let existed = match fs::remove_file(&link) {
+        Ok(()) => true,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+        Err(e) => return Err(format!("Failed to remove {}: {}", link.display(), e)),
+    };
+    if !enabled {
+        return Ok(existed);
+    }
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    // Nothing created in between may lead out of the partition either
+    image_path(mount_point, unit_dir)?;
+    std::os::unix::fs::symlink(config::customization::SSH_UNIT_TARGET, &link)
+        .map_err(|e| format!("Failed to create {}: {}", link.display(), e))?;
+    Ok(existed)
+}
+
+/// Windows cannot mount the root filesystem, so this is never reached
+#[cfg(not(unix))]
+fn set_ssh_enabled(_mount_point: &Path, _enabled: bool) -> Result<bool, String> {
+    Err("Changing the SSH unit is not supported on this platform".to_string())
+}
+
+/// Write settings to a mounted first partition, elevated if its files belong to root
+fn write_settings(mount_point: &Path, settings: FirstBoot) -> Result<CustomizationReport, String> {
+    #[cfg(target_os = "linux")]
+    if is_root_fs(mount_point) && unsafe { libc::geteuid() } != 0 {
+        return write_elevated(&ElevatedRequest {
+            mount_point: mount_point.to_path_buf(),
+            settings,
+        });
+    }
+    settings.write(mount_point)
+}
+
+/// Have the app, run again through pkexec, write the settings
+#[cfg(target_os = "linux")]
+fn write_elevated(request: &ElevatedRequest) -> Result<CustomizationReport, String> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    use crate::utils::{find_binary, run_with_timeout};
+
+    /// pkexec's exit status when the password prompt was dismissed
+    const PKEXEC_DISMISSED: i32 = 126;
+
+    // An AppImage runs from a FUSE mount that root can't see into
+    let exe = match std::env::var_os("APPIMAGE") {
+        Some(appimage) => PathBuf::from(appimage),
+        None => std::env::current_exe()
+            .map_err(|e| format!("Failed to find the app executable: {}", e))?,
+    };
+    let request_path = std::env::temp_dir().join(format!(
+        "armbian-imager-first-boot-{}.json",
+        std::process::id()
+    ));
+    let content = serde_json::to_vec(request)
+        .map_err(|e| format!("Failed to serialize first-boot settings: {}", e))?;
+    let _ = fs::remove_file(&request_path);
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&request_path)
+        .and_then(|mut file| file.write_all(&content))
+        .map_err(|e| format!("Failed to write {}: {}", request_path.display(), e))?;
+
+    log_info!(
+        MODULE,
+        "Writing to {} as root through pkexec",
+        request.mount_point.display()
+    );
+    let mut command = std::process::Command::new(find_binary("pkexec"));
+    command
+        .arg(exe)
+        .arg(config::customization::ELEVATED_FLAG)
+        .arg(&request_path);
+    let output = run_with_timeout(
+        &mut command,
+        std::time::Duration::from_secs(config::customization::ELEVATED_TIMEOUT_SECS),
+    );
+    let _ = fs::remove_file(&request_path);
+    let output = output?;
+    if output.status.code() == Some(PKEXEC_DISMISSED) {
+        return Err("Authorization cancelled".to_string());
+    }
+    let output = output.check()?;
+    serde_json::from_str(output.stdout.trim())
+        .map_err(|e| format!("Unexpected answer from the elevated writer: {}", e))
+}
+
+/// Request file of the elevated writer, if the app was started as one
+pub fn elevated_request_from_args(args: impl IntoIterator<Item = OsString>) -> Option<PathBuf> {
+    let mut args = args.into_iter().skip(1);
+    args.position(|arg| arg == config::customization::ELEVATED_FLAG)?;
+    args.next().map(PathBuf::from)
+}
+
+/// Run as the elevated writer: apply a request, print the report as JSON and
+/// return the exit code
+pub fn run_elevated(request_path: &Path) -> i32 {
+    let result = fs::read(request_path)
+        .map_err(|e| format!("Failed to read {}: {}", request_path.display(), e))
+        .and_then(|content| {
+            serde_json::from_slice::<ElevatedRequest>(&content)
+                .map_err(|e| format!("Invalid request: {}", e))
+        })
+        .and_then(|request| {
+            request.settings.validate()?;
+            // Only ever a root filesystem mounted for this, not any directory
+            if !is_root_fs(&request.mount_point) {
+                return Err(format!(
+                    "{} is not a root filesystem",
+                    request.mount_point.display()
+                ));
+            }
+            request.settings.write(&request.mount_point)
+        })
+        .and_then(|report| {
+            serde_json::to_string(&report).map_err(|e| format!("Failed to report: {}", e))
+        });
+    match result {
+        Ok(report) => {
+            println!("{}", report);
+            0
+        }
+        Err(e) => {
+            log_error!(MODULE, "Elevated write failed: {}", e);
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+/// Apply the customization to a device that was just written
+pub fn apply_customization(
+    device_path: &str,
+    customization: &Customization,
+) -> Result<CustomizationReport, String> {
+    customization.validate()?;
+    let report = with_first_partition(device_path, |mount_point| {
+        write_settings(mount_point, FirstBoot::Customization(customization.clone()))
+    })?;
+
+    log_info!(
+        MODULE,
+        "Customized {}: wrote {}",
+        device_path,
+        report.written.join(", ")
+    );
+    if !report.skipped.is_empty() {
+        log_warn!(
+            MODULE,
+            "Root filesystem not on the first partition, skipped: {}",
+            report.skipped.join(", ")
+        );
+    }
+    Ok(report)
+}
+
+/// Apply Wi-Fi settings to a device that was just written
+pub fn apply_network_config(
+    device_path: &str,
+    network: &NetworkConfig,
+) -> Result<CustomizationReport, String> {
+    network.validate()?;
+    let report = with_first_partition(device_path, |mount_point| {
+        write_settings(mount_point, FirstBoot::Network(network.clone()))
+    })?;
+
+    log_info!(
+        MODULE,
+        "Wi-Fi network {} set on {}: wrote {}",
+        network.wifi_ssid,
+        device_path,
+        report.written.join(", ")
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("customization-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn customization() -> Customization {
+        Customization {
+            hostname: Some("orangepi5".to_string()),
+            user_name: Some("pi".to_string()),
+            password: Some("it's secret".to_string()),
+            ssh_enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(customization().validate().is_ok());
+        assert!(Customization::default().validate().is_ok());
+
+        let invalid = [
+            Customization {
+                hostname: Some("-board".to_string()),
+                ..customization()
+            },
+            Customization {
+                hostname: Some("board.local".to_string()),
+                ..customization()
+            },
+            Customization {
+                user_name: Some("root".to_string()),
+                ..customization()
+            },
+            Customization {
+                user_name: Some("Pi".to_string()),
+                ..customization()
+            },
+            Customization {
+                password: None,
+                ..customization()
+            },
+            Customization {
+                password: Some("line\nbreak".to_string()),
+                ..customization()
+            },
+        ];
+        for c in invalid {
+            assert!(c.validate().is_err(), "{:?}", c);
+        }
+    }
+
+    fn network() -> NetworkConfig {
+        NetworkConfig {
+            wifi_ssid: "Lab WiFi".to_string(),
+            wifi_psk: Some("correct horse".to_string()),
+            country_code: Some("DE".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_validate_network_config() {
+        assert!(network().validate().is_ok());
+        let hex_key = NetworkConfig {
+            wifi_psk: Some("a".repeat(64)),
+            ..network()
+        };
+        assert!(hex_key.validate().is_ok());
+
+        let invalid = [
+            NetworkConfig {
+                wifi_ssid: String::new(),
+                ..network()
+            },
+            NetworkConfig {
+                wifi_ssid: "x".repeat(33),
+                ..network()
+            },
+            NetworkConfig {
+                wifi_psk: Some("short".to_string()),
+                ..network()
+            },
+            NetworkConfig {
+                country_code: Some("de".to_string()),
+                ..network()
+            },
+        ];
+        for n in invalid {
+            assert!(n.validate().is_err(), "{:?}", n);
+        }
+    }
+
+    #[test]
+    fn test_merge_presets() {
+        let presets = merge_presets("", &customization().presets());
+        assert!(presets.starts_with("# Written by Armbian Imager\n"));
+        assert!(presets.contains("PRESET_USER_NAME='pi'\n"));
+        assert!(presets.contains("PRESET_USER_PASSWORD='it'\\''s secret'\n"));
+        assert!(presets.contains("PRESET_ROOT_PASSWORD='it'\\''s secret'\n"));
+
+        // Keys already set are replaced, others kept
+        let merged = merge_presets(&presets, &[("PRESET_USER_NAME", "armbian".to_string())]);
+        assert!(merged.contains("PRESET_USER_NAME='armbian'\n"));
+        assert!(!merged.contains("PRESET_USER_NAME='pi'"));
+        assert!(merged.contains("PRESET_ROOT_PASSWORD="));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_customization_on_root_fs() {
+        let root = test_dir("rootfs");
+        for dir in ["boot", "etc", "root"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(root.join(config::customization::LOGIN_PRESET_FILE), "").unwrap();
+        fs::write(
+            root.join("etc/hosts"),
+            "127.0.0.1\tlocalhost\n127.0.1.1\tarmbian\n",
+        )
+        .unwrap();
+
+        let report = write_customization(&root, &customization()).unwrap();
+        assert!(report.skipped.is_empty());
+        assert!(report.removed.is_empty());
+        assert_eq!(
+            fs::read_to_string(root.join("etc/hostname")).unwrap(),
+            "orangepi5\n"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("etc/hosts")).unwrap(),
+            "127.0.0.1\tlocalhost\n127.0.1.1\torangepi5\n"
+        );
+        let presets = fs::read_to_string(root.join("boot/armbian_first_run.txt")).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.join(config::customization::LOGIN_PRESET_FILE)).unwrap(),
+            presets
+        );
+
+        // Wi-Fi settings are added to the same files
+        write_network_config(&root, &network()).unwrap();
+        let login =
+            fs::read_to_string(root.join(config::customization::LOGIN_PRESET_FILE)).unwrap();
+        assert!(login.contains("PRESET_USER_NAME='pi'\n"));
+        assert!(login.contains("PRESET_NET_WIFI_SSID='Lab WiFi'\n"));
+        let first_run = fs::read_to_string(root.join("boot/armbian_first_run.txt")).unwrap();
+        assert!(first_run.contains("FR_net_wifi_key='correct horse'\n"));
+        assert!(first_run.contains("FR_net_wifi_countrycode='DE'\n"));
+        let link = root.join(config::customization::SSH_UNIT_LINK);
+        assert_eq!(
+            fs::read_link(&link).unwrap(),
+            Path::new(config::customization::SSH_UNIT_TARGET)
+        );
+
+        let disabled = Customization {
+            ssh_enabled: false,
+            ..customization()
+        };
+        let report = write_customization(&root, &disabled).unwrap();
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert_eq!(
+            report.removed,
+            vec![config::customization::SSH_UNIT_LINK.to_string()]
+        );
+        assert!(!report
+            .written
+            .contains(&config::customization::SSH_UNIT_LINK.to_string()));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_customization_refuses_symlinks() {
+        let root = test_dir("symlinks");
+        let host = test_dir("symlinks-host");
+        fs::write(host.join("shadow"), "root:x:\n").unwrap();
+        for dir in ["boot", "etc", "root"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::os::unix::fs::symlink(host.join("shadow"), root.join("etc/hostname")).unwrap();
+
+        assert!(write_customization(&root, &customization()).is_err());
+        assert_eq!(
+            fs::read_to_string(host.join("shadow")).unwrap(),
+            "root:x:\n"
+        );
+
+        // A linked directory is refused as well
+        fs::remove_dir_all(root.join("etc")).unwrap();
+        std::os::unix::fs::symlink(&host, root.join("etc")).unwrap();
+        assert!(write_customization(&root, &customization()).is_err());
+        assert!(!host.join("hostname").exists());
+        assert!(image_path(&root, Path::new("../outside")).is_err());
+
+        fs::remove_dir_all(root).unwrap();
+        fs::remove_dir_all(host).unwrap();
+    }
+
+    #[test]
+    fn test_set_hosts_name() {
+        assert_eq!(
+            set_hosts_name(
+                "127.0.0.1 localhost\n127.0.1.1 armbian\n::1 localhost\n",
+                "pi"
+            ),
+            "127.0.0.1 localhost\n127.0.1.1\tpi\n::1 localhost\n"
+        );
+        assert_eq!(
+            set_hosts_name("127.0.0.1 localhost\n", "pi"),
+            "127.0.0.1 localhost\n127.0.1.1\tpi\n"
+        );
+        assert_eq!(set_hosts_name("", "pi"), "127.0.1.1\tpi\n");
+    }
+
+    #[test]
+    fn test_elevated_request_from_args() {
+        let args = |list: &[&str]| list.iter().map(OsString::from).collect::<Vec<_>>();
+        assert_eq!(
+            elevated_request_from_args(args(&["app", "--write-first-boot", "/tmp/req.json"])),
+            Some(PathBuf::from("/tmp/req.json"))
+        );
+        assert_eq!(elevated_request_from_args(args(&["app"])), None);
+        assert_eq!(
+            elevated_request_from_args(args(&["app", "--write-first-boot"])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_write_customization_on_boot_partition() {
+        let boot = test_dir("bootfs");
+        let report = write_customization(&boot, &customization()).unwrap();
+        assert_eq!(report.written, vec!["armbian_first_run.txt".to_string()]);
+        assert_eq!(
+            report.skipped,
+            vec!["hostname".to_string(), "ssh".to_string()]
+        );
+        fs::remove_dir_all(boot).unwrap();
+    }
+}
@@ -2,9 +2,13 @@
 //!
 //! Handles decompressing compressed image files (XZ, GZ, BZ2, ZST)
 //! using Rust native libraries with multi-threading support.
+//!
+//! Custom images are decompressed into the cache unless it sits on a
+//! RAM-backed filesystem (tmpfs, zram) or lacks room for the image, in which
+//! case a directory in Downloads or the home directory is used instead.
 
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Write};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
@@ -14,10 +18,17 @@ use flate2::read::GzDecoder;
 use lzma_rust2::XzReaderMt;
 use zstd::stream::read::Decoder as ZstdDecoder;
 
+use serde::Serialize;
+
 use crate::config;
+use crate::diskspace::{ensure_space, SpaceWatcher};
 use crate::download::DownloadState;
-use crate::log_info;
-use crate::utils::{get_recommended_threads, strip_compression_ext, ProgressTracker};
+use crate::utils::{
+    bytes_to_gb, get_cache_dir, get_recommended_threads, is_confined, storage_info,
+    strip_compression_ext, ProgressTracker, StorageInfo,
+};
+use crate::workdir::WorkDir;
+use crate::{log_info, log_warn};
 
 const MODULE: &str = "decompress";
 
@@ -103,6 +114,7 @@ fn decompress_with_reader_mt<R: Read>(
     let mut buf_writer =
         BufWriter::with_capacity(config::download::DECOMPRESS_BUFFER_SIZE, output_file);
     let mut buffer = vec![0u8; config::download::CHUNK_SIZE];
+    let space = SpaceWatcher::new(output_path.parent().unwrap_or(output_path));
 
     // Progress tracking - we don't know the decompressed size (0), so track output bytes
     // Use config interval for consistent logging
@@ -116,10 +128,9 @@ fn decompress_with_reader_mt<R: Read>(
 
     loop {
         if state.is_cancelled.load(Ordering::SeqCst) {
-            drop(buf_writer);
-            let _ = std::fs::remove_file(output_path);
             return Err("Decompression cancelled".to_string());
         }
+        space.check(state)?;
 
         let bytes_read = decoder
             .read(&mut buffer)
@@ -147,10 +158,193 @@ fn decompress_with_reader_mt<R: Read>(
     Ok(())
 }
 
+/// Where a custom image will be decompressed
+#[derive(Debug, Clone, Serialize)]
+pub struct DecompressPlacement {
+    pub dir: String,
+    pub storage: Option<StorageInfo>,
+    /// Space the decompressed image needs
+    pub required_bytes: u64,
+    /// `required_bytes` was estimated from the archive size
+    pub size_is_estimate: bool,
+    /// Why the cache directory was not used, or why no directory fits
+    pub warning: Option<String>,
+    /// Other directories that can hold the image
+    pub alternatives: Vec<StorageInfo>,
+}
+
+/// Directory custom images are decompressed into by default
+pub fn custom_decompress_dir() -> PathBuf {
+    get_cache_dir(config::app::NAME).join("custom-decompress")
+}
+
+/// All directories custom images may be decompressed into, preferred first
+///
+/// A Flatpak or strict snap can only rely on its own cache, so there are no
+/// alternatives.
+pub fn decompress_dirs() -> Vec<PathBuf> {
+    if is_confined() {
+        return vec![custom_decompress_dir()];
+    }
+    std::iter::once(custom_decompress_dir())
+        .chain(
+            [dirs::download_dir(), dirs::home_dir()]
+                .into_iter()
+                .flatten()
+                .map(|dir| dir.join(config::download::DECOMPRESS_ALTERNATIVE_DIR)),
+        )
+        .collect()
+}
+
+/// Why `storage` cannot hold `required` bytes, None if it can
+pub fn placement_problem(storage: &StorageInfo, required: u64) -> Option<String> {
+    if storage.ram_backed {
+        Some(format!(
+            "{} is on a RAM-backed filesystem ({})",
+            storage.path, storage.fs_type
+        ))
+    } else if storage.available_bytes < required + config::download::DECOMPRESS_FREE_MARGIN {
+        Some(format!(
+            "{} has {:.1} GB free, {:.1} GB needed",
+            storage.path,
+            bytes_to_gb(storage.available_bytes),
+            bytes_to_gb(required + config::download::DECOMPRESS_FREE_MARGIN)
+        ))
+    } else {
+        None
+    }
+}
+
+/// Pick the first candidate that fits, or the preferred one when none does
+fn choose_placement(
+    candidates: Vec<(PathBuf, Option<StorageInfo>)>,
+    required_bytes: u64,
+    size_is_estimate: bool,
+) -> DecompressPlacement {
+    let fits = |storage: &Option<StorageInfo>| {
+        storage
+            .as_ref()
+            .is_some_and(|s| placement_problem(s, required_bytes).is_none())
+    };
+    let preferred_problem = candidates
+        .first()
+        .and_then(|(_, storage)| storage.as_ref())
+        .and_then(|s| placement_problem(s, required_bytes));
+
+    let chosen = candidates
+        .iter()
+        .position(|(_, storage)| fits(storage))
+        .unwrap_or(0);
+    let warning = match (preferred_problem, chosen) {
+        (None, _) => None,
+        (Some(problem), 0) => Some(format!("{}, and no other location fits", problem)),
+        (Some(problem), _) => Some(format!(
+            "{}, using {} instead",
+            problem,
+            candidates[chosen].0.display()
+        )),
+    };
+
+    let mut alternatives = Vec::new();
+    let mut placement = None;
+    for (index, (dir, storage)) in candidates.into_iter().enumerate() {
+        if index == chosen {
+            placement = Some((dir, storage));
+        } else if fits(&storage) {
+            alternatives.extend(storage);
+        }
+    }
+    let (dir, storage) = placement.unwrap_or_default();
+
+    DecompressPlacement {
+        dir: dir.to_string_lossy().to_string(),
+        storage,
+        required_bytes,
+        size_is_estimate,
+        warning,
+        alternatives,
+    }
+}
+
+/// Uncompressed size recorded in the index of a single-stream XZ file
+pub fn xz_uncompressed_size(path: &Path) -> Option<u64> {
+    fn varint(bytes: &mut impl Iterator<Item = u8>) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..63).step_by(7) {
+            let byte = bytes.next()?;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    let mut file = File::open(path).ok()?;
+    let file_size = file.metadata().ok()?.len();
+
+    // Stream footer: CRC32, backward size, stream flags, magic
+    let mut footer = [0u8; 12];
+    file.seek(SeekFrom::End(-12)).ok()?;
+    file.read_exact(&mut footer).ok()?;
+    if &footer[10..] != b"YZ" {
+        return None;
+    }
+    let index_size = (u64::from(u32::from_le_bytes(footer[4..8].try_into().ok()?)) + 1) * 4;
+
+    let mut index = vec![0u8; usize::try_from(index_size).ok()?];
+    file.seek(SeekFrom::End(-12 - i64::try_from(index_size).ok()?))
+        .ok()?;
+    file.read_exact(&mut index).ok()?;
+
+    let mut bytes = index.into_iter();
+    if bytes.next()? != 0 {
+        return None;
+    }
+    let mut blocks_size = 0u64;
+    let mut uncompressed = 0u64;
+    for _ in 0..varint(&mut bytes)? {
+        blocks_size += varint(&mut bytes)?.div_ceil(4) * 4;
+        uncompressed += varint(&mut bytes)?;
+    }
+
+    // Concatenated streams or padding would need the other indexes too
+    (12 + blocks_size + index_size + 12 == file_size).then_some(uncompressed)
+}
+
+/// Space needed to decompress `input_path`, and whether it is an estimate
+pub fn decompressed_size(input_path: &Path) -> (u64, bool) {
+    let is_xz = input_path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("xz"));
+    if let Some(size) = is_xz.then(|| xz_uncompressed_size(input_path)).flatten() {
+        return (size, false);
+    }
+
+    let compressed = std::fs::metadata(input_path).map_or(0, |m| m.len());
+    (compressed * config::download::DECOMPRESS_SIZE_RATIO, true)
+}
+
+/// Choose where to decompress `input_path`
+pub fn plan_decompression(input_path: &Path) -> DecompressPlacement {
+    let (required_bytes, size_is_estimate) = decompressed_size(input_path);
+    let candidates = decompress_dirs()
+        .into_iter()
+        .map(|dir| {
+            let storage = storage_info(&dir);
+            (dir, storage)
+        })
+        .collect();
+    choose_placement(candidates, required_bytes, size_is_estimate)
+}
+
 /// Decompress a local file (for custom images)
-/// Returns the path to the decompressed file
+///
+/// `target_dir` must be one of `decompress_dirs()`; without it the directory
+/// is chosen by `plan_decompression`. Returns the path to the decompressed file.
 pub fn decompress_local_file(
     input_path: &PathBuf,
+    target_dir: Option<&Path>,
     state: &Arc<DownloadState>,
 ) -> Result<PathBuf, String> {
     let filename = input_path
@@ -170,13 +364,28 @@ pub fn decompress_local_file(
     // Use base_filename directly (it already has the correct .img extension)
     let output_filename = format!("{}-{}", base_filename, timestamp);
 
-    // Output to cache directory instead of user's directory
-    let custom_cache_dir = crate::utils::get_cache_dir(config::app::NAME).join("custom-decompress");
+    // Output to the cache, or an alternative when the cache cannot hold the image
+    let output_dir = match target_dir {
+        Some(dir) if decompress_dirs().iter().any(|d| d == dir) => dir.to_path_buf(),
+        Some(dir) => {
+            return Err(format!(
+                "Cannot decompress into {}: not a decompression directory",
+                dir.display()
+            ))
+        }
+        None => {
+            let placement = plan_decompression(input_path);
+            if let Some(warning) = &placement.warning {
+                log_warn!(MODULE, "Decompression destination: {}", warning);
+            }
+            PathBuf::from(placement.dir)
+        }
+    };
 
-    std::fs::create_dir_all(&custom_cache_dir)
-        .map_err(|e| format!("Failed to create cache directory: {}", e))?;
+    std::fs::create_dir_all(&output_dir)
+        .map_err(|e| format!("Failed to create decompression directory: {}", e))?;
 
-    let output_path = custom_cache_dir.join(&output_filename);
+    let output_path = output_dir.join(&output_filename);
 
     // Check if already decompressed
     if output_path.exists() {
@@ -188,6 +397,15 @@ pub fn decompress_local_file(
         return Ok(output_path);
     }
 
+    // An estimated size may be off, but the image is never smaller than the archive
+    let (required, size_is_estimate) = decompressed_size(input_path);
+    let required = if size_is_estimate {
+        std::fs::metadata(input_path).map_or(0, |m| m.len())
+    } else {
+        required
+    };
+    ensure_space(&output_dir, required, "the decompressed image")?;
+
     state.is_decompressing.store(true, Ordering::SeqCst);
 
     // Get input file size for progress indication
@@ -202,6 +420,10 @@ pub fn decompress_local_file(
         output_path.display()
     );
 
+    // Decompress into a working directory, removed with any partial output
+    let work = WorkDir::create(&output_dir, "decompress")?;
+    let partial_path = work.join(&output_filename);
+
     // Handle different compression formats
     let result = if filename.ends_with(".xz") {
         // Use Rust lzma-rust2 library (multi-threaded) on all platforms
@@ -209,24 +431,116 @@ pub fn decompress_local_file(
             MODULE,
             "Decompressing XZ format with Rust lzma-rust2 (multi-threaded)"
         );
-        decompress_with_rust_xz(input_path, &output_path, state)
+        decompress_with_rust_xz(input_path, &partial_path, state)
     } else if filename.ends_with(".gz") {
         log_info!(MODULE, "Decompressing GZ format");
-        decompress_with_gz(input_path, &output_path, state)
+        decompress_with_gz(input_path, &partial_path, state)
     } else if filename.ends_with(".bz2") {
         log_info!(MODULE, "Decompressing BZ2 format");
-        decompress_with_bz2(input_path, &output_path, state)
+        decompress_with_bz2(input_path, &partial_path, state)
     } else if filename.ends_with(".zst") {
         log_info!(MODULE, "Decompressing ZSTD format");
-        decompress_with_zstd(input_path, &output_path, state)
+        decompress_with_zstd(input_path, &partial_path, state)
     } else {
         return Err(format!("Unsupported compression format for: {}", filename));
     };
 
     result?;
+    work.persist(&output_filename, &output_path)?;
 
     state.is_decompressing.store(false, Ordering::SeqCst);
     log_info!(MODULE, "Decompression complete: {}", output_path.display());
 
     Ok(output_path)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    fn storage(path: &str, available_bytes: u64, ram_backed: bool) -> StorageInfo {
+        StorageInfo {
+            path: path.to_string(),
+            fs_type: if ram_backed { "tmpfs" } else { "ext4" }.to_string(),
+            total_bytes: 100 * GB,
+            available_bytes,
+            ram_backed,
+        }
+    }
+
+    fn candidates(storages: Vec<StorageInfo>) -> Vec<(PathBuf, Option<StorageInfo>)> {
+        storages
+            .into_iter()
+            .map(|s| (PathBuf::from(&s.path), Some(s)))
+            .collect()
+    }
+
+    #[test]
+    fn test_choose_placement() {
+        // The cache is used when it fits
+        let placement = choose_placement(
+            candidates(vec![
+                storage("/cache", 10 * GB, false),
+                storage("/downloads", 10 * GB, false),
+            ]),
+            4 * GB,
+            false,
+        );
+        assert_eq!(placement.dir, "/cache");
+        assert!(placement.warning.is_none());
+        assert_eq!(placement.alternatives.len(), 1);
+
+        // A RAM-backed or full cache falls back to the next directory that fits
+        let placement = choose_placement(
+            candidates(vec![
+                storage("/cache", 50 * GB, true),
+                storage("/downloads", 2 * GB, false),
+                storage("/home", 10 * GB, false),
+            ]),
+            4 * GB,
+            true,
+        );
+        assert_eq!(placement.dir, "/home");
+        assert!(placement.warning.unwrap().contains("RAM-backed"));
+        assert!(placement.alternatives.is_empty());
+
+        // Nothing fits: keep the cache and say so
+        let placement = choose_placement(
+            candidates(vec![storage("/cache", GB, false)]),
+            4 * GB,
+            false,
+        );
+        assert_eq!(placement.dir, "/cache");
+        assert!(placement.warning.unwrap().contains("no other location"));
+    }
+
+    #[test]
+    fn test_xz_uncompressed_size() {
+        let data = crate::selftest::fixture_image(300_000);
+        let mut options = lzma_rust2::XzOptions::with_preset(1);
+        options.set_block_size(std::num::NonZeroU64::new(100_000));
+        let mut writer = lzma_rust2::XzWriter::new(Vec::new(), options).unwrap();
+        writer.write_all(&data).unwrap();
+        let compressed = writer.finish().unwrap();
+
+        let path =
+            std::env::temp_dir().join(format!("decompress-test-{}.img.xz", std::process::id()));
+        std::fs::write(&path, &compressed).unwrap();
+        assert_eq!(decompressed_size(&path), (300_000, false));
+
+        // Trailing data is not a single stream any more
+        let mut padded = compressed.clone();
+        padded.extend_from_slice(&[0u8; 4]);
+        std::fs::write(&path, &padded).unwrap();
+        assert_eq!(
+            decompressed_size(&path),
+            (
+                padded.len() as u64 * config::download::DECOMPRESS_SIZE_RATIO,
+                true
+            )
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
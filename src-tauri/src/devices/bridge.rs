@@ -0,0 +1,167 @@
+//! USB bridge quirks
+//!
+//! Many flash failures on USB enclosures and adapters come from the bridge
+//! chip rather than the card or drive: several cheap chipsets misbehave
+//! with UAS (USB Attached SCSI), which shows as stalled writes, bus resets
+//! and I/O errors. The bridge of a USB disk is identified by its vendor and
+//! product ID (sysfs on Linux, the device tree on Windows) and looked up in
+//! `config::devices::UAS_QUIRK_BRIDGES`, so errors and failure reports can
+//! point at the workaround.
+//!
+//! The bridge also carries the link speed the device negotiated. A USB 3
+//! device in a USB 2.0 port, hub or cable runs at 480 Mbps, which is the
+//! usual reason for a flash that is several times slower than expected.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// USB bridge a disk is attached through
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsbBridge {
+    /// Vendor ID, four lowercase hex digits
+    pub vendor_id: String,
+    /// Product ID, four lowercase hex digits
+    pub product_id: String,
+    /// Whether the disk is driven over UAS, None if unknown
+    pub uas: Option<bool>,
+    /// Chipset name if the bridge is known for UAS problems
+    pub chipset: Option<String>,
+    /// Negotiated link speed in Mbit/s, None if unknown
+    #[serde(default)]
+    pub speed_mbps: Option<u32>,
+    /// Whether the device can run at USB 3 speeds, None if unknown
+    #[serde(default)]
+    pub superspeed_capable: Option<bool>,
+}
+
+impl UsbBridge {
+    /// Identify a bridge by its IDs, looking it up in the quirk list
+    pub fn new(vendor_id: &str, product_id: &str, uas: Option<bool>) -> Self {
+        let vendor_id = vendor_id.trim().to_lowercase();
+        let product_id = product_id.trim().to_lowercase();
+        let chipset = config::devices::UAS_QUIRK_BRIDGES
+            .iter()
+            .find(|(vendor, product, _)| *vendor == vendor_id && *product == product_id)
+            .map(|(_, _, name)| name.to_string());
+        Self {
+            vendor_id,
+            product_id,
+            uas,
+            chipset,
+            speed_mbps: None,
+            superspeed_capable: None,
+        }
+    }
+
+    /// Add the negotiated link speed and the device's bcdUSB
+    pub fn with_link(mut self, speed_mbps: Option<u32>, bcd_usb: Option<u16>) -> Self {
+        self.speed_mbps = speed_mbps;
+        self.superspeed_capable = bcd_usb.map(|bcd| bcd >= config::devices::SUPERSPEED_MIN_BCD_USB);
+        self
+    }
+
+    /// "vendor:product", as lsusb and the usb-storage quirks parameter write it
+    pub fn id(&self) -> String {
+        format!("{}:{}", self.vendor_id, self.product_id)
+    }
+
+    /// Whether the bridge is known for UAS problems and UAS may be in use
+    ///
+    /// A bridge already on the plain mass storage driver is past the problem.
+    pub fn is_problematic(&self) -> bool {
+        self.chipset.is_some() && self.uas != Some(false)
+    }
+
+    /// Whether a USB 3 device is held to USB 2.0 speed
+    pub fn is_slow_link(&self) -> bool {
+        self.superspeed_capable == Some(true)
+            && self
+                .speed_mbps
+                .is_some_and(|speed| speed <= config::devices::USB_HIGH_SPEED_MBPS)
+    }
+
+    /// Why writes through this bridge are slow, if the link is to blame
+    pub fn link_warning(&self) -> Option<String> {
+        self.is_slow_link().then(|| {
+            format!(
+                "USB 3 device ({}) connected at {} Mbps; use a USB 3 port and cable, without a USB 2.0 hub in between",
+                self.id(),
+                self.speed_mbps.unwrap_or_default()
+            )
+        })
+    }
+
+    /// What to try when an operation through this bridge fails
+    pub fn advice(&self) -> Option<String> {
+        if !self.is_problematic() {
+            return None;
+        }
+        let chipset = self.chipset.as_deref().unwrap_or_default();
+        if cfg!(target_os = "linux") {
+            Some(format!(
+                "{} USB bridge ({}) is known for UAS problems; disable UAS for it with the kernel parameter usb-storage.quirks={}:u, or use another adapter",
+                chipset,
+                self.id(),
+                self.id()
+            ))
+        } else {
+            Some(format!(
+                "{} USB bridge ({}) is known for UAS problems; connect it to a USB 2.0 port, or use another adapter",
+                chipset,
+                self.id()
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_bridges() {
+        let bridge = UsbBridge::new("152D", "0578", Some(true));
+        assert_eq!(bridge.id(), "152d:0578");
+        assert_eq!(bridge.chipset.as_deref(), Some("JMicron JMS578"));
+        assert!(bridge.advice().unwrap().contains("152d:0578"));
+
+        // Unknown whether UAS is used still warns; the mass storage driver does not
+        assert!(UsbBridge::new("152d", "0578", None).is_problematic());
+        assert!(!UsbBridge::new("152d", "0578", Some(false)).is_problematic());
+
+        let bridge = UsbBridge::new("0781", "5581", Some(true));
+        assert!(bridge.chipset.is_none());
+        assert!(bridge.advice().is_none());
+    }
+
+    #[test]
+    fn test_slow_link() {
+        let bridge = UsbBridge::new("0781", "5581", None);
+        // bcdUSB 2.10 may as well be a USB 2.0 device with link power management
+        assert!(!bridge
+            .clone()
+            .with_link(Some(480), Some(0x0210))
+            .is_slow_link());
+        assert!(bridge
+            .clone()
+            .with_link(Some(480), Some(0x0300))
+            .is_slow_link());
+        assert!(bridge
+            .clone()
+            .with_link(Some(480), Some(0x0320))
+            .link_warning()
+            .unwrap()
+            .contains("480 Mbps"));
+        assert!(!bridge
+            .clone()
+            .with_link(Some(5000), Some(0x0320))
+            .is_slow_link());
+        // USB 2.0 devices are as fast as they get
+        assert!(!bridge
+            .clone()
+            .with_link(Some(480), Some(0x0200))
+            .is_slow_link());
+        assert!(!bridge.with_link(None, Some(0x0320)).is_slow_link());
+    }
+}
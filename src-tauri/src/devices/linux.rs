@@ -1,35 +1,33 @@
 //! Linux device detection
 //!
-//! Uses lsblk to enumerate block devices.
+//! Uses lsblk to enumerate block devices and a udev netlink socket to hear
+//! about hotplug events.
 
-use std::process::Command;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::Path;
+use std::sync::mpsc::Sender;
 
 use crate::log_error;
-use crate::utils::format_size;
+use crate::utils::{format_size, is_flatpak, query_timeout, run_command};
 
+use super::bridge::UsbBridge;
 use super::types::BlockDevice;
 
 /// Get list of block devices on Linux
 pub fn get_block_devices() -> Result<Vec<BlockDevice>, String> {
     // Use JSON output for reliable parsing (handles spaces in model names)
-    let output = Command::new("lsblk")
-        .args(["-dpJo", "NAME,SIZE,MODEL,RM,TRAN", "-b"])
-        .output()
-        .map_err(|e| {
-            log_error!("devices", "Failed to run lsblk: {}", e);
-            format!("Failed to run lsblk: {}", e)
-        })?;
-
-    if !output.status.success() {
-        log_error!(
-            "devices",
-            "lsblk command failed with status: {:?}",
-            output.status
-        );
-        return Err("lsblk command failed".to_string());
-    }
+    let output = run_command(
+        "lsblk",
+        &["-dpJo", "NAME,SIZE,MODEL,RM,TRAN,SERIAL", "-b"],
+        query_timeout(),
+    )
+    .and_then(|output| output.check())
+    .map_err(|e| {
+        log_error!("devices", "{}", e);
+        e
+    })?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stdout = output.stdout;
     let mut devices = Vec::new();
     let system_disks = get_system_disks();
 
@@ -107,6 +105,19 @@ pub fn get_block_devices() -> Result<Vec<BlockDevice>, String> {
             other => Some(other.to_string()),
         };
 
+        let serial = dev["serial"]
+            .as_str()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+
+        let usb_bridge = if bus_type.as_deref() == Some("USB") {
+            std::fs::canonicalize(format!("/sys/block/{}/device", dev_name))
+                .ok()
+                .and_then(|device| usb_bridge(&device))
+        } else {
+            None
+        };
+
         devices.push(BlockDevice {
             path: path.to_string(),
             name: dev_name.to_string(),
@@ -116,30 +127,68 @@ pub fn get_block_devices() -> Result<Vec<BlockDevice>, String> {
             is_removable,
             is_system,
             bus_type,
+            serial,
+            alias: None,
+            usb_bridge,
         });
     }
 
     Ok(devices)
 }
 
+/// USB bridge above a disk's sysfs device directory
+///
+/// The first ancestor with `idVendor` is the USB device; the interface below
+/// it is bound to `uas` or to the plain mass storage driver `usb-storage`.
+/// The USB device also reports its link `speed` and bcdUSB `version`.
+fn usb_bridge(device: &Path) -> Option<UsbBridge> {
+    let mut uas = None;
+    for dir in device.ancestors() {
+        let driver = std::fs::read_link(dir.join("driver")).ok();
+        match driver.as_deref().and_then(Path::file_name) {
+            Some(name) if name == "uas" => uas = Some(true),
+            Some(name) if name == "usb-storage" => uas = Some(false),
+            _ => {}
+        }
+        let vendor = std::fs::read_to_string(dir.join("idVendor"));
+        let product = std::fs::read_to_string(dir.join("idProduct"));
+        if let (Ok(vendor), Ok(product)) = (vendor, product) {
+            let read = |name| std::fs::read_to_string(dir.join(name)).ok();
+            return Some(UsbBridge::new(&vendor, &product, uas).with_link(
+                read("speed").and_then(|speed| parse_usb_speed(&speed)),
+                read("version").and_then(|version| parse_usb_version(&version)),
+            ));
+        }
+    }
+    None
+}
+
+/// Link speed from sysfs, in whole Mbit/s ("480", "5000", or "1.5")
+fn parse_usb_speed(speed: &str) -> Option<u32> {
+    speed.trim().parse::<f64>().ok().map(|mbps| mbps as u32)
+}
+
+/// bcdUSB from the sysfs version (" 3.20" is 0x0320)
+fn parse_usb_version(version: &str) -> Option<u16> {
+    let (major, minor) = version.trim().split_once('.')?;
+    let major = u16::from_str_radix(major, 16).ok()?;
+    let minor = u16::from_str_radix(minor, 16).ok()?;
+    Some((major << 8) | minor)
+}
+
 /// Get list of system disk names to exclude
 fn get_system_disks() -> Vec<String> {
     let mut system_disks = Vec::new();
 
     for mount in &["/", "/boot", "/boot/efi"] {
-        if let Ok(output) = Command::new("findmnt")
-            .args(["-n", "-o", "SOURCE", mount])
-            .output()
+        if let Ok(output) = run_command("findmnt", &["-n", "-o", "SOURCE", mount], query_timeout())
         {
-            let source = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            let source = output.stdout.trim().to_string();
             if !source.is_empty() {
-                if let Ok(pkname_output) = Command::new("lsblk")
-                    .args(["-no", "PKNAME", &source])
-                    .output()
+                if let Ok(pkname_output) =
+                    run_command("lsblk", &["-no", "PKNAME", &source], query_timeout())
                 {
-                    let pkname = String::from_utf8_lossy(&pkname_output.stdout)
-                        .trim()
-                        .to_string();
+                    let pkname = pkname_output.stdout.trim().to_string();
                     if !pkname.is_empty() {
                         system_disks.push(pkname);
                     }
@@ -155,3 +204,185 @@ fn get_system_disks() -> Vec<String> {
     system_disks.dedup();
     system_disks
 }
+
+/// Netlink multicast group of events re-broadcast by udevd
+///
+/// Group 1 carries raw kernel events, which arrive before udev has created
+/// the device node and probed its model and serial.
+const UDEV_MONITOR_GROUP: u32 = 2;
+
+/// Header prefix of messages sent by libudev
+const UDEV_MESSAGE_PREFIX: &[u8] = b"libudev\0";
+
+/// Listen for udev block device events, signalling each on `tx`
+///
+/// Blocks until `tx` is closed. Fails when the socket cannot be opened, and
+/// inside a Flatpak, where udevd's events never reach the sandbox.
+pub fn watch_hotplug(tx: Sender<()>) -> Result<(), String> {
+    if is_flatpak() {
+        return Err("udev events are not visible inside a Flatpak".to_string());
+    }
+
+    // SAFETY: plain socket creation, the descriptor is owned right below
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            libc::NETLINK_KOBJECT_UEVENT,
+        )
+    };
+    if fd < 0 {
+        return Err(format!(
+            "Failed to open uevent socket: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    // SAFETY: fd is a fresh descriptor nobody else owns
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    // SAFETY: sockaddr_nl is plain data, all zeroes is a valid value
+    let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_groups = UDEV_MONITOR_GROUP;
+    // SAFETY: addr outlives the call and the length matches its type
+    let rc = unsafe {
+        libc::bind(
+            socket.as_raw_fd(),
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if rc < 0 {
+        return Err(format!(
+            "Failed to bind uevent socket: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    let mut buf = vec![0u8; 8192];
+    loop {
+        // SAFETY: buf is valid for writes of its full length
+        let n = unsafe {
+            libc::recv(
+                socket.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                0,
+            )
+        };
+        if n < 0 {
+            let err = std::io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                // Events were dropped; list devices to catch up
+                Some(libc::ENOBUFS) => {}
+                _ => return Err(format!("Failed to read uevent: {}", err)),
+            }
+        } else if !is_block_hotplug(&buf[..n as usize]) {
+            continue;
+        }
+        if tx.send(()).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Whether a udev message reports a disk or partition added, removed or
+/// changed (card inserted into a reader)
+fn is_block_hotplug(message: &[u8]) -> bool {
+    let properties = if message.starts_with(UDEV_MESSAGE_PREFIX) {
+        // prefix[8], magic, header_size, properties_off, properties_len (u32, native)
+        let Some(offset) = message
+            .get(16..20)
+            .and_then(|b| b.try_into().ok())
+            .map(|b| u32::from_ne_bytes(b) as usize)
+        else {
+            return false;
+        };
+        match message.get(offset..) {
+            Some(properties) => properties,
+            None => return false,
+        }
+    } else {
+        // Kernel format: "action@devpath" followed by the properties
+        message
+    };
+
+    let mut action = None;
+    let mut subsystem = None;
+    for field in properties.split(|&b| b == 0) {
+        if let Some(value) = field.strip_prefix(b"ACTION=") {
+            action = Some(value);
+        } else if let Some(value) = field.strip_prefix(b"SUBSYSTEM=") {
+            subsystem = Some(value);
+        }
+    }
+
+    subsystem == Some(b"block".as_slice())
+        && matches!(action, Some(b"add") | Some(b"remove") | Some(b"change"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn udev_message(properties: &str) -> Vec<u8> {
+        let mut message = UDEV_MESSAGE_PREFIX.to_vec();
+        message.extend_from_slice(&0xfeedcafeu32.to_be_bytes());
+        let header_size = 40u32;
+        message.extend_from_slice(&header_size.to_ne_bytes());
+        message.extend_from_slice(&header_size.to_ne_bytes());
+        message.extend_from_slice(&(properties.len() as u32).to_ne_bytes());
+        message.resize(header_size as usize, 0);
+        message.extend(properties.replace('\n', "\0").into_bytes());
+        message
+    }
+
+    #[test]
+    fn test_is_block_hotplug() {
+        assert!(is_block_hotplug(&udev_message(
+            "ACTION=add\nDEVPATH=/devices/usb1/block/sdb\nSUBSYSTEM=block\nDEVNAME=/dev/sdb\n"
+        )));
+        assert!(is_block_hotplug(&udev_message(
+            "ACTION=change\nSUBSYSTEM=block\nDISK_MEDIA_CHANGE=1\n"
+        )));
+        assert!(!is_block_hotplug(&udev_message(
+            "ACTION=add\nSUBSYSTEM=usb\nDEVTYPE=usb_device\n"
+        )));
+        assert!(!is_block_hotplug(&udev_message(
+            "ACTION=bind\nSUBSYSTEM=block\n"
+        )));
+        assert!(is_block_hotplug(
+            b"remove@/devices/usb1/block/sdb\0ACTION=remove\0SUBSYSTEM=block\0"
+        ));
+        assert!(!is_block_hotplug(b"libudev\0short"));
+    }
+
+    #[test]
+    fn test_usb_bridge() {
+        let root = std::env::temp_dir().join(format!("sysfs-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let usb = root.join("usb2/2-1");
+        let interface = usb.join("2-1:1.0");
+        let device = interface.join("host0/target0:0:0/0:0:0:0");
+        std::fs::create_dir_all(&device).unwrap();
+        std::fs::create_dir_all(root.join("drivers/uas")).unwrap();
+        std::os::unix::fs::symlink(root.join("drivers/uas"), interface.join("driver")).unwrap();
+        std::fs::write(usb.join("idVendor"), "152d\n").unwrap();
+        std::fs::write(usb.join("idProduct"), "0578\n").unwrap();
+        std::fs::write(usb.join("speed"), "480\n").unwrap();
+        std::fs::write(usb.join("version"), " 3.00\n").unwrap();
+
+        let bridge = usb_bridge(&device).unwrap();
+        assert_eq!(bridge.id(), "152d:0578");
+        assert_eq!(bridge.uas, Some(true));
+        assert!(bridge.is_problematic());
+        assert_eq!(bridge.speed_mbps, Some(480));
+        assert!(bridge.is_slow_link());
+        assert!(usb_bridge(&root).is_none());
+        assert_eq!(parse_usb_speed("1.5\n"), Some(1));
+        assert_eq!(parse_usb_version(" 3.20\n"), Some(0x0320));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
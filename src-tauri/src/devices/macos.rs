@@ -1,54 +1,58 @@
 //! macOS device detection
 //!
-//! Uses diskutil to enumerate block devices.
+//! Uses diskutil to enumerate block devices and DiskArbitration to hear
+//! about hotplug events. The USB bridges of external disks, with their
+//! link speed and serial number, come from the IOKit registry as `ioreg`
+//! prints it.
 
-use std::process::Command;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::sync::mpsc::Sender;
 
 use crate::log_error;
-use crate::utils::format_size;
+use crate::utils::{format_size, query_timeout, run_command};
 
+use super::bridge::UsbBridge;
 use super::types::BlockDevice;
 
 /// Get list of block devices on macOS
 pub fn get_block_devices() -> Result<Vec<BlockDevice>, String> {
-    let output = Command::new("diskutil")
-        .args(["list", "-plist", "external", "physical"])
-        .output()
-        .map_err(|e| {
-            log_error!("devices", "Failed to run diskutil: {}", e);
-            format!("Failed to run diskutil: {}", e)
-        })?;
-
-    if !output.status.success() {
+    let output = run_command(
+        "diskutil",
+        &["list", "-plist", "external", "physical"],
+        query_timeout(),
+    )
+    .map_err(|e| {
+        log_error!("devices", "{}", e);
+        e
+    })?;
+
+    if !output.success() {
         // Try without external flag for older macOS
-        let output = Command::new("diskutil")
-            .args(["list", "-plist"])
-            .output()
-            .map_err(|e| {
-                log_error!("devices", "Failed to run diskutil (fallback): {}", e);
-                format!("Failed to run diskutil: {}", e)
+        let output =
+            run_command("diskutil", &["list", "-plist"], query_timeout()).map_err(|e| {
+                log_error!("devices", "Fallback failed: {}", e);
+                e
             })?;
 
-        return parse_diskutil(&output.stdout);
+        return parse_diskutil(output.stdout.as_bytes());
     }
 
-    parse_diskutil(&output.stdout)
+    parse_diskutil(output.stdout.as_bytes())
 }
 
 /// Parse diskutil output to extract devices
 fn parse_diskutil(_plist_data: &[u8]) -> Result<Vec<BlockDevice>, String> {
     let mut devices = Vec::new();
 
-    let output = Command::new("diskutil")
-        .args(["list"])
-        .output()
-        .map_err(|e| {
-            log_error!("devices", "Failed to run diskutil list: {}", e);
-            format!("Failed to run diskutil list: {}", e)
-        })?;
+    let output = run_command("diskutil", &["list"], query_timeout()).map_err(|e| {
+        log_error!("devices", "{}", e);
+        e
+    })?;
 
-    let list_output = String::from_utf8_lossy(&output.stdout);
+    let list_output = output.stdout;
     let system_disk = get_system_disk();
+    let usb_disks = get_usb_disks();
 
     for line in list_output.lines() {
         if !line.starts_with("/dev/disk") {
@@ -82,6 +86,10 @@ fn parse_diskutil(_plist_data: &[u8]) -> Result<Vec<BlockDevice>, String> {
                 info.is_system = true;
             }
 
+            if let Some(usb) = usb_disks.get(&info.name) {
+                info.usb_bridge = usb.bridge.clone();
+                info.serial = usb.serial.clone();
+            }
             if info.size > 0 {
                 devices.push(info);
             }
@@ -93,9 +101,9 @@ fn parse_diskutil(_plist_data: &[u8]) -> Result<Vec<BlockDevice>, String> {
 
 /// Get the system disk identifier
 fn get_system_disk() -> Option<String> {
-    let output = Command::new("diskutil").args(["info", "/"]).output().ok()?;
+    let output = run_command("diskutil", &["info", "/"], query_timeout()).ok()?;
 
-    let info = String::from_utf8_lossy(&output.stdout);
+    let info = output.stdout;
     for line in info.lines() {
         if line.contains("Part of Whole:") {
             return line.split(':').nth(1).map(|s| s.trim().to_string());
@@ -106,20 +114,17 @@ fn get_system_disk() -> Option<String> {
 
 /// Get detailed info for a specific disk
 fn get_disk_info(disk_path: &str) -> Result<BlockDevice, String> {
-    let output = Command::new("diskutil")
-        .args(["info", disk_path])
-        .output()
-        .map_err(|e| {
-            log_error!(
-                "devices",
-                "Failed to get disk info for {}: {}",
-                disk_path,
-                e
-            );
-            format!("Failed to get disk info: {}", e)
-        })?;
+    let output = run_command("diskutil", &["info", disk_path], query_timeout()).map_err(|e| {
+        log_error!(
+            "devices",
+            "Failed to get disk info for {}: {}",
+            disk_path,
+            e
+        );
+        format!("Failed to get disk info: {}", e)
+    })?;
 
-    let info = String::from_utf8_lossy(&output.stdout);
+    let info = output.stdout;
 
     let mut size: u64 = 0;
     let mut model = String::new();
@@ -192,5 +197,228 @@ fn get_disk_info(disk_path: &str) -> Result<BlockDevice, String> {
         is_removable,
         is_system: is_internal && !is_removable,
         bus_type,
+        // diskutil does not report hardware serial numbers, ioreg fills it in
+        serial: None,
+        alias: None,
+        usb_bridge: None,
     })
 }
+
+/// What the IOKit registry knows about the USB device above a disk
+#[derive(Debug)]
+struct UsbDisk {
+    bridge: Option<UsbBridge>,
+    serial: Option<String>,
+}
+
+/// USB details of external disks, keyed by BSD name (e.g. "disk4")
+fn get_usb_disks() -> HashMap<String, UsbDisk> {
+    match run_command(
+        "ioreg",
+        &["-r", "-c", "IOUSBHostDevice", "-l", "-w0"],
+        query_timeout(),
+    ) {
+        Ok(output) if output.success() => parse_ioreg_usb(&output.stdout),
+        _ => HashMap::new(),
+    }
+}
+
+/// Properties of a USB device node seen so far
+#[derive(Default)]
+struct UsbNode {
+    indent: usize,
+    vendor_id: Option<u32>,
+    product_id: Option<u32>,
+    bcd_usb: Option<u16>,
+    speed: Option<u32>,
+    uas: Option<bool>,
+    serial: Option<String>,
+}
+
+impl UsbNode {
+    fn bridge(&self) -> Option<UsbBridge> {
+        let vendor = format!("{:04x}", self.vendor_id?);
+        let product = format!("{:04x}", self.product_id?);
+        // kUSBDeviceSpeed: low, full, high, super, super+, super+ 2x2
+        let speed_mbps = self.speed.and_then(|speed| match speed {
+            0 => Some(1),
+            1 => Some(12),
+            2 => Some(480),
+            3 => Some(5000),
+            4 => Some(10000),
+            5 => Some(20000),
+            _ => None,
+        });
+        Some(UsbBridge::new(&vendor, &product, self.uas).with_link(speed_mbps, self.bcd_usb))
+    }
+}
+
+/// Map disks to the USB devices above them in `ioreg -l` output
+///
+/// Every node line starts with "+-o" indented by its depth and is followed
+/// by its properties. The innermost USB device above a disk's IOMedia is
+/// its bridge and gives its serial number; hubs further up are passed over.
+fn parse_ioreg_usb(output: &str) -> HashMap<String, UsbDisk> {
+    let mut disks = HashMap::new();
+    let mut stack: Vec<UsbNode> = Vec::new();
+    let mut in_usb_node = false;
+
+    for line in output.lines() {
+        if let Some(indent) = line.find("+-o ") {
+            while stack.last().is_some_and(|node| node.indent >= indent) {
+                stack.pop();
+            }
+            in_usb_node = line.contains("<class IOUSBHostDevice");
+            if in_usb_node {
+                stack.push(UsbNode {
+                    indent,
+                    ..UsbNode::default()
+                });
+            } else if let Some(node) = stack.last_mut() {
+                if line.contains("<class IOUSBAttachedSCSI") {
+                    node.uas = Some(true);
+                } else if line.contains("<class IOUSBMassStorage") {
+                    node.uas = Some(false);
+                }
+            }
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once(" = ") else {
+            continue;
+        };
+        let key = key.trim_start_matches([' ', '|']).trim_matches('"');
+        let value = value.trim().trim_matches('"');
+        if key == "BSD Name" {
+            if let Some(node) = stack.last() {
+                let disk = UsbDisk {
+                    bridge: node.bridge(),
+                    serial: node.serial.clone(),
+                };
+                disks.insert(value.to_string(), disk);
+            }
+            continue;
+        }
+        let Some(node) = stack.last_mut().filter(|_| in_usb_node) else {
+            continue;
+        };
+        let number = value.parse::<u32>().ok();
+        match key {
+            "idVendor" => node.vendor_id = number,
+            "idProduct" => node.product_id = number,
+            "bcdUSB" => node.bcd_usb = number.and_then(|bcd| u16::try_from(bcd).ok()),
+            "Device Speed" | "USBSpeed" => node.speed = number,
+            "USB Serial Number" | "kUSBSerialNumberString" if !value.trim().is_empty() => {
+                node.serial = Some(value.trim().to_string())
+            }
+            _ => {}
+        }
+    }
+    disks
+}
+
+// DiskArbitration and CoreFoundation bindings for the hotplug watcher
+#[link(name = "DiskArbitration", kind = "framework")]
+extern "C" {
+    fn DASessionCreate(allocator: *const c_void) -> *mut c_void;
+    fn DARegisterDiskAppearedCallback(
+        session: *mut c_void,
+        match_: *const c_void,
+        callback: extern "C" fn(disk: *mut c_void, context: *mut c_void),
+        context: *mut c_void,
+    );
+    fn DARegisterDiskDisappearedCallback(
+        session: *mut c_void,
+        match_: *const c_void,
+        callback: extern "C" fn(disk: *mut c_void, context: *mut c_void),
+        context: *mut c_void,
+    );
+    fn DASessionScheduleWithRunLoop(
+        session: *mut c_void,
+        run_loop: *mut c_void,
+        run_loop_mode: *const c_void,
+    );
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    static kCFRunLoopDefaultMode: *const c_void;
+    fn CFRunLoopGetCurrent() -> *mut c_void;
+    fn CFRunLoopRun();
+    fn CFRunLoopStop(run_loop: *mut c_void);
+    fn CFRelease(cf: *const c_void);
+}
+
+/// DiskArbitration callback: forward the event, stop once nobody listens
+extern "C" fn disk_event(_disk: *mut c_void, context: *mut c_void) {
+    // SAFETY: context is the Sender owned by watch_hotplug's frame, which
+    // outlives the run loop that invokes this callback
+    let tx = unsafe { &*(context as *const Sender<()>) };
+    if tx.send(()).is_err() {
+        // SAFETY: called on the thread running the loop
+        unsafe { CFRunLoopStop(CFRunLoopGetCurrent()) };
+    }
+}
+
+/// Listen for disks appearing and disappearing, signalling each on `tx`
+///
+/// Runs a CoreFoundation run loop on the calling thread until `tx` is
+/// closed. Every existing disk is reported once at registration.
+pub fn watch_hotplug(tx: Sender<()>) -> Result<(), String> {
+    // SAFETY: a null allocator selects the default one
+    let session = unsafe { DASessionCreate(std::ptr::null()) };
+    if session.is_null() {
+        return Err("Failed to create DiskArbitration session".to_string());
+    }
+
+    let context = &tx as *const Sender<()> as *mut c_void;
+    // SAFETY: session is valid; context stays alive until the run loop returns
+    unsafe {
+        DARegisterDiskAppearedCallback(session, std::ptr::null(), disk_event, context);
+        DARegisterDiskDisappearedCallback(session, std::ptr::null(), disk_event, context);
+        DASessionScheduleWithRunLoop(session, CFRunLoopGetCurrent(), kCFRunLoopDefaultMode);
+        CFRunLoopRun();
+        CFRelease(session);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ioreg_usb() {
+        let output = r#"+-o USB3.0 Hub@01100000  <class IOUSBHostDevice, id 0x100000a01, registered>
+  | {
+  |   "idProduct" = 2066
+  |   "idVendor" = 1507
+  |   "bcdUSB" = 528
+  |   "Device Speed" = 2
+  | }
+  |
+  +-o JMS578@01110000  <class IOUSBHostDevice, id 0x100000a02, registered>
+    | {
+    |   "idProduct" = 1400
+    |   "idVendor" = 5421
+    |   "bcdUSB" = 768
+    |   "Device Speed" = 2
+    |   "USB Serial Number" = "0123456789AB"
+    | }
+    |
+    +-o IOUSBHostInterface@0  <class IOUSBHostInterface, id 0x100000a03, registered>
+      +-o IOUSBAttachedSCSI  <class IOUSBAttachedSCSI, id 0x100000a04, registered>
+        +-o JMicron Media  <class IOMedia, id 0x100000a05, registered>
+          | {
+          |   "BSD Name" = "disk4"
+          | }
+"#;
+        let disks = parse_ioreg_usb(output);
+        assert_eq!(disks["disk4"].serial.as_deref(), Some("0123456789AB"));
+        let bridge = disks["disk4"].bridge.as_ref().unwrap();
+        assert_eq!(bridge.id(), "152d:0578");
+        assert_eq!(bridge.uas, Some(true));
+        assert_eq!(bridge.speed_mbps, Some(480));
+        assert!(bridge.is_slow_link());
+    }
+}
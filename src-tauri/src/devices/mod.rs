@@ -1,8 +1,11 @@
 //! Block device detection module
 //!
-//! Platform-specific implementations for detecting available storage devices.
+//! Platform-specific implementations for detecting available storage devices
+//! and watching for them to be plugged in or removed.
 
+mod bridge;
 mod types;
+mod watcher;
 
 #[cfg(target_os = "macos")]
 mod macos;
@@ -14,7 +17,9 @@ mod linux;
 mod windows;
 
 // Re-export types
+pub use bridge::UsbBridge;
 pub use types::BlockDevice;
+pub use watcher::start_device_watcher;
 
 // Re-export platform-specific implementation
 #[cfg(target_os = "macos")]
@@ -24,4 +29,35 @@ pub use macos::get_block_devices;
 pub use linux::get_block_devices;
 
 #[cfg(target_os = "windows")]
-pub use windows::get_block_devices;
+pub use windows::{get_block_devices, request_disk_eject};
+
+#[cfg(target_os = "macos")]
+use macos::watch_hotplug;
+
+#[cfg(target_os = "linux")]
+use linux::watch_hotplug;
+
+#[cfg(target_os = "windows")]
+use windows::watch_hotplug;
+
+/// Canonical identity of a device path, for telling two paths to one device apart
+///
+/// Linux symlinks such as /dev/disk/by-id/... resolve to the kernel node,
+/// macOS raw nodes (/dev/rdiskN) map to their buffered twin, and Windows
+/// paths compare case-insensitively.
+pub fn device_identity(device_path: &str) -> String {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::canonicalize(device_path)
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_else(|_| device_path.to_string())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        device_path.replacen("/dev/rdisk", "/dev/disk", 1)
+    }
+    #[cfg(target_os = "windows")]
+    {
+        device_path.to_uppercase()
+    }
+}
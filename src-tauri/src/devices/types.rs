@@ -2,10 +2,14 @@
 //!
 //! Common types for block device representation.
 
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
+use super::bridge::UsbBridge;
+
 /// Represents a block device (disk) on the system
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BlockDevice {
     /// Device path (e.g., /dev/sda, /dev/disk2, \\.\PhysicalDrive1)
     pub path: String,
@@ -23,4 +27,28 @@ pub struct BlockDevice {
     pub is_system: bool,
     /// Bus type (e.g., "USB", "SD", "SATA", "NVMe", "MMC")
     pub bus_type: Option<String>,
+    /// Hardware serial number, if reported (card CID serial for built-in SD readers)
+    pub serial: Option<String>,
+    /// Name the user gave the device's serial (e.g. "blue 32GB SanDisk")
+    #[serde(default)]
+    pub alias: Option<String>,
+    /// USB bridge the device is attached through, with its link speed
+    #[serde(default)]
+    pub usb_bridge: Option<UsbBridge>,
+}
+
+impl BlockDevice {
+    /// Fill in `alias` from names keyed by serial
+    pub fn apply_alias(&mut self, aliases: &HashMap<String, String>) {
+        self.alias = self.serial.as_ref().and_then(|s| aliases.get(s)).cloned();
+    }
+
+    /// Device class used to group historical write speeds (e.g., "usb", "sd")
+    pub fn device_class(&self) -> String {
+        match &self.bus_type {
+            Some(bus) if !bus.trim().is_empty() => bus.trim().to_lowercase(),
+            _ if self.is_removable => "removable".to_string(),
+            _ => "fixed".to_string(),
+        }
+    }
 }
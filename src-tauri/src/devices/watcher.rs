@@ -0,0 +1,93 @@
+//! Device hotplug watcher
+//!
+//! Listens for disks appearing, disappearing or changing media and calls
+//! back once a burst of events has settled, so callers list devices once per
+//! insertion instead of polling. The event source is platform specific:
+//! udev on Linux, DiskArbitration on macOS and device interface
+//! notifications (`WM_DEVICECHANGE`) on Windows. Where the source cannot be
+//! used, e.g. inside a Flatpak, the watcher falls back to a periodic rescan.
+
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::config;
+use crate::{log_info, log_warn};
+
+const MODULE: &str = "devices";
+
+/// Start watching for hotplug events on background threads
+///
+/// `on_change` runs on the watcher thread after each settled burst of
+/// events, or after every rescan in polling mode.
+pub fn start_device_watcher(on_change: impl Fn() + Send + 'static) {
+    let (tx, rx) = mpsc::channel();
+
+    let source = thread::Builder::new()
+        .name("device-hotplug".to_string())
+        .spawn(move || {
+            match super::watch_hotplug(tx.clone()) {
+                Ok(()) => return,
+                Err(e) => log_warn!(MODULE, "Hotplug events unavailable, polling: {}", e),
+            }
+            let interval = Duration::from_secs(config::devices::HOTPLUG_POLL_INTERVAL_SECS);
+            loop {
+                thread::sleep(interval);
+                if tx.send(()).is_err() {
+                    break;
+                }
+            }
+        });
+    if let Err(e) = source {
+        log_warn!(MODULE, "Failed to start hotplug listener: {}", e);
+        return;
+    }
+
+    let settle = Duration::from_millis(config::devices::HOTPLUG_SETTLE_MS);
+    let worker = thread::Builder::new()
+        .name("device-watcher".to_string())
+        .spawn(move || {
+            log_info!(MODULE, "Watching for device changes");
+            while wait_for_burst(&rx, settle) {
+                on_change();
+            }
+        });
+    if let Err(e) = worker {
+        log_warn!(MODULE, "Failed to start device watcher: {}", e);
+    }
+}
+
+/// Block until an event arrives and no further one follows within `settle`
+///
+/// Returns false once the event source has gone away.
+fn wait_for_burst(rx: &Receiver<()>, settle: Duration) -> bool {
+    if rx.recv().is_err() {
+        return false;
+    }
+    loop {
+        match rx.recv_timeout(settle) {
+            Ok(()) => continue,
+            // Deliver the burst even if the source stopped meanwhile
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => return true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_for_burst_coalesces_events() {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..5 {
+            tx.send(()).unwrap();
+        }
+        assert!(wait_for_burst(&rx, Duration::from_millis(10)));
+        // The whole burst was consumed
+        assert!(rx.try_recv().is_err());
+
+        drop(tx);
+        assert!(!wait_for_burst(&rx, Duration::from_millis(10)));
+    }
+}
@@ -1,16 +1,29 @@
 //! Windows device detection using native Win32 APIs
+//!
+//! Disks are listed through SetupAPI disk interfaces, mapped to
+//! PhysicalDrive numbers with IOCTL_STORAGE_GET_DEVICE_NUMBER. If SetupAPI
+//! fails, PhysicalDrive0-31 are probed directly. Hotplug events come from
+//! device interface notifications delivered to a message-only window.
+//! The USB bridge of a disk is found by walking up the device tree from its
+//! interface with the configuration manager; the hub it is plugged into
+//! reports the speed of its link.
 
+use std::cell::RefCell;
 use std::ffi::c_void;
 use std::mem;
+use std::sync::mpsc::Sender;
 
-use crate::log_error;
 use crate::utils::format_size;
+use crate::{log_error, log_warn};
 
+use super::bridge::UsbBridge;
 use super::types::BlockDevice;
 
 #[cfg(target_os = "windows")]
 use windows_sys::Win32::{
-    Foundation::{CloseHandle, GetLastError, GENERIC_READ, HANDLE, INVALID_HANDLE_VALUE},
+    Foundation::{
+        CloseHandle, GetLastError, GENERIC_READ, GENERIC_WRITE, HANDLE, INVALID_HANDLE_VALUE,
+    },
     Storage::FileSystem::{CreateFileW, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING},
     System::Ioctl::IOCTL_DISK_GET_DRIVE_GEOMETRY_EX,
     System::IO::DeviceIoControl,
@@ -20,6 +33,83 @@ use windows_sys::Win32::{
 
 const IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS: u32 = 0x00560000;
 const IOCTL_STORAGE_QUERY_PROPERTY: u32 = 0x002D1400;
+const IOCTL_STORAGE_GET_DEVICE_NUMBER: u32 = 0x002D1080;
+const IOCTL_USB_GET_NODE_CONNECTION_INFORMATION_EX: u32 = 0x00220448;
+const IOCTL_USB_GET_NODE_CONNECTION_INFORMATION_EX_V2: u32 = 0x0022045C;
+
+// ===== SetupAPI Constants =====
+
+const DIGCF_PRESENT: u32 = 0x02;
+const DIGCF_DEVICEINTERFACE: u32 = 0x10;
+const ERROR_NO_MORE_ITEMS: u32 = 259;
+
+// ===== Configuration Manager Constants =====
+
+const CR_SUCCESS: u32 = 0;
+/// Longest device instance ID (MAX_DEVICE_ID_LEN)
+const MAX_DEVICE_ID_LEN: usize = 200;
+/// Levels of the device tree searched above a disk for its USB device
+const MAX_BRIDGE_DEPTH: usize = 8;
+/// Registry property holding a USB device's port number on its hub
+const CM_DRP_ADDRESS: u32 = 0x1D;
+/// No veto reported by CM_Request_Device_EjectW (PNP_VetoTypeUnknown)
+const PNP_VETO_TYPE_UNKNOWN: i32 = 0;
+/// Length of the veto name buffer (MAX_PATH)
+const VETO_NAME_LEN: usize = 260;
+/// Eject requests tried before giving up, as a handle may close meanwhile
+const EJECT_ATTEMPTS: usize = 3;
+
+// ===== USB Hub Constants =====
+
+/// GUID_DEVINTERFACE_USB_HUB, for the interface path of a hub
+const GUID_DEVINTERFACE_USB_HUB: &str = "{f18a0e88-c30c-11d0-8815-00a0c906bed8}";
+/// Offset of bcdUSB in USB_NODE_CONNECTION_INFORMATION_EX (packed)
+const CONNECTION_INFO_BCD_USB: usize = 6;
+/// Offset of Speed in USB_NODE_CONNECTION_INFORMATION_EX (packed)
+const CONNECTION_INFO_SPEED: usize = 23;
+/// Size of USB_NODE_CONNECTION_INFORMATION_EX_V2
+const CONNECTION_INFO_V2_SIZE: usize = 16;
+/// Offset of SupportedUsbProtocols in USB_NODE_CONNECTION_INFORMATION_EX_V2
+const CONNECTION_INFO_V2_PROTOCOLS: usize = 8;
+/// Offset of Flags in USB_NODE_CONNECTION_INFORMATION_EX_V2
+const CONNECTION_INFO_V2_FLAGS: usize = 12;
+/// USB_PROTOCOLS: Usb110, Usb200 and Usb300, the protocols asked about
+const USB_PROTOCOLS_ALL: u32 = 0b111;
+/// Flags: DeviceIsOperatingAtSuperSpeedOrHigher
+const V2_OPERATING_AT_SUPERSPEED: u32 = 1 << 0;
+/// Flags: DeviceIsSuperSpeedCapableOrHigher
+const V2_SUPERSPEED_CAPABLE: u32 = 1 << 1;
+/// Flags: DeviceIsOperatingAtSuperSpeedPlusOrHigher
+const V2_OPERATING_AT_SUPERSPEED_PLUS: u32 = 1 << 2;
+
+/// GUID_DEVINTERFACE_DISK {53f56307-b6bf-11d0-94f2-00a0c91efb8b}
+const GUID_DEVINTERFACE_DISK: Guid = Guid {
+    data1: 0x53f56307,
+    data2: 0xb6bf,
+    data3: 0x11d0,
+    data4: [0x94, 0xf2, 0x00, 0xa0, 0xc9, 0x1e, 0xfb, 0x8b],
+};
+
+/// GUID_DEVINTERFACE_VOLUME {53f5630d-b6bf-11d0-94f2-00a0c91efb8b}
+///
+/// Card readers keep their disk interface while empty; inserting a card
+/// only brings up a volume.
+const GUID_DEVINTERFACE_VOLUME: Guid = Guid {
+    data1: 0x53f5630d,
+    data2: 0xb6bf,
+    data3: 0x11d0,
+    data4: [0x94, 0xf2, 0x00, 0xa0, 0xc9, 0x1e, 0xfb, 0x8b],
+};
+
+// ===== Device Notification Constants =====
+
+const WM_DEVICECHANGE: u32 = 0x0219;
+const DBT_DEVICEARRIVAL: usize = 0x8000;
+const DBT_DEVICEREMOVECOMPLETE: usize = 0x8004;
+const DBT_DEVTYP_DEVICEINTERFACE: u32 = 5;
+const DEVICE_NOTIFY_WINDOW_HANDLE: u32 = 0;
+/// Parent handle that makes a window message-only
+const HWND_MESSAGE: isize = -3;
 
 // ===== Storage Property Constants =====
 
@@ -37,6 +127,45 @@ struct STORAGE_PROPERTY_QUERY {
     additional_parameters: [u8; 1],
 }
 
+/// GUID - matches the Win32 layout
+#[repr(C)]
+#[derive(Debug, Clone)]
+struct Guid {
+    data1: u32,
+    data2: u16,
+    data3: u16,
+    data4: [u8; 8],
+}
+
+/// SP_DEVICE_INTERFACE_DATA - one interface returned by SetupDiEnumDeviceInterfaces
+#[repr(C)]
+#[derive(Debug, Clone)]
+struct SpDeviceInterfaceData {
+    cb_size: u32,
+    interface_class_guid: Guid,
+    flags: u32,
+    reserved: usize,
+}
+
+/// SP_DEVINFO_DATA - the device an interface belongs to
+#[repr(C)]
+#[derive(Debug, Clone)]
+struct SpDevinfoData {
+    cb_size: u32,
+    class_guid: Guid,
+    dev_inst: u32,
+    reserved: usize,
+}
+
+/// STORAGE_DEVICE_NUMBER - returned by IOCTL_STORAGE_GET_DEVICE_NUMBER
+#[repr(C)]
+#[derive(Debug, Clone, Default)]
+struct StorageDeviceNumber {
+    device_type: u32,
+    device_number: u32,
+    partition_number: u32,
+}
+
 /// DISK_GEOMETRY_EX - returned by IOCTL_DISK_GET_DRIVE_GEOMETRY_EX
 #[repr(C)]
 #[derive(Debug, Clone)]
@@ -74,10 +203,126 @@ struct VolumeDiskExtents {
     extents: [VolumeDiskExtent; 1],
 }
 
+/// DEV_BROADCAST_DEVICEINTERFACE_W - filter for RegisterDeviceNotificationW
+#[repr(C)]
+#[derive(Debug, Clone)]
+struct DevBroadcastDeviceInterface {
+    size: u32,
+    device_type: u32,
+    reserved: u32,
+    class_guid: Guid,
+    name: [u16; 1],
+}
+
+/// WNDCLASSW - window class registered for the hotplug window
+#[repr(C)]
+struct WndClass {
+    style: u32,
+    wnd_proc: unsafe extern "system" fn(*mut c_void, u32, usize, isize) -> isize,
+    cls_extra: i32,
+    wnd_extra: i32,
+    instance: *mut c_void,
+    icon: *mut c_void,
+    cursor: *mut c_void,
+    background: *mut c_void,
+    menu_name: *const u16,
+    class_name: *const u16,
+}
+
+/// MSG - message retrieved by GetMessageW
+#[repr(C)]
+struct Msg {
+    hwnd: *mut c_void,
+    message: u32,
+    w_param: usize,
+    l_param: isize,
+    time: u32,
+    pt_x: i32,
+    pt_y: i32,
+}
+
 // ===== External Win32 API =====
 
 extern "system" {
     fn GetLogicalDrives() -> u32;
+    fn GetModuleHandleW(module_name: *const u16) -> *mut c_void;
+}
+
+#[link(name = "user32")]
+extern "system" {
+    fn RegisterClassW(wnd_class: *const WndClass) -> u16;
+    fn CreateWindowExW(
+        ex_style: u32,
+        class_name: *const u16,
+        window_name: *const u16,
+        style: u32,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        parent: *mut c_void,
+        menu: *mut c_void,
+        instance: *mut c_void,
+        param: *mut c_void,
+    ) -> *mut c_void;
+    fn DestroyWindow(hwnd: *mut c_void) -> i32;
+    fn DefWindowProcW(hwnd: *mut c_void, msg: u32, w_param: usize, l_param: isize) -> isize;
+    fn GetMessageW(msg: *mut Msg, hwnd: *mut c_void, filter_min: u32, filter_max: u32) -> i32;
+    fn DispatchMessageW(msg: *const Msg) -> isize;
+    fn PostQuitMessage(exit_code: i32);
+    fn RegisterDeviceNotificationW(
+        recipient: *mut c_void,
+        notification_filter: *const c_void,
+        flags: u32,
+    ) -> *mut c_void;
+    fn UnregisterDeviceNotification(handle: *mut c_void) -> i32;
+}
+
+#[link(name = "setupapi")]
+extern "system" {
+    fn SetupDiGetClassDevsW(
+        class_guid: *const Guid,
+        enumerator: *const u16,
+        hwnd_parent: *mut c_void,
+        flags: u32,
+    ) -> *mut c_void;
+    fn SetupDiEnumDeviceInterfaces(
+        device_info_set: *mut c_void,
+        device_info_data: *mut c_void,
+        interface_class_guid: *const Guid,
+        member_index: u32,
+        device_interface_data: *mut SpDeviceInterfaceData,
+    ) -> i32;
+    fn SetupDiGetDeviceInterfaceDetailW(
+        device_info_set: *mut c_void,
+        device_interface_data: *const SpDeviceInterfaceData,
+        device_interface_detail_data: *mut c_void,
+        device_interface_detail_data_size: u32,
+        required_size: *mut u32,
+        device_info_data: *mut c_void,
+    ) -> i32;
+    fn SetupDiDestroyDeviceInfoList(device_info_set: *mut c_void) -> i32;
+}
+
+#[link(name = "cfgmgr32")]
+extern "system" {
+    fn CM_Get_Parent(parent: *mut u32, dev_inst: u32, flags: u32) -> u32;
+    fn CM_Get_Device_IDW(dev_inst: u32, buffer: *mut u16, buffer_len: u32, flags: u32) -> u32;
+    fn CM_Request_Device_EjectW(
+        dev_inst: u32,
+        veto_type: *mut i32,
+        veto_name: *mut u16,
+        name_length: u32,
+        flags: u32,
+    ) -> u32;
+    fn CM_Get_DevNode_Registry_PropertyW(
+        dev_inst: u32,
+        property: u32,
+        reg_data_type: *mut u32,
+        buffer: *mut c_void,
+        length: *mut u32,
+        flags: u32,
+    ) -> u32;
 }
 
 // ===== Helper Functions =====
@@ -108,6 +353,410 @@ fn try_open_device(path_utf16: &[u16]) -> Result<HANDLE, u32> {
     }
 }
 
+/// Lists the PhysicalDrive numbers of present disks via SetupAPI, with
+/// the USB bridge of each
+///
+/// Returns None if the disk interfaces can't be enumerated.
+#[cfg(target_os = "windows")]
+fn enumerate_disk_numbers() -> Option<Vec<(i32, Option<UsbBridge>)>> {
+    let disks = enumerate_disk_nodes()?
+        .into_iter()
+        .map(|(number, dev_inst)| (number, usb_bridge(dev_inst)))
+        .collect();
+    Some(disks)
+}
+
+/// Lists the PhysicalDrive numbers of present disks with their device nodes
+///
+/// Returns None if the disk interfaces can't be enumerated.
+#[cfg(target_os = "windows")]
+fn enumerate_disk_nodes() -> Option<Vec<(i32, u32)>> {
+    let device_info_set = unsafe {
+        SetupDiGetClassDevsW(
+            &GUID_DEVINTERFACE_DISK,
+            std::ptr::null(),
+            std::ptr::null_mut(),
+            DIGCF_PRESENT | DIGCF_DEVICEINTERFACE,
+        )
+    };
+    if device_info_set as isize == -1 {
+        log_warn!("devices", "SetupDiGetClassDevsW failed: {}", unsafe {
+            GetLastError()
+        });
+        return None;
+    }
+
+    let mut disk_numbers = Vec::new();
+    let mut failed = false;
+
+    for index in 0.. {
+        let mut interface_data = SpDeviceInterfaceData {
+            cb_size: mem::size_of::<SpDeviceInterfaceData>() as u32,
+            interface_class_guid: GUID_DEVINTERFACE_DISK,
+            flags: 0,
+            reserved: 0,
+        };
+
+        let found = unsafe {
+            SetupDiEnumDeviceInterfaces(
+                device_info_set,
+                std::ptr::null_mut(),
+                &GUID_DEVINTERFACE_DISK,
+                index,
+                &mut interface_data,
+            )
+        };
+        if found == 0 {
+            let err = unsafe { GetLastError() };
+            if err != ERROR_NO_MORE_ITEMS {
+                log_warn!("devices", "SetupDiEnumDeviceInterfaces failed: {}", err);
+                failed = true;
+            }
+            break;
+        }
+
+        if let Some(disk) = interface_disk_number(device_info_set, &interface_data) {
+            disk_numbers.push(disk);
+        }
+    }
+
+    unsafe { SetupDiDestroyDeviceInfoList(device_info_set) };
+
+    if failed {
+        return None;
+    }
+    disk_numbers.sort_unstable_by_key(|(number, _)| *number);
+    disk_numbers.dedup_by_key(|(number, _)| *number);
+    Some(disk_numbers)
+}
+
+/// Resolves a disk interface to its PhysicalDrive number and device node
+#[cfg(target_os = "windows")]
+fn interface_disk_number(
+    device_info_set: *mut c_void,
+    interface_data: &SpDeviceInterfaceData,
+) -> Option<(i32, u32)> {
+    // SP_DEVICE_INTERFACE_DETAIL_DATA_W: cbSize followed by the device path
+    const DETAIL_CB_SIZE: u32 = if cfg!(target_pointer_width = "64") {
+        8
+    } else {
+        6
+    };
+
+    let mut required_size = 0u32;
+    unsafe {
+        SetupDiGetDeviceInterfaceDetailW(
+            device_info_set,
+            interface_data,
+            std::ptr::null_mut(),
+            0,
+            &mut required_size,
+            std::ptr::null_mut(),
+        )
+    };
+    if required_size <= 4 {
+        return None;
+    }
+
+    // u32 storage keeps the detail struct aligned
+    let mut detail = vec![0u32; (required_size as usize).div_ceil(4)];
+    detail[0] = DETAIL_CB_SIZE;
+    let mut devinfo_data = SpDevinfoData {
+        cb_size: mem::size_of::<SpDevinfoData>() as u32,
+        class_guid: GUID_DEVINTERFACE_DISK,
+        dev_inst: 0,
+        reserved: 0,
+    };
+    let result = unsafe {
+        SetupDiGetDeviceInterfaceDetailW(
+            device_info_set,
+            interface_data,
+            detail.as_mut_ptr() as *mut c_void,
+            required_size,
+            std::ptr::null_mut(),
+            &mut devinfo_data as *mut _ as *mut c_void,
+        )
+    };
+    if result == 0 {
+        return None;
+    }
+
+    // Querying the device number needs no access rights, so this works unelevated
+    let path = unsafe { (detail.as_ptr() as *const u16).add(2) };
+    let handle = unsafe {
+        CreateFileW(
+            path,
+            0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            std::ptr::null(),
+            OPEN_EXISTING,
+            0,
+            HANDLE::default(),
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return None;
+    }
+
+    let mut device_number = StorageDeviceNumber::default();
+    let mut bytes_returned = 0u32;
+    let result = unsafe {
+        DeviceIoControl(
+            handle,
+            IOCTL_STORAGE_GET_DEVICE_NUMBER,
+            std::ptr::null_mut(),
+            0,
+            &mut device_number as *mut _ as *mut c_void,
+            mem::size_of::<StorageDeviceNumber>() as u32,
+            &mut bytes_returned,
+            std::ptr::null_mut(),
+        )
+    };
+    unsafe { CloseHandle(handle) };
+
+    (result != 0).then_some((device_number.device_number as i32, devinfo_data.dev_inst))
+}
+
+/// Asks Plug and Play to remove a disk, as "Safely Remove Hardware" does
+///
+/// The request goes to the node above the disk (the USB mass storage device
+/// or card reader), so the whole device stops and can be unplugged. Fails if
+/// the disk is not removable or something vetoes, such as an open handle.
+#[cfg(target_os = "windows")]
+pub fn request_disk_eject(disk_number: u32) -> Result<(), String> {
+    let dev_inst = enumerate_disk_nodes()
+        .ok_or_else(|| "Failed to enumerate disks".to_string())?
+        .into_iter()
+        .find(|(number, _)| *number == disk_number as i32)
+        .map(|(_, dev_inst)| dev_inst)
+        .ok_or_else(|| format!("Disk {} has no device node", disk_number))?;
+    let mut parent = 0u32;
+    if unsafe { CM_Get_Parent(&mut parent, dev_inst, 0) } != CR_SUCCESS {
+        return Err(format!("Disk {} has no parent device", disk_number));
+    }
+
+    let mut veto = String::new();
+    for attempt in 0..EJECT_ATTEMPTS {
+        if attempt > 0 {
+            std::thread::sleep(std::time::Duration::from_millis(500));
+        }
+        let mut veto_type = PNP_VETO_TYPE_UNKNOWN;
+        let mut veto_name = [0u16; VETO_NAME_LEN];
+        let result = unsafe {
+            CM_Request_Device_EjectW(
+                parent,
+                &mut veto_type,
+                veto_name.as_mut_ptr(),
+                veto_name.len() as u32,
+                0,
+            )
+        };
+        if result == CR_SUCCESS && veto_type == PNP_VETO_TYPE_UNKNOWN {
+            return Ok(());
+        }
+        let len = veto_name.iter().position(|&c| c == 0).unwrap_or(0);
+        veto = format!(
+            "error {}, veto type {} {}",
+            result,
+            veto_type,
+            String::from_utf16_lossy(&veto_name[..len])
+        );
+    }
+    Err(format!("Device eject refused ({})", veto.trim_end()))
+}
+
+/// Instance ID of a device node, uppercased
+#[cfg(target_os = "windows")]
+fn device_instance_id(dev_inst: u32) -> Option<String> {
+    let mut buffer = [0u16; MAX_DEVICE_ID_LEN + 1];
+    let result =
+        unsafe { CM_Get_Device_IDW(dev_inst, buffer.as_mut_ptr(), buffer.len() as u32, 0) };
+    if result != CR_SUCCESS {
+        return None;
+    }
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Some(String::from_utf16_lossy(&buffer[..len]).to_uppercase())
+}
+
+/// Vendor and product ID from a USB instance ID (USB\VID_152D&PID_0578\...)
+fn parse_usb_instance_id(id: &str) -> Option<(&str, &str)> {
+    let rest = id.strip_prefix("USB\\VID_")?;
+    let vendor = rest.get(..4)?;
+    let product = rest.get(4..)?.strip_prefix("&PID_")?.get(..4)?;
+    Some((vendor, product))
+}
+
+/// USB bridge of a disk, from the nodes above it in the device tree
+///
+/// Disks on the mass storage driver sit on a USBSTOR\... node, UAS disks
+/// (SCSI\...) right on the USB device.
+#[cfg(target_os = "windows")]
+fn usb_bridge(disk_inst: u32) -> Option<UsbBridge> {
+    let mut node = disk_inst;
+    let mut mass_storage = false;
+    for _ in 0..MAX_BRIDGE_DEPTH {
+        let id = device_instance_id(node)?;
+        if id.starts_with("USBSTOR\\") {
+            mass_storage = true;
+        }
+        if let Some((vendor, product)) = parse_usb_instance_id(&id) {
+            let link = usb_link(node);
+            let mut bridge = UsbBridge::new(vendor, product, Some(!mass_storage)).with_link(
+                link.as_ref().and_then(|link| link.speed_mbps),
+                link.as_ref().map(|link| link.bcd_usb),
+            );
+            // The hub knows what bcdUSB 2.10 leaves open
+            if let Some(capable) = link.and_then(|link| link.superspeed_capable) {
+                bridge.superspeed_capable = Some(capable);
+            }
+            return Some(bridge);
+        }
+        let mut parent = 0u32;
+        if unsafe { CM_Get_Parent(&mut parent, node, 0) } != CR_SUCCESS {
+            return None;
+        }
+        node = parent;
+    }
+    None
+}
+
+/// What a hub reports about the USB device on one of its ports
+#[cfg(target_os = "windows")]
+struct UsbLink {
+    /// Negotiated speed in Mbit/s, None if unknown
+    speed_mbps: Option<u32>,
+    bcd_usb: u16,
+    /// Whether the device can run at USB 3 speeds, None if unknown
+    superspeed_capable: Option<bool>,
+}
+
+/// Link of a USB device, from its hub
+///
+/// The hub is opened by its interface path, which is its instance ID with
+/// backslashes replaced, and asked about the port the device is on. The
+/// Speed of USB_NODE_CONNECTION_INFORMATION_EX stays at high speed for
+/// SuperSpeed devices since Windows 8, so the speed and whether the device
+/// could run faster come from the _V2 query; without it a high speed link
+/// is unknown rather than slow.
+#[cfg(target_os = "windows")]
+fn usb_link(usb_inst: u32) -> Option<UsbLink> {
+    let mut hub = 0u32;
+    if unsafe { CM_Get_Parent(&mut hub, usb_inst, 0) } != CR_SUCCESS {
+        return None;
+    }
+    let mut port = 0u32;
+    let mut length = mem::size_of::<u32>() as u32;
+    let result = unsafe {
+        CM_Get_DevNode_Registry_PropertyW(
+            usb_inst,
+            CM_DRP_ADDRESS,
+            std::ptr::null_mut(),
+            &mut port as *mut _ as *mut c_void,
+            &mut length,
+            0,
+        )
+    };
+    if result != CR_SUCCESS {
+        return None;
+    }
+
+    let hub_path = format!(
+        "\\\\?\\{}#{}",
+        device_instance_id(hub)?.replace('\\', "#"),
+        GUID_DEVINTERFACE_USB_HUB
+    );
+    let hub_path = to_utf16(&hub_path);
+    let handle = unsafe {
+        CreateFileW(
+            hub_path.as_ptr(),
+            GENERIC_WRITE,
+            FILE_SHARE_WRITE,
+            std::ptr::null(),
+            OPEN_EXISTING,
+            0,
+            HANDLE::default(),
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return None;
+    }
+
+    // USB_NODE_CONNECTION_INFORMATION_EX, without room for the pipe list
+    let mut info = [0u8; 64];
+    info[..4].copy_from_slice(&port.to_le_bytes());
+    let info_len = hub_ioctl(
+        handle,
+        IOCTL_USB_GET_NODE_CONNECTION_INFORMATION_EX,
+        &mut info,
+    );
+
+    let mut info_v2 = [0u8; CONNECTION_INFO_V2_SIZE];
+    info_v2[..4].copy_from_slice(&port.to_le_bytes());
+    info_v2[4..8].copy_from_slice(&(CONNECTION_INFO_V2_SIZE as u32).to_le_bytes());
+    info_v2[CONNECTION_INFO_V2_PROTOCOLS..CONNECTION_INFO_V2_PROTOCOLS + 4]
+        .copy_from_slice(&USB_PROTOCOLS_ALL.to_le_bytes());
+    let flags = hub_ioctl(
+        handle,
+        IOCTL_USB_GET_NODE_CONNECTION_INFORMATION_EX_V2,
+        &mut info_v2,
+    )
+    .filter(|&len| len >= CONNECTION_INFO_V2_SIZE)
+    .map(|_| {
+        u32::from_le_bytes([
+            info_v2[CONNECTION_INFO_V2_FLAGS],
+            info_v2[CONNECTION_INFO_V2_FLAGS + 1],
+            info_v2[CONNECTION_INFO_V2_FLAGS + 2],
+            info_v2[CONNECTION_INFO_V2_FLAGS + 3],
+        ])
+    });
+    unsafe { CloseHandle(handle) };
+    if info_len? <= CONNECTION_INFO_SPEED {
+        return None;
+    }
+
+    let bcd_usb = u16::from_le_bytes([
+        info[CONNECTION_INFO_BCD_USB],
+        info[CONNECTION_INFO_BCD_USB + 1],
+    ]);
+    // USB_DEVICE_SPEED: low, full, high, super
+    let speed = match info[CONNECTION_INFO_SPEED] {
+        0 => Some(1),
+        1 => Some(12),
+        2 => None,
+        _ => Some(5000),
+    };
+    let speed_mbps = match flags {
+        Some(flags) if flags & V2_OPERATING_AT_SUPERSPEED_PLUS != 0 => Some(10000),
+        Some(flags) if flags & V2_OPERATING_AT_SUPERSPEED != 0 => Some(5000),
+        Some(_) => speed.or(Some(480)),
+        None => speed,
+    };
+    Some(UsbLink {
+        speed_mbps,
+        bcd_usb,
+        superspeed_capable: flags.map(|flags| flags & V2_SUPERSPEED_CAPABLE != 0),
+    })
+}
+
+/// Send a hub query that reads and writes `buffer`, returning the bytes written
+#[cfg(target_os = "windows")]
+fn hub_ioctl(handle: HANDLE, code: u32, buffer: &mut [u8]) -> Option<usize> {
+    let mut bytes_returned = 0u32;
+    let result = unsafe {
+        DeviceIoControl(
+            handle,
+            code,
+            buffer.as_mut_ptr() as *mut c_void,
+            buffer.len() as u32,
+            buffer.as_mut_ptr() as *mut c_void,
+            buffer.len() as u32,
+            &mut bytes_returned,
+            std::ptr::null_mut(),
+        )
+    };
+    (result != 0).then_some(bytes_returned as usize)
+}
+
 /// Maps STORAGE_BUS_TYPE enum byte to human-readable string
 fn bus_type_to_string(bus_type_enum: u8) -> Option<&'static str> {
     const BUS_TYPE_MAP: &[(&str, u8)] = &[
@@ -141,9 +790,9 @@ fn bus_type_to_string(bus_type_enum: u8) -> Option<&'static str> {
 }
 
 /// Extracts null-terminated ASCII string from buffer at offset
-fn extract_ascii_string(buffer: &[u8], offset: usize) -> String {
+fn extract_ascii_string(buffer: &[u8], offset: usize) -> Option<String> {
     if offset == 0 || offset >= buffer.len() {
-        return "Physical Drive".to_string();
+        return None;
     }
 
     let end = buffer[offset..]
@@ -152,19 +801,41 @@ fn extract_ascii_string(buffer: &[u8], offset: usize) -> String {
         .map(|pos| offset + pos)
         .unwrap_or(buffer.len());
 
-    if end > offset {
-        String::from_utf8_lossy(&buffer[offset..end])
-            .trim()
-            .to_string()
-    } else {
-        "Physical Drive".to_string()
+    let value = String::from_utf8_lossy(&buffer[offset..end])
+        .trim()
+        .to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+/// Reads a little-endian u32 offset field from a storage descriptor
+fn read_offset(buffer: &[u8], field: usize) -> usize {
+    u32::from_le_bytes(buffer[field..field + 4].try_into().unwrap()) as usize
+}
+
+/// Device properties from the storage descriptor
+struct DeviceProperties {
+    model: String,
+    is_removable: bool,
+    bus_type: Option<String>,
+    serial: Option<String>,
+}
+
+impl DeviceProperties {
+    fn unknown() -> Self {
+        Self {
+            model: "Physical Drive".to_string(),
+            is_removable: false,
+            bus_type: None,
+            serial: None,
+        }
     }
 }
 
 /// Queries device properties via IOCTL_STORAGE_QUERY_PROPERTY
-fn query_device_properties(disk_number: i32) -> Result<(String, bool, Option<String>), String> {
+fn query_device_properties(disk_number: i32) -> Result<DeviceProperties, String> {
     const MIN_DESCRIPTOR_SIZE: u32 = 33;
     const PRODUCT_ID_OFFSET: usize = 16;
+    const SERIAL_NUMBER_OFFSET: usize = 24;
     const BUS_TYPE_OFFSET: usize = 28;
 
     let device_path = format!("\\\\.\\PhysicalDrive{}", disk_number);
@@ -172,7 +843,7 @@ fn query_device_properties(disk_number: i32) -> Result<(String, bool, Option<Str
 
     let handle = match try_open_device(&device_path_utf16) {
         Ok(h) => h,
-        Err(_) => return Ok(("Physical Drive".to_string(), false, None)),
+        Err(_) => return Ok(DeviceProperties::unknown()),
     };
 
     let query = STORAGE_PROPERTY_QUERY {
@@ -200,30 +871,27 @@ fn query_device_properties(disk_number: i32) -> Result<(String, bool, Option<Str
     unsafe { CloseHandle(handle) };
 
     if result == 0 || bytes_returned < MIN_DESCRIPTOR_SIZE {
-        return Ok(("Physical Drive".to_string(), false, None));
+        return Ok(DeviceProperties::unknown());
     }
 
     let bus_type_enum = buffer[BUS_TYPE_OFFSET];
     let bus_type = bus_type_to_string(bus_type_enum).map(|s| s.to_string());
 
-    let product_id_offset = u32::from_le_bytes(
-        buffer[PRODUCT_ID_OFFSET..PRODUCT_ID_OFFSET + 4]
-            .try_into()
-            .unwrap(),
-    ) as usize;
-    let model = extract_ascii_string(&buffer, product_id_offset);
-    let model = if model.is_empty() {
-        "Physical Drive".to_string()
-    } else {
-        model
-    };
+    let model = extract_ascii_string(&buffer, read_offset(&buffer, PRODUCT_ID_OFFSET))
+        .unwrap_or_else(|| "Physical Drive".to_string());
+    let serial = extract_ascii_string(&buffer, read_offset(&buffer, SERIAL_NUMBER_OFFSET));
 
     let is_removable = match bus_type.as_deref() {
         Some(bt) => bt == "USB" || bt == "SD",
         None => disk_number > 0,
     };
 
-    Ok((model, is_removable, bus_type))
+    Ok(DeviceProperties {
+        model,
+        is_removable,
+        bus_type,
+        serial,
+    })
 }
 
 /// Retrieves drive letters mounted on a specific physical disk
@@ -295,9 +963,20 @@ pub fn get_block_devices() -> Result<Vec<BlockDevice>, String> {
     {
         let mut devices = Vec::new();
         let mut consecutive_errors = 0;
-        const MAX_CONSECUTIVE_ERRORS: usize = 4; // Stop after 4 consecutive non-existent drives
+        const MAX_CONSECUTIVE_ERRORS: usize = 4; // Stop probing after 4 consecutive non-existent drives
 
-        for disk_number in 0..32 {
+        let (disks, probing) = match enumerate_disk_numbers() {
+            Some(disks) => (disks, false),
+            None => {
+                log_warn!(
+                    "devices",
+                    "Disk interface enumeration failed, probing drives"
+                );
+                ((0..32).map(|number| (number, None)).collect(), true)
+            }
+        };
+
+        for (disk_number, usb_bridge) in disks {
             let device_path = format!("\\\\.\\PhysicalDrive{}", disk_number);
             let device_path_utf16 = to_utf16(&device_path);
 
@@ -308,7 +987,7 @@ pub fn get_block_devices() -> Result<Vec<BlockDevice>, String> {
                 }
                 Err(1 | 2 | 5 | 21) => {
                     consecutive_errors += 1;
-                    if consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+                    if probing && consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
                         break;
                     }
                     continue;
@@ -362,7 +1041,12 @@ pub fn get_block_devices() -> Result<Vec<BlockDevice>, String> {
                 continue;
             }
 
-            let (model, is_removable, bus_type) = query_device_properties(disk_number)?;
+            let DeviceProperties {
+                model,
+                is_removable,
+                bus_type,
+                serial,
+            } = query_device_properties(disk_number)?;
             let drive_letters = get_drive_letters_for_disk(disk_number);
 
             let is_system = drive_letters
@@ -383,6 +1067,9 @@ pub fn get_block_devices() -> Result<Vec<BlockDevice>, String> {
                 is_removable,
                 is_system,
                 bus_type,
+                serial,
+                alias: None,
+                usb_bridge,
             });
         }
 
@@ -394,3 +1081,135 @@ pub fn get_block_devices() -> Result<Vec<BlockDevice>, String> {
         Err("Windows device enumeration is only available on Windows".to_string())
     }
 }
+
+thread_local! {
+    /// Where the hotplug window procedure reports events
+    static HOTPLUG_TX: RefCell<Option<Sender<()>>> = const { RefCell::new(None) };
+}
+
+/// Window procedure of the hotplug window
+unsafe extern "system" fn hotplug_wnd_proc(
+    hwnd: *mut c_void,
+    msg: u32,
+    w_param: usize,
+    l_param: isize,
+) -> isize {
+    if msg == WM_DEVICECHANGE
+        && (w_param == DBT_DEVICEARRIVAL || w_param == DBT_DEVICEREMOVECOMPLETE)
+    {
+        let delivered =
+            HOTPLUG_TX.with(|tx| tx.borrow().as_ref().is_some_and(|tx| tx.send(()).is_ok()));
+        if !delivered {
+            PostQuitMessage(0);
+        }
+    }
+    DefWindowProcW(hwnd, msg, w_param, l_param)
+}
+
+/// Listen for disks and volumes arriving or leaving, signalling each on `tx`
+///
+/// Pumps messages for a message-only window on the calling thread until
+/// `tx` is closed.
+pub fn watch_hotplug(tx: Sender<()>) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        let class_name = to_utf16("ArmbianImagerHotplug");
+        // SAFETY: a null module name returns the executable's handle
+        let instance = unsafe { GetModuleHandleW(std::ptr::null()) };
+        let wnd_class = WndClass {
+            style: 0,
+            wnd_proc: hotplug_wnd_proc,
+            cls_extra: 0,
+            wnd_extra: 0,
+            instance,
+            icon: std::ptr::null_mut(),
+            cursor: std::ptr::null_mut(),
+            background: std::ptr::null_mut(),
+            menu_name: std::ptr::null(),
+            class_name: class_name.as_ptr(),
+        };
+        // SAFETY: wnd_class and the class name outlive the call
+        if unsafe { RegisterClassW(&wnd_class) } == 0 {
+            return Err(format!("RegisterClassW failed: {}", unsafe {
+                GetLastError()
+            }));
+        }
+
+        // SAFETY: the class was registered above; all other handles are optional
+        let hwnd = unsafe {
+            CreateWindowExW(
+                0,
+                class_name.as_ptr(),
+                class_name.as_ptr(),
+                0,
+                0,
+                0,
+                0,
+                0,
+                HWND_MESSAGE as *mut c_void,
+                std::ptr::null_mut(),
+                instance,
+                std::ptr::null_mut(),
+            )
+        };
+        if hwnd.is_null() {
+            return Err(format!("CreateWindowExW failed: {}", unsafe {
+                GetLastError()
+            }));
+        }
+
+        HOTPLUG_TX.with(|slot| *slot.borrow_mut() = Some(tx));
+
+        let mut registrations = Vec::new();
+        for guid in [GUID_DEVINTERFACE_DISK, GUID_DEVINTERFACE_VOLUME] {
+            let filter = DevBroadcastDeviceInterface {
+                size: mem::size_of::<DevBroadcastDeviceInterface>() as u32,
+                device_type: DBT_DEVTYP_DEVICEINTERFACE,
+                reserved: 0,
+                class_guid: guid,
+                name: [0],
+            };
+            // SAFETY: hwnd is our window and the filter outlives the call
+            let handle = unsafe {
+                RegisterDeviceNotificationW(
+                    hwnd,
+                    &filter as *const DevBroadcastDeviceInterface as *const c_void,
+                    DEVICE_NOTIFY_WINDOW_HANDLE,
+                )
+            };
+            if handle.is_null() {
+                log_warn!(
+                    "devices",
+                    "RegisterDeviceNotificationW failed: {}",
+                    unsafe { GetLastError() }
+                );
+            } else {
+                registrations.push(handle);
+            }
+        }
+
+        let result = if registrations.is_empty() {
+            Err("No device notifications could be registered".to_string())
+        } else {
+            let mut msg: Msg = unsafe { mem::zeroed() };
+            // SAFETY: msg is a live out parameter; 0 means WM_QUIT, -1 an error
+            while unsafe { GetMessageW(&mut msg, std::ptr::null_mut(), 0, 0) } > 0 {
+                unsafe { DispatchMessageW(&msg) };
+            }
+            Ok(())
+        };
+
+        for handle in registrations {
+            unsafe { UnregisterDeviceNotification(handle) };
+        }
+        unsafe { DestroyWindow(hwnd) };
+        HOTPLUG_TX.with(|slot| slot.borrow_mut().take());
+        result
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = tx;
+        Err("Not supported on this platform".to_string())
+    }
+}
@@ -0,0 +1,291 @@
+//! Direct write: download straight to the device
+//!
+//! Machines with a small disk cannot cache an 8 GB image before flashing
+//! it. In direct write mode the HTTP response is fed through the XZ or zstd
+//! decoder into the writer, so the image never touches the disk. A
+//! background task receives the download into a bounded queue that the
+//! writer drains; when the device falls behind, the full queue holds the
+//! download back.
+//!
+//! Progress shows on both sides: the window's `DownloadState` counts bytes
+//! received and names the serving mirror, the `FlashState` counts archive
+//! bytes written as for any compressed image. The archive is checked
+//! against the published checksum once its last byte arrived, before the
+//! writer sees the end of the stream. With no file left to compare the
+//! device with, the read-back is checked against the SHA-256 taken while
+//! writing. A failed transfer cannot be resumed or moved to another mirror
+//! halfway; the flash fails and has to be started again.
+
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use reqwest::Response;
+use sha2::{Digest, Sha256};
+use tauri::async_runtime::JoinHandle;
+
+use crate::config;
+use crate::credentials::authorize;
+use crate::download::{
+    check_download_url, download_client, extract_filename, fetch_expected_sha, DownloadState,
+};
+use crate::flash::ImageReader;
+use crate::history::{record_mirror_failure, record_mirror_success};
+use crate::mirrors::candidate_urls;
+use crate::utils::{run_blocking_future, ProgressTracker, Throttle};
+use crate::{log_error, log_info, log_warn};
+
+const MODULE: &str = "direct";
+
+/// Chunk of the download, or why it stopped
+type Chunk = Result<Vec<u8>, String>;
+
+/// Blocking reader over the chunks the download task receives
+///
+/// Reads end when the task finishes; an error it ran into fails the read.
+struct ChunkReader {
+    chunks: Receiver<Chunk>,
+    current: Vec<u8>,
+    position: usize,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.current.len() {
+            match self.chunks.recv() {
+                Ok(Ok(chunk)) => {
+                    self.current = chunk;
+                    self.position = 0;
+                }
+                Ok(Err(e)) => return Err(std::io::Error::other(e)),
+                // The task finished the download
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.current.len() - self.position);
+        buf[..n].copy_from_slice(&self.current[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// A download being fed to the writer
+pub struct DirectDownload {
+    /// Image bytes for `FlashState::set_image_stream`
+    pub image: ImageReader,
+    /// Outcome of the download, known once the writer read it all
+    pub task: JoinHandle<Result<(), String>>,
+}
+
+/// Start downloading `url` for a direct write
+///
+/// Returns once a mirror answered, so the archive size is known. The
+/// checksum from `sha_url` is fetched first: without it nothing would catch
+/// a corrupted download before it is on the device.
+pub async fn open(
+    url: &str,
+    sha_url: Option<&str>,
+    state: Arc<DownloadState>,
+) -> Result<DirectDownload, String> {
+    state.reset();
+    *state.mirror_host.lock().await = None;
+    let filename = extract_filename(url)?.to_string();
+
+    let allow_insecure = state.allow_insecure.load(Ordering::SeqCst);
+    check_download_url(url, allow_insecure)?;
+    if let Some(sha_url) = sha_url {
+        check_download_url(sha_url, allow_insecure)?;
+    }
+    let client = download_client(allow_insecure)?;
+
+    let expected_sha = match sha_url {
+        Some(sha_url) => Some(fetch_expected_sha(&client, sha_url).await.map_err(|e| {
+            format!(
+                "Cannot write directly without the image checksum: {}",
+                e.trim_start_matches("[SHA_UNAVAILABLE] ")
+            )
+        })?),
+        None => {
+            log_warn!(MODULE, "No SHA URL provided, the archive is not checked");
+            None
+        }
+    };
+
+    let region = state.mirror_region.lock().await.clone();
+    let mut last_error = String::new();
+    for candidate in candidate_urls(url, &region).await {
+        log_info!(MODULE, "Streaming {} to the device", candidate);
+        let started = Instant::now();
+        let response = match authorize(client.get(&candidate), &candidate)
+            .await
+            .send()
+            .await
+        {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => {
+                last_error = format!("Download failed with status: {}", response.status());
+                if let Some(host) = response.url().host_str() {
+                    record_mirror_failure(host, &last_error);
+                }
+                continue;
+            }
+            Err(e) => {
+                if let Some(host) = e.url().and_then(|u| u.host_str()) {
+                    record_mirror_failure(host, &e.to_string());
+                }
+                last_error = format!("Failed to start download: {}", e);
+                continue;
+            }
+        };
+
+        let latency = started.elapsed();
+        let host = response.url().host_str().unwrap_or("unknown").to_string();
+        let total = response.content_length().unwrap_or(0);
+        state.total_bytes.store(total, Ordering::SeqCst);
+        *state.mirror_host.lock().await = Some(host.clone());
+        log_info!(
+            MODULE,
+            "Serving mirror: {} ({} ms to respond, {} bytes)",
+            host,
+            latency.as_millis(),
+            total
+        );
+
+        let (sender, chunks) = sync_channel(config::download::DIRECT_WRITE_QUEUE_CHUNKS);
+        let reader = ChunkReader {
+            chunks,
+            current: Vec::new(),
+            position: 0,
+        };
+        let image = ImageReader::from_stream(Path::new(&filename), Box::new(reader), total)?;
+        // Sending blocks while the queue is full, so keep it off the runtime's workers
+        let receive = Receive {
+            response,
+            host,
+            latency,
+            started,
+            expected_sha: expected_sha.clone(),
+            sender,
+            state: state.clone(),
+        };
+        let task = tauri::async_runtime::spawn(run_blocking_future(receive.run()));
+        return Ok(DirectDownload { image, task });
+    }
+
+    log_error!(MODULE, "Download failed: {}", last_error);
+    Err(last_error)
+}
+
+/// Background half of a direct write
+struct Receive {
+    response: Response,
+    host: String,
+    latency: Duration,
+    started: Instant,
+    expected_sha: Option<String>,
+    sender: SyncSender<Chunk>,
+    state: Arc<DownloadState>,
+}
+
+impl Receive {
+    /// Receive the archive into the queue; an error is passed on to the writer
+    async fn run(self) -> Result<(), String> {
+        let sender = self.sender.clone();
+        let result = self.receive().await;
+        if let Err(e) = &result {
+            log_error!(MODULE, "Download failed: {}", e);
+            let _ = sender.send(Err(e.clone()));
+        }
+        result
+    }
+
+    async fn receive(self) -> Result<(), String> {
+        let state = self.state;
+        let total = state.total_bytes.load(Ordering::SeqCst);
+        let mut tracker = ProgressTracker::new(
+            "Download",
+            MODULE,
+            total,
+            config::logging::DOWNLOAD_LOG_INTERVAL_MB,
+        );
+        let mut throttle = Throttle::new(state.throttle_bytes_per_sec.load(Ordering::SeqCst));
+        let mut hasher = Sha256::new();
+        let mut received: u64 = 0;
+
+        let mut stream = self.response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            if state.is_cancelled.load(Ordering::SeqCst) {
+                return Err("Download cancelled".to_string());
+            }
+            let chunk = chunk.map_err(|e| {
+                record_mirror_failure(&self.host, &e.to_string());
+                format!("Download error: {}", e)
+            })?;
+            hasher.update(&chunk);
+            let len = chunk.len() as u64;
+            if self.sender.send(Ok(chunk.to_vec())).is_err() {
+                return Err("The flash stopped reading the download".to_string());
+            }
+            received += len;
+            state.downloaded_bytes.store(received, Ordering::SeqCst);
+            tracker.update(len);
+            throttle.pace_async(len, &state.is_cancelled).await;
+        }
+        tracker.finish();
+
+        if total > 0 && received < total {
+            let error = format!("Download ended after {} of {} bytes", received, total);
+            record_mirror_failure(&self.host, &error);
+            return Err(error);
+        }
+        if let Some(expected) = self.expected_sha {
+            let actual = hex::encode(hasher.finalize());
+            if actual != expected {
+                record_mirror_failure(&self.host, "Checksum mismatch");
+                return Err(format!(
+                    "SHA256 verification failed: expected {}, got {}",
+                    expected, actual
+                ));
+            }
+            log_info!(MODULE, "SHA256 verification successful");
+        }
+        record_mirror_success(&self.host, self.latency, received, self.started.elapsed());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_reader() {
+        let (sender, chunks) = sync_channel(4);
+        let mut reader = ChunkReader {
+            chunks,
+            current: Vec::new(),
+            position: 0,
+        };
+        sender.send(Ok(b"hello ".to_vec())).unwrap();
+        sender.send(Ok(b"world".to_vec())).unwrap();
+        drop(sender);
+        let mut data = String::new();
+        reader.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "hello world");
+
+        let (sender, chunks) = sync_channel(4);
+        let mut reader = ChunkReader {
+            chunks,
+            current: Vec::new(),
+            position: 0,
+        };
+        sender.send(Ok(b"partial".to_vec())).unwrap();
+        sender.send(Err("Download error".to_string())).unwrap();
+        let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.to_string(), "Download error");
+    }
+}
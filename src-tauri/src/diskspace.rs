@@ -0,0 +1,198 @@
+//! Free space watcher for downloads
+//!
+//! Other programs can fill the disk while an image downloads or a large
+//! archive decompresses, which otherwise only shows as a write error late
+//! in the operation. Between chunks the free space of the destination
+//! volume is checked; once it drops below `config::download::LOW_SPACE_BYTES`
+//! a `LowDiskSpace` event warns, and with the "pause_on_low_space" setting
+//! the operation waits until space is freed or it is cancelled.
+//!
+//! A long pause can outlast the server's patience; a download that fails
+//! that way resumes from the partial file like any interrupted download.
+//!
+//! Before a download or decompression starts, `ensure_space` fails early
+//! when the volume cannot hold what will be written, rather than letting
+//! the operation die on a full disk halfway through.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config;
+use crate::download::DownloadState;
+use crate::events::{self, AppEvent};
+use crate::utils::{bytes_to_gb, storage_info};
+use crate::{log_error, log_info, log_warn};
+
+const MODULE: &str = "diskspace";
+
+/// Whether `available` bytes are too little to carry on without a warning
+pub fn is_low(available: u64) -> bool {
+    available < config::download::LOW_SPACE_BYTES
+}
+
+/// Prefix of errors for a volume without room for an operation
+pub const NO_SPACE_MARKER: &str = "[NO_SPACE]";
+
+/// Whether an operation failed because its volume is too small
+pub fn is_no_space(error: &str) -> bool {
+    error.contains(NO_SPACE_MARKER)
+}
+
+/// Fail unless the volume holding `dir` has room for `required` more bytes
+///
+/// `config::download::DECOMPRESS_FREE_MARGIN` must stay free on top. Passes
+/// when the free space cannot be determined.
+pub fn ensure_space(dir: &Path, required: u64, what: &str) -> Result<(), String> {
+    let Some(storage) = storage_info(dir) else {
+        return Ok(());
+    };
+    let needed = required + config::download::DECOMPRESS_FREE_MARGIN;
+    if storage.available_bytes >= needed {
+        return Ok(());
+    }
+    let error = format!(
+        "{} Not enough space for {} on {}: {:.1} GB needed, {:.1} GB free",
+        NO_SPACE_MARKER,
+        what,
+        storage.path,
+        bytes_to_gb(needed),
+        bytes_to_gb(storage.available_bytes)
+    );
+    log_error!(MODULE, "{}", error);
+    Err(error)
+}
+
+/// Throttled free space check called between chunks
+///
+/// Shared by the ranges of a segmented download, so it only needs `&self`.
+pub struct SpaceWatcher {
+    dir: PathBuf,
+    last_check: Mutex<Option<Instant>>,
+}
+
+impl SpaceWatcher {
+    /// Watch the volume holding `dir`
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            last_check: Mutex::new(None),
+        }
+    }
+
+    fn interval() -> Duration {
+        Duration::from_secs(config::download::SPACE_CHECK_INTERVAL_SECS)
+    }
+
+    fn available(&self) -> Option<u64> {
+        storage_info(&self.dir).map(|info| info.available_bytes)
+    }
+
+    /// Check the free space if the interval passed; true if the operation should pause
+    ///
+    /// The warning event is published once each time space runs low.
+    fn poll(&self, state: &DownloadState) -> bool {
+        {
+            let mut last_check = self.last_check.lock().unwrap_or_else(|e| e.into_inner());
+            if last_check.is_some_and(|t| t.elapsed() < Self::interval()) {
+                return false;
+            }
+            *last_check = Some(Instant::now());
+        }
+
+        let Some(available) = self.available() else {
+            return false;
+        };
+        state.available_bytes.store(available, Ordering::SeqCst);
+        if !is_low(available) {
+            if state.is_low_on_space.swap(false, Ordering::SeqCst) {
+                log_info!(MODULE, "Free space recovered on {}", self.dir.display());
+            }
+            return false;
+        }
+
+        let pause = state.pause_on_low_space.load(Ordering::SeqCst);
+        if !state.is_low_on_space.swap(true, Ordering::SeqCst) {
+            log_warn!(
+                MODULE,
+                "Only {:.2} GB left on {}{}",
+                bytes_to_gb(available),
+                self.dir.display(),
+                if pause { ", pausing" } else { "" }
+            );
+            events::publish(AppEvent::LowDiskSpace {
+                path: self.dir.to_string_lossy().to_string(),
+                available_bytes: available,
+                paused: pause,
+            });
+        }
+        pause
+    }
+
+    /// Free space check while paused: None to keep waiting, else how the pause ends
+    fn still_short(&self, state: &DownloadState) -> Option<Result<(), String>> {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Some(Err("Download cancelled".to_string()));
+        }
+        match self.available() {
+            Some(available) if is_low(available) => {
+                state.available_bytes.store(available, Ordering::SeqCst);
+                None
+            }
+            _ => {
+                log_info!(MODULE, "Free space recovered, resuming");
+                state.is_low_on_space.store(false, Ordering::SeqCst);
+                Some(Ok(()))
+            }
+        }
+    }
+
+    /// Check the free space and block while it is too low to carry on
+    ///
+    /// Returns an error if the operation is cancelled while paused.
+    pub fn check(&self, state: &DownloadState) -> Result<(), String> {
+        if !self.poll(state) {
+            return Ok(());
+        }
+        state.is_paused_for_space.store(true, Ordering::SeqCst);
+        let result = loop {
+            std::thread::sleep(Self::interval());
+            if let Some(result) = self.still_short(state) {
+                break result;
+            }
+        };
+        state.is_paused_for_space.store(false, Ordering::SeqCst);
+        result
+    }
+
+    /// Like `check`, without blocking the runtime while paused
+    pub async fn check_async(&self, state: &DownloadState) -> Result<(), String> {
+        if !self.poll(state) {
+            return Ok(());
+        }
+        state.is_paused_for_space.store(true, Ordering::SeqCst);
+        let result = loop {
+            tokio::time::sleep(Self::interval()).await;
+            if let Some(result) = self.still_short(state) {
+                break result;
+            }
+        };
+        state.is_paused_for_space.store(false, Ordering::SeqCst);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ensure_space() {
+        let dir = std::env::temp_dir();
+        let error = ensure_space(&dir, u64::MAX / 2, "the image").unwrap_err();
+        assert!(is_no_space(&error));
+        assert!(error.contains("the image"));
+        assert!(!is_no_space("Download error: connection reset"));
+    }
+}
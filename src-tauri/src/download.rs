@@ -1,20 +1,37 @@
 //! Download module
 //!
-//! Handles downloading Armbian images from the web.
+//! Handles downloading Armbian images from the web. Downloads interrupted by
+//! an earlier exit continue where they stopped, see `interrupted`.
+//!
+//! XZ archives are cached as downloaded; the writers decompress them while
+//! flashing (see `flash::image_source`), so no decompressed copy is needed.
+//! Caches from older versions may still hold decompressed images, which are
+//! used as they are.
 
 use futures_util::StreamExt;
-use reqwest::Client;
+use reqwest::redirect::Policy;
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
 use sha2::{Digest, Sha256};
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 
 use crate::config;
-use crate::decompress::decompress_with_rust_xz;
-use crate::utils::{bytes_to_mb, ProgressTracker};
+use crate::credentials::authorize;
+use crate::decompress::placement_problem;
+use crate::diskspace::{ensure_space, is_no_space, SpaceWatcher};
+use crate::history::{record_mirror_failure, record_mirror_success, record_speed, OperationStage};
+use crate::interrupted::{self, partial_file_name, Tracked};
+use crate::mirrors::candidate_urls;
+use crate::proxy;
+use crate::segmented::{fetch_segments, plan_segments, SegmentProgress};
+use crate::utils::{bytes_to_mb, run_blocking, storage_info, ProgressTracker, Throttle};
+use crate::workdir::WorkDir;
 use crate::{log_debug, log_error, log_info, log_warn};
 
 const MODULE: &str = "download";
@@ -30,6 +47,32 @@ pub struct DownloadState {
     pub output_path: Mutex<Option<PathBuf>>,
     /// Temp file path for SHA unavailable retry (file kept for user decision)
     pub temp_path: Mutex<Option<PathBuf>>,
+    /// Mirror host that actually served the download (after redirects)
+    pub mirror_host: Mutex<Option<String>>,
+    /// Simulated network speed limit in bytes per second, 0 when off
+    pub throttle_bytes_per_sec: AtomicU64,
+    /// Accept plain HTTP URLs and redirects (the "allow_insecure_urls" setting)
+    pub allow_insecure: AtomicBool,
+    /// Mirror region tried first (the "mirror_region" setting)
+    pub mirror_region: Mutex<String>,
+    /// Parallel connections when the server accepts ranges (the "download_segments" setting)
+    pub segment_count: AtomicU64,
+    /// Progress of each range while a segmented download runs, empty otherwise
+    pub segments: std::sync::Mutex<Vec<SegmentProgress>>,
+    /// Wait for space when the volume runs low (the "pause_on_low_space" setting)
+    pub pause_on_low_space: AtomicBool,
+    /// Free space on the destination volume is below `config::download::LOW_SPACE_BYTES`
+    pub is_low_on_space: AtomicBool,
+    /// Waiting for space to be freed, see `diskspace`
+    pub is_paused_for_space: AtomicBool,
+    /// Free space on the destination volume at the last check
+    pub available_bytes: AtomicU64,
+    /// Retries of a mirror after a network error (the "download_retries" setting)
+    pub retry_limit: AtomicU64,
+    /// Retries made so far by this download, over all mirrors
+    pub retries: AtomicU64,
+    /// Keep the partial file of a cancelled download for the queue to resume
+    pub keep_partial: AtomicBool,
 }
 
 impl DownloadState {
@@ -43,6 +86,19 @@ impl DownloadState {
             error: Mutex::new(None),
             output_path: Mutex::new(None),
             temp_path: Mutex::new(None),
+            mirror_host: Mutex::new(None),
+            throttle_bytes_per_sec: AtomicU64::new(0),
+            allow_insecure: AtomicBool::new(false),
+            mirror_region: Mutex::new("auto".to_string()),
+            segment_count: AtomicU64::new(config::download::DEFAULT_SEGMENTS),
+            segments: std::sync::Mutex::new(Vec::new()),
+            pause_on_low_space: AtomicBool::new(true),
+            is_low_on_space: AtomicBool::new(false),
+            is_paused_for_space: AtomicBool::new(false),
+            available_bytes: AtomicU64::new(0),
+            retry_limit: AtomicU64::new(config::download::DEFAULT_RETRIES),
+            retries: AtomicU64::new(0),
+            keep_partial: AtomicBool::new(false),
         }
     }
 
@@ -52,6 +108,18 @@ impl DownloadState {
         self.is_verifying_sha.store(false, Ordering::SeqCst);
         self.is_decompressing.store(false, Ordering::SeqCst);
         self.is_cancelled.store(false, Ordering::SeqCst);
+        self.is_low_on_space.store(false, Ordering::SeqCst);
+        self.is_paused_for_space.store(false, Ordering::SeqCst);
+        self.retries.store(0, Ordering::SeqCst);
+        self.clear_segments();
+    }
+
+    /// Forget the ranges of a segmented download
+    fn clear_segments(&self) {
+        self.segments
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
     }
 }
 
@@ -61,8 +129,89 @@ impl Default for DownloadState {
     }
 }
 
+/// Prefix of errors a retry of the same mirror may get past
+const TRANSIENT_MARKER: &str = "[TRANSIENT]";
+
+/// Mark a network error as worth retrying on the same mirror
+pub fn transient(error: String) -> String {
+    format!("{} {}", TRANSIENT_MARKER, error)
+}
+
+/// Whether a response status may go away on a retry
+pub fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// What an earlier attempt left to continue from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Resume {
+    /// Nothing, the download starts over
+    #[default]
+    Start,
+    /// The partial file holds this many bytes from the start
+    Offset(u64),
+    /// The segmented file holds the received part of each range
+    Segments(Vec<SegmentProgress>),
+}
+
+impl Resume {
+    /// Bytes already received
+    pub fn bytes(&self) -> u64 {
+        match self {
+            Resume::Start => 0,
+            Resume::Offset(offset) => *offset,
+            Resume::Segments(ranges) => ranges.iter().map(|range| range.downloaded_bytes).sum(),
+        }
+    }
+}
+
+/// Wait before the `retry`th retry of a mirror, doubled each time
+fn retry_delay(retry: u64) -> Duration {
+    let factor = 1u64 << retry.saturating_sub(1).min(16);
+    Duration::from_millis(
+        config::download::RETRY_BASE_DELAY_MS
+            .saturating_mul(factor)
+            .min(config::download::RETRY_MAX_DELAY_MS),
+    )
+}
+
+/// Sleep for `delay`, returning early with false if the download is cancelled
+async fn wait_for_retry(delay: Duration, state: &DownloadState) -> bool {
+    let slice = Duration::from_millis(config::developer::THROTTLE_SLICE_MS);
+    let mut remaining = delay;
+    while !remaining.is_zero() {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return false;
+        }
+        let step = remaining.min(slice);
+        tokio::time::sleep(step).await;
+        remaining -= step;
+    }
+    !state.is_cancelled.load(Ordering::SeqCst)
+}
+
+/// Give up a cancelled download, keeping its partial file when asked to
+fn cancelled(work: WorkDir, tracked: Tracked, state: &DownloadState) -> String {
+    if state.keep_partial.load(Ordering::SeqCst) {
+        let segments = state
+            .segments
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        log_info!(
+            MODULE,
+            "Keeping partial download in {}",
+            work.keep().display()
+        );
+        tracked.pause(&segments);
+    }
+    "Download cancelled".to_string()
+}
+
 /// Extract filename from URL
-fn extract_filename(url: &str) -> Result<&str, String> {
+pub fn extract_filename(url: &str) -> Result<&str, String> {
     log_debug!(MODULE, "Extracting filename from URL: {}", url);
     let url_path = url.split('?').next().unwrap_or(url);
     let filename = url_path
@@ -74,13 +223,188 @@ fn extract_filename(url: &str) -> Result<&str, String> {
     Ok(filename)
 }
 
+/// Check that an image or checksum URL may be downloaded from
+///
+/// Only HTTPS is accepted unless `allow_insecure` lets plain HTTP through,
+/// e.g. for a mirror on the local network.
+pub fn check_download_url(url: &str, allow_insecure: bool) -> Result<(), String> {
+    let parsed =
+        reqwest::Url::parse(url).map_err(|e| format!("Invalid download URL {}: {}", url, e))?;
+    match parsed.scheme() {
+        "https" => Ok(()),
+        "http" if allow_insecure => Ok(()),
+        "http" => Err(format!(
+            "Refusing to download over plain HTTP: {} (insecure downloads are disabled in the settings)",
+            url
+        )),
+        scheme => Err(format!("Unsupported download URL scheme {}: {}", scheme, url)),
+    }
+}
+
+/// Redirect policy that does not follow HTTPS downloads onto plain HTTP
+fn redirect_policy(allow_insecure: bool) -> Policy {
+    Policy::custom(move |attempt| {
+        if attempt.previous().len() >= config::http::MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if !allow_insecure && attempt.url().scheme() != "https" {
+            let error = format!("refusing redirect to insecure URL {}", attempt.url());
+            attempt.error(error)
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+/// HTTP client for image and checksum downloads
+pub fn download_client(allow_insecure: bool) -> Result<Client, String> {
+    proxy::client_builder()
+        .user_agent(config::app::USER_AGENT)
+        .redirect(redirect_policy(allow_insecure))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// Whether `url` would be served from the image cache without downloading
+pub fn is_download_cached(url: &str) -> bool {
+    extract_filename(url).is_ok_and(|filename| {
+        let cache_dir = crate::cache::get_images_cache_dir();
+        cache_dir.join(filename).is_file()
+            || cache_dir.join(filename.trim_end_matches(".xz")).is_file()
+    })
+}
+
+/// Problem found by probing a download before it starts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProbeWarning {
+    /// The server could not be reached
+    Unreachable { error: String },
+    /// The server answered with an error, e.g. 404 for a missing nightly build
+    HttpStatus { status: u16 },
+    /// The server's size differs from the catalog's `file_size`
+    SizeMismatch { expected: u64, actual: u64 },
+}
+
+/// What a HEAD request tells about a download before it starts
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DownloadProbe {
+    /// Status of the final response after redirects, None if unreachable
+    pub status: Option<u16>,
+    pub content_length: Option<u64>,
+    /// The server accepts Range requests, so downloads could be resumed
+    pub accepts_ranges: bool,
+    /// Mirror the redirector chose
+    pub mirror_host: Option<String>,
+    /// The download may still work; these are shown so the user can decide
+    pub warnings: Vec<ProbeWarning>,
+}
+
+/// Warnings for a HEAD response
+///
+/// `expected_size` is the catalog size, 0 when unknown. Servers that don't
+/// implement HEAD (405, 501) say nothing about the download.
+fn probe_warnings(
+    status: u16,
+    content_length: Option<u64>,
+    expected_size: u64,
+) -> Vec<ProbeWarning> {
+    let mut warnings = Vec::new();
+    if status == 405 || status == 501 {
+        return warnings;
+    }
+    if !(200..300).contains(&status) {
+        warnings.push(ProbeWarning::HttpStatus { status });
+        return warnings;
+    }
+    match content_length {
+        Some(actual) if expected_size > 0 && actual != expected_size => {
+            warnings.push(ProbeWarning::SizeMismatch {
+                expected: expected_size,
+                actual,
+            });
+        }
+        _ => {}
+    }
+    warnings
+}
+
+/// Check with a HEAD request that a download is live and has the catalog size
+pub async fn probe_download(url: &str, expected_size: u64) -> DownloadProbe {
+    let client = match proxy::client_builder()
+        .user_agent(config::app::USER_AGENT)
+        .connect_timeout(std::time::Duration::from_secs(
+            config::http::SHORT_TIMEOUT_SECS,
+        ))
+        .timeout(std::time::Duration::from_secs(
+            config::http::SHORT_TIMEOUT_SECS,
+        ))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            return DownloadProbe {
+                warnings: vec![ProbeWarning::Unreachable {
+                    error: format!("Failed to create HTTP client: {}", e),
+                }],
+                ..DownloadProbe::default()
+            }
+        }
+    };
+
+    let response = match authorize(client.head(url), url).await.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            log_warn!(MODULE, "Download probe failed for {}: {}", url, e);
+            return DownloadProbe {
+                warnings: vec![ProbeWarning::Unreachable {
+                    error: e.to_string(),
+                }],
+                ..DownloadProbe::default()
+            };
+        }
+    };
+
+    let status = response.status().as_u16();
+    // content_length() is 0 for HEAD responses, the header carries the size
+    let content_length = response
+        .headers()
+        .get(reqwest::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let accepts_ranges = response
+        .headers()
+        .get(reqwest::header::ACCEPT_RANGES)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("bytes"));
+    let warnings = probe_warnings(status, content_length, expected_size);
+
+    log_info!(
+        MODULE,
+        "Download probe: status {}, size {:?}, ranges: {}",
+        status,
+        content_length,
+        accepts_ranges
+    );
+    for warning in &warnings {
+        log_warn!(MODULE, "Download probe for {}: {:?}", url, warning);
+    }
+
+    DownloadProbe {
+        status: Some(status),
+        content_length,
+        accepts_ranges,
+        mirror_host: response.url().host_str().map(|h| h.to_string()),
+        warnings,
+    }
+}
+
 /// Fetch expected SHA256 from URL
 /// Errors are prefixed with [SHA_UNAVAILABLE] to distinguish from SHA mismatch
-async fn fetch_expected_sha(client: &Client, sha_url: &str) -> Result<String, String> {
+pub async fn fetch_expected_sha(client: &Client, sha_url: &str) -> Result<String, String> {
     log_debug!(MODULE, "Fetching SHA256 from: {}", sha_url);
 
-    let response = client
-        .get(sha_url)
+    let response = authorize(client.get(sha_url), sha_url)
+        .await
         .send()
         .await
         .map_err(|e| format!("[SHA_UNAVAILABLE] Failed to fetch SHA: {}", e))?;
@@ -97,25 +421,51 @@ async fn fetch_expected_sha(client: &Client, sha_url: &str) -> Result<String, St
         .await
         .map_err(|e| format!("[SHA_UNAVAILABLE] Failed to read SHA response: {}", e))?;
 
-    // Parse SHA file format: "hash *filename" or "hash  filename"
-    let hash = content
-        .split_whitespace()
-        .next()
-        .ok_or("[SHA_UNAVAILABLE] Invalid SHA file format")?
-        .to_lowercase();
-
-    // Validate it looks like a SHA256 hash (64 hex chars)
-    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
-        return Err(format!(
+    let hash = parse_sha256_file(&content, None).ok_or_else(|| {
+        format!(
             "[SHA_UNAVAILABLE] Invalid SHA256 hash format: {}",
-            hash
-        ));
-    }
+            content.trim()
+        )
+    })?;
 
     log_debug!(MODULE, "Expected SHA256: {}", hash);
     Ok(hash)
 }
 
+/// Normalize a SHA256 hash, None if it is not 64 hex characters
+pub fn normalize_sha256(hash: &str) -> Option<String> {
+    let hash = hash.trim().to_lowercase();
+    (hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit())).then_some(hash)
+}
+
+/// Parse a SHA file: lines of "hash *filename", "hash  filename" or a bare hash
+///
+/// With `filename`, only the line naming that file or a bare hash counts;
+/// a hash listed for another file never stands in for it. Without, the
+/// first valid hash is taken.
+pub fn parse_sha256_file(content: &str, filename: Option<&str>) -> Option<String> {
+    let entries: Vec<(String, &str)> = content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let (hash, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let name = name.trim().trim_start_matches('*');
+            // Names may carry the path the file was hashed from
+            let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+            Some((normalize_sha256(hash)?, name))
+        })
+        .collect();
+
+    match filename {
+        Some(filename) => entries
+            .iter()
+            .find(|(_, name)| *name == filename)
+            .or_else(|| entries.iter().find(|(_, name)| name.is_empty())),
+        None => entries.first(),
+    }
+    .map(|(hash, _)| hash.clone())
+}
+
 /// Calculate SHA256 of a file
 fn calculate_file_sha256(path: &Path, state: &Arc<DownloadState>) -> Result<String, String> {
     log_debug!(MODULE, "Calculating SHA256 of: {}", path.display());
@@ -162,6 +512,13 @@ fn calculate_file_sha256(path: &Path, state: &Arc<DownloadState>) -> Result<Stri
     Ok(hash)
 }
 
+/// Calculate SHA256 of a file on the blocking pool, keeping the runtime free
+pub async fn hash_file_sha256(path: &Path, state: &Arc<DownloadState>) -> Result<String, String> {
+    let path = path.to_path_buf();
+    let state = state.clone();
+    run_blocking(move || calculate_file_sha256(&path, &state)).await
+}
+
 /// Verify file SHA256 against expected value
 async fn verify_sha256(
     client: &Client,
@@ -181,7 +538,7 @@ async fn verify_sha256(
         return Err("SHA256 verification cancelled".to_string());
     }
 
-    let actual = calculate_file_sha256(file_path, state)?;
+    let actual = hash_file_sha256(file_path, state).await?;
 
     if expected == actual {
         log_info!(MODULE, "SHA256 verification PASSED");
@@ -200,61 +557,122 @@ async fn verify_sha256(
     }
 }
 
-/// Download and decompress an Armbian image
-/// If sha_url is provided, verifies the downloaded compressed file before decompression
-pub async fn download_image(
-    url: &str,
-    sha_url: Option<&str>,
-    output_dir: &PathBuf,
-    state: Arc<DownloadState>,
-) -> Result<PathBuf, String> {
-    state.reset();
-    // Clear any stale temp_path from previous failed downloads
-    *state.temp_path.lock().await = None;
-
-    let filename = extract_filename(url)?;
-
-    // Determine output filename (remove .xz if present)
-    let output_filename = filename.trim_end_matches(".xz");
-    let output_path = output_dir.join(output_filename);
-
-    log_info!(MODULE, "Download requested: {}", url);
-    log_debug!(MODULE, "Output path: {}", output_path.display());
+/// A completed transfer from one mirror
+struct Fetched {
+    /// Mirror host that served the file (after redirects)
+    host: String,
+    /// Time until the response started
+    latency: std::time::Duration,
+    /// Bytes transferred from this mirror
+    bytes: u64,
+    elapsed: std::time::Duration,
+}
 
-    // Check if image is already in cache (also updates mtime for LRU)
-    if let Some(cached_path) = crate::cache::get_cached_image(output_filename) {
-        log_info!(MODULE, "Using cached image: {}", cached_path.display());
-        *state.output_path.lock().await = Some(cached_path.clone());
-        return Ok(cached_path);
+impl Fetched {
+    fn record_success(&self) {
+        record_mirror_success(&self.host, self.latency, self.bytes, self.elapsed);
     }
+}
 
-    // Create output directory if needed
-    std::fs::create_dir_all(output_dir)
-        .map_err(|e| format!("Failed to create output directory: {}", e))?;
-
-    let client = Client::builder()
-        .user_agent(config::app::USER_AGENT)
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+/// Download `url` into `temp_path`
+///
+/// With an offset to resume from, the bytes after it are requested and
+/// appended to the file; with segments, each range continues from its own
+/// progress. Otherwise, or if the server ignores the range, the file is
+/// replaced with what an earlier attempt left. A fresh download from a server
+/// that accepts ranges is split over several connections, see `segmented`.
+async fn fetch_to_file(
+    client: &Client,
+    url: &str,
+    temp_path: &Path,
+    resume: &Resume,
+    output_dir: &Path,
+    tracked: &Tracked,
+    state: &Arc<DownloadState>,
+) -> Result<Fetched, String> {
+    state.downloaded_bytes.store(0, Ordering::SeqCst);
+    state.clear_segments();
+    let segments = state.segment_count.load(Ordering::SeqCst).max(1);
 
     // Start download
-    log_info!(MODULE, "Starting download...");
-    let response = client.get(url).send().await.map_err(|e| {
+    log_info!(MODULE, "Starting download from {}", url);
+    let request_started = Instant::now();
+    let mut request = client.get(url);
+    let mut first_range = 0;
+    match resume {
+        Resume::Offset(offset) => {
+            request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+        }
+        // The first unfinished range is asked for, so the answer can carry it
+        Resume::Segments(ranges) => {
+            first_range = ranges
+                .iter()
+                .position(|range| range.remaining() > 0)
+                .unwrap_or(ranges.len().saturating_sub(1));
+            if let Some(range) = ranges.get(first_range) {
+                request = request.header(
+                    reqwest::header::RANGE,
+                    format!(
+                        "bytes={}-{}",
+                        range.start + range.downloaded_bytes,
+                        range.end
+                    ),
+                );
+            }
+        }
+        // A 206 answer shows the server accepts ranges and starts the first one
+        Resume::Start if segments > 1 => {
+            request = request.header(reqwest::header::RANGE, "bytes=0-");
+        }
+        Resume::Start => {}
+    }
+    let response = authorize(request, url).await.send().await.map_err(|e| {
         log_error!(MODULE, "Failed to start download: {}", e);
-        format!("Failed to start download: {}", e)
+        // The error carries the URL that failed, after any redirects
+        if let Some(host) = e.url().and_then(|u| u.host_str()) {
+            record_mirror_failure(host, &e.to_string());
+        }
+        transient(format!("Failed to start download: {}", e))
     })?;
+    let latency = request_started.elapsed();
+
+    // Record the mirror chosen by the redirector
+    let mirror_host = response.url().host_str().map(|h| h.to_string());
+    let host = mirror_host.clone().unwrap_or_else(|| "unknown".to_string());
 
     if !response.status().is_success() {
         log_error!(MODULE, "Download failed with status: {}", response.status());
-        return Err(format!(
-            "Download failed with status: {}",
-            response.status()
-        ));
+        let error = format!("Download failed with status: {}", response.status());
+        record_mirror_failure(&host, &error);
+        if is_transient_status(response.status()) {
+            return Err(transient(error));
+        }
+        return Err(error);
     }
 
-    // Get content length
-    let total_size = response.content_length().unwrap_or(0);
+    log_info!(
+        MODULE,
+        "Serving mirror: {} ({} ms to respond)",
+        host,
+        latency.as_millis()
+    );
+    *state.mirror_host.lock().await = mirror_host;
+
+    // A server that ignores the range sends the whole file again
+    let ranged = response.status() == StatusCode::PARTIAL_CONTENT;
+    let resume = if ranged { resume } else { &Resume::Start };
+    let offset = resume.bytes();
+    if offset > 0 {
+        log_info!(MODULE, "Resuming at {} bytes", offset);
+    }
+
+    // Get content length (0 if unknown); a resumed range only announces its own
+    let total_size = match resume {
+        Resume::Segments(ranges) => ranges.last().map_or(0, |range| range.end + 1),
+        _ => response.content_length().map_or(0, |len| len + offset),
+    };
     state.total_bytes.store(total_size, Ordering::SeqCst);
+    tracked.set_total(total_size);
 
     log_info!(
         MODULE,
@@ -263,30 +681,88 @@ pub async fn download_image(
         bytes_to_mb(total_size)
     );
 
-    // Create temp file for compressed data
-    let temp_path = output_dir.join(format!("{}.downloading", filename));
-    let mut temp_file =
-        File::create(&temp_path).map_err(|e| format!("Failed to create temp file: {}", e))?;
+    // Archives are cached as downloaded, so the download is all that needs room.
+    // Without a length only the SpaceWatcher guards the disk while writing.
+    let remaining = total_size.saturating_sub(offset);
+    if total_size == 0 {
+        log_warn!(
+            MODULE,
+            "Server sent no length, skipping the free space check"
+        );
+    } else {
+        ensure_space(output_dir, remaining, "the download")?;
+        if let Some(problem) =
+            storage_info(output_dir).and_then(|s| placement_problem(&s, total_size))
+        {
+            log_warn!(MODULE, "Image cache may not hold this image: {}", problem);
+        }
+    }
 
-    // Download with progress tracking
-    let mut stream = response.bytes_stream();
-    let mut downloaded: u64 = 0;
+    let ranges = match resume {
+        Resume::Segments(ranges) => ranges.clone(),
+        Resume::Start if ranged => {
+            plan_segments(total_size, segments, config::download::MIN_SEGMENT_SIZE)
+        }
+        _ => Vec::new(),
+    };
     let mut tracker = ProgressTracker::new(
         "Download",
         MODULE,
-        total_size,
+        remaining,
         config::logging::DOWNLOAD_LOG_INTERVAL_MB,
     );
 
+    if ranges.len() > 1 {
+        fetch_segments(
+            client,
+            response,
+            first_range,
+            ranges,
+            temp_path,
+            &host,
+            &mut tracker,
+            &SpaceWatcher::new(output_dir),
+            tracked,
+            state,
+        )
+        .await?;
+        tracker.finish();
+        let elapsed = tracker.elapsed();
+        let transferred = remaining;
+        record_speed(OperationStage::Download, None, transferred, None, elapsed);
+        return Ok(Fetched {
+            host,
+            latency,
+            bytes: transferred,
+            elapsed,
+        });
+    }
+
+    let mut temp_file = if offset > 0 {
+        OpenOptions::new().append(true).open(temp_path)
+    } else {
+        File::create(temp_path)
+    }
+    .map_err(|e| format!("Failed to create temp file: {}", e))?;
+
+    // Download with progress tracking
+    let mut stream = response.bytes_stream();
+    let mut downloaded: u64 = offset;
+    state.downloaded_bytes.store(downloaded, Ordering::SeqCst);
+    let mut throttle = Throttle::new(state.throttle_bytes_per_sec.load(Ordering::SeqCst));
+    let space = SpaceWatcher::new(output_dir);
+
     while let Some(chunk) = stream.next().await {
         if state.is_cancelled.load(Ordering::SeqCst) {
             log_info!(MODULE, "Download cancelled by user");
-            drop(temp_file);
-            let _ = std::fs::remove_file(&temp_path);
             return Err("Download cancelled".to_string());
         }
+        space.check_async(state).await?;
 
-        let chunk = chunk.map_err(|e| format!("Download error: {}", e))?;
+        let chunk = chunk.map_err(|e| {
+            record_mirror_failure(&host, &e.to_string());
+            transient(format!("Download error: {}", e))
+        })?;
         temp_file
             .write_all(&chunk)
             .map_err(|e| format!("Failed to write chunk: {}", e))?;
@@ -294,70 +770,235 @@ pub async fn download_image(
         downloaded += chunk.len() as u64;
         state.downloaded_bytes.store(downloaded, Ordering::SeqCst);
         tracker.update(chunk.len() as u64);
+        throttle
+            .pace_async(chunk.len() as u64, &state.is_cancelled)
+            .await;
     }
 
     drop(temp_file);
+    if total_size > 0 && downloaded < total_size {
+        let error = format!(
+            "Download ended after {} of {} bytes",
+            downloaded, total_size
+        );
+        record_mirror_failure(&host, &error);
+        return Err(transient(error));
+    }
     tracker.finish();
+    let elapsed = tracker.elapsed();
+    let transferred = downloaded - offset;
+    record_speed(OperationStage::Download, None, transferred, None, elapsed);
+
+    Ok(Fetched {
+        host,
+        latency,
+        bytes: transferred,
+        elapsed,
+    })
+}
+
+/// Download and decompress an Armbian image
+///
+/// Images on Armbian's redirector are retried on other mirrors when the
+/// download or its checksum fails, in the order of the preferred region.
+/// If sha_url is provided, verifies the downloaded compressed file before decompression
+pub async fn download_image(
+    url: &str,
+    sha_url: Option<&str>,
+    output_dir: &PathBuf,
+    state: Arc<DownloadState>,
+) -> Result<PathBuf, String> {
+    state.reset();
+    // Clear any stale temp_path from previous failed downloads
+    *state.temp_path.lock().await = None;
+    *state.mirror_host.lock().await = None;
 
-    // Verify SHA256 if URL provided
+    let filename = extract_filename(url)?;
+    let output_path = output_dir.join(filename);
+
+    log_info!(MODULE, "Download requested: {}", url);
+    log_debug!(MODULE, "Output path: {}", output_path.display());
+
+    // Check if image is already in cache (also updates mtime for LRU), as
+    // downloaded or decompressed by an older version
+    let cached = crate::cache::get_cached_image(filename)
+        .or_else(|| crate::cache::get_cached_image(filename.trim_end_matches(".xz")));
+    if let Some(cached_path) = cached {
+        log_info!(MODULE, "Using cached image: {}", cached_path.display());
+        *state.output_path.lock().await = Some(cached_path.clone());
+        return Ok(cached_path);
+    }
+
+    let allow_insecure = state.allow_insecure.load(Ordering::SeqCst);
+    check_download_url(url, allow_insecure)?;
     if let Some(sha_url) = sha_url {
-        state.is_verifying_sha.store(true, Ordering::SeqCst);
-        log_info!(MODULE, "Verifying SHA256...");
-        match verify_sha256(&client, &temp_path, sha_url, &state).await {
-            Ok(()) => {
-                log_info!(MODULE, "SHA256 verification successful");
+        check_download_url(sha_url, allow_insecure)?;
+    }
+
+    // Create output directory if needed
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Failed to create output directory: {}", e))?;
+
+    let client = download_client(allow_insecure)?;
+
+    // Partial data stays in a working directory until the image is complete;
+    // one left by an interrupted download of this image is continued
+    let (work, tracked, resumed) = match interrupted::claim(url, output_dir) {
+        Some(claimed) => claimed,
+        None => {
+            let work = WorkDir::create(output_dir, "download")?;
+            let tracked = interrupted::track(&work, url, sha_url, filename, output_dir);
+            (work, tracked, Resume::Start)
+        }
+    };
+    log_debug!(MODULE, "Downloading into {}", work.path().display());
+    let temp_path = work.join(partial_file_name(filename));
+
+    // A failed transfer or a corrupted copy is retried on the next mirror,
+    // once network errors have exhausted the retries of the current one.
+    // A resumed download gets one extra attempt from scratch, in case the
+    // partial file was damaged by the interruption.
+    let region = state.mirror_region.lock().await.clone();
+    let candidates = candidate_urls(url, &region).await;
+    let attempts: Vec<(&String, Resume)> = candidates
+        .first()
+        .filter(|_| resumed != Resume::Start)
+        .map(|first| (first, resumed.clone()))
+        .into_iter()
+        .chain(
+            candidates
+                .iter()
+                .map(|candidate| (candidate, Resume::Start)),
+        )
+        .collect();
+    let attempt_count = attempts.len();
+    let mut last_error = String::new();
+    let mut fetched = None;
+    let retry_limit = state.retry_limit.load(Ordering::SeqCst);
+    for (attempt, (candidate, mut resume)) in attempts.into_iter().enumerate() {
+        if attempt > 0 {
+            log_warn!(
+                MODULE,
+                "Retrying on the next mirror ({} of {}): {}",
+                attempt + 1,
+                attempt_count,
+                candidate
+            );
+        }
+
+        // Network errors are retried on the same mirror with a growing wait,
+        // continuing from the last byte written
+        let mut retry = 0;
+        let download = loop {
+            let error = match fetch_to_file(
+                &client, candidate, &temp_path, &resume, output_dir, &tracked, &state,
+            )
+            .await
+            {
+                Ok(download) => break Ok(download),
+                Err(_) if state.is_cancelled.load(Ordering::SeqCst) => {
+                    return Err(cancelled(work, tracked, &state))
+                }
+                // Another mirror would not make the disk any larger
+                Err(e) if is_no_space(&e) => return Err(e),
+                Err(e) => e,
+            };
+            let Some(error) = error.strip_prefix(TRANSIENT_MARKER).map(str::trim_start) else {
+                break Err(error);
+            };
+            if retry >= retry_limit {
+                break Err(error.to_string());
+            }
+            retry += 1;
+            state.retries.fetch_add(1, Ordering::SeqCst);
+
+            // Each range of a segmented download continues from its own
+            // progress; a single stream wrote everything up to its progress.
+            // An attempt that failed before writing leaves the resume as it was.
+            let segments = state
+                .segments
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone();
+            let written = state.downloaded_bytes.load(Ordering::SeqCst);
+            if !segments.is_empty() {
+                resume = Resume::Segments(segments);
+            } else if written > 0 {
+                resume = Resume::Offset(written);
+            }
+            let delay = retry_delay(retry);
+            log_warn!(
+                MODULE,
+                "{}; retrying from {} bytes in {} ms ({} of {})",
+                error,
+                resume.bytes(),
+                delay.as_millis(),
+                retry,
+                retry_limit
+            );
+            if !wait_for_retry(delay, &state).await {
+                return Err(cancelled(work, tracked, &state));
             }
+        };
+        let download = match download {
+            Ok(download) => download,
             Err(e) => {
-                log_error!(MODULE, "SHA256 verification failed: {}", e);
-                state.is_verifying_sha.store(false, Ordering::SeqCst);
+                last_error = e;
+                continue;
+            }
+        };
 
-                // Check if it was a cancellation
-                if state.is_cancelled.load(Ordering::SeqCst) {
-                    let _ = std::fs::remove_file(&temp_path);
-                    return Err("Download cancelled".to_string());
+        // Verify SHA256 if URL provided
+        if let Some(sha_url) = sha_url {
+            state.is_verifying_sha.store(true, Ordering::SeqCst);
+            log_info!(MODULE, "Verifying SHA256...");
+            let result = verify_sha256(&client, &temp_path, sha_url, &state).await;
+            state.is_verifying_sha.store(false, Ordering::SeqCst);
+            match result {
+                Ok(()) => {
+                    log_info!(MODULE, "SHA256 verification successful");
                 }
+                Err(e) => {
+                    log_error!(MODULE, "SHA256 verification failed: {}", e);
 
-                // If SHA is unavailable (fetch failed), keep the file for user decision
-                if e.contains("[SHA_UNAVAILABLE]") {
-                    log_info!(
-                        MODULE,
-                        "SHA unavailable, keeping temp file for user decision: {}",
-                        temp_path.display()
-                    );
-                    *state.temp_path.lock().await = Some(temp_path.clone());
-                    return Err(format!("SHA256 verification failed: {}", e));
-                }
+                    // Check if it was a cancellation
+                    if state.is_cancelled.load(Ordering::SeqCst) {
+                        return Err(cancelled(work, tracked, &state));
+                    }
 
-                // SHA mismatch (hash different) → delete file (corrupted image)
-                let _ = std::fs::remove_file(&temp_path);
-                return Err(format!("SHA256 verification failed: {}", e));
+                    // If SHA is unavailable (fetch failed), keep the file for user decision
+                    if e.contains("[SHA_UNAVAILABLE]") {
+                        log_info!(
+                            MODULE,
+                            "SHA unavailable, keeping temp file for user decision: {}",
+                            temp_path.display()
+                        );
+                        work.keep();
+                        *state.temp_path.lock().await = Some(temp_path.clone());
+                        download.record_success();
+                        return Err(format!("SHA256 verification failed: {}", e));
+                    }
+
+                    // SHA mismatch (hash different): this mirror's copy is corrupted
+                    record_mirror_failure(&download.host, "Checksum mismatch");
+                    last_error = format!("SHA256 verification failed: {}", e);
+                    continue;
+                }
             }
+        } else {
+            log_warn!(MODULE, "No SHA URL provided, skipping verification");
         }
-        state.is_verifying_sha.store(false, Ordering::SeqCst);
-    } else {
-        log_warn!(MODULE, "No SHA URL provided, skipping verification");
+        download.record_success();
+        fetched = Some(download);
+        break;
     }
-
-    // Decompress if needed
-    if filename.ends_with(".xz") {
-        state.is_decompressing.store(true, Ordering::SeqCst);
-        log_info!(
-            MODULE,
-            "Starting decompression with Rust lzma-rust2 (multi-threaded)..."
-        );
-
-        // Use Rust lzma-rust2 library (multi-threaded) on all platforms
-        decompress_with_rust_xz(&temp_path, &output_path, &state)?;
-        log_info!(MODULE, "Decompression complete");
-
-        // Clean up temp file
-        let _ = std::fs::remove_file(&temp_path);
-    } else {
-        // No decompression needed, just rename
-        std::fs::rename(&temp_path, &output_path)
-            .map_err(|e| format!("Failed to move file: {}", e))?;
+    // Every mirror failed; the working directory goes with the partial data
+    if fetched.is_none() {
+        return Err(last_error);
     }
 
+    work.persist(&temp_path, &output_path)?;
+
     log_info!(MODULE, "Image ready: {}", output_path.display());
     *state.output_path.lock().await = Some(output_path.clone());
     Ok(output_path)
@@ -376,20 +1017,13 @@ pub async fn continue_without_sha(
         .take()
         .ok_or("No pending download to continue")?;
 
-    // Defense in depth: verify temp_path is within cache directory
-    if let Ok(canonical_temp) = temp_path.canonicalize() {
-        if let Ok(canonical_cache) = output_dir.canonicalize() {
-            if !canonical_temp.starts_with(&canonical_cache) {
-                log_error!(
-                    MODULE,
-                    "Security: temp_path {} is outside cache directory {}",
-                    canonical_temp.display(),
-                    canonical_cache.display()
-                );
-                return Err("Invalid temp file location".to_string());
-            }
-        }
-    }
+    // Only a working directory kept by download_image may be resumed (and removed)
+    let work = WorkDir::adopt(temp_path.parent().ok_or("Invalid temp path")?, output_dir).map_err(
+        |e| {
+            log_error!(MODULE, "Security: {}", e);
+            "Invalid temp file location".to_string()
+        },
+    )?;
 
     log_info!(
         MODULE,
@@ -405,32 +1039,10 @@ pub async fn continue_without_sha(
     // temp_path is "filename.xz.downloading" or "filename.img.downloading"
     // Remove .downloading to get the original filename
     let original_filename = filename.trim_end_matches(".downloading");
-    // Output without .xz extension
-    let output_filename = original_filename.trim_end_matches(".xz");
-    let output_path = output_dir.join(output_filename);
+    let output_path = output_dir.join(original_filename);
 
     log_info!(MODULE, "Output path: {}", output_path.display());
-
-    // Decompress if needed
-    if original_filename.ends_with(".xz") {
-        state.is_decompressing.store(true, Ordering::SeqCst);
-        log_info!(
-            MODULE,
-            "Starting decompression with Rust lzma-rust2 (multi-threaded)..."
-        );
-
-        decompress_with_rust_xz(&temp_path, &output_path, &state)?;
-
-        state.is_decompressing.store(false, Ordering::SeqCst);
-        log_info!(MODULE, "Decompression complete");
-
-        // Clean up compressed temp file
-        let _ = std::fs::remove_file(&temp_path);
-    } else {
-        // No decompression needed, just rename
-        std::fs::rename(&temp_path, &output_path)
-            .map_err(|e| format!("Failed to move file: {}", e))?;
-    }
+    work.persist(&temp_path, &output_path)?;
 
     log_info!(MODULE, "Image ready: {}", output_path.display());
     *state.output_path.lock().await = Some(output_path.clone());
@@ -439,13 +1051,142 @@ pub async fn continue_without_sha(
 
 /// Clean up temp file from a failed download
 /// Called when user cancels after SHA unavailable error
-pub async fn cleanup_pending_download(state: Arc<DownloadState>) {
+pub async fn cleanup_pending_download(state: Arc<DownloadState>, output_dir: &Path) {
     if let Some(temp_path) = state.temp_path.lock().await.take() {
         log_info!(
             MODULE,
             "Cleaning up pending download: {}",
             temp_path.display()
         );
-        let _ = std::fs::remove_file(&temp_path);
+        // Dropping the adopted directory removes it with the partial file
+        if let Err(e) = WorkDir::adopt(temp_path.parent().unwrap_or(&temp_path), output_dir) {
+            log_warn!(MODULE, "Not removing pending download: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH_A: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+    const HASH_B: &str = "60303ae22b998861bce3b28f33eec1be758a213c86c93c076dbe9f558c11c752";
+
+    #[test]
+    fn test_probe_warnings() {
+        assert!(probe_warnings(200, Some(1234), 1234).is_empty());
+        // Unknown sizes on either side are not a mismatch
+        assert!(probe_warnings(200, None, 1234).is_empty());
+        assert!(probe_warnings(200, Some(1234), 0).is_empty());
+        assert_eq!(
+            probe_warnings(200, Some(999), 1234),
+            vec![ProbeWarning::SizeMismatch {
+                expected: 1234,
+                actual: 999
+            }]
+        );
+        assert_eq!(
+            probe_warnings(404, Some(999), 1234),
+            vec![ProbeWarning::HttpStatus { status: 404 }]
+        );
+        // HEAD not implemented
+        assert!(probe_warnings(405, None, 1234).is_empty());
+    }
+
+    #[test]
+    fn test_check_download_url() {
+        let url = "https://dl.armbian.com/rock-5b/Armbian.img.xz";
+        assert!(check_download_url(url, false).is_ok());
+
+        let insecure = "http://dl.armbian.com/rock-5b/Armbian.img.xz";
+        assert!(check_download_url(insecure, false).is_err());
+        assert!(check_download_url(insecure, true).is_ok());
+
+        // Other schemes are never downloaded from
+        assert!(check_download_url("file:///etc/passwd", true).is_err());
+        assert!(check_download_url("ftp://dl.armbian.com/x.img.xz", true).is_err());
+        assert!(check_download_url("not a url", true).is_err());
+    }
+
+    #[test]
+    fn test_parse_sha256_file() {
+        // Armbian style: "hash *filename"
+        let single = format!("{} *Armbian_25.2.1_Rock-5b.img.xz\n", HASH_A.to_uppercase());
+        assert_eq!(parse_sha256_file(&single, None), Some(HASH_A.to_string()));
+        assert_eq!(parse_sha256_file(HASH_B, None), Some(HASH_B.to_string()));
+
+        // sha256sum output for several files picks the named one
+        let multi = format!("{}  other.img.xz\n{}  ./out/foo.img.xz\n", HASH_A, HASH_B);
+        assert_eq!(
+            parse_sha256_file(&multi, Some("foo.img.xz")),
+            Some(HASH_B.to_string())
+        );
+        assert_eq!(parse_sha256_file(&multi, Some("missing.img")), None);
+        assert_eq!(parse_sha256_file(&single, Some("other.img.xz")), None);
+        assert_eq!(
+            parse_sha256_file(HASH_B, Some("foo.img.xz")),
+            Some(HASH_B.to_string())
+        );
+
+        assert_eq!(parse_sha256_file("not a hash  foo.img", None), None);
+        assert_eq!(parse_sha256_file("", None), None);
+    }
+
+    #[test]
+    fn test_progress_responsive_during_hashing() {
+        // One worker thread, as on a single-core machine
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let path =
+            std::env::temp_dir().join(format!("download-hash-test-{}.img", std::process::id()));
+        std::fs::write(&path, vec![0u8; 16 * 1024 * 1024]).unwrap();
+        let state = Arc::new(DownloadState::new());
+
+        let result = runtime.block_on(async {
+            let hashing = tokio::spawn({
+                let (path, state) = (path.clone(), state.clone());
+                async move { hash_file_sha256(&path, &state).await }
+            });
+
+            // A progress query gets the worker while hashing runs, and can cancel it
+            tokio::task::yield_now().await;
+            assert!(!hashing.is_finished());
+            assert!(!state.is_cancelled.load(Ordering::SeqCst));
+            state.is_cancelled.store(true, Ordering::SeqCst);
+            hashing.await.unwrap()
+        });
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result, Err("SHA256 verification cancelled".to_string()));
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        assert_eq!(retry_delay(1), Duration::from_secs(1));
+        assert_eq!(retry_delay(2), Duration::from_secs(2));
+        assert_eq!(retry_delay(4), Duration::from_secs(8));
+        assert_eq!(
+            retry_delay(60),
+            Duration::from_millis(config::download::RETRY_MAX_DELAY_MS)
+        );
+
+        assert!(is_transient_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_transient_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_transient_status(StatusCode::NOT_FOUND));
+        assert!(!is_transient_status(StatusCode::FORBIDDEN));
+    }
+
+    #[test]
+    fn test_resume_bytes() {
+        assert_eq!(Resume::Start.bytes(), 0);
+        assert_eq!(Resume::Offset(42).bytes(), 42);
+
+        // Each range counts what it received, wherever it is in the file
+        let mut ranges = plan_segments(4000, 4, 1000);
+        ranges[0].downloaded_bytes = 1000;
+        ranges[2].downloaded_bytes = 300;
+        assert_eq!(Resume::Segments(ranges).bytes(), 1300);
     }
 }
@@ -0,0 +1,259 @@
+//! Internal event bus
+//!
+//! Download, flash, cache and device code publish typed `AppEvent`s here
+//! instead of each wiring its own notification path. Subscribers (the
+//! frontend bridge, and later notifications, webhooks or audit sinks) are
+//! called synchronously on the publishing thread, so they must return
+//! quickly and must not block on I/O.
+//!
+//! The frontend bridge forwards every event to the webview as
+//! `config::events::FRONTEND_EVENT`. Progress events arrive far faster than
+//! the UI can render, so they are passed at most once per
+//! `config::events::PROGRESS_MIN_INTERVAL_MS` per operation; every other
+//! event is always delivered.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::config;
+use crate::log_warn;
+
+const MODULE: &str = "events";
+
+/// Event published by a backend subsystem
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AppEvent {
+    /// Bytes processed by a running operation ("Download", "Write", "Verify", ...)
+    Progress {
+        operation: String,
+        processed_bytes: u64,
+        total_bytes: u64,
+    },
+    DownloadStarted {
+        url: String,
+    },
+    DownloadFinished {
+        url: String,
+        path: Option<String>,
+        error: Option<String>,
+    },
+    FlashStarted {
+        device_path: String,
+        image: String,
+    },
+    FlashFinished {
+        device_path: String,
+        success: bool,
+        cancelled: bool,
+        error: Option<String>,
+    },
+    VerifyFinished {
+        device_path: String,
+        success: bool,
+        /// Taken from a stored verification instead of reading the device
+        cached: bool,
+    },
+    DevicesChanged {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    CacheChanged {
+        /// Number of cached files removed
+        removed_files: usize,
+    },
+    /// The download queue was changed by a command or a finished download
+    QueueChanged,
+    /// Fleet mode started or stopped, or one of its jobs moved on
+    FleetChanged,
+    /// The note of an operation was set or removed
+    OperationNoted {
+        operation_id: String,
+        note: Option<String>,
+    },
+    /// Free space on a download or decompression volume ran low
+    LowDiskSpace {
+        path: String,
+        available_bytes: u64,
+        /// The operation waits until space is freed
+        paused: bool,
+    },
+}
+
+impl AppEvent {
+    /// Key under which frequent events are rate limited, None if never limited
+    fn rate_limit_key(&self) -> Option<&str> {
+        match self {
+            AppEvent::Progress { operation, .. } => Some(operation),
+            _ => None,
+        }
+    }
+}
+
+type Handler = Arc<dyn Fn(&AppEvent) + Send + Sync>;
+
+/// Handle returned by `subscribe`, used to unsubscribe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriptionId(u64);
+
+/// Typed publish/subscribe bus
+pub struct EventBus {
+    next_id: AtomicU64,
+    subscribers: RwLock<Vec<(SubscriptionId, Handler)>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(1),
+            subscribers: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn subscribe(&self, handler: impl Fn(&AppEvent) + Send + Sync + 'static) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id.fetch_add(1, Ordering::SeqCst));
+        self.subscribers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((id, Arc::new(handler)));
+        id
+    }
+
+    pub fn unsubscribe(&self, id: SubscriptionId) {
+        self.subscribers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|(sub_id, _)| *sub_id != id);
+    }
+
+    /// Deliver an event to every subscriber
+    pub fn publish(&self, event: &AppEvent) {
+        // Handlers run without the lock held, so they may publish or subscribe
+        let handlers: Vec<Handler> = self
+            .subscribers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(_, handler)| handler.clone())
+            .collect();
+
+        for handler in handlers {
+            handler(event);
+        }
+    }
+}
+
+/// Global event bus
+static BUS: Lazy<EventBus> = Lazy::new(EventBus::new);
+
+/// Publish an event on the global bus
+pub fn publish(event: AppEvent) {
+    BUS.publish(&event);
+}
+
+/// Subscribe to all events on the global bus
+pub fn subscribe(handler: impl Fn(&AppEvent) + Send + Sync + 'static) -> SubscriptionId {
+    BUS.subscribe(handler)
+}
+
+/// Stop receiving events on the global bus
+pub fn unsubscribe(id: SubscriptionId) {
+    BUS.unsubscribe(id)
+}
+
+/// Passes one event per key and interval
+struct RateLimiter {
+    interval: Duration,
+    last_sent: HashMap<String, Instant>,
+}
+
+impl RateLimiter {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_sent: HashMap::new(),
+        }
+    }
+
+    fn allow(&mut self, key: &str, now: Instant) -> bool {
+        match self.last_sent.get(key) {
+            Some(last) if now.duration_since(*last) < self.interval => false,
+            _ => {
+                self.last_sent.insert(key.to_string(), now);
+                true
+            }
+        }
+    }
+}
+
+/// Forward bus events to the frontend, rate limiting progress
+pub fn start_frontend_bridge(app: AppHandle) -> SubscriptionId {
+    let limiter = Mutex::new(RateLimiter::new(Duration::from_millis(
+        config::events::PROGRESS_MIN_INTERVAL_MS,
+    )));
+
+    BUS.subscribe(move |event| {
+        if let Some(key) = event.rate_limit_key() {
+            let mut limiter = limiter.lock().unwrap_or_else(|e| e.into_inner());
+            if !limiter.allow(key, Instant::now()) {
+                return;
+            }
+        }
+        if let Err(e) = app.emit(config::events::FRONTEND_EVENT, event) {
+            log_warn!(MODULE, "Failed to forward event to frontend: {}", e);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(operation: &str, processed_bytes: u64) -> AppEvent {
+        AppEvent::Progress {
+            operation: operation.to_string(),
+            processed_bytes,
+            total_bytes: 100,
+        }
+    }
+
+    #[test]
+    fn test_publish_and_unsubscribe() {
+        let bus = EventBus::new();
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let sink = received.clone();
+        let id = bus.subscribe(move |event| sink.lock().unwrap().push(event.clone()));
+
+        bus.publish(&progress("Write", 1));
+        bus.unsubscribe(id);
+        bus.publish(&progress("Write", 2));
+
+        assert_eq!(*received.lock().unwrap(), vec![progress("Write", 1)]);
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(Duration::from_millis(200));
+
+        assert!(limiter.allow("Write", start));
+        assert!(!limiter.allow("Write", start + Duration::from_millis(100)));
+        // Keys are limited independently
+        assert!(limiter.allow("Verify", start + Duration::from_millis(100)));
+        assert!(limiter.allow("Write", start + Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn test_only_progress_is_rate_limited() {
+        assert_eq!(progress("Write", 1).rate_limit_key(), Some("Write"));
+        let event = AppEvent::CacheChanged { removed_files: 1 };
+        assert_eq!(event.rate_limit_key(), None);
+    }
+}
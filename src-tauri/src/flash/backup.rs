@@ -0,0 +1,267 @@
+//! Device backups
+//!
+//! The reverse of flashing: a block device is read from start to end and
+//! compressed into an `.img.xz` or `.img.zst` file, which can be flashed
+//! back later like any downloaded image. Each platform opens the device the
+//! way it does for verification and hands the file to `backup_to_file`.
+//!
+//! The archive is written in a working directory next to the destination
+//! and only moved into place once complete, so a cancelled or failed backup
+//! never leaves a truncated archive behind.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::num::NonZeroU64;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+use lzma_rust2::{XzOptions, XzWriterMt};
+use zstd::stream::write::Encoder as ZstdEncoder;
+
+use crate::config;
+use crate::utils::{bytes_to_gb, get_recommended_threads, ProgressTracker};
+use crate::workdir::WorkDir;
+use crate::{log_debug, log_info};
+
+use super::{speed_window, FlashState};
+
+const MODULE: &str = "flash::backup";
+
+/// Compression of a backup, chosen by the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BackupFormat {
+    Xz,
+    Zstd,
+}
+
+impl BackupFormat {
+    fn for_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "xz" => Some(Self::Xz),
+            "zst" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Compressing writer for either format
+enum Encoder<W: Write> {
+    Xz(Box<XzWriterMt<W>>),
+    Zstd(ZstdEncoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    fn new(format: BackupFormat, inner: W) -> io::Result<Self> {
+        match format {
+            BackupFormat::Xz => {
+                let mut options = XzOptions::with_preset(config::backup::XZ_PRESET);
+                options.set_block_size(NonZeroU64::new(config::backup::XZ_BLOCK_SIZE));
+                let threads = get_recommended_threads() as u32;
+                XzWriterMt::new(inner, options, threads).map(|writer| Self::Xz(Box::new(writer)))
+            }
+            BackupFormat::Zstd => {
+                ZstdEncoder::new(inner, config::backup::ZSTD_LEVEL).map(Self::Zstd)
+            }
+        }
+    }
+
+    /// Write the end of the archive and return the inner writer
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Self::Xz(writer) => (*writer).finish(),
+            Self::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Xz(writer) => writer.write(buf),
+            Self::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Xz(writer) => writer.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+fn backup_format(output: &Path) -> Result<BackupFormat, String> {
+    BackupFormat::for_path(output).ok_or_else(|| {
+        format!(
+            "Unsupported backup format: {} (use .img.xz or .img.zst)",
+            output.display()
+        )
+    })
+}
+
+/// Check that a backup can be written to `output` before opening the device
+pub fn check_backup_path(output: &Path) -> Result<(), String> {
+    backup_format(output).map(|_| ())
+}
+
+/// Read `device_size` bytes from an opened device into a compressed image
+///
+/// Progress is reported through `state` as written bytes, so the flash
+/// progress events and the watchdog work unchanged. Reads are whole chunks
+/// except at the end of the device, which keeps them sector-aligned for
+/// unbuffered device handles. Returns the size of the archive.
+pub fn backup_to_file(
+    device: &mut impl Read,
+    device_size: u64,
+    output: &Path,
+    state: &FlashState,
+) -> Result<u64, String> {
+    let format = backup_format(output)?;
+    let file_name = output
+        .file_name()
+        .ok_or_else(|| format!("Invalid backup path: {}", output.display()))?;
+    let dest_dir = output
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    state.total_bytes.store(device_size, Ordering::SeqCst);
+    log_info!(
+        MODULE,
+        "Backing up {} bytes ({:.2} GB) to {} as {:?}",
+        device_size,
+        bytes_to_gb(device_size),
+        output.display(),
+        format
+    );
+
+    let work = WorkDir::create(dest_dir, "backup")?;
+    let file = File::create(work.join(file_name))
+        .map_err(|e| format!("Failed to create backup file: {}", e))?;
+    let writer = BufWriter::with_capacity(config::cache::COPY_BUFFER_SIZE, file);
+    let mut encoder =
+        Encoder::new(format, writer).map_err(|e| format!("Failed to start compression: {}", e))?;
+
+    let chunk_size = config::flash::CHUNK_SIZE;
+    let mut buffer = vec![0u8; chunk_size];
+    let mut read: u64 = 0;
+    let mut tracker = ProgressTracker::new(
+        "Backup",
+        MODULE,
+        device_size,
+        config::logging::WRITE_LOG_INTERVAL_MB,
+    );
+    let mut speed = speed_window();
+
+    while read < device_size {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Err("Backup cancelled".to_string());
+        }
+
+        let len = std::cmp::min(chunk_size as u64, device_size - read) as usize;
+        device
+            .read_exact(&mut buffer[..len])
+            .map_err(|e| format!("Failed to read device at byte {}: {}", read, e))?;
+        encoder
+            .write_all(&buffer[..len])
+            .map_err(|e| format!("Failed to write backup: {}", e))?;
+
+        read += len as u64;
+        state.written_bytes.store(read, Ordering::SeqCst);
+        state
+            .write_elapsed_ms
+            .store(tracker.elapsed().as_millis() as u64, Ordering::SeqCst);
+        state.record_throughput(&mut speed);
+        tracker.update(len as u64);
+    }
+    tracker.finish();
+
+    let file = encoder
+        .finish()
+        .and_then(|writer| writer.into_inner().map_err(|e| e.into_error()))
+        .map_err(|e| format!("Failed to finish backup: {}", e))?;
+    file.sync_all()
+        .map_err(|e| format!("Failed to sync backup: {}", e))?;
+    let compressed = file
+        .metadata()
+        .map(|m| m.len())
+        .map_err(|e| format!("Failed to read backup size: {}", e))?;
+    drop(file);
+
+    work.persist(file_name, output)?;
+    log_debug!(
+        MODULE,
+        "Backup of {} bytes compressed to {} bytes",
+        device_size,
+        compressed
+    );
+    Ok(compressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flash::ImageReader;
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("backup-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn device_data() -> Vec<u8> {
+        // More than one chunk, ending in a partial one
+        let size = config::flash::CHUNK_SIZE + 512 * 1024;
+        (0..size).map(|i| (i / 4096 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_backup_round_trip() {
+        let dir = test_dir("round-trip");
+        let data = device_data();
+
+        for name in ["card.img.xz", "card.img.zst"] {
+            let output = dir.join(name);
+            let state = FlashState::new();
+            let compressed =
+                backup_to_file(&mut Cursor::new(&data), data.len() as u64, &output, &state)
+                    .unwrap();
+            assert_eq!(fs::metadata(&output).unwrap().len(), compressed);
+            assert_eq!(
+                state.written_bytes.load(Ordering::SeqCst),
+                data.len() as u64
+            );
+
+            let mut restored = Vec::new();
+            ImageReader::open(&output)
+                .unwrap()
+                .read_to_end(&mut restored)
+                .unwrap();
+            assert!(restored == data, "{} does not restore the device", name);
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_backup_cancelled_leaves_nothing() {
+        let dir = test_dir("cancel");
+        let data = device_data();
+        let output = dir.join("card.img.zst");
+        let state = FlashState::new();
+        state.is_cancelled.store(true, Ordering::SeqCst);
+
+        let result = backup_to_file(&mut Cursor::new(&data), data.len() as u64, &output, &state);
+        assert!(result.is_err());
+        assert!(!output.exists());
+        assert!(!crate::workdir::work_root(&dir).exists());
+
+        assert!(check_backup_path(&dir.join("card.img")).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
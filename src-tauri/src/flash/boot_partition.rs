@@ -0,0 +1,337 @@
+//! Boot partition access
+//!
+//! Mounts the first partition of a freshly written device so files can be
+//! placed on it: failure reports and first-boot customization. Most Armbian
+//! images have a single root filesystem with a `/boot` directory; images
+//! with a separate boot partition put it first.
+//!
+//! Partitions can also be mounted on demand after flashing, e.g. to tweak
+//! `armbianEnv.txt` by hand. Those mounts are tracked and released before
+//! the next operation on the device.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use crate::utils::{mount_timeout, query_timeout, run_command};
+use crate::{log_debug, log_info, log_warn};
+
+const MODULE: &str = "flash::boot_partition";
+
+/// Partitions mounted on demand, as (device, partition number, mount point)
+static MOUNTED: Mutex<Vec<(String, usize, PathBuf)>> = Mutex::new(Vec::new());
+
+/// Mount the first partition of the device, run `f` on its mount point and
+/// unmount again
+///
+/// A partition that was already mounted is left mounted.
+pub fn with_first_partition<T>(
+    device_path: &str,
+    f: impl FnOnce(&Path) -> Result<T, String>,
+) -> Result<T, String> {
+    let partition = first_partition_path(device_path);
+    log_debug!(MODULE, "Mounting partition {}", partition);
+
+    let (mount_point, mounted_by_us) = mount_partition(&partition)?;
+    let result = f(&mount_point);
+
+    if mounted_by_us {
+        if let Err(e) = unmount_partition(&partition) {
+            log_warn!(MODULE, "Failed to unmount {}: {}", partition, e);
+        }
+    }
+    result
+}
+
+/// Directory boot files go to: `/boot` if the partition has one, else its root
+pub fn boot_dir(mount_point: &Path) -> PathBuf {
+    let boot = mount_point.join("boot");
+    if boot.is_dir() {
+        boot
+    } else {
+        mount_point.to_path_buf()
+    }
+}
+
+/// Mount partition `index` (1-based) of a device and return its mount point
+///
+/// A partition we mount stays mounted until `release_mounts` is called for
+/// the device, which every later operation on it does first. One that was
+/// already mounted is left alone.
+pub fn mount_device_partition(device_path: &str, index: usize) -> Result<PathBuf, String> {
+    if index == 0 {
+        return Err("Partition numbers start at 1".to_string());
+    }
+    log_info!(MODULE, "Mounting partition {} of {}", index, device_path);
+
+    #[cfg(target_os = "windows")]
+    let (mount_point, mounted_by_us) = windows_mount(device_path, index)?;
+    #[cfg(not(target_os = "windows"))]
+    let (mount_point, mounted_by_us) = mount_partition(&partition_path(device_path, index))?;
+
+    if mounted_by_us {
+        let mut mounted = MOUNTED.lock().unwrap_or_else(|e| e.into_inner());
+        mounted.push((device_path.to_string(), index, mount_point.clone()));
+    }
+    Ok(mount_point)
+}
+
+/// Unmount the partitions of a device that were mounted on demand
+pub fn release_mounts(device_path: &str) {
+    let released: Vec<(String, usize, PathBuf)> = {
+        let mut mounted = MOUNTED.lock().unwrap_or_else(|e| e.into_inner());
+        let (released, kept) = mounted.drain(..).partition(|(d, _, _)| d == device_path);
+        *mounted = kept;
+        released
+    };
+
+    for (_, index, _mount_point) in released {
+        #[cfg(target_os = "windows")]
+        let result = windows_unmount(device_path, index, &_mount_point);
+        #[cfg(not(target_os = "windows"))]
+        let result = unmount_partition(&partition_path(device_path, index));
+
+        match result {
+            Ok(()) => log_info!(MODULE, "Unmounted partition {} of {}", index, device_path),
+            Err(e) => log_warn!(
+                MODULE,
+                "Failed to unmount partition {} of {}: {}",
+                index,
+                device_path,
+                e
+            ),
+        }
+    }
+}
+
+/// Path of partition `index` of a whole-disk device
+#[cfg(any(target_os = "linux", test))]
+fn linux_partition_path(device_path: &str, index: usize) -> String {
+    // /dev/mmcblk0 and /dev/nvme0n1 use a "p" separator, /dev/sdb does not
+    if device_path.ends_with(|c: char| c.is_ascii_digit()) {
+        format!("{}p{}", device_path, index)
+    } else {
+        format!("{}{}", device_path, index)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn partition_path(device_path: &str, index: usize) -> String {
+    linux_partition_path(device_path, index)
+}
+
+#[cfg(target_os = "macos")]
+fn partition_path(device_path: &str, index: usize) -> String {
+    format!(
+        "{}s{}",
+        device_path.replace("/dev/rdisk", "/dev/disk"),
+        index
+    )
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn first_partition_path(device_path: &str) -> String {
+    partition_path(device_path, 1)
+}
+
+#[cfg(target_os = "windows")]
+fn first_partition_path(device_path: &str) -> String {
+    device_path.to_string()
+}
+
+/// Mount a partition, returning its mount point and whether we mounted it
+#[cfg(target_os = "linux")]
+fn mount_partition(partition: &str) -> Result<(PathBuf, bool), String> {
+    if let Some(existing) = linux_mount_point(partition) {
+        return Ok((existing, false));
+    }
+
+    let output = run_command(
+        "udisksctl",
+        &["mount", "-b", partition, "--no-user-interaction"],
+        mount_timeout(),
+    )?;
+
+    if !output.success() {
+        return Err(format!(
+            "Failed to mount {}: {}",
+            partition,
+            output.stderr.trim()
+        ));
+    }
+
+    linux_mount_point(partition)
+        .map(|path| (path, true))
+        .ok_or_else(|| format!("Mount point of {} not found", partition))
+}
+
+#[cfg(target_os = "linux")]
+fn linux_mount_point(partition: &str) -> Option<PathBuf> {
+    let output = run_command("lsblk", &["-no", "MOUNTPOINT", partition], query_timeout()).ok()?;
+    output
+        .stdout
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(PathBuf::from)
+}
+
+#[cfg(target_os = "linux")]
+fn unmount_partition(partition: &str) -> Result<(), String> {
+    run_command(
+        "udisksctl",
+        &["unmount", "-b", partition, "--no-user-interaction"],
+        mount_timeout(),
+    )?
+    .check()
+    .map(|_| ())
+}
+
+#[cfg(target_os = "macos")]
+fn mount_partition(partition: &str) -> Result<(PathBuf, bool), String> {
+    let info = run_command("diskutil", &["info", partition], query_timeout())?.check()?;
+    if let Some(existing) = diskutil_mount_point(&info.stdout) {
+        return Ok((existing, false));
+    }
+    // diskutil lists no personality for file systems macOS has no driver for,
+    // and `diskutil mount` then fails without saying why
+    if diskutil_field(&info.stdout, "File System Personality:").is_none() {
+        return Err(format!(
+            "macOS can't mount the file system of {} (usually Linux ext4); \
+             apply these settings from Linux or on the board itself",
+            partition
+        ));
+    }
+
+    run_command("diskutil", &["mount", partition], mount_timeout())?.check()?;
+
+    run_command("diskutil", &["info", partition], query_timeout())
+        .ok()
+        .and_then(|info| diskutil_mount_point(&info.stdout))
+        .map(|path| (path, true))
+        .ok_or_else(|| format!("Mount point of {} not found", partition))
+}
+
+/// Value of a `diskutil info` field, if present and not empty
+#[cfg(any(target_os = "macos", test))]
+fn diskutil_field<'a>(info: &'a str, key: &str) -> Option<&'a str> {
+    info.lines()
+        .find_map(|line| line.trim().strip_prefix(key))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn diskutil_mount_point(info: &str) -> Option<PathBuf> {
+    diskutil_field(info, "Mount Point:")
+        .filter(|path| !path.starts_with("Not applicable"))
+        .map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+fn unmount_partition(partition: &str) -> Result<(), String> {
+    run_command("diskutil", &["unmount", partition], mount_timeout())?
+        .check()
+        .map(|_| ())
+}
+
+#[cfg(target_os = "windows")]
+fn mount_partition(_partition: &str) -> Result<(PathBuf, bool), String> {
+    Err("Mounting the boot partition is not supported on Windows".to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn unmount_partition(_partition: &str) -> Result<(), String> {
+    Ok(())
+}
+
+/// Run a PowerShell script without a console window
+#[cfg(target_os = "windows")]
+fn run_powershell(script: &str) -> Result<String, String> {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    use crate::utils::{mount_timeout, powershell_binary, run_with_timeout};
+
+    /// Keeps the PowerShell console from flashing up
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let mut command = Command::new(powershell_binary());
+    command
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .creation_flags(CREATE_NO_WINDOW);
+    run_with_timeout(&mut command, mount_timeout())
+        .and_then(|output| output.check())
+        .map(|output| output.stdout)
+}
+
+#[cfg(target_os = "windows")]
+fn windows_disk_number(device_path: &str) -> Result<u32, String> {
+    device_path
+        .strip_prefix(r"\\.\PhysicalDrive")
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| format!("Not a physical drive: {}", device_path))
+}
+
+/// Give the partition a drive letter unless it has one
+#[cfg(target_os = "windows")]
+fn windows_mount(device_path: &str, index: usize) -> Result<(PathBuf, bool), String> {
+    let disk = windows_disk_number(device_path)?;
+    let script = format!(
+        "$p = Get-Partition -DiskNumber {disk} -PartitionNumber {index} -ErrorAction Stop; \
+         if ($p.DriveLetter -match '[A-Z]') {{ \"existing|$($p.DriveLetter)\" }} else {{ \
+         $p | Add-PartitionAccessPath -AssignDriveLetter -ErrorAction Stop; \
+         $p = Get-Partition -DiskNumber {disk} -PartitionNumber {index}; \"new|$($p.DriveLetter)\" }}"
+    );
+    let output = run_powershell(&script)?;
+    let (kind, letter) = output
+        .trim()
+        .split_once('|')
+        .filter(|(_, letter)| letter.len() == 1)
+        .ok_or_else(|| format!("No drive letter assigned to partition {}", index))?;
+    Ok((PathBuf::from(format!("{}:\\", letter)), kind == "new"))
+}
+
+#[cfg(target_os = "windows")]
+fn windows_unmount(device_path: &str, index: usize, mount_point: &Path) -> Result<(), String> {
+    let disk = windows_disk_number(device_path)?;
+    run_powershell(&format!(
+        "Remove-PartitionAccessPath -DiskNumber {} -PartitionNumber {} -AccessPath '{}' -ErrorAction Stop",
+        disk,
+        index,
+        mount_point.display()
+    ))
+    .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linux_partition_path() {
+        assert_eq!(linux_partition_path("/dev/sdb", 1), "/dev/sdb1");
+        assert_eq!(linux_partition_path("/dev/mmcblk0", 2), "/dev/mmcblk0p2");
+        assert_eq!(linux_partition_path("/dev/nvme0n1", 1), "/dev/nvme0n1p1");
+    }
+
+    #[test]
+    fn test_diskutil_info() {
+        let fat = "   Device Node:              /dev/disk4s1\n   \
+                   File System Personality:  MS-DOS FAT32\n   \
+                   Mount Point:              /Volumes/BOOT\n";
+        assert_eq!(
+            diskutil_mount_point(fat),
+            Some(PathBuf::from("/Volumes/BOOT"))
+        );
+        assert_eq!(
+            diskutil_field(fat, "File System Personality:"),
+            Some("MS-DOS FAT32")
+        );
+
+        let ext4 = "   Device Node:              /dev/disk4s1\n   \
+                    Mount Point:              Not applicable (no file system)\n";
+        assert_eq!(diskutil_mount_point(ext4), None);
+        assert_eq!(diskutil_field(ext4, "File System Personality:"), None);
+    }
+}
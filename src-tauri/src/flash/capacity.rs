@@ -0,0 +1,316 @@
+//! Fake capacity detection
+//!
+//! Counterfeit cards report more capacity than they have: writes past the
+//! real capacity are dropped or wrap around onto lower addresses. Like F3 and
+//! H2testw, the check writes pseudo-random markers spread over the whole
+//! device, each derived from its own offset, and reads them back. A block
+//! that reads back garbage is past the real capacity, and so is a block whose
+//! marker turns up at a lower offset. Samples sit on a power-of-two grid, so
+//! the usual power-of-two real capacity wraps them onto each other.
+//!
+//! Only the sampled blocks are touched, and their original contents are
+//! written back afterwards, also when the check fails or is cancelled. Blocks
+//! past the real capacity of a fake card can't hold data anyway. The markers
+//! together are larger than the write caches of card controllers, so the
+//! read-back reaches the flash.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::atomic::Ordering;
+
+use serde::Serialize;
+
+use crate::config;
+use crate::utils::bytes_to_gb;
+use crate::{log_info, log_warn};
+
+use super::FlashState;
+
+const MODULE: &str = "flash::capacity";
+
+/// Result of a capacity check
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CapacityReport {
+    /// Size the device reports
+    pub reported_bytes: u64,
+    /// Bytes below the first block past the real capacity; the reported size
+    /// if every marker survived
+    pub usable_bytes: u64,
+    pub samples: usize,
+    pub bad_samples: usize,
+    /// Whether every marker read back intact
+    pub genuine: bool,
+}
+
+/// Offsets of the sampled blocks: multiples of the power of two that yields
+/// between `samples` and twice as many blocks on the device
+///
+/// `block` must be a power of two.
+fn sample_offsets(device_size: u64, samples: usize, block: u64) -> Vec<u64> {
+    let spacing = (device_size / samples.max(1) as u64).max(block);
+    let step = 1u64 << (63 - spacing.leading_zeros());
+    (0..)
+        .map(|i| i * step)
+        .take_while(|offset| offset + block <= device_size)
+        .collect()
+}
+
+/// Marker for the block at `offset`: xorshift output seeded by the offset
+fn marker(seed: u64, offset: u64, len: usize) -> Vec<u8> {
+    let mut x = (seed ^ offset.wrapping_mul(0x9e37_79b9_7f4a_7c15)) | 1;
+    let mut data = Vec::with_capacity(len);
+    while data.len() < len {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        data.extend_from_slice(&x.to_le_bytes());
+    }
+    // The offset itself leads the block, as in F3
+    data[..8].copy_from_slice(&offset.to_le_bytes());
+    data.truncate(len);
+    data
+}
+
+fn read_block<D: Read + Seek>(device: &mut D, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+    let mut block = vec![0u8; len];
+    device
+        .seek(SeekFrom::Start(offset))
+        .and_then(|_| device.read_exact(&mut block))
+        .map_err(|e| format!("Failed to read device at byte {}: {}", offset, e))?;
+    Ok(block)
+}
+
+fn write_block<D: Write + Seek>(device: &mut D, offset: u64, data: &[u8]) -> Result<(), String> {
+    device
+        .seek(SeekFrom::Start(offset))
+        .and_then(|_| device.write_all(data))
+        .map_err(|e| format!("Failed to write device at byte {}: {}", offset, e))
+}
+
+/// Check that a device holds data across its reported size
+///
+/// `drop_caches` runs between writing and reading the markers and must make
+/// the reads reach the device, e.g. by syncing and evicting the page cache.
+pub fn check_capacity<D: Read + Write + Seek>(
+    device: &mut D,
+    device_size: u64,
+    state: &FlashState,
+    mut drop_caches: impl FnMut(&mut D) -> Result<(), String>,
+) -> Result<CapacityReport, String> {
+    let block = config::flash::CAPACITY_CHECK_BLOCK_SIZE;
+    let offsets = sample_offsets(
+        device_size,
+        config::flash::CAPACITY_CHECK_SAMPLES,
+        block as u64,
+    );
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+
+    log_info!(
+        MODULE,
+        "Checking capacity: {} markers of {} bytes over {:.2} GB",
+        offsets.len(),
+        block,
+        bytes_to_gb(device_size)
+    );
+    state
+        .total_bytes
+        .store(offsets.len() as u64 * block as u64, Ordering::SeqCst);
+
+    let mut originals: Vec<(u64, Vec<u8>)> = Vec::with_capacity(offsets.len());
+    let result = write_and_read_markers(
+        device,
+        &offsets,
+        block,
+        seed,
+        state,
+        &mut originals,
+        &mut drop_caches,
+    );
+
+    // Put the sampled blocks back, the lowest last: on a card that wraps
+    // around, it shares its storage with higher blocks
+    let mut restore_error = None;
+    for (offset, data) in originals.iter().rev() {
+        if let Err(e) = write_block(device, *offset, data) {
+            restore_error.get_or_insert(e);
+        }
+    }
+    device.flush().ok();
+    if let Some(e) = restore_error {
+        log_warn!(MODULE, "Failed to restore sampled blocks: {}", e);
+        if result.is_ok() {
+            return Err(e);
+        }
+    }
+
+    let bad: Vec<u64> = result?;
+    let usable_bytes = bad.first().copied().unwrap_or(device_size);
+    let report = CapacityReport {
+        reported_bytes: device_size,
+        usable_bytes,
+        samples: offsets.len(),
+        bad_samples: bad.len(),
+        genuine: bad.is_empty(),
+    };
+    if report.genuine {
+        log_info!(MODULE, "All {} markers intact", report.samples);
+    } else {
+        log_warn!(
+            MODULE,
+            "{} of {} markers lost; the device reports {:.2} GB but holds data only up to {:.2} GB",
+            report.bad_samples,
+            report.samples,
+            bytes_to_gb(device_size),
+            bytes_to_gb(usable_bytes)
+        );
+    }
+    Ok(report)
+}
+
+/// Write every marker, then read them all back
+///
+/// Returns the offsets of the blocks past the real capacity, in order.
+fn write_and_read_markers<D: Read + Write + Seek>(
+    device: &mut D,
+    offsets: &[u64],
+    block: usize,
+    seed: u64,
+    state: &FlashState,
+    originals: &mut Vec<(u64, Vec<u8>)>,
+    drop_caches: &mut impl FnMut(&mut D) -> Result<(), String>,
+) -> Result<Vec<u64>, String> {
+    for &offset in offsets {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Err("Capacity check cancelled".to_string());
+        }
+        originals.push((offset, read_block(device, offset, block)?));
+        write_block(device, offset, &marker(seed, offset, block))?;
+        state
+            .written_bytes
+            .fetch_add(block as u64, Ordering::SeqCst);
+    }
+    device
+        .flush()
+        .map_err(|e| format!("Failed to flush the device: {}", e))?;
+    drop_caches(device)?;
+
+    // Which sample's marker each block holds; None for garbage
+    state.is_verifying.store(true, Ordering::SeqCst);
+    let mut owners = Vec::with_capacity(offsets.len());
+    for &offset in offsets {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Err("Capacity check cancelled".to_string());
+        }
+        let data = read_block(device, offset, block)?;
+        let owner = u64::from_le_bytes(data[..8].try_into().unwrap_or_default());
+        let intact = offsets.binary_search(&owner).is_ok() && data == marker(seed, owner, block);
+        owners.push(intact.then_some(owner));
+        state
+            .verified_bytes
+            .fetch_add(block as u64, Ordering::SeqCst);
+    }
+
+    // Samples reading the same marker share storage; only the lowest of them
+    // is real
+    let mut lowest_reader = std::collections::HashMap::new();
+    for (&offset, owner) in offsets.iter().zip(&owners) {
+        if let Some(owner) = owner {
+            lowest_reader.entry(*owner).or_insert(offset);
+        }
+    }
+    Ok(offsets
+        .iter()
+        .zip(&owners)
+        .filter(|(offset, owner)| owner.map_or(true, |o| lowest_reader[&o] != **offset))
+        .map(|(offset, _)| *offset)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Device reporting `reported` bytes that stores only `real`, wrapping
+    /// addresses around like most counterfeit cards
+    struct FakeCard {
+        data: Vec<u8>,
+        reported: u64,
+        position: u64,
+    }
+
+    impl Read for FakeCard {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            for b in buf.iter_mut() {
+                *b = self.data[(self.position % self.data.len() as u64) as usize];
+                self.position += 1;
+            }
+            Ok(buf.len())
+        }
+    }
+
+    impl Write for FakeCard {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let len = self.data.len() as u64;
+            for &b in buf {
+                self.data[(self.position % len) as usize] = b;
+                self.position += 1;
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for FakeCard {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            if let SeekFrom::Start(offset) = pos {
+                self.position = offset.min(self.reported);
+            }
+            Ok(self.position)
+        }
+    }
+
+    #[test]
+    fn test_sample_offsets() {
+        let offsets = sample_offsets(1 << 30, 4, 65536);
+        assert_eq!(offsets, [0, 1 << 28, 2 << 28, 3 << 28]);
+        // 30 GB: spacing rounds down to 4 GiB, giving up to twice the samples
+        let offsets = sample_offsets(30_000_000_000, 4, 65536);
+        assert_eq!(offsets.len(), 7);
+        assert!(offsets.windows(2).all(|w| w[1] - w[0] == 1 << 32));
+    }
+
+    #[test]
+    fn test_genuine_device_is_restored() {
+        let size = 64 * 1024 * 1024;
+        let original: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        let mut device = Cursor::new(original.clone());
+
+        let report =
+            check_capacity(&mut device, size as u64, &FlashState::new(), |_| Ok(())).unwrap();
+        assert!(report.genuine);
+        assert_eq!(report.usable_bytes, size as u64);
+        assert!(device.into_inner() == original);
+    }
+
+    #[test]
+    fn test_fake_device_is_detected() {
+        let real = 16 * 1024 * 1024;
+        let reported = 64 * 1024 * 1024;
+        let mut device = FakeCard {
+            data: vec![0u8; real],
+            reported,
+            position: 0,
+        };
+
+        let report = check_capacity(&mut device, reported, &FlashState::new(), |_| Ok(())).unwrap();
+        assert!(!report.genuine);
+        assert_eq!(report.usable_bytes, real as u64);
+        assert_eq!(report.bad_samples, report.samples * 3 / 4);
+    }
+}
@@ -0,0 +1,323 @@
+//! Inter-process device locks
+//!
+//! The in-app device registry only keeps this process's jobs apart. To keep
+//! other imaging tools and a second instance of the app off a device while it
+//! is flashed, a lock the OS knows about is taken as well:
+//!
+//! - Linux: an exclusive BSD `flock` on the device node, the convention udev
+//!   and partitioning tools follow; udev also holds off probing meanwhile
+//! - macOS: a DiskArbitration claim on the whole disk
+//! - Windows: a named mutex per physical drive; volumes are already locked
+//!   exclusively by the writer while it writes
+//!
+//! Finding the lock held elsewhere fails the job before it starts. Failing to
+//! take it for other reasons (e.g. no read access to the device node before
+//! privileges are granted) only logs, the in-app registry still applies.
+
+use crate::{log_debug, log_info};
+
+const MODULE: &str = "flash::device_lock";
+
+/// OS-level lock on a device, released on drop
+pub struct SystemLock {
+    _inner: Option<platform::Lock>,
+}
+
+/// Take the OS-level lock on a device for the duration of a job
+pub fn lock_device(device_path: &str) -> Result<SystemLock, String> {
+    let inner = platform::lock(device_path)?;
+    if inner.is_some() {
+        log_info!(MODULE, "Locked {} for other programs", device_path);
+    }
+    Ok(SystemLock { _inner: inner })
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::fs::File;
+    use std::os::unix::io::AsRawFd;
+    use std::time::{Duration, Instant};
+
+    use super::{log_debug, MODULE};
+    use crate::config;
+
+    pub type Lock = File;
+
+    pub fn lock(device_path: &str) -> Result<Option<Lock>, String> {
+        let file = match File::open(device_path) {
+            Ok(file) => file,
+            Err(e) => {
+                log_debug!(MODULE, "Not locking {}: {}", device_path, e);
+                return Ok(None);
+            }
+        };
+
+        // udev holds a shared lock for a moment while it probes the device
+        let deadline = Instant::now() + Duration::from_millis(config::flash::DEVICE_LOCK_WAIT_MS);
+        loop {
+            // SAFETY: flock only operates on the descriptor, which `file` owns
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+                return Ok(Some(file));
+            }
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::EWOULDBLOCK) {
+                log_debug!(MODULE, "Not locking {}: {}", device_path, err);
+                return Ok(None);
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "{} is in use by another program (the device is locked)",
+                    device_path
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::ffi::{c_char, c_void, CString};
+    use std::sync::mpsc::{channel, Sender};
+    use std::time::Duration;
+
+    use super::{log_debug, MODULE};
+    use crate::config;
+
+    #[link(name = "DiskArbitration", kind = "framework")]
+    extern "C" {
+        fn DASessionCreate(allocator: *const c_void) -> *mut c_void;
+        fn DASessionSetDispatchQueue(session: *mut c_void, queue: *mut c_void);
+        fn DADiskCreateFromBSDName(
+            allocator: *const c_void,
+            session: *mut c_void,
+            name: *const c_char,
+        ) -> *mut c_void;
+        fn DADiskIsClaimed(disk: *mut c_void) -> u8;
+        fn DADiskClaim(
+            disk: *mut c_void,
+            options: u32,
+            release: *const c_void,
+            release_context: *mut c_void,
+            callback: extern "C" fn(
+                disk: *mut c_void,
+                dissenter: *mut c_void,
+                context: *mut c_void,
+            ),
+            callback_context: *mut c_void,
+        );
+        fn DADiskUnclaim(disk: *mut c_void);
+        fn DADissenterGetStatus(dissenter: *mut c_void) -> i32;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFRelease(cf: *const c_void);
+    }
+
+    extern "C" {
+        fn dispatch_queue_create(label: *const c_char, attr: *const c_void) -> *mut c_void;
+        fn dispatch_release(object: *mut c_void);
+    }
+
+    /// Claim callback result: None on success, else the dissenter status
+    type ClaimResult = Option<i32>;
+
+    /// A DiskArbitration claim and the session it lives in
+    pub struct Lock {
+        session: *mut c_void,
+        queue: *mut c_void,
+        disk: *mut c_void,
+        claimed: bool,
+        /// Callback context, kept until the session stops dispatching
+        _context: Box<Sender<ClaimResult>>,
+    }
+
+    // SAFETY: the DiskArbitration objects are reference counted CF objects
+    // usable from any thread; they are only touched again in drop
+    unsafe impl Send for Lock {}
+
+    impl Drop for Lock {
+        fn drop(&mut self) {
+            // SAFETY: all pointers were created in `lock` and are released once
+            unsafe {
+                if self.claimed {
+                    DADiskUnclaim(self.disk);
+                }
+                DASessionSetDispatchQueue(self.session, std::ptr::null_mut());
+                CFRelease(self.disk);
+                CFRelease(self.session);
+                dispatch_release(self.queue);
+            }
+        }
+    }
+
+    extern "C" fn claim_done(_disk: *mut c_void, dissenter: *mut c_void, context: *mut c_void) {
+        // SAFETY: context is the Sender boxed in the Lock, alive until drop
+        let tx = unsafe { &*(context as *const Sender<ClaimResult>) };
+        // SAFETY: a non-null dissenter is valid for the callback's duration
+        let status = (!dissenter.is_null()).then(|| unsafe { DADissenterGetStatus(dissenter) });
+        let _ = tx.send(status);
+    }
+
+    pub fn lock(device_path: &str) -> Result<Option<Lock>, String> {
+        let name = device_path
+            .trim_start_matches("/dev/")
+            .trim_start_matches('r');
+        let Ok(bsd_name) = CString::new(name) else {
+            return Ok(None);
+        };
+
+        // SAFETY: null allocators select the default; results are checked
+        let (session, disk) = unsafe {
+            let session = DASessionCreate(std::ptr::null());
+            if session.is_null() {
+                log_debug!(MODULE, "Not locking {}: no DiskArbitration session", name);
+                return Ok(None);
+            }
+            let disk = DADiskCreateFromBSDName(std::ptr::null(), session, bsd_name.as_ptr());
+            if disk.is_null() {
+                CFRelease(session);
+                log_debug!(MODULE, "Not locking {}: unknown disk", name);
+                return Ok(None);
+            }
+            (session, disk)
+        };
+
+        // SAFETY: disk is valid
+        if unsafe { DADiskIsClaimed(disk) } != 0 {
+            // SAFETY: both were created above and are not used afterwards
+            unsafe {
+                CFRelease(disk);
+                CFRelease(session);
+            }
+            return Err(format!(
+                "{} is in use by another program (the disk is claimed)",
+                device_path
+            ));
+        }
+
+        let (tx, rx) = channel();
+        // SAFETY: the label is a static C string, attr null means serial
+        let queue = unsafe {
+            dispatch_queue_create(
+                b"armbian-imager.claim\0".as_ptr() as *const c_char,
+                std::ptr::null(),
+            )
+        };
+        let mut lock = Lock {
+            session,
+            queue,
+            disk,
+            claimed: false,
+            _context: Box::new(tx),
+        };
+        let context = &*lock._context as *const Sender<ClaimResult> as *mut c_void;
+        // SAFETY: session and queue are valid; a null release callback makes
+        // the claim non-releasable; context lives as long as the session
+        unsafe {
+            DASessionSetDispatchQueue(session, queue);
+            DADiskClaim(
+                disk,
+                0,
+                std::ptr::null(),
+                std::ptr::null_mut(),
+                claim_done,
+                context,
+            );
+        }
+
+        match rx.recv_timeout(Duration::from_millis(config::flash::DEVICE_LOCK_WAIT_MS)) {
+            Ok(None) => {
+                lock.claimed = true;
+                Ok(Some(lock))
+            }
+            Ok(Some(status)) => Err(format!(
+                "{} is in use by another program (claim refused: 0x{:x})",
+                device_path, status
+            )),
+            Err(_) => {
+                log_debug!(MODULE, "Not locking {}: no answer to the claim", name);
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::{log_debug, MODULE};
+
+    /// Handle of the named mutex
+    pub struct Lock(windows_sys::Win32::Foundation::HANDLE);
+
+    // SAFETY: a kernel handle may be closed from any thread
+    unsafe impl Send for Lock {}
+
+    impl Drop for Lock {
+        fn drop(&mut self) {
+            // SAFETY: the handle was opened in `lock` and is closed once
+            unsafe { windows_sys::Win32::Foundation::CloseHandle(self.0) };
+        }
+    }
+
+    pub fn lock(device_path: &str) -> Result<Option<Lock>, String> {
+        use windows_sys::Win32::Foundation::{CloseHandle, GetLastError, ERROR_ALREADY_EXISTS};
+        use windows_sys::Win32::System::Threading::CreateMutexW;
+
+        // The mutex exists as long as some process has it open
+        let drive: String = device_path
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_uppercase();
+        let name: Vec<u16> = format!("Global\\armbian-imager-{}", drive)
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .collect();
+
+        // SAFETY: name is NUL-terminated; the handle is checked
+        unsafe {
+            let handle = CreateMutexW(std::ptr::null(), 0, name.as_ptr());
+            if handle.is_null() {
+                log_debug!(
+                    MODULE,
+                    "Not locking {}: error {}",
+                    device_path,
+                    GetLastError()
+                );
+                return Ok(None);
+            }
+            if GetLastError() == ERROR_ALREADY_EXISTS {
+                CloseHandle(handle);
+                return Err(format!(
+                    "{} is in use by another instance of the app",
+                    device_path
+                ));
+            }
+            Ok(Some(Lock(handle)))
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let path = std::env::temp_dir().join(format!("device-lock-{}", std::process::id()));
+        std::fs::write(&path, b"card").unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        let lock = lock_device(&path).unwrap();
+        let err = lock_device(&path).err().unwrap();
+        assert!(err.contains("in use by another program"), "{}", err);
+        drop(lock);
+        assert!(lock_device(&path).is_ok());
+
+        // A node that can't be opened is not locked, but not refused either
+        assert!(lock_device("/nonexistent/device").is_ok());
+        std::fs::remove_file(path).unwrap();
+    }
+}
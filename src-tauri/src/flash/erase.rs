@@ -0,0 +1,550 @@
+//! Device erase
+//!
+//! Returns a card to the state it was sold in, like the "Erase" choice of
+//! Raspberry Pi Imager: an MBR with one partition spanning the device,
+//! formatted FAT32 or exFAT. The filesystem metadata is built here and
+//! written through the same authorized device handle as an image, so no
+//! platform formatting tool or extra privilege is involved.
+//!
+//! The partition starts at 1 MiB, and every region written is 4 KiB-aligned
+//! as raw device handles require. The last MiB of the device is zeroed as
+//! well, dropping the backup GPT of an earlier image. The partition table is
+//! written last, so a cancelled erase leaves no half-formatted partition.
+
+use std::io::{Seek, SeekFrom, Write};
+use std::sync::atomic::Ordering;
+
+use crate::config;
+use crate::log_info;
+use crate::utils::MB;
+
+use super::FlashState;
+
+const MODULE: &str = "flash::erase";
+
+const SECTOR_SIZE: u64 = 512;
+
+/// Alignment of device I/O, so raw/unbuffered device handles work
+const ALIGNMENT: u64 = 4096;
+
+/// First sector of the partition (1 MiB)
+const PARTITION_START: u64 = MB / SECTOR_SIZE;
+
+/// FAT32 needs at least this many clusters, or it reads as FAT16
+const FAT32_MIN_CLUSTERS: u64 = 65525;
+
+/// Reserved sectors before the first FAT32 FAT
+const FAT32_RESERVED_SECTORS: u64 = 32;
+
+/// Offset of the exFAT FAT in the partition, in sectors (1 MiB)
+const EXFAT_FAT_OFFSET: u64 = 2048;
+
+/// Filesystem of the erased device's partition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EraseFilesystem {
+    Fat32,
+    Exfat,
+}
+
+impl EraseFilesystem {
+    /// Parse "fat32" or "exfat"; None picks by size, exFAT above 32 GiB as
+    /// on SDXC cards
+    pub fn choose(name: Option<&str>, device_size: u64) -> Result<Self, String> {
+        match name.map(str::to_ascii_lowercase).as_deref() {
+            Some("fat32") => Ok(Self::Fat32),
+            Some("exfat") => Ok(Self::Exfat),
+            Some(other) => Err(format!("Unknown filesystem: {}", other)),
+            None if device_size > config::flash::ERASE_FAT32_MAX_SIZE => Ok(Self::Exfat),
+            None => Ok(Self::Fat32),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fat32 => "FAT32",
+            Self::Exfat => "exFAT",
+        }
+    }
+
+    /// MBR partition type
+    fn partition_type(self) -> u8 {
+        match self {
+            Self::Fat32 => 0x0c,
+            Self::Exfat => 0x07,
+        }
+    }
+}
+
+/// Bytes to write at an offset; the region is zero-filled past `data`
+#[derive(Debug)]
+struct Region {
+    offset: u64,
+    data: Vec<u8>,
+    len: u64,
+}
+
+impl Region {
+    fn new(offset: u64, data: Vec<u8>, len: u64) -> Self {
+        let len = len.max(data.len() as u64).div_ceil(ALIGNMENT) * ALIGNMENT;
+        Self { offset, data, len }
+    }
+}
+
+/// Everything written to erase a device, in write order
+#[derive(Debug)]
+pub struct EraseLayout {
+    pub filesystem: EraseFilesystem,
+    regions: Vec<Region>,
+}
+
+impl EraseLayout {
+    /// Lay out a device of `device_size` bytes with one `filesystem` partition
+    pub fn new(device_size: u64, filesystem: EraseFilesystem, serial: u32) -> Result<Self, String> {
+        let device_sectors = device_size / ALIGNMENT * ALIGNMENT / SECTOR_SIZE;
+        if device_sectors < PARTITION_START * 4 {
+            return Err(format!(
+                "The device is too small to format ({} bytes)",
+                device_size
+            ));
+        }
+        // MBR sector counts are 32-bit, so at most 2 TiB are used
+        let sectors = (device_sectors - PARTITION_START).min(u32::MAX as u64 / 8 * 8);
+        let label = config::flash::ERASE_VOLUME_LABEL;
+
+        let mut regions = vec![Region::new(
+            (device_sectors * SECTOR_SIZE).saturating_sub(MB),
+            Vec::new(),
+            MB,
+        )];
+        regions.extend(match filesystem {
+            EraseFilesystem::Fat32 => fat32_regions(sectors, serial, label)?,
+            EraseFilesystem::Exfat => exfat_regions(sectors, serial, label)?,
+        });
+        regions.push(Region::new(0, mbr(sectors, filesystem, serial), MB));
+
+        Ok(Self {
+            filesystem,
+            regions,
+        })
+    }
+
+    /// Bytes written in total
+    pub fn total_bytes(&self) -> u64 {
+        self.regions.iter().map(|r| r.len).sum()
+    }
+
+    /// Write the layout, reporting progress and stopping when cancelled
+    pub fn write<D: Write + Seek>(&self, device: &mut D, state: &FlashState) -> Result<(), String> {
+        log_info!(
+            MODULE,
+            "Erasing: {} partition, {} bytes to write",
+            self.filesystem.name(),
+            self.total_bytes()
+        );
+        state
+            .total_bytes
+            .store(self.total_bytes(), Ordering::SeqCst);
+
+        let zeros = vec![0u8; config::flash::ERASE_CHUNK_SIZE];
+        for region in &self.regions {
+            device
+                .seek(SeekFrom::Start(region.offset))
+                .map_err(|e| format!("Failed to seek to byte {}: {}", region.offset, e))?;
+
+            let mut written = 0u64;
+            while written < region.len {
+                if state.is_cancelled.load(Ordering::SeqCst) {
+                    return Err("Erase cancelled".to_string());
+                }
+                let chunk = (region.len - written).min(zeros.len() as u64) as usize;
+                let mut buffer = zeros[..chunk].to_vec();
+                if let Some(data) = region.data.get(written as usize..) {
+                    let n = data.len().min(chunk);
+                    buffer[..n].copy_from_slice(&data[..n]);
+                }
+                device.write_all(&buffer).map_err(|e| {
+                    format!("Failed to write at byte {}: {}", region.offset + written, e)
+                })?;
+                written += chunk as u64;
+                state
+                    .written_bytes
+                    .fetch_add(chunk as u64, Ordering::SeqCst);
+            }
+        }
+        device
+            .flush()
+            .map_err(|e| format!("Failed to flush the device: {}", e))
+    }
+}
+
+/// Volume serial number, derived from the time like the platform tools do
+pub fn volume_serial() -> u32 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    (now.as_secs() as u32).rotate_left(16) ^ now.subsec_nanos()
+}
+
+fn put_u16(buf: &mut [u8], at: usize, value: u16) {
+    buf[at..at + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut [u8], at: usize, value: u32) {
+    buf[at..at + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut [u8], at: usize, value: u64) {
+    buf[at..at + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Label as an 11-byte FAT field: upper case, space-padded
+fn fat_label(label: &str) -> [u8; 11] {
+    let mut field = [b' '; 11];
+    for (dst, src) in field
+        .iter_mut()
+        .zip(label.bytes().filter(u8::is_ascii_graphic))
+    {
+        *dst = src.to_ascii_uppercase();
+    }
+    field
+}
+
+/// Master boot record with one partition of `sectors` at `PARTITION_START`
+fn mbr(sectors: u64, filesystem: EraseFilesystem, serial: u32) -> Vec<u8> {
+    let mut mbr = vec![0u8; SECTOR_SIZE as usize];
+    put_u32(&mut mbr, 440, serial);
+    let entry = &mut mbr[446..462];
+    // CHS fields are unused past 8 GiB; LBA is what counts
+    entry[1..4].copy_from_slice(&[0xfe, 0xff, 0xff]);
+    entry[4] = filesystem.partition_type();
+    entry[5..8].copy_from_slice(&[0xfe, 0xff, 0xff]);
+    put_u32(entry, 8, PARTITION_START as u32);
+    put_u32(entry, 12, sectors as u32);
+    mbr[510] = 0x55;
+    mbr[511] = 0xaa;
+    mbr
+}
+
+/// Sectors per FAT32 cluster, as Windows picks them
+fn fat32_sectors_per_cluster(sectors: u64) -> u64 {
+    let size = sectors * SECTOR_SIZE;
+    match size {
+        s if s <= 260 * MB => 1,
+        s if s <= 8 * 1024 * MB => 8,
+        s if s <= 16 * 1024 * MB => 16,
+        s if s <= 32 * 1024 * MB => 32,
+        _ => 64,
+    }
+}
+
+/// Boot sectors, both FATs and the root directory of a FAT32 partition
+fn fat32_regions(sectors: u64, serial: u32, label: &str) -> Result<Vec<Region>, String> {
+    let per_cluster = fat32_sectors_per_cluster(sectors);
+    let reserved = FAT32_RESERVED_SECTORS;
+    // Microsoft's FAT size formula, rounded up to keep the data area aligned
+    let divisor = (256 * per_cluster + 2) / 2;
+    let fat_sectors = (sectors - reserved).div_ceil(divisor).div_ceil(8) * 8;
+    let data_start = reserved + 2 * fat_sectors;
+    let clusters = sectors.saturating_sub(data_start) / per_cluster;
+    if clusters < FAT32_MIN_CLUSTERS {
+        return Err(format!(
+            "The device is too small for FAT32 ({} clusters)",
+            clusters
+        ));
+    }
+
+    let mut boot = vec![0u8; (reserved * SECTOR_SIZE) as usize];
+    {
+        let bs = &mut boot[..SECTOR_SIZE as usize];
+        bs[0..3].copy_from_slice(&[0xeb, 0x58, 0x90]);
+        bs[3..11].copy_from_slice(b"ARMBIAN ");
+        put_u16(bs, 11, SECTOR_SIZE as u16);
+        bs[13] = per_cluster as u8;
+        put_u16(bs, 14, reserved as u16);
+        bs[16] = 2;
+        bs[21] = 0xf8;
+        put_u16(bs, 24, 63);
+        put_u16(bs, 26, 255);
+        put_u32(bs, 28, PARTITION_START as u32);
+        put_u32(bs, 32, sectors as u32);
+        put_u32(bs, 36, fat_sectors as u32);
+        put_u32(bs, 44, 2);
+        put_u16(bs, 48, 1);
+        put_u16(bs, 50, 6);
+        bs[64] = 0x80;
+        bs[66] = 0x29;
+        put_u32(bs, 67, serial);
+        bs[71..82].copy_from_slice(&fat_label(label));
+        bs[82..90].copy_from_slice(b"FAT32   ");
+        bs[510] = 0x55;
+        bs[511] = 0xaa;
+    }
+    {
+        let fs_info = &mut boot[SECTOR_SIZE as usize..2 * SECTOR_SIZE as usize];
+        put_u32(fs_info, 0, 0x4161_5252);
+        put_u32(fs_info, 484, 0x6141_7272);
+        // The root directory takes cluster 2
+        put_u32(fs_info, 488, (clusters - 1) as u32);
+        put_u32(fs_info, 492, 3);
+        put_u32(fs_info, 508, 0xaa55_0000);
+    }
+    // Backup boot sector and FSInfo at sector 6
+    boot.copy_within(0..2 * SECTOR_SIZE as usize, 6 * SECTOR_SIZE as usize);
+
+    let mut fat = vec![0u8; 12];
+    put_u32(&mut fat, 0, 0x0fff_fff8);
+    put_u32(&mut fat, 4, 0x0fff_ffff);
+    put_u32(&mut fat, 8, 0x0fff_ffff);
+
+    let mut root = vec![0u8; 32];
+    root[0..11].copy_from_slice(&fat_label(label));
+    root[11] = 0x08;
+
+    let start = PARTITION_START * SECTOR_SIZE;
+    let fat_len = fat_sectors * SECTOR_SIZE;
+    let fat_start = start + reserved * SECTOR_SIZE;
+    log_info!(
+        MODULE,
+        "FAT32: {} clusters of {} bytes",
+        clusters,
+        per_cluster * SECTOR_SIZE
+    );
+    Ok(vec![
+        Region::new(start, boot, 0),
+        Region::new(fat_start, fat.clone(), fat_len),
+        Region::new(fat_start + fat_len, fat, fat_len),
+        Region::new(
+            start + data_start * SECTOR_SIZE,
+            root,
+            per_cluster * SECTOR_SIZE,
+        ),
+    ])
+}
+
+/// Sectors per exFAT cluster, as Windows picks them
+fn exfat_sectors_per_cluster(sectors: u64) -> u64 {
+    let size = sectors * SECTOR_SIZE;
+    match size {
+        s if s <= 256 * MB => 8,
+        s if s <= 32 * 1024 * MB => 64,
+        _ => 256,
+    }
+}
+
+/// exFAT boot region checksum; skips VolumeFlags and PercentInUse
+fn exfat_boot_checksum(sectors: &[u8]) -> u32 {
+    sectors
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !matches!(i, 106 | 107 | 112))
+        .fold(0u32, |sum, (_, &b)| {
+            sum.rotate_right(1).wrapping_add(b as u32)
+        })
+}
+
+/// Up-case table mapping ASCII and Latin-1 letters, in compressed form
+///
+/// A run of identity mappings is stored as 0xFFFF and its length. Other
+/// characters compare case-sensitively, which the format allows.
+fn exfat_upcase_table() -> Vec<u8> {
+    let mut table: Vec<u16> = (0..0x61).collect();
+    table.extend((0x61..=0x7a).map(|c| c - 0x20));
+    table.extend([0xffff, 0xe0 - 0x7b]);
+    table.extend((0xe0..=0xfe).map(|c| if c == 0xf7 { c } else { c - 0x20 }));
+    table.push(0x178);
+    table.extend([0xffff, 0xff00]);
+    table.iter().flat_map(|c| c.to_le_bytes()).collect()
+}
+
+/// Boot regions, FAT, allocation bitmap, up-case table and root directory of
+/// an exFAT partition
+fn exfat_regions(sectors: u64, serial: u32, label: &str) -> Result<Vec<Region>, String> {
+    let per_cluster = exfat_sectors_per_cluster(sectors);
+    let cluster_size = per_cluster * SECTOR_SIZE;
+    let estimate = sectors.saturating_sub(EXFAT_FAT_OFFSET) / per_cluster;
+    let fat_sectors = ((estimate + 2) * 4)
+        .div_ceil(SECTOR_SIZE)
+        .div_ceil(per_cluster)
+        * per_cluster;
+    let heap_offset = EXFAT_FAT_OFFSET + fat_sectors;
+    let clusters = sectors.saturating_sub(heap_offset) / per_cluster;
+
+    let upcase = exfat_upcase_table();
+    let bitmap_len = clusters.div_ceil(8);
+    let bitmap_clusters = bitmap_len.div_ceil(cluster_size);
+    let upcase_clusters = (upcase.len() as u64).div_ceil(cluster_size);
+    let root_cluster = 2 + bitmap_clusters + upcase_clusters;
+    let used = bitmap_clusters + upcase_clusters + 1;
+    if clusters < used * 2 {
+        return Err(format!(
+            "The device is too small for exFAT ({} clusters)",
+            clusters
+        ));
+    }
+
+    // Main boot region (12 sectors), then its backup
+    let mut boot = vec![0u8; 24 * SECTOR_SIZE as usize];
+    {
+        let bs = &mut boot[..SECTOR_SIZE as usize];
+        bs[0..3].copy_from_slice(&[0xeb, 0x76, 0x90]);
+        bs[3..11].copy_from_slice(b"EXFAT   ");
+        put_u64(bs, 64, PARTITION_START);
+        put_u64(bs, 72, sectors);
+        put_u32(bs, 80, EXFAT_FAT_OFFSET as u32);
+        put_u32(bs, 84, fat_sectors as u32);
+        put_u32(bs, 88, heap_offset as u32);
+        put_u32(bs, 92, clusters as u32);
+        put_u32(bs, 96, root_cluster as u32);
+        put_u32(bs, 100, serial);
+        put_u16(bs, 104, 0x0100);
+        bs[108] = SECTOR_SIZE.trailing_zeros() as u8;
+        bs[109] = per_cluster.trailing_zeros() as u8;
+        bs[110] = 1;
+        bs[111] = 0x80;
+        bs[510] = 0x55;
+        bs[511] = 0xaa;
+    }
+    for sector in 1..=8 {
+        let end = (sector + 1) * SECTOR_SIZE as usize;
+        put_u32(&mut boot, end - 4, 0xaa55_0000);
+    }
+    let checksum = exfat_boot_checksum(&boot[..11 * SECTOR_SIZE as usize]);
+    for at in (11 * SECTOR_SIZE as usize..12 * SECTOR_SIZE as usize).step_by(4) {
+        put_u32(&mut boot, at, checksum);
+    }
+    boot.copy_within(0..12 * SECTOR_SIZE as usize, 12 * SECTOR_SIZE as usize);
+
+    // Media descriptor, then one chain per object; each chain is contiguous
+    let mut fat = vec![0u8; ((2 + used) * 4) as usize];
+    put_u32(&mut fat, 0, 0xffff_fff8);
+    put_u32(&mut fat, 4, 0xffff_ffff);
+    let mut cluster = 2u64;
+    for length in [bitmap_clusters, upcase_clusters, 1] {
+        for i in 0..length {
+            let next = if i + 1 == length {
+                0xffff_ffff
+            } else {
+                (cluster + 1) as u32
+            };
+            put_u32(&mut fat, (cluster * 4) as usize, next);
+            cluster += 1;
+        }
+    }
+
+    let mut bitmap = vec![0u8; used.div_ceil(8) as usize];
+    for i in 0..used as usize {
+        bitmap[i / 8] |= 1 << (i % 8);
+    }
+
+    let mut root = vec![0u8; 3 * 32];
+    let label: Vec<u16> = label.encode_utf16().take(11).collect();
+    root[0] = 0x83;
+    root[1] = label.len() as u8;
+    for (i, c) in label.iter().enumerate() {
+        put_u16(&mut root, 2 + i * 2, *c);
+    }
+    root[32] = 0x81;
+    put_u32(&mut root, 32 + 20, 2);
+    put_u64(&mut root, 32 + 24, bitmap_len);
+    root[64] = 0x82;
+    let upcase_checksum = upcase
+        .iter()
+        .fold(0u32, |sum, &b| sum.rotate_right(1).wrapping_add(b as u32));
+    put_u32(&mut root, 64 + 4, upcase_checksum);
+    put_u32(&mut root, 64 + 20, (2 + bitmap_clusters) as u32);
+    put_u64(&mut root, 64 + 24, upcase.len() as u64);
+
+    let start = PARTITION_START * SECTOR_SIZE;
+    let heap = start + heap_offset * SECTOR_SIZE;
+    log_info!(
+        MODULE,
+        "exFAT: {} clusters of {} bytes",
+        clusters,
+        cluster_size
+    );
+    Ok(vec![
+        Region::new(start, boot, 0),
+        Region::new(
+            start + EXFAT_FAT_OFFSET * SECTOR_SIZE,
+            fat,
+            fat_sectors * SECTOR_SIZE,
+        ),
+        Region::new(heap, bitmap, bitmap_clusters * cluster_size),
+        Region::new(
+            heap + bitmap_clusters * cluster_size,
+            upcase,
+            upcase_clusters * cluster_size,
+        ),
+        Region::new(heap + (root_cluster - 2) * cluster_size, root, cluster_size),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flash::inspect_image;
+    use std::io::Cursor;
+
+    fn erase(size: u64, filesystem: EraseFilesystem) -> Vec<u8> {
+        let layout = EraseLayout::new(size, filesystem, 0x1234_5678).unwrap();
+        let mut device = Cursor::new(vec![0xaau8; size as usize]);
+        layout.write(&mut device, &FlashState::new()).unwrap();
+        device.into_inner()
+    }
+
+    #[test]
+    fn test_choose_filesystem() {
+        let gib = 1024 * MB;
+        assert_eq!(
+            EraseFilesystem::choose(None, 16 * gib),
+            Ok(EraseFilesystem::Fat32)
+        );
+        assert_eq!(
+            EraseFilesystem::choose(None, 64 * gib),
+            Ok(EraseFilesystem::Exfat)
+        );
+        assert_eq!(
+            EraseFilesystem::choose(Some("FAT32"), 64 * gib),
+            Ok(EraseFilesystem::Fat32)
+        );
+        assert!(EraseFilesystem::choose(Some("ntfs"), gib).is_err());
+        assert!(EraseLayout::new(MB, EraseFilesystem::Fat32, 0).is_err());
+    }
+
+    #[test]
+    fn test_erase_layouts() {
+        let dir = std::env::temp_dir().join(format!("erase-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let size = 64 * MB;
+
+        for (filesystem, name) in [
+            (EraseFilesystem::Fat32, "FAT32"),
+            (EraseFilesystem::Exfat, "exFAT"),
+        ] {
+            let device = erase(size, filesystem);
+            // The old contents are gone from the end of the device
+            assert!(device[(size - MB) as usize..].iter().all(|&b| b == 0));
+
+            let path = dir.join(format!("{}.img", name));
+            std::fs::write(&path, &device).unwrap();
+            let details = inspect_image(&path).unwrap();
+            assert_eq!(details.partition_table.as_deref(), Some("MBR"));
+            assert_eq!(details.partitions.len(), 1);
+            let partition = &details.partitions[0];
+            assert_eq!(partition.partition.start, MB);
+            assert_eq!(partition.partition.end, size);
+            assert_eq!(partition.filesystem.as_deref(), Some(name));
+
+            if filesystem == EraseFilesystem::Exfat {
+                let boot = &device[MB as usize..];
+                let checksum = exfat_boot_checksum(&boot[..11 * 512]);
+                assert_eq!(&boot[11 * 512..11 * 512 + 4], &checksum.to_le_bytes());
+                // The backup boot region matches the main one
+                assert_eq!(boot[..12 * 512], boot[12 * 512..24 * 512]);
+            } else {
+                assert_eq!(partition.label.as_deref(), Some("ARMBIAN"));
+            }
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
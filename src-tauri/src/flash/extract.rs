@@ -0,0 +1,173 @@
+//! Partition extraction
+//!
+//! Pulls a single partition out of an image, e.g. the rootfs to inspect or
+//! the boot partition to repair, without losetup or 7-Zip. The partition is
+//! located with the image's partition table and streamed out of the image,
+//! decompressing on the fly, into a raw file. As with backups the file is
+//! written in a working directory and only moved into place once complete.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+use crate::config;
+use crate::utils::{bytes_to_gb, ProgressTracker};
+use crate::workdir::WorkDir;
+use crate::{log_debug, log_info};
+
+use super::image_layout::image_partitions;
+use super::image_source::ImageReader;
+use super::{speed_window, FlashState};
+
+const MODULE: &str = "flash::extract";
+
+/// Write partition `number` of `image_path` to `output` as a raw file
+///
+/// Progress is reported through `state` as written bytes, so flash
+/// progress events work unchanged. Returns the size of the partition.
+pub fn extract_partition(
+    image_path: &Path,
+    number: usize,
+    output: &Path,
+    state: &FlashState,
+) -> Result<u64, String> {
+    let partitions = image_partitions(image_path)?;
+    let partition = partitions
+        .iter()
+        .find(|p| p.number == number)
+        .ok_or_else(|| {
+            format!(
+                "{} has no partition {} ({} partitions found)",
+                image_path.display(),
+                number,
+                partitions.len()
+            )
+        })?;
+    let file_name = output
+        .file_name()
+        .ok_or_else(|| format!("Invalid output path: {}", output.display()))?;
+    let dest_dir = output
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let size = partition.size();
+    state.total_bytes.store(size, Ordering::SeqCst);
+    log_info!(
+        MODULE,
+        "Extracting partition {} ({}, {:.2} GB at byte {}) of {} to {}",
+        number,
+        partition.kind,
+        bytes_to_gb(size),
+        partition.start,
+        image_path.display(),
+        output.display()
+    );
+
+    let mut image = ImageReader::open(image_path)?;
+    let skipped = io::copy(&mut (&mut image).take(partition.start), &mut io::sink())
+        .map_err(|e| format!("Failed to read {}: {}", image_path.display(), e))?;
+    if skipped < partition.start {
+        return Err(format!(
+            "{} ends before partition {} starts",
+            image_path.display(),
+            number
+        ));
+    }
+
+    let work = WorkDir::create(dest_dir, "extract")?;
+    let file = File::create(work.join(file_name))
+        .map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+    let mut writer = BufWriter::with_capacity(config::cache::COPY_BUFFER_SIZE, file);
+
+    let chunk_size = config::flash::CHUNK_SIZE;
+    let mut buffer = vec![0u8; chunk_size];
+    let mut copied: u64 = 0;
+    let mut tracker = ProgressTracker::new(
+        "Extract",
+        MODULE,
+        size,
+        config::logging::WRITE_LOG_INTERVAL_MB,
+    );
+    let mut speed = speed_window();
+
+    while copied < size {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Err("Extraction cancelled".to_string());
+        }
+
+        let len = std::cmp::min(chunk_size as u64, size - copied) as usize;
+        image.read_exact(&mut buffer[..len]).map_err(|e| {
+            format!(
+                "Failed to read partition {} at byte {}: {}",
+                number, copied, e
+            )
+        })?;
+        writer
+            .write_all(&buffer[..len])
+            .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+
+        copied += len as u64;
+        state.written_bytes.store(copied, Ordering::SeqCst);
+        state
+            .write_elapsed_ms
+            .store(tracker.elapsed().as_millis() as u64, Ordering::SeqCst);
+        state.record_throughput(&mut speed);
+        tracker.update(len as u64);
+    }
+    tracker.finish();
+
+    let file = writer
+        .into_inner()
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e.into_error()))?;
+    file.sync_all()
+        .map_err(|e| format!("Failed to sync {}: {}", output.display(), e))?;
+    drop(file);
+
+    work.persist(file_name, output)?;
+    log_debug!(MODULE, "Partition {} extracted: {} bytes", number, size);
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_extract_mbr_partition() {
+        let dir = std::env::temp_dir().join(format!("extract-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        // Partition 1 at sector 16, 8 sectors long, followed by other data
+        let mut image = vec![0u8; 64 * 512];
+        image[510] = 0x55;
+        image[511] = 0xaa;
+        let entry = &mut image[446..462];
+        entry[4] = 0x83;
+        entry[8..12].copy_from_slice(&16u32.to_le_bytes());
+        entry[12..16].copy_from_slice(&8u32.to_le_bytes());
+        for (i, b) in image[16 * 512..24 * 512].iter_mut().enumerate() {
+            *b = (i % 13) as u8 + 1;
+        }
+        image[24 * 512..].fill(0xee);
+        let image_path = dir.join("card.img");
+        fs::write(&image_path, &image).unwrap();
+
+        let output = dir.join("rootfs.img");
+        let state = FlashState::new();
+        assert_eq!(
+            extract_partition(&image_path, 1, &output, &state).unwrap(),
+            8 * 512
+        );
+        assert_eq!(fs::read(&output).unwrap(), &image[16 * 512..24 * 512]);
+
+        let err = extract_partition(&image_path, 2, &dir.join("none.img"), &state).unwrap_err();
+        assert!(err.contains("no partition 2"), "{}", err);
+        assert!(!dir.join("none.img").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
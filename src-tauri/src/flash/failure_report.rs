@@ -0,0 +1,33 @@
+//! Flash failure reports
+//!
+//! After a failed verification, writes a small text report to the first
+//! partition of the target device (if it can be mounted) so the card itself
+//! carries the failure details across attempts and readers.
+
+use std::path::PathBuf;
+
+use super::boot_partition::{boot_dir, with_first_partition};
+use crate::config;
+use crate::log_info;
+
+const MODULE: &str = "flash::failure_report";
+
+/// Mount the first partition of the device, write the report and unmount again
+///
+/// The report goes into `/boot` if the partition has one, otherwise to the
+/// partition root. Returns the path the report was written to.
+pub fn write_boot_partition_report(device_path: &str, contents: &str) -> Result<PathBuf, String> {
+    let report_path = with_first_partition(device_path, |mount_point| {
+        let report_path = boot_dir(mount_point).join(config::history::FAILURE_REPORT_FILE);
+        std::fs::write(&report_path, contents)
+            .map(|_| report_path.clone())
+            .map_err(|e| format!("Failed to write {}: {}", report_path.display(), e))
+    })?;
+
+    log_info!(
+        MODULE,
+        "Failure report written to {}",
+        report_path.display()
+    );
+    Ok(report_path)
+}
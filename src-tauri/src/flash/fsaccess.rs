@@ -0,0 +1,440 @@
+//! Read-only access to files on the ext2/3/4 partitions of an image
+//!
+//! Finds a file by its path without mounting anything: the superblock,
+//! group descriptors, inodes and directory blocks are read straight from
+//! the image. Only what small text files such as `/etc/armbian-release`
+//! need is supported: extent trees and the direct blocks of older inodes,
+//! but no inline data or indirect blocks. A compressed image can only be
+//! read forward, so a read before the current position decompresses it
+//! again from the start.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use super::image_layout::Partition;
+use super::image_source::{is_streamed, ImageReader};
+
+/// Inode of the root directory
+const ROOT_INODE: u32 = 2;
+/// Largest directory read while looking up a path
+const MAX_DIR_SIZE: u64 = 1024 * 1024;
+/// Extent trees are at most 5 levels deep
+const MAX_EXTENT_DEPTH: u32 = 5;
+
+const EXT_MAGIC: u16 = 0xef53;
+const EXTENT_MAGIC: u16 = 0xf30a;
+const INCOMPAT_64BIT: u32 = 0x80;
+const INODE_FLAG_EXTENTS: u32 = 0x80000;
+const INODE_FLAG_INLINE_DATA: u32 = 0x1000_0000;
+const MODE_TYPE_MASK: u16 = 0xf000;
+const MODE_DIR: u16 = 0x4000;
+const MODE_FILE: u16 = 0x8000;
+
+fn corrupt() -> String {
+    "Corrupt ext filesystem".to_string()
+}
+
+fn u16_at(data: &[u8], at: usize) -> Result<u16, String> {
+    data.get(at..at + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(corrupt)
+}
+
+fn u32_at(data: &[u8], at: usize) -> Result<u32, String> {
+    data.get(at..at + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(corrupt)
+}
+
+/// Random reads from a raw or compressed image
+enum Source {
+    Raw(File),
+    Stream {
+        path: PathBuf,
+        reader: ImageReader,
+        position: u64,
+    },
+}
+
+impl Source {
+    fn open(path: &Path) -> Result<Self, String> {
+        if is_streamed(path) {
+            return Ok(Source::Stream {
+                path: path.to_path_buf(),
+                reader: ImageReader::open(path)?,
+                position: 0,
+            });
+        }
+        File::open(path)
+            .map(Source::Raw)
+            .map_err(|e| format!("Failed to open image: {}", e))
+    }
+
+    fn read_at(&mut self, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+        let read_error = |e: io::Error| format!("Failed to read image: {}", e);
+        let mut data = Vec::with_capacity(len);
+        match self {
+            Source::Raw(file) => {
+                file.seek(SeekFrom::Start(offset)).map_err(read_error)?;
+                file.by_ref()
+                    .take(len as u64)
+                    .read_to_end(&mut data)
+                    .map_err(read_error)?;
+            }
+            Source::Stream {
+                path,
+                reader,
+                position,
+            } => {
+                if offset < *position {
+                    *reader = ImageReader::open(path)?;
+                    *position = 0;
+                }
+                let skip = offset - *position;
+                *position += io::copy(&mut reader.by_ref().take(skip), &mut io::sink())
+                    .map_err(read_error)?;
+                reader
+                    .by_ref()
+                    .take(len as u64)
+                    .read_to_end(&mut data)
+                    .map_err(read_error)?;
+                *position += data.len() as u64;
+            }
+        }
+        if data.len() < len {
+            return Err("Image ends inside the filesystem".to_string());
+        }
+        Ok(data)
+    }
+}
+
+/// Files of an image, read from its partitions
+pub struct ImageFiles {
+    source: Source,
+}
+
+impl ImageFiles {
+    pub fn open(image_path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            source: Source::open(image_path)?,
+        })
+    }
+
+    /// Contents of the regular file at `path` on an ext partition
+    ///
+    /// None if the partition holds no ext filesystem or the file does not
+    /// exist; an error if it is larger than `max_len`.
+    pub fn read_file(
+        &mut self,
+        partition: &Partition,
+        path: &str,
+        max_len: u64,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let Some(mut fs) = ExtFs::open(&mut self.source, partition.start)? else {
+            return Ok(None);
+        };
+        let mut inode = ROOT_INODE;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            match fs.lookup(inode, name)? {
+                Some(found) => inode = found,
+                None => return Ok(None),
+            }
+        }
+        let inode = fs.inode(inode)?;
+        if u16_at(&inode, 0)? & MODE_TYPE_MASK != MODE_FILE {
+            return Ok(None);
+        }
+        fs.read_data(&inode, max_len).map(Some)
+    }
+}
+
+/// An ext2/3/4 filesystem starting at `start` in the image
+struct ExtFs<'a> {
+    source: &'a mut Source,
+    start: u64,
+    block_size: u64,
+    first_data_block: u64,
+    inodes_per_group: u32,
+    inode_size: usize,
+    desc_size: u64,
+}
+
+impl<'a> ExtFs<'a> {
+    fn open(source: &'a mut Source, start: u64) -> Result<Option<Self>, String> {
+        let sb = source.read_at(start + 1024, 1024)?;
+        if u16_at(&sb, 0x38)? != EXT_MAGIC {
+            return Ok(None);
+        }
+        let log_block_size = u32_at(&sb, 0x18)?;
+        let inodes_per_group = u32_at(&sb, 0x28)?;
+        let inode_size = match u32_at(&sb, 0x4c)? {
+            0 => 128,
+            _ => u16_at(&sb, 0x58)? as usize,
+        };
+        if log_block_size > 6 || inodes_per_group == 0 || inode_size < 128 {
+            return Err(corrupt());
+        }
+        let desc_size = if u32_at(&sb, 0x60)? & INCOMPAT_64BIT != 0 {
+            u64::from(u16_at(&sb, 0xfe)?).max(32)
+        } else {
+            32
+        };
+        Ok(Some(Self {
+            source,
+            start,
+            block_size: 1024 << log_block_size,
+            first_data_block: u64::from(u32_at(&sb, 0x14)?),
+            inodes_per_group,
+            inode_size,
+            desc_size,
+        }))
+    }
+
+    fn read_blocks(&mut self, block: u64, len: usize) -> Result<Vec<u8>, String> {
+        let offset = block
+            .checked_mul(self.block_size)
+            .and_then(|offset| offset.checked_add(self.start))
+            .ok_or_else(corrupt)?;
+        self.source.read_at(offset, len)
+    }
+
+    fn inode(&mut self, number: u32) -> Result<Vec<u8>, String> {
+        let index = number.checked_sub(1).ok_or_else(corrupt)?;
+        let group = u64::from(index / self.inodes_per_group);
+        let slot = u64::from(index % self.inodes_per_group);
+
+        let table_start = (self.first_data_block + 1) * self.block_size;
+        let desc = self.source.read_at(
+            self.start + table_start + group * self.desc_size,
+            self.desc_size as usize,
+        )?;
+        let mut inode_table = u64::from(u32_at(&desc, 0x08)?);
+        if self.desc_size >= 64 {
+            inode_table |= u64::from(u32_at(&desc, 0x28)?) << 32;
+        }
+        let offset = inode_table
+            .checked_mul(self.block_size)
+            .and_then(|offset| offset.checked_add(self.start + slot * self.inode_size as u64))
+            .ok_or_else(corrupt)?;
+        self.source.read_at(offset, self.inode_size)
+    }
+
+    /// Data blocks of an inode as (first logical block, first physical block, count)
+    fn extents(&mut self, inode: &[u8]) -> Result<Vec<(u64, u64, u64)>, String> {
+        let flags = u32_at(inode, 0x20)?;
+        let i_block = inode.get(0x28..0x28 + 60).ok_or_else(corrupt)?;
+        let mut extents = Vec::new();
+        if flags & INODE_FLAG_EXTENTS != 0 {
+            self.extent_node(i_block, MAX_EXTENT_DEPTH, &mut extents)?;
+        } else if flags & INODE_FLAG_INLINE_DATA != 0 {
+            return Err("Inline file data is not supported".to_string());
+        } else {
+            // Direct blocks only; files reaching indirect blocks are over 12 blocks
+            for index in 0..12 {
+                let block = u32_at(i_block, index * 4)?;
+                if block != 0 {
+                    extents.push((index as u64, u64::from(block), 1));
+                }
+            }
+        }
+        Ok(extents)
+    }
+
+    fn extent_node(
+        &mut self,
+        node: &[u8],
+        levels_left: u32,
+        extents: &mut Vec<(u64, u64, u64)>,
+    ) -> Result<(), String> {
+        if u16_at(node, 0)? != EXTENT_MAGIC || levels_left == 0 {
+            return Err(corrupt());
+        }
+        let entries = u16_at(node, 2)? as usize;
+        let depth = u16_at(node, 6)?;
+        for i in 0..entries {
+            let entry = node.get(12 + i * 12..24 + i * 12).ok_or_else(corrupt)?;
+            if depth == 0 {
+                let len = u16_at(entry, 4)?;
+                // Longer extents are allocated but unwritten, which reads as zeros
+                if len <= 32768 {
+                    let block = u64::from(u16_at(entry, 6)?) << 32 | u64::from(u32_at(entry, 8)?);
+                    extents.push((u64::from(u32_at(entry, 0)?), block, u64::from(len)));
+                }
+            } else {
+                let leaf = u64::from(u32_at(entry, 4)?) | u64::from(u16_at(entry, 8)?) << 32;
+                let child = self.read_blocks(leaf, self.block_size as usize)?;
+                self.extent_node(&child, levels_left - 1, extents)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Contents of an inode, at most `max_len` bytes long
+    fn read_data(&mut self, inode: &[u8], max_len: u64) -> Result<Vec<u8>, String> {
+        let size = u64::from(u32_at(inode, 0x04)?) | u64::from(u32_at(inode, 0x6c)?) << 32;
+        if size > max_len {
+            return Err(format!("File is larger than {} bytes", max_len));
+        }
+        let mut data = vec![0u8; size as usize];
+        for (logical, physical, count) in self.extents(inode)? {
+            let offset = logical.saturating_mul(self.block_size);
+            if offset >= size {
+                continue;
+            }
+            let len = count.saturating_mul(self.block_size).min(size - offset) as usize;
+            let bytes = self.read_blocks(physical, len)?;
+            data[offset as usize..offset as usize + len].copy_from_slice(&bytes);
+        }
+        Ok(data)
+    }
+
+    /// Inode of `name` in the directory with inode `dir`
+    fn lookup(&mut self, dir: u32, name: &str) -> Result<Option<u32>, String> {
+        let inode = self.inode(dir)?;
+        if u16_at(&inode, 0)? & MODE_TYPE_MASK != MODE_DIR {
+            return Ok(None);
+        }
+        // Hashed directories keep linear entries too, the index hides in empty ones
+        let data = self.read_data(&inode, MAX_DIR_SIZE)?;
+        let mut pos = 0;
+        while pos + 8 <= data.len() {
+            let entry_inode = u32_at(&data, pos)?;
+            let rec_len = u16_at(&data, pos + 4)? as usize;
+            let name_len = data[pos + 6] as usize;
+            if rec_len < 8 {
+                break;
+            }
+            if entry_inode != 0 && data.get(pos + 8..pos + 8 + name_len) == Some(name.as_bytes()) {
+                return Ok(Some(entry_inode));
+            }
+            pos += rec_len;
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    const BLOCK: usize = 1024;
+
+    fn put_u16(data: &mut [u8], at: usize, value: u16) {
+        data[at..at + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u32(data: &mut [u8], at: usize, value: u32) {
+        data[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Inode `number` in the table at block 5, its data in one extent
+    fn put_inode(fs: &mut [u8], number: usize, mode: u16, size: usize, block: u32) {
+        let at = 5 * BLOCK + (number - 1) * 128;
+        put_u16(fs, at, mode);
+        put_u32(fs, at + 0x04, size as u32);
+        put_u32(fs, at + 0x20, INODE_FLAG_EXTENTS);
+        let header = at + 0x28;
+        put_u16(fs, header, EXTENT_MAGIC);
+        put_u16(fs, header + 2, 1);
+        put_u16(fs, header + 4, 4);
+        put_u32(fs, header + 12, 0);
+        put_u16(fs, header + 16, 1);
+        put_u32(fs, header + 20, block);
+    }
+
+    fn put_dir(fs: &mut [u8], block: usize, entries: &[(u32, &str)]) {
+        let mut at = block * BLOCK;
+        for (i, (inode, name)) in entries.iter().enumerate() {
+            let rec_len = if i + 1 == entries.len() {
+                block * BLOCK + BLOCK - at
+            } else {
+                (8 + name.len()).next_multiple_of(4)
+            };
+            put_u32(fs, at, *inode);
+            put_u16(fs, at + 4, rec_len as u16);
+            fs[at + 6] = name.len() as u8;
+            fs[at + 8..at + 8 + name.len()].copy_from_slice(name.as_bytes());
+            at += rec_len;
+        }
+    }
+
+    /// A small ext4 filesystem with `/etc/<name>` holding `content`
+    pub(in crate::flash) fn ext4_with_file(name: &str, content: &[u8]) -> Vec<u8> {
+        let mut fs = vec![0u8; 16 * BLOCK];
+        let sb = 1024;
+        put_u32(&mut fs, sb + 0x14, 1);
+        put_u32(&mut fs, sb + 0x18, 0);
+        put_u32(&mut fs, sb + 0x28, 32);
+        put_u16(&mut fs, sb + 0x38, EXT_MAGIC);
+        put_u32(&mut fs, sb + 0x4c, 1);
+        put_u16(&mut fs, sb + 0x58, 128);
+        put_u32(&mut fs, sb + 0x60, 0x40);
+        // Group descriptor table in block 2, inode table from block 5
+        put_u32(&mut fs, 2 * BLOCK + 0x08, 5);
+
+        put_inode(&mut fs, 2, MODE_DIR | 0o755, BLOCK, 10);
+        put_dir(&mut fs, 10, &[(2, "."), (2, ".."), (12, "etc")]);
+        put_inode(&mut fs, 12, MODE_DIR | 0o755, BLOCK, 11);
+        put_dir(
+            &mut fs,
+            11,
+            &[(12, "."), (2, ".."), (0, "gone"), (13, name)],
+        );
+        put_inode(&mut fs, 13, MODE_FILE | 0o644, content.len(), 12);
+        fs[12 * BLOCK..12 * BLOCK + content.len()].copy_from_slice(content);
+        fs
+    }
+
+    #[test]
+    fn test_read_file() {
+        let dir = std::env::temp_dir().join(format!("fsaccess-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut image = vec![0u8; 4 * BLOCK];
+        image.extend(ext4_with_file("armbian-release", b"VERSION=25.2.1\n"));
+        let raw = dir.join("image.img");
+        std::fs::write(&raw, &image).unwrap();
+        let zst = dir.join("image.img.zst");
+        std::fs::write(&zst, zstd::encode_all(&image[..], 3).unwrap()).unwrap();
+        let partition = Partition {
+            number: 1,
+            start: 4 * BLOCK as u64,
+            end: image.len() as u64,
+            kind: "Linux".to_string(),
+        };
+
+        for path in [&raw, &zst] {
+            let mut files = ImageFiles::open(path).unwrap();
+            assert_eq!(
+                files
+                    .read_file(&partition, "/etc/armbian-release", 1024)
+                    .unwrap(),
+                Some(b"VERSION=25.2.1\n".to_vec())
+            );
+            // Read twice, which rewinds a compressed image
+            assert_eq!(
+                files.read_file(&partition, "etc/missing", 1024).unwrap(),
+                None
+            );
+            assert_eq!(files.read_file(&partition, "/etc", 1024).unwrap(), None);
+            assert!(files
+                .read_file(&partition, "/etc/armbian-release", 4)
+                .is_err());
+        }
+
+        // Not an ext filesystem
+        let fat = Partition {
+            start: 0,
+            ..partition
+        };
+        let mut files = ImageFiles::open(&raw).unwrap();
+        assert_eq!(
+            files.read_file(&fat, "/etc/armbian-release", 1024).unwrap(),
+            None
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,65 @@
+//! Image checksum taken while writing
+//!
+//! The writers read every image byte anyway, so hashing them on the way
+//! gives the SHA-256 of exactly what went to the device without a second
+//! pass over a multi-gigabyte file. The audit log records it.
+
+use std::io::Read;
+
+use sha2::{Digest, Sha256};
+
+use super::FlashState;
+
+/// Reader that hashes everything read through it
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Hex SHA-256 of the bytes read so far
+    pub fn digest(self) -> String {
+        hex::encode(self.hasher.finalize())
+    }
+
+    /// Store the hash of the written image in the flash state
+    pub fn finish(self, state: &FlashState) {
+        *state.image_sha256.lock().unwrap_or_else(|e| e.into_inner()) = Some(self.digest());
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashing_reader() {
+        let mut reader = HashingReader::new(&b"abc"[..]);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"abc");
+        assert_eq!(
+            reader.digest(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Image partition layout
+//!
+//! Parses the MBR or GPT partition table at the start of an image, so a
+//! verification mismatch offset can be named: partition table corruption
+//! reads very differently from damage deep inside a filesystem. The same
+//! table locates partitions to extract from an image.
+
+use std::io::Read;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::image_source::ImageReader;
+
+/// Sector size assumed for partition tables in images
+const SECTOR_SIZE: u64 = 512;
+
+/// Bytes read from the image start, enough for a GPT with 128 entries
+const TABLE_READ_SIZE: usize = 64 * 1024;
+
+/// A partition of the image
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Partition {
+    /// 1-based number, as in /dev/sdX1
+    pub number: usize,
+    /// Byte offsets in the image, `end` exclusive
+    pub start: u64,
+    pub end: u64,
+    /// Filesystem type (MBR) or partition name (GPT)
+    pub kind: String,
+}
+
+impl Partition {
+    pub fn size(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+pub(super) fn read_head(image_path: &Path) -> Result<Vec<u8>, String> {
+    let mut head = Vec::with_capacity(TABLE_READ_SIZE);
+    ImageReader::open(image_path)?
+        .take(TABLE_READ_SIZE as u64)
+        .read_to_end(&mut head)
+        .map_err(|e| format!("Failed to read {}: {}", image_path.display(), e))?;
+    Ok(head)
+}
+
+/// Name the region of the image an offset falls in
+///
+/// Returns None if the image can't be read.
+pub fn describe_image_offset(image_path: &Path, offset: u64) -> Option<String> {
+    let head = read_head(image_path).ok()?;
+    Some(describe_offset(&head, offset))
+}
+
+/// Partitions of the image, from its GPT or else its MBR
+pub fn image_partitions(image_path: &Path) -> Result<Vec<Partition>, String> {
+    let head = read_head(image_path)?;
+    Ok(partitions(&head))
+}
+
+pub(super) fn partitions(head: &[u8]) -> Vec<Partition> {
+    match gpt_partitions(head) {
+        Some((partitions, _)) => partitions,
+        None => mbr_partitions(head),
+    }
+}
+
+/// Partition table type of the image, None if it has none
+pub(super) fn partition_scheme(head: &[u8]) -> Option<&'static str> {
+    if gpt_partitions(head).is_some() {
+        Some("GPT")
+    } else if head.get(510..512) == Some(&[0x55, 0xaa]) && !mbr_partitions(head).is_empty() {
+        Some("MBR")
+    } else {
+        None
+    }
+}
+
+fn describe_offset(head: &[u8], offset: u64) -> String {
+    let gpt = gpt_partitions(head);
+    let is_gpt = gpt.is_some();
+
+    if offset < 446 {
+        return "MBR boot code".to_string();
+    }
+    if offset < 510 {
+        return "MBR partition table".to_string();
+    }
+    if offset < SECTOR_SIZE {
+        return "MBR signature".to_string();
+    }
+
+    let partitions = match gpt {
+        Some((partitions, entries_end)) => {
+            if offset < 2 * SECTOR_SIZE {
+                return "GPT header".to_string();
+            }
+            if offset < entries_end {
+                return "GPT partition entries".to_string();
+            }
+            partitions
+        }
+        None => mbr_partitions(head),
+    };
+
+    if let Some(p) = partitions
+        .iter()
+        .find(|p| (p.start..p.end).contains(&offset))
+    {
+        return format!(
+            "partition {} ({}), {} bytes into it",
+            p.number,
+            p.kind,
+            offset - p.start
+        );
+    }
+
+    match partitions.iter().map(|p| p.start).min() {
+        Some(first) if offset < first => "bootloader area before the first partition".to_string(),
+        Some(_) => "outside any partition".to_string(),
+        None if is_gpt => "GPT image without partitions".to_string(),
+        None => "image without a partition table".to_string(),
+    }
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn mbr_partitions(head: &[u8]) -> Vec<Partition> {
+    if head.get(510..512) != Some(&[0x55, 0xaa]) {
+        return Vec::new();
+    }
+
+    (0..4)
+        .filter_map(|i| {
+            let entry = head.get(446 + i * 16..446 + (i + 1) * 16)?;
+            let kind = entry[4];
+            let start = read_u32(entry, 8)? as u64 * SECTOR_SIZE;
+            let len = read_u32(entry, 12)? as u64 * SECTOR_SIZE;
+            (kind != 0 && len > 0).then(|| Partition {
+                number: i + 1,
+                start,
+                end: start + len,
+                kind: mbr_type_name(kind),
+            })
+        })
+        .collect()
+}
+
+fn mbr_type_name(kind: u8) -> String {
+    match kind {
+        0x83 => "Linux".to_string(),
+        0x0b | 0x0c => "FAT32".to_string(),
+        0x0e | 0x06 => "FAT16".to_string(),
+        0x07 => "NTFS/exFAT".to_string(),
+        0xef => "EFI system".to_string(),
+        0x82 => "Linux swap".to_string(),
+        other => format!("type 0x{:02x}", other),
+    }
+}
+
+/// GPT partitions and the end offset of the entry array, if the image has a GPT
+fn gpt_partitions(head: &[u8]) -> Option<(Vec<Partition>, u64)> {
+    let header = head.get(SECTOR_SIZE as usize..2 * SECTOR_SIZE as usize)?;
+    if &header[..8] != b"EFI PART" {
+        return None;
+    }
+
+    let entries_offset = read_u64(header, 72)?.saturating_mul(SECTOR_SIZE);
+    let entry_count = read_u32(header, 80)? as u64;
+    let entry_size = read_u32(header, 84)? as u64;
+    // The spec requires at least 128 bytes per entry; anything else is corrupt
+    if entry_size < 128 {
+        return None;
+    }
+    let entries_end = entries_offset.saturating_add(entry_count * entry_size);
+
+    // Only entries within the bytes read from the image can be parsed
+    let readable = (head.len() as u64).saturating_sub(entries_offset) / entry_size;
+    let partitions = (0..entry_count.min(readable))
+        .filter_map(|i| {
+            let at = (entries_offset + i * entry_size) as usize;
+            let entry = head.get(at..at + entry_size as usize)?;
+            if entry[..16].iter().all(|&b| b == 0) {
+                return None;
+            }
+            let first_lba = read_u64(entry, 32)?;
+            let last_lba = read_u64(entry, 40)?;
+            let name: Vec<u16> = entry
+                .get(56..128)?
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|&c| c != 0)
+                .collect();
+            let name = String::from_utf16_lossy(&name);
+            Some(Partition {
+                number: i as usize + 1,
+                start: first_lba.saturating_mul(SECTOR_SIZE),
+                end: last_lba.saturating_add(1).saturating_mul(SECTOR_SIZE),
+                kind: if name.is_empty() {
+                    "GPT".to_string()
+                } else {
+                    name
+                },
+            })
+        })
+        .collect();
+
+    Some((partitions, entries_end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mbr_image() -> Vec<u8> {
+        let mut head = vec![0u8; 4096];
+        head[510] = 0x55;
+        head[511] = 0xaa;
+        // Partition 1: Linux, starting at sector 8192, 100 sectors long
+        let entry = &mut head[446..462];
+        entry[4] = 0x83;
+        entry[8..12].copy_from_slice(&8192u32.to_le_bytes());
+        entry[12..16].copy_from_slice(&100u32.to_le_bytes());
+        head
+    }
+
+    #[test]
+    fn test_describe_mbr_offsets() {
+        let head = mbr_image();
+        assert_eq!(describe_offset(&head, 0), "MBR boot code");
+        assert_eq!(describe_offset(&head, 450), "MBR partition table");
+        assert_eq!(
+            describe_offset(&head, 8 * 1024),
+            "bootloader area before the first partition"
+        );
+        assert_eq!(
+            describe_offset(&head, 8192 * 512 + 10),
+            "partition 1 (Linux), 10 bytes into it"
+        );
+        assert_eq!(describe_offset(&head, 9000 * 512), "outside any partition");
+    }
+
+    #[test]
+    fn test_describe_gpt_offsets() {
+        let mut head = vec![0u8; TABLE_READ_SIZE];
+        head[512..520].copy_from_slice(b"EFI PART");
+        head[512 + 72..512 + 80].copy_from_slice(&2u64.to_le_bytes());
+        head[512 + 80..512 + 84].copy_from_slice(&128u32.to_le_bytes());
+        head[512 + 84..512 + 88].copy_from_slice(&128u32.to_le_bytes());
+        let entry = &mut head[1024..1152];
+        entry[0] = 1;
+        entry[32..40].copy_from_slice(&32768u64.to_le_bytes());
+        entry[40..48].copy_from_slice(&65535u64.to_le_bytes());
+        for (i, c) in "rootfs".encode_utf16().enumerate() {
+            entry[56 + i * 2..58 + i * 2].copy_from_slice(&c.to_le_bytes());
+        }
+
+        assert_eq!(describe_offset(&head, 600), "GPT header");
+        assert_eq!(describe_offset(&head, 2048), "GPT partition entries");
+        assert_eq!(
+            describe_offset(&head, 32768 * 512),
+            "partition 1 (rootfs), 0 bytes into it"
+        );
+    }
+}
@@ -0,0 +1,287 @@
+//! Image source for the writers
+//!
+//! XZ and zstd archives are decompressed while writing and fed straight to
+//! the device, so a compressed image needs neither a second copy on disk
+//! nor a wait for decompression before the write starts. Progress counts
+//! archive bytes consumed, the only total known up front for a stream.
+//! The archive itself may be a stream too, see `direct`.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use lzma_rust2::XzReader;
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+use crate::config;
+use crate::decompress::decompressed_size;
+
+/// Compression formats decoded while flashing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    fn for_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "xz" => Some(Self::Xz),
+            "zst" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Whether an image is decompressed while flashing instead of beforehand
+pub fn is_streamed(path: &Path) -> bool {
+    Compression::for_path(path).is_some()
+}
+
+/// Size of the image written to the device
+///
+/// Known for raw images and single-stream XZ archives; other archives
+/// would have to be decompressed to find out.
+pub fn image_size(path: &Path) -> Result<u64, String> {
+    if !is_streamed(path) {
+        return std::fs::metadata(path)
+            .map(|m| m.len())
+            .map_err(|e| format!("Failed to get image size: {}", e));
+    }
+    match decompressed_size(path) {
+        (size, false) => Ok(size),
+        (_, true) => Err(format!(
+            "Size of {} is unknown until it is decompressed",
+            path.display()
+        )),
+    }
+}
+
+/// Size of the image written to the device, decompressing to count it if needed
+///
+/// For zstd archives without a recorded content size, the decoder is run
+/// over the whole archive. Stops with an error once `cancelled` is set.
+pub fn measured_image_size(path: &Path, cancelled: &AtomicBool) -> Result<u64, String> {
+    if let Ok(size) = image_size(path) {
+        return Ok(size);
+    }
+
+    let mut reader = ImageReader::open(path)?;
+    let mut buffer = vec![0u8; config::flash::CHUNK_SIZE];
+    let mut size = 0u64;
+    loop {
+        if cancelled.load(Ordering::SeqCst) {
+            return Err("Verification cancelled".to_string());
+        }
+        match reader
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read image: {}", e))?
+        {
+            0 => return Ok(size),
+            n => size += n as u64,
+        }
+    }
+}
+
+/// Counts the bytes read from the image file
+struct CountingReader {
+    inner: Box<dyn Read + Send>,
+    consumed: Arc<AtomicU64>,
+}
+
+impl Read for CountingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.consumed.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// Reader over the image bytes of a raw or compressed image file
+///
+/// Reads fill the buffer unless the image ends, as they would from a file;
+/// raw devices and sector-aligned verification rely on full chunks.
+pub struct ImageReader {
+    inner: Box<dyn Read + Send>,
+    consumed: Arc<AtomicU64>,
+    source_size: u64,
+    image_size: Option<u64>,
+    compressed: bool,
+}
+
+impl ImageReader {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("Failed to open image: {}", e))?;
+        let source_size = file
+            .metadata()
+            .map_err(|e| format!("Failed to get image size: {}", e))?
+            .len();
+        Self::with_source(path, Box::new(file), source_size, image_size(path).ok())
+    }
+
+    /// Reader over an image arriving as a stream, such as a download
+    ///
+    /// The extension of `name` tells the compression. `source_size` is the
+    /// length of the stream, 0 if unknown.
+    pub fn from_stream(
+        name: &Path,
+        source: Box<dyn Read + Send>,
+        source_size: u64,
+    ) -> Result<Self, String> {
+        let image_size = (!is_streamed(name) && source_size > 0).then_some(source_size);
+        Self::with_source(name, source, source_size, image_size)
+    }
+
+    fn with_source(
+        name: &Path,
+        source: Box<dyn Read + Send>,
+        source_size: u64,
+        image_size: Option<u64>,
+    ) -> Result<Self, String> {
+        let consumed = Arc::new(AtomicU64::new(0));
+        let counting = CountingReader {
+            inner: source,
+            consumed: consumed.clone(),
+        };
+        let compression = Compression::for_path(name);
+        let inner: Box<dyn Read + Send> = match compression {
+            Some(Compression::Xz) => Box::new(XzReader::new(
+                BufReader::with_capacity(config::download::DECOMPRESS_BUFFER_SIZE, counting),
+                true,
+            )),
+            Some(Compression::Zstd) => Box::new(
+                ZstdDecoder::with_buffer(BufReader::with_capacity(
+                    config::download::DECOMPRESS_BUFFER_SIZE,
+                    counting,
+                ))
+                .map_err(|e| format!("Failed to create zstd decoder: {}", e))?,
+            ),
+            None => Box::new(counting),
+        };
+
+        Ok(Self {
+            inner,
+            consumed,
+            source_size,
+            image_size,
+            compressed: compression.is_some(),
+        })
+    }
+
+    /// Size of the image file on disk
+    pub fn source_size(&self) -> u64 {
+        self.source_size
+    }
+
+    /// Bytes of the image file read so far
+    pub fn consumed(&self) -> u64 {
+        self.consumed.load(Ordering::Relaxed)
+    }
+
+    /// Size of the image written to the device, if known up front
+    pub fn image_size(&self) -> Option<u64> {
+        self.image_size
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+}
+
+impl Read for ImageReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.inner.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(filled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const SIZE: usize = 300_000;
+
+    /// Read an image in small chunks, checking that each one is filled
+    fn read_all(path: &Path) -> (Vec<u8>, ImageReader) {
+        let mut reader = ImageReader::open(path).unwrap();
+        let mut data = Vec::new();
+        let mut buffer = vec![0u8; 4096];
+        loop {
+            let n = reader.read(&mut buffer).unwrap();
+            if n == 0 {
+                break;
+            }
+            assert!(n == buffer.len() || data.len() + n == SIZE);
+            data.extend_from_slice(&buffer[..n]);
+        }
+        (data, reader)
+    }
+
+    #[test]
+    fn test_image_reader() {
+        let image = crate::selftest::fixture_image(SIZE);
+        let base = std::env::temp_dir().join(format!("image-source-test-{}", std::process::id()));
+        let raw = base.with_extension("img");
+        let xz = base.with_extension("img.xz");
+        let zst = base.with_extension("img.zst");
+
+        let mut writer =
+            lzma_rust2::XzWriter::new(Vec::new(), lzma_rust2::XzOptions::with_preset(1)).unwrap();
+        writer.write_all(&image).unwrap();
+        std::fs::write(&xz, writer.finish().unwrap()).unwrap();
+        std::fs::write(&zst, zstd::encode_all(&image[..], 3).unwrap()).unwrap();
+        std::fs::write(&raw, &image).unwrap();
+
+        let (data, reader) = read_all(&raw);
+        assert_eq!(data, image);
+        assert!(!reader.is_compressed());
+        assert_eq!(reader.consumed(), SIZE as u64);
+        assert_eq!(reader.image_size(), Some(SIZE as u64));
+
+        for path in [&xz, &zst] {
+            let (data, reader) = read_all(path);
+            assert_eq!(data, image);
+            assert!(reader.is_compressed());
+            assert_eq!(reader.consumed(), reader.source_size());
+        }
+        assert_eq!(image_size(&xz), Ok(SIZE as u64));
+        assert!(image_size(&zst).is_err());
+        let cancelled = AtomicBool::new(false);
+        assert_eq!(measured_image_size(&zst, &cancelled), Ok(SIZE as u64));
+        assert_eq!(measured_image_size(&raw, &cancelled), Ok(SIZE as u64));
+        cancelled.store(true, Ordering::SeqCst);
+        assert!(measured_image_size(&zst, &cancelled).is_err());
+        assert!(is_streamed(Path::new("a.img.XZ")));
+        assert!(!is_streamed(Path::new("a.img.gz")));
+
+        let compressed = std::fs::read(&zst).unwrap();
+        let size = compressed.len() as u64;
+        let mut reader = ImageReader::from_stream(
+            Path::new("a.img.zst"),
+            Box::new(std::io::Cursor::new(compressed)),
+            size,
+        )
+        .unwrap();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, image);
+        assert_eq!(reader.consumed(), size);
+        assert_eq!(reader.image_size(), None);
+
+        for path in [raw, xz, zst] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
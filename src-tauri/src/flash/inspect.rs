@@ -0,0 +1,358 @@
+//! Image inspection
+//!
+//! Summarizes an image before it is flashed: the partition map, the
+//! filesystem type and label of each partition, and the sizes of the file
+//! and of the image it expands to. Filesystems are recognized by their
+//! superblocks, which sit within the first 68 KiB of a partition, so a
+//! compressed image is only decompressed up to the start of its last
+//! partition. The Armbian version, board, distro and kernel branch come
+//! from `/etc/armbian-release` on the root filesystem, read without
+//! mounting it (see `fsaccess`), and otherwise from the Armbian file name.
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::images::parse_image_filename;
+use crate::log_debug;
+
+use super::fsaccess::ImageFiles;
+use super::image_layout::{partition_scheme, partitions, read_head, Partition};
+use super::image_source::{image_size, is_streamed, ImageReader};
+
+const MODULE: &str = "flash::inspect";
+
+/// Bytes read from the start of each partition, enough for a btrfs label
+const PROBE_SIZE: usize = 0x11000;
+
+/// Release file written by the Armbian build, relative to the root filesystem
+const RELEASE_FILE: &str = "etc/armbian-release";
+/// Largest release file read; the real ones are under 1 KiB
+const RELEASE_MAX_SIZE: u64 = 64 * 1024;
+
+/// Filesystem type and label
+type Filesystem = (String, Option<String>);
+
+/// A partition and the filesystem found on it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PartitionDetails {
+    #[serde(flatten)]
+    pub partition: Partition,
+    /// e.g. "ext4", "FAT32", None if not recognized
+    pub filesystem: Option<String>,
+    pub label: Option<String>,
+}
+
+/// What an image contains, as far as it can be told without mounting it
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageDetails {
+    /// "GPT" or "MBR", None without a partition table
+    pub partition_table: Option<String>,
+    pub partitions: Vec<PartitionDetails>,
+    /// Whether the file is compressed
+    pub compressed: bool,
+    /// Size of the file
+    pub file_size: u64,
+    /// Size written to the device, None if unknown before decompressing
+    pub image_size: Option<u64>,
+    pub armbian_version: Option<String>,
+    pub board: Option<String>,
+    pub distro_release: Option<String>,
+    pub kernel_branch: Option<String>,
+}
+
+/// Inspect the image at `image_path`
+pub fn inspect_image(image_path: &Path) -> Result<ImageDetails, String> {
+    let file_size = std::fs::metadata(image_path)
+        .map_err(|e| format!("Failed to read {}: {}", image_path.display(), e))?
+        .len();
+    let head = read_head(image_path)?;
+    let table = partitions(&head);
+    let filesystems = probe_partitions(image_path, &table)?;
+    let parsed = parse_image_filename(&image_path.to_string_lossy());
+
+    let mut details = ImageDetails {
+        partition_table: partition_scheme(&head).map(str::to_string),
+        partitions: table
+            .into_iter()
+            .zip(filesystems)
+            .map(|(partition, fs)| {
+                let (filesystem, label) = match fs {
+                    Some((filesystem, label)) => (Some(filesystem), label),
+                    None => (None, None),
+                };
+                PartitionDetails {
+                    partition,
+                    filesystem,
+                    label,
+                }
+            })
+            .collect(),
+        compressed: is_streamed(image_path),
+        file_size,
+        image_size: image_size(image_path).ok(),
+        armbian_version: parsed.as_ref().map(|p| p.version.clone()),
+        board: parsed.as_ref().map(|p| p.board.clone()),
+        distro_release: parsed.as_ref().and_then(|p| p.distro_release.clone()),
+        kernel_branch: parsed.and_then(|p| p.kernel_branch),
+    };
+    if let Some(release) = read_release(image_path, &details.partitions) {
+        let field = |key: &str, value: &mut Option<String>| {
+            if let Some(found) = release.get(key).filter(|v| !v.is_empty()) {
+                *value = Some(found.clone());
+            }
+        };
+        field("VERSION", &mut details.armbian_version);
+        field("BOARD", &mut details.board);
+        field("BOARD_NAME", &mut details.board);
+        field("DISTRIBUTION_CODENAME", &mut details.distro_release);
+        field("BRANCH", &mut details.kernel_branch);
+    }
+    log_debug!(MODULE, "{}: {:?}", image_path.display(), details);
+    Ok(details)
+}
+
+/// Fields of `/etc/armbian-release` on the image's root filesystem
+///
+/// The root filesystem is usually the last partition, so ext partitions are
+/// tried from the end.
+fn read_release(
+    image_path: &Path,
+    partitions: &[PartitionDetails],
+) -> Option<HashMap<String, String>> {
+    let mut files = ImageFiles::open(image_path).ok()?;
+    for details in partitions.iter().rev() {
+        if !details
+            .filesystem
+            .as_deref()
+            .is_some_and(|fs| fs.starts_with("ext"))
+        {
+            continue;
+        }
+        match files.read_file(&details.partition, RELEASE_FILE, RELEASE_MAX_SIZE) {
+            Ok(Some(content)) => return Some(parse_release(&String::from_utf8_lossy(&content))),
+            Ok(None) => {}
+            Err(e) => log_debug!(
+                MODULE,
+                "No release file on partition {}: {}",
+                details.partition.number,
+                e
+            ),
+        }
+    }
+    None
+}
+
+/// Parse shell-style KEY=value lines, dropping quotes around values
+fn parse_release(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            (key.trim().to_string(), value.to_string())
+        })
+        .collect()
+}
+
+/// Filesystem type and label of each partition, in table order
+///
+/// The image is read once front to back; a partition starting before the
+/// end of the previous probe (overlapping entries) is not probed.
+fn probe_partitions(
+    image_path: &Path,
+    table: &[Partition],
+) -> Result<Vec<Option<Filesystem>>, String> {
+    let mut order: Vec<usize> = (0..table.len()).collect();
+    order.sort_by_key(|&i| table[i].start);
+
+    let mut results = vec![None; table.len()];
+    let mut image = ImageReader::open(image_path)?;
+    let mut position: u64 = 0;
+    let mut window = Vec::with_capacity(PROBE_SIZE);
+    for i in order {
+        let partition = &table[i];
+        if partition.start < position {
+            continue;
+        }
+        let skip = partition.start - position;
+        let skipped = io::copy(&mut (&mut image).take(skip), &mut io::sink())
+            .map_err(|e| format!("Failed to read {}: {}", image_path.display(), e))?;
+        position += skipped;
+        if skipped < skip {
+            break;
+        }
+
+        window.clear();
+        let len = (PROBE_SIZE as u64).min(partition.size());
+        (&mut image)
+            .take(len)
+            .read_to_end(&mut window)
+            .map_err(|e| format!("Failed to read {}: {}", image_path.display(), e))?;
+        position += window.len() as u64;
+        results[i] = probe_filesystem(&window);
+    }
+    Ok(results)
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Label stored as a fixed-size, NUL- or space-padded field
+fn label_at(data: &[u8], at: usize, len: usize) -> Option<String> {
+    let raw = data.get(at..at + len)?;
+    let end = raw.iter().position(|&b| b == 0).unwrap_or(len);
+    let label = String::from_utf8_lossy(&raw[..end]).trim_end().to_string();
+    (!label.is_empty() && label != "NO NAME").then_some(label)
+}
+
+/// Recognize a filesystem from the first bytes of its partition
+fn probe_filesystem(data: &[u8]) -> Option<Filesystem> {
+    const EXT_SUPERBLOCK: usize = 1024;
+    if read_u16(data, EXT_SUPERBLOCK + 0x38) == Some(0xef53) {
+        let compat = read_u32(data, EXT_SUPERBLOCK + 0x5c)?;
+        let incompat = read_u32(data, EXT_SUPERBLOCK + 0x60)?;
+        // Extents or flex_bg mean ext4, a journal alone ext3
+        let kind = if incompat & (0x40 | 0x200) != 0 {
+            "ext4"
+        } else if compat & 0x4 != 0 {
+            "ext3"
+        } else {
+            "ext2"
+        };
+        return Some((kind.to_string(), label_at(data, EXT_SUPERBLOCK + 0x78, 16)));
+    }
+    if data.get(0x10040..0x10048) == Some(b"_BHRfS_M") {
+        return Some(("btrfs".to_string(), label_at(data, 0x1012b, 256)));
+    }
+    if read_u32(data, 1024) == Some(0xf2f5_2010) {
+        return Some(("f2fs".to_string(), None));
+    }
+    if data.get(4086..4096) == Some(b"SWAPSPACE2") {
+        return Some(("swap".to_string(), label_at(data, 1024 + 28, 16)));
+    }
+    match data.get(3..11) {
+        Some(b"NTFS    ") => return Some(("NTFS".to_string(), None)),
+        Some(b"EXFAT   ") => return Some(("exFAT".to_string(), None)),
+        _ => {}
+    }
+    if data.get(510..512) == Some(&[0x55, 0xaa]) {
+        if data.get(82..87) == Some(b"FAT32") {
+            return Some(("FAT32".to_string(), label_at(data, 71, 11)));
+        }
+        if let Some(kind @ (b"FAT12" | b"FAT16")) = data.get(54..59) {
+            let kind = String::from_utf8_lossy(kind).to_string();
+            return Some((kind, label_at(data, 43, 11)));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_filesystems() {
+        let mut ext4 = vec![0u8; PROBE_SIZE];
+        ext4[1024 + 0x38..1024 + 0x3a].copy_from_slice(&0xef53u16.to_le_bytes());
+        ext4[1024 + 0x60] = 0x40;
+        ext4[1024 + 0x78..1024 + 0x78 + 10].copy_from_slice(b"armbi_root");
+        assert_eq!(
+            probe_filesystem(&ext4),
+            Some(("ext4".to_string(), Some("armbi_root".to_string())))
+        );
+
+        let mut fat = vec![0u8; 4096];
+        fat[510] = 0x55;
+        fat[511] = 0xaa;
+        fat[82..90].copy_from_slice(b"FAT32   ");
+        fat[71..82].copy_from_slice(b"NO NAME    ");
+        assert_eq!(probe_filesystem(&fat), Some(("FAT32".to_string(), None)));
+        fat[71..82].copy_from_slice(b"ARMBI_BOOT ");
+        assert_eq!(
+            probe_filesystem(&fat),
+            Some(("FAT32".to_string(), Some("ARMBI_BOOT".to_string())))
+        );
+
+        assert_eq!(probe_filesystem(&[0u8; 4096]), None);
+        // A truncated window can't be misread
+        assert_eq!(probe_filesystem(&ext4[..1100]), None);
+    }
+
+    #[test]
+    fn test_inspect_image() {
+        let dir = std::env::temp_dir().join(format!("inspect-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // MBR with one Linux partition at sector 256 holding an ext2 superblock
+        let mut image = vec![0u8; 256 * 512 + PROBE_SIZE];
+        image[510] = 0x55;
+        image[511] = 0xaa;
+        let entry = &mut image[446..462];
+        entry[4] = 0x83;
+        entry[8..12].copy_from_slice(&256u32.to_le_bytes());
+        entry[12..16].copy_from_slice(&((PROBE_SIZE / 512) as u32).to_le_bytes());
+        let sb = 256 * 512 + 1024;
+        image[sb + 0x38..sb + 0x3a].copy_from_slice(&0xef53u16.to_le_bytes());
+        let path = dir.join("Armbian_25.2.1_Orangepi5_bookworm_current_6.1.99.img");
+        std::fs::write(&path, &image).unwrap();
+
+        let details = inspect_image(&path).unwrap();
+        assert_eq!(details.partition_table.as_deref(), Some("MBR"));
+        assert_eq!(details.partitions.len(), 1);
+        assert_eq!(details.partitions[0].filesystem.as_deref(), Some("ext2"));
+        assert_eq!(details.image_size, Some(image.len() as u64));
+        assert!(!details.compressed);
+        assert_eq!(details.armbian_version.as_deref(), Some("25.2.1"));
+        assert_eq!(details.distro_release.as_deref(), Some("bookworm"));
+        assert_eq!(details.kernel_branch.as_deref(), Some("current"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_inspect_release_file() {
+        let dir = std::env::temp_dir().join(format!("inspect-release-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // The release file wins over a renamed image's file name
+        let rootfs = super::super::fsaccess::tests::ext4_with_file(
+            "armbian-release",
+            b"# PLEASE DO NOT EDIT\nBOARD=orangepi5\nBOARD_NAME=\"Orange Pi 5\"\n\
+              BRANCH=vendor\nVERSION=25.5.1\nDISTRIBUTION_CODENAME=noble\n",
+        );
+        let mut image = vec![0u8; 8 * 512];
+        image[510] = 0x55;
+        image[511] = 0xaa;
+        let entry = &mut image[446..462];
+        entry[4] = 0x83;
+        entry[8..12].copy_from_slice(&8u32.to_le_bytes());
+        entry[12..16].copy_from_slice(&((rootfs.len() / 512) as u32).to_le_bytes());
+        image.extend(rootfs);
+        let path = dir.join("Armbian_25.2.1_Orangepi5_bookworm_current_6.1.99.img");
+        std::fs::write(&path, &image).unwrap();
+
+        let details = inspect_image(&path).unwrap();
+        assert_eq!(details.partitions[0].filesystem.as_deref(), Some("ext4"));
+        assert_eq!(details.armbian_version.as_deref(), Some("25.5.1"));
+        assert_eq!(details.board.as_deref(), Some("Orange Pi 5"));
+        assert_eq!(details.distro_release.as_deref(), Some("noble"));
+        assert_eq!(details.kernel_branch.as_deref(), Some("vendor"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -8,4 +8,7 @@ mod privileges;
 mod writer;
 
 pub use privileges::request_authorization;
-pub use writer::flash_image;
+pub use writer::{
+    backup_device, check_capacity, eject_device, erase_device, flash_image, flash_range,
+    verify_device,
+};
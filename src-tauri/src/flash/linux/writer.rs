@@ -2,24 +2,37 @@
 //!
 //! Writes images to block devices using UDisks2 for privilege escalation.
 //! UDisks2 handles authentication via polkit, so the app can run as a normal user.
+//! Inside a Flatpak, UDisks2 is the only way in: there is no direct /dev access
+//! and partitions are unmounted through UDisks2 as well.
 
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::os::unix::io::{AsRawFd, FromRawFd};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use crate::config;
-use crate::flash::{sync_device, unmount_device, FlashState};
-use crate::utils::{bytes_to_gb, ProgressTracker};
-use crate::{log_debug, log_error, log_info};
+use crate::flash::backup::backup_to_file;
+use crate::flash::capacity;
+use crate::flash::power::PowerGuard;
+use crate::flash::range::RangeWrite;
+use crate::flash::smart_write::{log_skipped, SmartWriter};
+use crate::flash::verify::ChunkChecksums;
+use crate::flash::{
+    measured_image_size, speed_window, sync_device, sync_fd, unmount_device, CapacityReport,
+    EraseLayout, FlashState, HashingReader, VerifyOptions,
+};
+use crate::utils::{
+    bytes_to_gb, mount_timeout, requires_udisks2, run_command, ProgressTracker, Throttle,
+};
+use crate::{log_debug, log_error, log_info, log_warn};
 
 const MODULE: &str = "flash::linux::writer";
 
-/// Open a block device for writing using UDisks2
+/// Open a block device using UDisks2 ("rw" for writing, "r" for reading)
 /// This will trigger a polkit authentication dialog if needed
-async fn open_device_udisks2(device_path: &str) -> Result<File, String> {
+async fn open_device_udisks2(device_path: &str, mode: &str) -> Result<File, String> {
     use std::collections::HashMap;
 
     log_debug!(MODULE, "Opening device via UDisks2: {}", device_path);
@@ -48,12 +61,12 @@ async fn open_device_udisks2(device_path: &str) -> Result<File, String> {
         .await
         .map_err(|e| format!("Failed to get block interface: {}", e))?;
 
-    // Open device for read-write with exclusive access
+    // Open device in the requested mode
     // Options: empty HashMap for default options
     let options: HashMap<&str, udisks2::zbus::zvariant::Value<'_>> = HashMap::new();
 
     let fd = block
-        .open_device("rw", options)
+        .open_device(mode, options)
         .await
         .map_err(|e| format!("Failed to open device (polkit auth may have failed): {}", e))?;
 
@@ -70,26 +83,152 @@ async fn open_device_udisks2(device_path: &str) -> Result<File, String> {
 }
 
 /// Fallback: try to open device directly (requires root)
-fn open_device_direct(device_path: &str) -> Result<File, String> {
+fn open_device_direct(device_path: &str, mode: &str) -> Result<File, String> {
     use std::fs::OpenOptions;
 
     log_debug!(MODULE, "Attempting direct device open: {}", device_path);
 
     OpenOptions::new()
         .read(true)
-        .write(true)
+        .write(mode == "rw")
         .open(device_path)
         .map_err(|e| format!("Failed to open device {}: {}", device_path, e))
 }
 
+/// Open a block device via UDisks2, falling back to direct access
+///
+/// The fallback covers running as root without UDisks2. Sandboxed builds
+/// have no /dev access, so they fail with the UDisks2 error instead.
+async fn open_device(device_path: &str, mode: &str) -> Result<File, String> {
+    match open_device_udisks2(device_path, mode).await {
+        Ok(file) => Ok(file),
+        Err(e) if requires_udisks2() => {
+            log_error!(MODULE, "UDisks2 open failed inside sandbox: {}", e);
+            Err(e)
+        }
+        Err(e) => {
+            log_debug!(MODULE, "UDisks2 open failed ({}), trying direct open...", e);
+            open_device_direct(device_path, mode)
+        }
+    }
+}
+
+/// Unmount every mounted partition of a device through UDisks2
+///
+/// Used instead of `umount` inside a sandbox. Partitions are found in sysfs,
+/// which Flatpak exposes read-only.
+async fn unmount_device_udisks2(device_path: &str) -> Result<(), String> {
+    use std::collections::HashMap;
+
+    let dev_name = device_path
+        .strip_prefix("/dev/")
+        .ok_or_else(|| format!("Invalid device path: {}", device_path))?;
+
+    let client = udisks2::Client::new()
+        .await
+        .map_err(|e| format!("Failed to connect to UDisks2: {}", e))?;
+
+    let partitions: Vec<String> = std::fs::read_dir(format!("/sys/block/{}", dev_name))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.path().join("partition").exists())
+                .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    for partition in partitions {
+        let object_path = format!("/org/freedesktop/UDisks2/block_devices/{}", partition);
+        let Ok(object) = client.object(object_path.as_str()) else {
+            continue;
+        };
+        // Partitions without a filesystem interface have nothing to unmount
+        let Ok(filesystem) = object.filesystem().await else {
+            continue;
+        };
+        let options: HashMap<&str, udisks2::zbus::zvariant::Value<'_>> = HashMap::new();
+        if let Err(e) = filesystem.unmount(options).await {
+            // "Not mounted" errors are expected for most partitions
+            log_debug!(MODULE, "UDisks2 unmount of {} skipped: {}", partition, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Unmount, eject and power off a device so it can be pulled safely
+///
+/// UDisks2 powers off the drive (for USB, the port is switched off) the same
+/// way a file manager's "Safely Remove" does. Without UDisks2, partitions are
+/// unmounted and the media ejected with `eject`.
+pub async fn eject_device(device_path: &str) -> Result<(), String> {
+    sync_device(device_path);
+    match eject_udisks2(device_path).await {
+        Ok(()) => Ok(()),
+        Err(e) if requires_udisks2() => Err(e),
+        Err(e) => {
+            log_debug!(MODULE, "UDisks2 eject failed ({}), trying eject...", e);
+            unmount_device(device_path)?;
+            run_command("eject", &[device_path], mount_timeout())
+                .and_then(|output| output.check())
+                .map(|_| ())
+        }
+    }
+}
+
+async fn eject_udisks2(device_path: &str) -> Result<(), String> {
+    use std::collections::HashMap;
+
+    let dev_name = device_path
+        .strip_prefix("/dev/")
+        .ok_or_else(|| format!("Invalid device path: {}", device_path))?;
+
+    unmount_device_udisks2(device_path).await?;
+
+    let client = udisks2::Client::new()
+        .await
+        .map_err(|e| format!("Failed to connect to UDisks2: {}", e))?;
+    let object_path = format!("/org/freedesktop/UDisks2/block_devices/{}", dev_name);
+    let block = client
+        .object(object_path.as_str())
+        .map_err(|e| format!("Failed to find UDisks2 object: {}", e))?
+        .block()
+        .await
+        .map_err(|e| format!("Failed to get block interface: {}", e))?;
+    let drive = client
+        .drive_for_block(&block)
+        .await
+        .map_err(|e| format!("No drive found for {}: {}", device_path, e))?;
+
+    if drive.ejectable().await.unwrap_or(false) {
+        // Card readers often refuse; powering off below still detaches the card
+        if let Err(e) = drive.eject(HashMap::new()).await {
+            log_debug!(MODULE, "UDisks2 eject of {} skipped: {}", device_path, e);
+        }
+    }
+    if drive.can_power_off().await.unwrap_or(false) {
+        drive
+            .power_off(HashMap::new())
+            .await
+            .map_err(|e| format!("Failed to power off {}: {}", device_path, e))?;
+        log_info!(MODULE, "{} powered off", device_path);
+    } else {
+        log_info!(MODULE, "{} ejected", device_path);
+    }
+    Ok(())
+}
+
 /// Flash an image to a block device
 pub async fn flash_image(
     image_path: &PathBuf,
     device_path: &str,
     state: Arc<FlashState>,
     verify: bool,
+    verify_options: VerifyOptions,
 ) -> Result<(), String> {
     state.reset();
+    let secondary = state.secondary_image();
 
     log_info!(
         MODULE,
@@ -98,23 +237,33 @@ pub async fn flash_image(
         device_path
     );
 
-    // Get image size
-    let image_size = std::fs::metadata(image_path)
-        .map_err(|e| format!("Failed to get image size: {}", e))?
-        .len();
+    // Open image file, decompressing on the fly if it is an archive
+    let mut image_file = HashingReader::new(state.open_image(image_path)?);
+    let image_size = image_file.get_ref().image_size();
+    let source_size = image_file.get_ref().source_size();
 
-    state.total_bytes.store(image_size, Ordering::SeqCst);
+    // Progress counts bytes of the image file, compressed or not
+    state.total_bytes.store(source_size, Ordering::SeqCst);
 
     log_info!(
         MODULE,
-        "Image size: {} bytes ({:.2} GB)",
-        image_size,
-        bytes_to_gb(image_size)
+        "Image file size: {} bytes ({:.2} GB){}",
+        source_size,
+        bytes_to_gb(source_size),
+        if image_file.get_ref().is_compressed() {
+            ", decompressing while writing"
+        } else {
+            ""
+        }
     );
 
     // Unmount the device first
     log_info!(MODULE, "Unmounting device partitions...");
-    unmount_device(device_path)?;
+    if requires_udisks2() {
+        unmount_device_udisks2(device_path).await?;
+    } else {
+        unmount_device(device_path)?;
+    }
 
     // Small delay to ensure unmount completes
     std::thread::sleep(std::time::Duration::from_millis(
@@ -124,22 +273,24 @@ pub async fn flash_image(
     // Try to open device via UDisks2 first (handles polkit auth)
     // Fall back to direct open if UDisks2 fails (e.g., if running as root)
     log_debug!(MODULE, "Opening device for writing...");
-    let mut device = match open_device_udisks2(device_path).await {
-        Ok(file) => file,
-        Err(e) => {
-            log_debug!(MODULE, "UDisks2 open failed ({}), trying direct open...", e);
-            open_device_direct(device_path)?
-        }
-    };
+    let mut device = open_device(device_path, "rw").await?;
 
     let device_fd = device.as_raw_fd();
 
-    // Quick erase - clear partition table area
-    quick_erase(&mut device)?;
-
-    // Open image file
-    let mut image_file =
-        File::open(image_path).map_err(|e| format!("Failed to open image: {}", e))?;
+    // Smart write compares against what is on the device, so leave it intact
+    let mut writer = SmartWriter::new(
+        state.smart_write.load(Ordering::SeqCst),
+        state.skip_zero_blocks.load(Ordering::SeqCst),
+    );
+    if writer.is_enabled() {
+        log_info!(MODULE, "Smart write: only chunks that differ are written");
+    } else {
+        discard_device(&mut device, device_path, &state)?;
+        quick_erase(&mut device)?;
+        if writer.skips_zeros() {
+            writer.set_zeroed(zero_out_device(&mut device, device_path, &state)?);
+        }
+    }
 
     // Write image in chunks with progress
     let chunk_size = config::flash::CHUNK_SIZE;
@@ -150,21 +301,26 @@ pub async fn flash_image(
     let mut tracker = ProgressTracker::new(
         "Write",
         MODULE,
-        image_size,
+        image_size.unwrap_or(0),
         config::logging::WRITE_LOG_INTERVAL_MB,
     );
+    let mut throttle = Throttle::new(state.write_throttle_bytes_per_sec.load(Ordering::SeqCst));
 
     log_info!(MODULE, "Writing image...");
 
-    // Sync interval to show real progress (not just cache writes)
-    // This ensures the progress bar reflects actual disk writes, not just memory cache
+    // Periodic syncs bound how far the page cache runs ahead of the device
     let mut bytes_since_sync: u64 = 0;
+    let mut speed = speed_window();
+    let mut power_guard = PowerGuard::new();
+    let mut checksums = ChunkChecksums::for_options(verify, verify_options);
 
     loop {
         if state.is_cancelled.load(Ordering::SeqCst) {
             return Err("Flash cancelled".to_string());
         }
 
+        power_guard.check(&state, || sync_fd(device_fd))?;
+
         let bytes_read = image_file
             .read(&mut buffer)
             .map_err(|e| format!("Failed to read image: {}", e))?;
@@ -173,37 +329,62 @@ pub async fn flash_image(
             break;
         }
 
-        if let Err(e) = device.write_all(&buffer[..bytes_read]) {
+        if let Err(e) = writer.write(&mut device, &buffer[..bytes_read]) {
             log_error!(MODULE, "Write error at byte {}: {}", written, e);
             return Err(format!("Failed to write at byte {}: {}", written, e));
         }
 
         written += bytes_read as u64;
+        state
+            .skipped_bytes
+            .store(writer.skipped(), Ordering::SeqCst);
+        if let Some(checksums) = checksums.as_mut() {
+            checksums.update(&buffer[..bytes_read]);
+        }
         bytes_since_sync += bytes_read as u64;
-
-        // Periodic sync to flush data to disk and show real progress
-        if bytes_since_sync >= config::logging::LINUX_SYNC_INTERVAL {
-            unsafe {
-                libc::fdatasync(device_fd);
-            }
+        let consumed = image_file.get_ref().consumed();
+        state.written_bytes.store(consumed, Ordering::SeqCst);
+        state
+            .write_elapsed_ms
+            .store(tracker.elapsed().as_millis() as u64, Ordering::SeqCst);
+        state.record_throughput(&mut speed);
+
+        // Periodic sync to flush data to disk and report what it committed
+        if bytes_since_sync >= config::flash::SYNC_INTERVAL {
+            sync_fd(device_fd)?;
             bytes_since_sync = 0;
-            state.written_bytes.store(written, Ordering::SeqCst);
+            state.synced_bytes.store(consumed, Ordering::SeqCst);
         }
 
         // ProgressTracker handles logging automatically
         tracker.update(bytes_read as u64);
+        throttle.pace(bytes_read as u64, &state.is_cancelled);
     }
 
     // Log final summary
     tracker.finish();
+    log_skipped(&writer, written);
+    state.set_zero_ranges(writer.zero_ranges().clone());
+    image_file.finish(&state);
+    if let Some(checksums) = checksums {
+        checksums.finish(&state);
+    }
+    if let Some(tail) = verify_options.tail_check {
+        tail.write_zeros(&mut device)?;
+    }
+    if let Some(secondary) = &secondary {
+        secondary.write(&mut device, &state)?;
+    }
     log_debug!(MODULE, "Syncing...");
 
     // Sync
     device.flush().ok();
-    unsafe {
-        libc::fsync(device_fd);
-    }
+    sync_fd(device_fd)?;
     sync_device(device_path);
+    state.finish_write(written + secondary.as_ref().map_or(0, |s| s.size));
+    state
+        .write_elapsed_ms
+        .store(tracker.elapsed().as_millis() as u64, Ordering::SeqCst);
 
     // Verify if requested
     if verify {
@@ -214,7 +395,7 @@ pub async fn flash_image(
         // Invalidate page cache before verification to ensure we read from disk
         // This is critical - without this, we'd just be verifying cached data
         unsafe {
-            libc::posix_fadvise(device_fd, 0, image_size as i64, libc::POSIX_FADV_DONTNEED);
+            libc::posix_fadvise(device_fd, 0, 0, libc::POSIX_FADV_DONTNEED);
         }
 
         // Seek back to beginning
@@ -222,13 +403,319 @@ pub async fn flash_image(
             .seek(SeekFrom::Start(0))
             .map_err(|e| format!("Failed to seek device: {}", e))?;
 
-        verify_written_data(image_path, &mut device, state.clone())?;
+        verify_written_data(image_path, &mut device, state.clone(), verify_options)?;
+        if let Some(secondary) = &secondary {
+            secondary.verify(&mut device, &state)?;
+        }
     }
 
     log_info!(MODULE, "Flash complete!");
     Ok(())
 }
 
+/// Verify a device against an image without writing to it
+pub async fn verify_device(
+    image_path: &PathBuf,
+    device_path: &str,
+    state: Arc<FlashState>,
+    verify_options: VerifyOptions,
+) -> Result<(), String> {
+    state.reset();
+
+    let image_size = measured_image_size(image_path, &state.is_cancelled)?;
+    state.total_bytes.store(image_size, Ordering::SeqCst);
+
+    log_info!(
+        MODULE,
+        "Verifying {} against {}",
+        device_path,
+        image_path.display()
+    );
+
+    let mut device = open_device(device_path, "r").await?;
+
+    // Read from the disk, not from whatever is left in the page cache
+    unsafe {
+        libc::posix_fadvise(
+            device.as_raw_fd(),
+            0,
+            image_size as i64,
+            libc::POSIX_FADV_DONTNEED,
+        );
+    }
+
+    verify_written_data(image_path, &mut device, state, verify_options)
+}
+
+/// Read a whole device into a compressed image file
+///
+/// Returns the size of the written archive.
+pub async fn backup_device(
+    device_path: &str,
+    device_size: u64,
+    output: &Path,
+    state: Arc<FlashState>,
+) -> Result<u64, String> {
+    state.reset();
+
+    log_info!(MODULE, "Backing up {} to {}", device_path, output.display());
+
+    let mut device = open_device(device_path, "r").await?;
+
+    // One pass from start to end; nothing read needs to stay cached
+    unsafe {
+        libc::posix_fadvise(device.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL);
+    }
+
+    backup_to_file(&mut device, device_size, output, &state)
+}
+
+/// Write a blob at a byte offset, leaving the rest of the device untouched
+pub async fn flash_range(
+    device_path: &str,
+    range: &RangeWrite,
+    state: Arc<FlashState>,
+    verify: bool,
+) -> Result<(), String> {
+    state.reset();
+
+    log_info!(
+        MODULE,
+        "Starting range write: {} -> {} at byte {}",
+        range.input.display(),
+        device_path,
+        range.offset
+    );
+
+    if requires_udisks2() {
+        unmount_device_udisks2(device_path).await?;
+    } else {
+        unmount_device(device_path)?;
+    }
+    std::thread::sleep(std::time::Duration::from_millis(
+        config::flash::UNMOUNT_DELAY_MS,
+    ));
+
+    let mut device = open_device(device_path, "rw").await?;
+    let device_fd = device.as_raw_fd();
+    range.write(&mut device, &state)?;
+    sync_fd(device_fd)?;
+    sync_device(device_path);
+
+    if verify {
+        state.is_verifying.store(true, Ordering::SeqCst);
+        // Read from the disk, not from the page cache just written
+        unsafe {
+            libc::posix_fadvise(device_fd, 0, 0, libc::POSIX_FADV_DONTNEED);
+        }
+        range.verify(&mut device, &state)?;
+    }
+
+    log_info!(MODULE, "Range write complete!");
+    Ok(())
+}
+
+/// Replace everything on a device with one empty FAT32 or exFAT partition
+pub async fn erase_device(
+    device_path: &str,
+    layout: &EraseLayout,
+    state: Arc<FlashState>,
+) -> Result<(), String> {
+    state.reset();
+
+    log_info!(MODULE, "Starting erase of {}", device_path);
+
+    if requires_udisks2() {
+        unmount_device_udisks2(device_path).await?;
+    } else {
+        unmount_device(device_path)?;
+    }
+    std::thread::sleep(std::time::Duration::from_millis(
+        config::flash::UNMOUNT_DELAY_MS,
+    ));
+
+    let mut device = open_device(device_path, "rw").await?;
+    // Discarded blocks make the card's free space fast to write again
+    discard_device(&mut device, device_path, &state)?;
+    layout.write(&mut device, &state)?;
+    sync_fd(device.as_raw_fd())?;
+    sync_device(device_path);
+
+    log_info!(MODULE, "Erase complete!");
+    Ok(())
+}
+
+/// Check a device for fake capacity, restoring the sampled blocks
+pub async fn check_capacity(
+    device_path: &str,
+    device_size: u64,
+    state: Arc<FlashState>,
+) -> Result<CapacityReport, String> {
+    state.reset();
+
+    log_info!(MODULE, "Starting capacity check of {}", device_path);
+
+    if requires_udisks2() {
+        unmount_device_udisks2(device_path).await?;
+    } else {
+        unmount_device(device_path)?;
+    }
+    std::thread::sleep(std::time::Duration::from_millis(
+        config::flash::UNMOUNT_DELAY_MS,
+    ));
+
+    let mut device = open_device(device_path, "rw").await?;
+    let report = capacity::check_capacity(&mut device, device_size, &state, |device| {
+        // Read from the disk, not from the page cache just written
+        let fd = device.as_raw_fd();
+        sync_fd(fd)?;
+        unsafe {
+            libc::posix_fadvise(fd, 0, 0, libc::POSIX_FADV_DONTNEED);
+        }
+        Ok(())
+    })?;
+    sync_fd(device.as_raw_fd())?;
+    sync_device(device_path);
+    Ok(report)
+}
+
+/// Whether the kernel reports discard support for the device
+///
+/// Unknown (no sysfs entry) counts as supported; the ioctl will tell.
+fn discard_supported(device_path: &str) -> bool {
+    let Some(name) = Path::new(device_path).file_name() else {
+        return true;
+    };
+    let max_bytes = Path::new("/sys/class/block")
+        .join(name)
+        .join("queue/discard_max_bytes");
+    match std::fs::read_to_string(max_bytes) {
+        Ok(value) => value.trim() != "0",
+        Err(_) => true,
+    }
+}
+
+/// Discard every block of the device before writing
+///
+/// Resetting the flash translation layer lets the card allocate fresh
+/// blocks instead of erasing them during the write. BLKDISCARD is issued in
+/// chunks so a cancel is noticed; if the ioctl is refused, e.g. because
+/// the descriptor came from UDisks2, the blkdiscard tool is tried. Devices
+/// without discard support (most USB card readers) are left as they are.
+/// Discarded blocks are not guaranteed to read back as zeros, so the quick
+/// erase still runs afterwards.
+fn discard_device(device: &mut File, device_path: &str, state: &FlashState) -> Result<(), String> {
+    /// _IO(0x12, 119)
+    const BLKDISCARD: libc::c_ulong = 0x1277;
+
+    if !discard_supported(device_path) {
+        log_debug!(MODULE, "{} does not support discard", device_path);
+        return Ok(());
+    }
+    let size = device
+        .seek(SeekFrom::End(0))
+        .map_err(|e| format!("Failed to get device size: {}", e))?;
+    device
+        .seek(SeekFrom::Start(0))
+        .map_err(|e| format!("Failed to seek to start: {}", e))?;
+
+    log_info!(
+        MODULE,
+        "Discarding {:.2} GB on {}",
+        bytes_to_gb(size),
+        device_path
+    );
+    let fd = device.as_raw_fd();
+    let mut offset: u64 = 0;
+    while offset < size {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Err("Flash cancelled".to_string());
+        }
+        let range: [u64; 2] = [offset, config::flash::DISCARD_CHUNK_SIZE.min(size - offset)];
+        // SAFETY: BLKDISCARD reads a [start, length] pair of u64 from the pointer
+        if unsafe { libc::ioctl(fd, BLKDISCARD as _, range.as_ptr()) } != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::EOPNOTSUPP) {
+                log_info!(MODULE, "{} does not support discard", device_path);
+                return Ok(());
+            }
+            if offset > 0 {
+                log_warn!(MODULE, "Discard stopped at byte {}: {}", offset, err);
+                return Ok(());
+            }
+            log_debug!(MODULE, "BLKDISCARD failed: {}, trying blkdiscard", err);
+            discard_with_tool(device_path);
+            return Ok(());
+        }
+        offset += range[1];
+    }
+
+    log_info!(MODULE, "Device discarded");
+    Ok(())
+}
+
+/// Zero the whole device if it can do so without writing every block
+///
+/// Only devices reporting hardware write-zeroes support are zeroed; for the
+/// others BLKZEROOUT would write every byte, which zero skipping avoids.
+/// Returns whether the whole device now reads as zeros.
+fn zero_out_device(
+    device: &mut File,
+    device_path: &str,
+    state: &FlashState,
+) -> Result<bool, String> {
+    /// _IO(0x12, 127)
+    const BLKZEROOUT: libc::c_ulong = 0x127f;
+
+    let supported = Path::new(device_path)
+        .file_name()
+        .map(|name| {
+            Path::new("/sys/class/block")
+                .join(name)
+                .join("queue/write_zeroes_max_bytes")
+        })
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .is_some_and(|value| value.trim() != "0");
+    if !supported {
+        log_debug!(MODULE, "{} cannot zero blocks in hardware", device_path);
+        return Ok(false);
+    }
+    let size = device
+        .seek(SeekFrom::End(0))
+        .map_err(|e| format!("Failed to get device size: {}", e))?;
+    device
+        .seek(SeekFrom::Start(0))
+        .map_err(|e| format!("Failed to seek to start: {}", e))?;
+
+    let fd = device.as_raw_fd();
+    let mut offset: u64 = 0;
+    while offset < size {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Err("Flash cancelled".to_string());
+        }
+        let range: [u64; 2] = [offset, config::flash::DISCARD_CHUNK_SIZE.min(size - offset)];
+        // SAFETY: BLKZEROOUT reads a [start, length] pair of u64 from the pointer
+        if unsafe { libc::ioctl(fd, BLKZEROOUT as _, range.as_ptr()) } != 0 {
+            let err = std::io::Error::last_os_error();
+            log_info!(MODULE, "Zeroing stopped at byte {}: {}", offset, err);
+            return Ok(false);
+        }
+        offset += range[1];
+    }
+
+    log_info!(MODULE, "Device zeroed");
+    Ok(true)
+}
+
+/// Discard the device with the blkdiscard tool; failures are only logged
+fn discard_with_tool(device_path: &str) {
+    let timeout = std::time::Duration::from_secs(config::process::DISCARD_TIMEOUT_SECS);
+    match run_command("blkdiscard", &[device_path], timeout).and_then(|output| output.check()) {
+        Ok(_) => log_info!(MODULE, "Device discarded with blkdiscard"),
+        Err(e) => log_info!(MODULE, "Discard skipped: {}", e),
+    }
+}
+
 /// Quick erase - write zeros to first portion of device
 fn quick_erase(device: &mut File) -> Result<(), String> {
     let erase_size = config::flash::QUICK_ERASE_SIZE;
@@ -273,6 +760,11 @@ fn verify_written_data(
     image_path: &PathBuf,
     device: &mut File,
     state: Arc<FlashState>,
+    options: VerifyOptions,
 ) -> Result<(), String> {
-    crate::flash::verify::verify_data(image_path, device, state)
+    crate::flash::verify::verify_data_with_options(image_path, device, state.clone(), options)?;
+    match options.tail_check {
+        Some(tail) => tail.verify(device, &state),
+        None => Ok(()),
+    }
 }
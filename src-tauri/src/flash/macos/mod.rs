@@ -9,4 +9,7 @@ mod writer;
 
 // Re-export public API
 pub use authorization::request_authorization;
-pub use writer::flash_image;
+pub use writer::{
+    backup_device, check_capacity, eject_device, erase_device, flash_image, flash_range,
+    verify_device,
+};
@@ -5,13 +5,24 @@
 use std::fs::File;
 use std::io::{Read, Write};
 use std::os::unix::io::{AsRawFd, FromRawFd};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use crate::config;
-use crate::flash::{sync_device, unmount_device, FlashState};
-use crate::utils::{bytes_to_gb, ProgressTracker};
+use crate::flash::backup::backup_to_file;
+use crate::flash::capacity;
+use crate::flash::power::PowerGuard;
+use crate::flash::range::RangeWrite;
+use crate::flash::smart_write::{log_skipped, SmartWriter};
+use crate::flash::verify::ChunkChecksums;
+use crate::flash::{
+    measured_image_size, speed_window, sync_device, sync_fd, unmount_device, CapacityReport,
+    EraseLayout, FlashState, HashingReader, ImageReader, VerifyOptions,
+};
+use crate::utils::{
+    bytes_to_gb, mount_timeout, query_timeout, run_command, ProgressTracker, Throttle,
+};
 use crate::{log_debug, log_error, log_info};
 
 use super::authorization::{free_authorization, SAVED_AUTH};
@@ -121,6 +132,21 @@ pub fn open_device_with_saved_auth(device_path: &str) -> Result<OpenDeviceResult
         );
         libc::close(stdin_pipe[1]);
 
+        // authopen already holds the authorization and answers at once; a
+        // stuck helper must not hang the flash, so both waits are bounded
+        let timeout = query_timeout();
+        let receive_timeout = libc::timeval {
+            tv_sec: timeout.as_secs() as libc::time_t,
+            tv_usec: 0,
+        };
+        libc::setsockopt(
+            sock_pair[0],
+            libc::SOL_SOCKET,
+            libc::SO_RCVTIMEO,
+            &receive_timeout as *const libc::timeval as *const libc::c_void,
+            std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+        );
+
         // Receive file descriptor from authopen
         let buf_size = 64;
         let mut buf = vec![0u8; buf_size];
@@ -142,13 +168,22 @@ pub fn open_device_with_saved_auth(device_path: &str) -> Result<OpenDeviceResult
         let size = libc::recvmsg(sock_pair[0], &mut msg, 0);
         log_debug!(MODULE, "recvmsg returned size: {}", size);
 
-        // Wait for child
+        // Wait for child, killing it once the timeout has passed
+        let deadline = std::time::Instant::now() + timeout;
         let mut status: i32 = 0;
         loop {
-            let wpid = libc::waitpid(pid, &mut status, 0);
-            if wpid != -1 || *libc::__error() != libc::EINTR {
+            let wpid = libc::waitpid(pid, &mut status, libc::WNOHANG);
+            if wpid == pid || (wpid == -1 && *libc::__error() != libc::EINTR) {
                 break;
             }
+            if std::time::Instant::now() >= deadline {
+                log_error!(MODULE, "authopen timed out after {}s", timeout.as_secs());
+                libc::kill(pid, libc::SIGKILL);
+                libc::waitpid(pid, &mut status, 0);
+                libc::close(sock_pair[0]);
+                return Err(format!("authopen timed out after {}s", timeout.as_secs()));
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
         }
 
         log_debug!(MODULE, "authopen exit code: {}", libc::WEXITSTATUS(status));
@@ -223,9 +258,7 @@ pub fn quick_erase(device: &mut File, device_fd: i32) -> Result<(), String> {
 
     // Sync the erase
     device.flush().ok();
-    unsafe {
-        libc::fsync(device_fd);
-    }
+    sync_fd(device_fd)?;
 
     // Seek back to beginning for image write
     unsafe {
@@ -242,15 +275,18 @@ pub async fn flash_image(
     device_path: &str,
     state: Arc<FlashState>,
     verify: bool,
+    verify_options: VerifyOptions,
 ) -> Result<(), String> {
     state.reset();
+    let secondary = state.secondary_image();
 
-    // Get image size
-    let image_size = std::fs::metadata(image_path)
-        .map_err(|e| format!("Failed to get image size: {}", e))?
-        .len();
+    // Open image file, decompressing on the fly if it is an archive
+    let image_file = state.open_image(image_path)?;
 
-    state.total_bytes.store(image_size, Ordering::SeqCst);
+    // Progress counts bytes of the image file, compressed or not
+    state
+        .total_bytes
+        .store(image_file.source_size(), Ordering::SeqCst);
 
     // Use raw disk access for better performance
     let raw_device = device_path.replace("/dev/disk", "/dev/rdisk");
@@ -284,9 +320,10 @@ pub async fn flash_image(
         device_path,
         &mut device,
         device_fd,
-        image_size,
+        image_file,
         state,
         verify,
+        verify_options,
     )
     .await;
 
@@ -306,16 +343,25 @@ async fn do_flash_work(
     device_path: &str,
     device: &mut File,
     device_fd: i32,
-    image_size: u64,
+    image_file: ImageReader,
     state: Arc<FlashState>,
     verify: bool,
+    verify_options: VerifyOptions,
 ) -> Result<(), String> {
-    // Quick erase first - clear partition tables and boot sectors
-    quick_erase(device, device_fd)?;
+    // Quick erase first - clear partition tables and boot sectors, unless
+    // smart write needs to compare against them
+    let mut writer = SmartWriter::new(
+        state.smart_write.load(Ordering::SeqCst),
+        state.skip_zero_blocks.load(Ordering::SeqCst),
+    );
+    if writer.is_enabled() {
+        log_info!(MODULE, "Smart write: only chunks that differ are written");
+    } else {
+        quick_erase(device, device_fd)?;
+    }
 
-    // Open image file
-    let mut image_file =
-        File::open(image_path).map_err(|e| format!("Failed to open image: {}", e))?;
+    let image_size = image_file.image_size();
+    let mut image_file = HashingReader::new(image_file);
 
     // Write image in chunks with progress
     let chunk_size = config::flash::CHUNK_SIZE;
@@ -326,22 +372,36 @@ async fn do_flash_work(
     let mut tracker = ProgressTracker::new(
         "Write",
         MODULE,
-        image_size,
+        image_size.unwrap_or(0),
         config::logging::WRITE_LOG_INTERVAL_MB,
     );
+    let mut throttle = Throttle::new(state.write_throttle_bytes_per_sec.load(Ordering::SeqCst));
 
+    let source_size = image_file.get_ref().source_size();
     log_info!(
         MODULE,
-        "Starting to write {} bytes ({:.2} GB)",
-        image_size,
-        bytes_to_gb(image_size)
+        "Starting to write {} bytes ({:.2} GB){}",
+        source_size,
+        bytes_to_gb(source_size),
+        if image_file.get_ref().is_compressed() {
+            " of compressed image, decompressing while writing"
+        } else {
+            ""
+        }
     );
 
+    let mut bytes_since_sync: u64 = 0;
+    let mut speed = speed_window();
+    let mut power_guard = PowerGuard::new();
+    let mut checksums = ChunkChecksums::for_options(verify, verify_options);
+
     loop {
         if state.is_cancelled.load(Ordering::SeqCst) {
             return Err("Flash cancelled".to_string());
         }
 
+        power_guard.check(&state, || sync_fd(device_fd))?;
+
         let bytes_read = image_file
             .read(&mut buffer)
             .map_err(|e| format!("Failed to read image: {}", e))?;
@@ -350,14 +410,8 @@ async fn do_flash_work(
             break;
         }
 
-        if let Err(e) = device.write_all(&buffer[..bytes_read]) {
-            log_error!(
-                MODULE,
-                "Write error at byte {}/{}: {}",
-                written,
-                image_size,
-                e
-            );
+        if let Err(e) = writer.write(device, &buffer[..bytes_read]) {
+            log_error!(MODULE, "Write error at byte {}: {}", written, e);
             return Err(format!(
                 "Failed to write to device at byte {}: {}",
                 written, e
@@ -365,39 +419,307 @@ async fn do_flash_work(
         }
 
         written += bytes_read as u64;
-        state.written_bytes.store(written, Ordering::SeqCst);
+        state
+            .skipped_bytes
+            .store(writer.skipped(), Ordering::SeqCst);
+        if let Some(checksums) = checksums.as_mut() {
+            checksums.update(&buffer[..bytes_read]);
+        }
+        bytes_since_sync += bytes_read as u64;
+        let consumed = image_file.get_ref().consumed();
+        state.written_bytes.store(consumed, Ordering::SeqCst);
+        state
+            .write_elapsed_ms
+            .store(tracker.elapsed().as_millis() as u64, Ordering::SeqCst);
+        state.record_throughput(&mut speed);
+
+        // Periodic sync so the device's own cache does not run far ahead
+        if bytes_since_sync >= config::flash::SYNC_INTERVAL {
+            sync_fd(device_fd)?;
+            bytes_since_sync = 0;
+            state.synced_bytes.store(consumed, Ordering::SeqCst);
+        }
 
         // ProgressTracker handles logging automatically
         tracker.update(bytes_read as u64);
+        throttle.pace(bytes_read as u64, &state.is_cancelled);
     }
 
     // Log final summary
     tracker.finish();
+    log_skipped(&writer, written);
+    state.set_zero_ranges(writer.zero_ranges().clone());
+    image_file.finish(&state);
+    if let Some(checksums) = checksums {
+        checksums.finish(&state);
+    }
+    if let Some(tail) = verify_options.tail_check {
+        tail.write_zeros(device)?;
+    }
+    if let Some(secondary) = &secondary {
+        secondary.write(device, &state)?;
+    }
     log_debug!(MODULE, "Syncing...");
 
     // Sync to ensure all data is written
     device.flush().ok();
-    unsafe {
-        libc::fsync(device_fd);
-    }
+    sync_fd(device_fd)?;
     sync_device(device_path);
+    state.finish_write(written + secondary.as_ref().map_or(0, |s| s.size));
+    state
+        .write_elapsed_ms
+        .store(tracker.elapsed().as_millis() as u64, Ordering::SeqCst);
 
     // Verify if requested - reuse same fd (no additional auth needed)
     if verify {
         log_info!(MODULE, "Starting verification");
-        verify_written_data(image_path, device, device_fd, state.clone())?;
+        verify_written_data(image_path, device, device_fd, state.clone(), verify_options)?;
+        if let Some(secondary) = &secondary {
+            secondary.verify(device, &state)?;
+        }
     }
 
     log_info!(MODULE, "Flash complete!");
     Ok(())
 }
 
+/// Unmount all volumes of a disk and eject it with `diskutil eject`
+pub async fn eject_device(device_path: &str) -> Result<(), String> {
+    let disk_path = device_path.replace("/dev/rdisk", "/dev/disk");
+    log_info!(MODULE, "Ejecting {}", disk_path);
+    run_command("diskutil", &["eject", &disk_path], mount_timeout())
+        .and_then(|output| output.check())
+        .map_err(|e| format!("Failed to eject {}: {}", disk_path, e))?;
+    log_info!(MODULE, "{} ejected", disk_path);
+    Ok(())
+}
+
+/// Verify a device against an image without writing to it
+///
+/// Like flashing, this needs authorization saved by `request_authorization`.
+pub async fn verify_device(
+    image_path: &PathBuf,
+    device_path: &str,
+    state: Arc<FlashState>,
+    verify_options: VerifyOptions,
+) -> Result<(), String> {
+    state.reset();
+
+    let image_size = measured_image_size(image_path, &state.is_cancelled)?;
+    state.total_bytes.store(image_size, Ordering::SeqCst);
+
+    let raw_device = device_path.replace("/dev/disk", "/dev/rdisk");
+
+    // Mounted volumes would keep the raw device busy
+    unmount_device(device_path)?;
+    std::thread::sleep(std::time::Duration::from_millis(
+        config::flash::UNMOUNT_DELAY_MS,
+    ));
+
+    log_debug!(MODULE, "Opening device with saved authorization");
+    let open_result = open_device_with_saved_auth(&raw_device)?;
+    let mut device = open_result.file;
+    let device_fd = device.as_raw_fd();
+    let auth_ref_wrapper = open_result.auth_ref;
+
+    {
+        let mut saved = SAVED_AUTH.lock().unwrap();
+        *saved = None;
+    }
+
+    let result = verify_written_data(image_path, &mut device, device_fd, state, verify_options);
+
+    drop(device);
+    unsafe {
+        free_authorization(auth_ref_wrapper.0);
+    }
+
+    result
+}
+
+/// Read a whole device into a compressed image file
+///
+/// Like flashing, this needs authorization saved by `request_authorization`.
+/// Returns the size of the written archive.
+pub async fn backup_device(
+    device_path: &str,
+    device_size: u64,
+    output: &Path,
+    state: Arc<FlashState>,
+) -> Result<u64, String> {
+    state.reset();
+
+    let raw_device = device_path.replace("/dev/disk", "/dev/rdisk");
+
+    // Volumes changing while they are read would make an inconsistent backup
+    unmount_device(device_path)?;
+    std::thread::sleep(std::time::Duration::from_millis(
+        config::flash::UNMOUNT_DELAY_MS,
+    ));
+
+    log_debug!(MODULE, "Opening device with saved authorization");
+    let open_result = open_device_with_saved_auth(&raw_device)?;
+    let mut device = open_result.file;
+    let auth_ref_wrapper = open_result.auth_ref;
+
+    {
+        let mut saved = SAVED_AUTH.lock().unwrap();
+        *saved = None;
+    }
+
+    let result = backup_to_file(&mut device, device_size, output, &state);
+
+    drop(device);
+    unsafe {
+        free_authorization(auth_ref_wrapper.0);
+    }
+
+    result
+}
+
+/// Write a blob at a byte offset, leaving the rest of the device untouched
+///
+/// Like flashing, this needs authorization saved by `request_authorization`.
+/// The raw device bypasses the buffer cache, so the read-back reaches the disk.
+pub async fn flash_range(
+    device_path: &str,
+    range: &RangeWrite,
+    state: Arc<FlashState>,
+    verify: bool,
+) -> Result<(), String> {
+    state.reset();
+
+    let raw_device = device_path.replace("/dev/disk", "/dev/rdisk");
+
+    unmount_device(device_path)?;
+    std::thread::sleep(std::time::Duration::from_millis(
+        config::flash::UNMOUNT_DELAY_MS,
+    ));
+
+    log_debug!(MODULE, "Opening device with saved authorization");
+    let open_result = open_device_with_saved_auth(&raw_device)?;
+    let mut device = open_result.file;
+    let device_fd = device.as_raw_fd();
+    let auth_ref_wrapper = open_result.auth_ref;
+
+    {
+        let mut saved = SAVED_AUTH.lock().unwrap();
+        *saved = None;
+    }
+
+    let result = range.write(&mut device, &state).and_then(|()| {
+        sync_fd(device_fd)?;
+        sync_device(device_path);
+        if verify {
+            state.is_verifying.store(true, Ordering::SeqCst);
+            range.verify(&mut device, &state)?;
+        }
+        Ok(())
+    });
+
+    drop(device);
+    unsafe {
+        free_authorization(auth_ref_wrapper.0);
+    }
+
+    result
+}
+
+/// Replace everything on a device with one empty FAT32 or exFAT partition
+///
+/// Needs authorization saved by `request_authorization`, like flashing.
+pub async fn erase_device(
+    device_path: &str,
+    layout: &EraseLayout,
+    state: Arc<FlashState>,
+) -> Result<(), String> {
+    state.reset();
+
+    log_info!(MODULE, "Starting erase of {}", device_path);
+    let raw_device = device_path.replace("/dev/disk", "/dev/rdisk");
+
+    unmount_device(device_path)?;
+    std::thread::sleep(std::time::Duration::from_millis(
+        config::flash::UNMOUNT_DELAY_MS,
+    ));
+
+    log_debug!(MODULE, "Opening device with saved authorization");
+    let open_result = open_device_with_saved_auth(&raw_device)?;
+    let mut device = open_result.file;
+    let device_fd = device.as_raw_fd();
+    let auth_ref_wrapper = open_result.auth_ref;
+
+    {
+        let mut saved = SAVED_AUTH.lock().unwrap();
+        *saved = None;
+    }
+
+    let result = layout.write(&mut device, &state).and_then(|()| {
+        sync_fd(device_fd)?;
+        sync_device(device_path);
+        Ok(())
+    });
+
+    drop(device);
+    unsafe {
+        free_authorization(auth_ref_wrapper.0);
+    }
+
+    if result.is_ok() {
+        log_info!(MODULE, "Erase complete!");
+    }
+    result
+}
+
+/// Check a device for fake capacity, restoring the sampled blocks
+///
+/// Needs authorization saved by `request_authorization`, like flashing. The
+/// raw device bypasses the buffer cache, so the read-back reaches the disk.
+pub async fn check_capacity(
+    device_path: &str,
+    device_size: u64,
+    state: Arc<FlashState>,
+) -> Result<CapacityReport, String> {
+    state.reset();
+
+    log_info!(MODULE, "Starting capacity check of {}", device_path);
+    let raw_device = device_path.replace("/dev/disk", "/dev/rdisk");
+
+    unmount_device(device_path)?;
+    std::thread::sleep(std::time::Duration::from_millis(
+        config::flash::UNMOUNT_DELAY_MS,
+    ));
+
+    log_debug!(MODULE, "Opening device with saved authorization");
+    let open_result = open_device_with_saved_auth(&raw_device)?;
+    let mut device = open_result.file;
+    let auth_ref_wrapper = open_result.auth_ref;
+
+    {
+        let mut saved = SAVED_AUTH.lock().unwrap();
+        *saved = None;
+    }
+
+    let result = capacity::check_capacity(&mut device, device_size, &state, |device| {
+        sync_fd(device.as_raw_fd())
+    });
+    sync_device(device_path);
+
+    drop(device);
+    unsafe {
+        free_authorization(auth_ref_wrapper.0);
+    }
+
+    result
+}
+
 /// Verify written data by reading back and comparing
 fn verify_written_data(
     image_path: &PathBuf,
     device: &mut File,
     device_fd: i32,
     state: Arc<FlashState>,
+    options: VerifyOptions,
 ) -> Result<(), String> {
     // Seek device back to beginning before verification
     unsafe {
@@ -405,5 +727,9 @@ fn verify_written_data(
     }
 
     // Use shared verification logic
-    crate::flash::verify::verify_data(image_path, device, state)
+    crate::flash::verify::verify_data_with_options(image_path, device, state.clone(), options)?;
+    match options.tail_check {
+        Some(tail) => tail.verify(device, &state),
+        None => Ok(()),
+    }
 }
@@ -5,7 +5,47 @@
 //! - Linux: Uses pkexec for privilege escalation
 //! - Windows: Requires running as Administrator
 
+mod backup;
+mod boot_partition;
+mod capacity;
+mod device_lock;
+mod erase;
+mod extract;
+mod failure_report;
+mod fsaccess;
+mod image_hash;
+mod image_layout;
+mod image_source;
+mod inspect;
+mod power;
+mod privileges;
+mod range;
+mod secondary;
+mod smart_write;
+mod tail_check;
 mod verify;
+mod watchdog;
+
+pub use backup::check_backup_path;
+pub use boot_partition::{boot_dir, mount_device_partition, release_mounts, with_first_partition};
+pub use capacity::CapacityReport;
+pub use device_lock::lock_device;
+pub use erase::{volume_serial, EraseFilesystem, EraseLayout};
+pub use extract::extract_partition;
+pub use failure_report::write_boot_partition_report;
+pub use image_hash::HashingReader;
+pub use image_layout::{image_partitions, Partition};
+pub use image_source::{image_size, is_streamed, measured_image_size, ImageReader};
+pub use inspect::{inspect_image, ImageDetails};
+pub use privileges::{privilege_status, PrivilegeStatus};
+pub use range::RangeWrite;
+pub use secondary::SecondaryImage;
+pub use tail_check::TailCheck;
+pub use verify::{
+    hexdump_lines, verify_data_with_options, ChunkChecksums, VerifyMismatch, VerifyMode,
+    VerifyOptions,
+};
+pub use watchdog::Watchdog;
 
 #[cfg(target_os = "linux")]
 mod linux;
@@ -14,18 +54,62 @@ mod macos;
 #[cfg(target_os = "windows")]
 mod windows;
 
-#[cfg(any(target_os = "linux", target_os = "macos"))]
-use std::process::Command;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 
+use crate::config;
+use crate::utils::{eta_secs, RollingRate};
+use smart_write::SkippedRanges;
+
 /// Flash progress state shared between frontend and backend
 pub struct FlashState {
     pub total_bytes: AtomicU64,
     pub written_bytes: AtomicU64,
+    /// Part of `written_bytes` the device confirmed at the last sync; the
+    /// rest may still sit in OS or device write caches
+    pub synced_bytes: AtomicU64,
     pub verified_bytes: AtomicU64,
     pub is_verifying: AtomicBool,
     pub is_cancelled: AtomicBool,
+    /// Writing is paused because the battery is critical
+    pub is_paused_for_power: AtomicBool,
+    /// Time spent writing so far (final value includes the closing sync)
+    pub write_elapsed_ms: AtomicU64,
+    /// Time spent reading back for verification so far
+    pub verify_elapsed_ms: AtomicU64,
+    /// Read-back speed was far below what the device class should manage
+    pub is_read_slow: AtomicBool,
+    /// No bytes moved for longer than the stall timeout
+    pub is_stalled: AtomicBool,
+    /// Seconds without progress while stalled
+    pub stalled_secs: AtomicU64,
+    /// First mismatch found by the last failed verification
+    pub verify_mismatch: std::sync::Mutex<Option<VerifyMismatch>>,
+    /// CRC32C per chunk taken while writing, for fast verification
+    pub write_checksums: std::sync::Mutex<Option<Vec<u32>>>,
+    /// SHA-256 of the image bytes written by the last flash
+    pub image_sha256: std::sync::Mutex<Option<String>>,
+    /// SHA-256 of the image bytes read back by the last hash verification
+    pub device_sha256: std::sync::Mutex<Option<String>>,
+    /// Hash verification had no write hash and compared against the image
+    pub hash_fallback: AtomicBool,
+    /// Simulated device write speed limit in bytes per second, 0 when off
+    pub write_throttle_bytes_per_sec: AtomicU64,
+    /// Compare each chunk with the device and only write those that differ
+    pub smart_write: AtomicBool,
+    /// Bytes smart write found already on the device and did not write
+    pub skipped_bytes: AtomicU64,
+    /// Seek over all-zero chunks instead of writing them
+    pub skip_zero_blocks: AtomicBool,
+    /// All-zero ranges the last flash did not write, excluded from verification
+    pub zero_ranges: std::sync::Mutex<SkippedRanges>,
+    /// Image written at an offset after the main image, set per flash
+    pub secondary_image: std::sync::Mutex<Option<SecondaryImage>>,
+    /// Stream the next flash reads instead of its image file, see `direct`
+    pub image_stream: std::sync::Mutex<Option<ImageReader>>,
+    /// Rolling speed and ETA of the running write or verify phase
+    pub throughput: std::sync::Mutex<Option<Throughput>>,
     pub error: Mutex<Option<String>>,
 }
 
@@ -34,9 +118,29 @@ impl FlashState {
         Self {
             total_bytes: AtomicU64::new(0),
             written_bytes: AtomicU64::new(0),
+            synced_bytes: AtomicU64::new(0),
             verified_bytes: AtomicU64::new(0),
             is_verifying: AtomicBool::new(false),
             is_cancelled: AtomicBool::new(false),
+            is_paused_for_power: AtomicBool::new(false),
+            write_elapsed_ms: AtomicU64::new(0),
+            verify_elapsed_ms: AtomicU64::new(0),
+            is_read_slow: AtomicBool::new(false),
+            is_stalled: AtomicBool::new(false),
+            stalled_secs: AtomicU64::new(0),
+            verify_mismatch: std::sync::Mutex::new(None),
+            write_checksums: std::sync::Mutex::new(None),
+            image_sha256: std::sync::Mutex::new(None),
+            device_sha256: std::sync::Mutex::new(None),
+            hash_fallback: AtomicBool::new(false),
+            write_throttle_bytes_per_sec: AtomicU64::new(0),
+            smart_write: AtomicBool::new(false),
+            skipped_bytes: AtomicU64::new(0),
+            skip_zero_blocks: AtomicBool::new(false),
+            zero_ranges: std::sync::Mutex::new(SkippedRanges::default()),
+            secondary_image: std::sync::Mutex::new(None),
+            image_stream: std::sync::Mutex::new(None),
+            throughput: std::sync::Mutex::new(None),
             error: Mutex::new(None),
         }
     }
@@ -44,10 +148,158 @@ impl FlashState {
     pub fn reset(&self) {
         self.total_bytes.store(0, Ordering::SeqCst);
         self.written_bytes.store(0, Ordering::SeqCst);
+        self.synced_bytes.store(0, Ordering::SeqCst);
         self.verified_bytes.store(0, Ordering::SeqCst);
         self.is_verifying.store(false, Ordering::SeqCst);
         self.is_cancelled.store(false, Ordering::SeqCst);
+        self.is_paused_for_power.store(false, Ordering::SeqCst);
+        self.write_elapsed_ms.store(0, Ordering::SeqCst);
+        self.verify_elapsed_ms.store(0, Ordering::SeqCst);
+        self.is_read_slow.store(false, Ordering::SeqCst);
+        self.hash_fallback.store(false, Ordering::SeqCst);
+        self.is_stalled.store(false, Ordering::SeqCst);
+        self.stalled_secs.store(0, Ordering::SeqCst);
+        self.skipped_bytes.store(0, Ordering::SeqCst);
+        *self
+            .verify_mismatch
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = None;
+        *self
+            .write_checksums
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = None;
+        *self.image_sha256.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        *self.device_sha256.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        *self.throughput.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        self.set_zero_ranges(SkippedRanges::default());
+    }
+
+    pub fn set_zero_ranges(&self, ranges: SkippedRanges) {
+        *self.zero_ranges.lock().unwrap_or_else(|e| e.into_inner()) = ranges;
+    }
+
+    pub fn zero_ranges(&self) -> SkippedRanges {
+        self.zero_ranges
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Image to write after the main image in the next flash
+    pub fn set_secondary_image(&self, secondary: Option<SecondaryImage>) {
+        *self
+            .secondary_image
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = secondary;
+    }
+
+    pub fn secondary_image(&self) -> Option<SecondaryImage> {
+        self.secondary_image
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Stream to write in the next flash instead of reading the image file
+    pub fn set_image_stream(&self, stream: Option<ImageReader>) {
+        *self.image_stream.lock().unwrap_or_else(|e| e.into_inner()) = stream;
+    }
+
+    /// Whether the next flash writes a stream, which cannot be read again
+    pub fn has_image_stream(&self) -> bool {
+        self.image_stream
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+    }
+
+    /// Open the image to write: the stream set for this flash, else the file
+    pub fn open_image(&self, image_path: &std::path::Path) -> Result<ImageReader, String> {
+        let stream = self
+            .image_stream
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        match stream {
+            Some(stream) => Ok(stream),
+            None => ImageReader::open(image_path),
+        }
     }
+
+    /// Switch progress to the image bytes written once the write is done
+    ///
+    /// Compressed images report progress in archive bytes while writing;
+    /// verification and write checksums cover the image bytes. Called after
+    /// the closing sync, so everything written is also synced.
+    pub fn finish_write(&self, written: u64) {
+        self.total_bytes.store(written, Ordering::SeqCst);
+        self.written_bytes.store(written, Ordering::SeqCst);
+        self.synced_bytes.store(written, Ordering::SeqCst);
+        *self.throughput.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Sample the running phase's progress into `window` and store the
+    /// rolling speed and ETA
+    ///
+    /// Writers and verifiers call this after each chunk with a window of
+    /// their own; parallel verify streams all sample the shared counter.
+    pub fn record_throughput(&self, window: &mut RollingRate) {
+        let done = if self.is_verifying.load(Ordering::SeqCst) {
+            self.verified_bytes.load(Ordering::SeqCst)
+        } else {
+            self.written_bytes.load(Ordering::SeqCst)
+        };
+        let remaining = self.total_bytes.load(Ordering::SeqCst).saturating_sub(done);
+
+        window.record(Instant::now(), done);
+        let throughput = window.rate().map(|bytes_per_sec| Throughput {
+            bytes_per_sec,
+            eta_secs: eta_secs(remaining, bytes_per_sec),
+        });
+        *self.throughput.lock().unwrap_or_else(|e| e.into_inner()) = throughput;
+    }
+
+    /// Current rolling speed and ETA, None until enough samples were taken
+    pub fn throughput(&self) -> Option<Throughput> {
+        *self.throughput.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Write throughput in bytes per second, once writing has started
+    pub fn write_speed(&self) -> Option<f64> {
+        bytes_per_sec(
+            self.written_bytes.load(Ordering::SeqCst),
+            self.write_elapsed_ms.load(Ordering::SeqCst),
+        )
+    }
+
+    /// Read-back throughput in bytes per second, once verification has started
+    pub fn read_speed(&self) -> Option<f64> {
+        bytes_per_sec(
+            self.verified_bytes.load(Ordering::SeqCst),
+            self.verify_elapsed_ms.load(Ordering::SeqCst),
+        )
+    }
+}
+
+/// Rolling throughput of a write or verify phase
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Throughput {
+    /// Progress bytes per second over the last few seconds
+    pub bytes_per_sec: f64,
+    /// Seconds until the phase completes at that speed, None while stalled
+    pub eta_secs: Option<u64>,
+}
+
+/// Sliding window for `FlashState::record_throughput`
+pub fn speed_window() -> RollingRate {
+    RollingRate::new(
+        Duration::from_secs(config::flash::SPEED_WINDOW_SECS),
+        Duration::from_millis(config::flash::SPEED_MIN_SPAN_MS),
+    )
+}
+
+fn bytes_per_sec(bytes: u64, elapsed_ms: u64) -> Option<f64> {
+    (bytes > 0 && elapsed_ms > 0).then(|| bytes as f64 / (elapsed_ms as f64 / 1000.0))
 }
 
 // Re-export the platform-specific flash_image function
@@ -58,6 +310,54 @@ pub use macos::flash_image;
 #[cfg(target_os = "windows")]
 pub use windows::flash_image;
 
+// Re-export the platform-specific verify_device function
+#[cfg(target_os = "linux")]
+pub use linux::verify_device;
+#[cfg(target_os = "macos")]
+pub use macos::verify_device;
+#[cfg(target_os = "windows")]
+pub use windows::verify_device;
+
+// Re-export the platform-specific backup_device function
+#[cfg(target_os = "linux")]
+pub use linux::backup_device;
+#[cfg(target_os = "macos")]
+pub use macos::backup_device;
+#[cfg(target_os = "windows")]
+pub use windows::backup_device;
+
+// Re-export the platform-specific flash_range function
+#[cfg(target_os = "linux")]
+pub use linux::flash_range;
+#[cfg(target_os = "macos")]
+pub use macos::flash_range;
+#[cfg(target_os = "windows")]
+pub use windows::flash_range;
+
+// Re-export the platform-specific erase_device function
+#[cfg(target_os = "linux")]
+pub use linux::erase_device;
+#[cfg(target_os = "macos")]
+pub use macos::erase_device;
+#[cfg(target_os = "windows")]
+pub use windows::erase_device;
+
+// Re-export the platform-specific check_capacity function
+#[cfg(target_os = "linux")]
+pub use linux::check_capacity;
+#[cfg(target_os = "macos")]
+pub use macos::check_capacity;
+#[cfg(target_os = "windows")]
+pub use windows::check_capacity;
+
+// Re-export the platform-specific eject_device function
+#[cfg(target_os = "linux")]
+pub use linux::eject_device;
+#[cfg(target_os = "macos")]
+pub use macos::eject_device;
+#[cfg(target_os = "windows")]
+pub use windows::eject_device;
+
 // Re-export authorization functions
 #[cfg(target_os = "linux")]
 pub use linux::request_authorization;
@@ -78,22 +378,25 @@ pub fn request_authorization(_device_path: &str) -> Result<bool, String> {
 pub(crate) fn unmount_device(device_path: &str) -> Result<(), String> {
     #[cfg(target_os = "macos")]
     {
-        let _ = Command::new("diskutil")
-            .args(["unmountDisk", device_path])
-            .output();
+        use crate::utils::{mount_timeout, run_command};
+
+        let _ = run_command("diskutil", &["unmountDisk", device_path], mount_timeout());
     }
 
     #[cfg(target_os = "linux")]
     {
-        let output = Command::new("lsblk")
-            .args(["-ln", "-o", "NAME", device_path])
-            .output();
+        use crate::utils::{mount_timeout, query_timeout, run_command};
+
+        let output = run_command(
+            "lsblk",
+            &["-ln", "-o", "NAME", device_path],
+            query_timeout(),
+        );
 
         if let Ok(output) = output {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines() {
+            for line in output.stdout.lines() {
                 let part_path = format!("/dev/{}", line.trim());
-                let _ = Command::new("umount").arg(&part_path).output();
+                let _ = run_command("umount", &[&part_path], mount_timeout());
             }
         }
     }
@@ -106,11 +409,35 @@ pub(crate) fn unmount_device(device_path: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Flush the data written through `fd` to the device
+///
+/// A failed sync means earlier writes may not have reached the device, so
+/// callers fail the operation instead of reporting the bytes as synced.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub(crate) fn sync_fd(fd: std::os::unix::io::RawFd) -> Result<(), String> {
+    // A block device has no file metadata worth syncing (macOS lacks fdatasync)
+    #[cfg(target_os = "linux")]
+    let result = unsafe { libc::fdatasync(fd) };
+    #[cfg(target_os = "macos")]
+    let result = unsafe { libc::fsync(fd) };
+
+    if result != 0 {
+        let error = std::io::Error::last_os_error();
+        crate::log_error!("flash", "Device sync failed: {}", error);
+        return Err(format!("Failed to sync device: {}", error));
+    }
+    Ok(())
+}
+
 /// Sync device to ensure all data is written to disk
 #[allow(dead_code)]
 pub(crate) fn sync_device(_device_path: &str) {
     #[cfg(any(target_os = "linux", target_os = "macos"))]
     {
-        let _ = Command::new("sync").output();
+        let _ = crate::utils::run_command(
+            "sync",
+            &[],
+            std::time::Duration::from_secs(crate::config::process::SYNC_TIMEOUT_SECS),
+        );
     }
 }
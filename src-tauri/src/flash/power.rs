@@ -0,0 +1,230 @@
+//! Power source monitoring during flash
+//!
+//! Pauses writing at a synced chunk boundary when the system runs on a
+//! critically low battery, and resumes automatically once AC power returns.
+//! A laptop dying mid-write leaves the card with a half-written image.
+
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::config;
+use crate::{log_info, log_warn};
+
+use super::FlashState;
+
+const MODULE: &str = "flash::power";
+
+/// Current power source state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerStatus {
+    /// True when the system is discharging on battery
+    pub on_battery: bool,
+    /// Remaining battery charge in percent, if known
+    pub battery_percent: Option<u8>,
+}
+
+impl PowerStatus {
+    /// Whether writing should pause at the given battery threshold
+    pub fn is_critical(&self, threshold_percent: u8) -> bool {
+        self.on_battery
+            && self
+                .battery_percent
+                .is_some_and(|percent| percent <= threshold_percent)
+    }
+}
+
+/// Throttled power check called between write chunks
+#[derive(Default)]
+pub struct PowerGuard {
+    last_check: Option<Instant>,
+}
+
+impl PowerGuard {
+    pub fn new() -> Self {
+        Self { last_check: None }
+    }
+
+    /// Check the power source and block while the battery is critical
+    ///
+    /// `sync` is called once before pausing so everything written so far
+    /// reaches the device. Returns an error if that sync fails or the flash is
+    /// cancelled while paused.
+    pub fn check(
+        &mut self,
+        state: &FlashState,
+        sync: impl FnOnce() -> Result<(), String>,
+    ) -> Result<(), String> {
+        let interval = Duration::from_secs(config::flash::POWER_CHECK_INTERVAL_SECS);
+        if self.last_check.is_some_and(|t| t.elapsed() < interval) {
+            return Ok(());
+        }
+        self.last_check = Some(Instant::now());
+
+        let Some(status) = read_power_status() else {
+            return Ok(());
+        };
+        if !status.is_critical(config::flash::POWER_PAUSE_BATTERY_PERCENT) {
+            return Ok(());
+        }
+
+        log_warn!(
+            MODULE,
+            "Battery critical ({:?}%), pausing flash until AC power is connected",
+            status.battery_percent
+        );
+        sync()?;
+        state.is_paused_for_power.store(true, Ordering::SeqCst);
+
+        let result = loop {
+            if state.is_cancelled.load(Ordering::SeqCst) {
+                break Err("Flash cancelled".to_string());
+            }
+            std::thread::sleep(interval);
+            match read_power_status() {
+                Some(status) if status.on_battery => {}
+                _ => break Ok(()),
+            }
+        };
+
+        state.is_paused_for_power.store(false, Ordering::SeqCst);
+        if result.is_ok() {
+            log_info!(MODULE, "AC power restored, resuming flash");
+        }
+        result
+    }
+}
+
+/// Read the current power source (None if it cannot be determined)
+#[cfg(target_os = "linux")]
+pub fn read_power_status() -> Option<PowerStatus> {
+    let entries = std::fs::read_dir("/sys/class/power_supply").ok()?;
+
+    let mut mains_online = false;
+    let mut discharging = false;
+    let mut battery_percent = None;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let read = |name: &str| {
+            std::fs::read_to_string(path.join(name))
+                .map(|s| s.trim().to_string())
+                .ok()
+        };
+
+        match read("type").as_deref() {
+            Some("Mains") | Some("USB") if read("online").as_deref() == Some("1") => {
+                mains_online = true;
+            }
+            Some("Battery") => {
+                // Peripheral batteries (mice, keyboards) report scope "Device"
+                if read("scope").as_deref() == Some("Device") {
+                    continue;
+                }
+                if read("status").as_deref() == Some("Discharging") {
+                    discharging = true;
+                }
+                if let Some(capacity) = read("capacity").and_then(|c| c.parse::<u8>().ok()) {
+                    battery_percent =
+                        Some(battery_percent.map_or(capacity, |p: u8| p.min(capacity)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    battery_percent?;
+    Some(PowerStatus {
+        on_battery: discharging && !mains_online,
+        battery_percent,
+    })
+}
+
+/// Read the current power source (None if it cannot be determined)
+#[cfg(target_os = "macos")]
+pub fn read_power_status() -> Option<PowerStatus> {
+    let output =
+        crate::utils::run_command("pmset", &["-g", "batt"], crate::utils::query_timeout()).ok()?;
+    parse_pmset_output(&output.stdout)
+}
+
+/// Read the current power source (None if it cannot be determined)
+#[cfg(target_os = "windows")]
+pub fn read_power_status() -> Option<PowerStatus> {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return None;
+    }
+
+    // 128 = no system battery, 255 = unknown percentage
+    if status.BatteryFlag == 128 || status.BatteryLifePercent == 255 {
+        return None;
+    }
+
+    Some(PowerStatus {
+        on_battery: status.ACLineStatus == 0,
+        battery_percent: Some(status.BatteryLifePercent),
+    })
+}
+
+/// Parse `pmset -g batt` output
+///
+/// Example: "Now drawing from 'Battery Power'\n -InternalBattery-0 (id=1234)\t8%; discharging; 0:12 remaining"
+#[cfg(any(target_os = "macos", test))]
+fn parse_pmset_output(output: &str) -> Option<PowerStatus> {
+    let on_battery = output.contains("'Battery Power'");
+    let battery_percent = output
+        .lines()
+        .find(|line| line.contains("InternalBattery"))
+        .and_then(|line| line.split('\t').nth(1))
+        .and_then(|rest| rest.split('%').next())
+        .and_then(|p| p.trim().parse::<u8>().ok());
+
+    battery_percent?;
+    Some(PowerStatus {
+        on_battery,
+        battery_percent,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_critical() {
+        let status = |on_battery, battery_percent| PowerStatus {
+            on_battery,
+            battery_percent,
+        };
+        assert!(status(true, Some(5)).is_critical(10));
+        assert!(status(true, Some(10)).is_critical(10));
+        assert!(!status(true, Some(11)).is_critical(10));
+        assert!(!status(false, Some(5)).is_critical(10));
+        assert!(!status(true, None).is_critical(10));
+    }
+
+    #[test]
+    fn test_parse_pmset_output() {
+        let battery = "Now drawing from 'Battery Power'\n -InternalBattery-0 (id=4653155)\t8%; discharging; 0:12 remaining present: true\n";
+        assert_eq!(
+            parse_pmset_output(battery),
+            Some(PowerStatus {
+                on_battery: true,
+                battery_percent: Some(8),
+            })
+        );
+
+        let ac = "Now drawing from 'AC Power'\n -InternalBattery-0 (id=4653155)\t100%; charged; 0:00 remaining present: true\n";
+        assert_eq!(
+            parse_pmset_output(ac),
+            Some(PowerStatus {
+                on_battery: false,
+                battery_percent: Some(100),
+            })
+        );
+
+        assert_eq!(parse_pmset_output("Now drawing from 'AC Power'\n"), None);
+    }
+}
@@ -0,0 +1,190 @@
+//! Privilege status
+//!
+//! Reports how raw device access will be obtained, so the UI can explain the
+//! authentication prompt before the user clicks Write instead of surprising
+//! them with it.
+
+use serde::Serialize;
+
+/// What happens when the user starts writing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WritePrompt {
+    /// Already elevated, no prompt is shown
+    None,
+    /// UDisks2 asks for a password through the polkit agent
+    Polkit,
+    /// macOS authorization dialog (Touch ID or password) for authopen
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    Authorization,
+    /// No way to get device access, writing will fail
+    Unavailable,
+}
+
+/// Privileges of the running process and the helper used for device access
+#[derive(Debug, Clone, Serialize)]
+pub struct PrivilegeStatus {
+    /// Running as root or elevated Administrator
+    pub elevated: bool,
+    /// Helper that opens devices for the app ("udisks2", "authopen")
+    pub helper: Option<String>,
+    pub helper_available: bool,
+    /// A polkit authentication agent is running (Linux only)
+    pub auth_agent_running: Option<bool>,
+    pub write_prompt: WritePrompt,
+}
+
+/// Locations of the UDisks2 daemon across distributions
+#[cfg(target_os = "linux")]
+const UDISKSD_PATHS: &[&str] = &[
+    "/usr/libexec/udisks2/udisksd",
+    "/usr/lib/udisks2/udisksd",
+    "/usr/lib/x86_64-linux-gnu/udisks2/udisksd",
+    "/usr/lib/aarch64-linux-gnu/udisks2/udisksd",
+];
+
+/// Desktop shells that run their own polkit agent
+#[cfg(target_os = "linux")]
+const SHELLS_WITH_AGENT: &[&str] = &["gnome-shell", "cinnamon", "budgie-wm"];
+
+#[cfg(target_os = "linux")]
+pub fn privilege_status() -> PrivilegeStatus {
+    let elevated = unsafe { libc::geteuid() } == 0;
+    let helper_available = UDISKSD_PATHS
+        .iter()
+        .any(|path| std::path::Path::new(path).exists());
+
+    let write_prompt = if elevated {
+        WritePrompt::None
+    } else if helper_available {
+        WritePrompt::Polkit
+    } else {
+        WritePrompt::Unavailable
+    };
+
+    PrivilegeStatus {
+        elevated,
+        helper: Some("udisks2".to_string()),
+        helper_available,
+        auth_agent_running: Some(polkit_agent_running()),
+        write_prompt,
+    }
+}
+
+/// Look for a polkit agent among the running processes
+#[cfg(target_os = "linux")]
+fn polkit_agent_running() -> bool {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return false;
+    };
+
+    entries.flatten().any(|entry| {
+        std::fs::read_to_string(entry.path().join("comm"))
+            .is_ok_and(|comm| is_polkit_agent(comm.trim()))
+    })
+}
+
+/// Whether a process name (as in /proc/PID/comm) belongs to a polkit agent
+#[cfg(target_os = "linux")]
+fn is_polkit_agent(comm: &str) -> bool {
+    // The polkit daemon itself is not an agent
+    (comm.contains("polkit") && comm != "polkitd") || SHELLS_WITH_AGENT.contains(&comm)
+}
+
+#[cfg(target_os = "macos")]
+pub fn privilege_status() -> PrivilegeStatus {
+    let elevated = unsafe { libc::geteuid() } == 0;
+    let helper_available = std::path::Path::new("/usr/libexec/authopen").exists();
+
+    PrivilegeStatus {
+        elevated,
+        helper: Some("authopen".to_string()),
+        helper_available,
+        auth_agent_running: None,
+        // authopen asks for authorization even when running as root
+        write_prompt: if helper_available {
+            WritePrompt::Authorization
+        } else {
+            WritePrompt::Unavailable
+        },
+    }
+}
+
+/// Raw disk access needs the elevation requested by the app manifest
+#[cfg(target_os = "windows")]
+pub fn privilege_status() -> PrivilegeStatus {
+    let elevated = windows_is_elevated();
+
+    PrivilegeStatus {
+        elevated,
+        helper: None,
+        helper_available: false,
+        auth_agent_running: None,
+        write_prompt: if elevated {
+            WritePrompt::None
+        } else {
+            WritePrompt::Unavailable
+        },
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn windows_is_elevated() -> bool {
+    use std::ffi::c_void;
+    use windows_sys::Win32::Foundation::CloseHandle;
+
+    const TOKEN_QUERY: u32 = 0x0008;
+    const TOKEN_ELEVATION_CLASS: u32 = 20;
+
+    extern "system" {
+        fn GetCurrentProcess() -> *mut c_void;
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn OpenProcessToken(process: *mut c_void, access: u32, token: *mut *mut c_void) -> i32;
+        fn GetTokenInformation(
+            token: *mut c_void,
+            class: u32,
+            info: *mut c_void,
+            info_len: u32,
+            return_len: *mut u32,
+        ) -> i32;
+    }
+
+    let mut token: *mut c_void = std::ptr::null_mut();
+    if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+        return false;
+    }
+
+    // TOKEN_ELEVATION is a single DWORD
+    let mut is_elevated: u32 = 0;
+    let mut return_len = 0u32;
+    let result = unsafe {
+        GetTokenInformation(
+            token,
+            TOKEN_ELEVATION_CLASS,
+            &mut is_elevated as *mut u32 as *mut c_void,
+            std::mem::size_of::<u32>() as u32,
+            &mut return_len,
+        )
+    };
+    unsafe { CloseHandle(token) };
+
+    result != 0 && is_elevated != 0
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_polkit_agent() {
+        assert!(is_polkit_agent("polkit-gnome-au"));
+        assert!(is_polkit_agent("polkit-kde-auth"));
+        assert!(is_polkit_agent("lxpolkit"));
+        assert!(is_polkit_agent("gnome-shell"));
+        assert!(!is_polkit_agent("polkitd"));
+        assert!(!is_polkit_agent("bash"));
+    }
+}
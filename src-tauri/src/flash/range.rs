@@ -0,0 +1,225 @@
+//! Byte-range writes
+//!
+//! Updating only the bootloader of a card, e.g. u-boot at 32 KiB on Rockchip
+//! or 8 KiB on Allwinner, needs a blob written at a fixed offset while the
+//! rest of the card stays untouched. Offsets and blob sizes are rarely
+//! sector-aligned, and raw device handles only accept aligned I/O, so the
+//! covering aligned region is read, patched and written back whole.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+use crate::config;
+use crate::log_info;
+use crate::utils::MB;
+
+use super::verify::find_mismatch;
+use super::FlashState;
+
+const MODULE: &str = "flash::range";
+
+/// Alignment of device I/O, so raw/unbuffered device handles work
+const ALIGNMENT: u64 = 4096;
+
+/// Blob to write at a byte offset of a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeWrite {
+    pub input: PathBuf,
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
+
+impl RangeWrite {
+    /// Read `length` bytes from the start of `input`, the whole file if None,
+    /// and check that they fit on the device at `offset`
+    pub fn from_file(
+        input: &Path,
+        offset: u64,
+        length: Option<u64>,
+        device_size: u64,
+    ) -> Result<Self, String> {
+        let file_size = std::fs::metadata(input)
+            .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?
+            .len();
+        let length = length.unwrap_or(file_size);
+        check_range(offset, length, file_size, device_size)?;
+
+        let mut data = Vec::with_capacity(length as usize);
+        File::open(input)
+            .and_then(|file| file.take(length).read_to_end(&mut data))
+            .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+        if data.len() as u64 != length {
+            return Err(format!(
+                "{} ended after {} of {} bytes",
+                input.display(),
+                data.len(),
+                length
+            ));
+        }
+
+        Ok(Self {
+            input: input.to_path_buf(),
+            offset,
+            data,
+        })
+    }
+
+    /// Aligned region of the device covering the range, as (start, length)
+    fn aligned_region(&self) -> (u64, u64) {
+        let start = self.offset / ALIGNMENT * ALIGNMENT;
+        let end = (self.offset + self.data.len() as u64).div_ceil(ALIGNMENT) * ALIGNMENT;
+        (start, end - start)
+    }
+
+    /// Write the blob, leaving the bytes around it in the region as they were
+    pub fn write<D: Read + Write + Seek>(
+        &self,
+        device: &mut D,
+        state: &FlashState,
+    ) -> Result<(), String> {
+        let (start, len) = self.aligned_region();
+        log_info!(
+            MODULE,
+            "Writing {} bytes from {} at byte {} (region {}+{})",
+            self.data.len(),
+            self.input.display(),
+            self.offset,
+            start,
+            len
+        );
+        state.total_bytes.store(len, Ordering::SeqCst);
+
+        let mut region = read_region(device, start, len)?;
+        let patch_start = (self.offset - start) as usize;
+        region[patch_start..patch_start + self.data.len()].copy_from_slice(&self.data);
+
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Err("Flash cancelled".to_string());
+        }
+        device
+            .seek(SeekFrom::Start(start))
+            .map_err(|e| format!("Failed to seek to byte {}: {}", start, e))?;
+        device
+            .write_all(&region)
+            .map_err(|e| format!("Failed to write at byte {}: {}", start, e))?;
+        device.flush().ok();
+
+        state.written_bytes.store(len, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Read the range back and compare it with the blob
+    pub fn verify<D: Read + Seek>(&self, device: &mut D, state: &FlashState) -> Result<(), String> {
+        let (start, len) = self.aligned_region();
+        state.total_bytes.store(len, Ordering::SeqCst);
+
+        let region = read_region(device, start, len)?;
+        let patch_start = (self.offset - start) as usize;
+        let actual = &region[patch_start..patch_start + self.data.len()];
+        if let Some(mismatch) = find_mismatch(self.offset, &self.data, actual) {
+            let offset = mismatch.offset;
+            *state
+                .verify_mismatch
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()) = Some(mismatch);
+            return Err(format!(
+                "Verification failed: device differs from {} at byte {}",
+                self.input.display(),
+                offset
+            ));
+        }
+
+        state.verified_bytes.store(len, Ordering::SeqCst);
+        log_info!(
+            MODULE,
+            "{} bytes at byte {} verified",
+            self.data.len(),
+            self.offset
+        );
+        Ok(())
+    }
+}
+
+/// Check that `length` bytes of a `file_size`-byte input fit at `offset`
+fn check_range(offset: u64, length: u64, file_size: u64, device_size: u64) -> Result<(), String> {
+    if length == 0 {
+        return Err("Nothing to write: the length is zero".to_string());
+    }
+    if length > file_size {
+        return Err(format!(
+            "The input file has {} bytes, fewer than the {} to write",
+            file_size, length
+        ));
+    }
+    if length > config::flash::RANGE_WRITE_MAX_SIZE {
+        return Err(format!(
+            "Range writes are limited to {} MB, flash a full image instead",
+            config::flash::RANGE_WRITE_MAX_SIZE / MB
+        ));
+    }
+    if offset.saturating_add(length) > device_size {
+        return Err(format!(
+            "The range ends at byte {}, past the end of the device ({} bytes)",
+            offset.saturating_add(length),
+            device_size
+        ));
+    }
+    Ok(())
+}
+
+fn read_region<D: Read + Seek>(device: &mut D, start: u64, len: u64) -> Result<Vec<u8>, String> {
+    let mut region = vec![0u8; len as usize];
+    device
+        .seek(SeekFrom::Start(start))
+        .map_err(|e| format!("Failed to seek to byte {}: {}", start, e))?;
+    device
+        .read_exact(&mut region)
+        .map_err(|e| format!("Failed to read device at byte {}: {}", start, e))?;
+    Ok(region)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_check_range() {
+        assert!(check_range(32 * 1024, 1000, 1000, 8 * MB).is_ok());
+        assert!(check_range(32 * 1024, 0, 1000, 8 * MB).is_err());
+        assert!(check_range(32 * 1024, 2000, 1000, 8 * MB).is_err());
+        assert!(check_range(8 * MB - 10, 1000, 1000, 8 * MB).is_err());
+        let too_big = config::flash::RANGE_WRITE_MAX_SIZE + 1;
+        assert!(check_range(0, too_big, too_big, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_unaligned_write_keeps_surroundings() {
+        let mut device = Cursor::new(vec![0xaau8; 4 * ALIGNMENT as usize]);
+        let range = RangeWrite {
+            input: PathBuf::from("u-boot.bin"),
+            offset: ALIGNMENT + 100,
+            data: vec![1u8; ALIGNMENT as usize],
+        };
+        let state = FlashState::new();
+        assert_eq!(range.aligned_region(), (ALIGNMENT, 2 * ALIGNMENT));
+
+        range.write(&mut device, &state).unwrap();
+        range.verify(&mut device, &state).unwrap();
+
+        let bytes = device.get_ref();
+        let end = (ALIGNMENT + 100) as usize + ALIGNMENT as usize;
+        assert!(bytes[..(ALIGNMENT + 100) as usize]
+            .iter()
+            .all(|&b| b == 0xaa));
+        assert!(bytes[(ALIGNMENT + 100) as usize..end]
+            .iter()
+            .all(|&b| b == 1));
+        assert!(bytes[end..].iter().all(|&b| b == 0xaa));
+
+        device.get_mut()[end - 1] = 0;
+        assert!(range.verify(&mut device, &state).is_err());
+    }
+}
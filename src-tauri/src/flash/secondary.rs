@@ -0,0 +1,240 @@
+//! Second image written at an offset
+//!
+//! For testing boot + data layouts, a raw partition image (e.g. a data
+//! filesystem) can be written after the main image in the same operation.
+//! Its offset is validated against both images and the device before
+//! anything is written, and it is read back with the main image when
+//! verification is on.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+use crate::config;
+use crate::log_info;
+use crate::utils::MB;
+
+use super::image_source::{image_size, ImageReader};
+use super::verify::find_mismatch;
+use super::FlashState;
+
+const MODULE: &str = "flash::secondary";
+
+/// Granularity of the secondary image size, so raw/unbuffered I/O works
+const SIZE_ALIGNMENT: u64 = 4096;
+
+/// Image written at `offset` after the main image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecondaryImage {
+    pub path: PathBuf,
+    pub offset: u64,
+    /// Bytes written, after decompression
+    pub size: u64,
+}
+
+impl SecondaryImage {
+    /// Check a secondary image layout against the main image and the device
+    pub fn new(
+        path: &Path,
+        offset: u64,
+        primary_size: u64,
+        device_size: u64,
+    ) -> Result<Self, String> {
+        let size = image_size(path)?;
+        check_layout(offset, size, primary_size, device_size)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            offset,
+            size,
+        })
+    }
+
+    /// Write the image at its offset, after the main image was written
+    ///
+    /// Progress continues from the main image's counters.
+    pub fn write(&self, device: &mut File, state: &FlashState) -> Result<(), String> {
+        log_info!(
+            MODULE,
+            "Writing {} ({} MB) at byte {}",
+            self.path.display(),
+            self.size / MB,
+            self.offset
+        );
+        let base = state.written_bytes.load(Ordering::SeqCst);
+        state.total_bytes.store(base + self.size, Ordering::SeqCst);
+
+        let mut image = ImageReader::open(&self.path)?;
+        device
+            .seek(SeekFrom::Start(self.offset))
+            .map_err(|e| format!("Failed to seek to byte {}: {}", self.offset, e))?;
+
+        let chunk_size = config::flash::CHUNK_SIZE;
+        let mut buffer = vec![0u8; chunk_size];
+        let mut written: u64 = 0;
+        while written < self.size {
+            if state.is_cancelled.load(Ordering::SeqCst) {
+                return Err("Flash cancelled".to_string());
+            }
+            // Whole chunks keep writes sector-aligned for raw device handles
+            let len = std::cmp::min(chunk_size as u64, self.size - written) as usize;
+            image
+                .read_exact(&mut buffer[..len])
+                .map_err(|e| format!("Failed to read {}: {}", self.path.display(), e))?;
+            device
+                .write_all(&buffer[..len])
+                .map_err(|e| format!("Failed to write at byte {}: {}", self.offset + written, e))?;
+            written += len as u64;
+            state.written_bytes.store(base + written, Ordering::SeqCst);
+        }
+
+        device.flush().ok();
+        Ok(())
+    }
+
+    /// Compare the device with the image at its offset
+    ///
+    /// Runs after the main image was verified; progress continues from its
+    /// counters.
+    pub fn verify(&self, device: &mut File, state: &FlashState) -> Result<(), String> {
+        let base = state.verified_bytes.load(Ordering::SeqCst);
+        state.total_bytes.store(base + self.size, Ordering::SeqCst);
+
+        let mut image = ImageReader::open(&self.path)?;
+        device
+            .seek(SeekFrom::Start(self.offset))
+            .map_err(|e| format!("Failed to seek to byte {}: {}", self.offset, e))?;
+
+        let chunk_size = config::flash::CHUNK_SIZE;
+        let mut expected = vec![0u8; chunk_size];
+        let mut actual = vec![0u8; chunk_size];
+        let mut checked: u64 = 0;
+        while checked < self.size {
+            if state.is_cancelled.load(Ordering::SeqCst) {
+                return Err("Verification cancelled".to_string());
+            }
+            let len = std::cmp::min(chunk_size as u64, self.size - checked) as usize;
+            image
+                .read_exact(&mut expected[..len])
+                .map_err(|e| format!("Failed to read {}: {}", self.path.display(), e))?;
+            device.read_exact(&mut actual[..len]).map_err(|e| {
+                format!(
+                    "Failed to read device at byte {}: {}",
+                    self.offset + checked,
+                    e
+                )
+            })?;
+
+            if let Some(mut mismatch) =
+                find_mismatch(self.offset + checked, &expected[..len], &actual[..len])
+            {
+                mismatch.region = Some("secondary image".to_string());
+                let offset = mismatch.offset;
+                *state
+                    .verify_mismatch
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner()) = Some(mismatch);
+                return Err(format!(
+                    "Verification failed: secondary image differs at byte {}",
+                    offset
+                ));
+            }
+            checked += len as u64;
+            state.verified_bytes.store(base + checked, Ordering::SeqCst);
+        }
+
+        log_info!(MODULE, "Secondary image verified");
+        Ok(())
+    }
+}
+
+/// Check that a `size`-byte image at `offset` fits between the main image
+/// and the end of the device
+fn check_layout(offset: u64, size: u64, primary_size: u64, device_size: u64) -> Result<(), String> {
+    let alignment = config::flash::SECONDARY_ALIGNMENT;
+    if offset % alignment != 0 {
+        return Err(format!(
+            "The secondary image offset must be a multiple of {} MB",
+            alignment / MB
+        ));
+    }
+    if offset < primary_size {
+        return Err(format!(
+            "The secondary image at byte {} would overwrite the end of the main image ({} bytes)",
+            offset, primary_size
+        ));
+    }
+    if size == 0 || size % SIZE_ALIGNMENT != 0 {
+        return Err(format!(
+            "The secondary image size must be a non-zero multiple of {} bytes",
+            SIZE_ALIGNMENT
+        ));
+    }
+    if offset.saturating_add(size) > device_size {
+        return Err(format!(
+            "The secondary image ends at byte {}, past the end of the device ({} bytes)",
+            offset.saturating_add(size),
+            device_size
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_layout() {
+        let gb = 1024 * MB;
+        assert!(check_layout(4 * gb, 512 * MB, 3 * gb, 8 * gb).is_ok());
+        // Right after the main image, ending exactly at the device end
+        assert!(check_layout(3 * gb, 5 * gb, 3 * gb, 8 * gb).is_ok());
+
+        // Unaligned offset
+        assert!(check_layout(4 * gb + 512, 512 * MB, 3 * gb, 8 * gb).is_err());
+        // Overlaps the main image
+        assert!(check_layout(2 * gb, 512 * MB, 3 * gb, 8 * gb).is_err());
+        // Doesn't fit on the device
+        assert!(check_layout(4 * gb, 5 * gb, 3 * gb, 8 * gb).is_err());
+        // Partial sector
+        assert!(check_layout(4 * gb, 512 * MB + 100, 3 * gb, 8 * gb).is_err());
+    }
+
+    #[test]
+    fn test_write_and_verify() {
+        let dir = std::env::temp_dir().join(format!("secondary-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let data: Vec<u8> = (0..3 * SIZE_ALIGNMENT).map(|i| (i % 7) as u8).collect();
+        let image_path = dir.join("data.img");
+        std::fs::write(&image_path, &data).unwrap();
+        let device_path = dir.join("device");
+        std::fs::write(&device_path, vec![0u8; 4 * MB as usize]).unwrap();
+
+        let secondary = SecondaryImage {
+            path: image_path,
+            offset: MB,
+            size: data.len() as u64,
+        };
+        let state = FlashState::new();
+        let mut device = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&device_path)
+            .unwrap();
+        secondary.write(&mut device, &state).unwrap();
+        secondary.verify(&mut device, &state).unwrap();
+
+        let written = std::fs::read(&device_path).unwrap();
+        assert_eq!(&written[MB as usize..MB as usize + data.len()], &data[..]);
+
+        // A changed byte on the device is reported with its offset
+        device.seek(SeekFrom::Start(MB + 5)).unwrap();
+        device.write_all(&[0xff]).unwrap();
+        let err = secondary.verify(&mut device, &state).unwrap_err();
+        assert!(err.contains(&(MB + 5).to_string()), "{}", err);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,243 @@
+//! Differential writing
+//!
+//! Re-flashing a card that already holds most of the image, e.g. while
+//! iterating on a kernel, rewrites gigabytes of identical data. With smart
+//! write enabled each chunk is first read from the device and only written
+//! if it differs, which is faster on cards that read quicker than they write
+//! and saves flash wear. Verification is unaffected: it still reads back
+//! every byte after the write.
+//!
+//! A chunk that cannot be read back, e.g. an unaligned tail on a raw
+//! device, is simply written.
+//!
+//! Separately, all-zero chunks need not be written where the device already
+//! reads zeros: Armbian images hold large empty regions, and skipping them
+//! saves most of the write time and wear. After the device was zeroed (see
+//! `SmartWriter::set_zeroed`) they are seeked over; otherwise each is read
+//! back first and written unless it already is all zeros. Either way the
+//! device ends up holding the image, so verification compares every byte.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::log_info;
+use crate::utils::MB;
+
+const MODULE: &str = "flash::smart_write";
+
+/// Device ranges left unwritten because the image was all zeros there
+///
+/// Ranges are added in ascending order, adjacent ones are merged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SkippedRanges(Vec<(u64, u64)>);
+
+impl SkippedRanges {
+    /// Record `len` bytes from `start` as skipped
+    pub fn add(&mut self, start: u64, len: u64) {
+        match self.0.last_mut() {
+            Some((_, end)) if *end == start => *end += len,
+            _ => self.0.push((start, start + len)),
+        }
+    }
+
+    /// Total bytes skipped
+    pub fn total(&self) -> u64 {
+        self.0.iter().map(|(start, end)| end - start).sum()
+    }
+}
+
+/// Whether every byte is zero
+fn is_zero(data: &[u8]) -> bool {
+    // OR-folding fixed blocks vectorizes, unlike a short-circuiting scan
+    data.chunks(4096)
+        .all(|block| block.iter().fold(0u8, |acc, &b| acc | b) == 0)
+}
+
+/// Writes image chunks, skipping those the device already holds
+#[derive(Debug)]
+pub struct SmartWriter {
+    /// Buffer for the device's current content, None when disabled
+    compare: Option<Vec<u8>>,
+    skipped: u64,
+    skip_zeros: bool,
+    /// The whole device was zeroed, so zero chunks need no read-back
+    zeroed: bool,
+    /// Buffer for reading back zero chunks
+    readback: Vec<u8>,
+    zero_ranges: SkippedRanges,
+}
+
+impl SmartWriter {
+    /// `enabled` turns on comparing with the device, `skip_zeros` skipping
+    /// all-zero chunks the device already reads as zeros
+    pub fn new(enabled: bool, skip_zeros: bool) -> Self {
+        Self {
+            compare: enabled.then(Vec::new),
+            skipped: 0,
+            skip_zeros,
+            zeroed: false,
+            readback: Vec::new(),
+            zero_ranges: SkippedRanges::default(),
+        }
+    }
+
+    /// Record that a zeroing operation succeeded on the whole device
+    ///
+    /// Only an operation that guarantees zeros counts, such as BLKZEROOUT;
+    /// a discard may leave old data readable.
+    pub fn set_zeroed(&mut self, zeroed: bool) {
+        self.zeroed = zeroed;
+    }
+
+    /// Whether zero chunks are skipped where the device reads zeros
+    pub fn skips_zeros(&self) -> bool {
+        self.skip_zeros
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.compare.is_some()
+    }
+
+    /// Bytes that were already on the device and not written
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// All-zero ranges that were not written
+    pub fn zero_ranges(&self) -> &SkippedRanges {
+        &self.zero_ranges
+    }
+
+    /// Write `data` at the device's current position unless it is already
+    /// there or, with zero skipping, is all zeros and so is the device
+    ///
+    /// Either way the position ends up after `data`.
+    pub fn write<D: Read + Write + Seek>(&mut self, device: &mut D, data: &[u8]) -> io::Result<()> {
+        if self.skip_zeros && is_zero(data) {
+            let start = device.stream_position()?;
+            if self.zeroed {
+                device.seek(SeekFrom::Current(data.len() as i64))?;
+                self.zero_ranges.add(start, data.len() as u64);
+                return Ok(());
+            }
+            self.readback.resize(data.len(), 0);
+            if matches!(read_full(device, &mut self.readback), Ok(n) if n == data.len() && is_zero(&self.readback))
+            {
+                self.zero_ranges.add(start, data.len() as u64);
+                return Ok(());
+            }
+            device.seek(SeekFrom::Start(start))?;
+            return device.write_all(data);
+        }
+
+        let Some(compare) = self.compare.as_mut() else {
+            return device.write_all(data);
+        };
+
+        let start = device.stream_position()?;
+        compare.resize(data.len(), 0);
+        if matches!(read_full(device, compare), Ok(n) if n == data.len() && compare[..] == *data) {
+            self.skipped += data.len() as u64;
+            return Ok(());
+        }
+
+        device.seek(SeekFrom::Start(start))?;
+        device.write_all(data)
+    }
+}
+
+/// Log how much of the `written` bytes smart write and zero skipping skipped
+pub fn log_skipped(writer: &SmartWriter, written: u64) {
+    if writer.is_enabled() {
+        log_info!(
+            MODULE,
+            "Smart write: {} of {} MB were already on the device",
+            writer.skipped() / MB,
+            written / MB
+        );
+    }
+    if writer.skip_zeros {
+        log_info!(
+            MODULE,
+            "Zero skipping: {} of {} MB were empty and not written",
+            writer.zero_ranges.total() / MB,
+            written / MB
+        );
+    }
+}
+
+/// Fill `buf` unless the end of the device comes first; returns the bytes read
+fn read_full(device: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match device.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_smart_write_skips_identical_chunks() {
+        let mut device = Cursor::new(vec![1u8; 12]);
+        let mut writer = SmartWriter::new(true, false);
+
+        writer.write(&mut device, &[1, 1, 1, 1]).unwrap();
+        writer.write(&mut device, &[2, 2, 2, 2]).unwrap();
+        writer.write(&mut device, &[1, 1, 1, 1]).unwrap();
+        // Runs past the end of the device, so it can't match
+        writer.write(&mut device, &[1, 1]).unwrap();
+
+        assert_eq!(writer.skipped(), 8);
+        assert_eq!(
+            device.into_inner(),
+            vec![1, 1, 1, 1, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1]
+        );
+    }
+
+    #[test]
+    fn test_disabled_writes_everything() {
+        let mut device = Cursor::new(vec![1u8; 4]);
+        let mut writer = SmartWriter::new(false, false);
+        writer.write(&mut device, &[1, 1, 1, 1]).unwrap();
+        assert_eq!(writer.skipped(), 0);
+        assert!(!writer.is_enabled());
+    }
+
+    #[test]
+    fn test_zero_chunks_are_skipped_only_where_the_device_is_zero() {
+        let mut device = Cursor::new(vec![9, 9, 9, 9, 0, 0, 0, 0, 9, 9, 9, 9]);
+        let mut writer = SmartWriter::new(false, true);
+        writer.write(&mut device, &[1, 1, 1, 1]).unwrap();
+        writer.write(&mut device, &[0, 0, 0, 0]).unwrap();
+        writer.write(&mut device, &[0, 0]).unwrap();
+        writer.write(&mut device, &[2, 2]).unwrap();
+
+        // Old data where the image is empty is overwritten with zeros
+        assert_eq!(
+            device.into_inner(),
+            vec![1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 2, 2]
+        );
+        let ranges = writer.zero_ranges();
+        assert_eq!(ranges, &SkippedRanges(vec![(4, 8)]));
+        assert_eq!(ranges.total(), 4);
+    }
+
+    #[test]
+    fn test_zeroed_device_is_seeked_over() {
+        let mut device = Cursor::new(vec![0u8; 8]);
+        let mut writer = SmartWriter::new(false, true);
+        writer.set_zeroed(true);
+        writer.write(&mut device, &[0, 0, 0, 0]).unwrap();
+        writer.write(&mut device, &[3, 3, 3, 3]).unwrap();
+        assert_eq!(device.into_inner(), vec![0, 0, 0, 0, 3, 3, 3, 3]);
+        assert_eq!(writer.zero_ranges().total(), 4);
+    }
+}
@@ -0,0 +1,156 @@
+//! Wrap-around fake detection
+//!
+//! Verification compares exactly the image bytes, so space after the image
+//! is never looked at. Counterfeit cards report more capacity than they have
+//! and silently wrap writes past the real end back to the start. With the
+//! "full" erase mode, the region right after the image is zeroed while
+//! writing and read back after verification: on a wrap-around fake either
+//! the zeros clobber the image start or the region reads back non-zero.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::atomic::Ordering;
+
+use crate::config;
+use crate::log_info;
+
+use super::verify::{find_mismatch, VerifyMismatch};
+use super::FlashState;
+
+const MODULE: &str = "flash::tail_check";
+
+/// Alignment of the checked region, so raw/unbuffered device I/O works
+const ALIGNMENT: u64 = 4096;
+
+/// Region after the image that is zeroed while writing and checked on read-back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TailCheck {
+    /// First byte of the region (image size rounded up to `ALIGNMENT`)
+    pub start: u64,
+    pub len: u64,
+}
+
+impl TailCheck {
+    /// Region to check after an image on a device, None if there's no room
+    pub fn for_device(image_size: u64, device_size: u64) -> Option<Self> {
+        let start = image_size.div_ceil(ALIGNMENT) * ALIGNMENT;
+        let room = device_size.saturating_sub(start);
+        let len = std::cmp::min(config::flash::TAIL_CHECK_SIZE, room / ALIGNMENT * ALIGNMENT);
+        (len > 0).then_some(Self { start, len })
+    }
+
+    /// Zero the region after the image has been written
+    pub fn write_zeros(&self, device: &mut File) -> Result<(), String> {
+        log_info!(
+            MODULE,
+            "Zeroing {} MB after the image at byte {}",
+            self.len / (1024 * 1024),
+            self.start
+        );
+
+        device
+            .seek(SeekFrom::Start(self.start))
+            .map_err(|e| format!("Failed to seek past image: {}", e))?;
+
+        let zero_buffer = vec![0u8; config::flash::ERASE_CHUNK_SIZE];
+        let mut written: u64 = 0;
+        while written < self.len {
+            let to_write = std::cmp::min(zero_buffer.len() as u64, self.len - written) as usize;
+            device.write_all(&zero_buffer[..to_write]).map_err(|e| {
+                format!(
+                    "Failed to zero byte {} after image: {}",
+                    self.start + written,
+                    e
+                )
+            })?;
+            written += to_write as u64;
+        }
+
+        device.flush().ok();
+        Ok(())
+    }
+
+    /// Check that the region reads back as zeros
+    pub fn verify(&self, device: &mut File, state: &FlashState) -> Result<(), String> {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Err("Verification cancelled".to_string());
+        }
+
+        device
+            .seek(SeekFrom::Start(self.start))
+            .map_err(|e| format!("Failed to seek past image: {}", e))?;
+
+        let mut buffer = vec![0u8; config::flash::ERASE_CHUNK_SIZE];
+        let mut checked: u64 = 0;
+        while checked < self.len {
+            let to_read = std::cmp::min(buffer.len() as u64, self.len - checked) as usize;
+            device.read_exact(&mut buffer[..to_read]).map_err(|e| {
+                format!(
+                    "Failed to read byte {} after image: {}",
+                    self.start + checked,
+                    e
+                )
+            })?;
+
+            if let Some(mismatch) = find_nonzero(self.start + checked, &buffer[..to_read]) {
+                let offset = mismatch.offset;
+                *state
+                    .verify_mismatch
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner()) = Some(mismatch);
+                return Err(format!(
+                    "Verification failed: zeroed space after the image is not empty at byte {}, the device may report a fake capacity",
+                    offset
+                ));
+            }
+            checked += to_read as u64;
+        }
+
+        log_info!(
+            MODULE,
+            "{} MB after the image read back as zeros",
+            self.len / (1024 * 1024)
+        );
+        Ok(())
+    }
+}
+
+/// Describe the first non-zero byte of a chunk that should be all zeros
+fn find_nonzero(chunk_offset: u64, data: &[u8]) -> Option<VerifyMismatch> {
+    let zeros = vec![0u8; data.len()];
+    let mut mismatch = find_mismatch(chunk_offset, &zeros, data)?;
+    mismatch.region = Some("zeroed space after the image".to_string());
+    Some(mismatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tail_check_region() {
+        let mb = 1024 * 1024;
+
+        // Starts at the next aligned byte after the image
+        let tail = TailCheck::for_device(1000 * mb + 1, 8000 * mb).unwrap();
+        assert_eq!(tail.start, 1000 * mb + ALIGNMENT);
+        assert_eq!(tail.len, config::flash::TAIL_CHECK_SIZE);
+
+        // Limited by the space left on the device
+        let tail = TailCheck::for_device(1000 * mb, 1001 * mb).unwrap();
+        assert_eq!(tail.len, mb);
+
+        // No room after the image
+        assert!(TailCheck::for_device(1000 * mb, 1000 * mb).is_none());
+    }
+
+    #[test]
+    fn test_find_nonzero() {
+        assert!(find_nonzero(0, &[0, 0, 0]).is_none());
+
+        let mismatch = find_nonzero(4096, &[0, 0, 7, 0, 9]).unwrap();
+        assert_eq!(mismatch.offset, 4098);
+        assert_eq!(mismatch.actual, Some(7));
+        assert_eq!(mismatch.differing_bytes, 2);
+    }
+}
@@ -7,17 +7,271 @@
 
 use crate::config;
 use crate::utils::{bytes_to_gb, ProgressTracker};
-use crate::{log_error, log_info};
+use crate::{log_debug, log_error, log_info, log_warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fs::File;
 use std::io::Read;
-use std::path::PathBuf;
-use std::sync::atomic::Ordering;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-use super::FlashState;
+use super::image_layout::describe_image_offset;
+use super::image_source::{is_streamed, ImageReader};
+use super::tail_check::TailCheck;
+use super::{speed_window, FlashState};
 
 const MODULE: &str = "flash::verify";
 
+/// Details of the first mismatching chunk found during verification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyMismatch {
+    /// Absolute byte offset of the first differing byte
+    pub offset: u64,
+    /// Byte expected from the image at `offset`
+    pub expected: Option<u8>,
+    /// Byte read back from the device at `offset` (None if the read came up short)
+    pub actual: Option<u8>,
+    /// Number of differing (or missing) bytes in the chunk
+    pub differing_bytes: u64,
+    /// Size of the compared chunk
+    pub chunk_size: u64,
+    /// Absolute offset of the first byte of the hex context windows
+    #[serde(default)]
+    pub context_offset: u64,
+    /// Hex of up to `MISMATCH_CONTEXT_BYTES` image bytes around the mismatch
+    #[serde(default)]
+    pub expected_hex: String,
+    /// Hex of the device bytes read back at the same offsets
+    #[serde(default)]
+    pub actual_hex: String,
+    /// Region of the image the mismatch falls in (e.g., "partition 2 (Linux)")
+    #[serde(default)]
+    pub region: Option<String>,
+}
+
+/// Bytes of context captured around a mismatch
+const MISMATCH_CONTEXT_BYTES: usize = 64;
+
+/// How read-back data is checked (the "verify_mode" setting)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifyMode {
+    /// Byte-compare every chunk against the image
+    #[default]
+    Full,
+    /// Compare CRC32C of every chunk against checksums taken while writing
+    Fast,
+    /// Compare the SHA-256 of the read-back data with the one taken while writing
+    Hash,
+}
+
+/// How written data is read back for verification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyOptions {
+    /// Number of concurrent readers (1 = sequential)
+    pub streams: usize,
+    pub mode: VerifyMode,
+    /// Region after the image zeroed while writing and checked on read-back
+    pub tail_check: Option<TailCheck>,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            streams: 1,
+            mode: VerifyMode::Full,
+            tail_check: None,
+        }
+    }
+}
+
+impl VerifyOptions {
+    /// Pick verification options for a target device
+    ///
+    /// Only non-rotational NVMe/USB devices are read with several streams;
+    /// SD cards and spinning disks don't gain from it and seek-thrash instead.
+    pub fn for_device(device_path: &str, device_class: &str) -> Self {
+        let fast_class = config::flash::PARALLEL_VERIFY_CLASSES.contains(&device_class);
+        let parallel = match is_rotational(device_path) {
+            Some(rotational) => fast_class && !rotational,
+            // Without rotation info, only trust NVMe to be solid state
+            None => device_class == "nvme",
+        };
+
+        if parallel {
+            Self {
+                streams: config::flash::PARALLEL_VERIFY_STREAMS,
+                ..Self::default()
+            }
+        } else {
+            Self::default()
+        }
+    }
+}
+
+/// Whether the device reports rotational media (None if unknown)
+#[cfg(target_os = "linux")]
+fn is_rotational(device_path: &str) -> Option<bool> {
+    let name = device_path.strip_prefix("/dev/")?;
+    let value = std::fs::read_to_string(format!("/sys/block/{}/queue/rotational", name)).ok()?;
+    Some(value.trim() == "1")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_rotational(_device_path: &str) -> Option<bool> {
+    None
+}
+
+/// CRC32C of each `CHUNK_SIZE` chunk of the written data
+///
+/// Fed with whatever the write loop writes, regardless of how reads split it,
+/// so checksum `i` always covers bytes `i * CHUNK_SIZE..(i + 1) * CHUNK_SIZE`.
+#[derive(Debug, Default)]
+pub struct ChunkChecksums {
+    checksums: Vec<u32>,
+    current: u32,
+    filled: usize,
+}
+
+impl ChunkChecksums {
+    /// Start collecting checksums if the verify options need them
+    pub fn for_options(verify: bool, options: VerifyOptions) -> Option<Self> {
+        (verify && options.mode == VerifyMode::Fast).then(Self::default)
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        let chunk_size = config::flash::CHUNK_SIZE;
+        while !data.is_empty() {
+            let take = std::cmp::min(chunk_size - self.filled, data.len());
+            self.current = crc32c::crc32c_append(self.current, &data[..take]);
+            self.filled += take;
+            data = &data[take..];
+            if self.filled == chunk_size {
+                self.checksums.push(self.current);
+                self.current = 0;
+                self.filled = 0;
+            }
+        }
+    }
+
+    /// Hand the checksums to the verifier
+    pub fn finish(mut self, state: &FlashState) {
+        if self.filled > 0 {
+            self.checksums.push(self.current);
+        }
+        *state
+            .write_checksums
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(self.checksums);
+    }
+}
+
+/// Checksums taken while writing, if they cover the whole image
+pub fn write_checksums(state: &FlashState, image_size: u64) -> Option<Vec<u32>> {
+    let chunks = image_size.div_ceil(config::flash::CHUNK_SIZE as u64) as usize;
+    state
+        .write_checksums
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .filter(|checksums| checksums.len() == chunks)
+}
+
+/// Compare an image chunk with the data read back from the device
+///
+/// `actual` may be shorter than `expected` if the device read came up short;
+/// missing bytes count as differing. Returns None if the chunk matches.
+pub fn find_mismatch(chunk_offset: u64, expected: &[u8], actual: &[u8]) -> Option<VerifyMismatch> {
+    let missing = expected.len().saturating_sub(actual.len());
+    let mut differing = expected.iter().zip(actual).filter(|(a, b)| a != b).count();
+    differing += missing;
+    if differing == 0 {
+        return None;
+    }
+
+    let first = expected
+        .iter()
+        .zip(actual)
+        .position(|(a, b)| a != b)
+        .unwrap_or(actual.len());
+
+    // Start a line before the mismatch, on a 16-byte boundary
+    let context_start = (first & !15).saturating_sub(16);
+    let context_end = std::cmp::min(context_start + MISMATCH_CONTEXT_BYTES, expected.len());
+
+    Some(VerifyMismatch {
+        offset: chunk_offset + first as u64,
+        expected: expected.get(first).copied(),
+        actual: actual.get(first).copied(),
+        differing_bytes: differing as u64,
+        chunk_size: expected.len() as u64,
+        context_offset: chunk_offset + context_start as u64,
+        expected_hex: hex::encode(&expected[context_start..context_end]),
+        actual_hex: hex::encode(
+            actual
+                .get(context_start..std::cmp::min(context_end, actual.len()))
+                .unwrap_or_default(),
+        ),
+        region: None,
+    })
+}
+
+/// Fill in the image region of a mismatch and log its context
+///
+/// Returns a one-line description for the verification error.
+pub fn describe_mismatch(image_path: &Path, mismatch: &mut VerifyMismatch) -> String {
+    mismatch.region = describe_image_offset(image_path, mismatch.offset);
+    let region = mismatch.region.as_deref().unwrap_or("unknown region");
+
+    log_error!(
+        MODULE,
+        "First mismatch at byte {} (0x{:x}) in {}: {} of {} bytes differ in chunk",
+        mismatch.offset,
+        mismatch.offset,
+        region,
+        mismatch.differing_bytes,
+        mismatch.chunk_size
+    );
+    for (label, hex) in [
+        ("expected", &mismatch.expected_hex),
+        ("actual", &mismatch.actual_hex),
+    ] {
+        for line in hexdump_lines(mismatch.context_offset, hex) {
+            log_error!(MODULE, "  {:<8} {}", label, line);
+        }
+    }
+
+    format!(
+        "at byte {} in {} (expected {}, got {})",
+        mismatch.offset,
+        region,
+        format_byte(mismatch.expected),
+        format_byte(mismatch.actual)
+    )
+}
+
+fn format_byte(byte: Option<u8>) -> String {
+    byte.map(|b| format!("0x{:02x}", b))
+        .unwrap_or_else(|| "nothing".to_string())
+}
+
+/// Format hex-encoded bytes as 16-byte hexdump lines prefixed with their offset
+pub fn hexdump_lines(start: u64, hex: &str) -> Vec<String> {
+    hex.as_bytes()
+        .chunks(32)
+        .enumerate()
+        .map(|(i, line)| {
+            let bytes: Vec<&str> = line
+                .chunks(2)
+                .map(|pair| std::str::from_utf8(pair).unwrap_or("??"))
+                .collect();
+            format!("{:08x}: {}", start + i as u64 * 16, bytes.join(" "))
+        })
+        .collect()
+}
+
 /// Verification reader trait for platform-specific device reading
 pub trait VerificationReader: Read + Send {}
 
@@ -29,15 +283,14 @@ impl<T: Read + Send> VerificationReader for T {}
 /// the Read trait. Platform-specific code is responsible for providing
 /// the appropriate device reader.
 pub fn verify_data<R: Read>(
-    image_path: &PathBuf,
+    image_path: &Path,
     device_reader: &mut R,
     state: Arc<FlashState>,
 ) -> Result<(), String> {
     state.is_verifying.store(true, Ordering::SeqCst);
     state.verified_bytes.store(0, Ordering::SeqCst);
 
-    let mut image_file = File::open(image_path)
-        .map_err(|e| format!("Failed to open image for verification: {}", e))?;
+    let mut image_file = ImageReader::open(image_path)?;
 
     let chunk_size = config::flash::CHUNK_SIZE;
     let mut image_buffer = vec![0u8; chunk_size];
@@ -53,6 +306,7 @@ pub fn verify_data<R: Read>(
         image_size,
         config::logging::WRITE_LOG_INTERVAL_MB,
     );
+    let mut speed = speed_window();
 
     log_info!(
         MODULE,
@@ -88,34 +342,36 @@ pub fn verify_data<R: Read>(
             device_read += n;
         }
 
-        if device_read != image_read {
-            log_error!(
-                MODULE,
-                "Verification failed: size mismatch at byte {} (expected {}, got {})",
-                verified,
-                image_read,
-                device_read
-            );
-            return Err(format!(
-                "Verification failed: size mismatch at byte {} (expected {}, got {})",
-                verified, image_read, device_read
-            ));
-        }
+        // Abort on the first mismatching chunk
+        if let Some(mut mismatch) = find_mismatch(
+            verified,
+            &image_buffer[..image_read],
+            &device_buffer[..device_read],
+        ) {
+            let description = describe_mismatch(image_path, &mut mismatch);
+            *state
+                .verify_mismatch
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()) = Some(mismatch);
 
-        if image_buffer[..image_read] != device_buffer[..device_read] {
-            log_error!(
-                MODULE,
-                "Verification failed: data mismatch at byte {}",
-                verified
-            );
-            return Err(format!(
-                "Verification failed: data mismatch at byte {}",
-                verified
-            ));
+            let error = if device_read != image_read {
+                format!(
+                    "Verification failed: size mismatch at byte {} (expected {}, got {})",
+                    verified, image_read, device_read
+                )
+            } else {
+                format!("Verification failed: data mismatch {}", description)
+            };
+            log_error!(MODULE, "{}", error);
+            return Err(error);
         }
 
         verified += image_read as u64;
         state.verified_bytes.store(verified, Ordering::SeqCst);
+        state
+            .verify_elapsed_ms
+            .store(tracker.elapsed().as_millis() as u64, Ordering::SeqCst);
+        state.record_throughput(&mut speed);
 
         // ProgressTracker handles logging automatically
         tracker.update(image_read as u64);
@@ -123,14 +379,570 @@ pub fn verify_data<R: Read>(
 
     // Log final summary
     tracker.finish();
+    state
+        .verify_elapsed_ms
+        .store(tracker.elapsed().as_millis() as u64, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Verify written data by hashing the device read-back
+///
+/// The SHA-256 of the image bytes taken while writing is compared with the
+/// SHA-256 of the same number of bytes read back from the device, so the
+/// image is not read (or decompressed) again and only one buffer is used.
+/// Skipped all-zero ranges are hashed as zeros, which is what the image
+/// holds there. Reads are rounded up to `sector_size` for devices that need
+/// aligned I/O. Returns false without reading if no write hash is available,
+/// e.g. when verifying a card we did not just write.
+pub fn verify_hash<R: Read>(
+    device_reader: &mut R,
+    state: &FlashState,
+    sector_size: usize,
+) -> Result<bool, String> {
+    let Some(expected) = state
+        .image_sha256
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+    else {
+        log_warn!(MODULE, "No write hash available, comparing against image");
+        state.hash_fallback.store(true, Ordering::SeqCst);
+        return Ok(false);
+    };
+
+    state.is_verifying.store(true, Ordering::SeqCst);
+    state.verified_bytes.store(0, Ordering::SeqCst);
+
+    // The hash covers the main image, not a secondary image written after it
+    let image_size =
+        state.total_bytes.load(Ordering::SeqCst) - state.secondary_image().map_or(0, |s| s.size);
+    let chunk_size = config::flash::CHUNK_SIZE;
+    let mut buffer = vec![0u8; chunk_size];
+    let mut hasher = Sha256::new();
+    let mut verified: u64 = 0;
+
+    let mut tracker = ProgressTracker::new(
+        "Verify",
+        MODULE,
+        image_size,
+        config::logging::WRITE_LOG_INTERVAL_MB,
+    );
+    let mut speed = speed_window();
+
+    log_info!(
+        MODULE,
+        "Starting hash verification of {} bytes ({:.2} GB)",
+        image_size,
+        bytes_to_gb(image_size)
+    );
+
+    while verified < image_size {
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Err("Verification cancelled".to_string());
+        }
+
+        let len = std::cmp::min(chunk_size as u64, image_size - verified) as usize;
+        let read_size = std::cmp::min(len.div_ceil(sector_size) * sector_size, chunk_size);
+        let mut read = 0;
+        while read < read_size {
+            let n = device_reader
+                .read(&mut buffer[read..read_size])
+                .map_err(|e| format!("Failed to read device at byte {}: {}", verified, e))?;
+            if n == 0 {
+                break;
+            }
+            read += n;
+        }
+        if read < len {
+            let error = format!(
+                "Verification failed: size mismatch at byte {} (expected {}, got {})",
+                verified, len, read
+            );
+            log_error!(MODULE, "{}", error);
+            return Err(error);
+        }
+
+        hasher.update(&buffer[..len]);
+
+        verified += len as u64;
+        state.verified_bytes.store(verified, Ordering::SeqCst);
+        state
+            .verify_elapsed_ms
+            .store(tracker.elapsed().as_millis() as u64, Ordering::SeqCst);
+        state.record_throughput(&mut speed);
+        tracker.update(len as u64);
+    }
+    tracker.finish();
+    state
+        .verify_elapsed_ms
+        .store(tracker.elapsed().as_millis() as u64, Ordering::SeqCst);
+
+    let actual = hex::encode(hasher.finalize());
+    log_info!(MODULE, "Device SHA-256: {}", actual);
+    *state
+        .device_sha256
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = Some(actual.clone());
+
+    if actual != expected {
+        let error = format!(
+            "Verification failed: device checksum {} does not match image checksum {}",
+            actual, expected
+        );
+        log_error!(MODULE, "{}", error);
+        return Err(error);
+    }
+    Ok(true)
+}
+
+/// Verify written data, using several readers if the options ask for it
+///
+/// Hash verification reads the device once front to back. Otherwise
+/// compressed images can only be decoded front to back, so they are always
+/// compared in full by a single reader.
+pub fn verify_data_with_options(
+    image_path: &PathBuf,
+    device: &mut File,
+    state: Arc<FlashState>,
+    options: VerifyOptions,
+) -> Result<(), String> {
+    if options.mode == VerifyMode::Hash && verify_hash(device, &state, 1)? {
+        Ok(())
+    } else if is_streamed(image_path) {
+        log_info!(MODULE, "Compressed image, verifying with a single stream");
+        verify_data(image_path, device, state)
+    } else if options.streams > 1 || options.mode == VerifyMode::Fast {
+        verify_data_parallel(image_path, device, state, options)
+    } else {
+        verify_data(image_path, device, state)
+    }
+}
+
+/// Failure of one verification stream
+struct StreamFailure {
+    offset: u64,
+    error: String,
+    mismatch: Option<Box<VerifyMismatch>>,
+}
+
+/// Verify written data with several readers at interleaved chunk offsets
+///
+/// Stream `i` compares chunks `i`, `i + streams`, `i + 2 * streams`, ...
+/// using positional reads, so all streams share one device handle. Progress
+/// is merged into `state.verified_bytes`. The first failure stops all
+/// streams; the failure at the lowest offset is reported.
+///
+/// In fast mode, chunks are checked against the CRC32C taken while writing
+/// and the image is only read to describe a mismatch. Without write
+/// checksums (e.g. verifying a card we did not just write), chunks are
+/// byte-compared as usual.
+pub fn verify_data_parallel(
+    image_path: &PathBuf,
+    device: &File,
+    state: Arc<FlashState>,
+    options: VerifyOptions,
+) -> Result<(), String> {
+    state.is_verifying.store(true, Ordering::SeqCst);
+    state.verified_bytes.store(0, Ordering::SeqCst);
+
+    let image_size = state.total_bytes.load(Ordering::SeqCst);
+    let chunk_size = config::flash::CHUNK_SIZE as u64;
+    let chunk_count = image_size.div_ceil(chunk_size).max(1);
+    let streams = options.streams.clamp(1, chunk_count as usize);
+
+    let checksums = match options.mode {
+        VerifyMode::Fast => {
+            let checksums = write_checksums(&state, image_size);
+            if checksums.is_none() {
+                log_info!(
+                    MODULE,
+                    "No write checksums available, comparing against image"
+                );
+            }
+            checksums
+        }
+        VerifyMode::Full | VerifyMode::Hash => None,
+    };
+
+    log_info!(
+        MODULE,
+        "Starting {} verification of {} bytes ({:.2} GB) with {} stream(s)",
+        if checksums.is_some() { "fast" } else { "full" },
+        image_size,
+        bytes_to_gb(image_size),
+        streams
+    );
+
+    let started = Instant::now();
+    let stop = AtomicBool::new(false);
+    let failure: Mutex<Option<StreamFailure>> = Mutex::new(None);
+
+    std::thread::scope(|scope| {
+        for stream in 0..streams {
+            let (state, stop, failure) = (&state, &stop, &failure);
+            let checksums = checksums.as_deref();
+            scope.spawn(move || {
+                let result = verify_stream(
+                    image_path, device, state, stop, stream, streams, image_size, checksums,
+                    started,
+                );
+                if let Err(err) = result {
+                    stop.store(true, Ordering::SeqCst);
+                    let mut failure = failure.lock().unwrap_or_else(|e| e.into_inner());
+                    if failure.as_ref().map_or(true, |f| err.offset < f.offset) {
+                        *failure = Some(err);
+                    }
+                }
+            });
+        }
+    });
+
+    state
+        .verify_elapsed_ms
+        .store(started.elapsed().as_millis() as u64, Ordering::SeqCst);
+
+    if let Some(failure) = failure.into_inner().unwrap_or_else(|e| e.into_inner()) {
+        if failure.mismatch.is_some() {
+            *state
+                .verify_mismatch
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()) = failure.mismatch.map(|m| *m);
+        }
+        log_error!(MODULE, "{}", failure.error);
+        return Err(failure.error);
+    }
+
+    log_info!(
+        MODULE,
+        "Verify complete: {:.2} GB in {:.1}s",
+        bytes_to_gb(image_size),
+        started.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn verify_stream(
+    image_path: &PathBuf,
+    device: &File,
+    state: &FlashState,
+    stop: &AtomicBool,
+    stream: usize,
+    streams: usize,
+    image_size: u64,
+    checksums: Option<&[u32]>,
+    started: Instant,
+) -> Result<(), StreamFailure> {
+    let fail = |offset: u64, error: String| StreamFailure {
+        offset,
+        error,
+        mismatch: None,
+    };
+
+    let image = File::open(image_path)
+        .map_err(|e| fail(0, format!("Failed to open image for verification: {}", e)))?;
+
+    let chunk_size = config::flash::CHUNK_SIZE as u64;
+    let mut image_buffer = vec![0u8; chunk_size as usize];
+    let mut device_buffer = vec![0u8; chunk_size as usize];
+    let mut speed = speed_window();
+    let mut offset = stream as u64 * chunk_size;
+
+    log_debug!(
+        MODULE,
+        "Verify stream {} starting at byte {}",
+        stream,
+        offset
+    );
+
+    while offset < image_size {
+        if stop.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Err(fail(offset, "Verification cancelled".to_string()));
+        }
+
+        let to_read = std::cmp::min(chunk_size, image_size - offset) as usize;
+        let expected_checksum = checksums.map(|sums| sums[(offset / chunk_size) as usize]);
+
+        if let Some(expected) = expected_checksum {
+            let device_read = read_full_at(device, &mut device_buffer[..to_read], offset)
+                .map_err(|e| fail(offset, format!("Failed to read device: {}", e)))?;
+            if device_read == to_read && crc32c::crc32c(&device_buffer[..to_read]) == expected {
+                state
+                    .verified_bytes
+                    .fetch_add(to_read as u64, Ordering::SeqCst);
+                state
+                    .verify_elapsed_ms
+                    .store(started.elapsed().as_millis() as u64, Ordering::SeqCst);
+                state.record_throughput(&mut speed);
+                offset += streams as u64 * chunk_size;
+                continue;
+            }
+            // Fall through to the byte comparison to describe the mismatch
+        }
+
+        let image_read = read_full_at(&image, &mut image_buffer[..to_read], offset)
+            .map_err(|e| fail(offset, format!("Failed to read image: {}", e)))?;
+        if image_read == 0 {
+            break;
+        }
+
+        let device_read = read_full_at(device, &mut device_buffer[..image_read], offset)
+            .map_err(|e| fail(offset, format!("Failed to read device: {}", e)))?;
+
+        if let Some(mut mismatch) = find_mismatch(
+            offset,
+            &image_buffer[..image_read],
+            &device_buffer[..device_read],
+        ) {
+            let description = describe_mismatch(image_path, &mut mismatch);
+            let error = if device_read != image_read {
+                format!(
+                    "Verification failed: size mismatch at byte {} (expected {}, got {})",
+                    offset, image_read, device_read
+                )
+            } else {
+                format!("Verification failed: data mismatch {}", description)
+            };
+            return Err(StreamFailure {
+                offset,
+                error,
+                mismatch: Some(Box::new(mismatch)),
+            });
+        }
+
+        if expected_checksum.is_some() {
+            // Device matches the image but not what was written: the image changed
+            return Err(fail(
+                offset,
+                format!("Verification failed: checksum mismatch at byte {}", offset),
+            ));
+        }
+
+        state
+            .verified_bytes
+            .fetch_add(image_read as u64, Ordering::SeqCst);
+        state
+            .verify_elapsed_ms
+            .store(started.elapsed().as_millis() as u64, Ordering::SeqCst);
+        state.record_throughput(&mut speed);
+
+        offset += streams as u64 * chunk_size;
+    }
+
     Ok(())
 }
 
+/// Fill `buf` from `offset`, stopping early only at end of file
+fn read_full_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    let mut total = 0;
+    while total < buf.len() {
+        let n = read_at(file, &mut buf[total..], offset + total as u64)?;
+        if n == 0 {
+            break;
+        }
+        total += n;
+    }
+    Ok(total)
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn test_verify_matching_data() {
         // This test requires a temp file, which we'll skip for now
         // In production, we'd create temp files and verify they match
     }
+
+    fn temp_file(name: &str, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "armbian-imager-verify-{}-{}",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_verify_data_parallel() {
+        // Spans several chunks with a short last chunk
+        let chunk = config::flash::CHUNK_SIZE;
+        let image: Vec<u8> = (0..chunk * 3 + 1000).map(|i| (i % 251) as u8).collect();
+        let image_path = temp_file("image", &image);
+
+        let device_path = temp_file("device-ok", &image);
+        let state = Arc::new(FlashState::new());
+        state
+            .total_bytes
+            .store(image.len() as u64, Ordering::SeqCst);
+        let device = File::open(&device_path).unwrap();
+        assert!(verify_data_parallel(&image_path, &device, state.clone(), options(4)).is_ok());
+        assert_eq!(
+            state.verified_bytes.load(Ordering::SeqCst),
+            image.len() as u64
+        );
+
+        let mut corrupted = image.clone();
+        corrupted[chunk * 2 + 10] ^= 0xff;
+        let bad_path = temp_file("device-bad", &corrupted);
+        let state = Arc::new(FlashState::new());
+        state
+            .total_bytes
+            .store(image.len() as u64, Ordering::SeqCst);
+        let device = File::open(&bad_path).unwrap();
+        let err =
+            verify_data_parallel(&image_path, &device, state.clone(), options(4)).unwrap_err();
+        assert!(err.contains("data mismatch"));
+        let mismatch = state.verify_mismatch.lock().unwrap().clone().unwrap();
+        assert_eq!(mismatch.offset, (chunk * 2 + 10) as u64);
+
+        for path in [image_path, device_path, bad_path] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    fn options(streams: usize) -> VerifyOptions {
+        VerifyOptions {
+            streams,
+            ..VerifyOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_chunk_checksums() {
+        let chunk = config::flash::CHUNK_SIZE;
+        let data: Vec<u8> = (0..chunk + 100).map(|i| (i % 13) as u8).collect();
+
+        // Split at arbitrary points, as short reads in the write loop would
+        let mut checksums = ChunkChecksums::default();
+        for part in data.chunks(chunk / 3 + 7) {
+            checksums.update(part);
+        }
+        let state = FlashState::new();
+        checksums.finish(&state);
+
+        let sums = write_checksums(&state, data.len() as u64).unwrap();
+        assert_eq!(
+            sums,
+            vec![
+                crc32c::crc32c(&data[..chunk]),
+                crc32c::crc32c(&data[chunk..])
+            ]
+        );
+        assert!(write_checksums(&state, (chunk * 3) as u64).is_none());
+    }
+
+    #[test]
+    fn test_fast_verify() {
+        let chunk = config::flash::CHUNK_SIZE;
+        let image: Vec<u8> = (0..chunk * 2 + 5).map(|i| (i % 241) as u8).collect();
+        let image_path = temp_file("fast-image", &image);
+        let mut corrupted = image.clone();
+        corrupted[chunk + 3] ^= 0x01;
+        let device_path = temp_file("fast-device", &corrupted);
+
+        let state = Arc::new(FlashState::new());
+        state
+            .total_bytes
+            .store(image.len() as u64, Ordering::SeqCst);
+        let mut checksums = ChunkChecksums::default();
+        checksums.update(&image);
+        checksums.finish(&state);
+
+        let fast = VerifyOptions {
+            mode: VerifyMode::Fast,
+            ..VerifyOptions::default()
+        };
+        let device = File::open(&device_path).unwrap();
+        let err = verify_data_parallel(&image_path, &device, state.clone(), fast).unwrap_err();
+        assert!(err.contains("data mismatch"));
+        let mismatch = state.verify_mismatch.lock().unwrap().clone().unwrap();
+        assert_eq!(mismatch.offset, (chunk + 3) as u64);
+
+        for path in [image_path, device_path] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_hash_verify() {
+        let image: Vec<u8> = (0..config::flash::CHUNK_SIZE + 700)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let state = FlashState::new();
+        state
+            .total_bytes
+            .store(image.len() as u64, Ordering::SeqCst);
+
+        // Nothing to compare against without a write hash
+        assert!(!verify_hash(&mut &image[..], &state, 512).unwrap());
+
+        let mut hashing = crate::flash::HashingReader::new(&image[..]);
+        std::io::copy(&mut hashing, &mut std::io::sink()).unwrap();
+        hashing.finish(&state);
+
+        // Reads are rounded up to whole sectors past the end of the image
+        let mut device = image.clone();
+        device.extend_from_slice(&[0xff; 512]);
+        assert!(verify_hash(&mut &device[..], &state, 512).unwrap());
+        assert_eq!(
+            state.device_sha256.lock().unwrap().as_deref(),
+            state.image_sha256.lock().unwrap().as_deref()
+        );
+
+        device[100] ^= 0x01;
+        let err = verify_hash(&mut &device[..], &state, 512).unwrap_err();
+        assert!(err.contains("device checksum"), "{}", err);
+    }
+
+    #[test]
+    fn test_find_mismatch() {
+        assert!(find_mismatch(0, b"abcd", b"abcd").is_none());
+
+        let mismatch = find_mismatch(4096, b"abcd", b"abXY").unwrap();
+        assert_eq!(mismatch.offset, 4098);
+        assert_eq!(mismatch.expected, Some(b'c'));
+        assert_eq!(mismatch.actual, Some(b'X'));
+        assert_eq!(mismatch.differing_bytes, 2);
+        assert_eq!(mismatch.chunk_size, 4);
+
+        // Short device read
+        let mismatch = find_mismatch(0, b"abcd", b"ab").unwrap();
+        assert_eq!(mismatch.offset, 2);
+        assert_eq!(mismatch.actual, None);
+        assert_eq!(mismatch.differing_bytes, 2);
+        assert_eq!(mismatch.expected_hex, "61626364");
+        assert_eq!(mismatch.actual_hex, "6162");
+    }
+
+    #[test]
+    fn test_mismatch_context_window() {
+        let expected = vec![0u8; 256];
+        let mut actual = expected.clone();
+        actual[100] = 0xff;
+
+        let mismatch = find_mismatch(4096, &expected, &actual).unwrap();
+        // Starts one line before the 16-byte line holding the mismatch
+        assert_eq!(mismatch.context_offset, 4096 + 80);
+        assert_eq!(mismatch.expected_hex.len(), MISMATCH_CONTEXT_BYTES * 2);
+
+        let lines = hexdump_lines(mismatch.context_offset, &mismatch.actual_hex);
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("00001060: 00 00 00 00 ff"));
+    }
 }
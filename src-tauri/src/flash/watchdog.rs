@@ -0,0 +1,206 @@
+//! Stall watchdog for flash and verify operations
+//!
+//! A device whose firmware hangs can block a write or read syscall forever,
+//! leaving the progress bar frozen with no feedback. The watchdog runs on its
+//! own thread, flags the operation as stalled when no bytes move for
+//! `config::flash::STALL_TIMEOUT_SECS`, and logs device and writeback state
+//! for diagnosis. The user can keep waiting or cancel; a cancel takes effect
+//! as soon as the blocked call returns, so the device is never left mid-chunk
+//! by a killed thread.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::config;
+use crate::{log_info, log_warn};
+
+use super::FlashState;
+
+const MODULE: &str = "flash::watchdog";
+
+/// Tracks how long a progress counter has been unchanged
+#[derive(Debug)]
+struct StallDetector {
+    last_progress: u64,
+    last_change: Instant,
+}
+
+impl StallDetector {
+    fn new(progress: u64, now: Instant) -> Self {
+        Self {
+            last_progress: progress,
+            last_change: now,
+        }
+    }
+
+    /// Record the current progress, returning how long it has not moved
+    fn observe(&mut self, progress: u64, now: Instant) -> Duration {
+        if progress != self.last_progress {
+            self.last_progress = progress;
+            self.last_change = now;
+        }
+        now.duration_since(self.last_change)
+    }
+}
+
+/// Watches a running operation until dropped
+pub struct Watchdog {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Start watching the progress of an operation on `device_path`
+    pub fn start(state: Arc<FlashState>, device_path: &str) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let device_path = device_path.to_string();
+
+        let handle = std::thread::Builder::new()
+            .name("flash-watchdog".to_string())
+            .spawn(move || watch(&state, &device_path, &thread_stop))
+            .map_err(|e| log_warn!(MODULE, "Failed to start watchdog: {}", e))
+            .ok();
+
+        Self { stop, handle }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Bytes moved so far by the write and verify stages
+fn progress(state: &FlashState) -> u64 {
+    state.written_bytes.load(Ordering::SeqCst) + state.verified_bytes.load(Ordering::SeqCst)
+}
+
+fn watch(state: &FlashState, device_path: &str, stop: &AtomicBool) {
+    let timeout = Duration::from_secs(config::flash::STALL_TIMEOUT_SECS);
+    let poll = Duration::from_millis(config::flash::WATCHDOG_POLL_MS);
+    let mut detector = StallDetector::new(progress(state), Instant::now());
+    let mut cancel_logged = false;
+
+    while !stop.load(Ordering::SeqCst) {
+        std::thread::sleep(poll);
+
+        let now = Instant::now();
+        let mut idle = detector.observe(progress(state), now);
+
+        // Waiting for AC power is an intended pause, not a stall
+        if state.is_paused_for_power.load(Ordering::SeqCst) {
+            detector = StallDetector::new(progress(state), now);
+            idle = Duration::ZERO;
+        }
+
+        let stalled = idle >= timeout;
+        let was_stalled = state.is_stalled.swap(stalled, Ordering::SeqCst);
+        state
+            .stalled_secs
+            .store(if stalled { idle.as_secs() } else { 0 }, Ordering::SeqCst);
+
+        if stalled && !was_stalled {
+            log_warn!(
+                MODULE,
+                "No progress on {} for {}s, the device may have stopped responding",
+                device_path,
+                idle.as_secs()
+            );
+            log_device_state(state, device_path);
+            cancel_logged = false;
+        } else if !stalled && was_stalled {
+            log_info!(MODULE, "{} is making progress again", device_path);
+        }
+
+        if stalled && !cancel_logged && state.is_cancelled.load(Ordering::SeqCst) {
+            log_warn!(
+                MODULE,
+                "Cancel requested while stalled, waiting for the pending I/O on {} to return",
+                device_path
+            );
+            cancel_logged = true;
+        }
+    }
+
+    state.is_stalled.store(false, Ordering::SeqCst);
+    state.stalled_secs.store(0, Ordering::SeqCst);
+}
+
+/// Log what is known about the device and system I/O state
+fn log_device_state(state: &FlashState, device_path: &str) {
+    log_warn!(
+        MODULE,
+        "Progress: {} of {} bytes written, {} verified (verifying: {})",
+        state.written_bytes.load(Ordering::SeqCst),
+        state.total_bytes.load(Ordering::SeqCst),
+        state.verified_bytes.load(Ordering::SeqCst),
+        state.is_verifying.load(Ordering::SeqCst)
+    );
+
+    #[cfg(target_os = "linux")]
+    {
+        let present = std::path::Path::new(device_path).exists();
+        log_warn!(MODULE, "Device node {} present: {}", device_path, present);
+
+        if let Some(name) = device_path.strip_prefix("/dev/") {
+            for file in ["inflight", "stat"] {
+                if let Ok(value) = std::fs::read_to_string(format!("/sys/block/{}/{}", name, file))
+                {
+                    log_warn!(MODULE, "/sys/block/{}/{}: {}", name, file, value.trim());
+                }
+            }
+        }
+
+        // Large dirty/writeback counts mean the kernel is still pushing data
+        if let Ok(meminfo) = std::fs::read_to_string("/proc/meminfo") {
+            for line in meminfo
+                .lines()
+                .filter(|l| l.starts_with("Dirty:") || l.starts_with("Writeback:"))
+            {
+                log_warn!(MODULE, "{}", line);
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        log_warn!(
+            MODULE,
+            "Device {} is not reporting I/O completion",
+            device_path
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stall_detector() {
+        let start = Instant::now();
+        let mut detector = StallDetector::new(0, start);
+
+        assert_eq!(
+            detector.observe(0, start + Duration::from_secs(5)),
+            Duration::from_secs(5)
+        );
+
+        // Progress resets the idle time
+        assert_eq!(
+            detector.observe(100, start + Duration::from_secs(6)),
+            Duration::ZERO
+        );
+        assert_eq!(
+            detector.observe(100, start + Duration::from_secs(40)),
+            Duration::from_secs(34)
+        );
+    }
+}
@@ -2,9 +2,20 @@
 //!
 //! Requires Administrator privileges for raw disk access.
 
-use super::FlashState;
+use super::power::PowerGuard;
+use super::range::RangeWrite;
+use super::smart_write::{log_skipped, SmartWriter};
+#[cfg(target_os = "windows")]
+use super::verify::{
+    describe_mismatch, find_mismatch, verify_hash, write_checksums, ChunkChecksums, VerifyMode,
+    VerifyOptions,
+};
+use super::{
+    measured_image_size, speed_window, CapacityReport, EraseLayout, FlashState, HashingReader,
+    ImageReader,
+};
 use crate::config;
-use crate::utils::{bytes_to_gb, ProgressTracker};
+use crate::utils::{bytes_to_gb, ProgressTracker, Throttle};
 use crate::{log_debug, log_error, log_info, log_warn};
 use std::io::{Read, Write};
 use std::path::PathBuf;
@@ -27,14 +38,17 @@ const FILE_FLAG_WRITE_THROUGH: u32 = 0x80000000;
 
 /// Flashes an image to a block device.
 ///
-/// Requires Administrator privileges on Windows.
+/// Requires Administrator privileges on Windows. Verification is always
+/// sequential here: the unbuffered device handle needs sector-aligned reads.
 pub async fn flash_image(
     image_path: &PathBuf,
     device_path: &str,
     state: Arc<FlashState>,
     verify: bool,
+    verify_options: VerifyOptions,
 ) -> Result<(), String> {
     state.reset();
+    let secondary = state.secondary_image();
 
     log_info!(
         MODULE,
@@ -43,17 +57,24 @@ pub async fn flash_image(
         device_path
     );
 
-    let image_size = std::fs::metadata(image_path)
-        .map_err(|e| format!("Failed to get image size: {}", e))?
-        .len();
+    // Open image file, decompressing on the fly if it is an archive
+    let mut image_file = HashingReader::new(state.open_image(image_path)?);
+    let image_size = image_file.get_ref().image_size();
+    let source_size = image_file.get_ref().source_size();
 
-    state.total_bytes.store(image_size, Ordering::SeqCst);
+    // Progress counts bytes of the image file, compressed or not
+    state.total_bytes.store(source_size, Ordering::SeqCst);
 
     log_info!(
         MODULE,
-        "Image size: {} bytes ({:.2} GB)",
-        image_size,
-        bytes_to_gb(image_size)
+        "Image file size: {} bytes ({:.2} GB){}",
+        source_size,
+        bytes_to_gb(source_size),
+        if image_file.get_ref().is_compressed() {
+            ", decompressing while writing"
+        } else {
+            ""
+        }
     );
 
     let disk_number = extract_disk_number(device_path)?;
@@ -64,9 +85,6 @@ pub async fn flash_image(
         config::flash::UNMOUNT_DELAY_MS,
     ));
 
-    let mut image_file =
-        std::fs::File::open(image_path).map_err(|e| format!("Failed to open image: {}", e))?;
-
     log_debug!(MODULE, "Opening device for writing...");
     let mut device = open_device_for_write(device_path)?;
 
@@ -78,18 +96,33 @@ pub async fn flash_image(
     let mut tracker = ProgressTracker::new(
         "Write",
         MODULE,
-        image_size,
+        image_size.unwrap_or(0),
         config::logging::WRITE_LOG_INTERVAL_MB,
     );
+    let mut throttle = Throttle::new(state.write_throttle_bytes_per_sec.load(Ordering::SeqCst));
 
     log_info!(MODULE, "Writing image to device...");
 
+    let mut bytes_since_sync: u64 = 0;
+    let mut speed = speed_window();
+    let mut power_guard = PowerGuard::new();
+    let mut checksums = ChunkChecksums::for_options(verify, verify_options);
+    let mut writer = SmartWriter::new(
+        state.smart_write.load(Ordering::SeqCst),
+        state.skip_zero_blocks.load(Ordering::SeqCst),
+    );
+    if writer.is_enabled() {
+        log_info!(MODULE, "Smart write: only chunks that differ are written");
+    }
+
     loop {
         if state.is_cancelled.load(Ordering::SeqCst) {
             log_info!(MODULE, "Flash cancelled by user");
             return Err("Flash cancelled".to_string());
         }
 
+        power_guard.check(&state, || flush_device_buffers(&device))?;
+
         let bytes_read = image_file.read(&mut buffer).map_err(|e| {
             log_error!(MODULE, "Failed to read image: {}", e);
             format!("Failed to read image: {}", e)
@@ -99,16 +132,45 @@ pub async fn flash_image(
             break;
         }
 
-        device.write_all(&buffer[..bytes_read]).map_err(|e| {
-            log_error!(MODULE, "Failed to write to device: {}", e);
-            format!("Failed to write to device: {}", e)
-        })?;
+        writer
+            .write(&mut device, &buffer[..bytes_read])
+            .map_err(|e| {
+                log_error!(MODULE, "Failed to write to device: {}", e);
+                format!("Failed to write to device: {}", e)
+            })?;
 
         written += bytes_read as u64;
-        state.written_bytes.store(written, Ordering::SeqCst);
+        state
+            .skipped_bytes
+            .store(writer.skipped(), Ordering::SeqCst);
+        if let Some(checksums) = checksums.as_mut() {
+            checksums.update(&buffer[..bytes_read]);
+        }
+        bytes_since_sync += bytes_read as u64;
+        let consumed = image_file.get_ref().consumed();
+        state.written_bytes.store(consumed, Ordering::SeqCst);
+        state
+            .write_elapsed_ms
+            .store(tracker.elapsed().as_millis() as u64, Ordering::SeqCst);
+        state.record_throughput(&mut speed);
+
+        // Periodic flush; write-through handles still leave the drive's cache
+        if bytes_since_sync >= config::flash::SYNC_INTERVAL {
+            flush_device_buffers(&device)?;
+            bytes_since_sync = 0;
+            state.synced_bytes.store(consumed, Ordering::SeqCst);
+        }
 
         // ProgressTracker handles logging automatically
         tracker.update(bytes_read as u64);
+        throttle.pace(bytes_read as u64, &state.is_cancelled);
+    }
+
+    if let Some(tail) = verify_options.tail_check {
+        tail.write_zeros(&mut device)?;
+    }
+    if let Some(secondary) = &secondary {
+        secondary.write(&mut device, &state)?;
     }
 
     log_debug!(MODULE, "Flushing write cache...");
@@ -117,6 +179,16 @@ pub async fn flash_image(
 
     // Log final summary
     tracker.finish();
+    log_skipped(&writer, written);
+    state.set_zero_ranges(writer.zero_ranges().clone());
+    image_file.finish(&state);
+    if let Some(checksums) = checksums {
+        checksums.finish(&state);
+    }
+    state.finish_write(written + secondary.as_ref().map_or(0, |s| s.size));
+    state
+        .write_elapsed_ms
+        .store(tracker.elapsed().as_millis() as u64, Ordering::SeqCst);
 
     if verify {
         log_info!(MODULE, "Starting verification...");
@@ -124,8 +196,11 @@ pub async fn flash_image(
         std::thread::sleep(std::time::Duration::from_millis(
             config::flash::UNMOUNT_DELAY_MS,
         ));
-        let device = open_device_for_read(device_path)?;
-        verify_with_sector_alignment(image_path, device, state)?;
+        let mut device = open_device_for_read(device_path)?;
+        verify_with_sector_alignment(image_path, &mut device, state.clone(), verify_options)?;
+        if let Some(secondary) = &secondary {
+            secondary.verify(&mut device, &state)?;
+        }
     }
 
     log_info!(MODULE, "Flash complete, releasing volume locks...");
@@ -385,21 +460,257 @@ fn flush_device_buffers(_device: &std::fs::File) -> Result<(), String> {
     Ok(())
 }
 
+/// Verifies a device against an image without writing to it.
+pub async fn verify_device(
+    image_path: &PathBuf,
+    device_path: &str,
+    state: Arc<FlashState>,
+    verify_options: VerifyOptions,
+) -> Result<(), String> {
+    state.reset();
+
+    let image_size = measured_image_size(image_path, &state.is_cancelled)?;
+    state.total_bytes.store(image_size, Ordering::SeqCst);
+
+    log_info!(
+        MODULE,
+        "Verifying {} against {}",
+        device_path,
+        image_path.display()
+    );
+
+    let mut device = open_device_for_read(device_path)?;
+    verify_with_sector_alignment(image_path, &mut device, state, verify_options)
+}
+
+/// Reads a whole device into a compressed image file.
+///
+/// The device handle is unbuffered; `backup_to_file` keeps reads
+/// sector-aligned. Returns the size of the written archive.
+pub async fn backup_device(
+    device_path: &str,
+    device_size: u64,
+    output: &std::path::Path,
+    state: Arc<FlashState>,
+) -> Result<u64, String> {
+    state.reset();
+
+    log_info!(MODULE, "Backing up {} to {}", device_path, output.display());
+
+    let mut device = open_device_for_read(device_path)?;
+    super::backup::backup_to_file(&mut device, device_size, output, &state)
+}
+
+/// Dismounts all volumes of a disk and ejects the device.
+///
+/// With the volumes locked and dismounted nothing is cached for the disk any
+/// more. The device is then removed through Plug and Play like "Safely Remove
+/// Hardware", so it can be pulled without Windows warning about data loss.
+/// Devices Plug and Play won't remove (e.g. built-in readers) get their media
+/// ejected instead, or only the dismount.
+pub async fn eject_device(device_path: &str) -> Result<(), String> {
+    let disk_number = extract_disk_number(device_path)?;
+    log_info!(MODULE, "Ejecting disk {}", disk_number);
+
+    {
+        let _volume_locks = lock_disk_volumes(disk_number)?;
+        let device = open_device_for_write(device_path)?;
+        flush_device_buffers(&device)?;
+    }
+
+    // Our own handles on the disk or its volumes would veto the removal
+    match crate::devices::request_disk_eject(disk_number) {
+        Ok(()) => {
+            log_info!(MODULE, "{} removed", device_path);
+            Ok(())
+        }
+        Err(e) => {
+            log_warn!(
+                MODULE,
+                "Device eject of {} failed ({}), ejecting the media",
+                device_path,
+                e
+            );
+            let _volume_locks = lock_disk_volumes(disk_number)?;
+            let device = open_device_for_write(device_path)?;
+            eject_media(&device, device_path)
+        }
+    }
+}
+
+/// Allows media removal and ejects the media of an open disk.
+#[cfg(target_os = "windows")]
+fn eject_media(device: &std::fs::File, device_path: &str) -> Result<(), String> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::System::Ioctl::{
+        IOCTL_STORAGE_EJECT_MEDIA, IOCTL_STORAGE_MEDIA_REMOVAL,
+    };
+    use windows_sys::Win32::System::IO::DeviceIoControl;
+
+    #[repr(C)]
+    struct PreventMediaRemoval {
+        prevent_media_removal: u8,
+    }
+
+    unsafe {
+        let handle = device.as_raw_handle() as *mut _;
+        let mut bytes_returned: u32 = 0;
+        let mut allow = PreventMediaRemoval {
+            prevent_media_removal: 0,
+        };
+        // Best effort: most card readers never prevent removal
+        DeviceIoControl(
+            handle,
+            IOCTL_STORAGE_MEDIA_REMOVAL,
+            &mut allow as *mut _ as *mut _,
+            std::mem::size_of::<PreventMediaRemoval>() as u32,
+            std::ptr::null_mut(),
+            0,
+            &mut bytes_returned,
+            std::ptr::null_mut(),
+        );
+
+        let result = DeviceIoControl(
+            handle,
+            IOCTL_STORAGE_EJECT_MEDIA,
+            std::ptr::null(),
+            0,
+            std::ptr::null_mut(),
+            0,
+            &mut bytes_returned,
+            std::ptr::null_mut(),
+        );
+        if result == 0 {
+            let error_code = windows_sys::Win32::Foundation::GetLastError();
+            log_warn!(
+                MODULE,
+                "Media eject of {} failed (error {}), volumes stay dismounted",
+                device_path,
+                error_code
+            );
+        } else {
+            log_info!(MODULE, "{} ejected", device_path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn eject_media(_device: &std::fs::File, _device_path: &str) -> Result<(), String> {
+    Ok(())
+}
+
+/// Writes a blob at a byte offset, leaving the rest of the device untouched.
+///
+/// `RangeWrite` rewrites whole aligned sectors, as raw disk handles require.
+/// The read-back goes through a fresh unbuffered handle.
+pub async fn flash_range(
+    device_path: &str,
+    range: &RangeWrite,
+    state: Arc<FlashState>,
+    verify: bool,
+) -> Result<(), String> {
+    state.reset();
+
+    log_info!(
+        MODULE,
+        "Starting range write: {} -> {} at byte {}",
+        range.input.display(),
+        device_path,
+        range.offset
+    );
+
+    let disk_number = extract_disk_number(device_path)?;
+    let _volume_locks = lock_disk_volumes(disk_number)?;
+    std::thread::sleep(std::time::Duration::from_millis(
+        config::flash::UNMOUNT_DELAY_MS,
+    ));
+
+    let mut device = open_device_for_write(device_path)?;
+    range.write(&mut device, &state)?;
+    flush_device_buffers(&device)?;
+    drop(device);
+
+    if verify {
+        state.is_verifying.store(true, Ordering::SeqCst);
+        let mut device = open_device_for_read(device_path)?;
+        range.verify(&mut device, &state)?;
+    }
+
+    log_info!(MODULE, "Range write complete");
+    Ok(())
+}
+
+/// Replaces everything on a device with one empty FAT32 or exFAT partition.
+pub async fn erase_device(
+    device_path: &str,
+    layout: &EraseLayout,
+    state: Arc<FlashState>,
+) -> Result<(), String> {
+    state.reset();
+
+    log_info!(MODULE, "Starting erase of {}", device_path);
+
+    let disk_number = extract_disk_number(device_path)?;
+    let _volume_locks = lock_disk_volumes(disk_number)?;
+    std::thread::sleep(std::time::Duration::from_millis(
+        config::flash::UNMOUNT_DELAY_MS,
+    ));
+
+    let mut device = open_device_for_write(device_path)?;
+    layout.write(&mut device, &state)?;
+    flush_device_buffers(&device)?;
+
+    log_info!(MODULE, "Erase complete");
+    Ok(())
+}
+
+/// Checks a device for fake capacity, restoring the sampled blocks.
+///
+/// Physical drive handles are not cached by Windows, so markers are read
+/// back through the write-through handle they were written with.
+pub async fn check_capacity(
+    device_path: &str,
+    device_size: u64,
+    state: Arc<FlashState>,
+) -> Result<CapacityReport, String> {
+    state.reset();
+
+    log_info!(MODULE, "Starting capacity check of {}", device_path);
+
+    let disk_number = extract_disk_number(device_path)?;
+    let _volume_locks = lock_disk_volumes(disk_number)?;
+    std::thread::sleep(std::time::Duration::from_millis(
+        config::flash::UNMOUNT_DELAY_MS,
+    ));
+
+    let mut device = open_device_for_write(device_path)?;
+    let report = super::capacity::check_capacity(&mut device, device_size, &state, |device| {
+        flush_device_buffers(device)
+    })?;
+    flush_device_buffers(&device)?;
+    Ok(report)
+}
+
 /// Verifies written data using sector-aligned reads.
 ///
 /// Required when using `FILE_FLAG_NO_BUFFERING` which bypasses the OS cache.
+/// In hash mode, the device read-back is hashed as a whole. In fast mode,
+/// chunks are checked against the write checksums and the image is only
+/// read to describe a mismatch. Compressed images can't be read at an
+/// offset, so they are always compared in full.
 #[cfg(target_os = "windows")]
 fn verify_with_sector_alignment(
     image_path: &PathBuf,
-    mut device: std::fs::File,
+    device: &mut std::fs::File,
     state: Arc<FlashState>,
+    options: VerifyOptions,
 ) -> Result<(), String> {
+    use std::io::{Seek, SeekFrom};
+
     state.is_verifying.store(true, Ordering::SeqCst);
     state.verified_bytes.store(0, Ordering::SeqCst);
 
-    let mut image_file =
-        std::fs::File::open(image_path).map_err(|e| format!("Failed to open image: {}", e))?;
-
     let image_size = state.total_bytes.load(Ordering::SeqCst);
 
     log_info!(
@@ -409,7 +720,7 @@ fn verify_with_sector_alignment(
         bytes_to_gb(image_size)
     );
 
-    let sector_size = get_device_sector_size(&device)?;
+    let sector_size = get_device_sector_size(device)?;
     let chunk_size = config::flash::CHUNK_SIZE;
     let aligned_chunk_size = (chunk_size / sector_size) * sector_size;
 
@@ -420,9 +731,27 @@ fn verify_with_sector_alignment(
         aligned_chunk_size
     );
 
+    if options.mode == VerifyMode::Hash && verify_hash(device, &state, sector_size)? {
+        return Ok(());
+    }
+    // Only opened now: a streamed image has no file to compare against
+    let mut image_file = ImageReader::open(image_path)?;
+
+    // Checksums cover CHUNK_SIZE chunks, usable only if reads line up with them
+    let checksums = match options.mode {
+        VerifyMode::Fast if aligned_chunk_size == chunk_size && !image_file.is_compressed() => {
+            write_checksums(&state, image_size)
+        }
+        _ => None,
+    };
+    if options.mode == VerifyMode::Fast && checksums.is_none() {
+        log_info!(MODULE, "No usable write checksums, comparing against image");
+    }
+
     let mut image_buffer = vec![0u8; aligned_chunk_size];
     let mut device_buffer = vec![0u8; aligned_chunk_size];
     let mut verified: u64 = 0;
+    let mut chunk_index = 0;
 
     // Use ProgressTracker for automatic progress logging
     let mut tracker = ProgressTracker::new(
@@ -431,6 +760,7 @@ fn verify_with_sector_alignment(
         image_size,
         config::logging::WRITE_LOG_INTERVAL_MB,
     );
+    let mut speed = speed_window();
 
     while verified < image_size {
         if state.is_cancelled.load(Ordering::SeqCst) {
@@ -439,10 +769,14 @@ fn verify_with_sector_alignment(
 
         let remaining = image_size - verified;
         let read_size = std::cmp::min(aligned_chunk_size as u64, remaining) as usize;
+        let expected_checksum = checksums.as_ref().map(|sums| sums[chunk_index]);
 
-        let image_read = image_file
-            .read(&mut image_buffer[..read_size])
-            .map_err(|e| format!("Failed to read image: {}", e))?;
+        let image_read = match expected_checksum {
+            Some(_) => read_size,
+            None => image_file
+                .read(&mut image_buffer[..read_size])
+                .map_err(|e| format!("Failed to read image: {}", e))?,
+        };
 
         if image_read == 0 {
             break;
@@ -468,27 +802,53 @@ fn verify_with_sector_alignment(
             total_read += n;
         }
 
-        if image_buffer[..image_read] != device_buffer[..image_read] {
-            log_error!(MODULE, "Data mismatch at byte {}", verified);
-
-            for i in 0..std::cmp::min(image_read, 16) {
-                if image_buffer[i] != device_buffer[i] {
-                    log_error!(
-                        MODULE,
-                        "First mismatch at offset {}: expected {:02x}, got {:02x}",
-                        i,
-                        image_buffer[i],
-                        device_buffer[i]
-                    );
-                    break;
-                }
-            }
+        let checksum_ok = expected_checksum.map(|expected| {
+            total_read >= image_read && crc32c::crc32c(&device_buffer[..image_read]) == expected
+        });
+        if checksum_ok == Some(false) {
+            // Read the image chunk to describe the mismatch (checksums imply a raw image)
+            std::fs::File::open(image_path)
+                .and_then(|mut raw| {
+                    raw.seek(SeekFrom::Start(verified))?;
+                    raw.read_exact(&mut image_buffer[..image_read])
+                })
+                .map_err(|e| format!("Failed to read image: {}", e))?;
+        }
+
+        let compared = total_read.min(image_read);
+
+        if checksum_ok == Some(true) {
+            // Chunk matches what was written
+        } else if let Some(mut mismatch) = find_mismatch(
+            verified,
+            &image_buffer[..image_read],
+            &device_buffer[..compared],
+        ) {
+            let description = describe_mismatch(image_path, &mut mismatch);
+            *state
+                .verify_mismatch
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()) = Some(mismatch);
 
-            return Err(format!("Verification failed at byte {}", verified));
+            return Err(format!(
+                "Verification failed: data mismatch {}",
+                description
+            ));
+        } else if checksum_ok == Some(false) {
+            log_error!(MODULE, "Checksum mismatch at byte {}", verified);
+            return Err(format!(
+                "Verification failed: checksum mismatch at byte {}",
+                verified
+            ));
         }
 
         verified += image_read as u64;
+        chunk_index += 1;
         state.verified_bytes.store(verified, Ordering::SeqCst);
+        state
+            .verify_elapsed_ms
+            .store(tracker.elapsed().as_millis() as u64, Ordering::SeqCst);
+        state.record_throughput(&mut speed);
 
         // ProgressTracker handles logging automatically
         tracker.update(image_read as u64);
@@ -496,7 +856,14 @@ fn verify_with_sector_alignment(
 
     // Log final summary
     tracker.finish();
-    Ok(())
+    state
+        .verify_elapsed_ms
+        .store(tracker.elapsed().as_millis() as u64, Ordering::SeqCst);
+
+    match options.tail_check {
+        Some(tail) => tail.verify(device, &state),
+        None => Ok(()),
+    }
 }
 
 /// Retrieves the physical sector size of the device.
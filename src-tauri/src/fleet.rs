@@ -0,0 +1,293 @@
+//! Fleet mode
+//!
+//! An opt-in kiosk mode for preparing many cards in a row: with an image and
+//! first-boot settings chosen, every removable device inserted afterwards that
+//! matches the criteria is flashed, verified, customized, ejected and logged
+//! without further input. Devices present when fleet mode starts are left
+//! alone. This module holds the settings and the reported status; the worker
+//! driven by hotplug events lives in `commands::fleet`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::customization::{Customization, NetworkConfig};
+use crate::devices::BlockDevice;
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_size() -> u64 {
+    config::fleet::DEFAULT_MAX_SIZE_BYTES
+}
+
+/// Which inserted devices fleet mode writes
+///
+/// Only removable, non-system devices are ever considered, and never ones
+/// larger than the upper bound, which cannot be turned off.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FleetCriteria {
+    #[serde(default)]
+    pub min_size_bytes: u64,
+    #[serde(default = "default_max_size")]
+    pub max_size_bytes: u64,
+    /// Bus types to accept, e.g. "usb" or "sd"; empty for any
+    #[serde(default)]
+    pub bus_types: Vec<String>,
+}
+
+impl Default for FleetCriteria {
+    fn default() -> Self {
+        Self {
+            min_size_bytes: 0,
+            max_size_bytes: default_max_size(),
+            bus_types: Vec::new(),
+        }
+    }
+}
+
+impl FleetCriteria {
+    /// Check that the size bounds leave some devices to write
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_size_bytes == 0 {
+            return Err("Fleet mode needs a maximum device size".to_string());
+        }
+        if self.min_size_bytes > self.max_size_bytes {
+            return Err("The minimum device size is above the maximum".to_string());
+        }
+        Ok(())
+    }
+
+    /// Why `device` is not written, None if it matches
+    pub fn rejection(&self, device: &BlockDevice) -> Option<String> {
+        if device.is_system || !device.is_removable {
+            return Some("not a removable device".to_string());
+        }
+        if device.size < self.min_size_bytes {
+            return Some(format!("smaller than {} bytes", self.min_size_bytes));
+        }
+        if device.size > self.max_size_bytes {
+            return Some(format!("larger than {} bytes", self.max_size_bytes));
+        }
+        if !self.bus_types.is_empty()
+            && !self
+                .bus_types
+                .iter()
+                .any(|bus| bus.eq_ignore_ascii_case(&device.device_class()))
+        {
+            return Some(format!("bus {} not selected", device.device_class()));
+        }
+        None
+    }
+}
+
+/// What fleet mode writes to each device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetSettings {
+    /// Local image file, kept until fleet mode stops
+    pub image_path: String,
+    #[serde(default = "default_true")]
+    pub verify: bool,
+    #[serde(default)]
+    pub customization: Option<Customization>,
+    #[serde(default)]
+    pub network: Option<NetworkConfig>,
+    #[serde(default)]
+    pub criteria: FleetCriteria,
+    /// Eject each device once it is done, so it can be pulled right away
+    #[serde(default = "default_true")]
+    pub eject: bool,
+}
+
+/// Where a device is in its fleet job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FleetJobStage {
+    /// Waiting for a free slot, see `config::fleet::MAX_PARALLEL_JOBS`
+    Queued,
+    Flashing,
+    Verifying,
+    Customizing,
+    Ejecting,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl FleetJobStage {
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Done | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// One device handled by fleet mode
+#[derive(Debug, Clone, Serialize)]
+pub struct FleetJob {
+    pub id: String,
+    pub device_path: String,
+    /// Alias or model of the device
+    pub device_name: String,
+    pub serial: Option<String>,
+    pub stage: FleetJobStage,
+    /// Progress of the running flash or verification
+    pub progress_percent: f64,
+    pub error: Option<String>,
+    /// RFC 3339 timestamps
+    pub started_at: String,
+    pub finished_at: Option<String>,
+}
+
+/// State of fleet mode, as shown in the UI
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FleetStatus {
+    pub active: bool,
+    pub image_path: Option<String>,
+    /// RFC 3339 time fleet mode started
+    pub started_at: Option<String>,
+    /// Newest first, at most `config::fleet::MAX_JOBS_KEPT`
+    pub jobs: Vec<FleetJob>,
+    /// Devices done since fleet mode started
+    pub completed: usize,
+    pub failed: usize,
+}
+
+impl FleetStatus {
+    /// Fresh status for a session writing `image_path`
+    pub fn started(image_path: &str) -> Self {
+        Self {
+            active: true,
+            image_path: Some(image_path.to_string()),
+            started_at: Some(chrono::Utc::now().to_rfc3339()),
+            ..Self::default()
+        }
+    }
+
+    /// Add a queued job for a device, dropping the oldest finished jobs
+    pub fn add_job(&mut self, id: &str, device: &BlockDevice) -> FleetJob {
+        let job = FleetJob {
+            id: id.to_string(),
+            device_path: device.path.clone(),
+            device_name: device.alias.clone().unwrap_or_else(|| device.model.clone()),
+            serial: device.serial.clone(),
+            stage: FleetJobStage::Queued,
+            progress_percent: 0.0,
+            error: None,
+            started_at: chrono::Utc::now().to_rfc3339(),
+            finished_at: None,
+        };
+        self.jobs.insert(0, job.clone());
+        while self.jobs.len() > config::fleet::MAX_JOBS_KEPT {
+            match self.jobs.iter().rposition(|job| job.stage.is_finished()) {
+                Some(oldest) => self.jobs.remove(oldest),
+                None => break,
+            };
+        }
+        job
+    }
+
+    /// Whether a job for the device is still running
+    pub fn is_busy(&self, device_path: &str) -> bool {
+        self.jobs
+            .iter()
+            .any(|job| job.device_path == device_path && !job.stage.is_finished())
+    }
+
+    pub fn set_stage(&mut self, id: &str, stage: FleetJobStage) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            job.stage = stage;
+            job.progress_percent = 0.0;
+        }
+    }
+
+    /// Finish a job, counting it as completed or failed
+    pub fn finish(&mut self, id: &str, stage: FleetJobStage, error: Option<String>) {
+        let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) else {
+            return;
+        };
+        if job.stage.is_finished() {
+            return;
+        }
+        job.stage = stage;
+        job.error = error;
+        job.finished_at = Some(chrono::Utc::now().to_rfc3339());
+        match stage {
+            FleetJobStage::Done => self.completed += 1,
+            FleetJobStage::Failed => self.failed += 1,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(path: &str, size: u64) -> BlockDevice {
+        BlockDevice {
+            path: path.to_string(),
+            name: path.trim_start_matches("/dev/").to_string(),
+            size,
+            size_formatted: String::new(),
+            model: "Card".to_string(),
+            is_removable: true,
+            is_system: false,
+            bus_type: Some("USB".to_string()),
+            serial: None,
+            alias: None,
+            usb_bridge: None,
+        }
+    }
+
+    #[test]
+    fn test_criteria() {
+        let gb = 1_000_000_000;
+        let any = FleetCriteria::default();
+        assert_eq!(any.rejection(&card("/dev/sdb", 16 * gb)), None);
+        // Large drives are never written by default
+        assert!(any.rejection(&card("/dev/sdb", 500 * gb)).is_some());
+
+        let mut fixed = card("/dev/sda", 16 * gb);
+        fixed.is_removable = false;
+        assert!(any.rejection(&fixed).is_some());
+
+        let criteria = FleetCriteria {
+            min_size_bytes: 8 * gb,
+            max_size_bytes: 64 * gb,
+            bus_types: vec!["usb".to_string()],
+        };
+        assert_eq!(criteria.rejection(&card("/dev/sdb", 32 * gb)), None);
+        assert!(criteria.rejection(&card("/dev/sdb", 4 * gb)).is_some());
+        assert!(criteria.rejection(&card("/dev/sdb", 128 * gb)).is_some());
+        let mut sd = card("/dev/mmcblk0", 32 * gb);
+        sd.bus_type = Some("SD".to_string());
+        assert!(criteria.rejection(&sd).is_some());
+
+        assert!(criteria.validate().is_ok());
+        let unbounded = FleetCriteria {
+            max_size_bytes: 0,
+            ..FleetCriteria::default()
+        };
+        assert!(unbounded.validate().is_err());
+    }
+
+    #[test]
+    fn test_job_counts() {
+        let mut status = FleetStatus::started("/tmp/image.img");
+        let first = status.add_job("fleet-1", &card("/dev/sdb", 1));
+        let second = status.add_job("fleet-2", &card("/dev/sdc", 1));
+        assert!(status.is_busy("/dev/sdb"));
+
+        status.set_stage(&first.id, FleetJobStage::Flashing);
+        status.finish(&first.id, FleetJobStage::Done, None);
+        status.finish(
+            &second.id,
+            FleetJobStage::Failed,
+            Some("write error".into()),
+        );
+        // Finishing twice counts once
+        status.finish(&second.id, FleetJobStage::Done, None);
+        assert!(!status.is_busy("/dev/sdb"));
+        assert_eq!((status.completed, status.failed), (1, 1));
+        assert_eq!(status.jobs[0].stage, FleetJobStage::Failed);
+    }
+}
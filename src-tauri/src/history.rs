@@ -0,0 +1,694 @@
+//! Operation history module
+//!
+//! Persists measured throughput of past downloads, decompressions, writes
+//! and verifications, so durations of new operations can be estimated.
+//!
+//! Also keeps per-host download statistics (successes, failures, latency
+//! and throughput), so mirrors that keep failing from the user's location
+//! can be spotted.
+//!
+//! Also remembers devices that were verified against an image, so
+//! `verify_device` can skip re-reading a card nothing has written to since.
+//! A remembered verification is only trusted while all of these hold:
+//! - it was recorded by this app session (another program may have written
+//!   to the card while we were not running)
+//! - the device was not removed since (invalidated on unplug)
+//! - we did not start a flash to it since (invalidated on flash start)
+//! - the image file and the device size are unchanged
+//! - it is younger than `config::history::VERIFY_CACHE_MAX_AGE_SECS`
+//!
+//! Thread Safety:
+//! Reads and writes of the history file are serialized by a global Mutex.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::devices::UsbBridge;
+use crate::flash::VerifyMismatch;
+use crate::utils::get_cache_dir;
+use crate::{log_debug, log_warn};
+
+const MODULE: &str = "history";
+
+/// Global mutex to serialize history file access
+static HISTORY_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Stage of an image operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationStage {
+    Download,
+    Decompress,
+    Write,
+    Verify,
+}
+
+/// Measured throughput of a completed operation stage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeedSample {
+    pub stage: OperationStage,
+    /// Device class for write/verify samples (e.g., "usb", "sd", "nvme")
+    pub device_class: Option<String>,
+    /// Bytes produced by the stage (decompressed bytes for decompression)
+    pub bytes: u64,
+    /// Bytes consumed by the stage, when different from `bytes`
+    pub source_bytes: Option<u64>,
+    pub elapsed_ms: u64,
+    /// RFC 3339 timestamp
+    pub recorded_at: String,
+}
+
+/// A flash that failed during verification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlashFailureRecord {
+    /// RFC 3339 timestamp
+    pub recorded_at: String,
+    /// Image file name
+    pub image: String,
+    pub device_path: String,
+    #[serde(default)]
+    pub device_serial: Option<String>,
+    pub device_model: Option<String>,
+    pub device_class: Option<String>,
+    pub device_size: Option<u64>,
+    #[serde(default)]
+    pub image_size: Option<u64>,
+    /// Bytes after the image that were zeroed and checked (wrap-around fakes)
+    #[serde(default)]
+    pub tail_checked_bytes: Option<u64>,
+    pub error: String,
+    /// First mismatch, if the failure was a data comparison
+    pub mismatch: Option<VerifyMismatch>,
+    /// Write throughput in bytes per second
+    #[serde(default)]
+    pub write_speed: Option<f64>,
+    /// Read-back throughput in bytes per second up to the failure
+    #[serde(default)]
+    pub read_speed: Option<f64>,
+    /// USB bridge the device was attached through
+    #[serde(default)]
+    pub usb_bridge: Option<UsbBridge>,
+}
+
+/// Flash outcome counters for one device serial
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeviceOutcomes {
+    pub successes: u32,
+    pub failures: u32,
+    /// Failures since the last successful flash
+    pub consecutive_failures: u32,
+    /// RFC 3339 timestamp of the last failure
+    pub last_failure_at: Option<String>,
+}
+
+/// Reliability summary of a device, keyed by serial
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceReliability {
+    pub serial: String,
+    pub successes: u32,
+    pub failures: u32,
+    pub consecutive_failures: u32,
+    pub last_failure_at: Option<String>,
+    /// Repeated failures on this device suggest the card or reader is at fault
+    pub likely_faulty: bool,
+}
+
+/// Download outcomes for one mirror host
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MirrorOutcomes {
+    pub successes: u32,
+    pub failures: u32,
+    /// Failures since the last successful download
+    pub consecutive_failures: u32,
+    /// Recent time to first response byte in milliseconds, oldest first
+    #[serde(default)]
+    pub latency_ms: Vec<u64>,
+    /// Bytes and time of successful downloads, for the average speed
+    #[serde(default)]
+    pub bytes: u64,
+    #[serde(default)]
+    pub elapsed_ms: u64,
+    /// RFC 3339 timestamps
+    pub last_success_at: Option<String>,
+    pub last_failure_at: Option<String>,
+    pub last_error: Option<String>,
+}
+
+/// Health summary of a mirror host
+#[derive(Debug, Clone, Serialize)]
+pub struct MirrorStats {
+    pub host: String,
+    pub successes: u32,
+    pub failures: u32,
+    pub consecutive_failures: u32,
+    /// Average of the recent latencies
+    pub avg_latency_ms: Option<u64>,
+    /// Average speed of successful downloads in bytes per second
+    pub avg_speed: Option<f64>,
+    pub last_success_at: Option<String>,
+    pub last_failure_at: Option<String>,
+    pub last_error: Option<String>,
+    /// Failed repeatedly or more often than not
+    pub flaky: bool,
+}
+
+/// A successful verification of a device against an image
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerifiedDevice {
+    /// Image file name
+    pub image: String,
+    pub image_size: u64,
+    /// Image modification time in seconds since the Unix epoch
+    pub image_modified: Option<u64>,
+    pub device_size: u64,
+    /// App session that performed the verification
+    pub session: String,
+    /// RFC 3339 timestamp
+    pub verified_at: String,
+}
+
+impl VerifiedDevice {
+    /// Describe a verification of `device_size` bytes against an image file
+    pub fn new(image_path: &Path, device_size: u64) -> Result<Self, String> {
+        let metadata = fs::metadata(image_path)
+            .map_err(|e| format!("Failed to read image metadata: {}", e))?;
+        let image_modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+        Ok(Self {
+            image: image_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            image_size: metadata.len(),
+            image_modified,
+            device_size,
+            session: SESSION_ID.clone(),
+            verified_at: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Whether this entry still vouches for `candidate` at `now`
+    fn vouches_for(&self, candidate: &VerifiedDevice, now: chrono::DateTime<chrono::Utc>) -> bool {
+        let fresh = chrono::DateTime::parse_from_rfc3339(&self.verified_at)
+            .map(|at| {
+                let age = now.signed_duration_since(at).num_seconds();
+                (0..config::history::VERIFY_CACHE_MAX_AGE_SECS).contains(&age)
+            })
+            .unwrap_or(false);
+
+        fresh
+            && self.session == candidate.session
+            && self.image == candidate.image
+            && self.image_size == candidate.image_size
+            && self.image_modified == candidate.image_modified
+            && self.device_size == candidate.device_size
+    }
+}
+
+/// Persisted operation history
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct History {
+    #[serde(default)]
+    pub samples: Vec<SpeedSample>,
+    #[serde(default)]
+    pub flash_failures: Vec<FlashFailureRecord>,
+    /// Flash outcomes per device serial
+    #[serde(default)]
+    pub device_outcomes: BTreeMap<String, DeviceOutcomes>,
+    /// Last successful verification per device serial
+    #[serde(default)]
+    pub verified_devices: BTreeMap<String, VerifiedDevice>,
+    /// Download outcomes per mirror host
+    #[serde(default)]
+    pub mirror_outcomes: BTreeMap<String, MirrorOutcomes>,
+}
+
+impl History {
+    /// Average throughput in bytes per second of recent samples for a stage
+    ///
+    /// Samples from the given device class are preferred; if there are none,
+    /// samples from any device are used.
+    pub fn average_speed(&self, stage: OperationStage, device_class: Option<&str>) -> Option<f64> {
+        let matching = |class_filter: Option<&str>| {
+            let (bytes, ms) = self
+                .samples
+                .iter()
+                .rev()
+                .filter(|s| s.stage == stage && s.elapsed_ms > 0)
+                .filter(|s| class_filter.map_or(true, |c| s.device_class.as_deref() == Some(c)))
+                .take(config::history::SPEED_SAMPLE_WINDOW)
+                .fold((0u64, 0u64), |(b, t), s| (b + s.bytes, t + s.elapsed_ms));
+            (ms > 0).then(|| bytes as f64 / (ms as f64 / 1000.0))
+        };
+
+        device_class
+            .and_then(|class| matching(Some(class)))
+            .or_else(|| matching(None))
+    }
+
+    /// Average ratio of decompressed to compressed size
+    pub fn compression_ratio(&self) -> Option<f64> {
+        let (bytes, source) = self
+            .samples
+            .iter()
+            .rev()
+            .filter(|s| s.stage == OperationStage::Decompress)
+            .filter_map(|s| s.source_bytes.filter(|&b| b > 0).map(|b| (s.bytes, b)))
+            .take(config::history::SPEED_SAMPLE_WINDOW)
+            .fold((0u64, 0u64), |(a, b), (x, y)| (a + x, b + y));
+        (source > 0).then(|| bytes as f64 / source as f64)
+    }
+
+    /// Reliability summary for a device serial
+    pub fn device_reliability(&self, serial: &str) -> DeviceReliability {
+        let outcomes = self
+            .device_outcomes
+            .get(serial)
+            .cloned()
+            .unwrap_or_default();
+        DeviceReliability {
+            serial: serial.to_string(),
+            successes: outcomes.successes,
+            failures: outcomes.failures,
+            consecutive_failures: outcomes.consecutive_failures,
+            last_failure_at: outcomes.last_failure_at,
+            likely_faulty: outcomes.consecutive_failures
+                >= config::history::FAULTY_DEVICE_CONSECUTIVE_FAILURES,
+        }
+    }
+
+    /// Health of every mirror downloaded from, healthiest first
+    ///
+    /// Mirrors are ordered by consecutive failures, then failure share, then latency.
+    pub fn mirror_stats(&self) -> Vec<MirrorStats> {
+        let mut stats: Vec<MirrorStats> = self
+            .mirror_outcomes
+            .iter()
+            .map(|(host, outcomes)| {
+                let attempts = outcomes.successes + outcomes.failures;
+                MirrorStats {
+                    host: host.clone(),
+                    successes: outcomes.successes,
+                    failures: outcomes.failures,
+                    consecutive_failures: outcomes.consecutive_failures,
+                    avg_latency_ms: (!outcomes.latency_ms.is_empty()).then(|| {
+                        outcomes.latency_ms.iter().sum::<u64>() / outcomes.latency_ms.len() as u64
+                    }),
+                    avg_speed: (outcomes.elapsed_ms > 0)
+                        .then(|| outcomes.bytes as f64 / (outcomes.elapsed_ms as f64 / 1000.0)),
+                    last_success_at: outcomes.last_success_at.clone(),
+                    last_failure_at: outcomes.last_failure_at.clone(),
+                    last_error: outcomes.last_error.clone(),
+                    flaky: outcomes.consecutive_failures
+                        >= config::history::FLAKY_MIRROR_CONSECUTIVE_FAILURES
+                        || (attempts >= config::history::FLAKY_MIRROR_MIN_ATTEMPTS
+                            && outcomes.failures > outcomes.successes),
+                }
+            })
+            .collect();
+
+        let failure_share =
+            |s: &MirrorStats| s.failures as f64 / (s.successes + s.failures).max(1) as f64;
+        stats.sort_by(|a, b| {
+            a.consecutive_failures
+                .cmp(&b.consecutive_failures)
+                .then(failure_share(a).total_cmp(&failure_share(b)))
+                .then(
+                    a.avg_latency_ms
+                        .unwrap_or(u64::MAX)
+                        .cmp(&b.avg_latency_ms.unwrap_or(u64::MAX)),
+                )
+        });
+        stats
+    }
+
+    /// Stored verification of a device serial that still vouches for `candidate`
+    pub fn cached_verification(
+        &self,
+        serial: &str,
+        candidate: &VerifiedDevice,
+    ) -> Option<&VerifiedDevice> {
+        self.verified_devices
+            .get(serial)
+            .filter(|entry| entry.vouches_for(candidate, chrono::Utc::now()))
+    }
+}
+
+/// Identifies the running app instance, see `VerifiedDevice::session`
+static SESSION_ID: Lazy<String> = Lazy::new(|| {
+    format!(
+        "{}-{}",
+        std::process::id(),
+        chrono::Utc::now().timestamp_millis()
+    )
+});
+
+/// Get the history file path
+fn history_path() -> PathBuf {
+    get_cache_dir(config::app::NAME).join(config::history::FILE_NAME)
+}
+
+fn load_internal() -> History {
+    let path = history_path();
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            log_warn!(MODULE, "Ignoring unreadable history file: {}", e);
+            History::default()
+        }),
+        Err(_) => History::default(),
+    }
+}
+
+fn save_internal(history: &History) -> Result<(), String> {
+    let path = history_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create history directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(history)
+        .map_err(|e| format!("Failed to serialize history: {}", e))?;
+    fs::write(&path, content).map_err(|e| format!("Failed to write history: {}", e))
+}
+
+/// Load the operation history (empty if missing or unreadable)
+pub fn load_history() -> History {
+    let _lock = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load_internal()
+}
+
+/// Load, modify and save the history under the lock
+pub fn update_history(f: impl FnOnce(&mut History)) {
+    let _lock = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut history = load_internal();
+    f(&mut history);
+    if let Err(e) = save_internal(&history) {
+        log_warn!(MODULE, "{}", e);
+    }
+}
+
+/// Record the throughput of a completed stage
+///
+/// Very short operations are ignored since they don't give meaningful speeds.
+pub fn record_speed(
+    stage: OperationStage,
+    device_class: Option<&str>,
+    bytes: u64,
+    source_bytes: Option<u64>,
+    elapsed: Duration,
+) {
+    let elapsed_ms = elapsed.as_millis() as u64;
+    if bytes < config::history::MIN_SAMPLE_BYTES || elapsed_ms == 0 {
+        return;
+    }
+
+    log_debug!(
+        MODULE,
+        "Recording {:?} sample: {} bytes in {} ms (device class: {:?})",
+        stage,
+        bytes,
+        elapsed_ms,
+        device_class
+    );
+
+    let sample = SpeedSample {
+        stage,
+        device_class: device_class.map(|c| c.to_string()),
+        bytes,
+        source_bytes,
+        elapsed_ms,
+        recorded_at: chrono::Utc::now().to_rfc3339(),
+    };
+
+    update_history(|history| {
+        history.samples.push(sample);
+        let excess = history
+            .samples
+            .len()
+            .saturating_sub(config::history::MAX_SAMPLES);
+        history.samples.drain(..excess);
+    });
+}
+
+/// Record the outcome of a flash for a device serial
+///
+/// Returns the updated reliability summary of the device.
+pub fn record_flash_outcome(serial: &str, success: bool) -> DeviceReliability {
+    let mut reliability = None;
+    update_history(|history| {
+        let outcomes = history
+            .device_outcomes
+            .entry(serial.to_string())
+            .or_default();
+        if success {
+            outcomes.successes += 1;
+            outcomes.consecutive_failures = 0;
+        } else {
+            outcomes.failures += 1;
+            outcomes.consecutive_failures += 1;
+            outcomes.last_failure_at = Some(chrono::Utc::now().to_rfc3339());
+        }
+        reliability = Some(history.device_reliability(serial));
+    });
+    reliability.unwrap_or_else(|| History::default().device_reliability(serial))
+}
+
+/// Record a completed download from a mirror host
+///
+/// `latency` is the time to the response headers, `elapsed` the transfer time.
+pub fn record_mirror_success(host: &str, latency: Duration, bytes: u64, elapsed: Duration) {
+    update_history(|history| {
+        let outcomes = history.mirror_outcomes.entry(host.to_string()).or_default();
+        outcomes.successes += 1;
+        outcomes.consecutive_failures = 0;
+        outcomes.last_success_at = Some(chrono::Utc::now().to_rfc3339());
+        outcomes.latency_ms.push(latency.as_millis() as u64);
+        let excess = outcomes
+            .latency_ms
+            .len()
+            .saturating_sub(config::history::MIRROR_LATENCY_WINDOW);
+        outcomes.latency_ms.drain(..excess);
+        outcomes.bytes += bytes;
+        outcomes.elapsed_ms += elapsed.as_millis() as u64;
+    });
+}
+
+/// Record a download from a mirror host that failed
+pub fn record_mirror_failure(host: &str, error: &str) {
+    log_debug!(
+        MODULE,
+        "Recording download failure from {}: {}",
+        host,
+        error
+    );
+    update_history(|history| {
+        let outcomes = history.mirror_outcomes.entry(host.to_string()).or_default();
+        outcomes.failures += 1;
+        outcomes.consecutive_failures += 1;
+        outcomes.last_failure_at = Some(chrono::Utc::now().to_rfc3339());
+        outcomes.last_error = Some(error.to_string());
+    });
+}
+
+/// Remember a successful verification of a device serial
+pub fn record_verified_device(serial: &str, entry: VerifiedDevice) {
+    log_debug!(
+        MODULE,
+        "Remembering verification of {} against {}",
+        serial,
+        entry.image
+    );
+    update_history(|history| {
+        history.verified_devices.insert(serial.to_string(), entry);
+    });
+}
+
+/// Forget the stored verification of a device serial
+pub fn invalidate_verification(serial: &str, reason: &str) {
+    update_history(|history| {
+        if history.verified_devices.remove(serial).is_some() {
+            log_debug!(
+                MODULE,
+                "Invalidated stored verification of {}: {}",
+                serial,
+                reason
+            );
+        }
+    });
+}
+
+/// Record a verification failure
+pub fn record_flash_failure(record: FlashFailureRecord) {
+    update_history(|history| {
+        history.flash_failures.push(record);
+        let excess = history
+            .flash_failures
+            .len()
+            .saturating_sub(config::history::MAX_FAILURE_RECORDS);
+        history.flash_failures.drain(..excess);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(stage: OperationStage, class: Option<&str>, bytes: u64, ms: u64) -> SpeedSample {
+        SpeedSample {
+            stage,
+            device_class: class.map(|c| c.to_string()),
+            bytes,
+            source_bytes: None,
+            elapsed_ms: ms,
+            recorded_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_average_speed_prefers_device_class() {
+        let history = History {
+            samples: vec![
+                sample(OperationStage::Write, Some("usb"), 100_000_000, 10_000),
+                sample(OperationStage::Write, Some("sd"), 20_000_000, 10_000),
+                sample(OperationStage::Download, None, 50_000_000, 1_000),
+            ],
+            ..Default::default()
+        };
+
+        let sd = history
+            .average_speed(OperationStage::Write, Some("sd"))
+            .unwrap();
+        assert_eq!(sd, 2_000_000.0);
+
+        // Unknown class falls back to all write samples
+        let any = history
+            .average_speed(OperationStage::Write, Some("nvme"))
+            .unwrap();
+        assert_eq!(any, 6_000_000.0);
+
+        assert!(history
+            .average_speed(OperationStage::Verify, None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_mirror_stats() {
+        let mut history = History::default();
+        let mirror =
+            |successes, failures, consecutive_failures, latency_ms: Vec<u64>| MirrorOutcomes {
+                successes,
+                failures,
+                consecutive_failures,
+                latency_ms,
+                bytes: 100_000_000,
+                elapsed_ms: 10_000,
+                ..MirrorOutcomes::default()
+            };
+        history
+            .mirror_outcomes
+            .insert("slow.example".to_string(), mirror(5, 0, 0, vec![400, 600]));
+        history
+            .mirror_outcomes
+            .insert("fast.example".to_string(), mirror(5, 0, 0, vec![50]));
+        history
+            .mirror_outcomes
+            .insert("broken.example".to_string(), mirror(3, 2, 2, vec![80]));
+        history
+            .mirror_outcomes
+            .insert("shaky.example".to_string(), mirror(1, 3, 1, vec![80]));
+
+        let stats = history.mirror_stats();
+        let hosts: Vec<&str> = stats.iter().map(|s| s.host.as_str()).collect();
+        assert_eq!(
+            hosts,
+            vec![
+                "fast.example",
+                "slow.example",
+                "shaky.example",
+                "broken.example"
+            ]
+        );
+        assert_eq!(stats[1].avg_latency_ms, Some(500));
+        assert_eq!(stats[0].avg_speed, Some(10_000_000.0));
+        assert!(!stats[0].flaky);
+        assert!(stats[2].flaky);
+        assert!(stats[3].flaky);
+    }
+
+    #[test]
+    fn test_device_reliability() {
+        let mut history = History::default();
+        assert!(!history.device_reliability("ABC123").likely_faulty);
+
+        history.device_outcomes.insert(
+            "ABC123".to_string(),
+            DeviceOutcomes {
+                successes: 4,
+                failures: 2,
+                consecutive_failures: 2,
+                last_failure_at: None,
+            },
+        );
+        let reliability = history.device_reliability("ABC123");
+        assert_eq!(reliability.successes, 4);
+        assert!(reliability.likely_faulty);
+    }
+
+    #[test]
+    fn test_cached_verification() {
+        let entry = VerifiedDevice {
+            image: "Armbian.img".to_string(),
+            image_size: 1_000,
+            image_modified: Some(42),
+            device_size: 32_000,
+            session: SESSION_ID.clone(),
+            verified_at: chrono::Utc::now().to_rfc3339(),
+        };
+        let mut history = History::default();
+        history
+            .verified_devices
+            .insert("ABC123".to_string(), entry.clone());
+
+        assert!(history.cached_verification("ABC123", &entry).is_some());
+        assert!(history.cached_verification("XYZ", &entry).is_none());
+
+        let changed_image = VerifiedDevice {
+            image_modified: Some(43),
+            ..entry.clone()
+        };
+        assert!(history
+            .cached_verification("ABC123", &changed_image)
+            .is_none());
+
+        let other_session = VerifiedDevice {
+            session: "previous".to_string(),
+            ..entry.clone()
+        };
+        assert!(!other_session.vouches_for(&entry, chrono::Utc::now()));
+
+        let stale = chrono::Utc::now()
+            + chrono::Duration::seconds(config::history::VERIFY_CACHE_MAX_AGE_SECS);
+        assert!(!entry.vouches_for(&entry, stale));
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        let mut decompress = sample(OperationStage::Decompress, None, 4_000, 10);
+        decompress.source_bytes = Some(1_000);
+        let history = History {
+            samples: vec![decompress],
+            ..Default::default()
+        };
+        assert_eq!(history.compression_ratio(), Some(4.0));
+        assert_eq!(History::default().compression_ratio(), None);
+    }
+}
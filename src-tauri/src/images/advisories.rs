@@ -0,0 +1,256 @@
+//! Advisories for previously flashed images
+//!
+//! Joins the images written earlier, as recorded in the audit log, with the
+//! current catalog to nudge returning users about boards they set up before:
+//! the board has reached end of support, the distribution release went EOL
+//! (see `config::images::EOL_RELEASES`), or a fix release of the same
+//! Armbian version line was published since.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::filename::{compare_versions, parse_image_filename};
+use super::filters::{find_latest_image, get_unique_boards};
+use super::models::{ArmbianImage, ImageInfo, SupportLevel};
+use crate::config;
+use crate::utils::normalize_slug;
+
+/// An image written in an earlier flash
+#[derive(Debug, Clone)]
+pub struct FlashedImage {
+    /// Path or file name of the image
+    pub image: String,
+    /// RFC 3339 time of the flash
+    pub flashed_at: String,
+    pub device_model: Option<String>,
+}
+
+/// Why a previously flashed image deserves attention
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AdvisoryReason {
+    /// The board is no longer supported by Armbian
+    BoardEndOfSupport,
+    /// The distribution release reached its end of life on `since`
+    ReleaseEndOfLife { release: String, since: String },
+    /// A newer point release of the same version line fixes the flashed one
+    FixRelease { version: String },
+}
+
+/// A previously flashed image with the reasons to revisit it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardAdvisory {
+    /// File name of the flashed image
+    pub image: String,
+    pub board_slug: String,
+    /// Catalog name of the board, or the name from the filename
+    pub board_name: String,
+    pub version: String,
+    pub distro_release: Option<String>,
+    /// RFC 3339 time of the most recent flash of the image
+    pub flashed_at: String,
+    pub device_model: Option<String>,
+    pub reasons: Vec<AdvisoryReason>,
+    /// Newest catalog image for the same board, release and branch
+    pub latest_image: Option<ImageInfo>,
+}
+
+/// Advisories for the flashed images, newest flash first
+///
+/// `flashes` is oldest first, like the audit log. Each image is reported
+/// once, for its most recent flash. Images that don't follow the Armbian
+/// naming convention are skipped.
+pub fn flashed_board_advisories(
+    flashes: &[FlashedImage],
+    images: &[ArmbianImage],
+    today: chrono::NaiveDate,
+) -> Vec<BoardAdvisory> {
+    let boards = get_unique_boards(images);
+    let mut seen = HashSet::new();
+    let mut advisories = Vec::new();
+
+    for flash in flashes.iter().rev() {
+        let Some(parsed) = parse_image_filename(&flash.image) else {
+            continue;
+        };
+        let file_name = flash
+            .image
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(&flash.image)
+            .to_string();
+        if !seen.insert(file_name.clone()) {
+            continue;
+        }
+
+        let board_slug = normalize_slug(&parsed.board);
+        let board = boards
+            .iter()
+            .find(|b| normalize_slug(&b.slug) == board_slug);
+        let latest = find_latest_image(
+            images,
+            &board_slug,
+            parsed.distro_release.as_deref(),
+            parsed.kernel_branch.as_deref(),
+        );
+
+        let mut reasons = Vec::new();
+        if board.is_some_and(|b| b.support_level == Some(SupportLevel::Eos)) {
+            reasons.push(AdvisoryReason::BoardEndOfSupport);
+        }
+        if let Some(reason) = parsed
+            .distro_release
+            .as_deref()
+            .and_then(|release| release_end_of_life(release, today))
+        {
+            reasons.push(reason);
+        }
+        if let Some(latest) = latest
+            .as_ref()
+            .filter(|img| is_fix_release(&parsed.version, &img.armbian_version))
+        {
+            reasons.push(AdvisoryReason::FixRelease {
+                version: latest.armbian_version.clone(),
+            });
+        }
+        if reasons.is_empty() {
+            continue;
+        }
+
+        advisories.push(BoardAdvisory {
+            image: file_name,
+            board_name: board
+                .map(|b| b.name.clone())
+                .unwrap_or_else(|| parsed.board.clone()),
+            board_slug,
+            version: parsed.version,
+            distro_release: parsed.distro_release,
+            flashed_at: flash.flashed_at.clone(),
+            device_model: flash.device_model.clone(),
+            reasons,
+            latest_image: latest,
+        });
+    }
+    advisories
+}
+
+/// EOL reason for a distribution release whose end of life has passed
+fn release_end_of_life(release: &str, today: chrono::NaiveDate) -> Option<AdvisoryReason> {
+    config::images::EOL_RELEASES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(release))
+        .filter(|(_, since)| {
+            chrono::NaiveDate::parse_from_str(since, "%Y-%m-%d").is_ok_and(|date| date <= today)
+        })
+        .map(|(name, since)| AdvisoryReason::ReleaseEndOfLife {
+            release: name.to_string(),
+            since: since.to_string(),
+        })
+}
+
+/// Whether `latest` is a newer point release in the version line of `flashed`
+///
+/// Armbian point releases (25.2.1 after 25.2.0) only carry fixes, so one
+/// showing up is worth a nudge; a new version line is a regular upgrade.
+fn is_fix_release(flashed: &str, latest: &str) -> bool {
+    let line = |version: &str| {
+        version
+            .trim_start_matches('v')
+            .split(['.', '-'])
+            .take(2)
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    line(flashed) == line(latest) && compare_versions(latest, flashed).is_gt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog_image(version: &str, distro: &str, support: &str) -> ArmbianImage {
+        serde_json::from_value(serde_json::json!({
+            "board_slug": "nanopi-r5s",
+            "board_name": "NanoPi R5S",
+            "armbian_version": version,
+            "distro": distro,
+            "branch": "current",
+            "file_url": format!("https://dl.armbian.com/Armbian_{}_Nanopi-r5s_{}_current.img.xz", version, distro),
+            "download_repository": "archive",
+            "board_support": support,
+        }))
+        .unwrap()
+    }
+
+    fn flashed(image: &str, at: &str) -> FlashedImage {
+        FlashedImage {
+            image: image.to_string(),
+            flashed_at: at.to_string(),
+            device_model: None,
+        }
+    }
+
+    #[test]
+    fn test_flashed_board_advisories() {
+        let today = chrono::NaiveDate::from_ymd_opt(2026, 10, 15).unwrap();
+        let images = vec![
+            catalog_image("25.2.3", "bookworm", "eos"),
+            catalog_image("25.5.1", "noble", "eos"),
+        ];
+        let flashes = vec![
+            flashed(
+                "/cache/Armbian_25.2.1_Nanopi-r5s_bookworm_current.img.xz",
+                "2026-03-01T10:00:00Z",
+            ),
+            flashed(
+                "/cache/Armbian_24.2.1_Nanopi-r5s_bullseye_current.img.xz",
+                "2026-03-02T10:00:00Z",
+            ),
+            flashed("debian-12-custom.img", "2026-03-03T10:00:00Z"),
+            flashed(
+                "/cache/Armbian_25.2.1_Nanopi-r5s_bookworm_current.img.xz",
+                "2026-03-04T10:00:00Z",
+            ),
+        ];
+
+        let advisories = flashed_board_advisories(&flashes, &images, today);
+        assert_eq!(advisories.len(), 2);
+
+        // Reported once, for the most recent flash
+        let bookworm = &advisories[0];
+        assert_eq!(bookworm.flashed_at, "2026-03-04T10:00:00Z");
+        assert_eq!(bookworm.board_name, "NanoPi R5S");
+        assert_eq!(
+            bookworm.reasons,
+            vec![
+                AdvisoryReason::BoardEndOfSupport,
+                AdvisoryReason::FixRelease {
+                    version: "25.2.3".to_string()
+                },
+            ]
+        );
+
+        assert_eq!(
+            advisories[1].reasons,
+            vec![
+                AdvisoryReason::BoardEndOfSupport,
+                AdvisoryReason::ReleaseEndOfLife {
+                    release: "bullseye".to_string(),
+                    since: "2026-08-31".to_string()
+                },
+            ]
+        );
+        // Not EOL yet on the day before
+        let earlier = chrono::NaiveDate::from_ymd_opt(2026, 8, 30).unwrap();
+        assert_eq!(release_end_of_life("bullseye", earlier), None);
+    }
+
+    #[test]
+    fn test_is_fix_release() {
+        assert!(is_fix_release("25.2.1", "25.2.3"));
+        assert!(!is_fix_release("25.2.1", "25.5.1"));
+        assert!(!is_fix_release("25.2.3", "25.2.3"));
+        assert!(!is_fix_release("25.2.3", "25.2.1"));
+    }
+}
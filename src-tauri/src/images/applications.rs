@@ -0,0 +1,122 @@
+//! Preinstalled application metadata
+//!
+//! Maps the API `file_application` identifiers to display metadata
+//! so the image chooser can present application images meaningfully.
+
+use serde::Serialize;
+
+/// Category of a preinstalled application
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplicationCategory {
+    HomeAutomation,
+    Storage,
+    Cloud,
+    Security,
+    Networking,
+    Media,
+}
+
+/// Display metadata for a preinstalled application
+#[derive(Debug, Clone, Serialize)]
+pub struct ApplicationInfo {
+    /// Identifier as used in the API `file_application` field
+    pub id: &'static str,
+    /// Alternative identifiers seen in the API for the same application
+    pub aliases: &'static [&'static str],
+    pub display_name: &'static str,
+    pub description: &'static str,
+    pub category: ApplicationCategory,
+    /// Icon slug for the frontend logo lookup
+    pub icon_slug: &'static str,
+}
+
+/// Known preinstalled applications
+const APPLICATIONS: &[ApplicationInfo] = &[
+    ApplicationInfo {
+        id: "homeassistant",
+        aliases: &["home-assistant", "hass"],
+        display_name: "Home Assistant",
+        description: "Open source home automation that puts local control and privacy first",
+        category: ApplicationCategory::HomeAutomation,
+        icon_slug: "homeassistant",
+    },
+    ApplicationInfo {
+        id: "openhab",
+        aliases: &[],
+        display_name: "openHAB",
+        description: "Vendor and technology agnostic open source home automation platform",
+        category: ApplicationCategory::HomeAutomation,
+        icon_slug: "openhab",
+    },
+    ApplicationInfo {
+        id: "openmediavault",
+        aliases: &["omv"],
+        display_name: "OpenMediaVault",
+        description: "Network attached storage (NAS) solution with a web administration interface",
+        category: ApplicationCategory::Storage,
+        icon_slug: "openmediavault",
+    },
+    ApplicationInfo {
+        id: "kali",
+        aliases: &["kali-linux"],
+        display_name: "Kali Linux",
+        description: "Penetration testing and security auditing tools",
+        category: ApplicationCategory::Security,
+        icon_slug: "kali",
+    },
+    ApplicationInfo {
+        id: "pihole",
+        aliases: &["pi-hole"],
+        display_name: "Pi-hole",
+        description: "Network-wide ad blocking DNS sinkhole",
+        category: ApplicationCategory::Networking,
+        icon_slug: "pihole",
+    },
+    ApplicationInfo {
+        id: "nextcloud",
+        aliases: &[],
+        display_name: "Nextcloud",
+        description: "Self-hosted file sync, sharing and collaboration platform",
+        category: ApplicationCategory::Cloud,
+        icon_slug: "nextcloud",
+    },
+    ApplicationInfo {
+        id: "openwrt",
+        aliases: &[],
+        display_name: "OpenWrt",
+        description: "Linux-based router and network appliance firmware",
+        category: ApplicationCategory::Networking,
+        icon_slug: "openwrt",
+    },
+    ApplicationInfo {
+        id: "kodi",
+        aliases: &[],
+        display_name: "Kodi",
+        description: "Media center for playing videos, music and pictures",
+        category: ApplicationCategory::Media,
+        icon_slug: "kodi",
+    },
+];
+
+/// Get all known preinstalled applications
+pub fn application_catalog() -> &'static [ApplicationInfo] {
+    APPLICATIONS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_ids_unique() {
+        let mut ids: Vec<&str> = application_catalog()
+            .iter()
+            .flat_map(|app| std::iter::once(app.id).chain(app.aliases.iter().copied()))
+            .collect();
+        let total = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), total);
+    }
+}
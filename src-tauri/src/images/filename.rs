@@ -0,0 +1,129 @@
+//! Armbian image filename parsing
+//!
+//! Helpers for extracting metadata from the Armbian naming convention:
+//! `Armbian_VERSION_BOARD_DISTRO_BRANCH_KERNEL[_FLAVOR].img.xz`
+
+use std::cmp::Ordering;
+
+use crate::utils::strip_compression_ext;
+
+/// Metadata extracted from an Armbian image filename
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedImageName {
+    /// Armbian version (e.g., "25.2.1")
+    pub version: String,
+    /// Board name as written in the filename (e.g., "Orangepi5")
+    pub board: String,
+    /// Distribution release (e.g., "bookworm")
+    pub distro_release: Option<String>,
+    /// Kernel branch (e.g., "current", "vendor")
+    pub kernel_branch: Option<String>,
+}
+
+/// Parse an Armbian image filename (a path is accepted, only the file name is used)
+///
+/// Returns None if the filename does not follow the Armbian naming convention.
+pub fn parse_image_filename(filename: &str) -> Option<ParsedImageName> {
+    let filename_only = filename.rsplit(['/', '\\']).next().unwrap_or(filename);
+
+    let stem = strip_compression_ext(filename_only);
+    let stem = stem.strip_suffix(".img").unwrap_or(stem);
+
+    let parts: Vec<&str> = stem.split('_').collect();
+    if parts.len() < 4 || !parts[0].eq_ignore_ascii_case("Armbian") {
+        return None;
+    }
+
+    let non_empty = |s: Option<&&str>| s.filter(|s| !s.is_empty()).map(|s| s.to_string());
+
+    Some(ParsedImageName {
+        version: parts[1].to_string(),
+        board: parts[2].to_string(),
+        distro_release: non_empty(parts.get(3)),
+        kernel_branch: non_empty(parts.get(4)),
+    })
+}
+
+/// Compare two Armbian version strings (e.g., "24.11.1" vs "25.2.0-trunk.12")
+///
+/// Numeric components are compared numerically. A release version ranks
+/// above a pre-release suffix of the same base version.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a_base, a_suffix) = split_version(a);
+    let (b_base, b_suffix) = split_version(b);
+
+    let a_parts = numeric_parts(a_base);
+    let b_parts = numeric_parts(b_base);
+
+    for i in 0..a_parts.len().max(b_parts.len()) {
+        let x = a_parts.get(i).copied().unwrap_or(0);
+        let y = b_parts.get(i).copied().unwrap_or(0);
+        match x.cmp(&y) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+    }
+
+    match (a_suffix, b_suffix) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(x), Some(y)) => numeric_parts(x).cmp(&numeric_parts(y)).then(x.cmp(y)),
+    }
+}
+
+fn split_version(version: &str) -> (&str, Option<&str>) {
+    let version = version.trim().trim_start_matches('v');
+    match version.split_once('-') {
+        Some((base, suffix)) => (base, Some(suffix)),
+        None => (version, None),
+    }
+}
+
+fn numeric_parts(version: &str) -> Vec<u64> {
+    version
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| s.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_image_filename() {
+        let parsed =
+            parse_image_filename("Armbian_25.2.1_Orangepi5_bookworm_vendor_6.1.99_minimal.img.xz")
+                .unwrap();
+        assert_eq!(parsed.version, "25.2.1");
+        assert_eq!(parsed.board, "Orangepi5");
+        assert_eq!(parsed.distro_release.as_deref(), Some("bookworm"));
+        assert_eq!(parsed.kernel_branch.as_deref(), Some("vendor"));
+
+        let parsed = parse_image_filename("/tmp/cache/Armbian_24.5.0_Rock-5b_noble.img").unwrap();
+        assert_eq!(parsed.board, "Rock-5b");
+        assert_eq!(parsed.kernel_branch, None);
+
+        assert!(parse_image_filename("ubuntu-24.04-preinstalled.img.xz").is_none());
+        assert!(parse_image_filename("Armbian_25.2.1.img").is_none());
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("25.2.1", "25.2.1"), Ordering::Equal);
+        assert_eq!(compare_versions("25.2.1", "24.11.1"), Ordering::Greater);
+        assert_eq!(compare_versions("24.11.1", "24.2.10"), Ordering::Greater);
+        assert_eq!(compare_versions("25.2", "25.2.1"), Ordering::Less);
+        assert_eq!(
+            compare_versions("25.2.0-trunk.12", "25.2.0"),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_versions("25.2.0-trunk.12", "25.2.0-trunk.9"),
+            Ordering::Greater
+        );
+        assert_eq!(compare_versions("v25.2.1", "25.2.1"), Ordering::Equal);
+    }
+}
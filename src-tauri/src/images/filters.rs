@@ -2,13 +2,20 @@
 //!
 //! Functions for parsing and filtering image data.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::config;
 use crate::log_info;
 use crate::utils::normalize_slug;
 
-use super::models::{ArmbianImage, BoardInfo, ImageInfo};
+use super::filename::compare_versions;
+use super::models::{
+    ArmbianImage, BoardInfo, BoardList, CatalogDiagnostics, ImageInfo, RepositoryKind,
+    SupportLevel, SupportTier,
+};
+
+/// Maximum number of parse error messages kept in catalog diagnostics
+const MAX_SAMPLE_ERRORS: usize = 5;
 
 /// Capitalize vendor ID for display (e.g., "rockchip" -> "Rockchip", "intel-amd" -> "Intel-Amd")
 fn capitalize_vendor(vendor: &str) -> String {
@@ -41,33 +48,71 @@ fn is_valid_image_extension(ext: &str) -> bool {
 
 /// Extract all image objects from the nested JSON structure
 pub fn extract_images(json: &serde_json::Value) -> Vec<ArmbianImage> {
+    extract_images_with_diagnostics(json).0
+}
+
+/// Extract all image objects, counting records that were skipped or failed to parse
+pub fn extract_images_with_diagnostics(
+    json: &serde_json::Value,
+) -> (Vec<ArmbianImage>, CatalogDiagnostics) {
     let mut images = Vec::new();
-    extract_images_recursive(json, &mut images);
-    images
+    let mut diagnostics = CatalogDiagnostics {
+        schema_version: detect_schema_version(json),
+        ..Default::default()
+    };
+    extract_images_recursive(json, &mut images, &mut diagnostics);
+    diagnostics.parsed = images.len();
+    (images, diagnostics)
 }
 
-fn extract_images_recursive(value: &serde_json::Value, images: &mut Vec<ArmbianImage>) {
+/// Read an explicit schema version from the catalog root, if the API provides one
+fn detect_schema_version(json: &serde_json::Value) -> Option<String> {
+    ["schema_version", "schemaVersion", "version"]
+        .iter()
+        .find_map(|key| match json.get(key)? {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+}
+
+fn extract_images_recursive(
+    value: &serde_json::Value,
+    images: &mut Vec<ArmbianImage>,
+    diagnostics: &mut CatalogDiagnostics,
+) {
     match value {
         serde_json::Value::Object(map) => {
             if map.contains_key("board_slug") {
-                if let Ok(img) = serde_json::from_value::<ArmbianImage>(value.clone()) {
-                    if let Some(ref ext) = img.file_extension {
-                        if is_valid_image_extension(ext) {
-                            let kernel = img.kernel_branch.as_deref().unwrap_or("");
-                            if kernel != "cloud" {
-                                images.push(img);
-                            }
+                diagnostics.records += 1;
+                match serde_json::from_value::<ArmbianImage>(value.clone()) {
+                    Ok(img) => {
+                        let is_image = img
+                            .file_extension
+                            .as_deref()
+                            .is_some_and(is_valid_image_extension);
+                        let kernel = img.kernel_branch.as_deref().unwrap_or("");
+                        if is_image && kernel != "cloud" {
+                            images.push(img);
+                        } else {
+                            diagnostics.skipped += 1;
+                        }
+                    }
+                    Err(e) => {
+                        diagnostics.errors += 1;
+                        if diagnostics.sample_errors.len() < MAX_SAMPLE_ERRORS {
+                            diagnostics.sample_errors.push(e.to_string());
                         }
                     }
                 }
             }
             for (_, v) in map {
-                extract_images_recursive(v, images);
+                extract_images_recursive(v, images, diagnostics);
             }
         }
         serde_json::Value::Array(arr) => {
             for v in arr {
-                extract_images_recursive(v, images);
+                extract_images_recursive(v, images, diagnostics);
             }
         }
         _ => {}
@@ -82,18 +127,69 @@ struct BoardData {
     vendor_name: Option<String>,
     vendor_logo: Option<String>,
     count: usize,
+    support: SupportFlags,
+    latest_image_date: Option<chrono::NaiveDate>,
+}
+
+/// Raw support levels seen across a board's images
+#[derive(Debug, Default)]
+struct SupportFlags {
     /// board_support: "conf" without platinum
-    has_standard_support: bool,
+    standard: bool,
     /// board_support: "csc"
-    has_community_support: bool,
+    community: bool,
     /// board_support: "eos" (end of support)
-    has_eos_support: bool,
+    eos: bool,
     /// board_support: "tvb" (TV Box - experimental)
-    has_tvb_support: bool,
+    tvb: bool,
     /// board_support: "wip" (Work In Progress)
-    has_wip_support: bool,
-    /// board_support: "conf" with platinum: "true" and valid date
-    platinum_support_until: Option<String>,
+    wip: bool,
+    /// board_support: "conf" with platinum: "true"
+    platinum_until: Option<String>,
+}
+
+/// Resolve the single support level shown for a board
+///
+/// Precedence: Platinum (if not expired) > Standard > Community > EOS > TVB > WIP.
+fn resolve_support_level(flags: &SupportFlags, today: chrono::NaiveDate) -> Option<SupportLevel> {
+    let platinum_until = flags.platinum_until.as_ref().filter(|until| {
+        chrono::NaiveDate::parse_from_str(until, "%Y-%m-%d")
+            .map(|exp_date| exp_date >= today)
+            .unwrap_or(false)
+    });
+
+    if let Some(until) = platinum_until {
+        Some(SupportLevel::Platinum {
+            until: until.clone(),
+        })
+    } else if flags.standard {
+        Some(SupportLevel::Standard)
+    } else if flags.community {
+        Some(SupportLevel::Community)
+    } else if flags.eos {
+        Some(SupportLevel::Eos)
+    } else if flags.tvb {
+        Some(SupportLevel::Tvb)
+    } else if flags.wip {
+        Some(SupportLevel::Wip)
+    } else {
+        None
+    }
+}
+
+/// Parse the date part of an image timestamp ("2025-01-15T10:22:31Z", "2025-01-15 10:22", "2025-01-15")
+fn parse_image_date(value: &str) -> Option<chrono::NaiveDate> {
+    let date_part = value.trim().split(['T', ' ']).next()?;
+    chrono::NaiveDate::parse_from_str(date_part, "%Y-%m-%d").ok()
+}
+
+/// Sort rank for boards: Platinum first, then Standard, then everything else
+fn support_sort_rank(board: &BoardInfo) -> u8 {
+    match board.support_tier() {
+        Some(SupportTier::Platinum) => 0,
+        Some(SupportTier::Standard) => 1,
+        _ => 2,
+    }
 }
 
 /// Get unique board list from images
@@ -110,50 +206,46 @@ pub fn get_unique_boards(images: &[ArmbianImage]) -> Vec<BoardInfo> {
                 vendor_name: img.company_name.clone(),
                 vendor_logo: img.company_logo.clone(),
                 count: 0,
-                has_standard_support: false,
-                has_community_support: false,
-                has_eos_support: false,
-                has_tvb_support: false,
-                has_wip_support: false,
-                platinum_support_until: None,
+                support: SupportFlags::default(),
+                latest_image_date: None,
             });
             entry.count += 1;
 
+            if let Some(date) = img.file_updated.as_deref().and_then(parse_image_date) {
+                if entry.latest_image_date.map_or(true, |latest| date > latest) {
+                    entry.latest_image_date = Some(date);
+                }
+            }
+
             // Use board_support field to determine support level
             match img.board_support.as_deref() {
                 Some("conf") => {
                     // conf can be either Platinum (if platinum=true) or Standard
                     if img.platinum_support.as_deref() == Some("true") {
                         if let Some(ref until) = img.platinum_support_until {
-                            entry.platinum_support_until = Some(until.clone());
+                            entry.support.platinum_until = Some(until.clone());
                         }
                     } else {
-                        entry.has_standard_support = true;
+                        entry.support.standard = true;
                     }
                 }
-                Some("csc") => entry.has_community_support = true,
-                Some("eos") => entry.has_eos_support = true,
-                Some("tvb") => entry.has_tvb_support = true,
-                Some("wip") => entry.has_wip_support = true,
+                Some("csc") => entry.support.community = true,
+                Some("eos") => entry.support.eos = true,
+                Some("tvb") => entry.support.tvb = true,
+                Some("wip") => entry.support.wip = true,
                 _ => {}
             }
         }
     }
 
     let today = chrono::Utc::now().date_naive();
+    let stale_before = today - chrono::Duration::days(config::images::STALE_AFTER_DAYS);
 
     let mut boards: Vec<BoardInfo> = board_map
         .into_iter()
         .map(|(slug, data)| {
             let name = data.board_name.unwrap_or(data.original_slug);
 
-            let has_platinum_support = data
-                .platinum_support_until
-                .as_ref()
-                .and_then(|until| chrono::NaiveDate::parse_from_str(until, "%Y-%m-%d").ok())
-                .map(|exp_date| exp_date >= today)
-                .unwrap_or(false);
-
             let has_logo = data
                 .vendor_logo
                 .as_ref()
@@ -170,31 +262,6 @@ pub fn get_unique_boards(images: &[ArmbianImage]) -> Vec<BoardInfo> {
                 ("other".to_string(), "Other".to_string(), None)
             };
 
-            // Community is shown only if no standard or platinum support
-            let has_community_support =
-                data.has_community_support && !data.has_standard_support && !has_platinum_support;
-
-            // EOS is shown only if no other support level
-            let has_eos_support = data.has_eos_support
-                && !data.has_standard_support
-                && !has_platinum_support
-                && !has_community_support;
-
-            // TVB is shown only if no other support level
-            let has_tvb_support = data.has_tvb_support
-                && !data.has_standard_support
-                && !has_platinum_support
-                && !has_community_support
-                && !has_eos_support;
-
-            // WIP is shown only if no other support level
-            let has_wip_support = data.has_wip_support
-                && !data.has_standard_support
-                && !has_platinum_support
-                && !has_community_support
-                && !has_eos_support
-                && !has_tvb_support;
-
             BoardInfo {
                 slug,
                 name,
@@ -202,51 +269,121 @@ pub fn get_unique_boards(images: &[ArmbianImage]) -> Vec<BoardInfo> {
                 vendor_name: vendor_display,
                 vendor_logo,
                 image_count: data.count,
-                has_standard_support: data.has_standard_support,
-                has_community_support,
-                has_platinum_support,
-                has_eos_support,
-                has_tvb_support,
-                has_wip_support,
+                support_level: resolve_support_level(&data.support, today),
+                latest_image_date: data
+                    .latest_image_date
+                    .map(|date| date.format("%Y-%m-%d").to_string()),
+                is_stale: data
+                    .latest_image_date
+                    .is_some_and(|date| date < stale_before),
             }
         })
         .collect();
 
     boards.sort_by(|a, b| {
-        // Platinum first, then Standard, then others
-        match (a.has_platinum_support, b.has_platinum_support) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => match (a.has_standard_support, b.has_standard_support) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-            },
-        }
+        support_sort_rank(a)
+            .cmp(&support_sort_rank(b))
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
     });
 
     // Log board statistics by support level
-    let platinum_count = boards.iter().filter(|b| b.has_platinum_support).count();
-    let standard_count = boards.iter().filter(|b| b.has_standard_support).count();
-    let community_count = boards.iter().filter(|b| b.has_community_support).count();
-    let eos_count = boards.iter().filter(|b| b.has_eos_support).count();
-    let tvb_count = boards.iter().filter(|b| b.has_tvb_support).count();
-    let wip_count = boards.iter().filter(|b| b.has_wip_support).count();
+    let count_tier = |tier: SupportTier| {
+        boards
+            .iter()
+            .filter(|b| b.support_tier() == Some(tier))
+            .count()
+    };
 
     log_info!(
         "images",
         "Loaded {} boards: {} Platinum, {} Standard, {} Community, {} EOS, {} TVB, {} WIP",
         boards.len(),
-        platinum_count,
-        standard_count,
-        community_count,
-        eos_count,
-        tvb_count,
-        wip_count
+        count_tier(SupportTier::Platinum),
+        count_tier(SupportTier::Standard),
+        count_tier(SupportTier::Community),
+        count_tier(SupportTier::Eos),
+        count_tier(SupportTier::Tvb),
+        count_tier(SupportTier::Wip)
     );
     boards
 }
 
+/// Filter boards by vendor, support levels and search text
+///
+/// Facet counts are computed with every filter applied except the facet's own,
+/// so the UI can show how many boards each vendor/support level would yield.
+/// The input order is preserved.
+pub fn filter_boards(
+    boards: Vec<BoardInfo>,
+    vendor: Option<&str>,
+    support_levels: Option<&[SupportTier]>,
+    search: Option<&str>,
+) -> BoardList {
+    let total_count = boards.len();
+    let search = search
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty());
+
+    let matches_vendor = |board: &BoardInfo| vendor.map_or(true, |v| board.vendor == v);
+    let matches_support = |board: &BoardInfo| {
+        support_levels.map_or(true, |levels| {
+            board
+                .support_tier()
+                .is_some_and(|tier| levels.contains(&tier))
+        })
+    };
+    let matches_search = |board: &BoardInfo| {
+        search.as_deref().map_or(true, |q| {
+            board.name.to_lowercase().contains(q)
+                || board.slug.contains(q)
+                || board.vendor_name.to_lowercase().contains(q)
+        })
+    };
+
+    let mut vendor_counts = BTreeMap::new();
+    let mut support_level_counts = HashMap::new();
+
+    for board in boards.iter().filter(|b| matches_search(b)) {
+        if matches_support(board) {
+            *vendor_counts.entry(board.vendor.clone()).or_insert(0) += 1;
+        }
+        if matches_vendor(board) {
+            if let Some(tier) = board.support_tier() {
+                *support_level_counts.entry(tier).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let boards: Vec<BoardInfo> = boards
+        .into_iter()
+        .filter(|b| matches_vendor(b) && matches_support(b) && matches_search(b))
+        .collect();
+
+    BoardList {
+        boards,
+        catalog_diagnostics: CatalogDiagnostics::default(),
+        total_count,
+        vendor_counts,
+        support_level_counts,
+    }
+}
+
+/// Whether an image is a pre-release (trunk/nightly version or rolling repository)
+fn is_prerelease(armbian_version: &str, repository_kind: RepositoryKind) -> bool {
+    let version = armbian_version.to_lowercase();
+    repository_kind == RepositoryKind::Rolling
+        || version.contains("trunk")
+        || version.contains("nightly")
+        || version.contains("-rc")
+}
+
+/// Extract the host name of a URL
+fn url_host(url: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()))
+}
+
 /// Filter images for a specific board
 pub fn filter_images_for_board(
     images: &[ArmbianImage],
@@ -300,21 +437,35 @@ pub fn filter_images_for_board(
 
             true
         })
-        .map(|img| ImageInfo {
-            armbian_version: img.armbian_version.clone().unwrap_or_default(),
-            distro_release: img.distro_release.clone().unwrap_or_default(),
-            kernel_branch: img.kernel_branch.clone().unwrap_or_default(),
-            image_variant: img.image_variant.clone().unwrap_or_default(),
-            preinstalled_application: img.preinstalled_application.clone().unwrap_or_default(),
-            promoted: img.promoted.as_deref() == Some("true"),
-            file_url: img.file_url.clone().unwrap_or_default(),
-            file_url_sha: img.file_url_sha.clone(),
-            file_size: img
-                .file_size
-                .as_ref()
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(0),
-            download_repository: img.download_repository.clone().unwrap_or_default(),
+        .map(|img| {
+            let armbian_version = img.armbian_version.clone().unwrap_or_default();
+            let file_url = img.file_url.clone().unwrap_or_default();
+            let download_repository = img.download_repository.clone().unwrap_or_default();
+            let repository_kind = RepositoryKind::from_repository(&download_repository);
+
+            ImageInfo {
+                is_prerelease: is_prerelease(&armbian_version, repository_kind),
+                origin_host: url_host(&file_url),
+                repository_kind,
+                armbian_version,
+                distro_release: img.distro_release.clone().unwrap_or_default(),
+                kernel_branch: img.kernel_branch.clone().unwrap_or_default(),
+                image_variant: img.image_variant.clone().unwrap_or_default(),
+                preinstalled_application: img.preinstalled_application.clone().unwrap_or_default(),
+                promoted: img.promoted.as_deref() == Some("true"),
+                file_url,
+                file_url_sha: img.file_url_sha.clone(),
+                file_size: img
+                    .file_size
+                    .as_ref()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0),
+                download_repository,
+                origin: img
+                    .catalog_origin
+                    .clone()
+                    .unwrap_or_else(|| crate::config::catalog::OFFICIAL_ORIGIN.to_string()),
+            }
         })
         .collect();
 
@@ -326,3 +477,191 @@ pub fn filter_images_for_board(
 
     filtered
 }
+
+/// Find the newest catalog image matching a board, distro release and kernel branch
+///
+/// `distro_release` and `kernel_branch` are only matched when provided.
+pub fn find_latest_image(
+    images: &[ArmbianImage],
+    board_slug: &str,
+    distro_release: Option<&str>,
+    kernel_branch: Option<&str>,
+) -> Option<ImageInfo> {
+    filter_images_for_board(images, board_slug, None, kernel_branch, None, false)
+        .into_iter()
+        .filter(|img| distro_release.map_or(true, |d| img.distro_release == d))
+        .max_by(|a, b| compare_versions(&a.armbian_version, &b.armbian_version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> chrono::NaiveDate {
+        chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_resolve_support_level_none() {
+        let flags = SupportFlags::default();
+        assert_eq!(resolve_support_level(&flags, date("2025-01-01")), None);
+    }
+
+    #[test]
+    fn test_resolve_support_level_platinum_precedence() {
+        let flags = SupportFlags {
+            standard: true,
+            community: true,
+            platinum_until: Some("2025-06-30".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            resolve_support_level(&flags, date("2025-01-01")),
+            Some(SupportLevel::Platinum {
+                until: "2025-06-30".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_resolve_support_level_expired_platinum() {
+        let flags = SupportFlags {
+            community: true,
+            platinum_until: Some("2024-12-31".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            resolve_support_level(&flags, date("2025-01-01")),
+            Some(SupportLevel::Community)
+        );
+
+        let flags = SupportFlags {
+            platinum_until: Some("not-a-date".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(resolve_support_level(&flags, date("2025-01-01")), None);
+    }
+
+    #[test]
+    fn test_resolve_support_level_order() {
+        let today = date("2025-01-01");
+        let mut flags = SupportFlags {
+            standard: true,
+            community: true,
+            eos: true,
+            tvb: true,
+            wip: true,
+            platinum_until: None,
+        };
+        assert_eq!(
+            resolve_support_level(&flags, today),
+            Some(SupportLevel::Standard)
+        );
+        flags.standard = false;
+        assert_eq!(
+            resolve_support_level(&flags, today),
+            Some(SupportLevel::Community)
+        );
+        flags.community = false;
+        assert_eq!(
+            resolve_support_level(&flags, today),
+            Some(SupportLevel::Eos)
+        );
+        flags.eos = false;
+        assert_eq!(
+            resolve_support_level(&flags, today),
+            Some(SupportLevel::Tvb)
+        );
+        flags.tvb = false;
+        assert_eq!(
+            resolve_support_level(&flags, today),
+            Some(SupportLevel::Wip)
+        );
+    }
+
+    #[test]
+    fn test_parse_image_date() {
+        assert_eq!(
+            parse_image_date("2025-01-15T10:22:31Z"),
+            Some(date("2025-01-15"))
+        );
+        assert_eq!(
+            parse_image_date("2025-01-15 10:22"),
+            Some(date("2025-01-15"))
+        );
+        assert_eq!(parse_image_date("2025-01-15"), Some(date("2025-01-15")));
+        assert_eq!(parse_image_date(""), None);
+        assert_eq!(parse_image_date("yesterday"), None);
+    }
+
+    #[test]
+    fn test_extract_images_diagnostics() {
+        let json = serde_json::json!({
+            "version": 2,
+            "assets": [
+                { "board_slug": "rock-5b", "file_extension": "img.xz", "branch": "vendor" },
+                { "board_slug": "rock-5b", "file_extension": "img.xz.sha" },
+                { "board_slug": "rock-5b", "file_extension": "img.xz", "branch": "cloud" },
+                { "board_slug": "rock-5b", "file_extension": "img.xz", "file_size": 1234 },
+                { "unrelated": true }
+            ]
+        });
+        let (images, diagnostics) = extract_images_with_diagnostics(&json);
+        assert_eq!(images.len(), 1);
+        assert_eq!(diagnostics.schema_version.as_deref(), Some("2"));
+        assert_eq!(diagnostics.records, 4);
+        assert_eq!(diagnostics.parsed, 1);
+        assert_eq!(diagnostics.skipped, 2);
+        assert_eq!(diagnostics.errors, 1);
+        assert_eq!(diagnostics.sample_errors.len(), 1);
+        assert!(!diagnostics.is_suspicious());
+    }
+
+    #[test]
+    fn test_extract_images_schema_change() {
+        let json = serde_json::json!({ "assets": [{ "slug": "rock-5b" }] });
+        let (images, diagnostics) = extract_images_with_diagnostics(&json);
+        assert!(images.is_empty());
+        assert_eq!(diagnostics.records, 0);
+        assert!(diagnostics.is_suspicious());
+    }
+
+    #[test]
+    fn test_is_prerelease() {
+        assert!(!is_prerelease("25.2.1", RepositoryKind::Archive));
+        assert!(is_prerelease("25.2.0-trunk.12", RepositoryKind::Unknown));
+        assert!(is_prerelease("25.2.1", RepositoryKind::Rolling));
+        assert_eq!(
+            RepositoryKind::from_repository("archive"),
+            RepositoryKind::Archive
+        );
+        assert_eq!(
+            RepositoryKind::from_repository("os"),
+            RepositoryKind::Rolling
+        );
+        assert_eq!(RepositoryKind::from_repository(""), RepositoryKind::Unknown);
+    }
+
+    #[test]
+    fn test_url_host() {
+        assert_eq!(
+            url_host("https://dl.armbian.com/orangepi5/archive/Armbian.img.xz").as_deref(),
+            Some("dl.armbian.com")
+        );
+        assert_eq!(url_host("not a url"), None);
+    }
+
+    #[test]
+    fn test_support_level_serialization() {
+        let json = serde_json::to_value(SupportLevel::Platinum {
+            until: "2025-06-30".to_string(),
+        })
+        .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "level": "platinum", "until": "2025-06-30" })
+        );
+        let json = serde_json::to_value(SupportLevel::Tvb).unwrap();
+        assert_eq!(json, serde_json::json!({ "level": "tvb" }));
+    }
+}
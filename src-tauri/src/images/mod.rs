@@ -1,32 +1,95 @@
 //! Image management module
 //!
 //! Handles fetching, parsing, and filtering Armbian image data.
+//!
+//! The catalog is only accepted over HTTPS, including after redirects, and
+//! its signature is verified when `config::catalog::SIGNING_KEY` is set.
+//! Additional catalogs configured by the user are merged in, see `sources`.
 
+mod advisories;
+mod applications;
+mod filename;
 mod filters;
 mod models;
+mod signature;
+mod sources;
 
 // Re-export types and functions
-pub use filters::{extract_images, filter_images_for_board, get_unique_boards};
-pub use models::{BoardInfo, ImageInfo};
+pub use advisories::{flashed_board_advisories, BoardAdvisory, FlashedImage};
+pub use applications::{application_catalog, ApplicationInfo};
+pub use filename::{compare_versions, parse_image_filename};
+pub use filters::{
+    extract_images, extract_images_with_diagnostics, filter_boards, filter_images_for_board,
+    find_latest_image, get_unique_boards,
+};
+pub use models::{BoardInfo, BoardList, ImageInfo, SupportTier};
+pub use sources::{validate_sources, CatalogSource};
 // ArmbianImage is used internally by filters module
 
+use self::signature::verify_catalog_signature;
+use self::sources::{fetch_additional, merge_catalogs, tag_origin};
 use crate::config;
+use crate::proxy;
 use crate::{log_error, log_info};
 
+/// Fetch the all-images.json from Armbian, merged with the additional catalogs
+///
+/// Every image record is tagged with its origin. The official catalog is
+/// required; additional catalogs that fail to load are skipped.
+pub async fn fetch_all_images(
+    additional_sources: &[CatalogSource],
+) -> Result<serde_json::Value, String> {
+    let mut official = fetch_official_catalog().await?;
+    tag_origin(&mut official, config::catalog::OFFICIAL_ORIGIN);
+    let additional = fetch_additional(additional_sources).await;
+    Ok(merge_catalogs(official, additional))
+}
+
 /// Fetch the all-images.json from Armbian
-pub async fn fetch_all_images() -> Result<serde_json::Value, String> {
+async fn fetch_official_catalog() -> Result<serde_json::Value, String> {
     log_info!(
         "images",
         "Fetching all images from {}",
         config::urls::ALL_IMAGES
     );
 
-    let response = reqwest::get(config::urls::ALL_IMAGES).await.map_err(|e| {
-        log_error!("images", "Failed to fetch images: {}", e);
+    let response = proxy::client()?
+        .get(config::urls::ALL_IMAGES)
+        .send()
+        .await
+        .map_err(|e| {
+            log_error!("images", "Failed to fetch images: {}", e);
+            format!("Failed to fetch images: {}", e)
+        })?;
+
+    // reqwest follows redirects onto plain HTTP
+    if response.url().scheme() != "https" {
+        log_error!(
+            "images",
+            "Catalog was redirected to an insecure URL: {}",
+            response.url()
+        );
+        return Err(format!(
+            "Refusing image catalog served over plain HTTP ({})",
+            response.url()
+        ));
+    }
+
+    let body = response.bytes().await.map_err(|e| {
+        log_error!("images", "Failed to read images response: {}", e);
         format!("Failed to fetch images: {}", e)
     })?;
 
-    let json: serde_json::Value = response.json().await.map_err(|e| {
+    if let Some(public_key) = config::catalog::SIGNING_KEY {
+        let signature = fetch_catalog_signature().await?;
+        verify_catalog_signature(&body, &signature, public_key).map_err(|e| {
+            log_error!("images", "Rejecting image catalog: {}", e);
+            e
+        })?;
+        log_info!("images", "Catalog signature verified");
+    }
+
+    let json: serde_json::Value = serde_json::from_slice(&body).map_err(|e| {
         log_error!("images", "Failed to parse JSON response: {}", e);
         format!("Failed to parse JSON: {}", e)
     })?;
@@ -34,3 +97,26 @@ pub async fn fetch_all_images() -> Result<serde_json::Value, String> {
     log_info!("images", "Successfully fetched images data");
     Ok(json)
 }
+
+/// Fetch the detached signature of the all-images JSON
+async fn fetch_catalog_signature() -> Result<String, String> {
+    let response = proxy::client()?
+        .get(config::urls::ALL_IMAGES_SIGNATURE)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| {
+            log_error!("images", "Failed to fetch catalog signature: {}", e);
+            format!("Failed to fetch catalog signature: {}", e)
+        })?;
+    if response.url().scheme() != "https" {
+        return Err(format!(
+            "Refusing catalog signature served over plain HTTP ({})",
+            response.url()
+        ));
+    }
+    response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to fetch catalog signature: {}", e))
+}
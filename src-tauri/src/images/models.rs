@@ -2,6 +2,8 @@
 //!
 //! Types representing Armbian images and boards.
 
+use std::collections::{BTreeMap, HashMap};
+
 use serde::{Deserialize, Serialize};
 
 /// Raw Armbian image data from the API
@@ -30,6 +32,9 @@ pub struct ArmbianImage {
     pub file_url_sha: Option<String>,
     pub file_extension: Option<String>,
     pub file_size: Option<String>,
+    /// Build/upload timestamp (ISO 8601, e.g. "2025-01-15T10:22:31Z")
+    #[serde(alias = "file_date")]
+    pub file_updated: Option<String>,
     pub download_repository: Option<String>,
     pub redi_url: Option<String>,
     /// API field: "platinum"
@@ -40,6 +45,9 @@ pub struct ArmbianImage {
     pub platinum_support_until: Option<String>,
     /// Board support level: "conf", "csc", "eos", "tvb", "wip"
     pub board_support: Option<String>,
+    /// Name of the catalog the image came from, tagged while fetching
+    #[serde(default)]
+    pub catalog_origin: Option<String>,
 }
 
 /// Board information for display
@@ -51,12 +59,107 @@ pub struct BoardInfo {
     pub vendor_name: String,
     pub vendor_logo: Option<String>,
     pub image_count: usize,
-    pub has_standard_support: bool,
-    pub has_community_support: bool,
-    pub has_platinum_support: bool,
-    pub has_eos_support: bool,
-    pub has_tvb_support: bool,
-    pub has_wip_support: bool,
+    /// Highest-precedence support level across the board's images
+    pub support_level: Option<SupportLevel>,
+    /// Date of the most recent image build (YYYY-MM-DD), if known
+    pub latest_image_date: Option<String>,
+    /// Whether the most recent build is older than `config::images::STALE_AFTER_DAYS`
+    pub is_stale: bool,
+}
+
+/// Board support level, resolved from the API `board_support` field
+///
+/// Serialized as `{ "level": "platinum", "until": "2026-12-31" }`,
+/// `{ "level": "standard" }`, etc.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "level", rename_all = "lowercase")]
+pub enum SupportLevel {
+    /// board_support: "conf" with platinum: "true" and a non-expired date
+    Platinum { until: String },
+    /// board_support: "conf" without platinum
+    Standard,
+    /// board_support: "csc"
+    Community,
+    /// board_support: "wip" (Work In Progress)
+    Wip,
+    /// board_support: "tvb" (TV Box - experimental)
+    Tvb,
+    /// board_support: "eos" (end of support)
+    Eos,
+}
+
+impl SupportLevel {
+    /// Tier used for filtering, facet counts and sorting
+    pub fn tier(&self) -> SupportTier {
+        match self {
+            SupportLevel::Platinum { .. } => SupportTier::Platinum,
+            SupportLevel::Standard => SupportTier::Standard,
+            SupportLevel::Community => SupportTier::Community,
+            SupportLevel::Wip => SupportTier::Wip,
+            SupportLevel::Tvb => SupportTier::Tvb,
+            SupportLevel::Eos => SupportTier::Eos,
+        }
+    }
+}
+
+/// Support level without associated data, in display precedence order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SupportTier {
+    Platinum,
+    Standard,
+    Community,
+    Eos,
+    Tvb,
+    Wip,
+}
+
+impl BoardInfo {
+    /// Support tier of this board, if any
+    pub fn support_tier(&self) -> Option<SupportTier> {
+        self.support_level.as_ref().map(SupportLevel::tier)
+    }
+}
+
+/// Filtered board list with per-facet counts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardList {
+    /// Boards matching all filters, sorted for display
+    pub boards: Vec<BoardInfo>,
+    /// Number of boards in the catalog before filtering
+    pub total_count: usize,
+    /// Board count per vendor ID (ignoring the vendor filter)
+    pub vendor_counts: BTreeMap<String, usize>,
+    /// Board count per support tier (ignoring the support level filter)
+    pub support_level_counts: HashMap<SupportTier, usize>,
+    /// Parse statistics for the catalog the boards were built from
+    pub catalog_diagnostics: CatalogDiagnostics,
+}
+
+/// Parse statistics for the image catalog
+///
+/// Surfaces schema changes that would otherwise make the app look empty.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CatalogDiagnostics {
+    /// Schema version declared by the catalog root, if any
+    pub schema_version: Option<String>,
+    /// Objects that looked like image records (had a `board_slug` key)
+    pub records: usize,
+    /// Records parsed and kept as flashable images
+    pub parsed: usize,
+    /// Records parsed but skipped (checksums, signatures, cloud images)
+    pub skipped: usize,
+    /// Records that failed to parse
+    pub errors: usize,
+    /// First few parse error messages
+    pub sample_errors: Vec<String>,
+}
+
+impl CatalogDiagnostics {
+    /// Whether the catalog looks broken: no usable images, or mostly unparseable records
+    pub fn is_suspicious(&self) -> bool {
+        self.parsed == 0 || self.errors > self.parsed
+    }
 }
 
 /// Processed image information for the UI
@@ -72,4 +175,34 @@ pub struct ImageInfo {
     pub file_url_sha: Option<String>,
     pub file_size: u64,
     pub download_repository: String,
+    /// Release channel derived from `download_repository`
+    pub repository_kind: RepositoryKind,
+    /// Host serving `file_url` (usually the download redirector)
+    pub origin_host: Option<String>,
+    /// Nightly/trunk build or image from a rolling repository
+    pub is_prerelease: bool,
+    /// Catalog the image is listed in, `config::catalog::OFFICIAL_ORIGIN` for Armbian's
+    pub origin: String,
+}
+
+/// Release channel of an image, derived from the API `download_repository` field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepositoryKind {
+    /// Stable, archived releases ("archive")
+    Archive,
+    /// Rolling/nightly builds ("os", "nightly", "rolling")
+    Rolling,
+    /// Empty or unrecognized repository
+    Unknown,
+}
+
+impl RepositoryKind {
+    pub fn from_repository(repository: &str) -> Self {
+        match repository.trim().to_lowercase().as_str() {
+            r if r == crate::config::images::STABLE_REPO => RepositoryKind::Archive,
+            "os" | "nightly" | "rolling" | "beta" => RepositoryKind::Rolling,
+            _ => RepositoryKind::Unknown,
+        }
+    }
 }
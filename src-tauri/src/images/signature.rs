@@ -0,0 +1,44 @@
+//! Catalog signature verification
+//!
+//! The catalog decides which URLs images are downloaded from, so a
+//! tampered copy must not be trusted. Once Armbian signs it, the detached
+//! minisign signature (the format the updater already uses) is checked
+//! against `config::catalog::SIGNING_KEY` before the JSON is parsed.
+
+use minisign_verify::{PublicKey, Signature};
+
+/// Verify a detached minisign signature of the catalog
+pub fn verify_catalog_signature(
+    catalog: &[u8],
+    signature: &str,
+    public_key: &str,
+) -> Result<(), String> {
+    let public_key = PublicKey::from_base64(public_key)
+        .map_err(|e| format!("Invalid catalog signing key: {}", e))?;
+    let signature =
+        Signature::decode(signature).map_err(|e| format!("Invalid catalog signature: {}", e))?;
+    public_key
+        .verify(catalog, &signature, false)
+        .map_err(|e| format!("Catalog signature does not match: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vector from the minisign-verify documentation, signing b"test"
+    const PUBLIC_KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1633700835\tfile:test\tprehashed
+wLMDjy9FLAuxZ3q4NlEvkgtyhrr0gtTu6KC4KBJdITbbOeAi1zBIYo0v4iTgt8jJpIidRJnp94ABQkJAgAooBQ==";
+
+    #[test]
+    fn test_verify_catalog_signature() {
+        assert!(verify_catalog_signature(b"test", SIGNATURE, PUBLIC_KEY).is_ok());
+        // Tampered catalog
+        assert!(verify_catalog_signature(b"test2", SIGNATURE, PUBLIC_KEY).is_err());
+        assert!(verify_catalog_signature(b"test", "not a signature", PUBLIC_KEY).is_err());
+        assert!(verify_catalog_signature(b"test", SIGNATURE, "not a key").is_err());
+    }
+}
@@ -0,0 +1,267 @@
+//! Additional image catalogs
+//!
+//! Besides the official Armbian catalog, users can list catalogs of their
+//! own, such as a company-internal mirror or the JSON of self-built images,
+//! in the same format. Every image record is tagged with the name of the
+//! catalog it came from (`catalog_origin`), and the additional catalogs are
+//! attached to the official one, so the rest of the app sees a single JSON.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::proxy;
+use crate::{log_info, log_warn};
+
+/// Key on image records naming the catalog they came from
+const ORIGIN_KEY: &str = "catalog_origin";
+
+/// Key on the merged catalog root holding the additional catalogs
+const ADDITIONAL_KEY: &str = "additional_catalogs";
+
+/// A catalog in the Armbian all-images JSON format
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CatalogSource {
+    /// Shown next to images of the catalog
+    pub name: String,
+    /// HTTPS URL, or a file:// URL for a local JSON file
+    pub url: String,
+}
+
+impl CatalogSource {
+    /// Whether the catalog is fetched over plain HTTP
+    pub fn is_insecure(&self) -> bool {
+        self.url.starts_with("http:")
+    }
+
+    /// Check and normalize a source; plain HTTP only with `allow_insecure`
+    pub fn validated(&self, allow_insecure: bool) -> Result<Self, String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("Catalog sources need a name".to_string());
+        }
+        if name.chars().count() > config::catalog::MAX_SOURCE_NAME_LEN {
+            return Err(format!(
+                "Catalog names are limited to {} characters",
+                config::catalog::MAX_SOURCE_NAME_LEN
+            ));
+        }
+        if name.eq_ignore_ascii_case(config::catalog::OFFICIAL_ORIGIN) {
+            return Err(format!("\"{}\" names the official catalog", name));
+        }
+
+        let url = reqwest::Url::parse(self.url.trim())
+            .map_err(|e| format!("Invalid catalog URL {}: {}", self.url, e))?;
+        match url.scheme() {
+            "https" | "file" => {}
+            "http" if allow_insecure => {}
+            "http" => {
+                return Err(
+                    "Catalogs are only fetched over HTTPS unless insecure downloads are allowed"
+                        .to_string(),
+                )
+            }
+            scheme => return Err(format!("Unsupported catalog URL scheme: {}", scheme)),
+        }
+        Ok(Self {
+            name: name.to_string(),
+            url: url.to_string(),
+        })
+    }
+}
+
+/// Check a list of sources for the settings, rejecting duplicate names
+pub fn validate_sources(
+    sources: &[CatalogSource],
+    allow_insecure: bool,
+) -> Result<Vec<CatalogSource>, String> {
+    if sources.len() > config::catalog::MAX_SOURCES {
+        return Err(format!(
+            "At most {} additional catalogs can be configured",
+            config::catalog::MAX_SOURCES
+        ));
+    }
+    let mut validated: Vec<CatalogSource> = Vec::with_capacity(sources.len());
+    for source in sources {
+        let source = source.validated(allow_insecure)?;
+        if validated
+            .iter()
+            .any(|s| s.name.eq_ignore_ascii_case(&source.name))
+        {
+            return Err(format!("Two catalogs are named \"{}\"", source.name));
+        }
+        validated.push(source);
+    }
+    Ok(validated)
+}
+
+/// Fetch an additional catalog
+pub async fn fetch_source(source: &CatalogSource) -> Result<serde_json::Value, String> {
+    let url =
+        reqwest::Url::parse(&source.url).map_err(|e| format!("Invalid catalog URL: {}", e))?;
+    let body = if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|_| format!("Invalid catalog path: {}", url))?;
+        tokio::fs::read(&path)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+    } else {
+        let response = proxy::client()?
+            .get(url.clone())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+        // reqwest follows redirects onto plain HTTP
+        if url.scheme() == "https" && response.url().scheme() != "https" {
+            return Err(format!(
+                "Refusing catalog redirected to plain HTTP ({})",
+                response.url()
+            ));
+        }
+        response
+            .bytes()
+            .await
+            .map_err(|e| format!("Failed to fetch {}: {}", url, e))?
+            .to_vec()
+    };
+    serde_json::from_slice(&body).map_err(|e| format!("Failed to parse catalog: {}", e))
+}
+
+/// Tag every image record in `value` with the catalog it came from
+pub fn tag_origin(value: &mut serde_json::Value, origin: &str) {
+    match value {
+        serde_json::Value::Object(map) => {
+            if map.contains_key("board_slug") {
+                map.insert(ORIGIN_KEY.to_string(), origin.into());
+            }
+            for (_, v) in map.iter_mut() {
+                tag_origin(v, origin);
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                tag_origin(item, origin);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Attach additional catalogs to the official one
+///
+/// Image extraction walks the whole JSON, so attached records are found like
+/// the official ones, while the official root keeps its schema fields.
+pub fn merge_catalogs(
+    official: serde_json::Value,
+    additional: Vec<serde_json::Value>,
+) -> serde_json::Value {
+    if additional.is_empty() {
+        return official;
+    }
+    match official {
+        serde_json::Value::Object(mut root) => {
+            root.insert(ADDITIONAL_KEY.to_string(), additional.into());
+            serde_json::Value::Object(root)
+        }
+        other => {
+            let mut catalogs = vec![other];
+            catalogs.extend(additional);
+            serde_json::json!({ ADDITIONAL_KEY: catalogs })
+        }
+    }
+}
+
+/// Fetch the additional catalogs, tagged with their names
+///
+/// A catalog that can't be fetched is left out, so one unreachable mirror
+/// doesn't hide the official images.
+pub async fn fetch_additional(sources: &[CatalogSource]) -> Vec<serde_json::Value> {
+    let mut catalogs = Vec::with_capacity(sources.len());
+    for source in sources {
+        match fetch_source(source).await {
+            Ok(mut json) => {
+                tag_origin(&mut json, &source.name);
+                log_info!("images", "Fetched catalog \"{}\"", source.name);
+                catalogs.push(json);
+            }
+            Err(e) => log_warn!("images", "Skipping catalog \"{}\": {}", source.name, e),
+        }
+    }
+    catalogs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::images::extract_images;
+
+    fn source(name: &str, url: &str) -> CatalogSource {
+        CatalogSource {
+            name: name.to_string(),
+            url: url.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_validate_sources() {
+        let valid = validate_sources(
+            &[
+                source(
+                    " Office mirror ",
+                    "https://mirror.example.com/all-images.json",
+                ),
+                source("Builds", "file:///srv/armbian/all-images.json"),
+            ],
+            false,
+        )
+        .unwrap();
+        assert_eq!(valid[0].name, "Office mirror");
+
+        let plain = [source("Lab", "http://10.0.0.2/all-images.json")];
+        assert!(validate_sources(&plain, false).is_err());
+        assert!(validate_sources(&plain, true).is_ok());
+
+        assert!(validate_sources(&[source("", "https://a.example.com/x.json")], false).is_err());
+        assert!(
+            validate_sources(&[source("Armbian", "https://a.example.com/x.json")], false).is_err()
+        );
+        assert!(validate_sources(
+            &[
+                source("Lab", "https://a.example.com/x.json"),
+                source("lab", "https://b.example.com/x.json"),
+            ],
+            false
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_merge_tagged_catalogs() {
+        let record = |slug: &str| {
+            serde_json::json!({
+                "board_slug": slug,
+                "armbian_version": "25.2.1",
+                "file_extension": "img.xz",
+                "branch": "current",
+            })
+        };
+        let mut official = serde_json::json!({ "version": "2", "assets": [record("rock-5b")] });
+        let mut internal = serde_json::json!({ "assets": { "boards": [record("custom-board")] } });
+        tag_origin(&mut official, config::catalog::OFFICIAL_ORIGIN);
+        tag_origin(&mut internal, "Office mirror");
+
+        let merged = merge_catalogs(official, vec![internal]);
+        assert_eq!(merged["version"], "2");
+        let images = extract_images(&merged);
+        assert_eq!(images.len(), 2);
+        let origin = |slug: &str| {
+            images
+                .iter()
+                .find(|img| img.board_slug.as_deref() == Some(slug))
+                .and_then(|img| img.catalog_origin.clone())
+        };
+        assert_eq!(origin("rock-5b").as_deref(), Some("armbian"));
+        assert_eq!(origin("custom-board").as_deref(), Some("Office mirror"));
+    }
+}
@@ -0,0 +1,429 @@
+//! Interrupted downloads
+//!
+//! Downloads write into a working directory (see `workdir`) that goes away
+//! when the download ends. When the app quits or crashes mid-download, the
+//! directory stays behind with the data received so far. A manifest in the
+//! cache directory lists every running download, so the next launch can
+//! resume it with a Range request instead of starting over, or discard it.
+//! A segmented download keeps the progress of each of its ranges in the
+//! manifest and continues every range where it stopped.
+//! Working directories listed in the manifest are spared by the startup
+//! sweep of stale working directories.
+//!
+//! An entry belongs to the process that wrote it; entries of processes that
+//! are gone are the interrupted downloads. A download of the same URL into
+//! the same directory claims such an entry and continues its partial file.
+//! A download paused in the queue leaves its entry the same way, marked as
+//! paused so it is resumed from the queue rather than listed as interrupted.
+//!
+//! Thread Safety:
+//! Reads and writes of the manifest are serialized by a global Mutex.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::download::Resume;
+use crate::segmented::{segmented_path, SegmentProgress};
+use crate::utils::{get_cache_dir, process_alive};
+use crate::workdir::{work_root, WorkDir};
+use crate::{log_info, log_warn};
+
+const MODULE: &str = "interrupted";
+
+/// Global mutex to serialize manifest access
+static MANIFEST_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// A download that was running, or still runs, in some process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterruptedDownload {
+    /// Name of the working directory holding the partial file
+    pub id: String,
+    pub url: String,
+    pub sha_url: Option<String>,
+    /// File name from the URL
+    pub filename: String,
+    /// Directory the finished image goes to
+    pub output_dir: PathBuf,
+    /// Size announced by the server, 0 until known
+    pub total_bytes: u64,
+    /// Bytes on disk, filled in by `list_interrupted`
+    #[serde(default)]
+    pub downloaded_bytes: u64,
+    /// Progress of each range of a segmented download, empty for a single stream
+    #[serde(default)]
+    pub segments: Vec<SegmentProgress>,
+    /// RFC 3339 timestamp
+    pub started_at: String,
+    /// Process running the download
+    pub pid: u32,
+    /// Paused in the download queue, which resumes it
+    #[serde(default)]
+    pub paused: bool,
+}
+
+impl InterruptedDownload {
+    /// Working directory of the download
+    pub fn work_dir(&self) -> PathBuf {
+        work_root(&self.output_dir).join(&self.id)
+    }
+
+    /// Partial file inside the working directory
+    pub fn partial_path(&self) -> PathBuf {
+        self.work_dir().join(partial_file_name(&self.filename))
+    }
+
+    /// What the download left to continue from
+    fn resume(&self) -> Resume {
+        // The ranges count only bytes already written to the segmented file
+        if !self.segments.is_empty() && segmented_path(&self.partial_path()).is_file() {
+            return Resume::Segments(self.segments.clone());
+        }
+        match fs::metadata(self.partial_path()).map_or(0, |m| m.len()) {
+            0 => Resume::Start,
+            len => Resume::Offset(len),
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        !self.paused && (self.pid == std::process::id() || process_alive(self.pid))
+    }
+}
+
+/// Name of the file a download writes to until it completes
+pub fn partial_file_name(filename: &str) -> String {
+    format!("{}.downloading", filename)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    downloads: Vec<InterruptedDownload>,
+}
+
+fn manifest_path() -> PathBuf {
+    get_cache_dir(config::app::NAME).join(config::download::MANIFEST_FILE_NAME)
+}
+
+fn load_internal() -> Manifest {
+    match fs::read_to_string(manifest_path()) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            log_warn!(MODULE, "Ignoring unreadable download manifest: {}", e);
+            Manifest::default()
+        }),
+        Err(_) => Manifest::default(),
+    }
+}
+
+fn save_internal(manifest: &Manifest) -> Result<(), String> {
+    let path = manifest_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create manifest directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(manifest)
+        .map_err(|e| format!("Failed to serialize download manifest: {}", e))?;
+    fs::write(&path, content).map_err(|e| format!("Failed to write download manifest: {}", e))
+}
+
+/// Load, modify and save the manifest under the lock; nothing is saved on error
+fn update_manifest<T>(f: impl FnOnce(&mut Manifest) -> Result<T, String>) -> Result<T, String> {
+    let _lock = MANIFEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut manifest = load_internal();
+    let result = f(&mut manifest)?;
+    save_internal(&manifest)?;
+    Ok(result)
+}
+
+/// Manifest entry of a running download, removed on drop
+///
+/// Like `WorkDir`, the entry outlives only a crash or a quit, which is
+/// exactly when the download counts as interrupted.
+#[derive(Debug)]
+pub struct Tracked {
+    id: String,
+    keep: bool,
+}
+
+impl Tracked {
+    /// Record the size announced by the server
+    pub fn set_total(&self, total_bytes: u64) {
+        self.update(|entry| entry.total_bytes = total_bytes);
+    }
+
+    /// Record the progress of each range, nothing once the ranges are joined
+    pub fn set_segments(&self, segments: &[SegmentProgress]) {
+        self.update(|entry| entry.segments = segments.to_vec());
+    }
+
+    /// Leave the entry for the queue to resume, with the progress of each range
+    pub fn pause(mut self, segments: &[SegmentProgress]) {
+        self.update(|entry| {
+            entry.segments = segments.to_vec();
+            entry.paused = true;
+        });
+        self.keep = true;
+    }
+
+    fn update(&self, f: impl FnOnce(&mut InterruptedDownload)) {
+        let result = update_manifest(|manifest| {
+            if let Some(entry) = manifest.downloads.iter_mut().find(|d| d.id == self.id) {
+                f(entry);
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            log_warn!(MODULE, "Failed to update download manifest: {}", e);
+        }
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+        if let Err(e) = update_manifest(|manifest| {
+            manifest.downloads.retain(|d| d.id != self.id);
+            Ok(())
+        }) {
+            log_warn!(MODULE, "Failed to update download manifest: {}", e);
+        }
+    }
+}
+
+/// Record a download starting in `work`
+///
+/// A manifest that can't be written only costs the chance to resume, so
+/// the download goes on regardless.
+pub fn track(
+    work: &WorkDir,
+    url: &str,
+    sha_url: Option<&str>,
+    filename: &str,
+    output_dir: &Path,
+) -> Tracked {
+    let id = work
+        .path()
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let entry = InterruptedDownload {
+        id: id.clone(),
+        url: url.to_string(),
+        sha_url: sha_url.map(|s| s.to_string()),
+        filename: filename.to_string(),
+        output_dir: output_dir.to_path_buf(),
+        total_bytes: 0,
+        downloaded_bytes: 0,
+        segments: Vec::new(),
+        started_at: chrono::Utc::now().to_rfc3339(),
+        pid: std::process::id(),
+        paused: false,
+    };
+    if let Err(e) = update_manifest(|manifest| {
+        manifest.downloads.push(entry);
+        Ok(())
+    }) {
+        log_warn!(MODULE, "Failed to record download in manifest: {}", e);
+    }
+    Tracked { id, keep: false }
+}
+
+/// Take over the interrupted download of `url` into `output_dir`, if any
+///
+/// Returns the adopted working directory, the entry now owned by this
+/// process and what the download left to continue from.
+pub fn claim(url: &str, output_dir: &Path) -> Option<(WorkDir, Tracked, Resume)> {
+    let claimed = update_manifest(|manifest| {
+        let Some(entry) = manifest
+            .downloads
+            .iter_mut()
+            .find(|d| d.url == url && d.output_dir == output_dir && !d.is_running())
+        else {
+            return Ok(None);
+        };
+        entry.pid = std::process::id();
+        entry.paused = false;
+        Ok(Some(entry.clone()))
+    });
+    let entry = match claimed {
+        Ok(entry) => entry?,
+        Err(e) => {
+            log_warn!(MODULE, "Failed to read download manifest: {}", e);
+            return None;
+        }
+    };
+
+    // From here on, dropping the guard forgets an entry that can't be resumed
+    let tracked = Tracked {
+        id: entry.id.clone(),
+        keep: false,
+    };
+    let work = match WorkDir::adopt(&entry.work_dir(), output_dir) {
+        Ok(work) => work,
+        Err(e) => {
+            log_warn!(MODULE, "Not resuming {}: {}", entry.url, e);
+            return None;
+        }
+    };
+    let resume = entry.resume();
+    log_info!(
+        MODULE,
+        "Resuming interrupted download of {} at {} bytes",
+        entry.url,
+        resume.bytes()
+    );
+    Some((work, tracked, resume))
+}
+
+/// Downloads interrupted by an earlier exit, with the bytes received so far
+///
+/// Entries whose working directory is gone are dropped from the manifest.
+pub fn list_interrupted() -> Vec<InterruptedDownload> {
+    let result = update_manifest(|manifest| {
+        manifest
+            .downloads
+            .retain(|d| d.is_running() || d.work_dir().is_dir());
+        Ok(manifest
+            .downloads
+            .iter()
+            .filter(|d| !d.is_running() && !d.paused)
+            .cloned()
+            .map(|mut d| {
+                d.downloaded_bytes = d.resume().bytes();
+                d
+            })
+            .collect())
+    });
+    result.unwrap_or_else(|e| {
+        log_warn!(MODULE, "Failed to read download manifest: {}", e);
+        Vec::new()
+    })
+}
+
+/// Find an interrupted download by ID
+pub fn find_interrupted(id: &str) -> Result<InterruptedDownload, String> {
+    list_interrupted()
+        .into_iter()
+        .find(|d| d.id == id)
+        .ok_or_else(|| format!("No interrupted download {}", id))
+}
+
+/// Delete an interrupted download with its partial file
+pub fn discard(id: &str) -> Result<(), String> {
+    let entry = update_manifest(|manifest| {
+        let index = manifest
+            .downloads
+            .iter()
+            .position(|d| d.id == id && !d.is_running())
+            .ok_or_else(|| format!("No interrupted download {}", id))?;
+        Ok(manifest.downloads.remove(index))
+    })?;
+    log_info!(MODULE, "Discarding interrupted download of {}", entry.url);
+    // Dropping the adopted directory removes it with the partial file
+    WorkDir::adopt(&entry.work_dir(), &entry.output_dir).map(drop)
+}
+
+/// Delete the partial file of a download paused in the queue, if any
+pub fn discard_paused(url: &str, output_dir: &Path) {
+    let entry = update_manifest(|manifest| {
+        let index = manifest
+            .downloads
+            .iter()
+            .position(|d| d.url == url && d.output_dir == output_dir && d.paused);
+        Ok(index.map(|index| manifest.downloads.remove(index)))
+    });
+    match entry {
+        Ok(Some(entry)) => {
+            log_info!(MODULE, "Discarding paused download of {}", entry.url);
+            if let Err(e) = WorkDir::adopt(&entry.work_dir(), &entry.output_dir) {
+                log_warn!(MODULE, "Not removing paused download: {}", e);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => log_warn!(MODULE, "Failed to update download manifest: {}", e),
+    }
+}
+
+/// Working directories the startup sweep must leave alone
+pub fn listed_work_dirs() -> Vec<PathBuf> {
+    let _lock = MANIFEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load_internal()
+        .downloads
+        .iter()
+        .map(InterruptedDownload::work_dir)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_paths() {
+        let entry = InterruptedDownload {
+            id: "download-4242-3".to_string(),
+            url: "https://dl.armbian.com/rock-5b/Armbian.img.xz".to_string(),
+            sha_url: None,
+            filename: "Armbian.img.xz".to_string(),
+            output_dir: PathBuf::from("/cache/images"),
+            total_bytes: 0,
+            downloaded_bytes: 0,
+            segments: Vec::new(),
+            started_at: String::new(),
+            pid: std::process::id(),
+            paused: false,
+        };
+        assert_eq!(
+            entry.partial_path(),
+            work_root(Path::new("/cache/images"))
+                .join("download-4242-3")
+                .join("Armbian.img.xz.downloading")
+        );
+        // An entry of this process is never interrupted, unless paused
+        assert!(entry.is_running());
+        let paused = InterruptedDownload {
+            paused: true,
+            ..entry
+        };
+        assert!(!paused.is_running());
+    }
+
+    #[test]
+    fn test_entry_resume() {
+        let output_dir =
+            std::env::temp_dir().join(format!("interrupted-test-{}", std::process::id()));
+        let mut entry = InterruptedDownload {
+            id: "download-4242-4".to_string(),
+            url: "https://dl.armbian.com/rock-5b/Armbian.img.xz".to_string(),
+            sha_url: None,
+            filename: "Armbian.img.xz".to_string(),
+            output_dir: output_dir.clone(),
+            total_bytes: 0,
+            downloaded_bytes: 0,
+            segments: Vec::new(),
+            started_at: String::new(),
+            pid: std::process::id(),
+            paused: false,
+        };
+        fs::create_dir_all(entry.work_dir()).unwrap();
+        assert_eq!(entry.resume(), Resume::Start);
+
+        fs::write(entry.partial_path(), [0u8; 300]).unwrap();
+        assert_eq!(entry.resume(), Resume::Offset(300));
+
+        // Segments count once their file is there, not before
+        entry.segments = crate::segmented::plan_segments(4000, 2, 1000);
+        entry.segments[1].downloaded_bytes = 700;
+        assert_eq!(entry.resume(), Resume::Offset(300));
+        fs::write(segmented_path(&entry.partial_path()), [0u8; 4000]).unwrap();
+        assert_eq!(entry.resume(), Resume::Segments(entry.segments.clone()));
+        assert_eq!(entry.resume().bytes(), 700);
+
+        fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
@@ -2,14 +2,28 @@
 //!
 //! Provides structured, formatted logging with file output support.
 //! Logs are written to the application's log directory with timestamps
-//! and log level indicators.
+//! and log level indicators. Console output is colored; log files are plain
+//! text, so `get_logs` and paste uploads carry no escape sequences.
+//!
+//! Old log files are removed according to a `LogRetention` policy (count and
+//! age), configured in settings and applied at startup.
+//!
+//! In developer mode, per-module minimum levels (e.g. `devices=debug,
+//! download=info`) override the global level. A rule for `flash` also covers
+//! `flash::linux::writer`; the most specific rule wins.
+//!
+//! While a download, flash or verify runs, file lines carry its operation ID
+//! (`[op=flash-20250101-120000-1]`), so a paste can be limited to the lines of
+//! a single operation.
 
 use chrono::{DateTime, Local};
 use once_cell::sync::Lazy;
 use std::fs::{self, File, OpenOptions};
 use std::io::Write;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
 
 use crate::config;
 use crate::utils::get_cache_dir;
@@ -34,6 +48,56 @@ impl LogLevel {
             LogLevel::Error => "ERROR",
         }
     }
+
+    /// Parse a level name (case-insensitive)
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "debug" => Some(LogLevel::Debug),
+            "info" => Some(LogLevel::Info),
+            "warn" | "warning" => Some(LogLevel::Warn),
+            "error" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+}
+
+/// Minimum level for a module and its submodules
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleLevel {
+    pub module: String,
+    pub level: LogLevel,
+}
+
+impl ModuleLevel {
+    fn matches(&self, module: &str) -> bool {
+        module == self.module
+            || module
+                .strip_prefix(self.module.as_str())
+                .is_some_and(|rest| rest.starts_with("::"))
+    }
+}
+
+/// Parse per-module levels like `devices=debug, download=info`
+pub fn parse_module_levels(spec: &str) -> Result<Vec<ModuleLevel>, String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .map(|rule| {
+            let (module, level) = rule
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid rule '{}', expected module=level", rule))?;
+            let module = module.trim();
+            if module.is_empty() {
+                return Err(format!("Missing module name in '{}'", rule));
+            }
+            let level = LogLevel::parse(level)
+                .ok_or_else(|| format!("Invalid log level '{}' for {}", level.trim(), module))?;
+            Ok(ModuleLevel {
+                module: module.to_string(),
+                level,
+            })
+        })
+        .collect()
 }
 
 /// Logger configuration
@@ -46,6 +110,10 @@ pub struct LoggerConfig {
     pub file_output: bool,
     /// Whether to use colors in console output
     pub use_colors: bool,
+    /// Developer mode is on, enabling debug output and module levels
+    pub developer_mode: bool,
+    /// Per-module minimum levels, applied in developer mode
+    pub module_levels: Vec<ModuleLevel>,
 }
 
 impl Default for LoggerConfig {
@@ -55,6 +123,8 @@ impl Default for LoggerConfig {
             console_output: true,
             file_output: true,
             use_colors: true,
+            developer_mode: false,
+            module_levels: Vec::new(),
         }
     }
 }
@@ -64,6 +134,8 @@ struct Logger {
     config: LoggerConfig,
     log_file: Option<File>,
     log_path: Option<PathBuf>,
+    /// ID of the running operation, tagged onto file lines
+    operation: Option<String>,
 }
 
 impl Logger {
@@ -75,6 +147,7 @@ impl Logger {
             config,
             log_file,
             log_path,
+            operation: None,
         }
     }
 
@@ -83,6 +156,19 @@ impl Logger {
         self.config.min_level = level;
     }
 
+    /// Minimum level for a module, honoring module levels in developer mode
+    fn min_level_for(&self, module: &str) -> LogLevel {
+        if !self.config.developer_mode {
+            return self.config.min_level;
+        }
+        self.config
+            .module_levels
+            .iter()
+            .filter(|rule| rule.matches(module))
+            .max_by_key(|rule| rule.module.len())
+            .map_or(self.config.min_level, |rule| rule.level)
+    }
+
     fn create_log_file() -> (Option<File>, Option<PathBuf>) {
         let log_dir = get_log_dir();
 
@@ -106,27 +192,27 @@ impl Logger {
     }
 
     fn log(&mut self, level: LogLevel, module: &str, message: &str) {
-        if level < self.config.min_level {
+        if level < self.min_level_for(module) {
             return;
         }
 
         let timestamp = Local::now();
-        let formatted_colored = self.format_message_colored(level, module, message, &timestamp);
+        let formatted_plain = self.format_message_plain(level, module, message, &timestamp);
 
         // Console output
         if self.config.console_output {
             if self.config.use_colors {
-                eprintln!("{}", formatted_colored);
-            } else {
-                let formatted = self.format_message_plain(level, module, message, &timestamp);
+                let formatted = self.format_message_colored(level, module, message, &timestamp);
                 eprintln!("{}", formatted);
+            } else {
+                eprintln!("{}", formatted_plain);
             }
         }
 
-        // File output (with ANSI colors for hastebin)
+        // File output (plain, escape sequences would litter viewers and pastes)
         if self.config.file_output {
             if let Some(ref mut file) = self.log_file {
-                let _ = writeln!(file, "{}", formatted_colored);
+                let _ = writeln!(file, "{}", formatted_plain);
                 let _ = file.flush();
             }
         }
@@ -170,16 +256,75 @@ impl Logger {
         message: &str,
         timestamp: &DateTime<Local>,
     ) -> String {
+        let operation = self
+            .operation
+            .as_ref()
+            .map(|id| format!("[{}{}] ", OPERATION_TAG, id))
+            .unwrap_or_default();
         format!(
-            "[{}] [{}] [{}] {}",
-            timestamp.format("%Y-%m-%d %H:%M:%S%.3f"),
+            "[{}] [{}] [{}] {}{}",
+            timestamp.format(LINE_TIMESTAMP_FORMAT),
             level.as_str(),
             module,
+            operation,
             message
         )
     }
 }
 
+/// Timestamp format at the start of every log file line
+pub const LINE_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+/// Prefix of the operation tag in log file lines
+pub const OPERATION_TAG: &str = "op=";
+
+/// Sequence number making operation IDs unique within a session
+static OPERATION_SEQ: AtomicU64 = AtomicU64::new(1);
+
+/// Tags log lines with an operation ID until dropped
+///
+/// The tag is process-wide: lines logged from any thread while the scope is
+/// alive carry it, which covers the blocking writer threads of a flash.
+pub struct OperationScope {
+    id: String,
+}
+
+impl OperationScope {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Drop for OperationScope {
+    fn drop(&mut self) {
+        if let Ok(mut logger) = LOGGER.lock() {
+            // A later operation may have taken over the tag, leave it alone
+            if logger.operation.as_deref() == Some(self.id.as_str()) {
+                logger.operation = None;
+            }
+        }
+    }
+}
+
+/// Start tagging log lines with a new operation ID such as `flash-20250101-120000-1`
+pub fn begin_operation(kind: &str) -> OperationScope {
+    let id = format!(
+        "{}-{}-{}",
+        kind,
+        Local::now().format("%Y%m%d-%H%M%S"),
+        OPERATION_SEQ.fetch_add(1, Ordering::SeqCst)
+    );
+    if let Ok(mut logger) = LOGGER.lock() {
+        logger.operation = Some(id.clone());
+        logger.log(
+            LogLevel::Info,
+            "logging",
+            &format!("Operation {} started", id),
+        );
+    }
+    OperationScope { id }
+}
+
 /// Global logger instance
 static LOGGER: Lazy<Mutex<Logger>> = Lazy::new(|| Mutex::new(Logger::new()));
 
@@ -235,6 +380,7 @@ pub fn set_log_level(debug_enabled: bool) {
             LogLevel::Info
         };
         logger.set_min_level(new_level);
+        logger.config.developer_mode = debug_enabled;
 
         // Log the change after setting it (using the new level)
         let level_str = if debug_enabled { "DEBUG" } else { "INFO" };
@@ -246,6 +392,26 @@ pub fn set_log_level(debug_enabled: bool) {
     }
 }
 
+/// Set the per-module minimum levels used in developer mode
+pub fn set_module_levels(levels: Vec<ModuleLevel>) {
+    if let Ok(mut logger) = LOGGER.lock() {
+        let summary = levels
+            .iter()
+            .map(|rule| format!("{}={}", rule.module, rule.level.as_str()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        logger.config.module_levels = levels;
+        logger.log(
+            LogLevel::Info,
+            "logging",
+            &format!(
+                "Module log levels: {}",
+                if summary.is_empty() { "none" } else { &summary }
+            ),
+        );
+    }
+}
+
 /// Log a message with format arguments (debug level)
 #[macro_export]
 macro_rules! log_debug {
@@ -278,8 +444,42 @@ macro_rules! log_error {
     };
 }
 
-/// Clean up old log files, keeping only the most recent ones
-pub fn cleanup_old_logs(keep_count: usize) -> Result<usize, String> {
+/// Which old log files to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogRetention {
+    /// Number of most recent log files kept (at least 1, the current log)
+    pub keep_count: usize,
+    /// Log files last written longer ago than this are deleted
+    pub max_age: Option<Duration>,
+}
+
+impl LogRetention {
+    /// Policy from the retention settings (0 days keeps logs regardless of age)
+    pub fn new(keep_count: u64, max_age_days: u64) -> Self {
+        Self {
+            keep_count: keep_count.max(1) as usize,
+            max_age: (max_age_days > 0).then(|| Duration::from_secs(max_age_days * 24 * 60 * 60)),
+        }
+    }
+
+    /// Whether the log file at `index` (0 = newest), last written `age` ago, is kept
+    fn keeps(&self, index: usize, age: Duration) -> bool {
+        // The newest file is the current log and is never deleted
+        index == 0 || (index < self.keep_count && self.max_age.map_or(true, |max| age <= max))
+    }
+}
+
+impl Default for LogRetention {
+    fn default() -> Self {
+        Self::new(
+            config::logging::DEFAULT_RETENTION_COUNT,
+            config::logging::DEFAULT_RETENTION_DAYS,
+        )
+    }
+}
+
+/// Clean up old log files according to the retention policy
+pub fn cleanup_old_logs(retention: LogRetention) -> Result<usize, String> {
     let log_dir = get_log_dir();
 
     if !log_dir.exists() {
@@ -290,18 +490,22 @@ pub fn cleanup_old_logs(keep_count: usize) -> Result<usize, String> {
         .map_err(|e| format!("Failed to read log directory: {}", e))?
         .filter_map(|entry| entry.ok())
         .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "log"))
+        .map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).ok();
+            (entry, modified)
+        })
         .collect();
 
     // Sort by modification time (newest first)
-    log_files.sort_by(|a, b| {
-        let a_time = a.metadata().and_then(|m| m.modified()).ok();
-        let b_time = b.metadata().and_then(|m| m.modified()).ok();
-        b_time.cmp(&a_time)
-    });
+    log_files.sort_by(|(_, a_time), (_, b_time)| b_time.cmp(a_time));
 
+    let now = SystemTime::now();
     let mut deleted = 0;
-    for entry in log_files.into_iter().skip(keep_count) {
-        if fs::remove_file(entry.path()).is_ok() {
+    for (index, (entry, modified)) in log_files.into_iter().enumerate() {
+        let age = modified
+            .and_then(|time| now.duration_since(time).ok())
+            .unwrap_or_default();
+        if !retention.keeps(index, age) && fs::remove_file(entry.path()).is_ok() {
             deleted += 1;
         }
     }
@@ -339,9 +543,11 @@ pub fn init() {
     if let Some(path) = get_current_log_path() {
         info("logger", &format!("Log file: {}", path.display()));
     }
+}
 
-    // Clean up old logs (keep last 10)
-    match cleanup_old_logs(10) {
+/// Apply the log retention policy, logging what was removed
+pub fn apply_retention(retention: LogRetention) {
+    match cleanup_old_logs(retention) {
         Ok(deleted) if deleted > 0 => {
             info("logger", &format!("Cleaned up {} old log files", deleted));
         }
@@ -369,4 +575,66 @@ mod tests {
         assert!(log_dir.to_string_lossy().contains("armbian-imager"));
         assert!(log_dir.to_string_lossy().contains("logs"));
     }
+
+    #[test]
+    fn test_parse_module_levels() {
+        let levels = parse_module_levels("devices=debug, download = INFO,").unwrap();
+        assert_eq!(
+            levels,
+            vec![
+                ModuleLevel {
+                    module: "devices".to_string(),
+                    level: LogLevel::Debug
+                },
+                ModuleLevel {
+                    module: "download".to_string(),
+                    level: LogLevel::Info
+                },
+            ]
+        );
+        assert!(parse_module_levels("").unwrap().is_empty());
+        assert!(parse_module_levels("devices").is_err());
+        assert!(parse_module_levels("devices=loud").is_err());
+    }
+
+    #[test]
+    fn test_module_level_matching() {
+        let rule = ModuleLevel {
+            module: "flash".to_string(),
+            level: LogLevel::Warn,
+        };
+        assert!(rule.matches("flash"));
+        assert!(rule.matches("flash::linux::writer"));
+        assert!(!rule.matches("flashing"));
+    }
+
+    #[test]
+    fn test_log_retention() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        let retention = LogRetention::new(3, 7);
+
+        assert!(retention.keeps(1, day));
+        assert!(!retention.keeps(3, day));
+        assert!(!retention.keeps(1, 8 * day));
+        // The current log survives even a zero count
+        assert!(LogRetention::new(0, 7).keeps(0, 30 * day));
+        // No age limit
+        assert!(LogRetention::new(3, 0).keeps(2, 365 * day));
+    }
+
+    #[test]
+    fn test_operation_scope() {
+        let current = || LOGGER.lock().unwrap().operation.clone();
+
+        let first = begin_operation("flash");
+        assert!(first.id().starts_with("flash-"));
+        assert_eq!(current().as_deref(), Some(first.id()));
+
+        // Ending an older scope leaves a newer operation tagged
+        let second = begin_operation("verify");
+        drop(first);
+        assert_eq!(current().as_deref(), Some(second.id()));
+        drop(second);
+        assert_eq!(current(), None);
+    }
 }
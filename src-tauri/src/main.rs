@@ -6,52 +6,52 @@
 // Prevents additional console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod audit;
+mod benchmark;
+mod board_photos;
 mod cache;
 mod commands;
 mod config;
+mod credentials;
+mod cues;
+mod customization;
 mod decompress;
 mod devices;
+mod direct;
+mod diskspace;
 mod download;
+mod events;
 mod flash;
+mod fleet;
+mod history;
 mod images;
+mod interrupted;
 mod logging;
+mod mirrors;
 mod paste;
+mod proxy;
+mod queue;
+mod segmented;
+mod selftest;
+mod settings;
+mod settings_store;
 mod utils;
+mod workdir;
 
 use commands::AppState;
 #[allow(unused_imports)] // Used by get_webview_window in debug builds
-use tauri::Manager;
-use tauri_plugin_store::StoreExt;
-
-use crate::utils::get_cache_dir;
+use tauri::{Manager, RunEvent};
 
 /// Manage cached download images based on cache settings
 ///
 /// If cache is disabled, clears all cached images.
 /// If cache is enabled, enforces the maximum cache size by evicting oldest files.
 fn manage_download_cache(app: &tauri::App) {
-    // Load cache settings from store
-    let (cache_enabled, cache_max_size) = match app.store("settings.json") {
-        Ok(store) => {
-            let enabled = store
-                .get("cache_enabled")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(true);
-            let max_size = store
-                .get("cache_max_size")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(cache::DEFAULT_MAX_SIZE);
-            (enabled, max_size)
-        }
-        Err(e) => {
-            log_warn!(
-                "main",
-                "Failed to load cache settings: {}. Using defaults.",
-                e
-            );
-            (true, cache::DEFAULT_MAX_SIZE)
-        }
-    };
+    let settings::Settings {
+        cache_enabled,
+        cache_max_size,
+        ..
+    } = settings::current(app.handle());
 
     if !cache_enabled {
         // Cache disabled - clear all cached images
@@ -74,9 +74,11 @@ fn manage_download_cache(app: &tauri::App) {
 
 /// Clean up orphaned decompressed custom images from previous sessions
 fn cleanup_custom_decompress_cache() {
-    let custom_dir = get_cache_dir(config::app::NAME).join("custom-decompress");
+    for custom_dir in decompress::decompress_dirs() {
+        if !custom_dir.exists() {
+            continue;
+        }
 
-    if custom_dir.exists() {
         if let Ok(entries) = std::fs::read_dir(&custom_dir) {
             for entry in entries.flatten() {
                 let path = entry.path();
@@ -106,6 +108,16 @@ fn main() {
     // Initialize logging system
     logging::init();
 
+    // Hidden end-to-end self-test, used by CI; runs without a window
+    if let Some(scratch_path) = selftest::scratch_path_from_args(std::env::args_os()) {
+        std::process::exit(selftest::run_cli(&scratch_path));
+    }
+
+    // Hidden elevated writer for first-boot settings, started through pkexec
+    if let Some(request_path) = customization::elevated_request_from_args(std::env::args_os()) {
+        std::process::exit(customization::run_elevated(&request_path));
+    }
+
     // Log startup info
     log_info!("main", "=== Armbian Imager Starting ===");
     log_info!("main", "Version: {}", env!("CARGO_PKG_VERSION"));
@@ -123,6 +135,17 @@ fn main() {
         config::urls::BOARD_IMAGES_BASE
     );
 
+    // Inside a Flatpak, file pickers must go through the FileChooser portal so
+    // picked images are shared into the sandbox via the Documents portal
+    #[cfg(target_os = "linux")]
+    if utils::is_flatpak() {
+        log_info!(
+            "main",
+            "Running inside Flatpak, using portals for file access"
+        );
+        std::env::set_var("GTK_USE_PORTAL", "1");
+    }
+
     // Clean up orphaned custom decompressed images from previous sessions
     // (Cache management is done in setup with access to settings)
     cleanup_custom_decompress_cache();
@@ -153,39 +176,136 @@ fn main() {
             commands::board_queries::get_boards,
             commands::board_queries::get_images_for_board,
             commands::board_queries::get_block_devices,
+            commands::board_queries::get_device_locks,
+            commands::board_queries::check_image_update,
+            commands::board_queries::get_flashed_board_advisories,
+            commands::board_queries::get_application_catalog,
             commands::scraping::get_board_image_url,
+            commands::scraping::clear_board_image_cache,
             commands::operations::request_write_authorization,
+            commands::operations::get_privilege_status,
+            commands::operations::probe_image_download,
+            commands::operations::check_metered_download,
             commands::operations::download_image,
             commands::operations::flash_image,
+            commands::operations::flash_image_direct,
+            commands::operations::flash_range,
             commands::operations::delete_downloaded_image,
             commands::operations::force_delete_cached_image,
+            commands::operations::export_cached_image,
             commands::operations::continue_download_without_sha,
             commands::operations::cleanup_failed_download,
+            commands::operations::estimate_operation,
+            commands::operations::get_device_reliability,
+            commands::operations::get_mirror_stats,
+            commands::operations::measure_mirrors,
+            commands::operations::verify_device,
+            commands::operations::eject_device,
+            commands::operations::erase_device,
+            commands::operations::check_device_capacity,
+            commands::fleet::start_fleet_mode,
+            commands::fleet::stop_fleet_mode,
+            commands::fleet::get_fleet_status,
+            commands::backup::backup_device,
+            commands::partitions::inspect_image,
+            commands::partitions::list_image_partitions,
+            commands::partitions::extract_partition,
+            commands::partitions::mount_partition,
             commands::progress::cancel_operation,
+            commands::progress::get_jobs,
+            commands::progress::cancel_job,
             commands::progress::get_download_progress,
             commands::progress::get_flash_progress,
+            commands::timeseries::get_operation_timeseries,
             commands::custom_image::select_custom_image,
             commands::custom_image::check_needs_decompression,
+            commands::custom_image::verify_custom_image,
+            commands::custom_image::get_decompress_placement,
             commands::custom_image::decompress_custom_image,
             commands::custom_image::delete_decompressed_custom_image,
             commands::custom_image::detect_board_from_filename,
+            commands::customization::apply_customization,
+            commands::customization::apply_network_config,
+            commands::credentials::get_download_credentials,
+            commands::credentials::set_download_credentials,
+            commands::credentials::remove_download_credentials,
+            commands::queue::get_download_queue,
+            commands::queue::enqueue_download,
+            commands::queue::remove_queue_item,
+            commands::queue::move_queue_item,
+            commands::queue::set_queue_item_paused,
+            commands::queue::set_queue_item_priority,
+            commands::queue::set_download_queue_options,
+            commands::queue::clear_completed_queue_items,
+            commands::queue::get_interrupted_downloads,
+            commands::queue::resume_interrupted_download,
+            commands::queue::discard_interrupted_download,
             commands::system::open_url,
+            commands::system::reveal_path,
+            commands::system::open_new_window,
+            commands::system::run_selftest,
+            commands::system::run_benchmark,
+            commands::onboarding::get_onboarding_state,
+            commands::onboarding::advance_onboarding,
+            commands::confirmation::confirm_dangerous_target,
             commands::system::get_system_locale,
+            commands::system::get_locale_info,
             commands::system::log_from_frontend,
             commands::system::log_debug_from_frontend,
+            commands::system::export_audit_log,
+            commands::system::export_history,
+            commands::system::set_operation_note,
             commands::update::get_github_release,
             paste::upload::upload_logs,
+            paste::upload::list_log_operations,
+            commands::settings::get_settings,
+            commands::settings::update_settings,
             commands::settings::get_theme,
             commands::settings::set_theme,
             commands::settings::get_language,
             commands::settings::set_language,
+            commands::settings::get_device_aliases,
+            commands::settings::set_device_alias,
             commands::settings::get_show_motd,
             commands::settings::set_show_motd,
             commands::settings::get_show_updater_modal,
             commands::settings::set_show_updater_modal,
             commands::settings::get_developer_mode,
             commands::settings::set_developer_mode,
+            commands::settings::get_write_failure_report,
+            commands::settings::set_write_failure_report,
+            commands::settings::get_verify_mode,
+            commands::settings::set_verify_mode,
+            commands::settings::get_erase_mode,
+            commands::settings::set_erase_mode,
+            commands::settings::get_smart_write,
+            settings_store::save_settings,
+            settings_store::repair_settings,
+            commands::settings::get_completion_cues,
+            commands::settings::set_completion_cues,
+            commands::settings::preview_completion_cue,
+            commands::settings::get_catalog_sources,
+            commands::settings::set_catalog_sources,
+            commands::settings::get_skip_zero_blocks,
+            commands::settings::get_mirror_region,
+            commands::settings::set_mirror_region,
+            commands::settings::set_smart_write,
+            commands::settings::set_skip_zero_blocks,
             commands::settings::get_logs,
+            commands::settings::get_module_log_levels,
+            commands::settings::set_module_log_levels,
+            commands::settings::get_download_throttle_kbps,
+            commands::settings::set_download_throttle_kbps,
+            commands::settings::get_write_throttle_kbps,
+            commands::settings::set_write_throttle_kbps,
+            commands::settings::get_proxy,
+            commands::settings::set_proxy,
+            commands::settings::get_paste_url,
+            commands::settings::set_paste_url,
+            commands::settings::get_log_retention_count,
+            commands::settings::set_log_retention_count,
+            commands::settings::get_log_retention_days,
+            commands::settings::set_log_retention_days,
             commands::settings::get_system_info,
             commands::settings::get_tauri_version,
             commands::settings::get_cache_enabled,
@@ -195,6 +315,17 @@ fn main() {
             commands::settings::get_cache_size,
             commands::settings::clear_cache,
         ])
+        .on_window_event(|window, event| {
+            // Operations belong to their window; stop them when it goes away
+            if let tauri::WindowEvent::Destroyed = event {
+                window.state::<AppState>().release_window(window.label());
+            }
+            if let tauri::WindowEvent::Focused(true) = event {
+                if let Some(webview) = window.app_handle().get_webview_window(window.label()) {
+                    cues::clear_badge(&webview);
+                }
+            }
+        })
         .setup(|app| {
             #[cfg(debug_assertions)]
             {
@@ -203,35 +334,55 @@ fn main() {
                 }
             }
 
+            // Recover a settings file broken by a crash before anything reads it
+            settings_store::open_settings(app.handle());
+
+            // Load the settings, migrating an older layout
+            let loaded = settings::load(app.handle());
+
             // Initialize log level based on developer mode setting
-            match app.store("settings.json") {
-                Ok(store) => {
-                    let developer_mode = store
-                        .get("developer_mode")
-                        .and_then(|v| v.as_bool())
-                        .unwrap_or(false);
-
-                    if developer_mode {
-                        log_info!("main", "Developer mode enabled, setting log level to DEBUG");
-                        logging::set_log_level(true);
-                    } else {
-                        log_info!("main", "Developer mode disabled, using default log level");
-                    }
-                }
-                Err(e) => {
-                    log_warn!(
-                        "main",
-                        "Failed to access settings store: {}. Using default log level (INFO).",
-                        e
-                    );
+            if loaded.developer_mode {
+                log_info!("main", "Developer mode enabled, setting log level to DEBUG");
+                logging::set_log_level(true);
+                match logging::parse_module_levels(&loaded.module_log_levels) {
+                    Ok(levels) if !levels.is_empty() => logging::set_module_levels(levels),
+                    Ok(_) => {}
+                    Err(e) => log_warn!("main", "Ignoring module log levels: {}", e),
                 }
+            } else {
+                log_info!("main", "Developer mode disabled, using default log level");
             }
 
+            events::start_frontend_bridge(app.handle().clone());
+            cues::start_completion_cues(app.handle().clone());
+
+            // Push the device list to the frontend when media comes and goes
+            let handle = app.handle().clone();
+            devices::start_device_watcher(move || {
+                commands::board_queries::push_block_devices(&handle)
+            });
+
+            // Remove partial files left behind by a crashed run, except
+            // interrupted downloads the user may resume
+            workdir::sweep_stale_workdirs();
+
+            // Resume queued downloads
+            commands::queue::start_queue_worker(app.handle().clone());
+
+            // Delete old logs according to the retention settings
+            commands::settings::apply_log_retention(app.handle().clone());
+
             // Manage download cache based on settings
             manage_download_cache(app);
 
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            // Before the store plugin saves the settings in place on exit
+            if let RunEvent::ExitRequested { .. } = event {
+                settings_store::close_settings(app);
+            }
+        });
 }
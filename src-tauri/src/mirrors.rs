@@ -0,0 +1,250 @@
+//! Download mirrors
+//!
+//! Catalog downloads point at Armbian's redirector, which sends each client
+//! to a mirror of its choice. Every mirror serves the same tree, so when the
+//! chosen one fails the file can be fetched under the same path elsewhere.
+//! The user may prefer a region; within a region mirrors are tried by
+//! measured latency, and mirrors the download history marks as flaky last.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use futures_util::future::join_all;
+use serde::Serialize;
+
+use crate::config;
+use crate::history::{load_history, MirrorStats};
+use crate::proxy;
+use crate::{log_debug, log_info};
+
+const MODULE: &str = "mirrors";
+
+/// Latencies measured this session, by host
+static MEASURED: Mutex<Vec<(String, Option<u64>)>> = Mutex::new(Vec::new());
+
+/// A known mirror and how quickly it answered
+#[derive(Debug, Clone, Serialize)]
+pub struct Mirror {
+    pub region: String,
+    pub base_url: String,
+    pub host: String,
+    /// Time to answer a HEAD request, None if it did not answer
+    pub latency_ms: Option<u64>,
+}
+
+/// Whether `region` is a known region or "auto"
+pub fn is_valid_region(region: &str) -> bool {
+    config::mirrors::REGIONS.contains(&region)
+}
+
+fn host_of(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()))
+        .unwrap_or_default()
+}
+
+/// Path of `url` below the redirector or a known mirror
+fn mirror_path(url: &str) -> Option<&str> {
+    std::iter::once(config::mirrors::REDIRECTOR)
+        .chain(config::mirrors::MIRRORS.iter().map(|(_, base)| *base))
+        .find_map(|base| url.strip_prefix(base))
+        .filter(|path| !path.is_empty())
+}
+
+/// Probe every known mirror's latency with a HEAD request
+///
+/// The results are kept for the session and order later downloads.
+pub async fn measure_mirrors() -> Vec<Mirror> {
+    let client = match proxy::client_builder()
+        .user_agent(config::app::USER_AGENT)
+        .timeout(Duration::from_secs(config::mirrors::PROBE_TIMEOUT_SECS))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            log_info!(MODULE, "Failed to create HTTP client: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let probes = config::mirrors::MIRRORS.iter().map(|(region, base_url)| {
+        let client = client.clone();
+        async move {
+            let started = Instant::now();
+            let latency_ms = match client.head(*base_url).send().await {
+                Ok(_) => Some(started.elapsed().as_millis() as u64),
+                Err(e) => {
+                    log_debug!(MODULE, "Mirror {} did not answer: {}", base_url, e);
+                    None
+                }
+            };
+            Mirror {
+                region: region.to_string(),
+                base_url: base_url.to_string(),
+                host: host_of(base_url),
+                latency_ms,
+            }
+        }
+    });
+    let mirrors = join_all(probes).await;
+
+    for mirror in &mirrors {
+        log_info!(
+            MODULE,
+            "Mirror {} ({}): {}",
+            mirror.host,
+            mirror.region,
+            mirror
+                .latency_ms
+                .map_or("unreachable".to_string(), |ms| format!("{} ms", ms))
+        );
+    }
+    *MEASURED.lock().unwrap_or_else(|e| e.into_inner()) = mirrors
+        .iter()
+        .map(|m| (m.host.clone(), m.latency_ms))
+        .collect();
+    mirrors
+}
+
+/// URLs to download `url` from, in the order they should be tried
+///
+/// URLs outside the redirector's tree, e.g. custom sources, have no
+/// alternatives. Latencies are measured once per session on first use.
+pub async fn candidate_urls(url: &str, region: &str) -> Vec<String> {
+    if mirror_path(url).is_none() {
+        return vec![url.to_string()];
+    }
+
+    let measured = MEASURED.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let measured = if measured.is_empty() {
+        measure_mirrors()
+            .await
+            .into_iter()
+            .map(|m| (m.host, m.latency_ms))
+            .collect()
+    } else {
+        measured
+    };
+
+    let candidates = rank_candidates(url, region, &measured, &load_history().mirror_stats());
+    log_debug!(MODULE, "Download candidates: {:?}", candidates);
+    candidates
+}
+
+/// Order the URLs `url` can be fetched from
+///
+/// With "auto" the catalog URL goes first, then the redirector; with a
+/// region, that region's mirrors go before them. Mirrors are ordered by the
+/// measured latency (unreachable ones last), then by their average latency
+/// in the history; flaky mirrors go after all others.
+fn rank_candidates(
+    url: &str,
+    region: &str,
+    measured: &[(String, Option<u64>)],
+    stats: &[MirrorStats],
+) -> Vec<String> {
+    let Some(path) = mirror_path(url) else {
+        return vec![url.to_string()];
+    };
+
+    let mut mirrors: Vec<(&str, &str)> = config::mirrors::MIRRORS.to_vec();
+    mirrors.sort_by_key(|(_, base)| {
+        let host = host_of(base);
+        let stat = stats.iter().find(|s| s.host == host);
+        let latency = match measured.iter().find(|(h, _)| *h == host) {
+            Some((_, Some(ms))) => *ms,
+            Some((_, None)) => u64::MAX,
+            None => stat.and_then(|s| s.avg_latency_ms).unwrap_or(u64::MAX - 1),
+        };
+        (stat.is_some_and(|s| s.flaky), latency)
+    });
+
+    let (preferred, others): (Vec<_>, Vec<_>) = mirrors
+        .into_iter()
+        .partition(|(mirror_region, _)| *mirror_region == region);
+    let mirror_url = |(_, base): (&str, &str)| format!("{}{}", base, path);
+
+    let mut candidates: Vec<String> = preferred.into_iter().map(mirror_url).collect();
+    candidates.push(url.to_string());
+    candidates.push(format!("{}{}", config::mirrors::REDIRECTOR, path));
+    candidates.extend(others.into_iter().map(mirror_url));
+
+    let mut seen = Vec::new();
+    candidates.retain(|candidate| {
+        let new = !seen.contains(candidate);
+        seen.push(candidate.clone());
+        new
+    });
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://dl.armbian.com/orangepi5/archive/Armbian.img.xz";
+
+    fn stats(host: &str, flaky: bool) -> MirrorStats {
+        MirrorStats {
+            host: host.to_string(),
+            successes: 0,
+            failures: 0,
+            consecutive_failures: 0,
+            avg_latency_ms: None,
+            avg_speed: None,
+            last_success_at: None,
+            last_failure_at: None,
+            last_error: None,
+            flaky,
+        }
+    }
+
+    #[test]
+    fn test_auto_keeps_redirector_first() {
+        let candidates = rank_candidates(URL, "auto", &[], &[]);
+        assert_eq!(candidates[0], URL);
+        assert_eq!(candidates.len(), config::mirrors::MIRRORS.len() + 1);
+        assert!(candidates
+            .iter()
+            .all(|c| c.ends_with("/orangepi5/archive/Armbian.img.xz")));
+    }
+
+    #[test]
+    fn test_region_latency_and_flaky_order() {
+        let measured = vec![
+            ("mirror.netcologne.de".to_string(), Some(20)),
+            ("mirrors.dotsrc.org".to_string(), Some(80)),
+            ("mirrors.tuna.tsinghua.edu.cn".to_string(), None),
+        ];
+        let candidates = rank_candidates(URL, "europe", &measured, &[]);
+        assert!(candidates[0].starts_with("https://mirror.netcologne.de/"));
+        assert!(candidates[1].starts_with("https://mirrors.dotsrc.org/"));
+        assert_eq!(candidates[2], URL);
+        assert!(candidates
+            .last()
+            .unwrap()
+            .starts_with("https://mirrors.tuna.tsinghua.edu.cn/"));
+
+        // A flaky mirror goes last in its region despite answering fastest
+        let history = [stats("mirror.netcologne.de", true)];
+        let candidates = rank_candidates(URL, "europe", &measured, &history);
+        assert!(candidates[0].starts_with("https://mirrors.dotsrc.org/"));
+    }
+
+    #[test]
+    fn test_other_hosts_have_no_alternatives() {
+        let url = "https://example.com/images/custom.img.xz";
+        assert_eq!(rank_candidates(url, "asia", &[], &[]), vec![url]);
+
+        // A catalog URL already on a mirror can fail over to the others
+        let url = "https://mirrors.dotsrc.org/armbian-dl/rock5b/archive/Armbian.img.xz";
+        let candidates = rank_candidates(url, "auto", &[], &[]);
+        assert_eq!(candidates[0], url);
+        assert!(candidates.contains(&format!(
+            "{}rock5b/archive/Armbian.img.xz",
+            config::mirrors::REDIRECTOR
+        )));
+        assert_eq!(candidates.len(), config::mirrors::MIRRORS.len() + 1);
+    }
+}
@@ -2,16 +2,61 @@
 //!
 //! Uploads application logs to the Armbian paste service for debugging.
 //! The service is a Hastebin instance that accepts raw text via POST.
+//!
+//! By default the current session and up to two previous logs are sent. A
+//! `LogSelection` narrows the paste to the lines of one operation and/or a
+//! time range, searched across all kept log files.
+//!
+//! Organisations can point uploads at their own Hastebin-compatible service
+//! with the `paste_url` setting. Every upload to such an endpoint is logged
+//! as a warning, since whoever runs it receives the logs.
 
 use std::fs;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use tauri::AppHandle;
 
-use crate::logging::{get_current_log_path, get_log_dir};
-use crate::{log_error, log_info};
+use crate::commands::settings::get_paste_url;
+use crate::config;
+use crate::logging::{get_current_log_path, get_log_dir, LINE_TIMESTAMP_FORMAT, OPERATION_TAG};
+use crate::proxy;
+use crate::{log_error, log_info, log_warn};
 
-/// Paste service configuration
-const PASTE_URL: &str = "https://paste.armbian.com";
+/// Path of the upload endpoint on the paste service
 const PASTE_ENDPOINT: &str = "/log";
 
+/// Check a paste service URL and normalize it to `scheme://host[:port][/path]`
+///
+/// HTTPS is required, except for services on the local machine.
+pub fn normalize_paste_url(url: &str) -> Result<String, String> {
+    let parsed =
+        reqwest::Url::parse(url.trim()).map_err(|e| format!("Invalid paste URL: {}", e))?;
+
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| "Paste URL has no host".to_string())?;
+    let local = matches!(host, "localhost" | "127.0.0.1" | "[::1]");
+    match parsed.scheme() {
+        "https" => {}
+        "http" if local => {}
+        scheme => {
+            return Err(format!(
+                "Paste URL must use https, not {} (logs would travel unencrypted)",
+                scheme
+            ))
+        }
+    }
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        return Err("Paste URL must not contain credentials".to_string());
+    }
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err("Paste URL must not contain a query or fragment".to_string());
+    }
+
+    Ok(parsed.as_str().trim_end_matches('/').to_string())
+}
+
 /// Result of uploading logs
 #[derive(serde::Serialize)]
 pub struct UploadResult {
@@ -21,8 +66,126 @@ pub struct UploadResult {
     pub key: String,
 }
 
-/// Collect all relevant log content for upload
-fn collect_logs() -> Result<String, String> {
+/// Part of the logs to upload, all given criteria must match
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogSelection {
+    /// Operation ID as tagged on log lines (e.g. `flash-20250101-120000-1`)
+    pub operation_id: Option<String>,
+    /// RFC 3339 start of the time range
+    pub since: Option<String>,
+    /// RFC 3339 end of the time range
+    pub until: Option<String>,
+}
+
+/// `LogSelection` resolved for matching log lines
+#[derive(Debug, Default)]
+struct LineFilter {
+    operation_tag: Option<String>,
+    since: Option<NaiveDateTime>,
+    until: Option<NaiveDateTime>,
+}
+
+impl LineFilter {
+    fn from_selection(selection: &LogSelection) -> Result<Self, String> {
+        // Log lines carry local time without an offset
+        let parse = |value: &Option<String>| -> Result<Option<NaiveDateTime>, String> {
+            value
+                .as_deref()
+                .map(|s| {
+                    DateTime::parse_from_rfc3339(s)
+                        .map(|t| t.with_timezone(&Local).naive_local())
+                        .map_err(|e| format!("Invalid time '{}': {}", s, e))
+                })
+                .transpose()
+        };
+
+        Ok(Self {
+            operation_tag: selection
+                .operation_id
+                .as_ref()
+                .map(|id| format!("[{}{}]", OPERATION_TAG, id)),
+            since: parse(&selection.since)?,
+            until: parse(&selection.until)?,
+        })
+    }
+
+    fn matches(&self, line: &str, timestamp: NaiveDateTime) -> bool {
+        self.operation_tag
+            .as_ref()
+            .map_or(true, |tag| line.contains(tag.as_str()))
+            && self.since.map_or(true, |since| timestamp >= since)
+            && self.until.map_or(true, |until| timestamp <= until)
+    }
+}
+
+/// Timestamp at the start of a log line, None for continuation lines
+fn line_timestamp(line: &str) -> Option<NaiveDateTime> {
+    let end = line.find(']')?;
+    NaiveDateTime::parse_from_str(line.get(1..end)?, LINE_TIMESTAMP_FORMAT).ok()
+}
+
+/// Lines of a log matching the filter
+///
+/// Continuation lines of a multi-line message follow the line they belong to.
+fn select_lines(content: &str, filter: &LineFilter) -> String {
+    let mut selected = String::new();
+    let mut keep = false;
+    for line in content.lines() {
+        if let Some(timestamp) = line_timestamp(line) {
+            keep = filter.matches(line, timestamp);
+        }
+        if keep {
+            selected.push_str(line);
+            selected.push('\n');
+        }
+    }
+    selected
+}
+
+/// Operation IDs tagged in a log, in order of first appearance
+fn operation_ids(content: &str) -> Vec<String> {
+    let prefix = format!("[{}", OPERATION_TAG);
+    let mut ids: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let Some(start) = line.find(&prefix) else {
+            continue;
+        };
+        let rest = &line[start + prefix.len()..];
+        if let Some(end) = rest.find(']') {
+            let id = &rest[..end];
+            if !ids.iter().any(|known| known == id) {
+                ids.push(id.to_string());
+            }
+        }
+    }
+    ids
+}
+
+/// Log files in the log directory, sorted by modification time (newest first)
+fn log_files_newest_first() -> Result<Vec<PathBuf>, String> {
+    let log_dir = get_log_dir();
+    if !log_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut log_files: Vec<_> = fs::read_dir(&log_dir)
+        .map_err(|e| format!("Failed to read log directory: {}", e))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "log"))
+        .collect();
+
+    log_files.sort_by(|a, b| {
+        let a_time = a.metadata().and_then(|m| m.modified()).ok();
+        let b_time = b.metadata().and_then(|m| m.modified()).ok();
+        b_time.cmp(&a_time)
+    });
+
+    Ok(log_files.into_iter().map(|entry| entry.path()).collect())
+}
+
+/// Header describing the system, common to all uploads
+fn upload_header() -> String {
     let mut content = String::new();
 
     // Add header with system info
@@ -38,6 +201,46 @@ fn collect_logs() -> Result<String, String> {
         std::env::consts::ARCH
     ));
     content.push('\n');
+    content
+}
+
+/// Collect the selected log lines from all kept log files, oldest first
+fn collect_selected_logs(selection: &LogSelection) -> Result<String, String> {
+    let filter = LineFilter::from_selection(selection)?;
+    let mut content = upload_header();
+    content.push_str(&format!(
+        "Selection: operation {}, since {}, until {}\n",
+        selection.operation_id.as_deref().unwrap_or("any"),
+        selection.since.as_deref().unwrap_or("start"),
+        selection.until.as_deref().unwrap_or("now")
+    ));
+
+    let mut matched = false;
+    for path in log_files_newest_first()?.iter().rev() {
+        let Ok(log_content) = fs::read_to_string(path) else {
+            continue;
+        };
+        let selected = select_lines(&log_content, &filter);
+        if selected.is_empty() {
+            continue;
+        }
+        matched = true;
+        content.push_str(&format!(
+            "\n=== Log: {} ===\n",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        content.push_str(&selected);
+    }
+
+    if !matched {
+        return Err("No log lines match the selection".to_string());
+    }
+    Ok(content)
+}
+
+/// Collect all relevant log content for upload
+fn collect_logs() -> Result<String, String> {
+    let mut content = upload_header();
 
     // Get current session log
     if let Some(log_path) = get_current_log_path() {
@@ -55,81 +258,90 @@ fn collect_logs() -> Result<String, String> {
     }
 
     // Check for previous session logs (in case of crash recovery)
-    let log_dir = get_log_dir();
-    if log_dir.exists() {
-        let mut log_files: Vec<_> = fs::read_dir(&log_dir)
-            .map_err(|e| format!("Failed to read log directory: {}", e))?
-            .filter_map(|entry| entry.ok())
-            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "log"))
-            .collect();
-
-        // Sort by modification time (newest first)
-        log_files.sort_by(|a, b| {
-            let a_time = a.metadata().and_then(|m| m.modified()).ok();
-            let b_time = b.metadata().and_then(|m| m.modified()).ok();
-            b_time.cmp(&a_time)
-        });
-
-        // Include up to 2 previous logs if they exist
-        let current_log = get_current_log_path();
-        let mut included = 0;
-        for entry in log_files.iter() {
-            let path = entry.path();
-
-            // Skip current log (already included)
-            if let Some(ref current) = current_log {
-                if &path == current {
-                    continue;
-                }
-            }
+    let log_files = log_files_newest_first()?;
 
-            if included >= 2 {
-                break;
+    // Include up to 2 previous logs if they exist
+    let current_log = get_current_log_path();
+    let mut included = 0;
+    for path in log_files.iter() {
+        // Skip current log (already included)
+        if let Some(ref current) = current_log {
+            if path == current {
+                continue;
             }
+        }
+
+        if included >= 2 {
+            break;
+        }
+
+        content.push_str(&format!(
+            "\n=== Previous Log: {} ===\n",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ));
 
-            content.push_str(&format!(
-                "\n=== Previous Log: {} ===\n",
-                path.file_name().unwrap_or_default().to_string_lossy()
-            ));
-
-            match fs::read_to_string(&path) {
-                Ok(log_content) => {
-                    // Limit previous logs to last 500 lines
-                    let lines: Vec<&str> = log_content.lines().collect();
-                    if lines.len() > 500 {
-                        content.push_str(&format!(
-                            "... (truncated, showing last 500 of {} lines)\n",
-                            lines.len()
-                        ));
-                        for line in lines.iter().skip(lines.len() - 500) {
-                            content.push_str(line);
-                            content.push('\n');
-                        }
-                    } else {
-                        content.push_str(&log_content);
+        match fs::read_to_string(path) {
+            Ok(log_content) => {
+                // Limit previous logs to last 500 lines
+                let lines: Vec<&str> = log_content.lines().collect();
+                if lines.len() > 500 {
+                    content.push_str(&format!(
+                        "... (truncated, showing last 500 of {} lines)\n",
+                        lines.len()
+                    ));
+                    for line in lines.iter().skip(lines.len() - 500) {
+                        content.push_str(line);
+                        content.push('\n');
                     }
-                }
-                Err(e) => {
-                    content.push_str(&format!("Error reading log file: {}\n", e));
+                } else {
+                    content.push_str(&log_content);
                 }
             }
-
-            included += 1;
+            Err(e) => {
+                content.push_str(&format!("Error reading log file: {}\n", e));
+            }
         }
+
+        included += 1;
     }
 
     Ok(content)
 }
 
-/// Upload logs to paste.armbian.com
+/// List the operation IDs tagged in the current session log
+#[tauri::command]
+pub fn list_log_operations() -> Vec<String> {
+    get_current_log_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|content| operation_ids(&content))
+        .unwrap_or_default()
+}
+
+/// Upload logs to the configured paste service (paste.armbian.com by default)
 ///
-/// Returns the URL and key of the uploaded paste, or an error message.
+/// Without a selection, the current session and recent previous logs are
+/// sent. Returns the URL and key of the uploaded paste, or an error message.
 #[tauri::command]
-pub async fn upload_logs() -> Result<UploadResult, String> {
-    log_info!("paste", "Starting log upload to paste.armbian.com");
+pub async fn upload_logs(
+    selection: Option<LogSelection>,
+    app: AppHandle,
+) -> Result<UploadResult, String> {
+    let base_url = normalize_paste_url(&get_paste_url(app))?;
+    if base_url == config::paste::DEFAULT_URL {
+        log_info!("paste", "Starting log upload to {}", base_url);
+    } else {
+        log_warn!(
+            "paste",
+            "Starting log upload to custom endpoint {}, its operator will receive the logs",
+            base_url
+        );
+    }
 
     // Collect log content
-    let content = collect_logs()?;
+    let content = match selection {
+        Some(ref selection) => collect_selected_logs(selection)?,
+        None => collect_logs()?,
+    };
 
     if content.trim().is_empty() {
         return Err("No log content available to upload".to_string());
@@ -138,13 +350,13 @@ pub async fn upload_logs() -> Result<UploadResult, String> {
     log_info!("paste", "Collected {} bytes of log data", content.len());
 
     // Create HTTP client
-    let client = reqwest::Client::builder()
+    let client = proxy::client_builder()
         .timeout(std::time::Duration::from_secs(30))
         .build()
         .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
 
     // Upload to paste service
-    let url = format!("{}{}", PASTE_URL, PASTE_ENDPOINT);
+    let url = format!("{}{}", base_url, PASTE_ENDPOINT);
     let response = client
         .post(&url)
         .header("Content-Type", "text/plain")
@@ -201,4 +413,73 @@ mod tests {
         let content = result.unwrap();
         assert!(content.contains("Armbian Imager Log Upload"));
     }
+
+    const SAMPLE: &str = "\
+[2025-01-01 12:00:00.000] [INFO] [app] Started
+[2025-01-01 12:00:01.000] [INFO] [operations] [op=flash-1] Starting flash
+[2025-01-01 12:00:02.000] [ERROR] [flash] [op=flash-1] Write failed:
+  second line of the error
+[2025-01-01 12:05:00.000] [INFO] [operations] [op=verify-2] Verifying
+[2025-01-01 12:10:00.000] [INFO] [app] Idle
+";
+
+    fn at(time: &str) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").ok()
+    }
+
+    #[test]
+    fn test_normalize_paste_url() {
+        assert_eq!(
+            normalize_paste_url(" https://paste.example.org/ ").unwrap(),
+            "https://paste.example.org"
+        );
+        assert_eq!(
+            normalize_paste_url("https://example.org/paste/").unwrap(),
+            "https://example.org/paste"
+        );
+        assert!(normalize_paste_url("http://localhost:7777").is_ok());
+        assert!(normalize_paste_url("http://paste.example.org").is_err());
+        assert!(normalize_paste_url("https://user:pw@paste.example.org").is_err());
+        assert!(normalize_paste_url("https://paste.example.org/?x=1").is_err());
+        assert!(normalize_paste_url("paste.example.org").is_err());
+    }
+
+    #[test]
+    fn test_select_lines_by_operation() {
+        let filter = LineFilter {
+            operation_tag: Some("[op=flash-1]".to_string()),
+            ..Default::default()
+        };
+        let selected = select_lines(SAMPLE, &filter);
+        assert_eq!(selected.lines().count(), 3);
+        assert!(selected.contains("second line of the error"));
+        assert!(!selected.contains("verify-2"));
+    }
+
+    #[test]
+    fn test_select_lines_by_time_range() {
+        let filter = LineFilter {
+            since: at("2025-01-01 12:00:02"),
+            until: at("2025-01-01 12:05:00"),
+            ..Default::default()
+        };
+        let selected = select_lines(SAMPLE, &filter);
+        assert!(selected.starts_with("[2025-01-01 12:00:02.000]"));
+        assert!(selected.contains("verify-2"));
+        assert!(!selected.contains("Idle"));
+    }
+
+    #[test]
+    fn test_operation_ids() {
+        assert_eq!(operation_ids(SAMPLE), vec!["flash-1", "verify-2"]);
+    }
+
+    #[test]
+    fn test_invalid_selection_time() {
+        let selection = LogSelection {
+            since: Some("yesterday".to_string()),
+            ..Default::default()
+        };
+        assert!(LineFilter::from_selection(&selection).is_err());
+    }
 }
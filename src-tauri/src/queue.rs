@@ -0,0 +1,474 @@
+//! Download queue
+//!
+//! Lets users queue several images ("Bookworm minimal and Noble desktop for
+//! this board, overnight") to be downloaded into the image cache unattended.
+//! The queue is stored in the cache directory so it survives restarts;
+//! items that were downloading when the app quit are queued again and
+//! continue their partial files (see `interrupted`).
+//!
+//! Queued items start by priority, then by position, at most `max_parallel`
+//! at a time. `bandwidth_kbps` is split evenly between the parallel slots,
+//! so running downloads together stay within it.
+//!
+//! Thread Safety:
+//! Reads and writes of the queue file are serialized by a global Mutex.
+
+use std::cmp::Reverse;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::download::extract_filename;
+use crate::log_warn;
+use crate::utils::get_cache_dir;
+
+const MODULE: &str = "queue";
+
+/// Global mutex to serialize queue file access
+static QUEUE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Priority of a queued download
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueuePriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// State of a queued download
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueItemStatus {
+    Queued,
+    Paused,
+    Downloading,
+    Completed,
+    Failed,
+}
+
+/// One image in the download queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueItem {
+    pub id: String,
+    pub file_url: String,
+    pub file_url_sha: Option<String>,
+    /// Display name, e.g. "Rock 5B · Bookworm minimal"
+    pub label: String,
+    pub priority: QueuePriority,
+    pub status: QueueItemStatus,
+    /// RFC 3339 timestamp
+    pub added_at: String,
+    /// Cached image, once completed
+    pub output_path: Option<String>,
+    pub error: Option<String>,
+    /// Progress of a running download
+    #[serde(default)]
+    pub downloaded_bytes: u64,
+    #[serde(default)]
+    pub total_bytes: u64,
+}
+
+fn default_max_parallel() -> usize {
+    config::queue::DEFAULT_MAX_PARALLEL
+}
+
+/// Persisted download queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadQueue {
+    /// In queue order
+    pub items: Vec<QueueItem>,
+    /// Downloads run at the same time
+    #[serde(default = "default_max_parallel")]
+    pub max_parallel: usize,
+    /// Combined speed limit of queued downloads in KB/s, 0 for none
+    #[serde(default)]
+    pub bandwidth_kbps: u64,
+    #[serde(default)]
+    next_id: u64,
+}
+
+impl Default for DownloadQueue {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            max_parallel: default_max_parallel(),
+            bandwidth_kbps: 0,
+            next_id: 0,
+        }
+    }
+}
+
+impl DownloadQueue {
+    fn position(&self, id: &str) -> Result<usize, String> {
+        self.items
+            .iter()
+            .position(|item| item.id == id)
+            .ok_or_else(|| format!("No queued download {}", id))
+    }
+
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut QueueItem> {
+        self.items.iter_mut().find(|item| item.id == id)
+    }
+
+    /// Append an image, refusing one that is already waiting or downloading
+    ///
+    /// Images with the same file name end up at the same path in the cache,
+    /// so a second one is refused too until the first has completed.
+    pub fn add(
+        &mut self,
+        file_url: &str,
+        file_url_sha: Option<&str>,
+        label: &str,
+        priority: QueuePriority,
+    ) -> Result<QueueItem, String> {
+        if !file_url.starts_with("https://") && !file_url.starts_with("http://") {
+            return Err(format!("Not a download URL: {}", file_url));
+        }
+        let filename = extract_filename(file_url)?;
+        let pending = self
+            .items
+            .iter()
+            .filter(|item| item.status != QueueItemStatus::Completed);
+        for item in pending {
+            if item.file_url == file_url {
+                return Err("This image is already in the download queue".to_string());
+            }
+            if extract_filename(&item.file_url).ok() == Some(filename) {
+                return Err(format!(
+                    "Another image named {} is already in the download queue",
+                    filename
+                ));
+            }
+        }
+
+        self.next_id += 1;
+        let item = QueueItem {
+            id: format!("queue-{}", self.next_id),
+            file_url: file_url.to_string(),
+            file_url_sha: file_url_sha.map(|s| s.to_string()),
+            label: label.to_string(),
+            priority,
+            status: QueueItemStatus::Queued,
+            added_at: chrono::Utc::now().to_rfc3339(),
+            output_path: None,
+            error: None,
+            downloaded_bytes: 0,
+            total_bytes: 0,
+        };
+        self.items.push(item.clone());
+        Ok(item)
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<QueueItem, String> {
+        let index = self.position(id)?;
+        Ok(self.items.remove(index))
+    }
+
+    /// Move an item to `index`, clamped to the end of the queue
+    pub fn move_item(&mut self, id: &str, index: usize) -> Result<(), String> {
+        let item = self.remove(id)?;
+        let index = index.min(self.items.len());
+        self.items.insert(index, item);
+        Ok(())
+    }
+
+    /// Pause or resume an item; resuming a failed item retries it
+    ///
+    /// Returns the previous status, so a running download can be cancelled.
+    pub fn set_paused(&mut self, id: &str, paused: bool) -> Result<QueueItemStatus, String> {
+        let index = self.position(id)?;
+        let item = &mut self.items[index];
+        let previous = item.status;
+        item.status = match (previous, paused) {
+            (QueueItemStatus::Completed, _) => {
+                return Err("The download has already completed".to_string())
+            }
+            (_, true) => QueueItemStatus::Paused,
+            (QueueItemStatus::Downloading, false) => QueueItemStatus::Downloading,
+            (_, false) => QueueItemStatus::Queued,
+        };
+        if !paused {
+            item.error = None;
+        }
+        Ok(previous)
+    }
+
+    pub fn set_priority(&mut self, id: &str, priority: QueuePriority) -> Result<(), String> {
+        let index = self.position(id)?;
+        self.items[index].priority = priority;
+        Ok(())
+    }
+
+    pub fn set_options(&mut self, max_parallel: usize, bandwidth_kbps: u64) -> Result<(), String> {
+        if !(1..=config::queue::MAX_PARALLEL).contains(&max_parallel) {
+            return Err(format!(
+                "Parallel downloads must be between 1 and {}",
+                config::queue::MAX_PARALLEL
+            ));
+        }
+        self.max_parallel = max_parallel;
+        self.bandwidth_kbps = bandwidth_kbps;
+        Ok(())
+    }
+
+    /// Drop completed items, returning how many were removed
+    pub fn clear_completed(&mut self) -> usize {
+        let before = self.items.len();
+        self.items
+            .retain(|item| item.status != QueueItemStatus::Completed);
+        before - self.items.len()
+    }
+
+    /// Queue again the items a previous session was downloading
+    ///
+    /// Their progress stays, as they continue their partial files.
+    pub fn recover(&mut self) -> usize {
+        let mut recovered = 0;
+        for item in &mut self.items {
+            if item.status == QueueItemStatus::Downloading {
+                item.status = QueueItemStatus::Queued;
+                recovered += 1;
+            }
+        }
+        recovered
+    }
+
+    /// IDs of the items to start now, highest priority first
+    pub fn next_to_start(&self) -> Vec<String> {
+        let running = self
+            .items
+            .iter()
+            .filter(|item| item.status == QueueItemStatus::Downloading)
+            .count();
+
+        let mut waiting: Vec<&QueueItem> = self
+            .items
+            .iter()
+            .filter(|item| item.status == QueueItemStatus::Queued)
+            .collect();
+        // Stable, so equal priorities keep their queue order
+        waiting.sort_by_key(|item| Reverse(item.priority));
+
+        waiting
+            .into_iter()
+            .take(self.max_parallel.saturating_sub(running))
+            .map(|item| item.id.clone())
+            .collect()
+    }
+
+    /// Speed limit of each download in bytes per second, 0 for none
+    ///
+    /// `developer_limit` is the developer mode throttle, which caps it further.
+    pub fn per_download_limit(&self, developer_limit: u64) -> u64 {
+        let share = self.bandwidth_kbps * 1024 / self.max_parallel.max(1) as u64;
+        match (share, developer_limit) {
+            (0, limit) | (limit, 0) => limit,
+            (share, limit) => share.min(limit),
+        }
+    }
+}
+
+/// Get the queue file path
+fn queue_path() -> PathBuf {
+    get_cache_dir(config::app::NAME).join(config::queue::FILE_NAME)
+}
+
+fn load_internal() -> DownloadQueue {
+    match fs::read_to_string(queue_path()) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            log_warn!(MODULE, "Ignoring unreadable download queue: {}", e);
+            DownloadQueue::default()
+        }),
+        Err(_) => DownloadQueue::default(),
+    }
+}
+
+fn save_internal(queue: &DownloadQueue) -> Result<(), String> {
+    let path = queue_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create queue directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(queue)
+        .map_err(|e| format!("Failed to serialize download queue: {}", e))?;
+
+    // Written aside and renamed, so a crash never leaves half a queue
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(
+        ".{}{}",
+        std::process::id(),
+        config::queue::TEMP_SUFFIX
+    ));
+    let temp = path.with_file_name(temp_name);
+    if let Err(e) = fs::write(&temp, content) {
+        let _ = fs::remove_file(&temp);
+        return Err(format!("Failed to write download queue: {}", e));
+    }
+    fs::rename(&temp, &path).map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        format!("Failed to replace download queue: {}", e)
+    })
+}
+
+/// Load the download queue (empty if missing or unreadable)
+pub fn load_queue() -> DownloadQueue {
+    let _lock = QUEUE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load_internal()
+}
+
+/// Load, modify and save the queue under the lock; nothing is saved on error
+pub fn update_queue<T>(
+    f: impl FnOnce(&mut DownloadQueue) -> Result<T, String>,
+) -> Result<T, String> {
+    let _lock = QUEUE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut queue = load_internal();
+    let result = f(&mut queue)?;
+    save_internal(&queue)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue_with(urls: &[(&str, QueuePriority)]) -> DownloadQueue {
+        let mut queue = DownloadQueue::default();
+        for (url, priority) in urls {
+            queue
+                .add(
+                    &format!("https://dl.armbian.com/{}", url),
+                    None,
+                    url,
+                    *priority,
+                )
+                .unwrap();
+        }
+        queue
+    }
+
+    fn ids(queue: &DownloadQueue) -> Vec<&str> {
+        queue.items.iter().map(|item| item.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_add_and_reorder() {
+        let mut queue = queue_with(&[
+            ("a.img.xz", QueuePriority::Normal),
+            ("b.img.xz", QueuePriority::Normal),
+            ("c.img.xz", QueuePriority::Normal),
+        ]);
+        assert!(queue
+            .add(
+                "https://dl.armbian.com/a.img.xz",
+                None,
+                "a",
+                QueuePriority::Low
+            )
+            .is_err());
+        assert!(queue
+            .add("file:///etc/passwd", None, "x", QueuePriority::Low)
+            .is_err());
+        // Another image under the same name would share its path in the cache
+        assert!(queue
+            .add(
+                "https://mirror.example.org/armbian/a.img.xz",
+                None,
+                "a",
+                QueuePriority::Low
+            )
+            .is_err());
+
+        queue.move_item("queue-3", 0).unwrap();
+        assert_eq!(ids(&queue), vec!["queue-3", "queue-1", "queue-2"]);
+        queue.move_item("queue-3", 99).unwrap();
+        assert_eq!(ids(&queue), vec!["queue-1", "queue-2", "queue-3"]);
+
+        queue.remove("queue-2").unwrap();
+        assert!(queue.remove("queue-2").is_err());
+        // IDs are not reused
+        let item = queue
+            .add(
+                "https://dl.armbian.com/d.img.xz",
+                None,
+                "d",
+                QueuePriority::Normal,
+            )
+            .unwrap();
+        assert_eq!(item.id, "queue-4");
+    }
+
+    #[test]
+    fn test_next_to_start() {
+        let mut queue = queue_with(&[
+            ("a.img.xz", QueuePriority::Low),
+            ("b.img.xz", QueuePriority::Normal),
+            ("c.img.xz", QueuePriority::High),
+            ("d.img.xz", QueuePriority::Normal),
+        ]);
+        assert_eq!(queue.next_to_start(), vec!["queue-3"]);
+
+        queue.set_options(2, 0).unwrap();
+        queue.set_paused("queue-3", true).unwrap();
+        assert_eq!(queue.next_to_start(), vec!["queue-2", "queue-4"]);
+
+        let item = queue.get_mut("queue-2").unwrap();
+        item.status = QueueItemStatus::Downloading;
+        item.downloaded_bytes = 4096;
+        assert_eq!(queue.next_to_start(), vec!["queue-4"]);
+
+        // Interrupted downloads are queued again and keep their progress
+        assert_eq!(queue.recover(), 1);
+        let item = queue.get_mut("queue-2").unwrap();
+        assert_eq!(item.status, QueueItemStatus::Queued);
+        assert_eq!(item.downloaded_bytes, 4096);
+
+        assert!(queue.set_options(0, 0).is_err());
+        assert!(queue
+            .set_options(config::queue::MAX_PARALLEL + 1, 0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_pause_resume() {
+        let mut queue = queue_with(&[("a.img.xz", QueuePriority::Normal)]);
+        let item = queue.get_mut("queue-1").unwrap();
+        item.status = QueueItemStatus::Failed;
+        item.error = Some("Download failed with status: 404".to_string());
+
+        // Resuming a failed download retries it
+        assert_eq!(
+            queue.set_paused("queue-1", false).unwrap(),
+            QueueItemStatus::Failed
+        );
+        let item = queue.get_mut("queue-1").unwrap();
+        assert_eq!(item.status, QueueItemStatus::Queued);
+        assert!(item.error.is_none());
+
+        item.status = QueueItemStatus::Downloading;
+        assert_eq!(
+            queue.set_paused("queue-1", true).unwrap(),
+            QueueItemStatus::Downloading
+        );
+
+        queue.get_mut("queue-1").unwrap().status = QueueItemStatus::Completed;
+        assert!(queue.set_paused("queue-1", true).is_err());
+        assert_eq!(queue.clear_completed(), 1);
+    }
+
+    #[test]
+    fn test_per_download_limit() {
+        let mut queue = DownloadQueue::default();
+        assert_eq!(queue.per_download_limit(0), 0);
+        assert_eq!(queue.per_download_limit(4096), 4096);
+
+        queue.set_options(2, 1000).unwrap();
+        assert_eq!(queue.per_download_limit(0), 512_000);
+        assert_eq!(queue.per_download_limit(100_000), 100_000);
+    }
+}
@@ -0,0 +1,346 @@
+//! Segmented downloads
+//!
+//! A single connection often can't fill a fast link, so large images from
+//! servers that accept Range requests are fetched as several byte ranges at
+//! once. The request that discovered range support carries the first range;
+//! the other ranges go to the mirror it resolved to, so every range comes
+//! from the same copy of the file.
+//!
+//! Ranges are written in place into a preallocated file, which has holes
+//! until every range is in. It only becomes the partial file of the
+//! download once complete, so the partial file never has holes. The
+//! progress of each range says which parts of the file are in: a retry on
+//! the same mirror asks each range for the rest only, while a download
+//! moved to another mirror starts over. The progress is saved to the
+//! download manifest every few seconds, after the bytes it counts were
+//! written, so a download interrupted by a quit continues the same way.
+
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures_util::future::try_join_all;
+use futures_util::StreamExt;
+use reqwest::{Client, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::credentials::authorize;
+use crate::diskspace::SpaceWatcher;
+use crate::download::{is_transient_status, transient, DownloadState};
+use crate::history::record_mirror_failure;
+use crate::interrupted::Tracked;
+use crate::log_info;
+use crate::utils::{ProgressTracker, Throttle};
+
+const MODULE: &str = "segmented";
+
+/// Progress of one byte range of a segmented download
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentProgress {
+    /// First byte of the range
+    pub start: u64,
+    /// Last byte of the range, inclusive like the Range header
+    pub end: u64,
+    pub downloaded_bytes: u64,
+}
+
+impl SegmentProgress {
+    fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Bytes of the range still to download
+    pub fn remaining(&self) -> u64 {
+        self.len().saturating_sub(self.downloaded_bytes)
+    }
+}
+
+/// File a segmented download fills in place of the partial file `temp_path`
+pub fn segmented_path(temp_path: &Path) -> PathBuf {
+    let mut name = temp_path.file_name().unwrap_or_default().to_os_string();
+    name.push(config::download::SEGMENTED_SUFFIX);
+    temp_path.with_file_name(name)
+}
+
+/// Progress of the ranges in the download manifest, saved now and then
+struct Checkpoint<'a> {
+    tracked: &'a Tracked,
+    saved: Mutex<Instant>,
+}
+
+impl Checkpoint<'_> {
+    /// Save the progress in `state`, unless it was saved recently and not `forced`
+    fn save(&self, state: &DownloadState, forced: bool) {
+        {
+            let mut saved = self.saved.lock().unwrap_or_else(|e| e.into_inner());
+            let interval = Duration::from_millis(config::download::SEGMENT_CHECKPOINT_INTERVAL_MS);
+            if !forced && saved.elapsed() < interval {
+                return;
+            }
+            *saved = Instant::now();
+        }
+        let segments = state
+            .segments
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        self.tracked.set_segments(&segments);
+    }
+}
+
+/// Split `total` bytes into up to `segments` ranges of at least `min_size`
+///
+/// Returns a single range when the file is too small to be worth splitting,
+/// and nothing for an empty file.
+pub fn plan_segments(total: u64, segments: u64, min_size: u64) -> Vec<SegmentProgress> {
+    if total == 0 {
+        return Vec::new();
+    }
+    let count = segments.min(total / min_size.max(1)).max(1);
+    let size = total.div_ceil(count);
+    (0..count)
+        .map(|i| i * size)
+        .take_while(|&start| start < total)
+        .map(|start| SegmentProgress {
+            start,
+            end: (start + size).min(total) - 1,
+            downloaded_bytes: 0,
+        })
+        .collect()
+}
+
+/// Download the ranges of `first`'s file into `temp_path`
+///
+/// `first` answered a range request with 206 and streams the rest of the
+/// range at `first_index`. Ranges that already made progress continue in
+/// the file an earlier attempt left. Progress goes to `state` per range
+/// and in total, and to the manifest entry `tracked`.
+#[allow(clippy::too_many_arguments)]
+pub async fn fetch_segments(
+    client: &Client,
+    first: Response,
+    first_index: usize,
+    ranges: Vec<SegmentProgress>,
+    temp_path: &Path,
+    host: &str,
+    tracker: &mut ProgressTracker,
+    space: &SpaceWatcher,
+    tracked: &Tracked,
+    state: &Arc<DownloadState>,
+) -> Result<(), String> {
+    let url = first.url().to_string();
+    let total = ranges.last().map_or(0, |range| range.end + 1);
+    let segmented_path = segmented_path(temp_path);
+
+    let resumed = ranges.iter().any(|range| range.downloaded_bytes > 0);
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(!resumed)
+        .open(&segmented_path)
+        .and_then(|file| file.set_len(total))
+        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+    log_info!(
+        MODULE,
+        "Downloading {} in {} segments from {} ({} bytes left)",
+        total,
+        ranges.len(),
+        host,
+        ranges.iter().map(SegmentProgress::remaining).sum::<u64>()
+    );
+
+    // The total limit is shared among the connections
+    let limit = match state.throttle_bytes_per_sec.load(Ordering::SeqCst) {
+        0 => 0,
+        limit => (limit / ranges.len() as u64).max(1),
+    };
+    *state.segments.lock().unwrap_or_else(|e| e.into_inner()) = ranges.clone();
+    let checkpoint = Checkpoint {
+        tracked,
+        saved: Mutex::new(Instant::now()),
+    };
+    checkpoint.save(state, true);
+    let tracker = Mutex::new(tracker);
+    let mut first = Some(first);
+    let segments = ranges.iter().enumerate().map(|(index, range)| {
+        let response = if index == first_index {
+            first.take()
+        } else {
+            None
+        };
+        fetch_segment(
+            client,
+            &url,
+            response,
+            index,
+            range,
+            &segmented_path,
+            host,
+            Throttle::new(limit),
+            &tracker,
+            &checkpoint,
+            space,
+            state,
+        )
+    });
+    // A retry or a resumed download continues from the last progress
+    let result = try_join_all(segments).await;
+    checkpoint.save(state, true);
+    result?;
+
+    std::fs::rename(&segmented_path, temp_path)
+        .map_err(|e| format!("Failed to finish segmented download: {}", e))?;
+    tracked.set_segments(&[]);
+    Ok(())
+}
+
+/// Download the rest of one range, from `response` if it already streams it
+#[allow(clippy::too_many_arguments)]
+async fn fetch_segment(
+    client: &Client,
+    url: &str,
+    response: Option<Response>,
+    index: usize,
+    range: &SegmentProgress,
+    segmented_path: &Path,
+    host: &str,
+    mut throttle: Throttle,
+    tracker: &Mutex<&mut ProgressTracker>,
+    checkpoint: &Checkpoint<'_>,
+    space: &SpaceWatcher,
+    state: &Arc<DownloadState>,
+) -> Result<(), String> {
+    let failed = |error: String| {
+        record_mirror_failure(host, &error);
+        error
+    };
+
+    let offset = range.start + range.downloaded_bytes;
+    let mut remaining = range.remaining();
+    if remaining == 0 {
+        return Ok(());
+    }
+
+    let response = match response {
+        Some(response) => response,
+        None => {
+            let request = client.get(url).header(
+                reqwest::header::RANGE,
+                format!("bytes={}-{}", offset, range.end),
+            );
+            let response = authorize(request, url).await.send().await.map_err(|e| {
+                transient(failed(format!(
+                    "Failed to start segment {}: {}",
+                    index + 1,
+                    e
+                )))
+            })?;
+            if response.status() != StatusCode::PARTIAL_CONTENT {
+                let error = failed(format!(
+                    "Segment {} failed with status: {}",
+                    index + 1,
+                    response.status()
+                ));
+                if is_transient_status(response.status()) {
+                    return Err(transient(error));
+                }
+                return Err(error);
+            }
+            response
+        }
+    };
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(segmented_path)
+        .and_then(|mut file| file.seek(SeekFrom::Start(offset)).map(|_| file))
+        .map_err(|e| format!("Failed to open temp file: {}", e))?;
+
+    let mut stream = response.bytes_stream();
+    while remaining > 0 {
+        let Some(chunk) = stream.next().await else {
+            break;
+        };
+        if state.is_cancelled.load(Ordering::SeqCst) {
+            return Err("Download cancelled".to_string());
+        }
+        space.check_async(state).await?;
+        let chunk = chunk.map_err(|e| transient(failed(format!("Download error: {}", e))))?;
+        // The first range streams on into the next ones; stop at its end
+        let chunk = &chunk[..chunk.len().min(remaining as usize)];
+        file.write_all(chunk)
+            .map_err(|e| format!("Failed to write chunk: {}", e))?;
+
+        let len = chunk.len() as u64;
+        remaining -= len;
+        state.downloaded_bytes.fetch_add(len, Ordering::SeqCst);
+        if let Some(segment) = state
+            .segments
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(index)
+        {
+            segment.downloaded_bytes += len;
+        }
+        checkpoint.save(state, false);
+        tracker
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .update(len);
+        throttle.pace_async(len, &state.is_cancelled).await;
+    }
+
+    if remaining > 0 {
+        return Err(transient(failed(format!(
+            "Segment {} ended {} bytes early",
+            index + 1,
+            remaining
+        ))));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    #[test]
+    fn test_plan_segments() {
+        let ranges = plan_segments(1000 * MB, 4, 16 * MB);
+        assert_eq!(ranges.len(), 4);
+        assert_eq!(ranges[0].start, 0);
+        assert_eq!(ranges[1].start, 250 * MB);
+        assert_eq!(ranges[3].end, 1000 * MB - 1);
+        assert!(ranges.windows(2).all(|w| w[0].end + 1 == w[1].start));
+
+        // Uneven sizes leave the remainder to the last range
+        let ranges = plan_segments(100 * MB + 3, 4, MB);
+        assert_eq!(
+            ranges.iter().map(SegmentProgress::len).sum::<u64>(),
+            100 * MB + 3
+        );
+        assert_eq!(ranges[3].end, 100 * MB + 2);
+
+        // Small files get as many ranges as the minimum size allows
+        assert_eq!(plan_segments(40 * MB, 4, 16 * MB).len(), 2);
+        assert_eq!(plan_segments(10 * MB, 4, 16 * MB).len(), 1);
+        assert_eq!(plan_segments(10 * MB, 1, 16 * MB)[0].end, 10 * MB - 1);
+        assert!(plan_segments(0, 4, 16 * MB).is_empty());
+    }
+
+    #[test]
+    fn test_segment_remaining() {
+        let mut ranges = plan_segments(100 * MB, 4, MB);
+        assert_eq!(ranges[1].remaining(), 25 * MB);
+        ranges[1].downloaded_bytes = 10 * MB;
+        assert_eq!(ranges[1].remaining(), 15 * MB);
+        ranges[1].downloaded_bytes = 25 * MB;
+        assert_eq!(ranges[1].remaining(), 0);
+    }
+}
@@ -0,0 +1,757 @@
+//! End-to-end self-test
+//!
+//! Runs the real download, decompression, write, verification and cache
+//! code against a generated image, with no network or removable media
+//! involved: the image is served by a local HTTP fixture on 127.0.0.1 and
+//! "flashed" to a scratch file. Each stage asserts its invariants (checksums
+//! accepted and rejected as they should be, written bytes hash to the image,
+//! verification catches a flipped byte, eviction removes the oldest file)
+//! and the result is a report users can attach before blaming hardware.
+//!
+//! Not exposed in the UI: CI and curious users run it with
+//! `armbian-imager --selftest [scratch-file]`, which prints the report as
+//! JSON and exits non-zero on failure, or through the `run_selftest` command.
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+
+use filetime::FileTime;
+use serde::Serialize;
+
+use crate::cache::evict_dir_to_size;
+use crate::config;
+use crate::decompress::decompress_local_file;
+use crate::download::{download_image, DownloadState};
+use crate::flash::{
+    verify_data_with_options, ChunkChecksums, FlashState, HashingReader, VerifyMode, VerifyOptions,
+};
+use crate::logging::begin_operation;
+use crate::{log_error, log_info, log_warn};
+
+const MODULE: &str = "selftest";
+
+/// Self-test stages, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelftestStage {
+    /// Generate the image and start the local HTTP fixture
+    Fixture,
+    /// Download with checksum check and XZ decompression
+    Download,
+    /// Decompress a gzip image the way custom images are
+    Decompress,
+    /// Write the image to the scratch file
+    Flash,
+    /// Read back in full and fast mode, then detect a flipped byte
+    Verify,
+    /// Evict the least recently used file from a scratch cache
+    CacheEvict,
+}
+
+impl SelftestStage {
+    const ALL: [SelftestStage; 6] = [
+        SelftestStage::Fixture,
+        SelftestStage::Download,
+        SelftestStage::Decompress,
+        SelftestStage::Flash,
+        SelftestStage::Verify,
+        SelftestStage::CacheEvict,
+    ];
+}
+
+/// Outcome of one stage
+#[derive(Debug, Clone, Serialize)]
+pub struct StageReport {
+    pub stage: SelftestStage,
+    pub passed: bool,
+    pub duration_ms: u64,
+    /// What was checked, or why the stage failed
+    pub detail: String,
+}
+
+/// Self-test report; stages after the first failure are not run
+#[derive(Debug, Clone, Serialize)]
+pub struct SelftestReport {
+    pub app_version: String,
+    pub os: String,
+    pub started_at: String,
+    pub scratch_path: String,
+    pub passed: bool,
+    pub stages: Vec<StageReport>,
+}
+
+/// Scratch file path given with the hidden command line flag
+///
+/// The path may be left out, a file in the temp directory is used then.
+pub fn scratch_path_from_args(args: impl IntoIterator<Item = OsString>) -> Option<PathBuf> {
+    let mut args = args.into_iter().skip(1);
+    args.position(|arg| arg == config::selftest::CLI_FLAG)?;
+    Some(args.next().map(PathBuf::from).unwrap_or_else(|| {
+        std::env::temp_dir().join(format!(
+            "armbian-imager-selftest-{}.img",
+            std::process::id()
+        ))
+    }))
+}
+
+/// Run the self-test from the command line, print the JSON report and return the exit code
+pub fn run_cli(scratch_path: &Path) -> i32 {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Failed to start the async runtime: {}", e);
+            return 2;
+        }
+    };
+    let report = runtime.block_on(run(scratch_path));
+
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Failed to serialize the self-test report: {}", e),
+    }
+    if report.passed {
+        0
+    } else {
+        1
+    }
+}
+
+/// Run all stages against `scratch_path`, which must not exist yet
+pub async fn run(scratch_path: &Path) -> SelftestReport {
+    let _operation = begin_operation("selftest");
+    log_info!(
+        MODULE,
+        "Starting self-test with scratch file {:?}",
+        scratch_path
+    );
+
+    let mut report = SelftestReport {
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+        started_at: chrono::Utc::now().to_rfc3339(),
+        scratch_path: scratch_path.display().to_string(),
+        passed: false,
+        stages: Vec::new(),
+    };
+
+    let mut selftest = Selftest::new(scratch_path);
+    for stage in SelftestStage::ALL {
+        let started = Instant::now();
+        let result = selftest.run_stage(stage).await;
+        let passed = result.is_ok();
+        let detail = result.unwrap_or_else(|e| e);
+
+        if passed {
+            log_info!(MODULE, "{:?} passed: {}", stage, detail);
+        } else {
+            log_error!(MODULE, "{:?} failed: {}", stage, detail);
+        }
+        report.stages.push(StageReport {
+            stage,
+            passed,
+            duration_ms: started.elapsed().as_millis() as u64,
+            detail,
+        });
+        if !passed {
+            break;
+        }
+    }
+    selftest.cleanup();
+
+    report.passed =
+        report.stages.len() == SelftestStage::ALL.len() && report.stages.iter().all(|s| s.passed);
+    log_info!(
+        MODULE,
+        "Self-test {}",
+        if report.passed { "passed" } else { "failed" }
+    );
+    report
+}
+
+/// Files and state shared between stages
+struct Selftest {
+    scratch_path: PathBuf,
+    /// Fixture, download and cache files, next to the scratch file
+    work_dir: PathBuf,
+    /// Base name of the fixture image, unique so the image cache never matches it
+    name: String,
+    image_sha256: String,
+    server: Option<FixtureServer>,
+    downloaded: Option<PathBuf>,
+    flash_state: Arc<FlashState>,
+    /// Only files created by the self-test are removed afterwards
+    created_work_dir: bool,
+    created_scratch: bool,
+}
+
+impl Selftest {
+    fn new(scratch_path: &Path) -> Self {
+        let mut work_dir = scratch_path.as_os_str().to_owned();
+        work_dir.push(".selftest");
+        let millis = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+
+        Self {
+            scratch_path: scratch_path.to_path_buf(),
+            work_dir: PathBuf::from(work_dir),
+            name: format!("armbian-selftest-{}-{}", std::process::id(), millis),
+            image_sha256: String::new(),
+            server: None,
+            downloaded: None,
+            flash_state: Arc::new(FlashState::new()),
+            created_work_dir: false,
+            created_scratch: false,
+        }
+    }
+
+    async fn run_stage(&mut self, stage: SelftestStage) -> Result<String, String> {
+        match stage {
+            SelftestStage::Fixture => self.fixture(),
+            SelftestStage::Download => self.download().await,
+            SelftestStage::Decompress => self.decompress(),
+            SelftestStage::Flash => self.flash(),
+            SelftestStage::Verify => self.verify(),
+            SelftestStage::CacheEvict => self.cache_evict(),
+        }
+    }
+
+    fn fixture_dir(&self) -> PathBuf {
+        self.work_dir.join("fixture")
+    }
+
+    fn url(&self, file: &str) -> Result<String, String> {
+        let server = self
+            .server
+            .as_ref()
+            .ok_or("Fixture server is not running")?;
+        Ok(format!("http://{}/{}", server.addr, file))
+    }
+
+    fn fixture(&mut self) -> Result<String, String> {
+        for path in [&self.scratch_path, &self.work_dir] {
+            if path.exists() {
+                return Err(format!(
+                    "{} already exists, pass a path that does not",
+                    path.display()
+                ));
+            }
+        }
+
+        let fixture_dir = self.fixture_dir();
+        fs::create_dir_all(&fixture_dir)
+            .map_err(|e| format!("Failed to create {}: {}", fixture_dir.display(), e))?;
+        self.created_work_dir = true;
+
+        let image = fixture_image(config::selftest::IMAGE_SIZE);
+        let image_name = format!("{}.img", self.name);
+        fs::write(fixture_dir.join(&image_name), &image)
+            .map_err(|e| format!("Failed to write fixture image: {}", e))?;
+        self.image_sha256 = sha256_of(&fixture_dir.join(&image_name))?;
+
+        // XZ with a correct and a wrong checksum file, as served by the mirrors
+        let xz_name = format!("{}.xz", image_name);
+        let mut xz = lzma_rust2::XzWriter::new(Vec::new(), lzma_rust2::XzOptions::with_preset(1))
+            .map_err(|e| format!("Failed to create XZ encoder: {}", e))?;
+        xz.write_all(&image)
+            .map_err(|e| format!("Failed to compress fixture: {}", e))?;
+        let xz_data = xz
+            .finish()
+            .map_err(|e| format!("Failed to compress fixture: {}", e))?;
+        fs::write(fixture_dir.join(&xz_name), &xz_data)
+            .map_err(|e| format!("Failed to write XZ fixture: {}", e))?;
+        let xz_sha256 = sha256_of(&fixture_dir.join(&xz_name))?;
+        fs::write(
+            fixture_dir.join(format!("{}.sha", xz_name)),
+            format!("{} *{}\n", xz_sha256, xz_name),
+        )
+        .map_err(|e| format!("Failed to write checksum file: {}", e))?;
+        fs::write(
+            fixture_dir.join(format!("{}.bad.sha", xz_name)),
+            format!("{} *{}\n", "0".repeat(64), xz_name),
+        )
+        .map_err(|e| format!("Failed to write checksum file: {}", e))?;
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gz.write_all(&image)
+            .and_then(|_| gz.finish())
+            .and_then(|data| fs::write(fixture_dir.join(format!("{}.gz", image_name)), data))
+            .map_err(|e| format!("Failed to write gzip fixture: {}", e))?;
+
+        let server = FixtureServer::start(fixture_dir)?;
+        let detail = format!(
+            "Generated a {} byte image ({} bytes as XZ), serving it on {}",
+            image.len(),
+            xz_data.len(),
+            server.addr
+        );
+        self.server = Some(server);
+        Ok(detail)
+    }
+
+    async fn download(&mut self) -> Result<String, String> {
+        let xz_name = format!("{}.img.xz", self.name);
+        let download_dir = self.work_dir.join("download");
+        let state = Arc::new(DownloadState::new());
+        // The fixture server is plain HTTP on the loopback interface
+        state.allow_insecure.store(true, Ordering::SeqCst);
+        let url = self.url(&xz_name)?;
+        let sha_url = self.url(&format!("{}.sha", xz_name))?;
+        let bad_sha_url = self.url(&format!("{}.bad.sha", xz_name))?;
+
+        let rejected = download_image(
+            &url,
+            Some(bad_sha_url.as_str()),
+            &download_dir,
+            state.clone(),
+        )
+        .await;
+        match rejected {
+            Err(e) if e.contains("mismatch") => {}
+            Err(e) => return Err(format!("Wrong checksum failed for another reason: {}", e)),
+            Ok(_) => return Err("Download with a wrong checksum was accepted".to_string()),
+        }
+
+        let path =
+            download_image(&url, Some(sha_url.as_str()), &download_dir, state.clone()).await?;
+        let downloaded_bytes = state.downloaded_bytes.load(Ordering::SeqCst);
+        let expected_bytes = fs::metadata(self.fixture_dir().join(&xz_name))
+            .map_err(|e| format!("Failed to read fixture size: {}", e))?
+            .len();
+        if downloaded_bytes != expected_bytes {
+            return Err(format!(
+                "Downloaded {} bytes, the fixture has {}",
+                downloaded_bytes, expected_bytes
+            ));
+        }
+        expect_sha256(&path, &self.image_sha256)?;
+
+        self.downloaded = Some(path);
+        Ok(format!(
+            "Wrong checksum rejected; {} bytes downloaded, checksum accepted, decompressed image matches",
+            downloaded_bytes
+        ))
+    }
+
+    fn decompress(&mut self) -> Result<String, String> {
+        let gz_path = self.fixture_dir().join(format!("{}.img.gz", self.name));
+        let state = Arc::new(DownloadState::new());
+
+        let output = decompress_local_file(&gz_path, None, &state)?;
+        let result = expect_sha256(&output, &self.image_sha256);
+        // The output lands in the custom image cache, don't leave it there
+        let _ = fs::remove_file(&output);
+        result?;
+
+        Ok("Gzip image decompressed and matches".to_string())
+    }
+
+    fn flash(&mut self) -> Result<String, String> {
+        let image_path = self.downloaded.as_ref().ok_or("No downloaded image")?;
+        let image_size = fs::metadata(image_path)
+            .map_err(|e| format!("Failed to get image size: {}", e))?
+            .len();
+
+        let state = &self.flash_state;
+        state.reset();
+        state.total_bytes.store(image_size, Ordering::SeqCst);
+
+        let mut target = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&self.scratch_path)
+            .map_err(|e| format!("Failed to create scratch file: {}", e))?;
+        self.created_scratch = true;
+
+        // Same read, hash, checksum and write steps as the device writers
+        let mut image = HashingReader::new(
+            File::open(image_path).map_err(|e| format!("Failed to open image: {}", e))?,
+        );
+        let mut checksums = ChunkChecksums::default();
+        let mut buffer = vec![0u8; config::flash::CHUNK_SIZE];
+        let mut written: u64 = 0;
+        loop {
+            let bytes_read = image
+                .read(&mut buffer)
+                .map_err(|e| format!("Failed to read image: {}", e))?;
+            if bytes_read == 0 {
+                break;
+            }
+            target
+                .write_all(&buffer[..bytes_read])
+                .map_err(|e| format!("Failed to write at byte {}: {}", written, e))?;
+            checksums.update(&buffer[..bytes_read]);
+            written += bytes_read as u64;
+            state.written_bytes.store(written, Ordering::SeqCst);
+        }
+        image.finish(state);
+        checksums.finish(state);
+        target
+            .sync_all()
+            .map_err(|e| format!("Failed to sync scratch file: {}", e))?;
+        state.synced_bytes.store(written, Ordering::SeqCst);
+
+        if written != image_size {
+            return Err(format!("Wrote {} of {} bytes", written, image_size));
+        }
+        let written_sha256 = state
+            .image_sha256
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .unwrap_or_default();
+        if written_sha256 != self.image_sha256 {
+            return Err(format!(
+                "Written bytes hash to {}, the image to {}",
+                written_sha256, self.image_sha256
+            ));
+        }
+
+        Ok(format!(
+            "{} bytes written, their hash matches the image",
+            written
+        ))
+    }
+
+    fn verify(&mut self) -> Result<String, String> {
+        let image_path = self.downloaded.clone().ok_or("No downloaded image")?;
+        let open_target = || {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&self.scratch_path)
+                .map_err(|e| format!("Failed to open scratch file: {}", e))
+        };
+
+        let full = VerifyOptions::default();
+        let fast = VerifyOptions {
+            streams: 2,
+            mode: VerifyMode::Fast,
+            tail_check: None,
+        };
+        verify_data_with_options(
+            &image_path,
+            &mut open_target()?,
+            self.flash_state.clone(),
+            full,
+        )
+        .map_err(|e| format!("Full verification: {}", e))?;
+        verify_data_with_options(
+            &image_path,
+            &mut open_target()?,
+            self.flash_state.clone(),
+            fast,
+        )
+        .map_err(|e| format!("Fast verification: {}", e))?;
+
+        // Flip one byte; a full read-back must report exactly that offset
+        let offset = config::selftest::CORRUPT_OFFSET;
+        let mut target = open_target()?;
+        let mut byte = [0u8; 1];
+        target
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| target.read_exact(&mut byte))
+            .and_then(|_| target.seek(SeekFrom::Start(offset)))
+            .and_then(|_| target.write_all(&[!byte[0]]))
+            .and_then(|_| target.sync_all())
+            .map_err(|e| format!("Failed to corrupt scratch file: {}", e))?;
+        drop(target);
+
+        if verify_data_with_options(
+            &image_path,
+            &mut open_target()?,
+            self.flash_state.clone(),
+            full,
+        )
+        .is_ok()
+        {
+            return Err(format!("A flipped byte at {} was not detected", offset));
+        }
+        let reported = self
+            .flash_state
+            .verify_mismatch
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|m| m.offset);
+        if reported != Some(offset) {
+            return Err(format!(
+                "Flipped byte at {} reported at {:?}",
+                offset, reported
+            ));
+        }
+
+        Ok(format!(
+            "Full and fast read-back passed; flipped byte at {} detected",
+            offset
+        ))
+    }
+
+    fn cache_evict(&mut self) -> Result<String, String> {
+        let cache_dir = self.work_dir.join("cache");
+        fs::create_dir_all(&cache_dir)
+            .map_err(|e| format!("Failed to create {}: {}", cache_dir.display(), e))?;
+
+        // Three 1 MB files, used 300, 200 and 100 seconds ago
+        let now = SystemTime::now();
+        let files: Vec<PathBuf> = (0..3u64)
+            .map(|i| cache_dir.join(format!("cached-{}.img", i)))
+            .collect();
+        for (i, path) in files.iter().enumerate() {
+            fs::write(path, vec![0u8; 1024 * 1024])
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            let used = now - Duration::from_secs(300 - 100 * i as u64);
+            filetime::set_file_mtime(path, FileTime::from_system_time(used))
+                .map_err(|e| format!("Failed to set mtime: {}", e))?;
+        }
+
+        let removed = evict_dir_to_size(&cache_dir, 2 * 1024 * 1024)?;
+        let remaining: Vec<bool> = files.iter().map(|p| p.exists()).collect();
+        if removed != 1 || remaining != [false, true, true] {
+            return Err(format!(
+                "Expected only the oldest file evicted, removed {} (present: {:?})",
+                removed, remaining
+            ));
+        }
+
+        Ok("Least recently used file evicted, newer files kept".to_string())
+    }
+
+    /// Stop the fixture and remove everything the self-test created
+    fn cleanup(&mut self) {
+        self.server = None;
+        if self.created_scratch {
+            if let Err(e) = fs::remove_file(&self.scratch_path) {
+                log_warn!(MODULE, "Failed to remove scratch file: {}", e);
+            }
+        }
+        if self.created_work_dir {
+            if let Err(e) = fs::remove_dir_all(&self.work_dir) {
+                log_warn!(
+                    MODULE,
+                    "Failed to remove {}: {}",
+                    self.work_dir.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Deterministic image data, alternating incompressible and patterned 64 KB blocks
+pub fn fixture_image(size: usize) -> Vec<u8> {
+    const BLOCK: usize = 64 * 1024;
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    (0..size)
+        .map(|i| {
+            if (i / BLOCK) % 2 == 0 {
+                // xorshift64
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            } else {
+                (i % 251) as u8
+            }
+        })
+        .collect()
+}
+
+fn sha256_of(path: &Path) -> Result<String, String> {
+    let mut reader = HashingReader::new(
+        File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?,
+    );
+    io::copy(&mut reader, &mut io::sink())
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(reader.digest())
+}
+
+fn expect_sha256(path: &Path, expected: &str) -> Result<(), String> {
+    let actual = sha256_of(path)?;
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} hashes to {}, expected {}",
+            path.display(),
+            actual,
+            expected
+        ))
+    }
+}
+
+/// Minimal HTTP server handing out the files of one directory
+struct FixtureServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl FixtureServer {
+    fn start(root: PathBuf) -> Result<Self, String> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .and_then(|l| l.set_nonblocking(true).map(|_| l))
+            .map_err(|e| format!("Failed to start fixture server: {}", e))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to start fixture server: {}", e))?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        let handle = std::thread::Builder::new()
+            .name("selftest-http".to_string())
+            .spawn(move || {
+                while !thread_stop.load(Ordering::SeqCst) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(e) = serve(stream, &root) {
+                                log_warn!(MODULE, "Fixture request failed: {}", e);
+                            }
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            std::thread::sleep(Duration::from_millis(10));
+                        }
+                        Err(e) => {
+                            log_warn!(MODULE, "Fixture server accept failed: {}", e);
+                            std::thread::sleep(Duration::from_millis(10));
+                        }
+                    }
+                }
+            })
+            .map_err(|e| format!("Failed to start fixture server: {}", e))?;
+
+        Ok(Self {
+            addr,
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for FixtureServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Answer one GET request with a file from `root`, or 404
+fn serve(stream: TcpStream, root: &Path) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut stream = reader.into_inner();
+    let requested = request_line
+        .strip_prefix("GET /")
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or_default();
+    // Plain file names only, nothing outside the fixture directory
+    let file = Path::new(requested)
+        .file_name()
+        .filter(|name| !requested.is_empty() && *name == requested)
+        .and_then(|name| File::open(root.join(name)).ok());
+
+    match file {
+        Some(mut file) => {
+            let len = file.metadata()?.len();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Type: application/octet-stream\r\nConnection: close\r\n\r\n",
+                len
+            )?;
+            io::copy(&mut file, &mut stream)?;
+        }
+        None => {
+            stream.write_all(
+                b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            )?;
+        }
+    }
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, addr).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_scratch_path_from_args() {
+        let args = |list: &[&str]| list.iter().map(OsString::from).collect::<Vec<_>>();
+
+        assert_eq!(scratch_path_from_args(args(&["app"])), None);
+        assert_eq!(
+            scratch_path_from_args(args(&["app", "--selftest", "/tmp/scratch.img"])),
+            Some(PathBuf::from("/tmp/scratch.img"))
+        );
+        // Without a path, a temp file is used
+        let default = scratch_path_from_args(args(&["app", "--selftest"])).unwrap();
+        assert!(default.starts_with(std::env::temp_dir()));
+    }
+
+    #[test]
+    fn test_fixture_image() {
+        let image = fixture_image(200_000);
+        assert_eq!(image.len(), 200_000);
+        assert_eq!(image, fixture_image(200_000));
+        // Patterned blocks follow the random ones
+        assert_eq!(image[64 * 1024 + 1], ((64 * 1024 + 1) % 251) as u8);
+    }
+
+    #[test]
+    fn test_fixture_server() {
+        let root = std::env::temp_dir().join(format!("selftest-server-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("image.img"), b"hello").unwrap();
+        let server = FixtureServer::start(root.clone()).unwrap();
+
+        let ok = get(server.addr, "/image.img");
+        assert!(ok.starts_with("HTTP/1.1 200 OK"));
+        assert!(ok.ends_with("\r\n\r\nhello"));
+        assert!(get(server.addr, "/../image.img").starts_with("HTTP/1.1 404"));
+        assert!(get(server.addr, "/missing.img").starts_with("HTTP/1.1 404"));
+
+        drop(server);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_cache_evict_stage() {
+        let scratch =
+            std::env::temp_dir().join(format!("selftest-evict-{}.img", std::process::id()));
+        let mut selftest = Selftest::new(&scratch);
+        selftest.created_work_dir = true;
+
+        let result = selftest.cache_evict();
+        selftest.cleanup();
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert!(!selftest.work_dir.exists());
+    }
+}
@@ -0,0 +1,614 @@
+//! Settings model
+//!
+//! Every preference is a field of `Settings`, with its default and its
+//! validation in one place. The settings are stored as flat keys in
+//! `settings.json`, the layout the frontend reads and writes through the
+//! store plugin, next to a `settings_version` key. Settings written by older
+//! releases are migrated when they are loaded.
+//!
+//! The settings are loaded once into `AppState` and reloaded whenever the
+//! frontend commits the store; backend changes go through `update`.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::commands::AppState;
+use crate::config;
+use crate::cues::CompletionCues;
+use crate::flash::VerifyMode;
+use crate::images::{validate_sources, CatalogSource};
+use crate::proxy::{self, ProxySettings};
+use crate::settings_store::commit_settings;
+use crate::{log_info, log_warn};
+
+const MODULE: &str = "settings";
+
+type Entries = serde_json::Map<String, serde_json::Value>;
+
+/// Color scheme of the UI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Follow the system
+    #[default]
+    Auto,
+    Light,
+    Dark,
+}
+
+/// What is erased besides the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EraseMode {
+    /// Only the partition table area
+    #[default]
+    Quick,
+    /// Also the space right after the image, so verification can detect
+    /// cards that wrap writes around past their real capacity
+    Full,
+}
+
+impl Theme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Light => "light",
+            Self::Dark => "dark",
+        }
+    }
+}
+
+impl EraseMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Quick => "quick",
+            Self::Full => "full",
+        }
+    }
+}
+
+/// All user preferences
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Layout version, see `config::settings::VERSION`
+    #[serde(rename = "settings_version")]
+    pub version: u32,
+    pub theme: Theme,
+    /// UI language code, or "auto" for the system language
+    pub language: String,
+    pub show_motd: bool,
+    pub show_updater_modal: bool,
+    pub developer_mode: bool,
+    pub cache_enabled: bool,
+    /// Bytes the image cache may take
+    pub cache_max_size: u64,
+    /// Write a failure report to the card after a failed verification
+    pub write_failure_report: bool,
+    pub erase_mode: EraseMode,
+    pub verify_mode: VerifyMode,
+    /// Only write chunks that differ from the device
+    pub smart_write: bool,
+    /// Skip all-zero blocks instead of writing them
+    pub skip_zero_blocks: bool,
+    /// Mirror region tried first, or "auto" to let the redirector pick
+    pub mirror_region: String,
+    /// Log files kept
+    pub log_retention_count: u64,
+    /// Age in days after which log files are deleted, 0 for never
+    pub log_retention_days: u64,
+    /// Per-module log levels in developer mode, e.g. "devices=debug"
+    pub module_log_levels: String,
+    /// Simulated network speed in developer mode, 0 when off
+    pub download_throttle_kbps: u64,
+    /// Simulated device write speed in developer mode, 0 when off
+    pub write_throttle_kbps: u64,
+    /// `NetworkStatus::network_id`s large downloads are allowed on although metered
+    pub metered_allowed_networks: Vec<String>,
+    /// Names the user gave devices, keyed by serial number
+    pub device_aliases: HashMap<String, String>,
+    pub completion_cues: CompletionCues,
+    /// Catalogs merged with the official one
+    pub catalog_sources: Vec<CatalogSource>,
+    /// Accept plain HTTP for images, checksums and catalogs
+    pub allow_insecure_urls: bool,
+    /// Parallel connections per download when the server accepts ranges
+    pub download_segments: u64,
+    /// Retries of a mirror after a network error, resuming where it stopped
+    pub download_retries: u64,
+    /// Wait for space to be freed when the download volume runs low
+    pub pause_on_low_space: bool,
+    /// Flash downloads straight to the device without caching the image
+    pub direct_write: bool,
+    /// Proxy for all HTTP traffic
+    pub proxy: ProxySettings,
+    /// Paste service logs are uploaded to
+    pub paste_url: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: config::settings::VERSION,
+            theme: Theme::default(),
+            language: "auto".to_string(),
+            show_motd: true,
+            show_updater_modal: true,
+            developer_mode: false,
+            cache_enabled: true,
+            cache_max_size: crate::cache::DEFAULT_MAX_SIZE,
+            write_failure_report: false,
+            erase_mode: EraseMode::default(),
+            verify_mode: VerifyMode::default(),
+            smart_write: false,
+            skip_zero_blocks: false,
+            mirror_region: "auto".to_string(),
+            log_retention_count: config::logging::DEFAULT_RETENTION_COUNT,
+            log_retention_days: config::logging::DEFAULT_RETENTION_DAYS,
+            module_log_levels: String::new(),
+            download_throttle_kbps: 0,
+            write_throttle_kbps: 0,
+            metered_allowed_networks: Vec::new(),
+            device_aliases: HashMap::new(),
+            completion_cues: CompletionCues::default(),
+            catalog_sources: Vec::new(),
+            allow_insecure_urls: false,
+            download_segments: config::download::DEFAULT_SEGMENTS,
+            download_retries: config::download::DEFAULT_RETRIES,
+            pause_on_low_space: true,
+            direct_write: false,
+            proxy: ProxySettings::default(),
+            paste_url: config::paste::DEFAULT_URL.to_string(),
+        }
+    }
+}
+
+/// Migrations to the next layout, by the version they start from
+///
+/// Version 1 is the unversioned layout of earlier releases.
+type Migration = fn(&mut Entries);
+
+const MIGRATIONS: &[(u32, Migration)] = &[(1, migrate_v1)];
+
+/// Version 1 had no schema shared by the frontend and the backend, so
+/// numbers and flags may be stored as strings; they get their typed form
+fn migrate_v1(entries: &mut Entries) {
+    let Ok(serde_json::Value::Object(defaults)) = serde_json::to_value(Settings::default()) else {
+        return;
+    };
+    for (key, default) in &defaults {
+        let Some(serde_json::Value::String(text)) = entries.get(key) else {
+            continue;
+        };
+        let typed = match default {
+            serde_json::Value::Bool(_) => text.trim().parse::<bool>().ok().map(Into::into),
+            serde_json::Value::Number(_) => text.trim().parse::<u64>().ok().map(Into::into),
+            _ => None,
+        };
+        if let Some(typed) = typed {
+            entries.insert(key.clone(), typed);
+        }
+    }
+}
+
+impl Settings {
+    /// Build the settings from store entries written by any release
+    ///
+    /// Older layouts are migrated. A value of the wrong type or an invalid
+    /// one falls back to its default without affecting the others. Returns
+    /// whether the entries need to be written back.
+    pub fn from_entries(entries: &Entries) -> (Self, bool) {
+        let mut entries = entries.clone();
+        let version = entries
+            .get(config::settings::VERSION_KEY)
+            .and_then(|v| v.as_u64())
+            .map_or(1, |v| v as u32);
+        let mut changed = version < config::settings::VERSION;
+        for (from, migrate) in MIGRATIONS {
+            if version <= *from {
+                log_info!(MODULE, "Migrating settings from version {}", from);
+                migrate(&mut entries);
+            }
+        }
+        if version > config::settings::VERSION {
+            log_warn!(
+                MODULE,
+                "Settings were written by a newer release (version {})",
+                version
+            );
+        }
+
+        let Ok(serde_json::Value::Object(defaults)) = serde_json::to_value(Self::default()) else {
+            return (Self::default(), true);
+        };
+        let mut merged = defaults.clone();
+        for (key, default) in &defaults {
+            if key == config::settings::VERSION_KEY {
+                continue;
+            }
+            let Some(value) = entries.get(key) else {
+                continue;
+            };
+            merged.insert(key.clone(), value.clone());
+            if serde_json::from_value::<Self>(merged.clone().into()).is_err() {
+                log_warn!(MODULE, "Ignoring setting {} of the wrong type", key);
+                merged.insert(key.clone(), default.clone());
+                changed = true;
+            }
+        }
+        let mut settings: Self = serde_json::from_value(merged.into()).unwrap_or_default();
+        settings.version = config::settings::VERSION.max(version);
+
+        match settings.clone().checked() {
+            Ok(checked) => {
+                changed |= checked != settings;
+                (checked, changed)
+            }
+            Err(problems) => {
+                let defaults = Self::default();
+                for (field, problem) in &problems {
+                    log_warn!(MODULE, "Resetting setting {}: {}", field, problem);
+                    settings.reset_field(field, &defaults);
+                }
+                (settings.checked().unwrap_or(defaults), true)
+            }
+        }
+    }
+
+    /// The settings as store entries
+    pub fn to_entries(&self) -> Entries {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(entries)) => entries,
+            _ => Entries::new(),
+        }
+    }
+
+    /// The settings with the fields in `patch` replaced, checked
+    pub fn patched(&self, patch: &Entries) -> Result<Self, String> {
+        let mut entries = self.to_entries();
+        for (key, value) in patch {
+            if key == config::settings::VERSION_KEY || !entries.contains_key(key) {
+                return Err(format!("Unknown setting: {}", key));
+            }
+            entries.insert(key.clone(), value.clone());
+        }
+        let settings: Self = serde_json::from_value(entries.into())
+            .map_err(|e| format!("Invalid settings: {}", e))?;
+        let settings = settings.checked().map_err(|problems| {
+            problems
+                .into_iter()
+                .map(|(_, problem)| problem)
+                .collect::<Vec<_>>()
+                .join("; ")
+        })?;
+        for source in &settings.catalog_sources {
+            if !self.catalog_sources.contains(source) {
+                source.validated(settings.allow_insecure_urls)?;
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Check every field, normalizing those that have a canonical form
+    ///
+    /// Returns the invalid fields with their problem instead.
+    fn checked(mut self) -> Result<Self, Vec<(&'static str, String)>> {
+        let mut problems = Vec::new();
+
+        let language = self.language.trim();
+        if language != "auto"
+            && (language.is_empty()
+                || language.len() > 16
+                || !language
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        {
+            problems.push(("language", format!("Invalid language: {}", self.language)));
+        }
+        if !(config::cache::MIN_SIZE..=config::cache::MAX_SIZE).contains(&self.cache_max_size) {
+            problems.push((
+                "cache_max_size",
+                format!(
+                    "Invalid cache size: {} bytes (must be {} to {} bytes)",
+                    self.cache_max_size,
+                    config::cache::MIN_SIZE,
+                    config::cache::MAX_SIZE
+                ),
+            ));
+        }
+        if !crate::mirrors::is_valid_region(&self.mirror_region) {
+            problems.push((
+                "mirror_region",
+                format!("Invalid mirror region: {}", self.mirror_region),
+            ));
+        }
+        if !(1..=config::logging::MAX_RETENTION_COUNT).contains(&self.log_retention_count) {
+            problems.push((
+                "log_retention_count",
+                format!(
+                    "Invalid log retention count: {} (must be 1 to {})",
+                    self.log_retention_count,
+                    config::logging::MAX_RETENTION_COUNT
+                ),
+            ));
+        }
+        if let Err(e) = crate::logging::parse_module_levels(&self.module_log_levels) {
+            problems.push(("module_log_levels", e));
+        }
+        if let Some((serial, _)) = self.device_aliases.iter().find(|(_, alias)| {
+            alias.chars().count() > config::devices::MAX_ALIAS_LEN
+                || alias.chars().any(char::is_control)
+        }) {
+            problems.push((
+                "device_aliases",
+                format!("Invalid alias for device {}", serial),
+            ));
+        }
+        // HTTP catalogs are kept when insecure downloads are turned off, and
+        // skipped until they are allowed again; only new ones are refused
+        match validate_sources(&self.catalog_sources, true) {
+            Ok(sources) => self.catalog_sources = sources,
+            Err(e) => problems.push(("catalog_sources", e)),
+        }
+        if !(1..=config::download::MAX_SEGMENTS).contains(&self.download_segments) {
+            problems.push((
+                "download_segments",
+                format!(
+                    "Invalid download connections: {} (must be 1 to {})",
+                    self.download_segments,
+                    config::download::MAX_SEGMENTS
+                ),
+            ));
+        }
+        if self.download_retries > config::download::MAX_RETRIES {
+            problems.push((
+                "download_retries",
+                format!(
+                    "Invalid download retries: {} (must be 0 to {})",
+                    self.download_retries,
+                    config::download::MAX_RETRIES
+                ),
+            ));
+        }
+        match self.proxy.clone().normalized() {
+            Ok(proxy) => self.proxy = proxy,
+            Err(e) => problems.push(("proxy", e)),
+        }
+        if self.paste_url.trim().is_empty() {
+            self.paste_url = config::paste::DEFAULT_URL.to_string();
+        }
+        match crate::paste::upload::normalize_paste_url(&self.paste_url) {
+            Ok(url) => self.paste_url = url,
+            Err(e) => problems.push(("paste_url", e)),
+        }
+
+        if problems.is_empty() {
+            Ok(self)
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Replace `field` with its value in `defaults`
+    fn reset_field(&mut self, field: &str, defaults: &Self) {
+        let mut entries = self.to_entries();
+        if let Some(default) = defaults.to_entries().remove(field) {
+            entries.insert(field.to_string(), default);
+        }
+        if let Ok(settings) = serde_json::from_value(entries.into()) {
+            *self = settings;
+        }
+    }
+}
+
+/// Write `settings` to the store and commit it
+fn save(app: &AppHandle, settings: &Settings) -> Result<(), String> {
+    let store = app
+        .store(config::settings::FILE_NAME)
+        .map_err(|e| format!("Failed to access store: {}", e))?;
+    for (key, value) in settings.to_entries() {
+        store.set(key, value);
+    }
+    commit_settings(app)
+}
+
+/// Load the settings from the store into `AppState` and return the old ones
+///
+/// Migrated or repaired settings are written back.
+fn load_into_state(app: &AppHandle) -> (Settings, Settings) {
+    let entries: Entries = match app.store(config::settings::FILE_NAME) {
+        Ok(store) => store.entries().into_iter().collect(),
+        Err(e) => {
+            log_warn!(MODULE, "Failed to access store, using defaults: {}", e);
+            Entries::new()
+        }
+    };
+    let (settings, changed) = Settings::from_entries(&entries);
+    if changed {
+        if let Err(e) = save(app, &settings) {
+            log_warn!(MODULE, "Failed to save migrated settings: {}", e);
+        }
+    }
+    let old = std::mem::replace(
+        &mut *app
+            .state::<AppState>()
+            .settings
+            .write()
+            .unwrap_or_else(|e| e.into_inner()),
+        settings.clone(),
+    );
+    (old, settings)
+}
+
+/// Load the settings at startup
+pub fn load(app: &AppHandle) -> Settings {
+    let (_, settings) = load_into_state(app);
+    proxy::configure(&settings.proxy);
+    log_info!(MODULE, "Loaded settings version {}", settings.version);
+    settings
+}
+
+/// Load the settings again after the store changed behind the model
+///
+/// The frontend writes some keys to the store directly; changed settings
+/// take effect as if set through `update`.
+pub fn reload(app: &AppHandle) -> Settings {
+    let (old, settings) = load_into_state(app);
+    apply_changes(app, &old, &settings);
+    settings
+}
+
+/// Make changed settings take effect beyond the settings themselves
+fn apply_changes(app: &AppHandle, old: &Settings, new: &Settings) {
+    if old.developer_mode != new.developer_mode {
+        crate::logging::set_log_level(new.developer_mode);
+    }
+    if old.module_log_levels != new.module_log_levels {
+        // Checked by `Settings::checked`
+        if let Ok(levels) = crate::logging::parse_module_levels(&new.module_log_levels) {
+            crate::logging::set_module_levels(levels);
+        }
+    }
+    if old.log_retention_count != new.log_retention_count
+        || old.log_retention_days != new.log_retention_days
+    {
+        crate::commands::settings::apply_log_retention(app.clone());
+    }
+    if old.cache_max_size != new.cache_max_size {
+        if let Err(e) = crate::cache::evict_to_size(new.cache_max_size) {
+            log_info!(MODULE, "Failed to evict cache after size change: {}", e);
+        }
+    }
+    if !old.cache_enabled && new.cache_enabled {
+        // Queued downloads wait for the cache
+        crate::commands::queue::queue_changed();
+    }
+    if old.proxy != new.proxy {
+        proxy::configure(&new.proxy);
+    }
+    if old.device_aliases != new.device_aliases {
+        crate::commands::board_queries::push_block_devices(app);
+    }
+    if old.allow_insecure_urls != new.allow_insecure_urls
+        && new.catalog_sources.iter().any(CatalogSource::is_insecure)
+    {
+        // HTTP catalogs join or leave the merged catalog on its next load
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            *app.state::<AppState>().images_json.lock().await = None;
+        });
+    }
+}
+
+/// The settings currently in effect
+pub fn current(app: &AppHandle) -> Settings {
+    app.state::<AppState>()
+        .settings
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Replace the fields in `patch`, save, and return the new settings
+///
+/// Nothing changes if a field in `patch` is unknown or invalid.
+pub fn update(app: &AppHandle, patch: &Entries) -> Result<Settings, String> {
+    let old = {
+        let state = app.state::<AppState>();
+        let mut settings = state.settings.write().unwrap_or_else(|e| e.into_inner());
+        let updated = settings.patched(patch)?;
+        save(app, &updated)?;
+        std::mem::replace(&mut *settings, updated)
+    };
+    let new = current(app);
+    apply_changes(app, &old, &new);
+    Ok(new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(value: serde_json::Value) -> Entries {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_from_entries_migrates_and_repairs() {
+        // Unversioned layout with string-encoded values, a value of the wrong
+        // type and an invalid one
+        let (settings, changed) = Settings::from_entries(&entries(serde_json::json!({
+            "theme": "dark",
+            "developer_mode": "true",
+            "log_retention_count": "25",
+            "show_motd": 3,
+            "mirror_region": "atlantis",
+            "download_segments": 8,
+            "onboarding": { "write_warning_accepted": true },
+        })));
+        assert!(changed);
+        assert_eq!(settings.version, config::settings::VERSION);
+        assert_eq!(settings.theme, Theme::Dark);
+        assert!(settings.developer_mode);
+        assert_eq!(settings.log_retention_count, 25);
+        assert!(settings.show_motd);
+        assert_eq!(settings.mirror_region, "auto");
+        assert_eq!(settings.download_segments, 8);
+        assert!(!settings.to_entries().contains_key("onboarding"));
+
+        // Current settings load as they are
+        let (loaded, changed) = Settings::from_entries(&settings.to_entries());
+        assert!(!changed);
+        assert_eq!(loaded, settings);
+    }
+
+    #[test]
+    fn test_patched() {
+        let settings = Settings::default();
+        let patched = settings
+            .patched(&entries(serde_json::json!({
+                "verify_mode": "hash",
+                "paste_url": "",
+                "catalog_sources": [{ "name": " Lab ", "url": "https://lab.example.com/all.json" }],
+            })))
+            .unwrap();
+        assert_eq!(patched.verify_mode, VerifyMode::Hash);
+        assert_eq!(patched.paste_url, config::paste::DEFAULT_URL);
+        assert_eq!(patched.catalog_sources[0].name, "Lab");
+
+        // An HTTP catalog needs insecure downloads, and stays when they are
+        // turned off again
+        let http_source = serde_json::json!([{ "name": "Lan", "url": "http://10.0.0.2/all.json" }]);
+        assert!(settings
+            .patched(&entries(
+                serde_json::json!({ "catalog_sources": http_source })
+            ))
+            .is_err());
+        let insecure = settings
+            .patched(&entries(serde_json::json!({
+                "allow_insecure_urls": true,
+                "catalog_sources": http_source,
+            })))
+            .unwrap();
+        let secure = insecure
+            .patched(&entries(
+                serde_json::json!({ "allow_insecure_urls": false }),
+            ))
+            .unwrap();
+        assert_eq!(secure.catalog_sources.len(), 1);
+        assert!(secure.catalog_sources[0].is_insecure());
+        let (loaded, _) = Settings::from_entries(&secure.to_entries());
+        assert_eq!(loaded.catalog_sources, secure.catalog_sources);
+
+        let invalid = |patch| settings.patched(&entries(patch)).is_err();
+        assert!(invalid(serde_json::json!({ "erase_mode": "secure" })));
+        assert!(invalid(serde_json::json!({ "download_segments": 0 })));
+        assert!(invalid(serde_json::json!({ "download_retries": 100 })));
+        assert!(invalid(serde_json::json!({ "cache_max_size": 1 })));
+        assert!(invalid(serde_json::json!({ "no_such_setting": true })));
+        assert!(invalid(serde_json::json!({ "settings_version": 1 })));
+    }
+}
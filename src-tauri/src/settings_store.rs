@@ -0,0 +1,306 @@
+//! Crash-safe settings file
+//!
+//! The store plugin writes `settings.json` in place, so a crash during a
+//! write can leave a truncated file, which the plugin then loads as an empty
+//! store without a word. Settings are therefore committed in two phases: the
+//! new content goes to a temporary file that is synced to disk, the previous
+//! file is copied to `settings.json.bak` if it is intact, and the temporary
+//! file is renamed over it. The settings file is never missing along the
+//! way. A broken file found at startup, or when `repair_settings`
+//! runs, is set aside and replaced by the last good copy; a missing one is
+//! restored from the temporary file or the backup.
+//!
+//! The settings store is opened with auto-save disabled, so every write goes
+//! through `commit_settings`, from the backend or from the frontend. The
+//! plugin saves every open store in place when the app exits, so the store
+//! is committed and closed first, see `close_settings`.
+//!
+//! Thread Safety:
+//! Commits and repairs are serialized by a global Mutex.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tauri::AppHandle;
+use tauri_plugin_store::{resolve_store_path, StoreExt};
+
+use crate::config;
+use crate::{log_info, log_warn};
+
+const MODULE: &str = "settings_store";
+
+/// Global mutex to serialize writes of the settings file
+static SETTINGS_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// What a repair of the settings file found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingsRepair {
+    /// The file was intact (or absent) and left alone
+    Healthy,
+    /// The file was broken and replaced by the last good copy
+    Restored,
+    /// The file was broken without a good copy; settings start from defaults
+    Reset,
+}
+
+fn settings_path(app: &AppHandle) -> Result<PathBuf, String> {
+    resolve_store_path(app, config::settings::FILE_NAME)
+        .map_err(|e| format!("Failed to resolve settings path: {}", e))
+}
+
+/// Path next to the settings file with `suffix` appended to its name
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Whether `path` holds a settings object the store can load
+fn is_intact(path: &Path) -> bool {
+    fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<serde_json::Map<_, _>>(&bytes).ok())
+        .is_some()
+}
+
+/// Make renames in `dir` durable
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Err(e) = fs::File::open(dir).and_then(|d| d.sync_all()) {
+        log_warn!(MODULE, "Failed to sync {}: {}", dir.display(), e);
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+/// Write settings to `path` in two phases, rotating the previous good copy
+fn write_settings_file(
+    path: &Path,
+    entries: &serde_json::Map<String, serde_json::Value>,
+) -> Result<(), String> {
+    let dir = path.parent().ok_or("Invalid settings path")?;
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create settings directory: {}", e))?;
+    let content = serde_json::to_vec_pretty(entries)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+
+    // Phase one: the new content is on disk before anything is replaced
+    let temp = sibling(path, config::settings::TEMP_SUFFIX);
+    let written = fs::File::create(&temp).and_then(|mut file| {
+        use std::io::Write;
+        file.write_all(&content)?;
+        file.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(format!("Failed to write settings: {}", e));
+    }
+
+    // Phase two: keep the previous copy if it is good, then swap. The
+    // rename replaces the file at once, so there always is one.
+    if is_intact(path) {
+        let backup = sibling(path, config::settings::BACKUP_SUFFIX);
+        if let Err(e) = fs::copy(path, &backup) {
+            log_warn!(MODULE, "Failed to rotate settings backup: {}", e);
+        }
+    }
+    fs::rename(&temp, path).map_err(|e| format!("Failed to replace settings: {}", e))?;
+    sync_dir(dir);
+    Ok(())
+}
+
+/// Replace a broken settings file by the last good copy
+///
+/// The broken file is kept next to it for inspection. A missing file is
+/// restored from a complete temporary file, the newer copy, or the backup;
+/// with neither, it was never written.
+fn repair_file(path: &Path) -> Result<SettingsRepair, String> {
+    let temp = sibling(path, config::settings::TEMP_SUFFIX);
+    if !path.exists() {
+        let backup = sibling(path, config::settings::BACKUP_SUFFIX);
+        let Some(source) = [&temp, &backup].into_iter().find(|p| is_intact(p)) else {
+            let _ = fs::remove_file(&temp);
+            return Ok(SettingsRepair::Healthy);
+        };
+        fs::copy(source, path).map_err(|e| format!("Failed to restore settings: {}", e))?;
+        let _ = fs::remove_file(&temp);
+        if let Some(dir) = path.parent() {
+            sync_dir(dir);
+        }
+        log_warn!(
+            MODULE,
+            "Settings file was missing, restored from {}",
+            source.display()
+        );
+        return Ok(SettingsRepair::Restored);
+    }
+    let _ = fs::remove_file(&temp);
+    if is_intact(path) {
+        return Ok(SettingsRepair::Healthy);
+    }
+
+    let corrupt = sibling(path, config::settings::CORRUPT_SUFFIX);
+    fs::rename(path, &corrupt)
+        .map_err(|e| format!("Failed to set broken settings aside: {}", e))?;
+    log_warn!(
+        MODULE,
+        "Settings file was broken, kept as {}",
+        corrupt.display()
+    );
+
+    let backup = sibling(path, config::settings::BACKUP_SUFFIX);
+    if !is_intact(&backup) {
+        log_warn!(MODULE, "No good settings backup, starting from defaults");
+        return Ok(SettingsRepair::Reset);
+    }
+    fs::copy(&backup, path).map_err(|e| format!("Failed to restore settings backup: {}", e))?;
+    if let Some(dir) = path.parent() {
+        sync_dir(dir);
+    }
+    log_info!(MODULE, "Settings restored from {}", backup.display());
+    Ok(SettingsRepair::Restored)
+}
+
+/// Repair the settings file and open the store, before anything reads it
+///
+/// Must run before the store is first opened: the plugin would load a broken
+/// file as empty and overwrite it on the next save.
+pub fn open_settings(app: &AppHandle) -> SettingsRepair {
+    let repair = settings_path(app).and_then(|path| {
+        let _lock = SETTINGS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        repair_file(&path)
+    });
+    let repair = repair.unwrap_or_else(|e| {
+        log_warn!(MODULE, "Failed to check settings file: {}", e);
+        SettingsRepair::Healthy
+    });
+    if let Err(e) = tauri_plugin_store::StoreBuilder::new(app, config::settings::FILE_NAME)
+        .disable_auto_save()
+        .build()
+    {
+        log_warn!(MODULE, "Failed to open settings store: {}", e);
+    }
+    repair
+}
+
+/// Write the settings store to disk in two phases
+pub fn commit_settings(app: &AppHandle) -> Result<(), String> {
+    let store = app
+        .store(config::settings::FILE_NAME)
+        .map_err(|e| format!("Failed to access store: {}", e))?;
+    let entries = store.entries().into_iter().collect();
+    let path = settings_path(app)?;
+    let _lock = SETTINGS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    write_settings_file(&path, &entries)
+}
+
+/// Commit the settings store and close it before the app exits
+///
+/// Runs on exit requests; the store plugin would otherwise write the open
+/// store in place on exit, past the two-phase commit.
+pub fn close_settings(app: &AppHandle) {
+    let store = match app.get_store(config::settings::FILE_NAME) {
+        Some(store) => store,
+        None => return,
+    };
+    if let Err(e) = commit_settings(app) {
+        log_warn!(MODULE, "Failed to save settings on exit: {}", e);
+    }
+    store.close_resource();
+}
+
+/// Save the settings changed by the frontend
+///
+/// The settings are loaded again, so the changes take effect.
+#[tauri::command]
+pub fn save_settings(app: AppHandle) -> Result<(), String> {
+    commit_settings(&app)?;
+    crate::settings::reload(&app);
+    Ok(())
+}
+
+/// Fall back to the last good copy if the settings file is broken
+///
+/// The store is reloaded from the restored file; after a reset it is cleared.
+/// Either way the settings are loaded again.
+#[tauri::command]
+pub fn repair_settings(app: AppHandle) -> Result<SettingsRepair, String> {
+    let path = settings_path(&app)?;
+    let repair = {
+        let _lock = SETTINGS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        repair_file(&path)?
+    };
+    let store = app
+        .store(config::settings::FILE_NAME)
+        .map_err(|e| format!("Failed to access store: {}", e))?;
+    match repair {
+        SettingsRepair::Healthy => {}
+        SettingsRepair::Restored => store
+            .reload_ignore_defaults()
+            .map_err(|e| format!("Failed to reload settings: {}", e))?,
+        SettingsRepair::Reset => store.clear(),
+    }
+    crate::settings::reload(&app);
+    log_info!(MODULE, "Settings repair: {:?}", repair);
+    Ok(repair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(value: serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_commit_rotates_and_repair_restores() {
+        let dir = std::env::temp_dir().join(format!("settings-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("settings.json");
+        let backup = sibling(&path, config::settings::BACKUP_SUFFIX);
+
+        write_settings_file(&path, &entries(serde_json::json!({ "theme": "dark" }))).unwrap();
+        assert!(!backup.exists());
+        write_settings_file(&path, &entries(serde_json::json!({ "theme": "light" }))).unwrap();
+        assert!(fs::read_to_string(&backup).unwrap().contains("dark"));
+        assert_eq!(repair_file(&path).unwrap(), SettingsRepair::Healthy);
+
+        // A write cut short by a crash
+        fs::write(&path, "{\"theme\": \"li").unwrap();
+        // Broken files are never rotated over the good copy
+        write_settings_file(&path, &entries(serde_json::json!({ "theme": "auto" }))).unwrap();
+        assert!(fs::read_to_string(&backup).unwrap().contains("dark"));
+
+        fs::write(&path, "{\"theme\": \"au").unwrap();
+        assert_eq!(repair_file(&path).unwrap(), SettingsRepair::Restored);
+        assert!(fs::read_to_string(&path).unwrap().contains("dark"));
+        assert!(sibling(&path, config::settings::CORRUPT_SUFFIX).exists());
+
+        // A crash between the phases leaves the file, and the backup is a copy
+        write_settings_file(&path, &entries(serde_json::json!({ "theme": "auto" }))).unwrap();
+        assert!(fs::read_to_string(&backup).unwrap().contains("dark"));
+        assert!(fs::read_to_string(&path).unwrap().contains("auto"));
+
+        // A missing file comes back from a complete temporary file first
+        fs::remove_file(&path).unwrap();
+        let temp = sibling(&path, config::settings::TEMP_SUFFIX);
+        fs::write(&temp, "{\"theme\": \"light\"}").unwrap();
+        assert_eq!(repair_file(&path).unwrap(), SettingsRepair::Restored);
+        assert!(fs::read_to_string(&path).unwrap().contains("light"));
+        assert!(!temp.exists());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(repair_file(&path).unwrap(), SettingsRepair::Restored);
+        assert!(fs::read_to_string(&path).unwrap().contains("dark"));
+
+        fs::write(&path, "").unwrap();
+        fs::remove_file(&backup).unwrap();
+        assert_eq!(repair_file(&path).unwrap(), SettingsRepair::Reset);
+        assert!(!path.exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,33 @@
+//! Running blocking work from async commands
+//!
+//! Hashing, decompression and device writes are long synchronous loops.
+//! Run inline in an async command they hold a runtime worker thread for
+//! minutes; with one core that is the only worker, and every other async
+//! command (progress polling, cancel) waits behind them. These helpers move
+//! such work to Tokio's blocking pool. The loops still check the
+//! operation's cancel flag, since a blocking task cannot be aborted.
+
+use std::future::Future;
+
+/// Run a blocking closure on the blocking pool
+pub async fn run_blocking<T, F>(work: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| format!("Blocking task failed: {}", e))?
+}
+
+/// Drive a future whose body blocks (e.g. a platform writer) on the blocking pool
+///
+/// The future still has the runtime available for the awaits in between.
+pub async fn run_blocking_future<T, F>(future: F) -> Result<T, String>
+where
+    F: Future<Output = Result<T, String>> + Send + 'static,
+    T: Send + 'static,
+{
+    let handle = tokio::runtime::Handle::current();
+    run_blocking(move || handle.block_on(future)).await
+}
@@ -0,0 +1,350 @@
+//! Host architecture awareness
+//!
+//! The app runs on x64 and ARM64 desktops alike, and on ARM boards (often
+//! Armbian itself) to flash more cards. This module knows where helper tools
+//! live on each host, which PowerShell to run on Windows, how many threads a
+//! memory-constrained ARM board can afford, and whether the build is running
+//! natively or under emulation (Rosetta, Windows x64-on-ARM). The findings are
+//! reported by `host_compatibility` for the system info screen.
+
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// Processor architecture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostArch {
+    X64,
+    Arm64,
+    X86,
+    Arm,
+    Other,
+}
+
+impl HostArch {
+    fn from_rust_arch(arch: &str) -> Self {
+        match arch {
+            "x86_64" => HostArch::X64,
+            "aarch64" => HostArch::Arm64,
+            "x86" => HostArch::X86,
+            "arm" => HostArch::Arm,
+            _ => HostArch::Other,
+        }
+    }
+
+    pub fn is_arm(self) -> bool {
+        matches!(self, HostArch::Arm64 | HostArch::Arm)
+    }
+}
+
+/// One item of the compatibility self-check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompatibilityCheck {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+/// How well the running build fits the host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostCompatibility {
+    /// Architecture the app was built for
+    pub build_arch: HostArch,
+    /// Architecture of the machine, which differs when emulated
+    pub native_arch: HostArch,
+    /// Running under Rosetta or Windows x64 emulation
+    pub emulated: bool,
+    /// Threads used for decompression
+    pub worker_threads: usize,
+    pub checks: Vec<CompatibilityCheck>,
+}
+
+/// Architecture the app was built for
+pub fn build_arch() -> HostArch {
+    HostArch::from_rust_arch(std::env::consts::ARCH)
+}
+
+static NATIVE_ARCH: Lazy<HostArch> = Lazy::new(|| query_native_arch().unwrap_or_else(build_arch));
+
+/// Architecture of the machine, seen through any emulation layer
+pub fn native_arch() -> HostArch {
+    *NATIVE_ARCH
+}
+
+/// The build runs under an emulation layer on a different architecture
+pub fn is_emulated() -> bool {
+    native_arch() != build_arch()
+}
+
+#[cfg(target_os = "windows")]
+fn query_native_arch() -> Option<HostArch> {
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, IsWow64Process2};
+
+    let mut process_machine: u16 = 0;
+    let mut native_machine: u16 = 0;
+    // SAFETY: both out pointers refer to live locals
+    let ok = unsafe {
+        IsWow64Process2(
+            GetCurrentProcess(),
+            &mut process_machine,
+            &mut native_machine,
+        )
+    };
+    if ok == 0 {
+        return None;
+    }
+    // IMAGE_FILE_MACHINE_* values
+    match native_machine {
+        0x8664 => Some(HostArch::X64),
+        0xAA64 => Some(HostArch::Arm64),
+        0x014C => Some(HostArch::X86),
+        0x01C4 => Some(HostArch::Arm),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn query_native_arch() -> Option<HostArch> {
+    // Rosetta sets this for translated processes; it is missing on Intel Macs.
+    // Full path: tool lookup itself depends on the native architecture.
+    let output = super::run_command(
+        "/usr/sbin/sysctl",
+        &["-n", "sysctl.proc_translated"],
+        super::query_timeout(),
+    )
+    .ok()?;
+    match output.stdout.trim() {
+        "1" => Some(HostArch::Arm64),
+        _ => Some(build_arch()),
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn query_native_arch() -> Option<HostArch> {
+    None
+}
+
+/// Directories searched for a tool that is not on PATH, for this host
+///
+/// Desktop sessions on Armbian and other Debian derivatives often leave the
+/// sbin directories off a normal user's PATH; Homebrew lives in a different
+/// prefix on Apple Silicon than on Intel Macs.
+fn extra_binary_dirs() -> &'static [&'static str] {
+    if cfg!(target_os = "linux") {
+        config::host::LINUX_EXTRA_BINARY_DIRS
+    } else if cfg!(target_os = "macos") && native_arch() == HostArch::Arm64 {
+        config::host::MACOS_ARM64_EXTRA_BINARY_DIRS
+    } else if cfg!(target_os = "macos") {
+        config::host::MACOS_X64_EXTRA_BINARY_DIRS
+    } else {
+        &[]
+    }
+}
+
+/// Find a tool on PATH
+fn find_on_path(program: &str) -> Option<PathBuf> {
+    let names: Vec<String> =
+        if cfg!(target_os = "windows") && Path::new(program).extension().is_none() {
+            vec![format!("{}.exe", program), program.to_string()]
+        } else {
+            vec![program.to_string()]
+        };
+
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Find a host tool: PATH first, then the host's usual install locations
+pub fn find_host_binary(program: &str) -> Option<PathBuf> {
+    if Path::new(program).components().count() > 1 {
+        return Some(PathBuf::from(program));
+    }
+    find_on_path(program).or_else(|| {
+        extra_binary_dirs()
+            .iter()
+            .map(|dir| Path::new(dir).join(program))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// PowerShell to run WinRT queries with
+///
+/// The in-box Windows PowerShell is addressed by full path, so emulated x86
+/// builds and a stripped PATH still find it; it also ships natively on ARM64.
+/// PowerShell 7 (`pwsh`) lacks WinRT type loading and is only a last resort.
+#[cfg(target_os = "windows")]
+pub fn powershell_binary() -> PathBuf {
+    let system_root = std::env::var_os("SystemRoot").unwrap_or_else(|| "C:\\Windows".into());
+    let inbox = PathBuf::from(system_root).join(config::host::WINDOWS_POWERSHELL_PATH);
+    if inbox.is_file() {
+        return inbox;
+    }
+    find_host_binary("powershell")
+        .or_else(|| find_host_binary("pwsh"))
+        .unwrap_or_else(|| PathBuf::from("powershell"))
+}
+
+/// Available memory in bytes, where the OS reports it cheaply
+fn available_memory() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+        parse_mem_available(&meminfo)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+/// `MemAvailable` from /proc/meminfo, in bytes
+#[cfg(any(target_os = "linux", test))]
+fn parse_mem_available(meminfo: &str) -> Option<u64> {
+    meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))
+        .and_then(|rest| rest.trim().strip_suffix("kB"))
+        .and_then(|kb| kb.trim().parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+}
+
+/// Cap a thread count so each thread gets its memory budget
+fn cap_threads_by_memory(threads: usize, available: Option<u64>) -> usize {
+    match available {
+        Some(bytes) => {
+            let affordable = (bytes / config::host::THREAD_MEMORY_BUDGET).max(1) as usize;
+            threads.min(affordable)
+        }
+        None => threads,
+    }
+}
+
+/// Thread count for CPU-heavy work on this host
+///
+/// ARM boards pair many cores with little RAM, and a multi-threaded XZ
+/// decoder holds a block buffer per thread, so threads are also capped by
+/// available memory there.
+pub fn host_thread_limit(threads: usize) -> usize {
+    if build_arch().is_arm() {
+        cap_threads_by_memory(threads, available_memory())
+    } else {
+        threads
+    }
+}
+
+/// Helper tools the flash and device code runs on this platform
+fn required_tools() -> &'static [&'static str] {
+    if cfg!(target_os = "linux") {
+        &["lsblk", "findmnt", "umount"]
+    } else if cfg!(target_os = "macos") {
+        &["diskutil"]
+    } else {
+        &[]
+    }
+}
+
+/// Run the compatibility self-check for this host
+pub fn host_compatibility() -> HostCompatibility {
+    let mut checks = Vec::new();
+
+    checks.push(CompatibilityCheck {
+        name: "native_build".to_string(),
+        ok: !is_emulated(),
+        detail: if is_emulated() {
+            format!(
+                "{:?} build running emulated on {:?}; install the native build for full speed",
+                build_arch(),
+                native_arch()
+            )
+        } else {
+            format!("{:?} build running natively", build_arch())
+        },
+    });
+
+    for tool in required_tools() {
+        let path = super::find_binary(tool);
+        let found = path.is_file();
+        checks.push(CompatibilityCheck {
+            name: format!("tool:{}", tool),
+            ok: found,
+            detail: if found {
+                path.to_string_lossy().to_string()
+            } else {
+                format!("{} not found", tool)
+            },
+        });
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let powershell = powershell_binary();
+        checks.push(CompatibilityCheck {
+            name: "powershell".to_string(),
+            ok: powershell.is_file(),
+            detail: powershell.to_string_lossy().to_string(),
+        });
+    }
+
+    if build_arch().is_arm() {
+        let memory = available_memory();
+        checks.push(CompatibilityCheck {
+            name: "memory".to_string(),
+            ok: memory.map_or(true, |bytes| bytes >= config::host::THREAD_MEMORY_BUDGET),
+            detail: memory.map_or_else(
+                || "unknown".to_string(),
+                |bytes| format!("{} MB available", bytes / (1024 * 1024)),
+            ),
+        });
+    }
+
+    HostCompatibility {
+        build_arch: build_arch(),
+        native_arch: native_arch(),
+        emulated: is_emulated(),
+        worker_threads: super::get_recommended_threads(),
+        checks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_rust_arch() {
+        assert_eq!(HostArch::from_rust_arch("aarch64"), HostArch::Arm64);
+        assert_eq!(HostArch::from_rust_arch("x86_64"), HostArch::X64);
+        assert_eq!(HostArch::from_rust_arch("riscv64"), HostArch::Other);
+        assert!(HostArch::Arm.is_arm());
+        assert!(!HostArch::X64.is_arm());
+    }
+
+    #[test]
+    fn test_parse_mem_available() {
+        let meminfo = "MemTotal:        3944380 kB\nMemFree:          123456 kB\nMemAvailable:    2097152 kB\n";
+        assert_eq!(parse_mem_available(meminfo), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_mem_available("MemTotal: 1 kB\n"), None);
+    }
+
+    #[test]
+    fn test_cap_threads_by_memory() {
+        let budget = config::host::THREAD_MEMORY_BUDGET;
+        assert_eq!(cap_threads_by_memory(4, Some(2 * budget)), 2);
+        assert_eq!(cap_threads_by_memory(4, Some(budget / 2)), 1);
+        assert_eq!(cap_threads_by_memory(4, Some(16 * budget)), 4);
+        assert_eq!(cap_threads_by_memory(4, None), 4);
+    }
+
+    #[test]
+    fn test_find_host_binary_keeps_paths() {
+        assert_eq!(
+            find_host_binary("/usr/bin/env"),
+            Some(PathBuf::from("/usr/bin/env"))
+        );
+    }
+}
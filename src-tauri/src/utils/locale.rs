@@ -0,0 +1,123 @@
+//! Locale negotiation
+//!
+//! Maps a system locale such as "de-AT", "pt_BR.UTF-8" or "zh-Hans-CN" to the
+//! closest language the app has a translation for.
+
+use serde::Serialize;
+
+use crate::config;
+
+/// System locale and the app language negotiated from it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LocaleInfo {
+    /// Locale as reported by the OS
+    pub system_locale: String,
+    /// Closest supported app language
+    pub language: String,
+    /// The negotiated language is written right to left
+    pub rtl: bool,
+    /// The system language is written right to left, even if not supported
+    pub system_rtl: bool,
+}
+
+impl LocaleInfo {
+    pub fn negotiate(system_locale: &str, supported: &[&str]) -> Self {
+        let language = negotiate_language(system_locale, supported);
+        Self {
+            system_locale: system_locale.to_string(),
+            rtl: is_rtl(&language),
+            system_rtl: is_rtl(system_locale),
+            language,
+        }
+    }
+}
+
+/// Split a POSIX or BCP 47 locale into lowercase subtags
+///
+/// "pt_BR.UTF-8" and "pt-BR" both give ["pt", "br"]; "C" and "POSIX" give none.
+fn subtags(locale: &str) -> Vec<String> {
+    let locale = locale.split(['.', '@']).next().unwrap_or_default().trim();
+    if locale.eq_ignore_ascii_case("c") || locale.eq_ignore_ascii_case("posix") {
+        return Vec::new();
+    }
+    locale
+        .split(['-', '_'])
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| tag.to_ascii_lowercase())
+        .collect()
+}
+
+/// Pick the supported language closest to `locale`
+///
+/// Tries the full locale, then drops subtags from the end ("zh-Hans-CN",
+/// "zh-Hans", "zh"). If only regional variants of the language are
+/// supported, the first of them is used. Falls back to the default language.
+pub fn negotiate_language(locale: &str, supported: &[&str]) -> String {
+    let wanted = subtags(locale);
+
+    for len in (1..=wanted.len()).rev() {
+        let candidate = wanted[..len].join("-");
+        if let Some(found) = supported
+            .iter()
+            .find(|lang| subtags(lang).join("-") == candidate)
+        {
+            return found.to_string();
+        }
+    }
+
+    if let Some(base) = wanted.first() {
+        if let Some(found) = supported
+            .iter()
+            .find(|lang| subtags(lang).first() == Some(base))
+        {
+            return found.to_string();
+        }
+    }
+
+    config::i18n::DEFAULT_LANGUAGE.to_string()
+}
+
+/// Whether a language or locale is written right to left
+pub fn is_rtl(locale: &str) -> bool {
+    let tags = subtags(locale);
+    // A script subtag overrides the language default ("az-Arab", "pa-Arab")
+    if tags
+        .iter()
+        .skip(1)
+        .any(|tag| tag == "arab" || tag == "hebr")
+    {
+        return true;
+    }
+    tags.first()
+        .is_some_and(|base| config::i18n::RTL_LANGUAGES.contains(&base.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUPPORTED: &[&str] = &["en", "de", "pt", "pt-BR", "zh"];
+
+    #[test]
+    fn test_negotiate_language() {
+        assert_eq!(negotiate_language("de-AT", SUPPORTED), "de");
+        assert_eq!(negotiate_language("pt_BR.UTF-8", SUPPORTED), "pt-BR");
+        assert_eq!(negotiate_language("pt-PT", SUPPORTED), "pt");
+        assert_eq!(negotiate_language("zh-Hans-CN", SUPPORTED), "zh");
+        assert_eq!(negotiate_language("fi-FI", SUPPORTED), "en");
+        assert_eq!(negotiate_language("C", SUPPORTED), "en");
+        // Only a regional variant is supported
+        assert_eq!(negotiate_language("es", &["en", "es-MX"]), "es-MX");
+    }
+
+    #[test]
+    fn test_rtl_detection() {
+        let info = LocaleInfo::negotiate("ar-EG", SUPPORTED);
+        assert_eq!(info.language, "en");
+        assert!(!info.rtl);
+        assert!(info.system_rtl);
+        assert!(is_rtl("he_IL.UTF-8"));
+        assert!(is_rtl("pa-Arab-PK"));
+        assert!(!is_rtl("de-DE"));
+    }
+}
@@ -1,14 +1,33 @@
 //! Utility functions shared across the application
 //!
 //! This module contains common helpers for formatting, system info,
-//! path management, and progress tracking.
+//! path management, progress tracking, locale negotiation, transfer
+//! throttling, filesystem space checks, running external tools, moving blocking
+//! work off the async runtime, metered connection detection, sandbox
+//! detection and host architecture checks.
 
+mod blocking;
 mod format;
+mod host;
+mod locale;
+mod network;
 mod path;
+mod process;
 mod progress;
+mod sandbox;
+mod storage;
 mod system;
+mod throttle;
 
+pub use blocking::*;
 pub use format::*;
+pub use host::*;
+pub use locale::*;
+pub use network::*;
 pub use path::*;
+pub use process::*;
 pub use progress::*;
+pub use sandbox::*;
+pub use storage::*;
 pub use system::*;
+pub use throttle::*;
@@ -0,0 +1,153 @@
+//! Metered connection detection
+//!
+//! Multi-GB downloads over a mobile hotspot or a capped plan can cost real
+//! money, so downloads warn first when the OS reports the connection as
+//! metered. Windows reports the cost of the internet connection profile;
+//! on Linux NetworkManager reports it over D-Bus. Elsewhere, or when the
+//! query fails, the connection is treated as unmetered.
+
+use serde::Serialize;
+
+/// Current network connection as far as metering is concerned
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct NetworkStatus {
+    /// The OS reports the connection as metered (or guesses so)
+    pub metered: bool,
+    /// Stable identifier of the network, used to remember choices
+    pub network_id: Option<String>,
+    /// Display name of the network (connection name or Wi-Fi SSID)
+    pub network_name: Option<String>,
+}
+
+/// Whether a NetworkManager `NMMetered` value means metered
+///
+/// 1 = yes, 3 = guessed yes (e.g. a phone hotspot); 0, 2 and 4 are unknown or no.
+#[cfg(any(target_os = "linux", test))]
+fn nm_is_metered(value: u32) -> bool {
+    matches!(value, 1 | 3)
+}
+
+/// Parse the "cost|roaming|over limit|profile" line printed by the Windows query
+#[cfg(any(target_os = "windows", test))]
+fn parse_windows_cost(output: &str) -> Option<NetworkStatus> {
+    let mut fields = output.trim().splitn(4, '|');
+    let cost = fields.next()?;
+    let roaming = fields.next()?.eq_ignore_ascii_case("true");
+    let over_limit = fields.next()?.eq_ignore_ascii_case("true");
+    let profile = fields.next().map(str::trim).filter(|p| !p.is_empty());
+
+    Some(NetworkStatus {
+        // Unrestricted and Unknown are not metered
+        metered: matches!(cost, "Fixed" | "Variable") || roaming || over_limit,
+        network_id: profile.map(|p| p.to_string()),
+        network_name: profile.map(|p| p.to_string()),
+    })
+}
+
+/// Query the current connection; unmetered when it cannot be determined
+pub fn network_status() -> NetworkStatus {
+    query().unwrap_or_default()
+}
+
+#[cfg(target_os = "linux")]
+fn query() -> Option<NetworkStatus> {
+    use zbus::blocking::{Connection, Proxy};
+    use zbus::zvariant::OwnedObjectPath;
+
+    const SERVICE: &str = "org.freedesktop.NetworkManager";
+
+    let connection = Connection::system().ok()?;
+    let manager = Proxy::new(
+        &connection,
+        SERVICE,
+        "/org/freedesktop/NetworkManager",
+        "org.freedesktop.NetworkManager",
+    )
+    .ok()?;
+    let metered = nm_is_metered(manager.get_property::<u32>("Metered").ok()?);
+
+    // "/" when there is no primary connection
+    let primary: OwnedObjectPath = manager.get_property("PrimaryConnection").ok()?;
+    if primary.as_str() == "/" {
+        return Some(NetworkStatus {
+            metered,
+            ..NetworkStatus::default()
+        });
+    }
+    let active = Proxy::new(
+        &connection,
+        SERVICE,
+        primary.as_str(),
+        "org.freedesktop.NetworkManager.Connection.Active",
+    )
+    .ok()?;
+
+    Some(NetworkStatus {
+        metered,
+        network_id: active.get_property::<String>("Uuid").ok(),
+        network_name: active.get_property::<String>("Id").ok(),
+    })
+}
+
+#[cfg(target_os = "windows")]
+fn query() -> Option<NetworkStatus> {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    use super::{powershell_binary, query_timeout, run_with_timeout};
+
+    /// Keeps the PowerShell console from flashing up
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    // The WinRT connection cost API is not reachable through windows-sys
+    const SCRIPT: &str = "$p = [Windows.Networking.Connectivity.NetworkInformation, \
+        Windows.Networking.Connectivity, ContentType = WindowsRuntime]::GetInternetConnectionProfile(); \
+        if ($p) { $c = $p.GetConnectionCost(); \
+        \"$($c.NetworkCostType)|$($c.Roaming)|$($c.OverDataLimit)|$($p.ProfileName)\" }";
+
+    let mut command = Command::new(powershell_binary());
+    command
+        .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .creation_flags(CREATE_NO_WINDOW);
+    let output = run_with_timeout(&mut command, query_timeout())
+        .and_then(|output| output.check())
+        .ok()?;
+    parse_windows_cost(&output.stdout)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn query() -> Option<NetworkStatus> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nm_is_metered() {
+        assert!(nm_is_metered(1));
+        assert!(nm_is_metered(3));
+        assert!(!nm_is_metered(0));
+        assert!(!nm_is_metered(2));
+        assert!(!nm_is_metered(4));
+    }
+
+    #[test]
+    fn test_parse_windows_cost() {
+        let status = parse_windows_cost("Fixed|False|False|Pixel 8 hotspot\r\n").unwrap();
+        assert!(status.metered);
+        assert_eq!(status.network_name.as_deref(), Some("Pixel 8 hotspot"));
+
+        let status = parse_windows_cost("Unrestricted|False|False|Home").unwrap();
+        assert!(!status.metered);
+        assert!(
+            parse_windows_cost("Unrestricted|True|False|Abroad")
+                .unwrap()
+                .metered
+        );
+
+        // No internet profile: the script prints nothing
+        assert_eq!(parse_windows_cost(""), None);
+    }
+}
@@ -0,0 +1,240 @@
+//! External process runner
+//!
+//! Runs helper tools (lsblk, diskutil, udisksctl, ...) with a timeout, so a
+//! hung tool can't freeze device refresh or a flash forever. The child is
+//! killed if it runs too long or if the caller goes away, and stderr is
+//! captured for error messages. Inside a snap, tools bundled with the snap
+//! are preferred over the host's.
+
+// Windows only runs the network cost query, through run_with_timeout
+#![cfg_attr(target_os = "windows", allow(dead_code))]
+
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::config;
+use crate::log_warn;
+
+use super::host::find_host_binary;
+use super::sandbox::snap_dir;
+
+const MODULE: &str = "utils::process";
+
+/// How often a running child is checked for exit
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Output of a finished process
+#[derive(Debug)]
+pub struct CommandOutput {
+    pub program: String,
+    pub status: ExitStatus,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.status.success()
+    }
+
+    /// Turn a non-zero exit into an error carrying the captured stderr
+    pub fn check(self) -> Result<Self, String> {
+        if self.success() {
+            return Ok(self);
+        }
+        let stderr = self.stderr.trim();
+        Err(if stderr.is_empty() {
+            format!("{} failed ({})", self.program, self.status)
+        } else {
+            format!("{} failed ({}): {}", self.program, self.status, stderr)
+        })
+    }
+}
+
+/// Child process that is killed and reaped when dropped
+struct KillOnDrop(Child);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        if let Ok(None) = self.0.try_wait() {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+}
+
+/// Timeout for quick queries such as lsblk or diskutil info
+pub fn query_timeout() -> Duration {
+    Duration::from_secs(config::process::QUERY_TIMEOUT_SECS)
+}
+
+/// Timeout for mounting and unmounting partitions
+pub fn mount_timeout() -> Duration {
+    Duration::from_secs(config::process::MOUNT_TIMEOUT_SECS)
+}
+
+/// Resolve a helper tool to the binary that should run
+///
+/// Snaps bundle their own copies of tools under `$SNAP`, and those are not on
+/// the PATH of every launcher; they win over the host's. Tools missing from
+/// PATH are looked up where the host usually installs them. If nothing is
+/// found, the bare name is returned and spawning reports the error.
+pub fn find_binary(program: &str) -> PathBuf {
+    if let Some(snap) = snap_dir() {
+        if let Some(bundled) = config::sandbox::SNAP_BINARY_DIRS
+            .iter()
+            .map(|dir| snap.join(dir).join(program))
+            .find(|candidate| candidate.is_file())
+        {
+            return bundled;
+        }
+    }
+    find_host_binary(program).unwrap_or_else(|| PathBuf::from(program))
+}
+
+/// Whether a process with this ID is still running
+pub fn process_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        // Signal 0 only checks for existence; EPERM means it runs as another user
+        // SAFETY: kill with signal 0 has no effect on the target
+        let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+        result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::Foundation::CloseHandle;
+        use windows_sys::Win32::System::Threading::{
+            GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+        };
+
+        // Exit code reported while a process runs
+        const STILL_ACTIVE: u32 = 259;
+
+        // SAFETY: the handle is checked and closed; exit_code is a live local
+        unsafe {
+            let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if handle.is_null() {
+                return false;
+            }
+            let mut exit_code = 0u32;
+            let ok = GetExitCodeProcess(handle, &mut exit_code);
+            CloseHandle(handle);
+            ok != 0 && exit_code == STILL_ACTIVE
+        }
+    }
+}
+
+/// Run a program to completion, killing it after `timeout`
+///
+/// A non-zero exit is not an error here; use `CommandOutput::check` for that.
+pub fn run_command(
+    program: &str,
+    args: &[&str],
+    timeout: Duration,
+) -> Result<CommandOutput, String> {
+    let mut command = Command::new(find_binary(program));
+    command.args(args);
+    run_with_timeout(&mut command, timeout)
+}
+
+/// Run a prepared command to completion, killing it after `timeout`
+pub fn run_with_timeout(command: &mut Command, timeout: Duration) -> Result<CommandOutput, String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+
+    let mut child = KillOnDrop(
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run {}: {}", program, e))?,
+    );
+
+    // Drain both pipes while waiting so a chatty child can't block on a full pipe
+    let stdout = child.0.stdout.take().map(read_pipe);
+    let stderr = child.0.stderr.take().map(read_pipe);
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.0.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() >= deadline => {
+                // Dropping the guard kills the child; the reader threads end
+                // once the pipes close and are not waited for
+                drop(child);
+                log_warn!(
+                    MODULE,
+                    "{} timed out after {}s and was killed",
+                    program,
+                    timeout.as_secs()
+                );
+                return Err(format!(
+                    "{} timed out after {}s",
+                    program,
+                    timeout.as_secs()
+                ));
+            }
+            Ok(None) => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(format!("Failed to wait for {}: {}", program, e)),
+        }
+    };
+
+    Ok(CommandOutput {
+        program,
+        status,
+        stdout: join_pipe(stdout),
+        stderr: join_pipe(stderr),
+    })
+}
+
+fn read_pipe<R: Read + Send + 'static>(mut pipe: R) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut data = Vec::new();
+        let _ = pipe.read_to_end(&mut data);
+        data
+    })
+}
+
+fn join_pipe(handle: Option<JoinHandle<Vec<u8>>>) -> String {
+    handle
+        .and_then(|h| h.join().ok())
+        .map(|data| String::from_utf8_lossy(&data).into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_command_captures_output() {
+        let output = run_command(
+            "sh",
+            &["-c", "echo out; echo err >&2; exit 3"],
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert_eq!(output.stdout.trim(), "out");
+        assert_eq!(output.stderr.trim(), "err");
+        assert!(!output.success());
+        assert!(output.check().unwrap_err().ends_with(": err"));
+    }
+
+    #[test]
+    fn test_run_command_timeout() {
+        let start = Instant::now();
+        let err = run_command("sleep", &["30"], Duration::from_millis(200)).unwrap_err();
+        assert!(err.contains("timed out"));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_process_alive() {
+        assert!(process_alive(std::process::id()));
+        assert!(!process_alive(u32::MAX / 2));
+    }
+}
@@ -2,10 +2,13 @@
 //!
 //! Provides a reusable progress tracker with speed calculation for
 //! download, flash, verification, SHA256, and decompression operations.
+//! Every update is also published on the event bus.
 
-use std::time::Instant;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 
 use super::bytes_to_mb;
+use crate::events::{self, AppEvent};
 use crate::{log_debug, log_info};
 
 /// Progress tracker for operations with speed calculation
@@ -77,6 +80,11 @@ impl ProgressTracker {
     /// Returns Some(ProgressUpdate) if it's time to log, None otherwise
     pub fn update(&mut self, bytes_added: u64) -> Option<ProgressUpdate> {
         self.processed_bytes += bytes_added;
+        events::publish(AppEvent::Progress {
+            operation: self.operation_name.clone(),
+            processed_bytes: self.processed_bytes,
+            total_bytes: self.total_bytes,
+        });
 
         // Check if we've crossed a log interval boundary
         if self.log_interval_bytes == 0 {
@@ -138,6 +146,11 @@ impl ProgressTracker {
         }
     }
 
+    /// Time elapsed since the tracker was created
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.start_time.elapsed()
+    }
+
     /// Get final summary with average speed and log completion
     pub fn finish(&self) -> ProgressSummary {
         let total_elapsed = self.start_time.elapsed().as_secs_f64();
@@ -167,3 +180,83 @@ impl ProgressTracker {
         summary
     }
 }
+
+/// Rate of a growing byte counter over a sliding time window
+///
+/// Unlike the average since the start, this follows a card that slows down
+/// once its cache fills, and drops to zero when the transfer stalls.
+pub struct RollingRate {
+    window: Duration,
+    min_span: Duration,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RollingRate {
+    /// Rate over `window`, reported once samples span at least `min_span`
+    pub fn new(window: Duration, min_span: Duration) -> Self {
+        Self {
+            window,
+            min_span,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Record the counter's value at `now`
+    pub fn record(&mut self, now: Instant, value: u64) {
+        self.samples.push_back((now, value));
+        // Keep one sample at or beyond the window edge as the baseline
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= self.window {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Bytes per second across the window, None until enough time passed
+    pub fn rate(&self) -> Option<f64> {
+        let (first_time, first_value) = *self.samples.front()?;
+        let (last_time, last_value) = *self.samples.back()?;
+        let span = last_time.duration_since(first_time);
+        if span < self.min_span {
+            return None;
+        }
+        Some(last_value.saturating_sub(first_value) as f64 / span.as_secs_f64())
+    }
+}
+
+/// Seconds until `remaining` bytes are done at `bytes_per_sec`
+pub fn eta_secs(remaining: u64, bytes_per_sec: f64) -> Option<u64> {
+    (bytes_per_sec > 0.0).then(|| (remaining as f64 / bytes_per_sec).ceil() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_rate() {
+        let start = Instant::now();
+        let mut rate = RollingRate::new(Duration::from_secs(4), Duration::from_secs(1));
+        rate.record(start, 0);
+        assert_eq!(rate.rate(), None);
+
+        // 10 MB/s for 4 seconds, then 2 MB/s: the window forgets the fast start
+        for sec in 1..=4 {
+            rate.record(start + Duration::from_secs(sec), sec * 10_000_000);
+        }
+        assert_eq!(rate.rate(), Some(10_000_000.0));
+        for sec in 5..=12 {
+            rate.record(
+                start + Duration::from_secs(sec),
+                40_000_000 + (sec - 4) * 2_000_000,
+            );
+        }
+        assert_eq!(rate.rate(), Some(2_000_000.0));
+    }
+
+    #[test]
+    fn test_eta_secs() {
+        assert_eq!(eta_secs(100, 10.0), Some(10));
+        assert_eq!(eta_secs(101, 10.0), Some(11));
+        assert_eq!(eta_secs(0, 10.0), Some(0));
+        assert_eq!(eta_secs(100, 0.0), None);
+    }
+}
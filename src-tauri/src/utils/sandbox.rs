@@ -0,0 +1,238 @@
+//! Sandbox detection
+//!
+//! Inside a Flatpak the app cannot open `/dev` nodes or browse the host
+//! filesystem: block devices are opened through UDisks2 and files picked by
+//! the user arrive through the Documents portal, under a FUSE mount that
+//! only exposes the chosen file.
+//!
+//! A strictly confined snap is in the same position, except that what it
+//! may reach depends on which interfaces (plugs) are connected. Classic
+//! snaps run unconfined and are treated like a normal install.
+
+// UDisks2, portals and snap interfaces only exist on Linux
+#![cfg_attr(not(target_os = "linux"), allow(dead_code))]
+
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+use super::process::{query_timeout, run_command};
+
+/// Packaging sandbox the app runs in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sandbox {
+    None,
+    Flatpak,
+    Snap,
+}
+
+/// Confinement a snap was installed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapConfinement {
+    Strict,
+    Classic,
+    Devmode,
+}
+
+/// Connection state of one snap interface the app plugs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapInterface {
+    pub name: String,
+    pub connected: bool,
+}
+
+/// Sandbox report for the system info screen
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SandboxStatus {
+    pub kind: Sandbox,
+    pub snap_confinement: Option<SnapConfinement>,
+    /// Devices are only reachable through UDisks2
+    pub udisks2_only: bool,
+    /// Interfaces plugged by the snap, empty outside a snap
+    pub interfaces: Vec<SnapInterface>,
+    /// Interfaces that must be connected before flashing works
+    pub missing_interfaces: Vec<String>,
+}
+
+static SANDBOX: Lazy<Sandbox> = Lazy::new(|| {
+    detect_sandbox(
+        Path::new("/.flatpak-info").exists(),
+        std::env::var_os("FLATPAK_ID").is_some(),
+        std::env::var_os("SNAP").is_some() && std::env::var_os("SNAP_NAME").is_some(),
+    )
+});
+
+static SNAP_CONFINEMENT: Lazy<Option<SnapConfinement>> = Lazy::new(|| {
+    let snap = snap_dir()?;
+    let snap_yaml = std::fs::read_to_string(snap.join("meta/snap.yaml")).unwrap_or_default();
+    Some(parse_snap_confinement(&snap_yaml))
+});
+
+fn detect_sandbox(has_flatpak_info: bool, has_flatpak_id: bool, has_snap: bool) -> Sandbox {
+    if has_flatpak_info || has_flatpak_id {
+        Sandbox::Flatpak
+    } else if has_snap {
+        Sandbox::Snap
+    } else {
+        Sandbox::None
+    }
+}
+
+/// Confinement from a snap's `meta/snap.yaml`; snapd defaults to strict
+fn parse_snap_confinement(snap_yaml: &str) -> SnapConfinement {
+    let value = snap_yaml
+        .lines()
+        .filter(|line| !line.starts_with(' '))
+        .find_map(|line| line.strip_prefix("confinement:"))
+        .map(|v| v.trim().trim_matches(|c| c == '"' || c == '\''));
+
+    match value {
+        Some("classic") => SnapConfinement::Classic,
+        Some("devmode") => SnapConfinement::Devmode,
+        _ => SnapConfinement::Strict,
+    }
+}
+
+/// Sandbox of the running process, detected once
+pub fn sandbox() -> Sandbox {
+    *SANDBOX
+}
+
+/// Running inside a Flatpak
+pub fn is_flatpak() -> bool {
+    sandbox() == Sandbox::Flatpak
+}
+
+/// Root of the snap's read-only squashfs, when running from a snap
+pub fn snap_dir() -> Option<PathBuf> {
+    if sandbox() != Sandbox::Snap {
+        return None;
+    }
+    std::env::var_os("SNAP").map(PathBuf::from)
+}
+
+/// Confinement of the running snap, None outside a snap
+pub fn snap_confinement() -> Option<SnapConfinement> {
+    *SNAP_CONFINEMENT
+}
+
+/// The sandbox restricts host access (Flatpak or a strict snap)
+///
+/// Devmode snaps only log denials, classic snaps have none.
+pub fn is_confined() -> bool {
+    match sandbox() {
+        Sandbox::None => false,
+        Sandbox::Flatpak => true,
+        Sandbox::Snap => snap_confinement() == Some(SnapConfinement::Strict),
+    }
+}
+
+/// Block devices may only be opened through UDisks2, never directly
+pub fn requires_udisks2() -> bool {
+    is_confined()
+}
+
+/// Path handed out by the Documents portal
+///
+/// Such paths expose only the selected file, not its siblings.
+pub fn is_document_portal_path(path: &Path) -> bool {
+    let parts: Vec<&str> = path
+        .components()
+        .take(5)
+        .filter_map(|c| c.as_os_str().to_str())
+        .collect();
+    matches!(
+        parts.as_slice(),
+        ["/", "run", "flatpak", "doc", ..] | ["/", "run", "user", _, "doc", ..]
+    )
+}
+
+/// Ask snapd whether one of the snap's plugs is connected
+fn snap_interface_connected(name: &str) -> bool {
+    run_command("snapctl", &["is-connected", name], query_timeout())
+        .is_ok_and(|output| output.success())
+}
+
+/// Report the sandbox and, inside a snap, which interfaces are connected
+pub fn sandbox_status() -> SandboxStatus {
+    let interfaces: Vec<SnapInterface> = if sandbox() == Sandbox::Snap {
+        config::sandbox::SNAP_PLUGS
+            .iter()
+            .map(|name| SnapInterface {
+                name: name.to_string(),
+                connected: snap_interface_connected(name),
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let missing_interfaces = if is_confined() {
+        interfaces
+            .iter()
+            .filter(|i| !i.connected)
+            .filter(|i| config::sandbox::SNAP_REQUIRED_PLUGS.contains(&i.name.as_str()))
+            .map(|i| i.name.clone())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    SandboxStatus {
+        kind: sandbox(),
+        snap_confinement: snap_confinement(),
+        udisks2_only: requires_udisks2(),
+        interfaces,
+        missing_interfaces,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_sandbox() {
+        assert_eq!(detect_sandbox(false, false, false), Sandbox::None);
+        assert_eq!(detect_sandbox(true, false, false), Sandbox::Flatpak);
+        assert_eq!(detect_sandbox(false, true, false), Sandbox::Flatpak);
+        assert_eq!(detect_sandbox(false, false, true), Sandbox::Snap);
+    }
+
+    #[test]
+    fn test_parse_snap_confinement() {
+        let yaml = "name: armbian-imager\nconfinement: classic\napps:\n  imager:\n    command: bin/armbian-imager\n";
+        assert_eq!(parse_snap_confinement(yaml), SnapConfinement::Classic);
+        assert_eq!(
+            parse_snap_confinement("confinement: 'devmode'\n"),
+            SnapConfinement::Devmode
+        );
+        // Nested keys are not the snap's confinement
+        assert_eq!(
+            parse_snap_confinement("apps:\n  x:\n    confinement: classic\n"),
+            SnapConfinement::Strict
+        );
+        assert_eq!(parse_snap_confinement(""), SnapConfinement::Strict);
+    }
+
+    #[test]
+    fn test_is_document_portal_path() {
+        assert!(is_document_portal_path(Path::new(
+            "/run/user/1000/doc/1a2b3c4d/Armbian.img.xz"
+        )));
+        assert!(is_document_portal_path(Path::new(
+            "/run/flatpak/doc/1a2b3c4d/Armbian.img.xz"
+        )));
+        assert!(!is_document_portal_path(Path::new(
+            "/run/user/1000/gvfs/Armbian.img.xz"
+        )));
+        assert!(!is_document_portal_path(Path::new(
+            "/home/user/Downloads/Armbian.img.xz"
+        )));
+    }
+}
@@ -0,0 +1,220 @@
+//! Filesystem type and free space of directories
+//!
+//! Used to keep large decompressed images off RAM-backed filesystems
+//! (tmpfs, ramfs, zram) and off filesystems without enough room.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+/// Filesystem holding a directory
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageInfo {
+    /// The directory that was queried
+    pub path: String,
+    /// Filesystem type as reported by the OS, e.g. "ext4", "tmpfs", "NTFS"
+    pub fs_type: String,
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+    /// Contents live in RAM (tmpfs, ramfs, zram)
+    pub ram_backed: bool,
+}
+
+/// Whether a filesystem type or backing device keeps its data in RAM
+pub fn is_ram_backed(fs_type: &str, device: &str) -> bool {
+    matches!(fs_type, "tmpfs" | "ramfs") || device.starts_with("/dev/zram")
+}
+
+/// Filesystem information for `path`, or its nearest existing ancestor
+pub fn storage_info(path: &Path) -> Option<StorageInfo> {
+    let existing = existing_ancestor(path)?;
+    let (fs_type, device, total_bytes, available_bytes) = query(&existing)?;
+
+    Some(StorageInfo {
+        path: path.to_string_lossy().to_string(),
+        ram_backed: is_ram_backed(&fs_type, &device),
+        fs_type,
+        total_bytes,
+        available_bytes,
+    })
+}
+
+/// Directories created on demand (e.g. the cache) may not exist yet
+fn existing_ancestor(path: &Path) -> Option<PathBuf> {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().ok()?.join(path)
+    };
+    path.ancestors()
+        .find(|p| p.exists())
+        .and_then(|p| p.canonicalize().ok())
+}
+
+/// Device and filesystem type of the mount containing `path`
+///
+/// `mounts` is in /proc/self/mounts format; the longest matching mount point wins.
+#[cfg(any(target_os = "linux", test))]
+fn mount_for(mounts: &str, path: &Path) -> Option<(String, String)> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = fields.next()?;
+            // Spaces in mount points are escaped as \040
+            let mount_point = fields.next()?.replace("\\040", " ");
+            let fs_type = fields.next()?;
+            Some((device, mount_point, fs_type))
+        })
+        .filter(|(_, mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(_, mount_point, _)| mount_point.len())
+        .map(|(device, _, fs_type)| (device.to_string(), fs_type.to_string()))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn space(path: &Path) -> Option<(u64, u64)> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    unsafe {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(c_path.as_ptr(), &mut stat) != 0 {
+            return None;
+        }
+        let fragment = stat.f_frsize as u64;
+        Some((
+            stat.f_blocks as u64 * fragment,
+            stat.f_bavail as u64 * fragment,
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn query(path: &Path) -> Option<(String, String, u64, u64)> {
+    let (total, available) = space(path)?;
+    let mounts = std::fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+    let (device, fs_type) =
+        mount_for(&mounts, path).unwrap_or_else(|| (String::new(), "unknown".to_string()));
+    Some((fs_type, device, total, available))
+}
+
+#[cfg(target_os = "macos")]
+fn query(path: &Path) -> Option<(String, String, u64, u64)> {
+    use std::ffi::{CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+
+    let (total, available) = space(path)?;
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    unsafe {
+        let mut stat: libc::statfs = std::mem::zeroed();
+        if libc::statfs(c_path.as_ptr(), &mut stat) != 0 {
+            return None;
+        }
+        let fs_type = CStr::from_ptr(stat.f_fstypename.as_ptr())
+            .to_string_lossy()
+            .to_string();
+        let device = CStr::from_ptr(stat.f_mntfromname.as_ptr())
+            .to_string_lossy()
+            .to_string();
+        Some((fs_type, device, total, available))
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn query(path: &Path) -> Option<(String, String, u64, u64)> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetDiskFreeSpaceExW, GetVolumeInformationW, GetVolumePathNameW,
+    };
+
+    let wide_path: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+
+    unsafe {
+        let mut available = 0u64;
+        let mut total = 0u64;
+        if GetDiskFreeSpaceExW(
+            wide_path.as_ptr(),
+            &mut available,
+            &mut total,
+            std::ptr::null_mut(),
+        ) == 0
+        {
+            return None;
+        }
+
+        let mut volume = [0u16; 261];
+        let mut fs_name = [0u16; 261];
+        let fs_type = if GetVolumePathNameW(
+            wide_path.as_ptr(),
+            volume.as_mut_ptr(),
+            volume.len() as u32,
+        ) != 0
+            && GetVolumeInformationW(
+                volume.as_ptr(),
+                std::ptr::null_mut(),
+                0,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                fs_name.as_mut_ptr(),
+                fs_name.len() as u32,
+            ) != 0
+        {
+            let len = fs_name.iter().position(|&c| c == 0).unwrap_or(0);
+            String::from_utf16_lossy(&fs_name[..len])
+        } else {
+            "unknown".to_string()
+        };
+        let volume_len = volume.iter().position(|&c| c == 0).unwrap_or(0);
+
+        Some((
+            fs_type,
+            String::from_utf16_lossy(&volume[..volume_len]),
+            total,
+            available,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mount_for() {
+        let mounts = "/dev/sda2 / ext4 rw,relatime 0 0\n\
+                      tmpfs /tmp tmpfs rw,nosuid 0 0\n\
+                      /dev/zram1 /var/cache zram rw 0 0\n\
+                      /dev/sdb1 /media/my\\040card vfat rw 0 0\n";
+
+        let mount = |p: &str| mount_for(mounts, Path::new(p)).unwrap();
+        assert_eq!(mount("/home/user").1, "ext4");
+        assert_eq!(mount("/tmp/armbian").1, "tmpfs");
+        // A path prefix alone is not a mount point match
+        assert_eq!(mount("/tmpdata").1, "ext4");
+        assert_eq!(mount("/var/cache/armbian").0, "/dev/zram1");
+        assert_eq!(mount("/media/my card/x").1, "vfat");
+    }
+
+    #[test]
+    fn test_is_ram_backed() {
+        assert!(is_ram_backed("tmpfs", "tmpfs"));
+        assert!(is_ram_backed("ext4", "/dev/zram0"));
+        assert!(!is_ram_backed("ext4", "/dev/nvme0n1p2"));
+    }
+
+    #[test]
+    fn test_storage_info_missing_dir() {
+        let dir = std::env::temp_dir()
+            .join("storage-test-missing")
+            .join("deeper");
+        let info = storage_info(&dir).unwrap();
+        assert!(info.total_bytes > 0);
+        assert!(info.available_bytes <= info.total_bytes);
+    }
+}
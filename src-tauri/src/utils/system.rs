@@ -2,7 +2,7 @@
 //!
 //! Provides system-level utilities for cross-platform functionality.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Get the number of CPU cores available on the system
 pub fn get_cpu_cores() -> usize {
@@ -13,15 +13,34 @@ pub fn get_cpu_cores() -> usize {
 
 /// Get recommended thread count for CPU-intensive operations
 /// Uses half of available cores to avoid saturating the system
+/// On ARM hosts the count is also capped by available memory
 pub fn get_recommended_threads() -> usize {
-    std::cmp::max(1, get_cpu_cores() / 2)
+    std::cmp::max(1, super::host_thread_limit(get_cpu_cores() / 2))
 }
 
 /// Get the cache directory for the application
 /// On Linux, when running as root via pkexec/sudo, uses the original user's cache directory
+/// Inside a Flatpak, uses the per-app XDG cache directory set up by the sandbox
+/// Inside a snap, uses the revision-independent user data directory
 pub fn get_cache_dir(app_name: &str) -> PathBuf {
     #[cfg(target_os = "linux")]
     {
+        // Flatpak points XDG_CACHE_HOME at ~/.var/app/<id>/cache, the only
+        // cache location the sandbox can write to
+        if super::is_flatpak() {
+            if let Some(xdg_cache) = std::env::var_os("XDG_CACHE_HOME") {
+                return PathBuf::from(xdg_cache).join(app_name);
+            }
+        }
+
+        // A snap's HOME changes with every revision; SNAP_USER_COMMON keeps
+        // cached images across refreshes
+        if super::sandbox() == super::Sandbox::Snap {
+            if let Some(common) = std::env::var_os("SNAP_USER_COMMON") {
+                return PathBuf::from(common).join(".cache").join(app_name);
+            }
+        }
+
         // Check if running as root
         let euid = unsafe { libc::geteuid() };
         if euid == 0 {
@@ -42,6 +61,61 @@ pub fn get_cache_dir(app_name: &str) -> PathBuf {
         .join(app_name)
 }
 
+/// Get the configuration directory for the application
+/// Unlike the cache directory, it holds what the user set up and is never cleared
+/// The same rules apply for root, Flatpak and snap as for `get_cache_dir`
+pub fn get_config_dir(app_name: &str) -> PathBuf {
+    #[cfg(target_os = "linux")]
+    {
+        if super::is_flatpak() {
+            if let Some(xdg_config) = std::env::var_os("XDG_CONFIG_HOME") {
+                return PathBuf::from(xdg_config).join(app_name);
+            }
+        }
+
+        if super::sandbox() == super::Sandbox::Snap {
+            if let Some(common) = std::env::var_os("SNAP_USER_COMMON") {
+                return PathBuf::from(common).join(".config").join(app_name);
+            }
+        }
+
+        let euid = unsafe { libc::geteuid() };
+        if euid == 0 {
+            if let Some(home) = get_original_user_home() {
+                return PathBuf::from(home).join(".config").join(app_name);
+            }
+        }
+    }
+
+    dirs::config_dir()
+        .map(|dir| dir.join(app_name))
+        .unwrap_or_else(|| get_cache_dir(app_name))
+}
+
+/// Move a file older versions kept in the cache directory to `path`
+///
+/// Nothing happens if `path` exists or there is no such file. Returns
+/// whether the file was moved.
+pub fn move_from_cache_dir(app_name: &str, path: &Path) -> std::io::Result<bool> {
+    let Some(name) = path.file_name() else {
+        return Ok(false);
+    };
+    move_legacy_file(&get_cache_dir(app_name).join(name), path)
+}
+
+fn move_legacy_file(legacy: &Path, path: &Path) -> std::io::Result<bool> {
+    if path.exists() || !legacy.is_file() {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Copied, as the two directories may be on different filesystems
+    std::fs::copy(legacy, path)?;
+    std::fs::remove_file(legacy)?;
+    Ok(true)
+}
+
 /// Get the original user's home directory when running as root via pkexec/sudo
 #[cfg(target_os = "linux")]
 fn get_original_user_home() -> Option<String> {
@@ -108,4 +182,25 @@ mod tests {
         let cache = get_cache_dir("test-app");
         assert!(cache.to_string_lossy().contains("test-app"));
     }
+
+    #[test]
+    fn test_move_legacy_file() {
+        let dir = std::env::temp_dir().join(format!("legacy-move-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let legacy = dir.join("cache").join("audit.jsonl");
+        let path = dir.join("config").join("audit.jsonl");
+        std::fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        std::fs::write(&legacy, "old\n").unwrap();
+
+        assert!(move_legacy_file(&legacy, &path).unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\n");
+        assert!(!legacy.exists());
+
+        // A file already in place is never overwritten
+        std::fs::write(&legacy, "stale\n").unwrap();
+        assert!(!move_legacy_file(&legacy, &path).unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,91 @@
+//! Transfer rate throttle
+//!
+//! Developer mode can cap download and write speeds so that slow networks
+//! and slow cards (ETA, stall detection, pause/resume) can be reproduced on
+//! a fast machine. A limit of 0 disables throttling.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::config;
+
+/// Keeps the average rate of a transfer loop at or below a limit
+pub struct Throttle {
+    bytes_per_sec: u64,
+    started: Instant,
+    bytes: u64,
+}
+
+impl Throttle {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec,
+            started: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.bytes_per_sec > 0
+    }
+
+    /// Account for `bytes` more, returning how long to wait to stay at the limit
+    fn delay(&mut self, bytes: u64, now: Instant) -> Duration {
+        if !self.is_active() {
+            return Duration::ZERO;
+        }
+        self.bytes += bytes;
+        let due = Duration::from_secs_f64(self.bytes as f64 / self.bytes_per_sec as f64);
+        due.saturating_sub(now.duration_since(self.started))
+    }
+
+    /// Block after `bytes` were transferred, returning early once `cancelled` is set
+    pub fn pace(&mut self, bytes: u64, cancelled: &AtomicBool) {
+        let mut remaining = self.delay(bytes, Instant::now());
+        let slice = Duration::from_millis(config::developer::THROTTLE_SLICE_MS);
+        while !remaining.is_zero() && !cancelled.load(Ordering::SeqCst) {
+            let step = remaining.min(slice);
+            std::thread::sleep(step);
+            remaining -= step;
+        }
+    }
+
+    /// Async variant of `pace`
+    pub async fn pace_async(&mut self, bytes: u64, cancelled: &AtomicBool) {
+        let mut remaining = self.delay(bytes, Instant::now());
+        let slice = Duration::from_millis(config::developer::THROTTLE_SLICE_MS);
+        while !remaining.is_zero() && !cancelled.load(Ordering::SeqCst) {
+            let step = remaining.min(slice);
+            tokio::time::sleep(step).await;
+            remaining -= step;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let mut throttle = Throttle::new(1000);
+        let start = throttle.started;
+
+        // 500 bytes at 1000 B/s are due after 0.5s
+        assert_eq!(throttle.delay(500, start), Duration::from_millis(500));
+        // Time already spent counts against the wait
+        assert_eq!(
+            throttle.delay(500, start + Duration::from_millis(800)),
+            Duration::from_millis(200)
+        );
+        // Running behind the limit never waits
+        assert_eq!(
+            throttle.delay(100, start + Duration::from_secs(5)),
+            Duration::ZERO
+        );
+
+        let mut off = Throttle::new(0);
+        assert!(!off.is_active());
+        assert_eq!(off.delay(u64::MAX, start), Duration::ZERO);
+    }
+}
@@ -0,0 +1,278 @@
+//! Per-operation working directories
+//!
+//! Downloads and decompressions write their partial output into a private
+//! directory and only move the finished file to its destination, so a
+//! failed or cancelled operation never leaves a half-written image where a
+//! complete one is expected. The directory lives in a `.work` folder next to
+//! the destination, keeping the final move a cheap rename on the same
+//! filesystem.
+//!
+//! `WorkDir` removes its directory when dropped, whatever the outcome, and
+//! the `.work` folder once it is empty, so backups and extractions leave
+//! nothing next to the user's files after an error or cancel. A crash skips
+//! that, so each directory name carries the owning process ID
+//! and `sweep_stale_workdirs` deletes those of processes that are gone,
+//! except interrupted downloads kept for resuming (see `interrupted`).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::cache::get_images_cache_dir;
+use crate::config;
+use crate::decompress::decompress_dirs;
+use crate::interrupted::listed_work_dirs;
+use crate::utils::process_alive;
+use crate::{log_debug, log_info, log_warn};
+
+const MODULE: &str = "workdir";
+
+/// Distinguishes directories created by the same process
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Working directory of one operation, removed on drop
+#[derive(Debug)]
+pub struct WorkDir {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl WorkDir {
+    /// Create a working directory for an operation writing into `dest_dir`
+    ///
+    /// `kind` names the operation ("download", "decompress") in the
+    /// directory name, for logs and for anyone looking at the disk.
+    pub fn create(dest_dir: &Path, kind: &str) -> Result<Self, String> {
+        let root = work_root(dest_dir);
+        let name = format!(
+            "{}-{}-{}",
+            kind,
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::SeqCst)
+        );
+        let path = root.join(name);
+        // A second try covers the folder being removed as empty in between
+        fs::create_dir_all(&path)
+            .or_else(|_| fs::create_dir_all(&path))
+            .map_err(|e| format!("Failed to create working directory: {}", e))?;
+        log_debug!(MODULE, "Created {}", path.display());
+        Ok(Self { path, keep: false })
+    }
+
+    /// Take over a directory kept by `keep`, for the operation that resumes it
+    ///
+    /// The path must be a working directory under `dest_dir`, so a path
+    /// handed back by the frontend cannot direct a deletion elsewhere.
+    pub fn adopt(path: &Path, dest_dir: &Path) -> Result<Self, String> {
+        let root = work_root(dest_dir)
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve working directory root: {}", e))?;
+        let canonical = path
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))?;
+        if canonical.parent() != Some(root.as_path()) {
+            return Err(format!(
+                "{} is not a working directory in {}",
+                canonical.display(),
+                root.display()
+            ));
+        }
+        Ok(Self {
+            path: canonical,
+            keep: false,
+        })
+    }
+
+    /// Location of the directory
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of a file inside the directory
+    pub fn join(&self, name: impl AsRef<Path>) -> PathBuf {
+        self.path.join(name)
+    }
+
+    /// Move a finished file out of the directory to `dest`
+    pub fn persist(&self, name: impl AsRef<Path>, dest: &Path) -> Result<(), String> {
+        let source = self.join(name);
+        fs::rename(&source, dest).map_err(|e| {
+            format!(
+                "Failed to move {} to {}: {}",
+                source.display(),
+                dest.display(),
+                e
+            )
+        })
+    }
+
+    /// Leave the directory on disk when this guard drops
+    ///
+    /// Used when an operation pauses for a user decision; the resuming
+    /// operation takes it back with `adopt`.
+    pub fn keep(mut self) -> PathBuf {
+        self.keep = true;
+        self.path.clone()
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+        match fs::remove_dir_all(&self.path) {
+            Ok(()) => log_debug!(MODULE, "Removed {}", self.path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log_warn!(MODULE, "Failed to remove {}: {}", self.path.display(), e),
+        }
+        if let Some(root) = self.path.parent() {
+            remove_empty_root(root);
+        }
+    }
+}
+
+/// Remove a `.work` folder that no other operation is using any more
+///
+/// `remove_dir` fails on a folder with entries, which covers the race with
+/// an operation creating its directory meanwhile.
+fn remove_empty_root(root: &Path) {
+    if root.file_name() == Some(std::ffi::OsStr::new(config::workdir::DIR_NAME)) {
+        let _ = fs::remove_dir(root);
+    }
+}
+
+/// Folder holding the working directories for `dest_dir`
+pub fn work_root(dest_dir: &Path) -> PathBuf {
+    dest_dir.join(config::workdir::DIR_NAME)
+}
+
+/// Process that created a working directory, from its name
+fn owner_pid(name: &str) -> Option<u32> {
+    let mut parts = name.rsplitn(3, '-');
+    let _id = parts.next()?;
+    let pid = parts.next()?.parse().ok()?;
+    parts.next()?;
+    Some(pid)
+}
+
+/// Remove working directories under `root` left behind by dead processes
+///
+/// Directories in `spared` are kept. Returns the number of directories removed.
+pub fn sweep_root(root: &Path, alive: impl Fn(u32) -> bool, spared: &[PathBuf]) -> usize {
+    let Ok(entries) = fs::read_dir(root) else {
+        return 0;
+    };
+
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        let name = entry.file_name();
+        let Some(pid) = name.to_str().and_then(owner_pid) else {
+            continue;
+        };
+        if !is_dir || alive(pid) || spared.contains(&entry.path()) {
+            continue;
+        }
+        match fs::remove_dir_all(entry.path()) {
+            Ok(()) => removed += 1,
+            Err(e) => log_warn!(MODULE, "Failed to remove {}: {}", entry.path().display(), e),
+        }
+    }
+    remove_empty_root(root);
+    removed
+}
+
+/// Remove working directories of crashed runs from every destination
+pub fn sweep_stale_workdirs() {
+    let roots = std::iter::once(get_images_cache_dir())
+        .chain(decompress_dirs())
+        .map(|dir| work_root(&dir));
+
+    let spared = listed_work_dirs();
+    let removed: usize = roots
+        .map(|root| sweep_root(&root, process_alive, &spared))
+        .sum();
+    if removed > 0 {
+        log_info!(
+            MODULE,
+            "Removed {} working directories left by earlier runs",
+            removed
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("workdir-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_owner_pid() {
+        assert_eq!(owner_pid("download-1234-7"), Some(1234));
+        assert_eq!(owner_pid("decompress-42-1"), Some(42));
+        assert_eq!(owner_pid("download-x-7"), None);
+        assert_eq!(owner_pid("1234-7"), None);
+    }
+
+    #[test]
+    fn test_workdir_removed_on_drop() {
+        let dest = test_dir("drop");
+        let work = WorkDir::create(&dest, "download").unwrap();
+        let path = work.path().to_path_buf();
+        fs::write(work.join("image.img.xz.downloading"), b"partial").unwrap();
+        drop(work);
+        assert!(!path.exists());
+        assert!(!work_root(&dest).exists());
+
+        // Persisted files survive the directory
+        let work = WorkDir::create(&dest, "decompress").unwrap();
+        fs::write(work.join("image.img"), b"done").unwrap();
+        work.persist("image.img", &dest.join("image.img")).unwrap();
+        drop(work);
+        assert_eq!(fs::read(dest.join("image.img")).unwrap(), b"done");
+
+        fs::remove_dir_all(dest).unwrap();
+    }
+
+    #[test]
+    fn test_keep_and_adopt() {
+        let dest = test_dir("adopt");
+        let kept = WorkDir::create(&dest, "download").unwrap().keep();
+        assert!(kept.exists());
+
+        // The folder stays while another directory is in it
+        drop(WorkDir::create(&dest, "extract").unwrap());
+        assert!(kept.exists());
+
+        assert!(WorkDir::adopt(&dest, &dest).is_err());
+        drop(WorkDir::adopt(&kept, &dest).unwrap());
+        assert!(!kept.exists());
+
+        fs::remove_dir_all(dest).unwrap();
+    }
+
+    #[test]
+    fn test_sweep_root() {
+        let dest = test_dir("sweep");
+        let root = work_root(&dest);
+        for name in ["download-1-1", "download-3-1", "decompress-2-1", "notes"] {
+            fs::create_dir_all(root.join(name)).unwrap();
+        }
+
+        let spared = [root.join("download-3-1")];
+        assert_eq!(sweep_root(&root, |pid| pid == 2, &spared), 1);
+        assert!(!root.join("download-1-1").exists());
+        assert!(root.join("download-3-1").exists());
+        assert!(root.join("decompress-2-1").exists());
+        assert!(root.join("notes").exists());
+
+        fs::remove_dir_all(dest).unwrap();
+    }
+}
@@ -1,12 +1,12 @@
 import { useState, useCallback } from 'react';
 import { useTranslation } from 'react-i18next';
 import { Header, HomePage } from './components/layout';
-import { ManufacturerModal, BoardModal, ImageModal, DeviceModal } from './components/modals';
+import { ManufacturerModal, BoardModal, ImageModal, DeviceModal, CustomizationModal, SecondaryImageModal } from './components/modals';
 import { FlashProgress } from './components/flash';
 import { SettingsButton } from './components/settings';
 import { selectCustomImage, detectBoardFromFilename, logInfo } from './hooks/useTauri';
 import { useDeviceMonitor } from './hooks/useDeviceMonitor';
-import type { BoardInfo, ImageInfo, BlockDevice, ModalType, SelectionStep, Manufacturer } from './types';
+import type { BoardInfo, ImageInfo, BlockDevice, ModalType, SelectionStep, Manufacturer, Customization, NetworkConfig, SecondaryImage } from './types';
 import './styles/index.css';
 
 function App() {
@@ -17,6 +17,12 @@ function App() {
   const [selectedBoard, setSelectedBoard] = useState<BoardInfo | null>(null);
   const [selectedImage, setSelectedImage] = useState<ImageInfo | null>(null);
   const [selectedDevice, setSelectedDevice] = useState<BlockDevice | null>(null);
+  const [confirmationToken, setConfirmationToken] = useState<string | undefined>(undefined);
+  const [customization, setCustomization] = useState<Customization | null>(null);
+  const [networkConfig, setNetworkConfig] = useState<NetworkConfig | null>(null);
+  const [showCustomization, setShowCustomization] = useState(false);
+  const [secondaryImage, setSecondaryImage] = useState<SecondaryImage | null>(null);
+  const [showSecondaryImage, setShowSecondaryImage] = useState(false);
 
   // Monitor selected device - clear if disconnected (only when not flashing)
   useDeviceMonitor(
@@ -57,8 +63,9 @@ function App() {
     setActiveModal('none');
   }
 
-  function handleDeviceSelect(device: BlockDevice) {
+  function handleDeviceSelect(device: BlockDevice, token?: string) {
     setSelectedDevice(device);
+    setConfirmationToken(token);
     setActiveModal('none');
     // Start flashing immediately after device selection
     setIsFlashing(true);
@@ -92,8 +99,14 @@ function App() {
           file_url_sha: null,
           file_size: result.size,
           download_repository: 'local',
+          repository_kind: 'unknown',
+          origin_host: null,
+          is_prerelease: false,
+          origin: 'local',
           is_custom: true,
           custom_path: result.path,
+          custom_sha256: result.expected_sha256,
+          custom_sha_file: result.sha_file,
         };
 
         // Reset selections and set board for display
@@ -107,12 +120,9 @@ function App() {
           vendor_name: 'Custom',
           vendor_logo: null,
           image_count: 1,
-          has_standard_support: false,
-          has_community_support: false,
-          has_platinum_support: false,
-          has_eos_support: false,
-          has_tvb_support: false,
-          has_wip_support: false,
+          support_level: null,
+          latest_image_date: null,
+          is_stale: false,
         };
 
         setSelectedBoard(displayBoard);
@@ -123,6 +133,10 @@ function App() {
     }
   }
 
+  function handleCustomShaChange(sha: string) {
+    setSelectedImage((image) => (image ? { ...image, custom_sha256: sha.trim() || null } : image));
+  }
+
   function handleComplete() {
     setIsFlashing(false);
     resetSelectionsFrom('manufacturer'); // Reset all selections
@@ -167,6 +181,11 @@ function App() {
             onChooseImage={() => setActiveModal('image')}
             onChooseDevice={() => setActiveModal('device')}
             onChooseCustomImage={handleCustomImage}
+            onCustomShaChange={handleCustomShaChange}
+            isCustomized={customization !== null || networkConfig !== null}
+            onCustomize={() => setShowCustomization(true)}
+            secondaryImageName={secondaryImage?.name ?? null}
+            onChooseSecondaryImage={() => setShowSecondaryImage(true)}
           />
         ) : (
           selectedBoard && selectedImage && selectedDevice && (
@@ -174,6 +193,10 @@ function App() {
               board={selectedBoard}
               image={selectedImage}
               device={selectedDevice}
+              confirmationToken={confirmationToken}
+              customization={customization}
+              networkConfig={networkConfig}
+              secondaryImage={secondaryImage}
               onComplete={handleComplete}
               onBack={handleBackFromFlash}
             />
@@ -208,6 +231,28 @@ function App() {
         onSelect={handleDeviceSelect}
       />
 
+      <CustomizationModal
+        isOpen={showCustomization}
+        customization={customization}
+        networkConfig={networkConfig}
+        onClose={() => setShowCustomization(false)}
+        onSave={(value, network) => {
+          setCustomization(value);
+          setNetworkConfig(network);
+          setShowCustomization(false);
+        }}
+      />
+
+      <SecondaryImageModal
+        isOpen={showSecondaryImage}
+        secondaryImage={secondaryImage}
+        onClose={() => setShowSecondaryImage(false)}
+        onSave={(value) => {
+          setSecondaryImage(value);
+          setShowSecondaryImage(false);
+        }}
+      />
+
       {!isFlashing && <SettingsButton />}
     </div>
   );
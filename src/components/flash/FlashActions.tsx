@@ -1,4 +1,4 @@
-import { RotateCcw } from 'lucide-react';
+import { RotateCcw, FolderOpen, HardDriveUpload, Save } from 'lucide-react';
 import { useTranslation } from 'react-i18next';
 import type { FlashStage } from './FlashStageIcon';
 
@@ -8,6 +8,12 @@ interface FlashActionsProps {
   onBack: () => void;
   onRetry: () => void;
   onCancel: () => void;
+  /** Mount the flashed card's boot partition and open it */
+  onOpenBootPartition?: () => void;
+  /** Unmount and eject the flashed card so it can be pulled */
+  onEject?: () => void;
+  /** Save a copy of the downloaded image outside the cache */
+  onSaveImage?: () => void;
 }
 
 export function FlashActions({
@@ -16,12 +22,33 @@ export function FlashActions({
   onBack,
   onRetry,
   onCancel,
+  onOpenBootPartition,
+  onEject,
+  onSaveImage,
 }: FlashActionsProps) {
   const { t } = useTranslation();
 
   if (stage === 'complete') {
     return (
       <div className="flash-actions-inline">
+        {onOpenBootPartition && (
+          <button className="btn btn-secondary" onClick={onOpenBootPartition}>
+            <FolderOpen size={16} />
+            {t('flash.openBootPartition')}
+          </button>
+        )}
+        {onEject && (
+          <button className="btn btn-secondary" onClick={onEject}>
+            <HardDriveUpload size={16} />
+            {t('flash.eject')}
+          </button>
+        )}
+        {onSaveImage && (
+          <button className="btn btn-secondary" onClick={onSaveImage}>
+            <Save size={16} />
+            {t('flash.saveImage')}
+          </button>
+        )}
         <button className="btn btn-secondary" onClick={onBack}>
           {t('flash.flashAnother')}
         </button>
@@ -32,6 +59,11 @@ export function FlashActions({
     );
   }
 
+  // Customization takes a few seconds and can't be cancelled
+  if (stage === 'customizing') {
+    return null;
+  }
+
   if (stage === 'error') {
     return (
       <div className="flash-actions-inline">
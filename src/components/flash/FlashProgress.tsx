@@ -1,36 +1,61 @@
 import { useState, useEffect, useRef, useCallback } from 'react';
 import { HardDrive, Disc, FileImage } from 'lucide-react';
 import { useTranslation } from 'react-i18next';
-import type { BoardInfo, ImageInfo, BlockDevice } from '../../types';
+import type { UnlistenFn } from '@tauri-apps/api/event';
+import type { BoardInfo, ImageInfo, BlockDevice, Customization, NetworkConfig, SecondaryImage } from '../../types';
 import { getImageLogo, getOsName } from '../../assets/os-logos';
 import {
   downloadImage,
   flashImage,
-  getDownloadProgress,
-  getFlashProgress,
+  flashImageDirect,
+  onDownloadProgress,
+  onFlashProgress,
+  onDevicesChanged,
   cancelOperation,
   getBoardImageUrl,
   deleteDownloadedImage,
   deleteDecompressedCustomImage,
   forceDeleteCachedImage,
+  exportCachedImage,
   requestWriteAuthorization,
   checkNeedsDecompression,
   decompressCustomImage,
+  verifyCustomImage,
   getBlockDevices,
   continueDownloadWithoutSha,
   cleanupFailedDownload,
+  checkMeteredDownload,
+  probeImageDownload,
+  applyCustomization,
+  applyNetworkConfig,
+  mountPartition,
+  ejectDevice,
+  type NetworkStatus,
+  type ProbeWarning,
 } from '../../hooks/useTauri';
+import { allowMeteredNetwork, getDirectWrite } from '../../hooks/useSettings';
 import { FlashStageIcon, getStageKey, type FlashStage } from './FlashStageIcon';
 import { FlashActions } from './FlashActions';
+import { FleetPanel } from './FleetPanel';
+import { OperationNoteField } from './OperationNoteField';
 import { ErrorDisplay, MarqueeText, ConfirmationDialog } from '../shared';
 import fallbackImage from '../../assets/armbian-logo_nofound.png';
-import { POLLING, CACHE, STORAGE_KEYS } from '../../config';
+import { CACHE, STORAGE_KEYS } from '../../config';
 import { isDeviceConnected } from '../../utils/deviceUtils';
+import { formatDuration, formatFileSize } from '../../utils';
 
 interface FlashProgressProps {
   board: BoardInfo;
   image: ImageInfo;
   device: BlockDevice;
+  /** Confirmation of a fixed disk target, see confirmDangerousTarget */
+  confirmationToken?: string;
+  /** First-boot settings written after a successful flash */
+  customization?: Customization | null;
+  /** Wi-Fi network written after a successful flash */
+  networkConfig?: NetworkConfig | null;
+  /** Raw image written after the main image in the same operation */
+  secondaryImage?: SecondaryImage | null;
   onComplete: () => void;
   onBack: () => void;
 }
@@ -39,6 +64,10 @@ export function FlashProgress({
   board,
   image,
   device,
+  confirmationToken,
+  customization,
+  networkConfig,
+  secondaryImage,
   onComplete,
   onBack,
 }: FlashProgressProps) {
@@ -50,8 +79,28 @@ export function FlashProgress({
   const [imageLoadError, setImageLoadError] = useState(false);
   const [imagePath, setImagePath] = useState<string | null>(null);
   const [showShaWarning, setShowShaWarning] = useState(false);
-  const intervalRef = useRef<number | null>(null);
-  const deviceMonitorRef = useRef<number | null>(null);
+  const [probeWarnings, setProbeWarnings] = useState<ProbeWarning[] | null>(null);
+  const [meteredNetwork, setMeteredNetwork] = useState<NetworkStatus | null>(null);
+  const [rememberNetwork, setRememberNetwork] = useState(false);
+  const [stalledSecs, setStalledSecs] = useState<number | null>(null);
+  /** Percent committed to the device, while writes are still buffered */
+  const [syncedPercent, setSyncedPercent] = useState<number | null>(null);
+  const [speedMbps, setSpeedMbps] = useState<number | null>(null);
+  const [etaSecs, setEtaSecs] = useState<number | null>(null);
+  const [stallDismissed, setStallDismissed] = useState(false);
+  const [customizeWarning, setCustomizeWarning] = useState<string | null>(null);
+  const [deviceActionError, setDeviceActionError] = useState<string | null>(null);
+  const [ejected, setEjected] = useState(false);
+  const [savedImagePath, setSavedImagePath] = useState<string | null>(null);
+  const [skippedBytes, setSkippedBytes] = useState(0);
+  const [zeroSkippedBytes, setZeroSkippedBytes] = useState(0);
+  const [deviceSha256, setDeviceSha256] = useState<string | null>(null);
+  const [hashFallback, setHashFallback] = useState(false);
+  const [operationId, setOperationId] = useState<string | null>(null);
+  const [lowSpace, setLowSpace] = useState<{ bytes: number; paused: boolean } | null>(null);
+  const [downloadRetries, setDownloadRetries] = useState(0);
+  const progressUnlistenRef = useRef<UnlistenFn | null>(null);
+  const deviceMonitorRef = useRef<UnlistenFn | null>(null);
   const maxProgressRef = useRef<number>(0);
   const hasStartedRef = useRef<boolean>(false);
   const deviceDisconnectedRef = useRef<boolean>(false);
@@ -96,6 +145,16 @@ export function FlashProgress({
     return error.includes('[SHA_UNAVAILABLE]');
   };
 
+  /**
+   * Explain an error from a volume too small for the image
+   * Returns null for any other error
+   */
+  const noSpaceMessage = (err: unknown): string | null => {
+    const message = err instanceof Error ? err.message : String(err);
+    if (!message.includes('[NO_SPACE]')) return null;
+    return t('error.noSpace', { details: message.replace('[NO_SPACE]', '').trim() });
+  };
+
 
   // Cleanup downloaded image file or decompressed custom image
   async function cleanupImage(path: string | null) {
@@ -130,8 +189,9 @@ export function FlashProgress({
       // Ignore cleanup errors
     }
 
-    if (intervalRef.current) clearInterval(intervalRef.current);
-    if (deviceMonitorRef.current) clearInterval(deviceMonitorRef.current);
+    stopProgressUpdates();
+    deviceMonitorRef.current?.();
+    deviceMonitorRef.current = null;
     try {
       await cancelOperation();
     } catch {
@@ -145,32 +205,40 @@ export function FlashProgress({
   useEffect(() => {
     const activeStages: FlashStage[] = ['downloading', 'verifying_sha', 'decompressing', 'flashing', 'verifying'];
     if (!activeStages.includes(stage)) {
-      if (deviceMonitorRef.current) {
-        clearInterval(deviceMonitorRef.current);
-        deviceMonitorRef.current = null;
-      }
+      deviceMonitorRef.current?.();
+      deviceMonitorRef.current = null;
       return;
     }
 
-    const checkDevice = async () => {
-      try {
-        const devices = await getBlockDevices();
-        if (!isDeviceConnected(device.path, devices)) {
+    let cancelled = false;
+
+    // Check once, then rely on the device list pushed on hotplug
+    getBlockDevices()
+      .then((devices) => {
+        if (!cancelled && !isDeviceConnected(device.path, devices)) {
           handleDeviceDisconnected();
         }
-      } catch {
-        // Ignore polling errors
-      }
-    };
+      })
+      .catch(() => {
+        // Ignore errors, the hotplug event still reports removal
+      });
 
-    checkDevice();
-    deviceMonitorRef.current = window.setInterval(checkDevice, POLLING.DEVICE_CHECK);
+    onDevicesChanged((devices) => {
+      if (!isDeviceConnected(device.path, devices)) {
+        handleDeviceDisconnected();
+      }
+    }).then((unlisten) => {
+      if (cancelled) {
+        unlisten();
+      } else {
+        deviceMonitorRef.current = unlisten;
+      }
+    });
 
     return () => {
-      if (deviceMonitorRef.current) {
-        clearInterval(deviceMonitorRef.current);
-        deviceMonitorRef.current = null;
-      }
+      cancelled = true;
+      deviceMonitorRef.current?.();
+      deviceMonitorRef.current = null;
     };
   }, [stage, device.path, handleDeviceDisconnected]);
 
@@ -201,7 +269,7 @@ export function FlashProgress({
       if (image.is_custom && image.custom_path) {
         await handleCustomImage(image.custom_path);
       } else {
-        startDownload();
+        await probeBeforeDownload();
       }
     } catch (err) {
       setError(err instanceof Error ? err.message : t('error.authFailed'));
@@ -217,15 +285,26 @@ export function FlashProgress({
     handleAuthorization();
 
     return () => {
-      if (intervalRef.current) {
-        clearInterval(intervalRef.current);
-      }
+      stopProgressUpdates();
     };
     // eslint-disable-next-line react-hooks/exhaustive-deps
   }, []);
 
   async function handleCustomImage(customPath: string) {
     try {
+      if (image.custom_sha256) {
+        setStage('verifying_sha');
+        const { sha_verification } = await verifyCustomImage(customPath, image.custom_sha256);
+        if (sha_verification && !sha_verification.matched) {
+          throw new Error(
+            t('error.shaMismatch', {
+              expected: sha_verification.expected,
+              actual: sha_verification.actual,
+            })
+          );
+        }
+      }
+
       const needsDecompress = await checkNeedsDecompression(customPath);
 
       if (needsDecompress) {
@@ -252,56 +331,144 @@ export function FlashProgress({
         // If we can't check, continue with decompression error
       }
 
-      setError(err instanceof Error ? err.message : t('error.decompressionFailed'));
+      setError(noSpaceMessage(err) ?? (err instanceof Error ? err.message : t('error.decompressionFailed')));
       setStage('error');
     }
   }
 
+  /**
+   * Warn when the server reports a missing image or a size other than the catalog's
+   */
+  async function probeBeforeDownload() {
+    try {
+      const probe = await probeImageDownload(image.file_url, image.file_size);
+      if (probe.warnings.length > 0) {
+        setProbeWarnings(probe.warnings);
+        return;
+      }
+    } catch {
+      // The download itself reports a dead link
+    }
+    checkConnectionBeforeDownload();
+  }
+
+  function handleProbeConfirm() {
+    setProbeWarnings(null);
+    checkConnectionBeforeDownload();
+  }
+
+  function handleProbeCancel() {
+    setProbeWarnings(null);
+    onBack();
+  }
+
+  function describeProbeWarning(warning: ProbeWarning): string {
+    switch (warning.kind) {
+      case 'unreachable':
+        return t('flash.probeUnreachable', { error: warning.error });
+      case 'http_status':
+        return t('flash.probeHttpStatus', { status: warning.status });
+      case 'size_mismatch':
+        return t('flash.probeSizeMismatch', {
+          expected: formatFileSize(warning.expected, '?', true),
+          actual: formatFileSize(warning.actual, '?', true),
+        });
+    }
+  }
+
+  /**
+   * Warn before a large download on a metered connection the user hasn't allowed
+   */
+  async function checkConnectionBeforeDownload() {
+    try {
+      const check = await checkMeteredDownload(image.file_url, image.file_size);
+      if (check.warn) {
+        setRememberNetwork(false);
+        setMeteredNetwork(check.network);
+        return;
+      }
+    } catch {
+      // Don't hold up the download if the connection can't be checked
+    }
+    startDownload();
+  }
+
+  async function handleMeteredConfirm() {
+    const networkId = meteredNetwork?.network_id;
+    setMeteredNetwork(null);
+    if (rememberNetwork && networkId) {
+      try {
+        await allowMeteredNetwork(networkId);
+      } catch {
+        // The warning shows again next time
+      }
+    }
+    startDownload();
+  }
+
+  function handleMeteredCancel() {
+    setMeteredNetwork(null);
+    onBack();
+  }
+
+  function stopProgressUpdates() {
+    progressUnlistenRef.current?.();
+    progressUnlistenRef.current = null;
+  }
+
   async function startDownload() {
+    // The secondary image is written from a file, so it needs the cached path
+    if (!secondaryImage && (await getDirectWrite().catch(() => false))) {
+      startDirectFlash();
+      return;
+    }
+
     setStage('downloading');
     setProgress(0);
     setError(null);
     maxProgressRef.current = 0;
 
-    intervalRef.current = window.setInterval(async () => {
-      try {
-        const prog = await getDownloadProgress();
+    stopProgressUpdates();
+    progressUnlistenRef.current = await onDownloadProgress((prog) => {
+      if (prog.is_verifying_sha && stage !== 'verifying_sha') {
+        setStage('verifying_sha');
+        maxProgressRef.current = 0;
+        setProgress(0);
+      } else if (prog.is_decompressing && stage !== 'decompressing') {
+        setStage('decompressing');
+        maxProgressRef.current = 0;
+        setProgress(0);
+      }
 
-        if (prog.is_verifying_sha && stage !== 'verifying_sha') {
-          setStage('verifying_sha');
-          maxProgressRef.current = 0;
-          setProgress(0);
-        } else if (prog.is_decompressing && stage !== 'decompressing') {
-          setStage('decompressing');
-          maxProgressRef.current = 0;
-          setProgress(0);
-        }
+      setLowSpace(
+        prog.low_space_bytes !== null
+          ? { bytes: prog.low_space_bytes, paused: prog.paused_for_space }
+          : null
+      );
+      setDownloadRetries(prog.retries);
 
-        if (!prog.is_decompressing && !prog.is_verifying_sha) {
-          const newProgress = prog.progress_percent;
-          if (newProgress >= maxProgressRef.current) {
-            maxProgressRef.current = newProgress;
-            setProgress(newProgress);
-          }
+      if (!prog.is_decompressing && !prog.is_verifying_sha) {
+        const newProgress = prog.progress_percent;
+        if (newProgress >= maxProgressRef.current) {
+          maxProgressRef.current = newProgress;
+          setProgress(newProgress);
         }
+      }
 
-        if (prog.error && !deviceDisconnectedRef.current) {
-          setError(prog.error);
-          setStage('error');
-          if (intervalRef.current) clearInterval(intervalRef.current);
-        }
-      } catch {
-        // Ignore polling errors
+      if (prog.error && !deviceDisconnectedRef.current) {
+        setError(prog.error);
+        setStage('error');
+        stopProgressUpdates();
       }
-    }, POLLING.DOWNLOAD_PROGRESS);
+    });
 
     try {
       const path = await downloadImage(image.file_url, image.file_url_sha);
       setImagePath(path);
-      if (intervalRef.current) clearInterval(intervalRef.current);
+      stopProgressUpdates();
       startFlash(path);
     } catch (err) {
-      if (intervalRef.current) clearInterval(intervalRef.current);
+      stopProgressUpdates();
       if (deviceDisconnectedRef.current) return;
 
       const errorMsg = err instanceof Error ? err.message : String(err);
@@ -323,50 +490,114 @@ export function FlashProgress({
         // If we can't check, continue with download error
       }
 
-      setError(err instanceof Error ? err.message : t('error.downloadFailed'));
+      setError(noSpaceMessage(err) ?? (err instanceof Error ? err.message : t('error.downloadFailed')));
       setStage('error');
     }
   }
 
-  async function startFlash(path: string) {
+  /** Reset the progress display and follow the flash until it ends */
+  async function watchFlashProgress() {
     setStage('flashing');
     setProgress(0);
+    setSkippedBytes(0);
+    setZeroSkippedBytes(0);
+    setDeviceSha256(null);
+    setHashFallback(false);
+    setOperationId(null);
     maxProgressRef.current = 0;
 
-    intervalRef.current = window.setInterval(async () => {
-      try {
-        const prog = await getFlashProgress();
-        if (prog.is_verifying) {
-          setStage('verifying');
-          if (maxProgressRef.current > 50) {
-            maxProgressRef.current = 0;
-          }
-        }
-        if (prog.progress_percent >= maxProgressRef.current) {
-          maxProgressRef.current = prog.progress_percent;
-          setProgress(prog.progress_percent);
+    stopProgressUpdates();
+    progressUnlistenRef.current = await onFlashProgress((prog) => {
+      if (prog.is_verifying) {
+        setStage('verifying');
+        if (maxProgressRef.current > 50) {
+          maxProgressRef.current = 0;
         }
-        if (prog.error && !deviceDisconnectedRef.current) {
-          setError(prog.error);
-          setStage('error');
-          if (intervalRef.current) clearInterval(intervalRef.current);
+      }
+      setStalledSecs(prog.stalled ? prog.stalled_secs : null);
+      if (!prog.stalled) setStallDismissed(false);
+      setSpeedMbps(prog.current_speed_mbps);
+      setEtaSecs(prog.eta_secs);
+      setSkippedBytes(prog.skipped_bytes);
+      setZeroSkippedBytes(prog.zero_skipped_bytes);
+      setDeviceSha256(prog.device_sha256);
+      setHashFallback(prog.hash_fallback);
+      if (prog.operation_id) setOperationId(prog.operation_id);
+      setSyncedPercent(
+        !prog.is_verifying && prog.total_bytes > 0 && prog.synced_bytes < prog.written_bytes
+          ? (prog.synced_bytes / prog.total_bytes) * 100
+          : null
+      );
+      if (prog.progress_percent >= maxProgressRef.current) {
+        maxProgressRef.current = prog.progress_percent;
+        setProgress(prog.progress_percent);
+      }
+      if (prog.error && !deviceDisconnectedRef.current) {
+        setError(prog.error);
+        setStage('error');
+        stopProgressUpdates();
+      }
+    });
+  }
+
+  /** Error to show for a failed flash, with a hint for USB bridges known for UAS trouble */
+  function flashErrorMessage(err: unknown): string {
+    const message = err instanceof Error ? err.message : t('error.flashFailed');
+    const bridge = device.usb_bridge;
+    return bridge?.chipset && bridge.uas !== false
+      ? `${message}. ${t('error.uasQuirk', { chipset: bridge.chipset, id: `${bridge.vendor_id}:${bridge.product_id}` })}`
+      : message;
+  }
+
+  /**
+   * Flash the image while it downloads, without caching it
+   *
+   * Download progress is not shown separately: the flash only advances as
+   * fast as the image arrives.
+   */
+  async function startDirectFlash() {
+    await watchFlashProgress();
+    try {
+      await flashImageDirect(image.file_url, image.file_url_sha, device.path, true, confirmationToken);
+      stopProgressUpdates();
+      if (customization || networkConfig) {
+        await customizeDevice(customization ?? null, networkConfig ?? null);
+      }
+      setStage('complete');
+      setProgress(100);
+    } catch (err) {
+      stopProgressUpdates();
+      if (deviceDisconnectedRef.current) return;
+
+      try {
+        const devices = await getBlockDevices();
+        if (!isDeviceConnected(device.path, devices)) {
+          handleDeviceDisconnected();
+          return;
         }
       } catch {
-        // Ignore polling errors
+        // If we can't check, show the flash error
       }
-    }, POLLING.FLASH_PROGRESS);
+      setError(flashErrorMessage(err));
+      setStage('error');
+    }
+  }
 
+  async function startFlash(path: string) {
+    await watchFlashProgress();
     try {
-      await flashImage(path, device.path, true);
-      if (intervalRef.current) clearInterval(intervalRef.current);
+      await flashImage(path, device.path, true, confirmationToken, secondaryImage);
+      stopProgressUpdates();
+      if (customization || networkConfig) {
+        await customizeDevice(customization ?? null, networkConfig ?? null);
+      }
       setStage('complete');
       setProgress(100);
       // Reset failure count on success
       setFlashFailureCount(0);
-      // Cleanup decompressed file after successful flash
-      await cleanupImage(path);
+      // The image stays for fleet mode until the screen is left
     } catch (err) {
-      if (intervalRef.current) clearInterval(intervalRef.current);
+      stopProgressUpdates();
       if (deviceDisconnectedRef.current) return;
 
       // Check if device is still connected before showing flash error
@@ -398,15 +629,35 @@ export function FlashProgress({
 
       // Cleanup decompressed file before showing error
       await cleanupImage(path);
-      setError(err instanceof Error ? err.message : t('error.flashFailed'));
+      setError(flashErrorMessage(err));
       setStage('error');
     }
   }
 
+  /** Write first-boot settings; failures leave the flash itself successful */
+  async function customizeDevice(settings: Customization | null, network: NetworkConfig | null) {
+    setStage('customizing');
+    setCustomizeWarning(null);
+    try {
+      const skipped: string[] = [];
+      if (settings) {
+        skipped.push(...(await applyCustomization(device.path, settings)).skipped);
+      }
+      if (network) {
+        skipped.push(...(await applyNetworkConfig(device.path, network)).skipped);
+      }
+      if (skipped.length > 0) {
+        setCustomizeWarning(t('customize.skipped', { settings: skipped.join(', ') }));
+      }
+    } catch (err) {
+      setCustomizeWarning(t('customize.failed', { error: String(err) }));
+    }
+  }
+
   async function handleCancel() {
     try {
       await cancelOperation();
-      if (intervalRef.current) clearInterval(intervalRef.current);
+      stopProgressUpdates();
       await cleanupImage(imagePath);
       onBack();
     } catch {
@@ -414,6 +665,36 @@ export function FlashProgress({
     }
   }
 
+  async function handleOpenBootPartition() {
+    setDeviceActionError(null);
+    try {
+      await mountPartition(device.path, 1);
+    } catch (err) {
+      setDeviceActionError(t('flash.openBootPartitionFailed', { error: String(err) }));
+    }
+  }
+
+  async function handleEject() {
+    setDeviceActionError(null);
+    try {
+      await ejectDevice(device.path);
+      setEjected(true);
+    } catch (err) {
+      setDeviceActionError(t('flash.ejectFailed', { error: String(err) }));
+    }
+  }
+
+  async function handleSaveImage() {
+    if (!imagePath) return;
+    setDeviceActionError(null);
+    try {
+      const saved = await exportCachedImage(imagePath);
+      if (saved) setSavedImagePath(saved);
+    } catch (err) {
+      setDeviceActionError(t('flash.saveImageFailed', { error: String(err) }));
+    }
+  }
+
   async function handleRetry() {
     setError(null);
     deviceDisconnectedRef.current = false;
@@ -446,6 +727,11 @@ export function FlashProgress({
     onBack();
   }
 
+  async function handleDone() {
+    await cleanupImage(imagePath);
+    onComplete();
+  }
+
   /**
    * Handle confirmation from SHA unavailable modal
    * Proceeds with decompression/flashing without re-downloading
@@ -473,7 +759,7 @@ export function FlashProgress({
       startFlash(path);
     } catch (err) {
       if (deviceDisconnectedRef.current) return;
-      setError(err instanceof Error ? err.message : t('error.decompressionFailed'));
+      setError(noSpaceMessage(err) ?? (err instanceof Error ? err.message : t('error.decompressionFailed')));
       setStage('error');
     }
   }
@@ -541,7 +827,7 @@ export function FlashProgress({
               </div>
               <div className="flash-device-row">
                 <HardDrive size={16} />
-                <MarqueeText text={device.model || device.name} maxWidth={150} className="flash-device-name" />
+                <MarqueeText text={getDeviceDisplayName(device)} maxWidth={150} className="flash-device-name" />
                 <span className="flash-device-size">{device.size_formatted}</span>
               </div>
             </div>
@@ -555,7 +841,8 @@ export function FlashProgress({
 
         {stage !== 'complete' &&
           stage !== 'error' &&
-          stage !== 'authorizing' && (
+          stage !== 'authorizing' &&
+          stage !== 'customizing' && (
             <div className="progress-container">
               <div
                 className={`progress-bar ${
@@ -575,6 +862,32 @@ export function FlashProgress({
             </div>
           )}
 
+        {speedMbps !== null && (stage === 'flashing' || stage === 'verifying') && (
+          <p className="flash-speed">
+            {etaSecs !== null
+              ? t('flash.speedEta', { speed: speedMbps.toFixed(1), eta: formatDuration(etaSecs) })
+              : t('flash.speed', { speed: speedMbps.toFixed(1) })}
+          </p>
+        )}
+
+        {syncedPercent !== null && stage === 'flashing' && (
+          <p className="flash-synced">{t('flash.synced', { percent: syncedPercent.toFixed(0) })}</p>
+        )}
+
+        {stalledSecs !== null && !stallDismissed && (stage === 'flashing' || stage === 'verifying') && (
+          <div className="flash-stalled">
+            <p>{t('flash.stalled', { seconds: stalledSecs })}</p>
+            <div className="flash-actions-inline">
+              <button className="btn btn-secondary" onClick={() => setStallDismissed(true)}>
+                {t('flash.keepWaiting')}
+              </button>
+              <button className="btn btn-secondary" onClick={handleCancel}>
+                {t('common.cancel')}
+              </button>
+            </div>
+          </div>
+        )}
+
         {stage === 'complete' && (
           <p className="flash-success-hint">
             {image.is_custom
@@ -583,14 +896,75 @@ export function FlashProgress({
           </p>
         )}
 
+        {stage === 'complete' && skippedBytes > 0 && (
+          <p className="flash-skipped-hint">
+            {t('flash.skippedUnchanged', { size: formatFileSize(skippedBytes, '0 MB', true) })}
+          </p>
+        )}
+
+        {stage === 'complete' && zeroSkippedBytes > 0 && (
+          <p className="flash-skipped-hint">
+            {t('flash.skippedZeros', { size: formatFileSize(zeroSkippedBytes, '0 MB', true) })}
+          </p>
+        )}
+
+        {lowSpace && (stage === 'downloading' || stage === 'decompressing') && (
+          <p className="flash-customize-warning">
+            {t(lowSpace.paused ? 'flash.lowSpacePaused' : 'flash.lowSpace', {
+              available: formatFileSize(lowSpace.bytes, '?', true),
+            })}
+          </p>
+        )}
+
+        {downloadRetries > 0 && stage === 'downloading' && (
+          <p className="flash-skipped-hint">
+            {t('flash.downloadRetried', { count: downloadRetries })}
+          </p>
+        )}
+
+        {hashFallback && (stage === 'verifying' || stage === 'complete') && (
+          <p className="flash-skipped-hint">{t('flash.hashFallback')}</p>
+        )}
+
+        {stage === 'complete' && deviceSha256 && (
+          <p className="flash-skipped-hint">
+            {t('flash.deviceChecksum', { checksum: deviceSha256 })}
+          </p>
+        )}
+
+        {stage === 'complete' && customizeWarning && (
+          <p className="flash-customize-warning">{customizeWarning}</p>
+        )}
+
+        {stage === 'complete' && deviceActionError && (
+          <p className="flash-customize-warning">{deviceActionError}</p>
+        )}
+
+        {stage === 'complete' && ejected && (
+          <p className="flash-skipped-hint">{t('flash.ejected')}</p>
+        )}
+
+        {stage === 'complete' && savedImagePath && (
+          <p className="flash-skipped-hint">{t('flash.imageSaved', { path: savedImagePath })}</p>
+        )}
+
+        {stage === 'complete' && imagePath && (
+          <FleetPanel imagePath={imagePath} customization={customization} networkConfig={networkConfig} />
+        )}
+
+        {stage !== 'authorizing' && <OperationNoteField operationId={operationId} />}
+
         {error && <ErrorDisplay error={error} />}
 
         <FlashActions
           stage={stage}
-          onComplete={onComplete}
+          onComplete={handleDone}
           onBack={handleBack}
           onRetry={handleRetry}
           onCancel={handleCancel}
+          onOpenBootPartition={ejected ? undefined : handleOpenBootPartition}
+          onEject={ejected ? undefined : handleEject}
+          onSaveImage={imagePath && !image.is_custom ? handleSaveImage : undefined}
         />
       </div>
 
@@ -605,6 +979,52 @@ export function FlashProgress({
           onConfirm={handleShaWarningConfirm}
         />
       )}
+
+      {probeWarnings && (
+        <ConfirmationDialog
+          isOpen={probeWarnings !== null}
+          title={t('flash.probeTitle')}
+          message={t('flash.probeMessage')}
+          confirmText={t('flash.downloadAnyway')}
+          isDanger={false}
+          onCancel={handleProbeCancel}
+          onConfirm={handleProbeConfirm}
+        >
+          <div className="confirm-device">
+            {probeWarnings.map((warning) => (
+              <span key={warning.kind}>{describeProbeWarning(warning)}</span>
+            ))}
+          </div>
+        </ConfirmationDialog>
+      )}
+
+      {meteredNetwork && (
+        <ConfirmationDialog
+          isOpen={meteredNetwork !== null}
+          title={t('flash.meteredTitle')}
+          message={t('flash.meteredMessage', { size: formatFileSize(image.file_size) })}
+          confirmText={t('flash.downloadAnyway')}
+          isDanger={false}
+          onCancel={handleMeteredCancel}
+          onConfirm={handleMeteredConfirm}
+        >
+          {meteredNetwork.network_name && (
+            <div className="confirm-device">
+              <strong>{meteredNetwork.network_name}</strong>
+            </div>
+          )}
+          {meteredNetwork.network_id && (
+            <label className="confirm-remember">
+              <input
+                type="checkbox"
+                checked={rememberNetwork}
+                onChange={(e) => setRememberNetwork(e.target.checked)}
+              />
+              {t('flash.meteredRemember')}
+            </label>
+          )}
+        </ConfirmationDialog>
+      )}
     </div>
   );
 }
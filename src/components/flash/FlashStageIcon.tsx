@@ -7,6 +7,7 @@ import {
   Archive,
   Shield,
   ShieldCheck,
+  Settings2,
 } from 'lucide-react';
 import { UI } from '../../config';
 
@@ -17,6 +18,7 @@ export type FlashStage =
   | 'decompressing'
   | 'flashing'
   | 'verifying'
+  | 'customizing'
   | 'complete'
   | 'error';
 
@@ -39,6 +41,8 @@ export function FlashStageIcon({ stage, size = UI.ICON_SIZE.FLASH_STAGE }: Flash
       return <HardDrive size={size} className="stage-icon flashing" />;
     case 'verifying':
       return <Check size={size} className="stage-icon verifying" />;
+    case 'customizing':
+      return <Settings2 size={size} className="stage-icon customizing" />;
     case 'complete':
       return <CheckCircle size={size} className="stage-icon complete" />;
     case 'error':
@@ -61,6 +65,8 @@ export function getStageKey(stage: FlashStage): string {
       return 'flash.writing';
     case 'verifying':
       return 'flash.verifying';
+    case 'customizing':
+      return 'flash.customizing';
     case 'complete':
       return 'flash.complete';
     case 'error':
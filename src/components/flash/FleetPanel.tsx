@@ -0,0 +1,155 @@
+import { useState, useEffect } from 'react';
+import { useTranslation } from 'react-i18next';
+import { Layers, Square } from 'lucide-react';
+import type { Customization, FleetStatus, NetworkConfig } from '../../types';
+import { getFleetStatus, onAppEvent, startFleetMode, stopFleetMode } from '../../hooks/useTauri';
+import { FLEET_DEFAULT_MAX_SIZE_GB, FLEET_MAX_SIZE_OPTIONS_GB, TIMING } from '../../config';
+
+interface FleetPanelProps {
+  /** Image just flashed, written again to every inserted device */
+  imagePath: string;
+  customization?: Customization | null;
+  networkConfig?: NetworkConfig | null;
+}
+
+type BusFilter = 'any' | 'usb' | 'sd';
+
+/**
+ * Fleet mode controls shown after a successful flash
+ *
+ * Once started, every removable device inserted afterwards is flashed with
+ * the same image and settings, verified and ejected by the backend. Leaving
+ * the screen stops fleet mode, as the image is cleaned up then.
+ */
+export function FleetPanel({ imagePath, customization, networkConfig }: FleetPanelProps) {
+  const { t } = useTranslation();
+  const [status, setStatus] = useState<FleetStatus | null>(null);
+  const [busFilter, setBusFilter] = useState<BusFilter>('any');
+  const [maxSizeGb, setMaxSizeGb] = useState(FLEET_DEFAULT_MAX_SIZE_GB);
+  const [error, setError] = useState<string | null>(null);
+  const active = status?.active ?? false;
+
+  // Refresh on every fleet change, and while active for job progress
+  useEffect(() => {
+    if (!active) return;
+    let unlisten: (() => void) | null = null;
+    const refresh = () => getFleetStatus().then(setStatus).catch(() => {});
+    onAppEvent((event) => {
+      if (event.kind === 'fleet_changed') refresh();
+    }).then((fn) => (unlisten = fn));
+    const interval = setInterval(refresh, TIMING.FLEET_STATUS_POLL);
+    return () => {
+      clearInterval(interval);
+      unlisten?.();
+    };
+  }, [active]);
+
+  // A running fleet outlives neither this screen nor its image
+  useEffect(() => {
+    if (!active) return;
+    return () => {
+      stopFleetMode().catch(() => {});
+    };
+  }, [active]);
+
+  async function handleStart() {
+    setError(null);
+    try {
+      setStatus(
+        await startFleetMode({
+          image_path: imagePath,
+          verify: true,
+          customization: customization ?? null,
+          network: networkConfig ?? null,
+          criteria: {
+            max_size_bytes: maxSizeGb * 1_000_000_000,
+            bus_types: busFilter === 'any' ? [] : [busFilter],
+          },
+          eject: true,
+        })
+      );
+    } catch (err) {
+      setError(String(err));
+    }
+  }
+
+  async function handleStop() {
+    try {
+      setStatus(await stopFleetMode());
+    } catch (err) {
+      setError(String(err));
+    }
+  }
+
+  if (!active) {
+    return (
+      <div className="fleet-panel">
+        <p className="flash-skipped-hint">{t('fleet.description')}</p>
+        {status && (
+          <p className="flash-skipped-hint">
+            {t('fleet.summary', { completed: status.completed, failed: status.failed })}
+          </p>
+        )}
+        <div className="flash-actions-inline">
+          <select
+            className="settings-select"
+            value={busFilter}
+            onChange={(e) => setBusFilter(e.target.value as BusFilter)}
+          >
+            <option value="any">{t('fleet.anyDevice')}</option>
+            <option value="usb">USB</option>
+            <option value="sd">SD</option>
+          </select>
+          <select
+            className="settings-select"
+            value={maxSizeGb}
+            onChange={(e) => setMaxSizeGb(Number(e.target.value))}
+            aria-label={t('fleet.maxSize')}
+          >
+            {FLEET_MAX_SIZE_OPTIONS_GB.map((size) => (
+              <option key={size} value={size}>
+                {t('fleet.upToSize', { size })}
+              </option>
+            ))}
+          </select>
+          <button className="btn btn-secondary" onClick={handleStart}>
+            <Layers size={16} />
+            {t('fleet.start')}
+          </button>
+        </div>
+        {error && <p className="flash-customize-warning">{error}</p>}
+      </div>
+    );
+  }
+
+  return (
+    <div className="fleet-panel active" role="status">
+      <div className="fleet-banner">
+        <strong>{t('fleet.active')}</strong>
+        <span>{t('fleet.summary', { completed: status?.completed ?? 0, failed: status?.failed ?? 0 })}</span>
+      </div>
+      <p className="flash-skipped-hint">{t('fleet.insertHint')}</p>
+      <ul className="fleet-jobs">
+        {status?.jobs.map((job) => (
+          <li key={job.id} className={`fleet-job ${job.stage}`}>
+            <span>
+              {job.device_name} ({job.device_path})
+            </span>
+            <span>
+              {t(`fleet.stage.${job.stage}`)}
+              {(job.stage === 'flashing' || job.stage === 'verifying') && ` ${job.progress_percent.toFixed(0)}%`}
+            </span>
+            {job.error && <span className="fleet-job-error">{job.error}</span>}
+          </li>
+        ))}
+      </ul>
+      <div className="flash-actions-inline">
+        <button className="btn btn-danger" onClick={handleStop}>
+          <Square size={16} />
+          {t('fleet.stop')}
+        </button>
+      </div>
+      {error && <p className="flash-customize-warning">{error}</p>}
+    </div>
+  );
+}
@@ -0,0 +1,53 @@
+import { useState } from 'react';
+import { useTranslation } from 'react-i18next';
+import { StickyNote } from 'lucide-react';
+import { setOperationNote } from '../../hooks/useTauri';
+import { UI } from '../../config';
+
+interface OperationNoteFieldProps {
+  /** Running or finished operation, null before the write has started */
+  operationId: string | null;
+}
+
+/**
+ * Free-text note on the flash, e.g. "card for greenhouse node 3"
+ *
+ * Saved when the field loses focus. Before the write starts, the backend
+ * holds the note for it; it ends up in the history and its exports.
+ */
+export function OperationNoteField({ operationId }: OperationNoteFieldProps) {
+  const { t } = useTranslation();
+  const [text, setText] = useState('');
+  const [saved, setSaved] = useState('');
+  const [error, setError] = useState<string | null>(null);
+
+  async function save() {
+    if (text === saved) return;
+    setError(null);
+    try {
+      await setOperationNote(operationId, text);
+      setSaved(text);
+    } catch (err) {
+      setError(String(err));
+    }
+  }
+
+  return (
+    <div className="operation-note">
+      <label className="operation-note-input">
+        <StickyNote size={16} />
+        <input
+          type="text"
+          value={text}
+          maxLength={UI.OPERATION_NOTE_MAX_LENGTH}
+          placeholder={t('flash.notePlaceholder')}
+          aria-label={t('flash.note')}
+          onChange={(e) => setText(e.target.value)}
+          onBlur={save}
+          onKeyDown={(e) => e.key === 'Enter' && e.currentTarget.blur()}
+        />
+      </label>
+      {error && <p className="flash-customize-warning">{error}</p>}
+    </div>
+  );
+}
@@ -4,5 +4,7 @@
 
 export { FlashProgress } from './FlashProgress';
 export { FlashActions } from './FlashActions';
+export { FleetPanel } from './FleetPanel';
+export { OperationNoteField } from './OperationNoteField';
 export { FlashStageIcon, getStageKey } from './FlashStageIcon';
 export type { FlashStage } from './FlashStageIcon';
@@ -2,7 +2,7 @@ import { Check } from 'lucide-react';
 import { useTranslation } from 'react-i18next';
 import armbianLogo from '../../assets/armbian-logo.png';
 import type { BoardInfo, ImageInfo, BlockDevice, SelectionStep, Manufacturer } from '../../types';
-import { UpdateModal, MotdTip } from '../shared';
+import { UpdateModal, MotdTip, AdvisoryTip, InterruptedDownloadsModal } from '../shared';
 
 interface HeaderProps {
   selectedManufacturer?: Manufacturer | null;
@@ -55,6 +55,7 @@ export function Header({
   return (
     <>
       <UpdateModal />
+      <InterruptedDownloadsModal />
       <header className="header">
         <div className="header-left">
           <img
@@ -81,6 +82,7 @@ export function Header({
           ))}
         </div>
       </header>
+      <AdvisoryTip />
       <MotdTip />
     </>
   );
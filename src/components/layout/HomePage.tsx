@@ -1,7 +1,8 @@
-import { Factory, Cpu, Database, HardDrive, FolderOpen } from 'lucide-react';
+import { Factory, Cpu, Database, HardDrive, FolderOpen, Settings2, Layers } from 'lucide-react';
 import { useTranslation } from 'react-i18next';
 import type { BoardInfo, ImageInfo, BlockDevice, Manufacturer } from '../../types';
 import { MarqueeText } from '../shared';
+import { ImageDetailsPane } from './ImageDetailsPane';
 
 interface HomePageProps {
   selectedManufacturer: Manufacturer | null;
@@ -13,6 +14,14 @@ interface HomePageProps {
   onChooseImage: () => void;
   onChooseDevice: () => void;
   onChooseCustomImage: () => void;
+  /** Expected SHA256 of a custom image was edited */
+  onCustomShaChange: (sha: string) => void;
+  /** First-boot customization is set up for the next flash */
+  isCustomized: boolean;
+  onCustomize: () => void;
+  /** File name of the image written after the main one, null when none */
+  secondaryImageName: string | null;
+  onChooseSecondaryImage: () => void;
 }
 
 export function HomePage({
@@ -25,6 +34,11 @@ export function HomePage({
   onChooseImage,
   onChooseDevice,
   onChooseCustomImage,
+  onCustomShaChange,
+  isCustomized,
+  onCustomize,
+  secondaryImageName,
+  onChooseSecondaryImage,
 }: HomePageProps) {
   const { t } = useTranslation();
   const isCustomImage = selectedImage?.is_custom;
@@ -121,6 +135,37 @@ export function HomePage({
             <FolderOpen size={16} />
             {isCustomImage ? t('home.changeCustomImage') : t('home.useCustomImage')}
           </button>
+          {isCustomImage && (
+            <input
+              className="home-custom-sha"
+              type="text"
+              spellCheck={false}
+              value={selectedImage?.custom_sha256 ?? ''}
+              placeholder={t('custom.expectedSha')}
+              title={
+                selectedImage?.custom_sha_file
+                  ? t('custom.shaFromFile', { file: selectedImage.custom_sha_file })
+                  : undefined
+              }
+              onChange={(e) => onCustomShaChange(e.target.value)}
+            />
+          )}
+          {isCustomImage && selectedImage?.custom_path && (
+            <ImageDetailsPane imagePath={selectedImage.custom_path} />
+          )}
+        </div>
+      )}
+
+      {selectedImage && (
+        <div className="home-custom-section">
+          <button className="home-custom-button" onClick={onCustomize}>
+            <Settings2 size={16} />
+            {isCustomized ? t('customize.edit') : t('customize.open')}
+          </button>
+          <button className="home-custom-button" onClick={onChooseSecondaryImage}>
+            <Layers size={16} />
+            {secondaryImageName ? t('secondary.edit', { name: secondaryImageName }) : t('secondary.open')}
+          </button>
         </div>
       )}
     </div>
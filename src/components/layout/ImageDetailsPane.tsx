@@ -0,0 +1,79 @@
+import { useState, useEffect } from 'react';
+import { useTranslation } from 'react-i18next';
+import type { ImageDetails } from '../../types';
+import { inspectImage } from '../../hooks/useTauri';
+import { formatFileSize } from '../../utils';
+
+interface ImageDetailsPaneProps {
+  /** Path of the custom image to inspect */
+  imagePath: string;
+}
+
+/**
+ * Partition map, filesystems, version and sizes of a custom image
+ *
+ * Shown before flashing so the image can be checked at a glance; the image
+ * is inspected again whenever another file is chosen.
+ */
+export function ImageDetailsPane({ imagePath }: ImageDetailsPaneProps) {
+  const { t } = useTranslation();
+  const [details, setDetails] = useState<ImageDetails | null>(null);
+  const [failed, setFailed] = useState<boolean>(false);
+
+  useEffect(() => {
+    let cancelled = false;
+    setDetails(null);
+    setFailed(false);
+    inspectImage(imagePath)
+      .then((result) => {
+        if (!cancelled) setDetails(result);
+      })
+      .catch((error) => {
+        console.error('Failed to inspect image:', error);
+        if (!cancelled) setFailed(true);
+      });
+    return () => {
+      cancelled = true;
+    };
+  }, [imagePath]);
+
+  if (failed) {
+    return <div className="image-details">{t('inspect.failed')}</div>;
+  }
+  if (!details) {
+    return <div className="image-details">{t('inspect.loading')}</div>;
+  }
+
+  const release = [details.armbian_version, details.distro_release, details.kernel_branch]
+    .filter(Boolean)
+    .join(' · ');
+
+  return (
+    <div className="image-details">
+      {release && <div className="image-details-release">Armbian {release}</div>}
+      <div>
+        {details.compressed
+          ? t('inspect.compressedSizes', {
+              file: formatFileSize(details.file_size),
+              image: details.image_size ? formatFileSize(details.image_size) : t('inspect.unknownSize'),
+            })
+          : t('inspect.rawSize', { size: formatFileSize(details.file_size) })}
+      </div>
+      <div>
+        {details.partition_table
+          ? t('inspect.partitionTable', { scheme: details.partition_table })
+          : t('inspect.noPartitionTable')}
+      </div>
+      {details.partitions.length > 0 && (
+        <ul className="image-details-partitions">
+          {details.partitions.map((p) => (
+            <li key={p.number}>
+              {t('inspect.partition', { number: p.number })}: {p.filesystem ?? t('inspect.unknownFilesystem')}
+              {p.label && ` "${p.label}"`} · {formatFileSize(p.end - p.start, '0 MB', true)}
+            </li>
+          ))}
+        </ul>
+      )}
+    </div>
+  );
+}
@@ -4,3 +4,4 @@
 
 export { Header } from './Header';
 export { HomePage } from './HomePage';
+export { ImageDetailsPane } from './ImageDetailsPane';
@@ -0,0 +1,127 @@
+import { useState, useEffect, useRef } from 'react';
+import { useTranslation } from 'react-i18next';
+import { X } from 'lucide-react';
+import type { UnlistenFn } from '@tauri-apps/api/event';
+import type { BlockDevice, BackupSummary } from '../../types';
+import { backupDevice, cancelOperation, onFlashProgress, requestWriteAuthorization } from '../../hooks/useTauri';
+import { formatFileSize } from '../../utils';
+import { getDeviceDisplayName } from '../../utils/deviceUtils';
+
+interface BackupModalProps {
+  /** Device to back up, null when the modal is closed */
+  device: BlockDevice | null;
+  onClose: () => void;
+}
+
+type BackupStage = 'running' | 'complete' | 'error';
+
+/**
+ * Modal that reads a device into a compressed image file
+ *
+ * The file is chosen in a save dialog when the backup starts; closing the
+ * dialog without a file closes the modal.
+ */
+export function BackupModal({ device, onClose }: BackupModalProps) {
+  const { t } = useTranslation();
+  const [stage, setStage] = useState<BackupStage>('running');
+  const [progress, setProgress] = useState(0);
+  const [summary, setSummary] = useState<BackupSummary | null>(null);
+  const [error, setError] = useState<string | null>(null);
+  const unlistenRef = useRef<UnlistenFn | null>(null);
+
+  useEffect(() => {
+    if (!device) return;
+    let cancelled = false;
+
+    async function run(target: BlockDevice) {
+      setStage('running');
+      setProgress(0);
+      setSummary(null);
+      setError(null);
+      try {
+        unlistenRef.current = await onFlashProgress((prog) => setProgress(prog.progress_percent));
+        if (!(await requestWriteAuthorization(target.path))) {
+          throw new Error(t('error.authCancelled'));
+        }
+        const result = await backupDevice(target.path);
+        if (cancelled) return;
+        if (!result) {
+          onClose();
+          return;
+        }
+        setSummary(result);
+        setStage('complete');
+      } catch (err) {
+        if (cancelled) return;
+        setError(err instanceof Error ? err.message : String(err));
+        setStage('error');
+      } finally {
+        unlistenRef.current?.();
+        unlistenRef.current = null;
+      }
+    }
+
+    run(device);
+    return () => {
+      cancelled = true;
+      unlistenRef.current?.();
+      unlistenRef.current = null;
+    };
+    // eslint-disable-next-line react-hooks/exhaustive-deps -- restart only for a different device
+  }, [device]);
+
+  if (!device) return null;
+
+  const handleClose = () => {
+    if (stage === 'running') {
+      cancelOperation().catch(() => {});
+      return;
+    }
+    onClose();
+  };
+
+  return (
+    <div className="modal-overlay">
+      <div className="modal modal-content backup-modal" onClick={(e) => e.stopPropagation()}>
+        <div className="modal-header">
+          <h2>{t('backup.title')}</h2>
+          {stage !== 'running' && (
+            <button className="modal-close" onClick={onClose} aria-label="Close">
+              <X size={20} />
+            </button>
+          )}
+        </div>
+
+        <div className="modal-body">
+          <p className="backup-device">
+            <strong>{getDeviceDisplayName(device)}</strong> ({device.size_formatted})
+          </p>
+
+          {stage === 'running' && (
+            <div className="progress-container">
+              <div className="progress-bar">
+                <div className="progress-fill" style={{ width: `${progress}%` }} />
+              </div>
+              <span className="progress-text">{progress.toFixed(0)}%</span>
+            </div>
+          )}
+          {stage === 'complete' && summary && (
+            <p className="backup-result">
+              {t('backup.complete', {
+                path: summary.path,
+                size: formatFileSize(summary.compressed_bytes, t('common.unknown'), true),
+              })}
+            </p>
+          )}
+          {stage === 'error' && <div className="logs-error">{t('backup.failed', { error })}</div>}
+
+          <div className="flash-actions-inline">
+            <button className={`btn ${stage === 'running' ? 'btn-secondary' : 'btn-primary'}`} onClick={handleClose}>
+              {stage === 'running' ? t('flash.cancel') : t('flash.done')}
+            </button>
+          </div>
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -4,7 +4,7 @@ import { useTranslation } from 'react-i18next';
 import { Modal } from './Modal';
 import { ErrorDisplay, BoardCardSkeleton, SearchBox } from '../shared';
 import type { BoardInfo, Manufacturer } from '../../types';
-import { getBoards, getBoardImageUrl } from '../../hooks/useTauri';
+import { getBoards, getBoardImageUrl, onBoardImageReady } from '../../hooks/useTauri';
 import { useAsyncDataWhen } from '../../hooks/useAsyncData';
 import { useVendorLogos } from '../../hooks/useVendorLogos';
 import { compareBoardsBySupport, preloadImage } from '../../utils';
@@ -99,6 +99,31 @@ export function BoardModal({ isOpen, onClose, onSelect, manufacturer }: BoardMod
     loadImages();
   }, [isOpen, manufacturer?.id, boards, vendorLogosChecked, getEffectiveVendor]);
 
+  // Swap in photos as their background downloads land
+  useEffect(() => {
+    if (!isOpen) return;
+
+    let cancelled = false;
+    let unlisten: (() => void) | null = null;
+
+    onBoardImageReady((slug, url) => {
+      // Only boards of the current manufacturer are shown
+      if (!loadedSlugsRef.current.has(slug)) return;
+      setBoardImages((prev) => ({ ...prev, [slug]: url }));
+    }).then((fn) => {
+      if (cancelled) {
+        fn();
+      } else {
+        unlisten = fn;
+      }
+    });
+
+    return () => {
+      cancelled = true;
+      unlisten?.();
+    };
+  }, [isOpen]);
+
   const filteredBoards = useMemo(() => {
     if (!manufacturer || !boards || !vendorLogosChecked) return [];
     const searchLower = search.toLowerCase();
@@ -165,37 +190,37 @@ export function BoardModal({ isOpen, onClose, onSelect, manufacturer }: BoardMod
                 <div className="board-grid-badges">
                   {boardsReady ? (
                     <>
-                      {board.has_platinum_support && (
+                      {board.support_level?.level === 'platinum' && (
                         <span className="badge-platinum">
                           <Crown size={10} />
                           <span>Platinum</span>
                         </span>
                       )}
-                      {board.has_standard_support && !board.has_platinum_support && (
+                      {board.support_level?.level === 'standard' && (
                         <span className="badge-standard">
                           <Shield size={10} />
                           <span>Standard</span>
                         </span>
                       )}
-                      {board.has_community_support && (
+                      {board.support_level?.level === 'community' && (
                         <span className="badge-community">
                           <Users size={10} />
                           <span>Community</span>
                         </span>
                       )}
-                      {board.has_eos_support && (
+                      {board.support_level?.level === 'eos' && (
                         <span className="badge-eos">
                           <Clock size={10} />
                           <span>EOS</span>
                         </span>
                       )}
-                      {board.has_tvb_support && (
+                      {board.support_level?.level === 'tvb' && (
                         <span className="badge-tvb">
                           <Tv size={10} />
                           <span>TV Box</span>
                         </span>
                       )}
-                      {board.has_wip_support && (
+                      {board.support_level?.level === 'wip' && (
                         <span className="badge-wip">
                           <Wrench size={10} />
                           <span>WIP</span>
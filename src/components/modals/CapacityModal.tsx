@@ -0,0 +1,143 @@
+import { useState, useEffect, useRef } from 'react';
+import { useTranslation } from 'react-i18next';
+import { X } from 'lucide-react';
+import type { UnlistenFn } from '@tauri-apps/api/event';
+import type { BlockDevice, CapacityReport } from '../../types';
+import {
+  acceptWriteWarning,
+  cancelOperation,
+  checkDeviceCapacity,
+  confirmDangerousTarget,
+  onFlashProgress,
+  requestWriteAuthorization,
+} from '../../hooks/useTauri';
+import { getDeviceDisplayName } from '../../utils/deviceUtils';
+import { formatFileSize } from '../../utils';
+
+interface CapacityModalProps {
+  /** Device to check, null when the modal is closed */
+  device: BlockDevice | null;
+  onClose: () => void;
+}
+
+type CapacityStage = 'confirm' | 'running' | 'complete' | 'error';
+
+/**
+ * Modal that checks a device for fake capacity, as sold on counterfeit cards
+ *
+ * Markers are written across the device and read back; the blocks they
+ * replace are restored afterwards, so the data on the device survives.
+ */
+export function CapacityModal({ device, onClose }: CapacityModalProps) {
+  const { t } = useTranslation();
+  const [stage, setStage] = useState<CapacityStage>('confirm');
+  const [progress, setProgress] = useState(0);
+  const [report, setReport] = useState<CapacityReport | null>(null);
+  const [error, setError] = useState<string | null>(null);
+  const unlistenRef = useRef<UnlistenFn | null>(null);
+
+  useEffect(() => {
+    if (!device) return;
+    setStage('confirm');
+    setReport(null);
+    setError(null);
+    return () => {
+      unlistenRef.current?.();
+      unlistenRef.current = null;
+    };
+  }, [device]);
+
+  if (!device) return null;
+
+  async function handleCheck(target: BlockDevice) {
+    setStage('running');
+    setProgress(0);
+    try {
+      // The backend refuses to write until this acknowledgment is stored
+      await acceptWriteWarning();
+      const confirmationToken = target.is_removable
+        ? undefined
+        : (await confirmDangerousTarget(target.path)).token;
+      unlistenRef.current = await onFlashProgress((prog) => setProgress(prog.progress_percent));
+      if (!(await requestWriteAuthorization(target.path))) {
+        throw new Error(t('error.authCancelled'));
+      }
+      setReport(await checkDeviceCapacity(target.path, confirmationToken));
+      setStage('complete');
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+      setStage('error');
+    } finally {
+      unlistenRef.current?.();
+      unlistenRef.current = null;
+    }
+  }
+
+  const handleClose = () => {
+    if (stage === 'running') {
+      cancelOperation().catch(() => {});
+      return;
+    }
+    onClose();
+  };
+
+  return (
+    <div className="modal-overlay">
+      <div className="modal modal-content capacity-modal" onClick={(e) => e.stopPropagation()}>
+        <div className="modal-header">
+          <h2>{t('capacity.title')}</h2>
+          {stage !== 'running' && (
+            <button className="modal-close" onClick={onClose} aria-label="Close">
+              <X size={20} />
+            </button>
+          )}
+        </div>
+
+        <div className="modal-body">
+          <p className="backup-device">
+            <strong>{getDeviceDisplayName(device)}</strong> ({device.size_formatted})
+          </p>
+
+          {stage === 'confirm' && <p className="backup-result">{t('capacity.description')}</p>}
+          {stage === 'running' && (
+            <div className="progress-container">
+              <div className="progress-bar">
+                <div className="progress-fill" style={{ width: `${progress}%` }} />
+              </div>
+              <span className="progress-text">{progress.toFixed(0)}%</span>
+            </div>
+          )}
+          {stage === 'complete' && report?.genuine && (
+            <p className="backup-result">{t('capacity.genuine', { samples: report.samples })}</p>
+          )}
+          {stage === 'complete' && report && !report.genuine && (
+            <div className="logs-error">
+              {t('capacity.fake', {
+                reported: formatFileSize(report.reported_bytes),
+                usable: formatFileSize(report.usable_bytes, '0 MB'),
+              })}
+            </div>
+          )}
+          {stage === 'error' && <div className="logs-error">{t('capacity.failed', { error })}</div>}
+
+          <div className="flash-actions-inline">
+            {stage === 'confirm' ? (
+              <>
+                <button className="btn btn-secondary" onClick={onClose}>
+                  {t('common.cancel')}
+                </button>
+                <button className="btn btn-primary" onClick={() => handleCheck(device)}>
+                  {t('capacity.start')}
+                </button>
+              </>
+            ) : (
+              <button className={`btn ${stage === 'running' ? 'btn-secondary' : 'btn-primary'}`} onClick={handleClose}>
+                {stage === 'running' ? t('flash.cancel') : t('flash.done')}
+              </button>
+            )}
+          </div>
+        </div>
+      </div>
+    </div>
+  );
+}
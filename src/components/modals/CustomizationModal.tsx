@@ -0,0 +1,171 @@
+import { useState, useEffect } from 'react';
+import { useTranslation } from 'react-i18next';
+import { X } from 'lucide-react';
+import type { Customization, NetworkConfig } from '../../types';
+
+interface CustomizationModalProps {
+  isOpen: boolean;
+  /** Current settings, null when customization is off */
+  customization: Customization | null;
+  /** Current Wi-Fi network, null when none is set */
+  networkConfig: NetworkConfig | null;
+  onClose: () => void;
+  /** Called with the new settings, or nulls to turn customization off */
+  onSave: (customization: Customization | null, networkConfig: NetworkConfig | null) => void;
+}
+
+// Same rules as the backend, checked here so a typo doesn't surface after flashing
+const HOSTNAME_PATTERN = /^[A-Za-z0-9]([A-Za-z0-9-]{0,61}[A-Za-z0-9])?$/;
+const USER_NAME_PATTERN = /^[a-z_][a-z0-9_-]{0,31}$/;
+const WIFI_PSK_PATTERN = /^([ -~]{8,63}|[0-9A-Fa-f]{64})$/;
+const COUNTRY_CODE_PATTERN = /^[A-Z]{2}$/;
+
+/**
+ * Modal for first-boot customization: host name, default user, SSH and Wi-Fi
+ *
+ * The settings are applied to the device right after a successful flash.
+ */
+export function CustomizationModal({ isOpen, customization, networkConfig, onClose, onSave }: CustomizationModalProps) {
+  const { t } = useTranslation();
+  const [hostname, setHostname] = useState<string>('');
+  const [userName, setUserName] = useState<string>('');
+  const [password, setPassword] = useState<string>('');
+  const [sshEnabled, setSshEnabled] = useState<boolean>(true);
+  const [wifiSsid, setWifiSsid] = useState<string>('');
+  const [wifiPsk, setWifiPsk] = useState<string>('');
+  const [countryCode, setCountryCode] = useState<string>('');
+
+  // Start from the saved settings each time the modal opens
+  useEffect(() => {
+    if (isOpen) {
+      setHostname(customization?.hostname ?? '');
+      setUserName(customization?.user_name ?? '');
+      setPassword(customization?.password ?? '');
+      setSshEnabled(customization?.ssh_enabled ?? true);
+      setWifiSsid(networkConfig?.wifi_ssid ?? '');
+      setWifiPsk(networkConfig?.wifi_psk ?? '');
+      setCountryCode(networkConfig?.country_code ?? '');
+    }
+  }, [isOpen, customization, networkConfig]);
+
+  if (!isOpen) return null;
+
+  const hostnameValid = hostname === '' || HOSTNAME_PATTERN.test(hostname);
+  const userNameValid = userName === '' || (USER_NAME_PATTERN.test(userName) && userName !== 'root');
+  const passwordMissing = userName !== '' && password === '';
+  const ssidValid = new TextEncoder().encode(wifiSsid).length <= 32;
+  const pskValid = wifiPsk === '' || WIFI_PSK_PATTERN.test(wifiPsk);
+  const countryValid = countryCode === '' || COUNTRY_CODE_PATTERN.test(countryCode);
+  const canSave = hostnameValid && userNameValid && !passwordMissing && ssidValid && pskValid && countryValid;
+
+  const handleSave = () => {
+    onSave(
+      {
+        hostname: hostname || null,
+        user_name: userName || null,
+        password: password || null,
+        ssh_enabled: sshEnabled,
+      },
+      wifiSsid
+        ? { wifi_ssid: wifiSsid, wifi_psk: wifiPsk || null, country_code: countryCode || null }
+        : null
+    );
+  };
+
+  return (
+    <div className="modal-overlay" onClick={onClose}>
+      <div className="modal modal-content customize-modal" onClick={(e) => e.stopPropagation()}>
+        <div className="modal-header">
+          <h2>{t('customize.title')}</h2>
+          <button className="modal-close" onClick={onClose} aria-label="Close">
+            <X size={20} />
+          </button>
+        </div>
+
+        <div className="modal-body">
+          <p className="customize-hint">{t('customize.hint')}</p>
+          <div className="customize-form">
+            <input
+              className="settings-input"
+              type="text"
+              placeholder={t('customize.hostname')}
+              value={hostname}
+              onChange={(e) => setHostname(e.target.value.trim())}
+              spellCheck={false}
+            />
+            {!hostnameValid && <div className="logs-error">{t('customize.invalidHostname')}</div>}
+            <input
+              className="settings-input"
+              type="text"
+              placeholder={t('customize.userName')}
+              value={userName}
+              onChange={(e) => setUserName(e.target.value.trim())}
+              autoComplete="off"
+              spellCheck={false}
+            />
+            {!userNameValid && <div className="logs-error">{t('customize.invalidUserName')}</div>}
+            <input
+              className="settings-input"
+              type="password"
+              placeholder={t('customize.password')}
+              value={password}
+              onChange={(e) => setPassword(e.target.value)}
+              autoComplete="new-password"
+            />
+            {passwordMissing && <div className="logs-error">{t('customize.passwordRequired')}</div>}
+            <label className="customize-checkbox">
+              <input
+                type="checkbox"
+                checked={sshEnabled}
+                onChange={(e) => setSshEnabled(e.target.checked)}
+              />
+              {t('customize.enableSsh')}
+            </label>
+            <input
+              className="settings-input"
+              type="text"
+              placeholder={t('customize.wifiSsid')}
+              value={wifiSsid}
+              onChange={(e) => setWifiSsid(e.target.value)}
+              autoComplete="off"
+              spellCheck={false}
+            />
+            {!ssidValid && <div className="logs-error">{t('customize.invalidWifiSsid')}</div>}
+            {wifiSsid !== '' && (
+              <>
+                <input
+                  className="settings-input"
+                  type="password"
+                  placeholder={t('customize.wifiPassword')}
+                  value={wifiPsk}
+                  onChange={(e) => setWifiPsk(e.target.value)}
+                  autoComplete="new-password"
+                />
+                {!pskValid && <div className="logs-error">{t('customize.invalidWifiPassword')}</div>}
+                <input
+                  className="settings-input"
+                  type="text"
+                  placeholder={t('customize.wifiCountry')}
+                  value={countryCode}
+                  onChange={(e) => setCountryCode(e.target.value.trim().toUpperCase())}
+                  maxLength={2}
+                  spellCheck={false}
+                />
+                {!countryValid && <div className="logs-error">{t('customize.invalidWifiCountry')}</div>}
+              </>
+            )}
+          </div>
+
+          <div className="flash-actions-inline">
+            <button className="btn btn-secondary" onClick={() => onSave(null, null)}>
+              {t('customize.disable')}
+            </button>
+            <button className="btn btn-primary" onClick={handleSave} disabled={!canSave}>
+              {t('customize.save')}
+            </button>
+          </div>
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -1,14 +1,18 @@
-import { useState, useEffect, useRef, useCallback, useMemo } from 'react';
+import { useState, useEffect, useRef, useMemo } from 'react';
 import { HardDrive, RefreshCw, AlertTriangle, Shield, MemoryStick, Usb } from 'lucide-react';
 import { useTranslation } from 'react-i18next';
 import { Modal } from './Modal';
+import { BackupModal } from './BackupModal';
+import { VerifyModal } from './VerifyModal';
+import { EraseModal } from './EraseModal';
+import { CapacityModal } from './CapacityModal';
 import { ErrorDisplay, ConfirmationDialog, ListItemSkeleton } from '../shared';
 import type { BlockDevice } from '../../types';
-import { getBlockDevices } from '../../hooks/useTauri';
+import { getBlockDevices, onDevicesChanged, acceptWriteWarning, confirmDangerousTarget, setDeviceAlias } from '../../hooks/useTauri';
 import { useAsyncDataWhen } from '../../hooks/useAsyncData';
-import { POLLING, UI, type DeviceType } from '../../config';
+import { UI, type DeviceType } from '../../config';
 import { getDeviceColors } from '../../config/deviceColors';
-import { getDeviceType } from '../../utils/deviceUtils';
+import { getDeviceDisplayName, getDeviceType, hasSlowUsbLink } from '../../utils/deviceUtils';
 
 /** Get icon component for device type */
 function DeviceIcon({ type, size = 24 }: { type: DeviceType; size?: number }) {
@@ -67,7 +71,8 @@ function sortDevices(devices: BlockDevice[]): BlockDevice[] {
 interface DeviceModalProps {
   isOpen: boolean;
   onClose: () => void;
-  onSelect: (device: BlockDevice) => void;
+  /** Called with the confirmation token when a fixed disk was confirmed */
+  onSelect: (device: BlockDevice, confirmationToken?: string) => void;
 }
 
 export function DeviceModal({ isOpen, onClose, onSelect }: DeviceModalProps) {
@@ -76,6 +81,11 @@ export function DeviceModal({ isOpen, onClose, onSelect }: DeviceModalProps) {
   const [showConfirm, setShowConfirm] = useState(false);
   const [showSkeleton, setShowSkeleton] = useState(false);
   const [showSystemDevices, setShowSystemDevices] = useState(false);
+  const [backupTarget, setBackupTarget] = useState<BlockDevice | null>(null);
+  const [verifyTarget, setVerifyTarget] = useState<BlockDevice | null>(null);
+  const [eraseTarget, setEraseTarget] = useState<BlockDevice | null>(null);
+  const [capacityTarget, setCapacityTarget] = useState<BlockDevice | null>(null);
+  const [aliasDraft, setAliasDraft] = useState<string | null>(null);
 
   // Track previous devices for change detection
   const prevDevicesRef = useRef<BlockDevice[] | null>(null);
@@ -119,7 +129,7 @@ export function DeviceModal({ isOpen, onClose, onSelect }: DeviceModalProps) {
         clearTimeout(skeletonTimeout);
       }
     };
-    // eslint-disable-next-line react-hooks/exhaustive-deps -- devicesReady already tracks devices changes; adding devices.length causes re-renders during polling
+    // eslint-disable-next-line react-hooks/exhaustive-deps -- devicesReady already tracks devices changes; adding devices.length causes re-renders on device updates
   }, [loading, devicesReady]);
 
   // Update devices only when they actually change
@@ -131,36 +141,66 @@ export function DeviceModal({ isOpen, onClose, onSelect }: DeviceModalProps) {
     }
   }, [rawDevices]);
 
-  // Poll for device changes while modal is open
-  const pollDevices = useCallback(async () => {
-    try {
-      const newDevices = await getBlockDevices();
+  // Refresh devices while modal is open (new USB/SD insertions are pushed by the backend)
+  useEffect(() => {
+    if (!isOpen || showConfirm) return;
+
+    let cancelled = false;
+    let unlisten: (() => void) | null = null;
+
+    onDevicesChanged((newDevices) => {
       if (devicesChanged(prevDevicesRef.current, newDevices)) {
         prevDevicesRef.current = newDevices;
         setDevices(sortDevices(newDevices));
       }
-    } catch {
-      // Silently ignore polling errors
-    }
-  }, []);
-
-  // Auto-refresh devices while modal is open (detect new USB/SD insertions)
-  useEffect(() => {
-    if (!isOpen || showConfirm) return;
+    }).then((fn) => {
+      if (cancelled) {
+        fn();
+      } else {
+        unlisten = fn;
+      }
+    });
 
-    const interval = setInterval(pollDevices, POLLING.DEVICE_CHECK);
-    return () => clearInterval(interval);
-  }, [isOpen, showConfirm, pollDevices]);
+    return () => {
+      cancelled = true;
+      unlisten?.();
+    };
+  }, [isOpen, showConfirm]);
 
   function handleDeviceClick(device: BlockDevice) {
     if (device.is_system) return;
     setSelectedDevice(device);
+    setAliasDraft(null);
     setShowConfirm(true);
   }
 
-  function handleConfirm() {
+  async function handleSaveAlias() {
+    if (!selectedDevice?.serial || aliasDraft === null) return;
+    try {
+      await setDeviceAlias(selectedDevice.serial, aliasDraft);
+      setSelectedDevice({ ...selectedDevice, alias: aliasDraft.trim() || null });
+      setAliasDraft(null);
+    } catch (err) {
+      console.error('Failed to save device alias:', err);
+    }
+  }
+
+  async function handleConfirm() {
     if (selectedDevice && !selectedDevice.is_system) {
-      onSelect(selectedDevice);
+      // The backend refuses to flash until this acknowledgment is stored
+      await acceptWriteWarning().catch((err) => {
+        console.error('Failed to record write warning acknowledgment:', err);
+      });
+      // Fixed disks are only written with a backend-issued confirmation
+      let confirmationToken: string | undefined;
+      if (!selectedDevice.is_removable) {
+        try {
+          confirmationToken = (await confirmDangerousTarget(selectedDevice.path)).token;
+        } catch (err) {
+          console.error('Failed to confirm fixed disk:', err);
+        }
+      }
+      onSelect(selectedDevice, confirmationToken);
       setShowConfirm(false);
     }
   }
@@ -229,7 +269,7 @@ export function DeviceModal({ isOpen, onClose, onSelect }: DeviceModalProps) {
                     </div>
                     <div className="list-item-content">
                       <div className="list-item-title">
-                        {device.model || device.name}
+                        {getDeviceDisplayName(device)}
                         {badge && (
                           <span className={`${deviceType}-badge`} style={{ marginLeft: 8 }}>
                             {badge}
@@ -237,7 +277,12 @@ export function DeviceModal({ isOpen, onClose, onSelect }: DeviceModalProps) {
                         )}
                       </div>
                       <div className="list-item-subtitle">
-                        {device.name} • {device.size_formatted}
+                        {device.alias && `${device.model} • `}{device.name} • {device.size_formatted}
+                        {hasSlowUsbLink(device) && (
+                          <span className="device-slow-link" title={t('device.slowLinkHint')}>
+                            {' • '}{t('device.slowLink')}
+                          </span>
+                        )}
                       </div>
                     </div>
                   </button>
@@ -258,7 +303,7 @@ export function DeviceModal({ isOpen, onClose, onSelect }: DeviceModalProps) {
 
       {/* Confirmation Dialog */}
       <ConfirmationDialog
-        isOpen={showConfirm && !!selectedDevice && !selectedDevice.is_system}
+        isOpen={showConfirm && !backupTarget && !verifyTarget && !eraseTarget && !capacityTarget && !!selectedDevice && !selectedDevice.is_system}
         title={t('flash.confirmTitle')}
         message={t('flash.confirmText')}
         warning={t('flash.confirmWarning')}
@@ -268,11 +313,52 @@ export function DeviceModal({ isOpen, onClose, onSelect }: DeviceModalProps) {
       >
         {selectedDevice && (
           <div className="confirm-device">
-            <strong>{selectedDevice.model || selectedDevice.name}</strong>
-            <span>{selectedDevice.name} ({selectedDevice.size_formatted})</span>
+            <strong>{getDeviceDisplayName(selectedDevice)}</strong>
+            <span>
+              {selectedDevice.alias && `${selectedDevice.model} • `}
+              {selectedDevice.name} ({selectedDevice.size_formatted})
+            </span>
+            {selectedDevice.serial && aliasDraft === null && (
+              <button className="confirm-backup-link" onClick={() => setAliasDraft(selectedDevice.alias ?? '')}>
+                {t(selectedDevice.alias ? 'device.renameAlias' : 'device.addAlias')}
+              </button>
+            )}
+            {aliasDraft !== null && (
+              <input
+                className="settings-input"
+                type="text"
+                autoFocus
+                maxLength={UI.DEVICE_ALIAS_MAX_LENGTH}
+                placeholder={t('device.aliasPlaceholder')}
+                value={aliasDraft}
+                onChange={(e) => setAliasDraft(e.target.value)}
+                onBlur={handleSaveAlias}
+                onKeyDown={(e) => {
+                  if (e.key === 'Enter') handleSaveAlias();
+                  if (e.key === 'Escape') setAliasDraft(null);
+                }}
+              />
+            )}
+            <button className="confirm-backup-link" onClick={() => setBackupTarget(selectedDevice)}>
+              {t('backup.backUpFirst')}
+            </button>
+            <button className="confirm-backup-link" onClick={() => setVerifyTarget(selectedDevice)}>
+              {t('verifyOnly.verifyInstead')}
+            </button>
+            <button className="confirm-backup-link" onClick={() => setEraseTarget(selectedDevice)}>
+              {t('erase.eraseInstead')}
+            </button>
+            <button className="confirm-backup-link" onClick={() => setCapacityTarget(selectedDevice)}>
+              {t('capacity.checkInstead')}
+            </button>
           </div>
         )}
       </ConfirmationDialog>
+
+      <BackupModal device={backupTarget} onClose={() => setBackupTarget(null)} />
+      <VerifyModal device={verifyTarget} onClose={() => setVerifyTarget(null)} />
+      <EraseModal device={eraseTarget} onClose={() => setEraseTarget(null)} />
+      <CapacityModal device={capacityTarget} onClose={() => setCapacityTarget(null)} />
     </>
   );
 }
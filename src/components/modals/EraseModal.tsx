@@ -0,0 +1,151 @@
+import { useState, useEffect, useRef } from 'react';
+import { useTranslation } from 'react-i18next';
+import { X } from 'lucide-react';
+import type { UnlistenFn } from '@tauri-apps/api/event';
+import type { BlockDevice, EraseFilesystem } from '../../types';
+import {
+  acceptWriteWarning,
+  cancelOperation,
+  confirmDangerousTarget,
+  eraseDevice,
+  onFlashProgress,
+  requestWriteAuthorization,
+} from '../../hooks/useTauri';
+import { getDeviceDisplayName } from '../../utils/deviceUtils';
+
+interface EraseModalProps {
+  /** Device to erase, null when the modal is closed */
+  device: BlockDevice | null;
+  onClose: () => void;
+}
+
+type EraseStage = 'choose' | 'running' | 'complete' | 'error';
+
+/**
+ * Modal that erases a device to one empty FAT32 or exFAT partition
+ */
+export function EraseModal({ device, onClose }: EraseModalProps) {
+  const { t } = useTranslation();
+  const [stage, setStage] = useState<EraseStage>('choose');
+  const [filesystem, setFilesystem] = useState<EraseFilesystem | 'auto'>('auto');
+  const [progress, setProgress] = useState(0);
+  const [formatted, setFormatted] = useState<string | null>(null);
+  const [error, setError] = useState<string | null>(null);
+  const unlistenRef = useRef<UnlistenFn | null>(null);
+
+  useEffect(() => {
+    if (!device) return;
+    setStage('choose');
+    setFilesystem('auto');
+    setFormatted(null);
+    setError(null);
+    return () => {
+      unlistenRef.current?.();
+      unlistenRef.current = null;
+    };
+  }, [device]);
+
+  if (!device) return null;
+
+  async function handleErase(target: BlockDevice) {
+    setStage('running');
+    setProgress(0);
+    try {
+      // The backend refuses to write until this acknowledgment is stored
+      await acceptWriteWarning();
+      const confirmationToken = target.is_removable
+        ? undefined
+        : (await confirmDangerousTarget(target.path)).token;
+      unlistenRef.current = await onFlashProgress((prog) => setProgress(prog.progress_percent));
+      if (!(await requestWriteAuthorization(target.path))) {
+        throw new Error(t('error.authCancelled'));
+      }
+      const name = await eraseDevice(
+        target.path,
+        filesystem === 'auto' ? undefined : filesystem,
+        confirmationToken
+      );
+      setFormatted(name);
+      setStage('complete');
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+      setStage('error');
+    } finally {
+      unlistenRef.current?.();
+      unlistenRef.current = null;
+    }
+  }
+
+  const handleClose = () => {
+    if (stage === 'running') {
+      cancelOperation().catch(() => {});
+      return;
+    }
+    onClose();
+  };
+
+  return (
+    <div className="modal-overlay">
+      <div className="modal modal-content erase-modal" onClick={(e) => e.stopPropagation()}>
+        <div className="modal-header">
+          <h2>{t('erase.title')}</h2>
+          {stage !== 'running' && (
+            <button className="modal-close" onClick={onClose} aria-label="Close">
+              <X size={20} />
+            </button>
+          )}
+        </div>
+
+        <div className="modal-body">
+          <p className="backup-device">
+            <strong>{getDeviceDisplayName(device)}</strong> ({device.size_formatted})
+          </p>
+
+          {stage === 'choose' && (
+            <>
+              <p className="backup-result">{t('erase.description')}</p>
+              <select
+                className="settings-select"
+                value={filesystem}
+                onChange={(e) => setFilesystem(e.target.value as EraseFilesystem | 'auto')}
+              >
+                <option value="auto">{t('erase.filesystemAuto')}</option>
+                <option value="fat32">FAT32</option>
+                <option value="exfat">exFAT</option>
+              </select>
+            </>
+          )}
+          {stage === 'running' && (
+            <div className="progress-container">
+              <div className="progress-bar">
+                <div className="progress-fill" style={{ width: `${progress}%` }} />
+              </div>
+              <span className="progress-text">{progress.toFixed(0)}%</span>
+            </div>
+          )}
+          {stage === 'complete' && (
+            <p className="backup-result">{t('erase.complete', { filesystem: formatted })}</p>
+          )}
+          {stage === 'error' && <div className="logs-error">{t('erase.failed', { error })}</div>}
+
+          <div className="flash-actions-inline">
+            {stage === 'choose' ? (
+              <>
+                <button className="btn btn-secondary" onClick={onClose}>
+                  {t('common.cancel')}
+                </button>
+                <button className="btn btn-primary" onClick={() => handleErase(device)}>
+                  {t('erase.confirm')}
+                </button>
+              </>
+            ) : (
+              <button className={`btn ${stage === 'running' ? 'btn-secondary' : 'btn-primary'}`} onClick={handleClose}>
+                {stage === 'running' ? t('flash.cancel') : t('flash.done')}
+              </button>
+            )}
+          </div>
+        </div>
+      </div>
+    </div>
+  );
+}
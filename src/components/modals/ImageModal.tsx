@@ -1,5 +1,5 @@
 import { useState, useMemo, useEffect } from 'react';
-import { Download, Package, Monitor, Terminal, Zap, Star, Layers, Shield, FlaskConical, AppWindow, Box } from 'lucide-react';
+import { Download, Package, Monitor, Terminal, Zap, Star, Layers, Shield, FlaskConical, AppWindow, Box, Library } from 'lucide-react';
 import { useTranslation } from 'react-i18next';
 import { Modal } from './Modal';
 import { ErrorDisplay, ListItemSkeleton, ConfirmationDialog } from '../shared';
@@ -15,6 +15,7 @@ import {
   KERNEL_BADGES,
   DESKTOP_ENVIRONMENTS,
   UI,
+  CATALOG,
 } from '../../config';
 import { formatFileSize, DEFAULT_COLOR } from '../../utils';
 
@@ -127,7 +128,7 @@ export function ImageModal({ isOpen, onClose, onSelect, board }: ImageModalProps
   function handleImageClick(image: ImageInfo) {
     // Check if warning is needed
     const isNightly = image.armbian_version.includes('trunk');
-    const isCommunityBoard = board?.has_community_support === true;
+    const isCommunityBoard = board?.support_level?.level === 'community';
 
     // No warning for custom images or stable images on supported boards
     if (!isNightly && !isCommunityBoard) {
@@ -275,6 +276,12 @@ export function ImageModal({ isOpen, onClose, onSelect, board }: ImageModalProps
                         {KERNEL_BADGES[kernelType].label}
                       </span>
                     )}
+                    {image.origin !== CATALOG.OFFICIAL_ORIGIN && (
+                      <span className="badge badge-origin" title={t('modal.catalogOrigin')}>
+                        <Library size={11} />
+                        {image.origin}
+                      </span>
+                    )}
                   </div>
                 </div>
                 <span className="badge badge-size">
@@ -294,7 +301,7 @@ export function ImageModal({ isOpen, onClose, onSelect, board }: ImageModalProps
           isOpen={showUnstableWarning}
           title={t('modal.imageStatusTitle')}
           message={
-            board?.has_community_support === true
+            board?.support_level?.level === 'community'
               ? t('modal.communityBoardMessage')
               : t('modal.nightlyBuildMessage')
           }
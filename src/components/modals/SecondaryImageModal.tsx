@@ -0,0 +1,98 @@
+import { useState, useEffect } from 'react';
+import { useTranslation } from 'react-i18next';
+import { X, FolderOpen } from 'lucide-react';
+import type { SecondaryImage } from '../../types';
+import { selectCustomImage } from '../../hooks/useTauri';
+
+interface SecondaryImageModalProps {
+  isOpen: boolean;
+  /** Current secondary image, null when none is set */
+  secondaryImage: SecondaryImage | null;
+  onClose: () => void;
+  /** Called with the new secondary image, or null to remove it */
+  onSave: (secondaryImage: SecondaryImage | null) => void;
+}
+
+const MB = 1024 * 1024;
+
+/**
+ * Modal for a raw image written after the main image, e.g. a data partition
+ *
+ * The offset is entered in whole megabytes, which matches the alignment the
+ * backend requires; overlap with the main image and the device size are
+ * checked when flashing starts.
+ */
+export function SecondaryImageModal({ isOpen, secondaryImage, onClose, onSave }: SecondaryImageModalProps) {
+  const { t } = useTranslation();
+  const [path, setPath] = useState<string>('');
+  const [name, setName] = useState<string>('');
+  const [offsetMb, setOffsetMb] = useState<string>('');
+
+  // Start from the saved image each time the modal opens
+  useEffect(() => {
+    if (isOpen) {
+      setPath(secondaryImage?.path ?? '');
+      setName(secondaryImage?.name ?? '');
+      setOffsetMb(secondaryImage ? String(secondaryImage.offset / MB) : '');
+    }
+  }, [isOpen, secondaryImage]);
+
+  if (!isOpen) return null;
+
+  const offsetValid = /^[0-9]+$/.test(offsetMb) && Number(offsetMb) > 0;
+  const canSave = path !== '' && offsetValid;
+
+  const handleChooseFile = async () => {
+    const file = await selectCustomImage().catch(() => null);
+    if (file) {
+      setPath(file.path);
+      setName(file.name);
+    }
+  };
+
+  return (
+    <div className="modal-overlay" onClick={onClose}>
+      <div className="modal modal-content customize-modal" onClick={(e) => e.stopPropagation()}>
+        <div className="modal-header">
+          <h2>{t('secondary.title')}</h2>
+          <button className="modal-close" onClick={onClose} aria-label="Close">
+            <X size={20} />
+          </button>
+        </div>
+
+        <div className="modal-body">
+          <p className="customize-hint">{t('secondary.hint')}</p>
+          <div className="customize-form">
+            <button className="home-custom-button secondary-file-button" onClick={handleChooseFile}>
+              <FolderOpen size={16} />
+              {name || t('secondary.chooseFile')}
+            </button>
+            <input
+              className="settings-input"
+              type="text"
+              inputMode="numeric"
+              placeholder={t('secondary.offset')}
+              value={offsetMb}
+              onChange={(e) => setOffsetMb(e.target.value.trim())}
+              spellCheck={false}
+            />
+            {offsetMb !== '' && !offsetValid && <div className="logs-error">{t('secondary.invalidOffset')}</div>}
+          </div>
+
+          <div className="flash-actions-inline">
+            <button className="btn btn-secondary" onClick={() => onSave(null)}>
+              {t('secondary.remove')}
+            </button>
+            <button
+              className="btn btn-primary"
+              onClick={() => onSave({ path, name, offset: Number(offsetMb) * MB })}
+              disabled={!canSave}
+            >
+              {t('customize.save')}
+            </button>
+          </div>
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -0,0 +1,138 @@
+import { useState, useEffect, useRef } from 'react';
+import { useTranslation } from 'react-i18next';
+import { X } from 'lucide-react';
+import type { UnlistenFn } from '@tauri-apps/api/event';
+import type { BlockDevice, DeviceVerification } from '../../types';
+import { cancelOperation, onFlashProgress, requestWriteAuthorization, selectCustomImage, verifyDevice } from '../../hooks/useTauri';
+import { getDeviceDisplayName } from '../../utils/deviceUtils';
+
+interface VerifyModalProps {
+  /** Device to verify, null when the modal is closed */
+  device: BlockDevice | null;
+  onClose: () => void;
+}
+
+type VerifyStage = 'running' | 'complete' | 'error';
+
+/**
+ * Modal that checks a device against an image without writing to it
+ *
+ * The image is chosen in a file picker when the check starts, e.g. the one a
+ * card was flashed with earlier or by another tool; closing the picker
+ * without a file closes the modal.
+ */
+export function VerifyModal({ device, onClose }: VerifyModalProps) {
+  const { t } = useTranslation();
+  const [stage, setStage] = useState<VerifyStage>('running');
+  const [progress, setProgress] = useState(0);
+  const [imageName, setImageName] = useState<string | null>(null);
+  const [result, setResult] = useState<DeviceVerification | null>(null);
+  const [deviceSha256, setDeviceSha256] = useState<string | null>(null);
+  const [error, setError] = useState<string | null>(null);
+  const unlistenRef = useRef<UnlistenFn | null>(null);
+
+  useEffect(() => {
+    if (!device) return;
+    let cancelled = false;
+
+    async function run(target: BlockDevice) {
+      setStage('running');
+      setProgress(0);
+      setImageName(null);
+      setResult(null);
+      setDeviceSha256(null);
+      setError(null);
+      try {
+        const image = await selectCustomImage();
+        if (cancelled) return;
+        if (!image) {
+          onClose();
+          return;
+        }
+        setImageName(image.name);
+        unlistenRef.current = await onFlashProgress((prog) => {
+          setProgress(prog.progress_percent);
+          setDeviceSha256(prog.device_sha256);
+        });
+        if (!(await requestWriteAuthorization(target.path))) {
+          throw new Error(t('error.authCancelled'));
+        }
+        const verification = await verifyDevice(image.path, target.path);
+        if (cancelled) return;
+        setResult(verification);
+        setStage('complete');
+      } catch (err) {
+        if (cancelled) return;
+        setError(err instanceof Error ? err.message : String(err));
+        setStage('error');
+      } finally {
+        unlistenRef.current?.();
+        unlistenRef.current = null;
+      }
+    }
+
+    run(device);
+    return () => {
+      cancelled = true;
+      unlistenRef.current?.();
+      unlistenRef.current = null;
+    };
+    // eslint-disable-next-line react-hooks/exhaustive-deps -- restart only for a different device
+  }, [device]);
+
+  if (!device) return null;
+
+  const handleClose = () => {
+    if (stage === 'running') {
+      cancelOperation().catch(() => {});
+      return;
+    }
+    onClose();
+  };
+
+  return (
+    <div className="modal-overlay">
+      <div className="modal modal-content verify-modal" onClick={(e) => e.stopPropagation()}>
+        <div className="modal-header">
+          <h2>{t('verifyOnly.title')}</h2>
+          {stage !== 'running' && (
+            <button className="modal-close" onClick={onClose} aria-label="Close">
+              <X size={20} />
+            </button>
+          )}
+        </div>
+
+        <div className="modal-body">
+          <p className="backup-device">
+            <strong>{getDeviceDisplayName(device)}</strong> ({device.size_formatted})
+            {imageName && <> — {imageName}</>}
+          </p>
+
+          {stage === 'running' && (
+            <div className="progress-container">
+              <div className="progress-bar">
+                <div className="progress-fill" style={{ width: `${progress}%` }} />
+              </div>
+              <span className="progress-text">{progress.toFixed(0)}%</span>
+            </div>
+          )}
+          {stage === 'complete' && result && (
+            <p className="backup-result">
+              {t(result.cached ? 'verifyOnly.passedCached' : 'verifyOnly.passed', { name: imageName })}
+            </p>
+          )}
+          {stage === 'complete' && deviceSha256 && (
+            <p className="backup-result">{t('flash.deviceChecksum', { checksum: deviceSha256 })}</p>
+          )}
+          {stage === 'error' && <div className="logs-error">{t('verifyOnly.failed', { error })}</div>}
+
+          <div className="flash-actions-inline">
+            <button className={`btn ${stage === 'running' ? 'btn-secondary' : 'btn-primary'}`} onClick={handleClose}>
+              {stage === 'running' ? t('flash.cancel') : t('flash.done')}
+            </button>
+          </div>
+        </div>
+      </div>
+    </div>
+  );
+}
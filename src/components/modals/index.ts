@@ -3,7 +3,13 @@
  */
 
 export { Modal } from './Modal';
+export { BackupModal } from './BackupModal';
 export { BoardModal } from './BoardModal';
+export { CapacityModal } from './CapacityModal';
+export { CustomizationModal } from './CustomizationModal';
 export { DeviceModal } from './DeviceModal';
+export { EraseModal } from './EraseModal';
 export { ImageModal } from './ImageModal';
 export { ManufacturerModal, type Manufacturer } from './ManufacturerModal';
+export { SecondaryImageModal } from './SecondaryImageModal';
+export { VerifyModal } from './VerifyModal';
@@ -1,20 +1,29 @@
 import { useState, useEffect } from 'react';
 import { useTranslation } from 'react-i18next';
-import { Code, FileText } from 'lucide-react';
+import { AppWindow, Code, FileText, KeyRound, Library, LifeBuoy, Network } from 'lucide-react';
 import { getDeveloperMode, setDeveloperMode } from '../../hooks/useSettings';
+import { openNewWindow, repairSettings } from '../../hooks/useTauri';
 import { LogsModal } from './LogsModal';
+import { DownloadCredentialsModal } from './DownloadCredentialsModal';
+import { CatalogSourcesModal } from './CatalogSourcesModal';
+import { ProxyModal } from './ProxyModal';
 import { EVENTS } from '../../config';
 
 /**
  * Advanced settings section for power users
  *
- * Contains developer mode toggle and view logs button.
+ * Contains developer mode toggle, view logs, download credentials, image catalogs,
+ * proxy, settings repair and new window buttons.
  */
 export function AdvancedSection() {
   const { t } = useTranslation();
   const [developerMode, setDeveloperModeState] = useState<boolean>(false);
   const [logsModalOpen, setLogsModalOpen] = useState<boolean>(false);
+  const [credentialsModalOpen, setCredentialsModalOpen] = useState<boolean>(false);
+  const [catalogsModalOpen, setCatalogsModalOpen] = useState<boolean>(false);
+  const [proxyModalOpen, setProxyModalOpen] = useState<boolean>(false);
   const [isToggling, setIsToggling] = useState<boolean>(false);
+  const [repairResult, setRepairResult] = useState<string | null>(null);
 
   // Load developer mode preference on mount
   useEffect(() => {
@@ -56,6 +65,18 @@ export function AdvancedSection() {
     }
   };
 
+  const handleRepairSettings = async () => {
+    try {
+      const result = await repairSettings();
+      setRepairResult(t(`settings.repairResult.${result}`));
+      if (result !== 'healthy') {
+        window.dispatchEvent(new Event(EVENTS.SETTINGS_CHANGED));
+      }
+    } catch (error) {
+      setRepairResult(String(error));
+    }
+  };
+
   return (
     <div className="settings-section">
       <h3 className="settings-section-title">{t('settings.advancedCategory')}</h3>
@@ -98,10 +119,101 @@ export function AdvancedSection() {
             <polyline points="9 18 15 12 9 6"></polyline>
           </svg>
         </div>
+
+        {/* Download Credentials Button */}
+        <div className="settings-item settings-item-clickable" onClick={() => setCredentialsModalOpen(true)}>
+          <div className="settings-item-left">
+            <div className="settings-item-icon">
+              <KeyRound />
+            </div>
+            <div className="settings-item-content">
+              <div className="settings-item-label">{t('settings.downloadCredentials')}</div>
+              <div className="settings-item-description">{t('settings.downloadCredentialsDescription')}</div>
+            </div>
+          </div>
+          <svg width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2">
+            <polyline points="9 18 15 12 9 6"></polyline>
+          </svg>
+        </div>
+
+        {/* Image Catalogs Button */}
+        <div className="settings-item settings-item-clickable" onClick={() => setCatalogsModalOpen(true)}>
+          <div className="settings-item-left">
+            <div className="settings-item-icon">
+              <Library />
+            </div>
+            <div className="settings-item-content">
+              <div className="settings-item-label">{t('settings.catalogSources')}</div>
+              <div className="settings-item-description">{t('settings.catalogSourcesDescription')}</div>
+            </div>
+          </div>
+          <svg width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2">
+            <polyline points="9 18 15 12 9 6"></polyline>
+          </svg>
+        </div>
+
+        {/* Proxy Button */}
+        <div className="settings-item settings-item-clickable" onClick={() => setProxyModalOpen(true)}>
+          <div className="settings-item-left">
+            <div className="settings-item-icon">
+              <Network />
+            </div>
+            <div className="settings-item-content">
+              <div className="settings-item-label">{t('settings.proxy')}</div>
+              <div className="settings-item-description">{t('settings.proxyDescription')}</div>
+            </div>
+          </div>
+          <svg width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2">
+            <polyline points="9 18 15 12 9 6"></polyline>
+          </svg>
+        </div>
+
+        {/* Repair Settings Button */}
+        <div className="settings-item settings-item-clickable" onClick={handleRepairSettings}>
+          <div className="settings-item-left">
+            <div className="settings-item-icon">
+              <LifeBuoy />
+            </div>
+            <div className="settings-item-content">
+              <div className="settings-item-label">{t('settings.repairSettings')}</div>
+              <div className="settings-item-description">
+                {repairResult ?? t('settings.repairSettingsDescription')}
+              </div>
+            </div>
+          </div>
+        </div>
+
+        {/* New Window Button */}
+        <div
+          className="settings-item settings-item-clickable"
+          onClick={() => openNewWindow().catch((error) => console.error('Failed to open window:', error))}
+        >
+          <div className="settings-item-left">
+            <div className="settings-item-icon">
+              <AppWindow />
+            </div>
+            <div className="settings-item-content">
+              <div className="settings-item-label">{t('settings.newWindow')}</div>
+              <div className="settings-item-description">{t('settings.newWindowDescription')}</div>
+            </div>
+          </div>
+          <svg width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2">
+            <polyline points="9 18 15 12 9 6"></polyline>
+          </svg>
+        </div>
       </div>
 
       {/* Logs Modal */}
       <LogsModal isOpen={logsModalOpen} onClose={() => setLogsModalOpen(false)} />
+
+      {/* Download Credentials Modal */}
+      <DownloadCredentialsModal isOpen={credentialsModalOpen} onClose={() => setCredentialsModalOpen(false)} />
+
+      {/* Image Catalogs Modal */}
+      <CatalogSourcesModal isOpen={catalogsModalOpen} onClose={() => setCatalogsModalOpen(false)} />
+
+      {/* Proxy Modal */}
+      <ProxyModal isOpen={proxyModalOpen} onClose={() => setProxyModalOpen(false)} />
     </div>
   );
 }
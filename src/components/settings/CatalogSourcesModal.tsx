@@ -0,0 +1,134 @@
+import { useState, useEffect } from 'react';
+import { useTranslation } from 'react-i18next';
+import { X, Trash2 } from 'lucide-react';
+import { getCatalogSources, setCatalogSources } from '../../hooks/useTauri';
+import { getAllowInsecureUrls } from '../../hooks/useSettings';
+import type { CatalogSource } from '../../types';
+import { CATALOG } from '../../config';
+
+interface CatalogSourcesModalProps {
+  isOpen: boolean;
+  onClose: () => void;
+}
+
+/**
+ * Modal for managing additional image catalogs
+ *
+ * Catalogs in the Armbian JSON format, such as an internal mirror or
+ * self-built images, are merged with the official one. Their images are
+ * badged with the catalog name. HTTP catalogs are kept but skipped while
+ * insecure downloads are off, and marked as such.
+ */
+export function CatalogSourcesModal({ isOpen, onClose }: CatalogSourcesModalProps) {
+  const { t } = useTranslation();
+  const [sources, setSources] = useState<CatalogSource[]>([]);
+  const [name, setName] = useState<string>('');
+  const [url, setUrl] = useState<string>('');
+  const [saving, setSaving] = useState<boolean>(false);
+  const [error, setError] = useState<string | null>(null);
+  const [allowInsecure, setAllowInsecure] = useState<boolean>(false);
+
+  // Load sources when modal opens
+  useEffect(() => {
+    if (isOpen) {
+      setError(null);
+      getCatalogSources()
+        .then(setSources)
+        .catch((err) => console.error('Failed to load catalog sources:', err));
+      getAllowInsecureUrls()
+        .then(setAllowInsecure)
+        .catch((err) => console.error('Failed to load insecure downloads preference:', err));
+    }
+  }, [isOpen]);
+
+  const save = async (next: CatalogSource[]) => {
+    setSaving(true);
+    setError(null);
+    try {
+      setSources(await setCatalogSources(next));
+      return true;
+    } catch (err) {
+      setError(String(err));
+      return false;
+    } finally {
+      setSaving(false);
+    }
+  };
+
+  const handleAdd = async () => {
+    if (await save([...sources, { name, url }])) {
+      setName('');
+      setUrl('');
+    }
+  };
+
+  const handleRemove = (sourceName: string) => save(sources.filter((s) => s.name !== sourceName));
+
+  if (!isOpen) return null;
+
+  const canAdd = name.trim() !== '' && url.trim() !== '' && sources.length < CATALOG.MAX_SOURCES;
+
+  return (
+    <div className="modal-overlay" onClick={onClose}>
+      <div className="modal modal-content credentials-modal" onClick={(e) => e.stopPropagation()}>
+        <div className="modal-header">
+          <h2>{t('settings.catalogSources')}</h2>
+          <button className="modal-close" onClick={onClose} aria-label="Close">
+            <X size={20} />
+          </button>
+        </div>
+
+        <div className="modal-body">
+          {sources.length === 0 ? (
+            <p className="credentials-empty">{t('settings.noCatalogSources')}</p>
+          ) : (
+            <ul className="credentials-list">
+              {sources.map((source) => (
+                <li key={source.name} className="credentials-row">
+                  <span className="credentials-host">{source.name}</span>
+                  <span className="credentials-scheme">{source.url}</span>
+                  {!allowInsecure && source.url.startsWith('http:') && (
+                    <span className="credentials-scheme catalog-source-disabled">
+                      {t('settings.catalogSourceDisabled')}
+                    </span>
+                  )}
+                  <button
+                    className="btn-sm"
+                    onClick={() => handleRemove(source.name)}
+                    disabled={saving}
+                    aria-label={t('settings.removeCatalogSource')}
+                    title={t('settings.removeCatalogSource')}
+                  >
+                    <Trash2 size={14} />
+                  </button>
+                </li>
+              ))}
+            </ul>
+          )}
+
+          <div className="credentials-form">
+            <input
+              className="settings-input"
+              type="text"
+              placeholder={t('settings.catalogSourceName')}
+              value={name}
+              onChange={(e) => setName(e.target.value)}
+            />
+            <input
+              className="settings-input"
+              type="text"
+              placeholder={t('settings.catalogSourceUrl')}
+              value={url}
+              onChange={(e) => setUrl(e.target.value)}
+            />
+            <button className="btn btn-primary" onClick={handleAdd} disabled={!canAdd || saving}>
+              {t('settings.addCatalogSource')}
+            </button>
+            {error && <div className="logs-error">{error}</div>}
+            <p className="credentials-hint">{t('settings.catalogSourcesHint')}</p>
+          </div>
+        </div>
+      </div>
+    </div>
+  );
+}
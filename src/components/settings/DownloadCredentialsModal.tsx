@@ -0,0 +1,158 @@
+import { useState, useEffect } from 'react';
+import { useTranslation } from 'react-i18next';
+import { X, Trash2 } from 'lucide-react';
+import {
+  getDownloadCredentials,
+  setDownloadCredentials,
+  removeDownloadCredentials,
+} from '../../hooks/useTauri';
+import type { AuthScheme, AuthSource } from '../../types';
+
+interface DownloadCredentialsModalProps {
+  isOpen: boolean;
+  onClose: () => void;
+}
+
+/**
+ * Modal for managing credentials of gated download hosts
+ *
+ * Lists the hosts with stored credentials and adds new ones. Secrets are
+ * written to the OS keychain and never read back into the UI.
+ */
+export function DownloadCredentialsModal({ isOpen, onClose }: DownloadCredentialsModalProps) {
+  const { t } = useTranslation();
+  const [sources, setSources] = useState<AuthSource[]>([]);
+  const [host, setHost] = useState<string>('');
+  const [scheme, setScheme] = useState<AuthScheme>('bearer');
+  const [username, setUsername] = useState<string>('');
+  const [secret, setSecret] = useState<string>('');
+  const [saving, setSaving] = useState<boolean>(false);
+  const [error, setError] = useState<string | null>(null);
+
+  // Load sources when modal opens
+  useEffect(() => {
+    if (isOpen) {
+      setError(null);
+      getDownloadCredentials()
+        .then(setSources)
+        .catch((err) => console.error('Failed to load download credentials:', err));
+    }
+  }, [isOpen]);
+
+  const handleSave = async () => {
+    setSaving(true);
+    setError(null);
+    try {
+      const source = await setDownloadCredentials(
+        host,
+        scheme,
+        scheme === 'basic' ? username : null,
+        secret
+      );
+      setSources((prev) => [...prev.filter((s) => s.host !== source.host), source]);
+      setHost('');
+      setUsername('');
+      setSecret('');
+    } catch (err) {
+      setError(String(err));
+    } finally {
+      setSaving(false);
+    }
+  };
+
+  const handleRemove = async (sourceHost: string) => {
+    try {
+      await removeDownloadCredentials(sourceHost);
+      setSources((prev) => prev.filter((s) => s.host !== sourceHost));
+    } catch (err) {
+      setError(String(err));
+    }
+  };
+
+  if (!isOpen) return null;
+
+  const canSave = host.trim() !== '' && secret !== '' && (scheme === 'bearer' || username.trim() !== '');
+
+  return (
+    <div className="modal-overlay" onClick={onClose}>
+      <div className="modal modal-content credentials-modal" onClick={(e) => e.stopPropagation()}>
+        <div className="modal-header">
+          <h2>{t('settings.downloadCredentials')}</h2>
+          <button className="modal-close" onClick={onClose} aria-label="Close">
+            <X size={20} />
+          </button>
+        </div>
+
+        <div className="modal-body">
+          {sources.length === 0 ? (
+            <p className="credentials-empty">{t('settings.noDownloadCredentials')}</p>
+          ) : (
+            <ul className="credentials-list">
+              {sources.map((source) => (
+                <li key={source.host} className="credentials-row">
+                  <span className="credentials-host">{source.host}</span>
+                  <span className="credentials-scheme">
+                    {source.scheme === 'basic'
+                      ? `${t('settings.credentialsBasic')} (${source.username})`
+                      : t('settings.credentialsBearer')}
+                  </span>
+                  <button
+                    className="btn-sm"
+                    onClick={() => handleRemove(source.host)}
+                    aria-label={t('settings.removeCredentials')}
+                    title={t('settings.removeCredentials')}
+                  >
+                    <Trash2 size={14} />
+                  </button>
+                </li>
+              ))}
+            </ul>
+          )}
+
+          <div className="credentials-form">
+            <input
+              className="settings-input"
+              type="text"
+              placeholder={t('settings.credentialsHost')}
+              value={host}
+              onChange={(e) => setHost(e.target.value)}
+            />
+            <select
+              className="settings-select"
+              value={scheme}
+              onChange={(e) => setScheme(e.target.value as AuthScheme)}
+            >
+              <option value="bearer">{t('settings.credentialsBearer')}</option>
+              <option value="basic">{t('settings.credentialsBasic')}</option>
+            </select>
+            {scheme === 'basic' && (
+              <input
+                className="settings-input"
+                type="text"
+                placeholder={t('settings.credentialsUsername')}
+                value={username}
+                onChange={(e) => setUsername(e.target.value)}
+                autoComplete="off"
+              />
+            )}
+            <input
+              className="settings-input"
+              type="password"
+              placeholder={
+                scheme === 'basic' ? t('settings.credentialsPassword') : t('settings.credentialsToken')
+              }
+              value={secret}
+              onChange={(e) => setSecret(e.target.value)}
+              autoComplete="off"
+            />
+            <button className="btn btn-primary" onClick={handleSave} disabled={!canSave || saving}>
+              {t('settings.saveCredentials')}
+            </button>
+            {error && <div className="logs-error">{error}</div>}
+            <p className="credentials-hint">{t('settings.downloadCredentialsHint')}</p>
+          </div>
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -1,6 +1,6 @@
 import { useState, useEffect, useCallback } from 'react';
 import { useTranslation } from 'react-i18next';
-import { Lightbulb, Download, HardDrive, Database, Trash2 } from 'lucide-react';
+import { Lightbulb, Download, Volume2, BellRing, BadgeCheck, HardDrive, Database, Trash2, Wifi, ShieldAlert, Globe, Network, PauseCircle, Zap, RotateCw, Images } from 'lucide-react';
 import {
   getShowMotd,
   setShowMotd,
@@ -10,10 +10,41 @@ import {
   setCacheEnabled,
   getCacheMaxSize,
   setCacheMaxSize,
+  getMeteredAllowedNetworks,
+  setMeteredAllowedNetworks,
+  getAllowInsecureUrls,
+  setAllowInsecureUrls,
+  getMirrorRegion,
+  setMirrorRegion,
+  getDownloadSegments,
+  setDownloadSegments,
+  getDownloadRetries,
+  setDownloadRetries,
+  getPauseOnLowSpace,
+  setPauseOnLowSpace,
+  getDirectWrite,
+  setDirectWrite,
 } from '../../hooks/useSettings';
-import { getCacheSize, clearCache } from '../../hooks/useTauri';
+import {
+  getCacheSize,
+  clearCache,
+  clearBoardImageCache,
+  measureMirrors,
+  getCompletionCues,
+  setCompletionCues,
+  previewCompletionCue,
+} from '../../hooks/useTauri';
 import { ConfirmationDialog } from '../shared/ConfirmationDialog';
-import { CACHE, EVENTS } from '../../config';
+import { CACHE, DOWNLOAD_RETRY_OPTIONS, DOWNLOAD_SEGMENT_OPTIONS, EVENTS, MIRROR_REGIONS, type MirrorRegion } from '../../config';
+import type { CompletionCues, Mirror } from '../../types';
+
+/** Translation keys of the mirror region names */
+const MIRROR_REGION_LABELS: Record<MirrorRegion, string> = {
+  auto: 'settings.mirrorRegionAuto',
+  europe: 'settings.mirrorRegionEurope',
+  'north-america': 'settings.mirrorRegionNorthAmerica',
+  asia: 'settings.mirrorRegionAsia',
+};
 
 /**
  * Format bytes to human-readable string
@@ -32,7 +63,8 @@ function formatBytes(bytes: number): string {
 /**
  * General settings section for sidebar layout
  *
- * Contains notification preferences and cache management.
+ * Contains notification preferences, cache management, metered networks, the
+ * download mirror region, parallel download connections and insecure downloads.
  */
 export function GeneralSection() {
   const { t } = useTranslation();
@@ -40,15 +72,35 @@ export function GeneralSection() {
   // Notification states
   const [showMotd, setShowMotdState] = useState<boolean>(true);
   const [showUpdaterModal, setShowUpdaterModalState] = useState<boolean>(true);
+  const [completionCues, setCompletionCuesState] = useState<CompletionCues>({
+    sound: false,
+    attention: true,
+    badge: true,
+  });
 
   // Cache states
   const [cacheEnabled, setCacheEnabledState] = useState<boolean>(true);
   const [cacheMaxSize, setCacheMaxSizeState] = useState<number>(CACHE.DEFAULT_SIZE);
   const [currentCacheSize, setCurrentCacheSize] = useState<number>(0);
   const [isClearing, setIsClearing] = useState<boolean>(false);
+  const [isClearingPhotos, setIsClearingPhotos] = useState<boolean>(false);
   const [isLoadingCacheSize, setIsLoadingCacheSize] = useState<boolean>(true);
   const [showClearConfirm, setShowClearConfirm] = useState<boolean>(false);
 
+  // Metered networks the user allowed large downloads on
+  const [meteredAllowedCount, setMeteredAllowedCount] = useState<number>(0);
+
+  // Plain HTTP image downloads, off by default
+  const [allowInsecureUrls, setAllowInsecureUrlsState] = useState<boolean>(false);
+  const [pauseOnLowSpace, setPauseOnLowSpaceState] = useState<boolean>(true);
+  const [directWrite, setDirectWriteState] = useState<boolean>(false);
+
+  // Download mirror region and the latencies measured for it
+  const [mirrorRegion, setMirrorRegionState] = useState<MirrorRegion>('auto');
+  const [mirrors, setMirrors] = useState<Mirror[]>([]);
+  const [downloadSegments, setDownloadSegmentsState] = useState<number>(4);
+  const [downloadRetries, setDownloadRetriesState] = useState<number>(5);
+
   /**
    * Load current cache size from backend
    */
@@ -90,6 +142,13 @@ export function GeneralSection() {
     loadUpdaterModalPreference();
   }, []);
 
+  // Load completion cues on mount
+  useEffect(() => {
+    getCompletionCues()
+      .then(setCompletionCuesState)
+      .catch((error) => console.error('Failed to load completion cues:', error));
+  }, []);
+
   // Load cache preferences on mount
   useEffect(() => {
     const loadCachePreferences = async () => {
@@ -108,6 +167,58 @@ export function GeneralSection() {
     loadCacheSize();
   }, [loadCacheSize]);
 
+  // Load allowed metered networks on mount
+  useEffect(() => {
+    getMeteredAllowedNetworks()
+      .then((networks) => setMeteredAllowedCount(networks.length))
+      .catch((error) => console.error('Failed to load allowed metered networks:', error));
+  }, []);
+
+  // Load insecure downloads preference on mount
+  useEffect(() => {
+    getAllowInsecureUrls()
+      .then(setAllowInsecureUrlsState)
+      .catch((error) => console.error('Failed to load insecure downloads preference:', error));
+  }, []);
+
+  // Load low space preference on mount
+  useEffect(() => {
+    getPauseOnLowSpace()
+      .then(setPauseOnLowSpaceState)
+      .catch((error) => console.error('Failed to load low space preference:', error));
+  }, []);
+
+  // Load direct write preference on mount
+  useEffect(() => {
+    getDirectWrite()
+      .then(setDirectWriteState)
+      .catch((error) => console.error('Failed to load direct write preference:', error));
+  }, []);
+
+  // Load mirror region and measure mirror latencies on mount
+  useEffect(() => {
+    getMirrorRegion()
+      .then(setMirrorRegionState)
+      .catch((error) => console.error('Failed to load mirror region:', error));
+    measureMirrors()
+      .then(setMirrors)
+      .catch((error) => console.error('Failed to measure mirrors:', error));
+  }, []);
+
+  // Load parallel download connections on mount
+  useEffect(() => {
+    getDownloadSegments()
+      .then(setDownloadSegmentsState)
+      .catch((error) => console.error('Failed to load download segments:', error));
+  }, []);
+
+  // Load download retries on mount
+  useEffect(() => {
+    getDownloadRetries()
+      .then(setDownloadRetriesState)
+      .catch((error) => console.error('Failed to load download retries:', error));
+  }, []);
+
   /**
    * Toggle MOTD visibility
    */
@@ -136,6 +247,19 @@ export function GeneralSection() {
     }
   };
 
+  /**
+   * Toggle one of the cues announcing finished operations
+   */
+  const handleToggleCue = async (cue: keyof CompletionCues) => {
+    try {
+      const newValue = { ...completionCues, [cue]: !completionCues[cue] };
+      await setCompletionCues(newValue);
+      setCompletionCuesState(newValue);
+    } catch (error) {
+      console.error('Failed to set completion cues:', error);
+    }
+  };
+
   /**
    * Toggle cache enabled/disabled
    */
@@ -150,6 +274,89 @@ export function GeneralSection() {
     }
   };
 
+  /**
+   * Toggle plain HTTP downloads
+   */
+  const handleToggleAllowInsecureUrls = async () => {
+    try {
+      const newValue = !allowInsecureUrls;
+      await setAllowInsecureUrls(newValue);
+      setAllowInsecureUrlsState(newValue);
+    } catch (error) {
+      console.error('Failed to set insecure downloads preference:', error);
+    }
+  };
+
+  /**
+   * Toggle pausing downloads when their volume runs low on space
+   */
+  const handleTogglePauseOnLowSpace = async () => {
+    try {
+      const newValue = !pauseOnLowSpace;
+      await setPauseOnLowSpace(newValue);
+      setPauseOnLowSpaceState(newValue);
+    } catch (error) {
+      console.error('Failed to set low space preference:', error);
+    }
+  };
+
+  /**
+   * Toggle flashing downloads as they arrive, without caching the image
+   */
+  const handleToggleDirectWrite = async () => {
+    try {
+      const newValue = !directWrite;
+      await setDirectWrite(newValue);
+      setDirectWriteState(newValue);
+    } catch (error) {
+      console.error('Failed to set direct write preference:', error);
+    }
+  };
+
+  /**
+   * Handle mirror region change from dropdown
+   */
+  const handleMirrorRegionChange = async (e: React.ChangeEvent<HTMLSelectElement>) => {
+    try {
+      const region = e.target.value as MirrorRegion;
+      await setMirrorRegion(region);
+      setMirrorRegionState(region);
+    } catch (error) {
+      console.error('Failed to set mirror region:', error);
+    }
+  };
+
+  /**
+   * Handle parallel download connections change from dropdown
+   */
+  const handleDownloadSegmentsChange = async (e: React.ChangeEvent<HTMLSelectElement>) => {
+    try {
+      const segments = Number(e.target.value);
+      await setDownloadSegments(segments);
+      setDownloadSegmentsState(segments);
+    } catch (error) {
+      console.error('Failed to set download segments:', error);
+    }
+  };
+
+  /**
+   * Handle download retries change from dropdown
+   */
+  const handleDownloadRetriesChange = async (e: React.ChangeEvent<HTMLSelectElement>) => {
+    try {
+      const retries = Number(e.target.value);
+      await setDownloadRetries(retries);
+      setDownloadRetriesState(retries);
+    } catch (error) {
+      console.error('Failed to set download retries:', error);
+    }
+  };
+
+  // Fastest reachable mirror in the selected region, any region for 'auto'
+  const fastestMirror = mirrors
+    .filter((m) => m.latency_ms !== null && (mirrorRegion === 'auto' || m.region === mirrorRegion))
+    .sort((a, b) => (a.latency_ms ?? 0) - (b.latency_ms ?? 0))[0];
+
   /**
    * Handle cache max size change from dropdown
    */
@@ -190,6 +397,32 @@ export function GeneralSection() {
     }
   };
 
+  /**
+   * Delete cached board photos; they are downloaded again when shown
+   */
+  const handleClearBoardPhotos = async () => {
+    try {
+      setIsClearingPhotos(true);
+      await clearBoardImageCache();
+    } catch (error) {
+      console.error('Failed to clear board photos:', error);
+    } finally {
+      setIsClearingPhotos(false);
+    }
+  };
+
+  /**
+   * Warn again on every metered network
+   */
+  const handleForgetMeteredNetworks = async () => {
+    try {
+      await setMeteredAllowedNetworks([]);
+      setMeteredAllowedCount(0);
+    } catch (error) {
+      console.error('Failed to forget allowed metered networks:', error);
+    }
+  };
+
   return (
     <div className="settings-section">
       {/* NOTIFICATIONS Section */}
@@ -249,6 +482,96 @@ export function GeneralSection() {
               <span className="toggle-slider"></span>
             </label>
           </div>
+
+          {/* Completion sound toggle, with a preview of both outcomes */}
+          <div className="settings-item">
+            <div className="settings-item-left">
+              <div className="settings-item-icon">
+                <Volume2 />
+              </div>
+              <div className="settings-item-content">
+                <div className="settings-item-label">
+                  {t('settings.completionSound')}
+                </div>
+                <div className="settings-item-description">
+                  {t('settings.completionSoundDescription')}
+                </div>
+              </div>
+            </div>
+            <button
+              className="btn btn-secondary btn-sm"
+              onClick={() => previewCompletionCue('success').catch(() => {})}
+            >
+              {t('settings.previewSuccess')}
+            </button>
+            <button
+              className="btn btn-secondary btn-sm"
+              onClick={() => previewCompletionCue('failure').catch(() => {})}
+            >
+              {t('settings.previewFailure')}
+            </button>
+            <label className="toggle-switch">
+              <input
+                type="checkbox"
+                checked={completionCues.sound}
+                onChange={() => handleToggleCue('sound')}
+                aria-label={t('settings.completionSound')}
+              />
+              <span className="toggle-slider"></span>
+            </label>
+          </div>
+
+          {/* Taskbar/dock attention toggle */}
+          <div className="settings-item">
+            <div className="settings-item-left">
+              <div className="settings-item-icon">
+                <BellRing />
+              </div>
+              <div className="settings-item-content">
+                <div className="settings-item-label">
+                  {t('settings.completionAttention')}
+                </div>
+                <div className="settings-item-description">
+                  {t('settings.completionAttentionDescription')}
+                </div>
+              </div>
+            </div>
+            <label className="toggle-switch">
+              <input
+                type="checkbox"
+                checked={completionCues.attention}
+                onChange={() => handleToggleCue('attention')}
+                aria-label={t('settings.completionAttention')}
+              />
+              <span className="toggle-slider"></span>
+            </label>
+          </div>
+
+          {/* App icon badge toggle */}
+          <div className="settings-item">
+            <div className="settings-item-left">
+              <div className="settings-item-icon">
+                <BadgeCheck />
+              </div>
+              <div className="settings-item-content">
+                <div className="settings-item-label">
+                  {t('settings.completionBadge')}
+                </div>
+                <div className="settings-item-description">
+                  {t('settings.completionBadgeDescription')}
+                </div>
+              </div>
+            </div>
+            <label className="toggle-switch">
+              <input
+                type="checkbox"
+                checked={completionCues.badge}
+                onChange={() => handleToggleCue('badge')}
+                aria-label={t('settings.completionBadge')}
+              />
+              <span className="toggle-slider"></span>
+            </label>
+          </div>
         </div>
       </div>
 
@@ -342,6 +665,234 @@ export function GeneralSection() {
               {isClearing ? t('modal.loading') : t('settings.clearCache')}
             </button>
           </div>
+
+          {/* Board photo cache clear button */}
+          <div className="settings-item">
+            <div className="settings-item-left">
+              <div className="settings-item-icon">
+                <Images />
+              </div>
+              <div className="settings-item-content">
+                <div className="settings-item-label">
+                  {t('settings.boardPhotos')}
+                </div>
+                <div className="settings-item-description">
+                  {t('settings.boardPhotosDescription')}
+                </div>
+              </div>
+            </div>
+            <button
+              className="btn btn-secondary btn-sm"
+              onClick={handleClearBoardPhotos}
+              disabled={isClearingPhotos}
+              aria-label={t('settings.clearBoardPhotos')}
+            >
+              {isClearingPhotos ? t('modal.loading') : t('settings.clearBoardPhotos')}
+            </button>
+          </div>
+        </div>
+      </div>
+
+      {/* NETWORK Section */}
+      <div className="settings-category">
+        <h4 className="settings-category-title">
+          {t('settings.networkCategory')}
+        </h4>
+        <div className="settings-list">
+          {/* Metered networks allowed without a warning */}
+          <div className="settings-item">
+            <div className="settings-item-left">
+              <div className="settings-item-icon">
+                <Wifi />
+              </div>
+              <div className="settings-item-content">
+                <div className="settings-item-label">
+                  {t('settings.meteredNetworks')}
+                </div>
+                <div className="settings-item-description">
+                  {t('settings.meteredNetworksDescription')}
+                </div>
+              </div>
+            </div>
+            <button
+              className="btn btn-secondary btn-sm"
+              onClick={handleForgetMeteredNetworks}
+              disabled={meteredAllowedCount === 0}
+              aria-label={t('settings.forgetMeteredNetworks')}
+            >
+              {t('settings.forgetMeteredNetworks')}
+            </button>
+          </div>
+
+          {/* Download mirror region dropdown */}
+          <div className="settings-item">
+            <div className="settings-item-left">
+              <div className="settings-item-icon">
+                <Globe />
+              </div>
+              <div className="settings-item-content">
+                <div className="settings-item-label">
+                  {t('settings.mirrorRegion')}
+                </div>
+                <div className="settings-item-description">
+                  {fastestMirror
+                    ? t('settings.mirrorRegionFastest', {
+                        host: fastestMirror.host,
+                        ms: fastestMirror.latency_ms,
+                      })
+                    : t('settings.mirrorRegionDescription')}
+                </div>
+              </div>
+            </div>
+            <select
+              className="settings-select"
+              value={mirrorRegion}
+              onChange={handleMirrorRegionChange}
+              aria-label={t('settings.mirrorRegion')}
+            >
+              {MIRROR_REGIONS.map((region) => (
+                <option key={region} value={region}>
+                  {t(MIRROR_REGION_LABELS[region])}
+                </option>
+              ))}
+            </select>
+          </div>
+
+          {/* Parallel download connections dropdown */}
+          <div className="settings-item">
+            <div className="settings-item-left">
+              <div className="settings-item-icon">
+                <Network />
+              </div>
+              <div className="settings-item-content">
+                <div className="settings-item-label">
+                  {t('settings.downloadSegments')}
+                </div>
+                <div className="settings-item-description">
+                  {t('settings.downloadSegmentsDescription')}
+                </div>
+              </div>
+            </div>
+            <select
+              className="settings-select"
+              value={downloadSegments}
+              onChange={handleDownloadSegmentsChange}
+              aria-label={t('settings.downloadSegments')}
+            >
+              {DOWNLOAD_SEGMENT_OPTIONS.map((count) => (
+                <option key={count} value={count}>
+                  {count}
+                </option>
+              ))}
+            </select>
+          </div>
+
+          {/* Download retries dropdown */}
+          <div className="settings-item">
+            <div className="settings-item-left">
+              <div className="settings-item-icon">
+                <RotateCw />
+              </div>
+              <div className="settings-item-content">
+                <div className="settings-item-label">
+                  {t('settings.downloadRetries')}
+                </div>
+                <div className="settings-item-description">
+                  {t('settings.downloadRetriesDescription')}
+                </div>
+              </div>
+            </div>
+            <select
+              className="settings-select"
+              value={downloadRetries}
+              onChange={handleDownloadRetriesChange}
+              aria-label={t('settings.downloadRetries')}
+            >
+              {DOWNLOAD_RETRY_OPTIONS.map((count) => (
+                <option key={count} value={count}>
+                  {count}
+                </option>
+              ))}
+            </select>
+          </div>
+
+          {/* Plain HTTP downloads toggle */}
+          <div className="settings-item">
+            <div className="settings-item-left">
+              <div className="settings-item-icon">
+                <ShieldAlert />
+              </div>
+              <div className="settings-item-content">
+                <div className="settings-item-label">
+                  {t('settings.allowInsecureUrls')}
+                </div>
+                <div className="settings-item-description">
+                  {t('settings.allowInsecureUrlsDescription')}
+                </div>
+              </div>
+            </div>
+            <label className="toggle-switch">
+              <input
+                type="checkbox"
+                checked={allowInsecureUrls}
+                onChange={handleToggleAllowInsecureUrls}
+                aria-label={t('settings.allowInsecureUrls')}
+              />
+              <span className="toggle-slider"></span>
+            </label>
+          </div>
+
+          {/* Pause downloads on low disk space toggle */}
+          <div className="settings-item">
+            <div className="settings-item-left">
+              <div className="settings-item-icon">
+                <PauseCircle />
+              </div>
+              <div className="settings-item-content">
+                <div className="settings-item-label">
+                  {t('settings.pauseOnLowSpace')}
+                </div>
+                <div className="settings-item-description">
+                  {t('settings.pauseOnLowSpaceDescription')}
+                </div>
+              </div>
+            </div>
+            <label className="toggle-switch">
+              <input
+                type="checkbox"
+                checked={pauseOnLowSpace}
+                onChange={handleTogglePauseOnLowSpace}
+                aria-label={t('settings.pauseOnLowSpace')}
+              />
+              <span className="toggle-slider"></span>
+            </label>
+          </div>
+
+          {/* Direct write */}
+          <div className="settings-item">
+            <div className="settings-item-left">
+              <div className="settings-item-icon">
+                <Zap />
+              </div>
+              <div className="settings-item-content">
+                <div className="settings-item-label">
+                  {t('settings.directWrite')}
+                </div>
+                <div className="settings-item-description">
+                  {t('settings.directWriteDescription')}
+                </div>
+              </div>
+            </div>
+            <label className="toggle-switch">
+              <input
+                type="checkbox"
+                checked={directWrite}
+                onChange={handleToggleDirectWrite}
+                aria-label={t('settings.directWrite')}
+              />
+              <span className="toggle-slider"></span>
+            </label>
+          </div>
         </div>
       </div>
 
@@ -0,0 +1,142 @@
+import { useState, useEffect } from 'react';
+import { useTranslation } from 'react-i18next';
+import { X } from 'lucide-react';
+import { getProxy, setProxy } from '../../hooks/useTauri';
+import type { ProxyMode, ProxySettings } from '../../types';
+
+interface ProxyModalProps {
+  isOpen: boolean;
+  onClose: () => void;
+}
+
+/** Translation keys of the proxy modes */
+const PROXY_MODE_LABELS: Record<ProxyMode, string> = {
+  system: 'settings.proxyModeSystem',
+  manual: 'settings.proxyModeManual',
+  off: 'settings.proxyModeOff',
+};
+
+/**
+ * Modal for the proxy used by all HTTP traffic
+ *
+ * The password is written to the OS keychain and never read back into the
+ * UI; leaving the field empty keeps the stored one.
+ */
+export function ProxyModal({ isOpen, onClose }: ProxyModalProps) {
+  const { t } = useTranslation();
+  const [proxy, setProxyState] = useState<ProxySettings | null>(null);
+  const [password, setPassword] = useState<string>('');
+  const [saving, setSaving] = useState<boolean>(false);
+  const [error, setError] = useState<string | null>(null);
+  const [saved, setSaved] = useState<boolean>(false);
+
+  // Load proxy settings when modal opens
+  useEffect(() => {
+    if (isOpen) {
+      setError(null);
+      setSaved(false);
+      setPassword('');
+      getProxy()
+        .then(setProxyState)
+        .catch((err) => console.error('Failed to load proxy settings:', err));
+    }
+  }, [isOpen]);
+
+  const update = (changes: Partial<ProxySettings>) => {
+    setSaved(false);
+    setProxyState((prev) => (prev ? { ...prev, ...changes } : prev));
+  };
+
+  const handleSave = async () => {
+    if (!proxy) return;
+    setSaving(true);
+    setError(null);
+    try {
+      setProxyState(await setProxy(proxy, password === '' ? null : password));
+      setPassword('');
+      setSaved(true);
+    } catch (err) {
+      setError(String(err));
+    } finally {
+      setSaving(false);
+    }
+  };
+
+  if (!isOpen || !proxy) return null;
+
+  return (
+    <div className="modal-overlay" onClick={onClose}>
+      <div className="modal modal-content credentials-modal" onClick={(e) => e.stopPropagation()}>
+        <div className="modal-header">
+          <h2>{t('settings.proxy')}</h2>
+          <button className="modal-close" onClick={onClose} aria-label="Close">
+            <X size={20} />
+          </button>
+        </div>
+
+        <div className="modal-body">
+          <div className="credentials-form">
+            <select
+              className="settings-select"
+              value={proxy.mode}
+              onChange={(e) => update({ mode: e.target.value as ProxyMode })}
+              aria-label={t('settings.proxy')}
+            >
+              {(Object.keys(PROXY_MODE_LABELS) as ProxyMode[]).map((mode) => (
+                <option key={mode} value={mode}>
+                  {t(PROXY_MODE_LABELS[mode])}
+                </option>
+              ))}
+            </select>
+            {proxy.mode === 'manual' && (
+              <>
+                <input
+                  className="settings-input"
+                  type="text"
+                  placeholder={t('settings.proxyUrl')}
+                  value={proxy.url}
+                  onChange={(e) => update({ url: e.target.value })}
+                />
+                <input
+                  className="settings-input"
+                  type="text"
+                  placeholder={t('settings.credentialsUsername')}
+                  value={proxy.username}
+                  onChange={(e) => update({ username: e.target.value })}
+                  autoComplete="off"
+                />
+                {proxy.username.trim() !== '' && (
+                  <input
+                    className="settings-input"
+                    type="password"
+                    placeholder={t('settings.proxyPassword')}
+                    value={password}
+                    onChange={(e) => {
+                      setSaved(false);
+                      setPassword(e.target.value);
+                    }}
+                    autoComplete="off"
+                  />
+                )}
+                <input
+                  className="settings-input"
+                  type="text"
+                  placeholder={t('settings.proxyBypass')}
+                  value={proxy.no_proxy}
+                  onChange={(e) => update({ no_proxy: e.target.value })}
+                />
+              </>
+            )}
+            <button className="btn btn-primary" onClick={handleSave} disabled={saving}>
+              {t('settings.saveProxy')}
+            </button>
+            {error && <div className="logs-error">{error}</div>}
+            <p className="credentials-hint">
+              {saved ? t('settings.proxySaved') : t(`settings.proxyHint.${proxy.mode}`)}
+            </p>
+          </div>
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -5,3 +5,6 @@ export { LanguageSection } from './LanguageSection';
 export { AdvancedSection } from './AdvancedSection';
 export { AboutSection } from './AboutSection';
 export { LogsModal } from './LogsModal';
+export { DownloadCredentialsModal } from './DownloadCredentialsModal';
+export { CatalogSourcesModal } from './CatalogSourcesModal';
+export { ProxyModal } from './ProxyModal';
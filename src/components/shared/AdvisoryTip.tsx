@@ -0,0 +1,75 @@
+import { useState, useEffect } from 'react';
+import { useTranslation } from 'react-i18next';
+import { ShieldAlert, X } from 'lucide-react';
+import type { BoardAdvisory } from '../../types';
+import { getFlashedBoardAdvisories } from '../../hooks/useTauri';
+import { STORAGE_KEYS } from '../../config';
+
+/** Key an advisory is dismissed under; a new reason or fix release shows it again */
+function advisoryKey(advisory: BoardAdvisory): string {
+  const reason = advisory.reasons[0];
+  return `${advisory.image}|${reason.kind}|${reason.kind === 'fix_release' ? reason.version : ''}`;
+}
+
+function loadDismissed(): string[] {
+  try {
+    const stored = localStorage.getItem(STORAGE_KEYS.DISMISSED_ADVISORIES);
+    return stored ? JSON.parse(stored) : [];
+  } catch {
+    return [];
+  }
+}
+
+/**
+ * Nudge about a previously flashed image whose board or release reached
+ * end of life, or that got a fix release since
+ *
+ * Shows one advisory at a time under the header; dismissed advisories are
+ * remembered across restarts.
+ */
+export function AdvisoryTip() {
+  const { t, i18n } = useTranslation();
+  const [advisories, setAdvisories] = useState<BoardAdvisory[]>([]);
+  const [dismissed, setDismissed] = useState<string[]>(loadDismissed);
+
+  useEffect(() => {
+    getFlashedBoardAdvisories()
+      .then(setAdvisories)
+      .catch((err) => console.error('Failed to load board advisories:', err));
+  }, []);
+
+  const advisory = advisories.find((a) => !dismissed.includes(advisoryKey(a)));
+  if (!advisory) return null;
+
+  const handleDismiss = () => {
+    const next = [...dismissed, advisoryKey(advisory)];
+    setDismissed(next);
+    try {
+      localStorage.setItem(STORAGE_KEYS.DISMISSED_ADVISORIES, JSON.stringify(next));
+    } catch {
+      // Dismissed for this session only
+    }
+  };
+
+  const reason = advisory.reasons[0];
+  const params = {
+    board: advisory.board_name,
+    date: new Date(advisory.flashed_at).toLocaleDateString(i18n.language, { month: 'long', year: 'numeric' }),
+  };
+  const message =
+    reason.kind === 'board_end_of_support'
+      ? t('advisory.boardEndOfSupport', params)
+      : reason.kind === 'release_end_of_life'
+        ? t('advisory.releaseEndOfLife', { ...params, release: reason.release })
+        : t('advisory.fixRelease', { ...params, version: reason.version });
+
+  return (
+    <div className="motd-tip advisory-tip" role="status">
+      <ShieldAlert size={16} className="motd-icon" />
+      <span className="motd-message">{message}</span>
+      <button className="advisory-dismiss" onClick={handleDismiss} aria-label={t('advisory.dismiss')}>
+        <X size={14} />
+      </button>
+    </div>
+  );
+}
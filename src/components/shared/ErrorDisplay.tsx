@@ -1,9 +1,9 @@
-import { useState } from 'react';
+import { useState, useEffect } from 'react';
 import { Upload, ExternalLink, AlertCircle } from 'lucide-react';
 import { useTranslation } from 'react-i18next';
-import { uploadLogs, openUrl } from '../../hooks/useTauri';
+import { uploadLogs, openUrl, getPasteUrl } from '../../hooks/useTauri';
 import QRCode from 'qrcode';
-import { COLORS, QR_CODE } from '../../config';
+import { COLORS, QR_CODE, LINKS } from '../../config';
 
 interface ErrorDisplayProps {
   error: string;
@@ -17,6 +17,13 @@ export function ErrorDisplay({ error, onRetry, compact = false }: ErrorDisplayPr
   const [pasteUrl, setPasteUrl] = useState<string | null>(null);
   const [qrCodeDataUrl, setQrCodeDataUrl] = useState<string | null>(null);
   const [uploadError, setUploadError] = useState<string | null>(null);
+  const [customEndpoint, setCustomEndpoint] = useState<string | null>(null);
+
+  useEffect(() => {
+    getPasteUrl()
+      .then((url) => setCustomEndpoint(url !== LINKS.PASTE ? url : null))
+      .catch(() => setCustomEndpoint(null));
+  }, []);
 
   async function handleUploadLogs() {
     setUploading(true);
@@ -82,6 +89,12 @@ export function ErrorDisplay({ error, onRetry, compact = false }: ErrorDisplayPr
             </button>
           )}
         </div>
+        {customEndpoint && !pasteUrl && (
+          <div className="error-display-upload-error">
+            <AlertCircle size={12} />
+            <span>{t('errorDisplay.customPasteEndpoint', { url: customEndpoint })}</span>
+          </div>
+        )}
         {uploadError && (
           <div className="error-display-upload-error">
             <AlertCircle size={12} />
@@ -99,6 +112,13 @@ export function ErrorDisplay({ error, onRetry, compact = false }: ErrorDisplayPr
         <span>{error}</span>
       </div>
 
+      {customEndpoint && !pasteUrl && (
+        <div className="upload-error">
+          <AlertCircle size={14} />
+          <span>{t('errorDisplay.customPasteEndpoint', { url: customEndpoint })}</span>
+        </div>
+      )}
+
       {!pasteUrl ? (
         <button
           className="btn btn-secondary upload-logs-btn"
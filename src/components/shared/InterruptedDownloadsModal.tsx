@@ -0,0 +1,85 @@
+import { useState, useEffect } from 'react';
+import { useTranslation } from 'react-i18next';
+import { X } from 'lucide-react';
+import {
+  discardInterruptedDownload,
+  getInterruptedDownloads,
+  resumeInterruptedDownload,
+  type InterruptedDownload,
+} from '../../hooks/useTauri';
+import { formatFileSize } from '../../utils';
+
+/**
+ * Modal shown at launch when the last session quit in the middle of downloads
+ *
+ * Resuming puts a download in the queue, where it continues from the data
+ * already received; discarding deletes that data. Closing the modal leaves
+ * both choices for the next launch.
+ */
+export function InterruptedDownloadsModal() {
+  const { t } = useTranslation();
+  const [downloads, setDownloads] = useState<InterruptedDownload[]>([]);
+  const [dismissed, setDismissed] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    getInterruptedDownloads()
+      .then(setDownloads)
+      .catch((err) => console.error('Failed to load interrupted downloads:', err));
+  }, []);
+
+  if (dismissed || downloads.length === 0) return null;
+
+  async function handle(download: InterruptedDownload, action: 'resume' | 'discard') {
+    setError(null);
+    try {
+      if (action === 'resume') {
+        await resumeInterruptedDownload(download.id);
+      } else {
+        await discardInterruptedDownload(download.id);
+      }
+      setDownloads((current) => current.filter((d) => d.id !== download.id));
+    } catch (err) {
+      setError(String(err));
+    }
+  }
+
+  return (
+    <div className="modal-overlay">
+      <div className="modal modal-content interrupted-modal" onClick={(e) => e.stopPropagation()}>
+        <div className="modal-header">
+          <h2>{t('interrupted.title')}</h2>
+          <button className="modal-close" onClick={() => setDismissed(true)} aria-label="Close">
+            <X size={20} />
+          </button>
+        </div>
+
+        <div className="modal-body">
+          <p className="backup-device">{t('interrupted.description')}</p>
+          <ul className="interrupted-downloads">
+            {downloads.map((download) => (
+              <li key={download.id} className="interrupted-download">
+                <div className="interrupted-download-info">
+                  <strong>{download.filename}</strong>
+                  <span>
+                    {t('interrupted.progress', {
+                      downloaded: formatFileSize(download.downloaded_bytes, '0 MB'),
+                      total: formatFileSize(download.total_bytes, '?'),
+                    })}
+                  </span>
+                </div>
+                <button className="btn btn-secondary btn-sm" onClick={() => handle(download, 'discard')}>
+                  {t('interrupted.discard')}
+                </button>
+                <button className="btn btn-primary btn-sm" onClick={() => handle(download, 'resume')}>
+                  {t('interrupted.resume')}
+                </button>
+              </li>
+            ))}
+          </ul>
+          {error && <div className="logs-error">{error}</div>}
+        </div>
+      </div>
+    </div>
+  );
+}
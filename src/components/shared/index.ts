@@ -2,10 +2,12 @@
  * Shared components exports
  */
 
+export { AdvisoryTip } from './AdvisoryTip';
 export { BoardCardSkeleton, ListItemSkeleton } from './SkeletonCard';
 export { ChangelogModal } from './ChangelogModal';
 export { ConfirmationDialog } from './ConfirmationDialog';
 export { ErrorDisplay } from './ErrorDisplay';
+export { InterruptedDownloadsModal } from './InterruptedDownloadsModal';
 export { LoadingState } from './LoadingState';
 export { MarqueeText } from './MarqueeText';
 export { MotdTip } from './MotdTip';
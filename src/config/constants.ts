@@ -2,19 +2,33 @@
  * Application constants and configuration values
  */
 
-/** Polling intervals in milliseconds */
-export const POLLING = {
-  /** Device connection check interval */
-  DEVICE_CHECK: 2000,
-  /** Download progress update interval */
-  DOWNLOAD_PROGRESS: 250,
-  /** Flash progress update interval */
-  FLASH_PROGRESS: 250,
-} as const;
-
 /** Device type identifiers */
 export type DeviceType = 'system' | 'sd' | 'usb' | 'sata' | 'sas' | 'nvme' | 'hdd';
 
+/** Verification modes: byte comparison or CRC32C of written chunks */
+export type VerifyMode = 'full' | 'fast' | 'hash';
+
+/** Erase modes: clear the partition table area, or also zero the space after the image */
+export type EraseMode = 'quick' | 'full';
+
+/** Download mirror regions tried first; 'auto' leaves the choice to Armbian's redirector */
+export type MirrorRegion = 'auto' | 'europe' | 'north-america' | 'asia';
+
+/** Mirror regions in the order they are offered */
+export const MIRROR_REGIONS: MirrorRegion[] = ['auto', 'europe', 'north-america', 'asia'];
+
+/** Parallel connections per download offered in the settings */
+export const DOWNLOAD_SEGMENT_OPTIONS = [1, 2, 4, 8];
+
+/** Largest device fleet mode may write, in GB; larger drives are never wiped */
+export const FLEET_MAX_SIZE_OPTIONS_GB = [16, 32, 64, 128, 256];
+
+/** Fleet mode size limit selected by default, in GB */
+export const FLEET_DEFAULT_MAX_SIZE_GB = 64;
+
+/** Retries of a mirror after a network error offered in the settings */
+export const DOWNLOAD_RETRY_OPTIONS = [0, 3, 5, 10, 20];
+
 /** External links */
 export const LINKS = {
   /** GitHub repository URL */
@@ -23,6 +37,8 @@ export const LINKS = {
   DOCS: 'https://docs.armbian.com',
   /** Community forum URL */
   FORUM: 'https://forum.armbian.com',
+  /** Default log paste service, see the paste_url setting */
+  PASTE: 'https://paste.armbian.com',
   /** MOTD (Message of the Day) JSON file */
   MOTD: 'https://raw.githubusercontent.com/armbian/os/main/motd.json',
 } as const;
@@ -33,6 +49,8 @@ export const TIMING = {
   MOTD_ROTATION: 30000,
   /** Duration to show "Copied!" notification */
   COPIED_NOTIFICATION: 2000,
+  /** Fleet mode status refresh while devices are being written */
+  FLEET_STATUS_POLL: 1000,
 } as const;
 
 /** Cache configuration */
@@ -57,12 +75,24 @@ export const EVENTS = {
   MOTD_CHANGED: 'armbian-motd-changed',
   /** Fired when general settings change */
   SETTINGS_CHANGED: 'armbian-settings-changed',
+  /** Tauri event carrying backend event bus events */
+  APP_EVENT: 'app-event',
+  /** Tauri event carrying download progress to the downloading window */
+  DOWNLOAD_PROGRESS: 'download://progress',
+  /** Tauri event carrying flash progress to the flashing window */
+  FLASH_PROGRESS: 'flash://progress',
+  /** Tauri event carrying the device list after a hotplug */
+  DEVICES_CHANGED: 'devices://changed',
+  /** Tauri event announcing a board photo that was downloaded or changed */
+  BOARD_IMAGE_READY: 'board_image://ready',
 } as const;
 
 /** Storage key prefixes for sessionStorage/localStorage */
 export const STORAGE_KEYS = {
   /** Prefix for flash failure count (appended with image URL) */
   FLASH_FAILURE_PREFIX: 'flash_failure_count_',
+  /** Advisories dismissed by the user (JSON array of advisory keys) */
+  DISMISSED_ADVISORIES: 'dismissed_board_advisories',
 } as const;
 
 /** Settings store configuration */
@@ -78,6 +108,22 @@ export const SETTINGS = {
     DEVELOPER_MODE: 'developer_mode',
     CACHE_ENABLED: 'cache_enabled',
     CACHE_MAX_SIZE: 'cache_max_size',
+    WRITE_FAILURE_REPORT: 'write_failure_report',
+    VERIFY_MODE: 'verify_mode',
+    ERASE_MODE: 'erase_mode',
+    SMART_WRITE: 'smart_write',
+    SKIP_ZERO_BLOCKS: 'skip_zero_blocks',
+    MIRROR_REGION: 'mirror_region',
+    LOG_RETENTION_COUNT: 'log_retention_count',
+    LOG_RETENTION_DAYS: 'log_retention_days',
+    METERED_ALLOWED_NETWORKS: 'metered_allowed_networks',
+    ALLOW_INSECURE_URLS: 'allow_insecure_urls',
+    DOWNLOAD_SEGMENTS: 'download_segments',
+    DOWNLOAD_RETRIES: 'download_retries',
+    PAUSE_ON_LOW_SPACE: 'pause_on_low_space',
+    DIRECT_WRITE: 'direct_write',
+    /** Layout version, maintained by the backend */
+    SETTINGS_VERSION: 'settings_version',
   },
   /** Default values for settings */
   DEFAULTS: {
@@ -87,6 +133,19 @@ export const SETTINGS = {
     SHOW_UPDATER_MODAL: true,
     DEVELOPER_MODE: false,
     CACHE_ENABLED: true,
+    WRITE_FAILURE_REPORT: false,
+    VERIFY_MODE: 'full' as VerifyMode,
+    ERASE_MODE: 'quick' as EraseMode,
+    SMART_WRITE: false,
+    SKIP_ZERO_BLOCKS: false,
+    MIRROR_REGION: 'auto' as MirrorRegion,
+    LOG_RETENTION_COUNT: 10,
+    LOG_RETENTION_DAYS: 30,
+    ALLOW_INSECURE_URLS: false,
+    DOWNLOAD_SEGMENTS: 4,
+    DOWNLOAD_RETRIES: 5,
+    PAUSE_ON_LOW_SPACE: true,
+    DIRECT_WRITE: false,
   },
 } as const;
 
@@ -130,6 +189,18 @@ export const UI = {
     SEARCH: 18,
     FLASH_STAGE: 32,
   },
+  /** Longest device alias, as enforced by the backend */
+  DEVICE_ALIAS_MAX_LENGTH: 64,
+  /** Longest operation note, as enforced by the backend */
+  OPERATION_NOTE_MAX_LENGTH: 500,
+} as const;
+
+/** Image catalog constants */
+export const CATALOG = {
+  /** Origin of images from the official Armbian catalog */
+  OFFICIAL_ORIGIN: 'armbian',
+  /** Most additional catalogs, as enforced by the backend */
+  MAX_SOURCES: 8,
 } as const;
 
 /** Vendor/manufacturer constants */
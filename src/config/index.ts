@@ -22,9 +22,8 @@ export {
   type BadgeConfig,
 } from './badges';
 
-// Constants and polling intervals
+// Constants
 export {
-  POLLING,
   LINKS,
   TIMING,
   CACHE,
@@ -34,8 +33,17 @@ export {
   COLORS,
   QR_CODE,
   UI,
+  CATALOG,
   VENDOR,
   type DeviceType,
+  type VerifyMode,
+  MIRROR_REGIONS,
+  DOWNLOAD_SEGMENT_OPTIONS,
+  DOWNLOAD_RETRY_OPTIONS,
+  FLEET_MAX_SIZE_OPTIONS_GB,
+  FLEET_DEFAULT_MAX_SIZE_GB,
+  type EraseMode,
+  type MirrorRegion,
 } from './constants';
 
 // Device colors
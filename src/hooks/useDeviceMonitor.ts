@@ -1,6 +1,5 @@
-import { useEffect, useCallback } from 'react';
-import { getBlockDevices } from './useTauri';
-import { POLLING } from '../config';
+import { useEffect } from 'react';
+import { getBlockDevices, onDevicesChanged } from './useTauri';
 import type { BlockDevice } from '../types';
 
 /**
@@ -12,27 +11,36 @@ export function useDeviceMonitor(
   onDeviceDisconnected: () => void,
   enabled: boolean = true
 ) {
-  const checkDevice = useCallback(async () => {
-    if (!selectedDevice) return;
+  useEffect(() => {
+    if (!enabled || !selectedDevice) return;
 
-    try {
-      const devices = await getBlockDevices();
-      const stillConnected = devices.some(d => d.path === selectedDevice.path);
+    let cancelled = false;
+    let unlisten: (() => void) | null = null;
 
+    const checkDevices = (devices: BlockDevice[]) => {
+      if (cancelled) return;
+      const stillConnected = devices.some(d => d.path === selectedDevice.path);
       if (!stillConnected) {
         onDeviceDisconnected();
       }
-    } catch {
-      // Silently ignore polling errors
-    }
-  }, [selectedDevice, onDeviceDisconnected]);
+    };
 
-  useEffect(() => {
-    if (!enabled || !selectedDevice) return;
+    // Check once, then rely on the device list pushed on hotplug
+    getBlockDevices().then(checkDevices).catch(() => {
+      // Silently ignore errors
+    });
 
-    checkDevice();
+    onDevicesChanged(checkDevices).then((fn) => {
+      if (cancelled) {
+        fn();
+      } else {
+        unlisten = fn;
+      }
+    });
 
-    const interval = setInterval(checkDevice, POLLING.DEVICE_CHECK);
-    return () => clearInterval(interval);
-  }, [enabled, selectedDevice, checkDevice]);
+    return () => {
+      cancelled = true;
+      unlisten?.();
+    };
+  }, [enabled, selectedDevice, onDeviceDisconnected]);
 }
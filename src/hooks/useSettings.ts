@@ -5,8 +5,10 @@
  * All operations are wrapped in proper error handling to prevent silent failures.
  */
 
+import { invoke } from '@tauri-apps/api/core';
 import { load } from '@tauri-apps/plugin-store';
 import { CACHE, SETTINGS } from '../config';
+import type { EraseMode, MirrorRegion, VerifyMode } from '../config';
 let storeInstance: Awaited<ReturnType<typeof load>> | null = null;
 let storePromise: Promise<Awaited<ReturnType<typeof load>>> | null = null;
 
@@ -40,6 +42,18 @@ async function getStore() {
   return storePromise;
 }
 
+/**
+ * Write the settings to disk
+ *
+ * The store is opened without auto-save; the backend writes the file in two
+ * phases and keeps the last good copy, so a crash can't leave it broken.
+ *
+ * @throws Error if the settings can't be written
+ */
+export async function saveSettings(): Promise<void> {
+  await invoke('save_settings');
+}
+
 /**
  * Get the current theme preference
  *
@@ -65,7 +79,7 @@ export async function setTheme(theme: string): Promise<void> {
   try {
     const store = await getStore();
     await store.set(SETTINGS.KEYS.THEME, theme);
-    await store.save(); // Explicitly save to ensure persistence
+    await saveSettings(); // Explicitly save to ensure persistence
   } catch (error) {
     throw new Error(`Failed to set theme: ${error}`);
   }
@@ -96,7 +110,7 @@ export async function setLanguage(language: string): Promise<void> {
   try {
     const store = await getStore();
     await store.set(SETTINGS.KEYS.LANGUAGE, language);
-    await store.save(); // Explicitly save to ensure persistence
+    await saveSettings(); // Explicitly save to ensure persistence
   } catch (error) {
     throw new Error(`Failed to set language: ${error}`);
   }
@@ -128,7 +142,7 @@ export async function setShowMotd(show: boolean): Promise<void> {
   try {
     const store = await getStore();
     await store.set(SETTINGS.KEYS.SHOW_MOTD, show);
-    await store.save(); // Explicitly save to ensure persistence
+    await saveSettings(); // Explicitly save to ensure persistence
   } catch (error) {
     throw new Error(`Failed to set MOTD preference: ${error}`);
   }
@@ -160,7 +174,7 @@ export async function setShowUpdaterModal(show: boolean): Promise<void> {
   try {
     const store = await getStore();
     await store.set(SETTINGS.KEYS.SHOW_UPDATER_MODAL, show);
-    await store.save(); // Explicitly save to ensure persistence
+    await saveSettings(); // Explicitly save to ensure persistence
   } catch (error) {
     throw new Error(`Failed to set updater modal preference: ${error}`);
   }
@@ -195,12 +209,356 @@ export async function setDeveloperMode(enabled: boolean): Promise<void> {
   try {
     const store = await getStore();
     await store.set(SETTINGS.KEYS.DEVELOPER_MODE, enabled);
-    await store.save(); // Explicitly save to ensure persistence
+    await saveSettings(); // Explicitly save to ensure persistence
   } catch (error) {
     throw new Error(`Failed to set developer mode preference: ${error}`);
   }
 }
 
+/**
+ * Get whether a failure report is written to the card after a failed verification
+ *
+ * @returns Promise resolving to true if failure reports are enabled
+ * @throws Error if store access fails
+ */
+export async function getWriteFailureReport(): Promise<boolean> {
+  try {
+    const store = await getStore();
+    const value = await store.get<boolean>(SETTINGS.KEYS.WRITE_FAILURE_REPORT);
+    return value ?? SETTINGS.DEFAULTS.WRITE_FAILURE_REPORT;
+  } catch (error) {
+    throw new Error(`Failed to get failure report preference: ${error}`);
+  }
+}
+
+/**
+ * Set whether a failure report is written to the card after a failed verification
+ *
+ * When enabled, a flash-failure.txt with the mismatch offset and details is
+ * written to the card's boot partition if it can be mounted.
+ *
+ * @param enabled - true to write failure reports, false to disable
+ * @throws Error if store access or save fails
+ */
+export async function setWriteFailureReport(enabled: boolean): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.WRITE_FAILURE_REPORT, enabled);
+    await saveSettings();
+  } catch (error) {
+    throw new Error(`Failed to set failure report preference: ${error}`);
+  }
+}
+
+/**
+ * Get the verification mode
+ *
+ * @returns Promise resolving to 'full' (byte comparison), 'fast' (CRC32C) or
+ *   'hash' (SHA-256)
+ * @throws Error if store access fails
+ */
+export async function getVerifyMode(): Promise<VerifyMode> {
+  try {
+    const store = await getStore();
+    const value = await store.get<VerifyMode>(SETTINGS.KEYS.VERIFY_MODE);
+    return value ?? SETTINGS.DEFAULTS.VERIFY_MODE;
+  } catch (error) {
+    throw new Error(`Failed to get verify mode preference: ${error}`);
+  }
+}
+
+/**
+ * Set the verification mode
+ *
+ * 'fast' compares a CRC32C per 4 MB chunk, taken while writing, against the
+ * read-back data instead of re-reading the image. Cards verified without a
+ * preceding write are always compared byte by byte. 'hash' reads the device
+ * back once and compares its SHA-256 with the one taken while writing, without
+ * reading the image again; the device checksum is reported with the progress.
+ *
+ * @param mode - 'full', 'fast' or 'hash'
+ * @throws Error if store access or save fails
+ */
+export async function setVerifyMode(mode: VerifyMode): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.VERIFY_MODE, mode);
+    await saveSettings();
+  } catch (error) {
+    throw new Error(`Failed to set verify mode preference: ${error}`);
+  }
+}
+
+/**
+ * Get the erase mode
+ *
+ * @returns Promise resolving to 'quick' or 'full'
+ * @throws Error if store access fails
+ */
+export async function getEraseMode(): Promise<EraseMode> {
+  try {
+    const store = await getStore();
+    const value = await store.get<EraseMode>(SETTINGS.KEYS.ERASE_MODE);
+    return value ?? SETTINGS.DEFAULTS.ERASE_MODE;
+  } catch (error) {
+    throw new Error(`Failed to get erase mode preference: ${error}`);
+  }
+}
+
+/**
+ * Set the erase mode
+ *
+ * 'full' also zeroes the first 64 MB after the image and checks them during
+ * verification, catching fake-capacity cards that wrap writes around.
+ *
+ * @param mode - 'quick' or 'full'
+ * @throws Error if store access or save fails
+ */
+export async function setEraseMode(mode: EraseMode): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.ERASE_MODE, mode);
+    await saveSettings();
+  } catch (error) {
+    throw new Error(`Failed to set erase mode preference: ${error}`);
+  }
+}
+
+/**
+ * Get whether flashing only writes chunks that differ from the device
+ *
+ * @returns Promise resolving to true if smart write is enabled
+ * @throws Error if store access fails
+ */
+export async function getSmartWrite(): Promise<boolean> {
+  try {
+    const store = await getStore();
+    const value = await store.get<boolean>(SETTINGS.KEYS.SMART_WRITE);
+    return value ?? SETTINGS.DEFAULTS.SMART_WRITE;
+  } catch (error) {
+    throw new Error(`Failed to get smart write preference: ${error}`);
+  }
+}
+
+/**
+ * Set whether flashing only writes chunks that differ from the device
+ *
+ * Each 4 MB chunk is read from the device first and skipped if identical,
+ * which saves time and wear when re-flashing a card with a similar image.
+ *
+ * @param enabled - true to enable smart write
+ * @throws Error if store access or save fails
+ */
+export async function setSmartWrite(enabled: boolean): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.SMART_WRITE, enabled);
+    await saveSettings();
+  } catch (error) {
+    throw new Error(`Failed to set smart write preference: ${error}`);
+  }
+}
+
+/**
+ * Get whether flashing skips all-zero chunks instead of writing them
+ *
+ * @returns Promise resolving to true if zero skipping is enabled
+ * @throws Error if store access fails
+ */
+export async function getSkipZeroBlocks(): Promise<boolean> {
+  try {
+    const store = await getStore();
+    const value = await store.get<boolean>(SETTINGS.KEYS.SKIP_ZERO_BLOCKS);
+    return value ?? SETTINGS.DEFAULTS.SKIP_ZERO_BLOCKS;
+  } catch (error) {
+    throw new Error(`Failed to get zero skipping preference: ${error}`);
+  }
+}
+
+/**
+ * Set whether flashing skips all-zero chunks instead of writing them
+ *
+ * Empty regions of the image are seeked over, which saves write time and
+ * card wear. They keep the device's old content and are not verified.
+ *
+ * @param enabled - true to enable zero skipping
+ * @throws Error if store access or save fails
+ */
+export async function setSkipZeroBlocks(enabled: boolean): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.SKIP_ZERO_BLOCKS, enabled);
+    await saveSettings();
+  } catch (error) {
+    throw new Error(`Failed to set zero skipping preference: ${error}`);
+  }
+}
+
+/**
+ * Get the download mirror region tried first
+ *
+ * @returns Promise resolving to the region, 'auto' by default
+ * @throws Error if store access fails
+ */
+export async function getMirrorRegion(): Promise<MirrorRegion> {
+  try {
+    const store = await getStore();
+    const value = await store.get<MirrorRegion>(SETTINGS.KEYS.MIRROR_REGION);
+    return value ?? SETTINGS.DEFAULTS.MIRROR_REGION;
+  } catch (error) {
+    throw new Error(`Failed to get mirror region: ${error}`);
+  }
+}
+
+/**
+ * Set the download mirror region tried first
+ *
+ * Mirrors in the region are tried before Armbian's redirector; a failed
+ * download moves on to the next mirror either way.
+ *
+ * @param region - Region to prefer, or 'auto'
+ * @throws Error if store access or save fails
+ */
+export async function setMirrorRegion(region: MirrorRegion): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.MIRROR_REGION, region);
+    await saveSettings();
+  } catch (error) {
+    throw new Error(`Failed to set mirror region: ${error}`);
+  }
+}
+
+/**
+ * Get the number of parallel connections per download
+ *
+ * @returns Promise resolving to the connection count, 4 by default
+ * @throws Error if store access fails
+ */
+export async function getDownloadSegments(): Promise<number> {
+  try {
+    const store = await getStore();
+    const value = await store.get<number>(SETTINGS.KEYS.DOWNLOAD_SEGMENTS);
+    return value ?? SETTINGS.DEFAULTS.DOWNLOAD_SEGMENTS;
+  } catch (error) {
+    throw new Error(`Failed to get download segments: ${error}`);
+  }
+}
+
+/**
+ * Set the number of parallel connections per download
+ *
+ * Servers that accept Range requests get the image in as many ranges at
+ * once; 1 downloads on a single connection.
+ *
+ * @param segments - Connection count
+ * @throws Error if store access or save fails
+ */
+export async function setDownloadSegments(segments: number): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.DOWNLOAD_SEGMENTS, segments);
+    await saveSettings();
+  } catch (error) {
+    throw new Error(`Failed to set download segments: ${error}`);
+  }
+}
+
+/**
+ * Get the number of retries of a mirror after a network error
+ *
+ * @returns Promise resolving to the retry count, 5 by default
+ * @throws Error if store access fails
+ */
+export async function getDownloadRetries(): Promise<number> {
+  try {
+    const store = await getStore();
+    const value = await store.get<number>(SETTINGS.KEYS.DOWNLOAD_RETRIES);
+    return value ?? SETTINGS.DEFAULTS.DOWNLOAD_RETRIES;
+  } catch (error) {
+    throw new Error(`Failed to get download retries: ${error}`);
+  }
+}
+
+/**
+ * Set the number of retries of a mirror after a network error
+ *
+ * Each retry waits twice as long as the one before and continues from the
+ * last byte received; 0 moves on to the next mirror at once.
+ *
+ * @param retries - Retry count
+ * @throws Error if store access or save fails
+ */
+export async function setDownloadRetries(retries: number): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.DOWNLOAD_RETRIES, retries);
+    await saveSettings();
+  } catch (error) {
+    throw new Error(`Failed to set download retries: ${error}`);
+  }
+}
+
+/**
+ * Get the number of log files kept
+ *
+ * @returns Promise resolving to the number of kept log files
+ */
+export async function getLogRetentionCount(): Promise<number> {
+  try {
+    const store = await getStore();
+    const value = await store.get<number>(SETTINGS.KEYS.LOG_RETENTION_COUNT);
+    return value ?? SETTINGS.DEFAULTS.LOG_RETENTION_COUNT;
+  } catch (error) {
+    throw new Error(`Failed to get log retention count: ${error}`);
+  }
+}
+
+/**
+ * Set the number of log files kept (1 to 1000), applied at the next start
+ *
+ * @param count - Number of log files to keep
+ */
+export async function setLogRetentionCount(count: number): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.LOG_RETENTION_COUNT, count);
+    await saveSettings();
+  } catch (error) {
+    throw new Error(`Failed to set log retention count: ${error}`);
+  }
+}
+
+/**
+ * Get the age in days after which log files are deleted (0 = never)
+ *
+ * @returns Promise resolving to the maximum log age in days
+ */
+export async function getLogRetentionDays(): Promise<number> {
+  try {
+    const store = await getStore();
+    const value = await store.get<number>(SETTINGS.KEYS.LOG_RETENTION_DAYS);
+    return value ?? SETTINGS.DEFAULTS.LOG_RETENTION_DAYS;
+  } catch (error) {
+    throw new Error(`Failed to get log retention days: ${error}`);
+  }
+}
+
+/**
+ * Set the age in days after which log files are deleted, applied at the next start
+ *
+ * @param days - Maximum log age in days (0 keeps logs regardless of age)
+ */
+export async function setLogRetentionDays(days: number): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.LOG_RETENTION_DAYS, days);
+    await saveSettings();
+  } catch (error) {
+    throw new Error(`Failed to set log retention days: ${error}`);
+  }
+}
+
 // ============================================================================
 // Cache Settings
 // ============================================================================
@@ -238,7 +596,7 @@ export async function setCacheEnabled(enabled: boolean): Promise<void> {
   try {
     const store = await getStore();
     await store.set(SETTINGS.KEYS.CACHE_ENABLED, enabled);
-    await store.save();
+    await saveSettings();
   } catch (error) {
     throw new Error(`Failed to set cache enabled preference: ${error}`);
   }
@@ -277,8 +635,156 @@ export async function setCacheMaxSize(size: number): Promise<void> {
   try {
     const store = await getStore();
     await store.set(SETTINGS.KEYS.CACHE_MAX_SIZE, size);
-    await store.save();
+    await saveSettings();
   } catch (error) {
     throw new Error(`Failed to set cache max size: ${error}`);
   }
 }
+
+/**
+ * Get the metered networks large downloads may start on without a warning
+ *
+ * @returns Promise resolving to network IDs (see checkMeteredDownload)
+ * @throws Error if store access fails
+ */
+export async function getMeteredAllowedNetworks(): Promise<string[]> {
+  try {
+    const store = await getStore();
+    const value = await store.get<string[]>(SETTINGS.KEYS.METERED_ALLOWED_NETWORKS);
+    return value ?? [];
+  } catch (error) {
+    throw new Error(`Failed to get allowed metered networks: ${error}`);
+  }
+}
+
+/**
+ * Set the metered networks large downloads may start on without a warning
+ *
+ * @param networks - Network IDs (see checkMeteredDownload)
+ * @throws Error if store access or save fails
+ */
+export async function setMeteredAllowedNetworks(networks: string[]): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.METERED_ALLOWED_NETWORKS, networks);
+    await saveSettings();
+  } catch (error) {
+    throw new Error(`Failed to set allowed metered networks: ${error}`);
+  }
+}
+
+/**
+ * Remember that large downloads may start on a metered network without a warning
+ *
+ * @param networkId - Network ID from checkMeteredDownload
+ */
+export async function allowMeteredNetwork(networkId: string): Promise<void> {
+  const networks = await getMeteredAllowedNetworks();
+  if (!networks.includes(networkId)) {
+    await setMeteredAllowedNetworks([...networks, networkId]);
+  }
+}
+
+/**
+ * Get whether images and checksums may be downloaded over plain HTTP
+ *
+ * @returns Promise resolving to true if insecure downloads are allowed
+ * @throws Error if store access fails
+ */
+export async function getAllowInsecureUrls(): Promise<boolean> {
+  try {
+    const store = await getStore();
+    const value = await store.get<boolean>(SETTINGS.KEYS.ALLOW_INSECURE_URLS);
+    return value ?? SETTINGS.DEFAULTS.ALLOW_INSECURE_URLS;
+  } catch (error) {
+    throw new Error(`Failed to get insecure downloads preference: ${error}`);
+  }
+}
+
+/**
+ * Set whether images and checksums may be downloaded over plain HTTP
+ *
+ * Meant for local mirrors without HTTPS. The image catalog itself is
+ * always fetched over HTTPS.
+ *
+ * @param allowed - true to allow plain HTTP downloads
+ * @throws Error if store access or save fails
+ */
+export async function setAllowInsecureUrls(allowed: boolean): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.ALLOW_INSECURE_URLS, allowed);
+    await saveSettings();
+  } catch (error) {
+    throw new Error(`Failed to set insecure downloads preference: ${error}`);
+  }
+}
+
+/**
+ * Get whether downloads wait for space to be freed when their volume runs low
+ *
+ * @returns Promise resolving to true if downloads pause on low space
+ * @throws Error if store access fails
+ */
+export async function getPauseOnLowSpace(): Promise<boolean> {
+  try {
+    const store = await getStore();
+    const value = await store.get<boolean>(SETTINGS.KEYS.PAUSE_ON_LOW_SPACE);
+    return value ?? SETTINGS.DEFAULTS.PAUSE_ON_LOW_SPACE;
+  } catch (error) {
+    throw new Error(`Failed to get low space preference: ${error}`);
+  }
+}
+
+/**
+ * Set whether downloads wait for space to be freed when their volume runs low
+ *
+ * Otherwise downloads only warn and carry on until a write fails.
+ *
+ * @param enabled - true to pause downloads on low space
+ * @throws Error if store access or save fails
+ */
+export async function setPauseOnLowSpace(enabled: boolean): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.PAUSE_ON_LOW_SPACE, enabled);
+    await saveSettings();
+  } catch (error) {
+    throw new Error(`Failed to set low space preference: ${error}`);
+  }
+}
+
+/**
+ * Get whether downloads are flashed as they arrive instead of cached first
+ *
+ * @returns Promise resolving to true if direct write is enabled
+ * @throws Error if store access fails
+ */
+export async function getDirectWrite(): Promise<boolean> {
+  try {
+    const store = await getStore();
+    const value = await store.get<boolean>(SETTINGS.KEYS.DIRECT_WRITE);
+    return value ?? SETTINGS.DEFAULTS.DIRECT_WRITE;
+  } catch (error) {
+    throw new Error(`Failed to get direct write preference: ${error}`);
+  }
+}
+
+/**
+ * Set whether downloads are flashed as they arrive instead of cached first
+ *
+ * Needs no disk space for the image, but an interrupted download has to
+ * start over and verification compares checksums only.
+ *
+ * @param enabled - true to write downloads straight to the device
+ * @throws Error if store access or save fails
+ */
+export async function setDirectWrite(enabled: boolean): Promise<void> {
+  try {
+    const store = await getStore();
+    await store.set(SETTINGS.KEYS.DIRECT_WRITE, enabled);
+    await saveSettings();
+  } catch (error) {
+    throw new Error(`Failed to set direct write preference: ${error}`);
+  }
+}
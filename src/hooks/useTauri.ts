@@ -1,8 +1,22 @@
-import { invoke } from '@tauri-apps/api/core';
-import type { BoardInfo, ImageInfo, BlockDevice, DownloadProgress, FlashProgress, CustomImageInfo } from '../types';
+import { convertFileSrc, invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow';
+import type { BackupSummary, BoardInfo, CapacityReport, CompletionCues, CueOutcome, ImageInfo, BlockDevice, DeviceLock, Job, DownloadProgress, EraseFilesystem, FleetSettings, FleetStatus, FlashProgress, CustomImageInfo, CatalogSource, ProxySettings, Settings, SettingsRepair, ImageUpdateInfo, BoardAdvisory, BoardFilters, BoardList, ApplicationInfo, OperationEstimate, DeviceReliability, Mirror, MirrorStats, AuthScheme, AuthSource, Customization, CustomizationReport, NetworkConfig, SecondaryImage, DeviceVerification, ImageDetails, ImagePartition, PrivilegeStatus, AppEvent, SystemInfo } from '../types';
+import { EVENTS } from '../config';
+
+/**
+ * Get the filtered board list with per-facet counts
+ *
+ * @param filters - Optional vendor ID, support levels and search text
+ * @returns Promise resolving to matching boards, sorted for display
+ */
+export async function getBoardList(filters: BoardFilters = {}): Promise<BoardList> {
+  return invoke('get_boards', { ...filters });
+}
 
 export async function getBoards(): Promise<BoardInfo[]> {
-  return invoke('get_boards');
+  const list = await getBoardList();
+  return list.boards;
 }
 
 export async function getImagesForBoard(
@@ -21,18 +35,151 @@ export async function getImagesForBoard(
   });
 }
 
+/**
+ * Get the URL of a board's cached photo
+ *
+ * Returns at once, null while the photo has never been downloaded; the
+ * backend refreshes it in the background and announces it through
+ * onBoardImageReady.
+ */
 export async function getBoardImageUrl(boardSlug: string): Promise<string | null> {
-  return invoke('get_board_image_url', { boardSlug });
+  const path = await invoke<string | null>('get_board_image_url', { boardSlug });
+  return path ? convertFileSrc(path) : null;
+}
+
+/**
+ * Subscribe to board photos that were downloaded or changed
+ *
+ * The URL changes with every announcement, so a replaced photo is not
+ * served from the webview's cache.
+ * @returns Function that stops listening
+ */
+export async function onBoardImageReady(
+  handler: (slug: string, url: string) => void
+): Promise<UnlistenFn> {
+  return listen<{ slug: string; path: string }>(EVENTS.BOARD_IMAGE_READY, (event) =>
+    handler(event.payload.slug, `${convertFileSrc(event.payload.path)}?v=${Date.now()}`)
+  );
+}
+
+/**
+ * Delete every cached board photo
+ *
+ * Boards show their placeholder until the photo is downloaded again.
+ */
+export async function clearBoardImageCache(): Promise<void> {
+  return invoke('clear_board_image_cache');
 }
 
 export async function getBlockDevices(): Promise<BlockDevice[]> {
   return invoke('get_block_devices');
 }
 
+/**
+ * Devices locked by a running flash, verify, backup or customization, from
+ * any window
+ */
+export async function getDeviceLocks(): Promise<DeviceLock[]> {
+  return invoke('get_device_locks');
+}
+
+/**
+ * Subscribe to the device list pushed when removable media is inserted or removed
+ *
+ * @returns Function that stops listening
+ */
+export async function onDevicesChanged(
+  handler: (devices: BlockDevice[]) => void
+): Promise<UnlistenFn> {
+  return listen<BlockDevice[]>(EVENTS.DEVICES_CHANGED, (event) => handler(event.payload));
+}
+
+/**
+ * Check whether a newer build exists for a cached or previously flashed image
+ *
+ * @param filename - Armbian image filename (can include path)
+ * @returns Promise resolving to update info, or null if not an Armbian filename
+ */
+export async function checkImageUpdate(filename: string): Promise<ImageUpdateInfo | null> {
+  return invoke('check_image_update', { filename });
+}
+
+/**
+ * Get advisories for previously flashed images whose board or release
+ * reached end of life, or that got a fix release since
+ */
+export async function getFlashedBoardAdvisories(): Promise<BoardAdvisory[]> {
+  return invoke('get_flashed_board_advisories');
+}
+
+/**
+ * Get display metadata for known preinstalled applications
+ */
+export async function getApplicationCatalog(): Promise<ApplicationInfo[]> {
+  return invoke('get_application_catalog');
+}
+
+/**
+ * Subscribe to backend event bus events (progress is rate limited)
+ *
+ * @returns Function that stops listening
+ */
+export async function onAppEvent(handler: (event: AppEvent) => void): Promise<UnlistenFn> {
+  return listen<AppEvent>(EVENTS.APP_EVENT, (event) => handler(event.payload));
+}
+
 export async function requestWriteAuthorization(devicePath: string): Promise<boolean> {
   return invoke('request_write_authorization', { devicePath });
 }
 
+/**
+ * Get the process privileges and the prompt that writing will trigger
+ */
+export async function getPrivilegeStatus(): Promise<PrivilegeStatus> {
+  return invoke('get_privilege_status');
+}
+
+export type ProbeWarning =
+  | { kind: 'unreachable'; error: string }
+  | { kind: 'http_status'; status: number }
+  | { kind: 'size_mismatch'; expected: number; actual: number };
+
+export interface DownloadProbe {
+  /** Status of the HEAD response, null if unreachable */
+  status: number | null;
+  content_length: number | null;
+  /** The server accepts Range requests */
+  accepts_ranges: boolean;
+  mirror_host: string | null;
+  warnings: ProbeWarning[];
+}
+
+/**
+ * Check with a HEAD request that a download is live and matches the catalog size
+ *
+ * @param fileSize - Catalog size, 0 if unknown
+ */
+export async function probeImageDownload(fileUrl: string, fileSize: number): Promise<DownloadProbe> {
+  return invoke('probe_image_download', { fileUrl, fileSize });
+}
+
+export interface NetworkStatus {
+  metered: boolean;
+  /** Stable network identifier, stored when the user always allows it */
+  network_id: string | null;
+  network_name: string | null;
+}
+
+export interface MeteredDownloadCheck {
+  /** Warn before downloading: large image, metered network not allowed yet */
+  warn: boolean;
+  network: NetworkStatus;
+}
+
+export async function checkMeteredDownload(fileUrl: string, fileSize: number): Promise<MeteredDownloadCheck> {
+  return invoke('check_metered_download', { fileUrl, fileSize });
+}
+
 export async function downloadImage(fileUrl: string, fileUrlSha?: string | null): Promise<string> {
   return invoke('download_image', { fileUrl, fileUrlSha });
 }
@@ -41,26 +188,238 @@ export async function getDownloadProgress(): Promise<DownloadProgress> {
   return invoke('get_download_progress');
 }
 
+/**
+ * Subscribe to download progress pushed to this window (about 4 Hz)
+ *
+ * @returns Function that stops listening
+ */
+export async function onDownloadProgress(
+  handler: (progress: DownloadProgress) => void
+): Promise<UnlistenFn> {
+  return getCurrentWebviewWindow().listen<DownloadProgress>(EVENTS.DOWNLOAD_PROGRESS, (event) =>
+    handler(event.payload)
+  );
+}
+
+/**
+ * Flash an image to a device
+ * @param confirmationToken - Token from confirmDangerousTarget, required for fixed disks
+ */
 export async function flashImage(
   imagePath: string,
   devicePath: string,
-  verify: boolean = true
+  verify: boolean = true,
+  confirmationToken?: string,
+  secondary?: SecondaryImage | null
+): Promise<void> {
+  return invoke('flash_image', {
+    imagePath,
+    devicePath,
+    verify,
+    confirmationToken: confirmationToken ?? null,
+    secondary: secondary ? { path: secondary.path, offset: secondary.offset } : null,
+  });
+}
+
+/**
+ * Download an image and flash it as it arrives, without caching it
+ *
+ * The image's checksum is required; verification compares hashes only,
+ * since the download cannot be read back a second time.
+ * @param confirmationToken - Token from confirmDangerousTarget, required for fixed disks
+ */
+export async function flashImageDirect(
+  fileUrl: string,
+  fileUrlSha: string | null,
+  devicePath: string,
+  verify: boolean = true,
+  confirmationToken?: string
+): Promise<void> {
+  return invoke('flash_image_direct', {
+    fileUrl,
+    fileUrlSha,
+    devicePath,
+    verify,
+    confirmationToken: confirmationToken ?? null,
+  });
+}
+
+/**
+ * Write part of a file at a byte offset of a device, e.g. u-boot at 32 KiB
+ * @param length - Bytes to write from the start of the file, the whole file if omitted
+ * @param confirmationToken - Token from confirmDangerousTarget, required for fixed disks
+ */
+export async function flashRange(
+  inputPath: string,
+  devicePath: string,
+  deviceOffset: number,
+  length?: number,
+  verify: boolean = true,
+  confirmationToken?: string
 ): Promise<void> {
-  return invoke('flash_image', { imagePath, devicePath, verify });
+  return invoke('flash_range', {
+    inputPath,
+    devicePath,
+    deviceOffset,
+    length: length ?? null,
+    verify,
+    confirmationToken: confirmationToken ?? null,
+  });
+}
+
+/** Confirmation of a fixed (non-removable) disk as write target */
+export interface TargetConfirmation {
+  token: string;
+  /** Device details the user acknowledged */
+  device: BlockDevice;
+  expires_in_secs: number;
+}
+
+/**
+ * Confirm a fixed, non-system disk; flashImage refuses such disks without the token
+ */
+export async function confirmDangerousTarget(devicePath: string): Promise<TargetConfirmation> {
+  return invoke('confirm_dangerous_target', { devicePath });
 }
 
 export async function getFlashProgress(): Promise<FlashProgress> {
   return invoke('get_flash_progress');
 }
 
+/**
+ * Subscribe to flash and verify progress pushed to this window (about 4 Hz)
+ *
+ * @returns Function that stops listening
+ */
+export async function onFlashProgress(
+  handler: (progress: FlashProgress) => void
+): Promise<UnlistenFn> {
+  return getCurrentWebviewWindow().listen<FlashProgress>(EVENTS.FLASH_PROGRESS, (event) =>
+    handler(event.payload)
+  );
+}
+
+/** Stage an operation was in when a throughput sample was taken */
+export type TransferPhase = 'download' | 'write' | 'verify';
+
+export interface ThroughputSample {
+  /** Milliseconds since the operation started */
+  elapsed_ms: number;
+  phase: TransferPhase;
+  /** Bytes processed in this phase so far */
+  processed_bytes: number;
+  /** Throughput since the previous sample */
+  bytes_per_sec: number;
+}
+
+export interface OperationTimeseries {
+  operation_id: string;
+  /** No more samples will be added */
+  finished: boolean;
+  sample_interval_ms: number;
+  /** Oldest first */
+  samples: ThroughputSample[];
+}
+
+/**
+ * Get recent throughput samples of an operation, for a speed graph
+ * @param id - operation_id from getDownloadProgress or getFlashProgress
+ * @param seconds - Span to return, defaults to the last minute
+ */
+export async function getOperationTimeseries(
+  id: string,
+  seconds?: number
+): Promise<OperationTimeseries> {
+  return invoke('get_operation_timeseries', { id, seconds: seconds ?? null });
+}
+
+/** Outcome of one self-test stage */
+export interface SelftestStageReport {
+  stage: 'fixture' | 'download' | 'decompress' | 'flash' | 'verify' | 'cache_evict';
+  passed: boolean;
+  duration_ms: number;
+  /** What was checked, or why the stage failed */
+  detail: string;
+}
+
+/** Self-test report; stages after the first failure are not run */
+export interface SelftestReport {
+  app_version: string;
+  os: string;
+  started_at: string;
+  scratch_path: string;
+  passed: boolean;
+  stages: SelftestStageReport[];
+}
+
+/**
+ * Run the end-to-end self-test (local download, decompress, write to a file, verify, cache eviction)
+ *
+ * Not exposed in the UI. The same test runs with `armbian-imager --selftest [scratch-file]`.
+ * @param scratchPath - File to write to, must not exist yet
+ */
+export async function runSelftest(scratchPath: string): Promise<SelftestReport> {
+  return invoke('run_selftest', { scratchPath });
+}
+
+export interface BenchmarkResult {
+  group: string;
+  name: string;
+  bytes: number;
+  elapsed_ms: number;
+  mb_per_sec: number;
+  output_bytes: number | null;
+}
+
+export interface BenchmarkReport {
+  started_at: string;
+  cpu_cores: number;
+  sample_bytes: number;
+  results: BenchmarkResult[];
+}
+
+/**
+ * Benchmark XZ threads, zstd levels, hashes and chunk sizes on this machine
+ *
+ * Developer mode only; takes tens of seconds.
+ */
+export async function runBenchmark(): Promise<BenchmarkReport> {
+  return invoke('run_benchmark');
+}
+
 export async function cancelOperation(): Promise<void> {
   return invoke('cancel_operation');
 }
 
+/**
+ * Running and recently finished downloads, flashes and verifications, from
+ * any window
+ */
+export async function getJobs(): Promise<Job[]> {
+  return invoke('get_jobs');
+}
+
+/**
+ * Cancel one job by its operation ID, leaving the others running
+ */
+export async function cancelJob(jobId: string): Promise<void> {
+  return invoke('cancel_job', { jobId });
+}
+
 export async function deleteDownloadedImage(imagePath: string): Promise<void> {
   return invoke('delete_downloaded_image', { imagePath });
 }
 
+/**
+ * Save a copy of a cached image to a file chosen in a save dialog
+ *
+ * @param imagePath - Path to the cached image file
+ * @returns The saved path, or null if the user cancelled
+ */
+export async function exportCachedImage(imagePath: string): Promise<string | null> {
+  return invoke('export_cached_image', { imagePath });
+}
+
 /**
  * Force delete a cached image regardless of cache settings
  *
@@ -93,6 +452,288 @@ export async function cleanupFailedDownload(): Promise<void> {
   return invoke('cleanup_failed_download');
 }
 
+/**
+ * Estimate download, decompress, write and verify durations from past operations
+ */
+export async function estimateOperation(image: ImageInfo, device: BlockDevice): Promise<OperationEstimate> {
+  return invoke('estimate_operation', { image, device });
+}
+
+/**
+ * Get flash statistics for a device serial; likely_faulty flags repeated failures
+ */
+export async function getDeviceReliability(serial: string): Promise<DeviceReliability> {
+  return invoke('get_device_reliability', { serial });
+}
+
+/**
+ * Get download statistics per mirror host, healthiest first
+ */
+export async function getMirrorStats(): Promise<MirrorStats[]> {
+  return invoke('get_mirror_stats');
+}
+
+/**
+ * Probe the latency of every known download mirror
+ *
+ * The results also order the mirrors tried by later downloads.
+ */
+export async function measureMirrors(): Promise<Mirror[]> {
+  return invoke('measure_mirrors');
+}
+
+/**
+ * Get all settings
+ */
+export async function getSettings(): Promise<Settings> {
+  return invoke('get_settings');
+}
+
+/**
+ * Change several settings at once
+ *
+ * Nothing changes if one of them is invalid.
+ *
+ * @param patch - Settings to change
+ * @returns Promise resolving to all settings
+ */
+export async function updateSettings(patch: Partial<Omit<Settings, 'settings_version'>>): Promise<Settings> {
+  return invoke('update_settings', { patch });
+}
+
+/**
+ * Fall back to the last good copy of the settings file if it is broken
+ */
+export async function repairSettings(): Promise<SettingsRepair> {
+  return invoke('repair_settings');
+}
+
+/**
+ * Get the proxy for all HTTP traffic
+ */
+export async function getProxy(): Promise<ProxySettings> {
+  return invoke('get_proxy');
+}
+
+/**
+ * Set the proxy for all HTTP traffic
+ *
+ * @param proxy - Proxy settings
+ * @param password - New proxy password, or null to keep the stored one
+ * @returns Promise resolving to the normalized settings
+ */
+export async function setProxy(proxy: ProxySettings, password: string | null): Promise<ProxySettings> {
+  return invoke('set_proxy', { proxy, password });
+}
+
+/**
+ * Get the catalogs merged with the official Armbian catalog
+ */
+export async function getCatalogSources(): Promise<CatalogSource[]> {
+  return invoke('get_catalog_sources');
+}
+
+/**
+ * Replace the catalogs merged with the official Armbian catalog
+ *
+ * The cached catalog is dropped, so board lists reload with the new set.
+ *
+ * @returns The sources as stored, with names trimmed and URLs normalized
+ */
+export async function setCatalogSources(sources: CatalogSource[]): Promise<CatalogSource[]> {
+  return invoke('set_catalog_sources', { sources });
+}
+
+/**
+ * Get the download hosts with stored credentials
+ */
+export async function getDownloadCredentials(): Promise<AuthSource[]> {
+  return invoke('get_download_credentials');
+}
+
+/**
+ * Store credentials for a gated download host, replacing any existing ones
+ *
+ * Credentials are only sent over HTTPS to the matching host.
+ *
+ * @param host - Host name, or "*.example.com" for every subdomain
+ * @param scheme - Bearer token or basic auth
+ * @param username - User name, required for basic auth
+ * @param secret - Token or password, kept in the OS keychain
+ */
+export async function setDownloadCredentials(
+  host: string,
+  scheme: AuthScheme,
+  username: string | null,
+  secret: string
+): Promise<AuthSource> {
+  return invoke('set_download_credentials', { host, scheme, username, secret });
+}
+
+/**
+ * Forget the credentials of a download host
+ */
+export async function removeDownloadCredentials(host: string): Promise<void> {
+  return invoke('remove_download_credentials', { host });
+}
+
+/**
+ * Verify a device against an image without writing; reuses a recent verification when valid
+ */
+export async function verifyDevice(imagePath: string, devicePath: string): Promise<DeviceVerification> {
+  return invoke('verify_device', { imagePath, devicePath });
+}
+
+/**
+ * Back up a device to a compressed image file chosen in a save dialog
+ * @returns The finished backup, or null if no file was chosen
+ */
+export async function backupDevice(devicePath: string): Promise<BackupSummary | null> {
+  return invoke('backup_device', { devicePath });
+}
+
+/**
+ * Inspect an image: partition map, filesystems, Armbian version and sizes
+ */
+export async function inspectImage(path: string): Promise<ImageDetails> {
+  return invoke('inspect_image', { path });
+}
+
+/**
+ * List the partitions of an image
+ */
+export async function listImagePartitions(imagePath: string): Promise<ImagePartition[]> {
+  return invoke('list_image_partitions', { imagePath });
+}
+
+/**
+ * Extract a partition of an image to a raw file
+ * @param index - Partition number, as listed by listImagePartitions
+ * @returns Size of the extracted partition
+ */
+export async function extractPartition(imagePath: string, index: number, dest: string): Promise<number> {
+  return invoke('extract_partition', { imagePath, index, dest });
+}
+
+/**
+ * Mount a partition of a device, e.g. the boot partition after flashing
+ * @param index - Partition number, 1 for the first
+ * @param reveal - Open the mount point in the file manager
+ * @returns The mount point; it is unmounted before the next operation on the device
+ */
+export async function mountPartition(devicePath: string, index: number, reveal: boolean = true): Promise<string> {
+  return invoke('mount_partition', { devicePath, index, reveal });
+}
+
+/**
+ * Get the names given to devices, keyed by serial number
+ */
+export async function getDeviceAliases(): Promise<Record<string, string>> {
+  return invoke('get_device_aliases');
+}
+
+/**
+ * Name the device with this serial; an empty alias removes the name
+ */
+export async function setDeviceAlias(serial: string, alias: string): Promise<void> {
+  return invoke('set_device_alias', { serial, alias });
+}
+
+/**
+ * Get the cues that announce finished operations
+ */
+export async function getCompletionCues(): Promise<CompletionCues> {
+  return invoke('get_completion_cues');
+}
+
+export async function setCompletionCues(cues: CompletionCues): Promise<void> {
+  return invoke('set_completion_cues', { cues });
+}
+
+/**
+ * Play the completion sound for an outcome, regardless of the settings
+ */
+export async function previewCompletionCue(outcome: CueOutcome): Promise<void> {
+  return invoke('preview_completion_cue', { outcome });
+}
+
+/**
+ * Erase a device to one empty partition
+ * @param filesystem - Defaults to exFAT above 32 GiB, FAT32 otherwise
+ * @param confirmationToken - Token from confirmDangerousTarget, required for fixed disks
+ * @returns The filesystem name, e.g. "FAT32"
+ */
+export async function eraseDevice(
+  devicePath: string,
+  filesystem?: EraseFilesystem,
+  confirmationToken?: string
+): Promise<string> {
+  return invoke('erase_device', {
+    devicePath,
+    filesystem: filesystem ?? null,
+    confirmationToken: confirmationToken ?? null,
+  });
+}
+
+/**
+ * Start fleet mode: every matching device inserted from now on is flashed, customized and ejected
+ */
+export async function startFleetMode(settings: FleetSettings): Promise<FleetStatus> {
+  return invoke('start_fleet_mode', { settings });
+}
+
+/**
+ * Stop fleet mode, cancelling running flashes and queued devices
+ */
+export async function stopFleetMode(): Promise<FleetStatus> {
+  return invoke('stop_fleet_mode');
+}
+
+/**
+ * Get the fleet mode status, with the progress of running flashes
+ */
+export async function getFleetStatus(): Promise<FleetStatus> {
+  return invoke('get_fleet_status');
+}
+
+/**
+ * Check a device for fake capacity by writing markers across it and reading them back
+ * @param confirmationToken - Token from confirmDangerousTarget, required for fixed disks
+ */
+export async function checkDeviceCapacity(devicePath: string, confirmationToken?: string): Promise<CapacityReport> {
+  return invoke('check_device_capacity', {
+    devicePath,
+    confirmationToken: confirmationToken ?? null,
+  });
+}
+
+/**
+ * Unmount and eject a device (powering it off where supported) so it can be removed safely
+ */
+export async function ejectDevice(devicePath: string): Promise<void> {
+  return invoke('eject_device', { devicePath });
+}
+
+/**
+ * Write first-boot customization to a device that was just flashed
+ */
+export async function applyCustomization(
+  devicePath: string,
+  customization: Customization
+): Promise<CustomizationReport> {
+  return invoke('apply_customization', { devicePath, customization });
+}
+
+/**
+ * Write Wi-Fi credentials for the first boot to a device that was just flashed
+ */
+export async function applyNetworkConfig(
+  devicePath: string,
+  config: NetworkConfig
+): Promise<CustomizationReport> {
+  return invoke('apply_network_config', { devicePath, config });
+}
+
 export async function deleteDecompressedCustomImage(imagePath: string): Promise<void> {
   return invoke('delete_decompressed_custom_image', { imagePath });
 }
@@ -119,8 +760,143 @@ export async function checkNeedsDecompression(imagePath: string): Promise<boolea
   return invoke('check_needs_decompression', { imagePath });
 }
 
-export async function decompressCustomImage(imagePath: string): Promise<string> {
-  return invoke('decompress_custom_image', { imagePath });
+export interface StorageInfo {
+  path: string;
+  fs_type: string;
+  total_bytes: number;
+  available_bytes: number;
+  ram_backed: boolean;
+}
+
+export interface DecompressPlacement {
+  dir: string;
+  storage: StorageInfo | null;
+  required_bytes: number;
+  size_is_estimate: boolean;
+  warning: string | null;
+  alternatives: StorageInfo[];
+}
+
+/**
+ * Where a custom image would be decompressed
+ *
+ * The cache is avoided when it is RAM-backed (tmpfs, zram) or too small;
+ * `warning` says why, `alternatives` lists other directories that fit.
+ */
+export async function getDecompressPlacement(imagePath: string): Promise<DecompressPlacement> {
+  return invoke('get_decompress_placement', { imagePath });
+}
+
+/**
+ * Decompress a custom image
+ *
+ * @param targetDir - A directory from getDecompressPlacement, chosen automatically when omitted
+ */
+/**
+ * Check a custom image against its expected SHA256 before flashing
+ *
+ * @param expectedSha256 - Pasted hash; defaults to the checksum file next to the image
+ * @returns Image info with sha_verification set, or null there when no hash is known
+ */
+export async function verifyCustomImage(
+  imagePath: string,
+  expectedSha256?: string | null
+): Promise<CustomImageInfo> {
+  return invoke('verify_custom_image', { imagePath, expectedSha256: expectedSha256 ?? null });
+}
+
+export async function decompressCustomImage(imagePath: string, targetDir?: string): Promise<string> {
+  return invoke('decompress_custom_image', { imagePath, targetDir: targetDir ?? null });
+}
+
+export type QueuePriority = 'low' | 'normal' | 'high';
+
+export type QueueItemStatus = 'queued' | 'paused' | 'downloading' | 'completed' | 'failed';
+
+export interface QueueItem {
+  id: string;
+  file_url: string;
+  file_url_sha: string | null;
+  label: string;
+  priority: QueuePriority;
+  status: QueueItemStatus;
+  added_at: string;
+  output_path: string | null;
+  error: string | null;
+  downloaded_bytes: number;
+  total_bytes: number;
+}
+
+export interface DownloadQueue {
+  items: QueueItem[];
+  max_parallel: number;
+  /** Combined limit of queued downloads in KB/s, 0 for none */
+  bandwidth_kbps: number;
+}
+
+export async function getDownloadQueue(): Promise<DownloadQueue> {
+  return invoke('get_download_queue');
+}
+
+export async function enqueueDownload(
+  fileUrl: string,
+  fileUrlSha: string | null,
+  label: string,
+  priority?: QueuePriority
+): Promise<QueueItem> {
+  return invoke('enqueue_download', { fileUrl, fileUrlSha, label, priority: priority ?? null });
+}
+
+export async function removeQueueItem(id: string): Promise<void> {
+  return invoke('remove_queue_item', { id });
+}
+
+export async function moveQueueItem(id: string, index: number): Promise<void> {
+  return invoke('move_queue_item', { id, index });
+}
+
+/** Pausing a running download cancels it; resuming a failed item retries it */
+export async function setQueueItemPaused(id: string, paused: boolean): Promise<void> {
+  return invoke('set_queue_item_paused', { id, paused });
+}
+
+export async function setQueueItemPriority(id: string, priority: QueuePriority): Promise<void> {
+  return invoke('set_queue_item_priority', { id, priority });
+}
+
+export async function setDownloadQueueOptions(maxParallel: number, bandwidthKbps: number): Promise<void> {
+  return invoke('set_download_queue_options', { maxParallel, bandwidthKbps });
+}
+
+export async function clearCompletedQueueItems(): Promise<number> {
+  return invoke('clear_completed_queue_items');
+}
+
+/** A download the last session quit in the middle of */
+export interface InterruptedDownload {
+  id: string;
+  url: string;
+  sha_url: string | null;
+  filename: string;
+  output_dir: string;
+  /** 0 if the server's size was not known yet */
+  total_bytes: number;
+  downloaded_bytes: number;
+  started_at: string;
+  pid: number;
+}
+
+export async function getInterruptedDownloads(): Promise<InterruptedDownload[]> {
+  return invoke('get_interrupted_downloads');
+}
+
+/** Queue an interrupted download, which continues from its partial file */
+export async function resumeInterruptedDownload(id: string): Promise<QueueItem> {
+  return invoke('resume_interrupted_download', { id });
+}
+
+export async function discardInterruptedDownload(id: string): Promise<void> {
+  return invoke('discard_interrupted_download', { id });
 }
 
 export interface UploadResult {
@@ -128,14 +904,96 @@ export interface UploadResult {
   key: string;
 }
 
-export async function uploadLogs(): Promise<UploadResult> {
-  return invoke('upload_logs');
+/** Part of the logs to upload; times are RFC 3339 */
+export interface LogSelection {
+  operationId?: string;
+  since?: string;
+  until?: string;
 }
 
+export async function uploadLogs(selection?: LogSelection): Promise<UploadResult> {
+  return invoke('upload_logs', { selection: selection ?? null });
+}
+
+/** Operation IDs tagged in the current session log, oldest first */
+export async function listLogOperations(): Promise<string[]> {
+  return invoke('list_log_operations');
+}
+
+/** Rejection of openUrl: invalid URL, scheme other than http(s)/mailto, or launch failure */
+export type OpenUrlError =
+  | { kind: 'invalid'; reason: string }
+  | { kind: 'unsupported_scheme'; scheme: string }
+  | { kind: 'launch_failed'; message: string };
+
 export async function openUrl(url: string): Promise<void> {
   return invoke('open_url', { url });
 }
 
+/** System locale and the closest supported app language */
+export interface LocaleInfo {
+  system_locale: string;
+  language: string;
+  /** The negotiated language is written right to left */
+  rtl: boolean;
+  /** The system language is written right to left, even if not supported */
+  system_rtl: boolean;
+}
+
+/**
+ * Negotiate the app language from the system locale
+ * @param supported - Language codes to choose from, defaults to the backend list
+ */
+export async function getLocaleInfo(supported?: string[]): Promise<LocaleInfo> {
+  return invoke('get_locale_info', { supported: supported ?? null });
+}
+
+/** Onboarding steps, completed in this order */
+export type OnboardingStep = 'welcome' | 'write_warning' | 'defaults' | 'complete';
+
+export interface OnboardingState {
+  /** Next step to complete, 'complete' once onboarding is done */
+  step: OnboardingStep;
+  write_warning_accepted_at: string | null;
+  completed_at: string | null;
+}
+
+export async function getOnboardingState(): Promise<OnboardingState> {
+  return invoke('get_onboarding_state');
+}
+
+/**
+ * Complete an onboarding step; fails if an earlier step is still open
+ */
+export async function advanceOnboarding(step: Exclude<OnboardingStep, 'complete'>): Promise<OnboardingState> {
+  return invoke('advance_onboarding', { step });
+}
+
+/**
+ * Record that the user accepted the erase warning, required by flash_image
+ *
+ * Reaching the device confirmation implies the welcome (home page) was seen.
+ */
+export async function acceptWriteWarning(): Promise<OnboardingState> {
+  await advanceOnboarding('welcome');
+  return advanceOnboarding('write_warning');
+}
+
+/**
+ * Open another app window; each window owns its download and flash
+ * @returns Label of the new window
+ */
+export async function openNewWindow(): Promise<string> {
+  return invoke('open_new_window');
+}
+
+/** Location that revealPath can open in the file manager */
+export type RevealKind = 'logs' | 'cache' | 'last_download';
+
+export async function revealPath(kind: RevealKind): Promise<void> {
+  return invoke('reveal_path', { kind });
+}
+
 export async function logInfo(module: string, message: string): Promise<void> {
   return invoke('log_from_frontend', { module, message });
 }
@@ -191,9 +1049,9 @@ export async function setLanguage(language: string): Promise<void> {
 }
 
 /**
- * Get the real system platform and architecture
+ * Get the real system platform and architecture, and the sandbox the app runs in
  */
-export async function getSystemInfo(): Promise<{ platform: string; arch: string }> {
+export async function getSystemInfo(): Promise<SystemInfo> {
   return invoke('get_system_info');
 }
 
@@ -231,6 +1089,110 @@ export async function getLogs(): Promise<string> {
   return invoke('get_logs');
 }
 
+/**
+ * Get the per-module log levels used in developer mode
+ *
+ * @returns Promise resolving to comma-separated rules, e.g. "devices=debug, download=info"
+ */
+export async function getModuleLogLevels(): Promise<string> {
+  return invoke<string>('get_module_log_levels');
+}
+
+/**
+ * Set the per-module log levels used in developer mode
+ *
+ * Goes through the backend, which validates the rules and applies them immediately.
+ *
+ * @param levels - Comma-separated module=level rules
+ */
+export async function setModuleLogLevels(levels: string): Promise<void> {
+  return invoke('set_module_log_levels', { levels });
+}
+
+/**
+ * Get the simulated network speed used in developer mode
+ *
+ * @returns Promise resolving to the limit in KB/s, 0 when off
+ */
+export async function getDownloadThrottleKbps(): Promise<number> {
+  return invoke<number>('get_download_throttle_kbps');
+}
+
+/**
+ * Set the simulated network speed used in developer mode, applied from the next download
+ *
+ * @param kbps - Limit in KB/s, 0 to turn it off
+ */
+export async function setDownloadThrottleKbps(kbps: number): Promise<void> {
+  return invoke('set_download_throttle_kbps', { kbps });
+}
+
+/**
+ * Get the simulated device write speed used in developer mode
+ *
+ * @returns Promise resolving to the limit in KB/s, 0 when off
+ */
+export async function getWriteThrottleKbps(): Promise<number> {
+  return invoke<number>('get_write_throttle_kbps');
+}
+
+/**
+ * Set the simulated device write speed used in developer mode, applied from the next flash
+ *
+ * @param kbps - Limit in KB/s, 0 to turn it off
+ */
+export async function setWriteThrottleKbps(kbps: number): Promise<void> {
+  return invoke('set_write_throttle_kbps', { kbps });
+}
+
+/**
+ * Get the paste service logs are uploaded to
+ */
+export async function getPasteUrl(): Promise<string> {
+  return invoke<string>('get_paste_url');
+}
+
+/**
+ * Set the paste service logs are uploaded to
+ *
+ * Non-default endpoints receive the uploaded logs; the UI warns before uploading.
+ *
+ * @param url - HTTPS URL of a Hastebin-compatible service, empty for the default
+ * @returns Promise resolving to the normalized URL
+ */
+export async function setPasteUrl(url: string): Promise<string> {
+  return invoke<string>('set_paste_url', { url });
+}
+
+/**
+ * Export the audit log of device writes to a file chosen in a save dialog
+ *
+ * @returns Number of exported entries, or null if the dialog was cancelled
+ */
+export async function exportAuditLog(): Promise<number | null> {
+  return invoke('export_audit_log');
+}
+
+/**
+ * Export the flash history, with checksums and device serials, for asset tracking
+ *
+ * @param format - 'json' for an array of entries, 'csv' for a spreadsheet
+ * @param path - Destination file; a save dialog asks for one when omitted
+ * @returns Number of exported entries, or null if the dialog was cancelled
+ */
+export async function exportHistory(format: 'json' | 'csv', path?: string): Promise<number | null> {
+  return invoke('export_history', { path: path ?? null, format });
+}
+
+/**
+ * Attach a note to an operation, kept in the history and its exports
+ * @param id - operation_id from the progress; null for the next operation this window starts
+ * @param text - Empty text removes the note
+ */
+export async function setOperationNote(id: string | null, text: string): Promise<void> {
+  return invoke('set_operation_note', { id, text });
+}
+
 // ============================================================================
 // Cache Management
 // ============================================================================
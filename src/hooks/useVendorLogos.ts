@@ -129,12 +129,12 @@ export function useManufacturerList(
       vendorMap[vendorId].count++;
 
       // Increment platinum count if this board has platinum support
-      if (board.has_platinum_support) {
+      if (board.support_level?.level === 'platinum') {
         vendorMap[vendorId].platinumCount++;
       }
 
       // Increment standard count if this board has standard support
-      if (board.has_standard_support) {
+      if (board.support_level?.level === 'standard') {
         vendorMap[vendorId].standardCount++;
       }
     }
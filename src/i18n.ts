@@ -2,7 +2,9 @@ import i18n from 'i18next';
 import { initReactI18next } from 'react-i18next';
 import { invoke } from '@tauri-apps/api/core';
 import { load } from '@tauri-apps/plugin-store';
-import { SUPPORTED_LANGUAGES, getLanguageFromLocale } from './config/i18n';
+import { SUPPORTED_LANGUAGES } from './config/i18n';
+import type { LocaleInfo } from './hooks/useTauri';
+import { saveSettings } from './hooks/useSettings';
 
 /**
  * Dynamically load all translation files
@@ -31,6 +33,16 @@ const resources = Object.entries(localeModules).reduce((acc, [path, module]) =>
 // Export supported language codes for use in other components
 export const supportedLanguages = SUPPORTED_LANGUAGES.map((lang) => lang.code);
 
+/**
+ * Map the system locale to the closest shipped language (e.g. "de-AT" -> "de")
+ */
+async function detectSystemLanguage(): Promise<string> {
+  const info = await invoke<LocaleInfo>('get_locale_info', {
+    supported: supportedLanguages.filter((code) => code !== 'auto'),
+  });
+  return info.language;
+}
+
 /**
  * Initialize i18n with saved language or system locale detection
  */
@@ -47,8 +59,7 @@ export async function initI18n(): Promise<void> {
   } catch {
     // If no saved language, detect from system locale
     try {
-      const systemLocale = await invoke<string>('get_system_locale');
-      language = getLanguageFromLocale(systemLocale);
+      language = await detectSystemLanguage();
     } catch (localeError) {
       console.warn('Failed to get system locale, using default:', localeError);
       language = 'en';
@@ -81,15 +92,14 @@ export async function changeLanguage(lang: string): Promise<void> {
     // Remove saved language to enable auto-detection
     try {
       await store.delete('language');
+      await saveSettings();
     } catch (error) {
       console.error('Failed to delete language from storage:', error);
     }
 
     // Detect system locale and change to it
     try {
-      const systemLocale = await invoke<string>('get_system_locale');
-      const detectedLang = getLanguageFromLocale(systemLocale);
-      await i18n.changeLanguage(detectedLang);
+      await i18n.changeLanguage(await detectSystemLanguage());
     } catch (localeError) {
       console.warn('Failed to get system locale, using default:', localeError);
       await i18n.changeLanguage('en');
@@ -101,6 +111,7 @@ export async function changeLanguage(lang: string): Promise<void> {
     // Persist to storage using Store plugin
     try {
       await store.set('language', lang);
+      await saveSettings();
     } catch (error) {
       console.error('Failed to save language to storage:', error);
     }
@@ -1,3 +1,5 @@
+import type { EraseMode, MirrorRegion, VerifyMode } from '../config';
+
 export interface BoardInfo {
   slug: string;
   name: string;
@@ -5,12 +7,51 @@ export interface BoardInfo {
   vendor_name: string;
   vendor_logo: string | null;
   image_count: number;
-  has_standard_support: boolean;
-  has_community_support: boolean;
-  has_platinum_support: boolean;
-  has_eos_support: boolean;
-  has_tvb_support: boolean;
-  has_wip_support: boolean;
+  support_level: SupportLevel | null;
+  latest_image_date: string | null;
+  is_stale: boolean;
+}
+
+/**
+ * Board support level resolved by the backend
+ */
+export type SupportLevel =
+  | { level: 'platinum'; until: string }
+  | { level: 'standard' }
+  | { level: 'community' }
+  | { level: 'eos' }
+  | { level: 'tvb' }
+  | { level: 'wip' };
+
+export type SupportTier = SupportLevel['level'];
+
+/**
+ * Backend board filters for get_boards
+ */
+export interface BoardFilters {
+  vendor?: string;
+  supportLevels?: SupportTier[];
+  search?: string;
+}
+
+/**
+ * Filtered board list with per-facet counts
+ */
+export interface BoardList {
+  boards: BoardInfo[];
+  total_count: number;
+  vendor_counts: Record<string, number>;
+  support_level_counts: Partial<Record<SupportTier, number>>;
+  catalog_diagnostics: CatalogDiagnostics;
+}
+
+export interface CatalogDiagnostics {
+  schema_version: string | null;
+  records: number;
+  parsed: number;
+  skipped: number;
+  errors: number;
+  sample_errors: string[];
 }
 
 export interface ImageInfo {
@@ -24,9 +65,63 @@ export interface ImageInfo {
   file_url_sha: string | null;
   file_size: number;
   download_repository: string;
+  repository_kind: 'archive' | 'rolling' | 'unknown';
+  origin_host: string | null;
+  is_prerelease: boolean;
+  /** Catalog the image is listed in, CATALOG.OFFICIAL_ORIGIN for Armbian's */
+  origin: string;
   // Custom image fields
   is_custom?: boolean;
   custom_path?: string;
+  /** Expected SHA256, from a checksum file next to the image or pasted by the user */
+  custom_sha256?: string | null;
+  custom_sha_file?: string | null;
+}
+
+/**
+ * Update check result for a cached or previously flashed image
+ */
+export interface ImageUpdateInfo {
+  current_version: string;
+  latest_version: string | null;
+  update_available: boolean;
+  latest_image: ImageInfo | null;
+}
+
+/**
+ * Why a previously flashed image deserves attention
+ */
+export type AdvisoryReason =
+  | { kind: 'board_end_of_support' }
+  | { kind: 'release_end_of_life'; release: string; since: string }
+  | { kind: 'fix_release'; version: string };
+
+/**
+ * Previously flashed image that went EOL or got a fix release since
+ */
+export interface BoardAdvisory {
+  image: string;
+  board_slug: string;
+  board_name: string;
+  version: string;
+  distro_release: string | null;
+  /** RFC 3339 time of the most recent flash of the image */
+  flashed_at: string;
+  device_model: string | null;
+  reasons: AdvisoryReason[];
+  latest_image: ImageInfo | null;
+}
+
+/**
+ * Display metadata for a preinstalled application
+ */
+export interface ApplicationInfo {
+  id: string;
+  aliases: string[];
+  display_name: string;
+  description: string;
+  category: 'home_automation' | 'storage' | 'cloud' | 'security' | 'networking' | 'media';
+  icon_slug: string;
 }
 
 export interface BlockDevice {
@@ -38,6 +133,431 @@ export interface BlockDevice {
   is_removable: boolean;
   is_system: boolean;
   bus_type?: string;
+  serial?: string | null;
+  /** Name the user gave this device's serial */
+  alias?: string | null;
+  /** USB bridge the device is attached through (Linux and Windows) */
+  usb_bridge?: UsbBridge | null;
+}
+
+/**
+ * USB bridge chip of an enclosure or adapter
+ */
+export interface UsbBridge {
+  /** Four lowercase hex digits */
+  vendor_id: string;
+  product_id: string;
+  /** Whether the device is driven over UAS, null if unknown */
+  uas: boolean | null;
+  /** Chipset name if the bridge is known for UAS problems */
+  chipset: string | null;
+  /** Negotiated link speed in Mbit/s, null if unknown */
+  speed_mbps: number | null;
+  /** Whether the device can run at USB 3 speeds, null if unknown */
+  superspeed_capable: boolean | null;
+}
+
+/**
+ * A device held by a running job
+ */
+export interface DeviceLock {
+  device_path: string;
+  /** Label of the window that started the job */
+  owner: string;
+  /** Operation ID or kind, e.g. "flash-20250101-120000-1" */
+  job: string;
+  /** RFC 3339 time the lock was taken */
+  since: string;
+}
+
+export type JobStatus = 'running' | 'succeeded' | 'failed' | 'cancelled';
+
+/**
+ * A download, flash or verification started by a window
+ */
+export interface Job {
+  /** Operation ID, e.g. "flash-20250101-120000-1" */
+  id: string;
+  kind: 'download' | 'flash' | 'verify';
+  /** Label of the window that started the job */
+  owner: string;
+  /** Image URL or device path the job works on */
+  target: string;
+  status: JobStatus;
+  /** RFC 3339 start and end times */
+  started: string;
+  finished: string | null;
+  error: string | null;
+}
+
+/**
+ * Filesystem of the partition an erase leaves behind
+ */
+export type EraseFilesystem = 'fat32' | 'exfat';
+
+/**
+ * Result of a fake capacity check
+ */
+export interface CapacityReport {
+  reported_bytes: number;
+  /** Bytes below the first block past the real capacity */
+  usable_bytes: number;
+  samples: number;
+  bad_samples: number;
+  /** Whether every marker read back intact */
+  genuine: boolean;
+}
+
+/**
+ * Flash success/failure statistics for a device serial
+ */
+export interface DeviceReliability {
+  serial: string;
+  successes: number;
+  failures: number;
+  consecutive_failures: number;
+  last_failure_at: string | null;
+  likely_faulty: boolean;
+}
+
+/**
+ * A known download mirror and its measured latency
+ */
+export interface Mirror {
+  region: string;
+  base_url: string;
+  host: string;
+  /** Time to answer a HEAD request, null if it did not answer */
+  latency_ms: number | null;
+}
+
+/**
+ * Download statistics of a mirror host
+ */
+export interface MirrorStats {
+  host: string;
+  successes: number;
+  failures: number;
+  consecutive_failures: number;
+  avg_latency_ms: number | null;
+  /** Average speed of successful downloads in bytes per second */
+  avg_speed: number | null;
+  last_success_at: string | null;
+  last_failure_at: string | null;
+  last_error: string | null;
+  /** Failed repeatedly or more often than not */
+  flaky: boolean;
+}
+
+/**
+ * How a gated download host authenticates
+ */
+export type AuthScheme = 'bearer' | 'basic';
+
+/**
+ * Outcome of a settings repair: file intact, restored from the last good
+ * copy, or reset to defaults for lack of one
+ */
+export type SettingsRepair = 'healthy' | 'restored' | 'reset';
+
+/**
+ * All settings, checked and migrated by the backend
+ */
+export interface Settings {
+  /** Layout version the backend migrates older settings from */
+  settings_version: number;
+  theme: 'auto' | 'light' | 'dark';
+  /** UI language code, or 'auto' for the system language */
+  language: string;
+  show_motd: boolean;
+  show_updater_modal: boolean;
+  developer_mode: boolean;
+  cache_enabled: boolean;
+  /** Bytes the image cache may take */
+  cache_max_size: number;
+  write_failure_report: boolean;
+  erase_mode: EraseMode;
+  verify_mode: VerifyMode;
+  smart_write: boolean;
+  skip_zero_blocks: boolean;
+  mirror_region: MirrorRegion;
+  log_retention_count: number;
+  /** 0 keeps logs regardless of age */
+  log_retention_days: number;
+  module_log_levels: string;
+  download_throttle_kbps: number;
+  write_throttle_kbps: number;
+  metered_allowed_networks: string[];
+  /** Device names keyed by serial number */
+  device_aliases: Record<string, string>;
+  completion_cues: CompletionCues;
+  catalog_sources: CatalogSource[];
+  allow_insecure_urls: boolean;
+  download_segments: number;
+  download_retries: number;
+  pause_on_low_space: boolean;
+  direct_write: boolean;
+  proxy: ProxySettings;
+  paste_url: string;
+}
+
+/**
+ * Where the proxy for HTTP traffic comes from: the proxy environment
+ * variables, the manual settings, or none
+ */
+export type ProxyMode = 'system' | 'manual' | 'off';
+
+/**
+ * Proxy for all HTTP traffic; the password stays in the OS keychain
+ */
+export interface ProxySettings {
+  mode: ProxyMode;
+  /** HTTP or HTTPS proxy URL for the manual mode */
+  url: string;
+  /** User name for basic auth, empty for none */
+  username: string;
+  /** Comma-separated hosts, domains or networks reached directly */
+  no_proxy: string;
+}
+
+/**
+ * Additional image catalog in the Armbian all-images JSON format
+ */
+export interface CatalogSource {
+  /** Shown next to images of the catalog */
+  name: string;
+  /** HTTPS URL, or a file:// URL for a local JSON file */
+  url: string;
+}
+
+/**
+ * A download host with stored credentials; the secret stays in the OS keychain
+ */
+export interface AuthSource {
+  /** Host name, or "*.example.com" for every subdomain */
+  host: string;
+  scheme: AuthScheme;
+  /** User name for basic auth */
+  username: string | null;
+}
+
+/**
+ * Result of a finished device backup
+ */
+export interface BackupSummary {
+  /** File the backup was written to */
+  path: string;
+  /** Bytes read from the device */
+  image_bytes: number;
+  /** Size of the compressed file */
+  compressed_bytes: number;
+}
+
+/**
+ * Partition found in an image's partition table
+ */
+export interface ImagePartition {
+  /** 1-based number, as in /dev/sdX1 */
+  number: number;
+  /** Byte offsets in the image, end exclusive */
+  start: number;
+  end: number;
+  /** Filesystem type (MBR) or partition name (GPT) */
+  kind: string;
+}
+
+/**
+ * Image partition and the filesystem found on it
+ */
+export interface PartitionDetails extends ImagePartition {
+  /** e.g. "ext4", "FAT32", null if not recognized */
+  filesystem: string | null;
+  label: string | null;
+}
+
+/**
+ * What an image contains, from its partition table, superblocks and file name
+ */
+export interface ImageDetails {
+  /** "GPT" or "MBR", null without a partition table */
+  partition_table: string | null;
+  partitions: PartitionDetails[];
+  compressed: boolean;
+  file_size: number;
+  /** Size written to the device, null if unknown before decompressing */
+  image_size: number | null;
+  armbian_version: string | null;
+  board: string | null;
+  distro_release: string | null;
+  kernel_branch: string | null;
+}
+
+/**
+ * Result of a successful device verification
+ */
+export interface DeviceVerification {
+  /** Taken from a stored verification instead of reading the device */
+  cached: boolean;
+  verified_at: string;
+}
+
+/**
+ * First-boot settings written to a device after flashing
+ */
+export interface Customization {
+  hostname: string | null;
+  /** Default user created on first login */
+  user_name: string | null;
+  /** Password of the default user, also used for root */
+  password: string | null;
+  ssh_enabled: boolean;
+}
+
+/**
+ * Wi-Fi network a headless board joins on first boot
+ */
+export interface NetworkConfig {
+  wifi_ssid: string;
+  /** WPA passphrase or 64-digit hex key, null for an open network */
+  wifi_psk: string | null;
+  /** Two-letter country code for the regulatory domain */
+  country_code: string | null;
+}
+
+/**
+ * Raw image written at an offset after the main image, e.g. a data partition
+ */
+export interface SecondaryImage {
+  path: string;
+  /** File name shown in the UI */
+  name: string;
+  /** Byte offset on the device, at or after the end of the main image */
+  offset: number;
+}
+
+/**
+ * What applying a customization changed on the device
+ */
+export interface CustomizationReport {
+  /** Files written, relative to the partition root */
+  written: string[];
+  /** Files removed, relative to the partition root */
+  removed: string[];
+  /** Settings that need the root filesystem, which was not reachable */
+  skipped: string[];
+}
+
+/**
+ * Event published on the backend event bus
+ */
+export type AppEvent =
+  | { kind: 'progress'; operation: string; processed_bytes: number; total_bytes: number }
+  | { kind: 'download_started'; url: string }
+  | { kind: 'download_finished'; url: string; path: string | null; error: string | null }
+  | { kind: 'flash_started'; device_path: string; image: string }
+  | { kind: 'flash_finished'; device_path: string; success: boolean; cancelled: boolean; error: string | null }
+  | { kind: 'verify_finished'; device_path: string; success: boolean; cached: boolean }
+  | { kind: 'devices_changed'; added: string[]; removed: string[] }
+  | { kind: 'cache_changed'; removed_files: number }
+  | { kind: 'queue_changed' }
+  | { kind: 'fleet_changed' }
+  | { kind: 'operation_noted'; operation_id: string; note: string | null }
+  | { kind: 'low_disk_space'; path: string; available_bytes: number; paused: boolean };
+
+/**
+ * Which inserted devices fleet mode writes; only removable devices are considered
+ */
+export interface FleetCriteria {
+  min_size_bytes?: number;
+  /** Larger devices are never written; 64 GB when left out */
+  max_size_bytes?: number;
+  /** Bus types to accept, e.g. "usb" or "sd"; empty for any */
+  bus_types?: string[];
+}
+
+/**
+ * What fleet mode writes to each inserted device
+ */
+export interface FleetSettings {
+  /** Local image file, kept until fleet mode stops */
+  image_path: string;
+  verify?: boolean;
+  customization?: Customization | null;
+  network?: NetworkConfig | null;
+  criteria?: FleetCriteria;
+  /** Eject each device once it is done */
+  eject?: boolean;
+}
+
+export type FleetJobStage =
+  | 'queued'
+  | 'flashing'
+  | 'verifying'
+  | 'customizing'
+  | 'ejecting'
+  | 'done'
+  | 'failed'
+  | 'cancelled';
+
+/**
+ * One device handled by fleet mode
+ */
+export interface FleetJob {
+  id: string;
+  device_path: string;
+  /** Alias or model of the device */
+  device_name: string;
+  serial: string | null;
+  stage: FleetJobStage;
+  progress_percent: number;
+  error: string | null;
+  started_at: string;
+  finished_at: string | null;
+}
+
+/**
+ * State of fleet mode
+ */
+export interface FleetStatus {
+  active: boolean;
+  image_path: string | null;
+  started_at: string | null;
+  /** Newest first */
+  jobs: FleetJob[];
+  completed: number;
+  failed: number;
+}
+
+/**
+ * Which cues announce a finished flash or verification
+ */
+export interface CompletionCues {
+  sound: boolean;
+  /** Flash the taskbar button or bounce the dock icon */
+  attention: boolean;
+  /** Badge on the app icon until the app is focused */
+  badge: boolean;
+}
+
+export type CueOutcome = 'success' | 'failure';
+
+/**
+ * What happens when the user starts writing
+ */
+export type WritePrompt = 'none' | 'polkit' | 'authorization' | 'unavailable';
+
+/**
+ * Process privileges and the helper used for device access
+ */
+export interface PrivilegeStatus {
+  /** Running as root or elevated Administrator */
+  elevated: boolean;
+  /** Helper that opens devices ("udisks2", "authopen") */
+  helper: string | null;
+  helper_available: boolean;
+  /** A polkit authentication agent is running (Linux only) */
+  auth_agent_running: boolean | null;
+  write_prompt: WritePrompt;
 }
 
 export interface DownloadProgress {
@@ -47,15 +567,57 @@ export interface DownloadProgress {
   is_decompressing: boolean;
   progress_percent: number;
   error: string | null;
+  mirror_host: string | null;
+  /** ID of this window's newest download, for getOperationTimeseries */
+  operation_id: string | null;
+  /** Progress of each range of a segmented download, empty otherwise */
+  segments: SegmentProgress[];
+  /** Free bytes left on the destination volume while they run low */
+  low_space_bytes: number | null;
+  /** Waiting until space is freed on the destination volume */
+  paused_for_space: boolean;
+  /** Times the download was retried after a network error */
+  retries: number;
+}
+
+/** One byte range of a segmented download */
+export interface SegmentProgress {
+  start: number;
+  /** Last byte, inclusive */
+  end: number;
+  downloaded_bytes: number;
 }
 
 export interface FlashProgress {
   total_bytes: number;
   written_bytes: number;
+  /** Part of written_bytes the device confirmed at the last sync */
+  synced_bytes: number;
   verified_bytes: number;
   is_verifying: boolean;
   progress_percent: number;
   error: string | null;
+  paused_for_power: boolean;
+  write_speed_mbps: number | null;
+  read_speed_mbps: number | null;
+  /** Speed of the running phase over the last few seconds */
+  current_speed_mbps: number | null;
+  /** Estimated seconds until the running phase completes */
+  eta_secs: number | null;
+  slow_read: boolean;
+  /** No progress for a while; offer to keep waiting or cancel */
+  stalled: boolean;
+  stalled_secs: number;
+  /** Bytes smart write found already on the device and did not write */
+  skipped_bytes: number;
+  /** All-zero bytes skipped instead of written */
+  zero_skipped_bytes: number;
+  /** SHA-256 of the image read back from the device, after hash verification */
+  device_sha256: string | null;
+  /** Hash verification compared with the image file, lacking a written checksum */
+  hash_fallback: boolean;
+  /** ID of this window's newest flash or verify, for getOperationTimeseries */
+  operation_id: string | null;
 }
 
 /**
@@ -86,8 +648,68 @@ export type ModalType = 'none' | SelectionStep;
 /**
  * Custom image info from file picker
  */
+/**
+ * Expected stage durations in seconds (null when there is no history yet)
+ */
+export interface OperationEstimate {
+  download_secs: number | null;
+  decompress_secs: number | null;
+  write_secs: number | null;
+  verify_secs: number | null;
+  cached: boolean;
+  image_bytes: number;
+}
+
+/** Result of checking a custom image against its expected SHA256 */
+export interface ShaVerification {
+  source: 'file' | 'manual';
+  expected: string;
+  actual: string;
+  matched: boolean;
+}
+
 export interface CustomImageInfo {
   path: string;
   name: string;
   size: number;
+  /** Checksum file found next to the image, e.g. foo.img.xz.sha */
+  sha_file: string | null;
+  /** Hash from sha_file, if it could be parsed */
+  expected_sha256: string | null;
+  /** Set by verifyCustomImage */
+  sha_verification: ShaVerification | null;
+}
+
+/** Packaging sandbox the app runs in */
+export interface SandboxStatus {
+  kind: 'none' | 'flatpak' | 'snap';
+  snap_confinement: 'strict' | 'classic' | 'devmode' | null;
+  /** Devices are only reachable through UDisks2 */
+  udisks2_only: boolean;
+  /** Interfaces plugged by the snap, empty outside a snap */
+  interfaces: { name: string; connected: boolean }[];
+  /** Interfaces that must be connected before flashing works */
+  missing_interfaces: string[];
+}
+
+export type HostArch = 'x64' | 'arm64' | 'x86' | 'arm' | 'other';
+
+/** Host compatibility self-check */
+export interface HostCompatibility {
+  /** Architecture the app was built for */
+  build_arch: HostArch;
+  /** Architecture of the machine, differs when emulated */
+  native_arch: HostArch;
+  /** Running under Rosetta or Windows x64 emulation */
+  emulated: boolean;
+  /** Threads used for decompression */
+  worker_threads: number;
+  checks: { name: string; ok: boolean; detail: string }[];
+}
+
+export interface SystemInfo {
+  platform: string;
+  arch: string;
+  sandbox: SandboxStatus;
+  compatibility: HostCompatibility;
 }
@@ -12,6 +12,21 @@ export function isDeviceConnected(devicePath: string, devices: BlockDevice[]): b
   return devices.some(d => d.path === devicePath);
 }
 
+/**
+ * Name to show for a device: the user's alias, else its model
+ */
+export function getDeviceDisplayName(device: BlockDevice): string {
+  return device.alias || device.model || device.name;
+}
+
+/**
+ * Whether a USB 3 device is held to USB 2.0 speed by its port, hub or cable
+ */
+export function hasSlowUsbLink(device: BlockDevice): boolean {
+  const bridge = device.usb_bridge;
+  return !!bridge?.superspeed_capable && bridge.speed_mbps !== null && bridge.speed_mbps <= 480;
+}
+
 /**
  * Detect device type from BlockDevice properties
  */
@@ -3,6 +3,7 @@
  */
 
 import { COLORS } from '../config';
+import type { SupportLevel, SupportTier } from '../types';
 
 /** Default color for icons without specific branding */
 export const DEFAULT_COLOR = COLORS.DEFAULT_ICON;
@@ -33,6 +34,21 @@ export function formatFileSize(
   return `${mb.toFixed(0)} MB`;
 }
 
+/**
+ * Format a duration as a clock (e.g., "4:05", "1:02:09")
+ * @param totalSeconds - Duration in seconds
+ * @returns Formatted duration string
+ */
+export function formatDuration(totalSeconds: number): string {
+  const secs = Math.max(0, Math.round(totalSeconds));
+  const hours = Math.floor(secs / 3600);
+  const minutes = Math.floor((secs % 3600) / 60);
+  const seconds = String(secs % 60).padStart(2, '0');
+  return hours > 0
+    ? `${hours}:${String(minutes).padStart(2, '0')}:${seconds}`
+    : `${minutes}:${seconds}`;
+}
+
 /**
  * Preload an image and return whether it loaded successfully
  * @param url - Image URL to preload
@@ -59,29 +75,21 @@ export function getErrorMessage(error: unknown, fallback: string = 'An error occ
   return fallback;
 }
 
+/** Support tier precedence used for sorting boards */
+const SUPPORT_TIER_ORDER: SupportTier[] = ['platinum', 'standard', 'community', 'eos', 'tvb', 'wip'];
+
+function supportRank(level: SupportLevel | null): number {
+  return level ? SUPPORT_TIER_ORDER.indexOf(level.level) : SUPPORT_TIER_ORDER.length;
+}
+
 /**
  * Sort comparator for boards: Platinum > Standard > Community > EOS > TVB > WIP > Others (alphabetically)
  */
 export function compareBoardsBySupport<T extends {
-  has_platinum_support: boolean;
-  has_standard_support: boolean;
-  has_community_support: boolean;
-  has_eos_support: boolean;
-  has_tvb_support: boolean;
-  has_wip_support: boolean;
+  support_level: SupportLevel | null;
   name: string;
 }>(a: T, b: T): number {
-  if (a.has_platinum_support && !b.has_platinum_support) return -1;
-  if (!a.has_platinum_support && b.has_platinum_support) return 1;
-  if (a.has_standard_support && !b.has_standard_support) return -1;
-  if (!a.has_standard_support && b.has_standard_support) return 1;
-  if (a.has_community_support && !b.has_community_support) return -1;
-  if (!a.has_community_support && b.has_community_support) return 1;
-  if (a.has_eos_support && !b.has_eos_support) return -1;
-  if (!a.has_eos_support && b.has_eos_support) return 1;
-  if (a.has_tvb_support && !b.has_tvb_support) return -1;
-  if (!a.has_tvb_support && b.has_tvb_support) return 1;
-  if (a.has_wip_support && !b.has_wip_support) return -1;
-  if (!a.has_wip_support && b.has_wip_support) return 1;
+  const rankDiff = supportRank(a.support_level) - supportRank(b.support_level);
+  if (rankDiff !== 0) return rankDiff;
   return a.name.localeCompare(b.name);
 }